@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use tokio::sync::Mutex;
+use webb_relayer_store::QUEUED_TXS_TIMEOUT;
 
 use super::{event_watcher::EventWatcher, *};
 
@@ -39,9 +40,15 @@ where
 
     /// Returns a task that should be running in the background
     /// that will watch for all commands
+    ///
+    /// The commands are taken one at a time from the `commands` channel of
+    /// the bridge, once the transactions queued for the previous one are
+    /// sent, so the event handlers sending them are held back while the
+    /// signer is behind.
     #[tracing::instrument(
         skip_all,
         fields(
+            chain_id = tracing::field::Empty,
             address = %contract.address(),
             tag = %Self::TAG,
         ),
@@ -52,6 +59,7 @@ where
         store: Arc<Self::Store>,
         contract: Self::Contract,
         metrics: Arc<Mutex<metric::Metrics>>,
+        commands: BridgeCommandChannels,
     ) -> webb_relayer_utils::Result<()> {
         let backoff = backoff::backoff::Constant::new(Duration::from_secs(1));
         let task = || async {
@@ -60,28 +68,33 @@ where
                 .map_err(Into::into)
                 .map_err(backoff::Error::transient)
                 .await?;
+            tracing::Span::current().record("chain_id", chain_id.as_u32());
             let typed_chain_id =
                 webb_proposals::TypedChainId::Evm(chain_id.as_u32());
             let bridge_key = BridgeKey::new(typed_chain_id);
             let key = SledQueueKey::from_bridge_key(bridge_key);
+            let tx_key = SledQueueKey::from_evm_chain_id(chain_id.as_u32());
+            let commands = commands.subscribe(bridge_key);
             loop {
-                let result = match store.dequeue_item(key)? {
-                    Some(cmd) => {
-                        self.handle_cmd(store.clone(), &contract, cmd).await
-                    }
-                    None => {
-                        // yeild back to the runtime, to allow for other tasks
-                        // to make progress, instead of this busy loop.
-                        tokio::task::yield_now().await;
-                        // the small sleep here just in case the runtime decides to
-                        // run this task again immediately.
-                        tokio::time::sleep(Duration::from_millis(10)).await;
-                        continue;
-                    }
+                let next =
+                    commands.recv(store.as_ref(), Duration::from_secs(1));
+                let Some(cmd) = next.await? else {
+                    continue;
                 };
+                let result =
+                    self.handle_cmd(store.clone(), &contract, cmd).await;
                 match result {
                     Ok(_) => {
                         tracing::debug!(?key, %chain_id, "Handled command successfully");
+                        // hand the next command over once the signer sent
+                        // the transactions queued so far.
+                        commands
+                            .wait_for_queued_txs::<transaction::eip2718::TypedTransaction, _>(
+                                store.as_ref(),
+                                tx_key,
+                                QUEUED_TXS_TIMEOUT,
+                            )
+                            .await?;
                         continue;
                     }
                     Err(e) => {
@@ -12,8 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt;
 use tokio::sync::Mutex;
+use webb::evm::ethers::abi;
 use webb::evm::ethers::prelude::TimeLag;
+use webb_relayer_utils::metric::ChainMetricsUpdate;
 use webb_relayer_utils::{multi_provider::MultiProvider, retry};
 
 use super::*;
@@ -44,6 +47,225 @@ pub trait WatchableContract: Send + Sync {
 
     /// The frequency of printing the sync progress.
     fn print_progress_interval(&self) -> Duration;
+
+    /// How long the watcher may go without making progress before its
+    /// [`Watchdog`] restarts it, zero means never.
+    fn watchdog_timeout(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// How many blocks behind the chain head the logs are queried, see
+    /// [`get_logs_in_range`].
+    fn safety_lag(&self) -> u64 {
+        0
+    }
+
+    /// The types of the events the watcher hands to its handlers, see
+    /// [`to_event_type`], `None` means all of them.
+    fn event_type_allowlist(&self) -> Option<&[String]> {
+        None
+    }
+
+    /// The transaction that deployed this contract, checked against
+    /// [`Self::deployed_at`] when the watcher starts, see
+    /// [`verify_deployment`].
+    fn deployment_tx_hash(&self) -> Option<types::H256> {
+        None
+    }
+}
+
+/// The type of the events missing from the contract ABI, see
+/// [`to_event_type`].
+pub const UNKNOWN_EVENT_TYPE: &str = "Unknown";
+
+/// Returns the type of the event of a log with the given topics, which is
+/// the name of the event in the contract ABI, such as `NewCommitment`.
+///
+/// The event is the one of the ABI with the signature of the first topic,
+/// which is also how the events get decoded into the variants of the enums
+/// generated from the ABI. The anonymous events have no signature, they
+/// are of the [`UNKNOWN_EVENT_TYPE`].
+pub fn to_event_type<'a>(abi: &'a abi::Abi, topics: &[types::H256]) -> &'a str {
+    let Some(signature) = topics.first() else {
+        return UNKNOWN_EVENT_TYPE;
+    };
+    abi.events()
+        .find(|event| !event.anonymous && event.signature() == *signature)
+        .map_or(UNKNOWN_EVENT_TYPE, |event| event.name.as_str())
+}
+
+/// Returns true if the type of the event is in the allowlist, or if there
+/// is no allowlist.
+///
+/// The events left out are logged and counted, so that no event type gets
+/// ignored without anyone noticing.
+pub async fn is_event_type_allowed(
+    allowlist: Option<&[String]>,
+    event_type: &str,
+    metrics: &Mutex<metric::Metrics>,
+) -> bool {
+    let Some(allowlist) = allowlist else {
+        return true;
+    };
+    if allowlist.iter().any(|allowed| allowed == event_type) {
+        return true;
+    }
+    tracing::warn!(%event_type, "Skipping an event not in the allowlist");
+    metrics.lock().await.unhandled_event_types.inc();
+    false
+}
+
+/// Returns true if the error is a provider not having the requested block,
+/// which is what a replica lagging behind the others answers.
+pub fn is_block_not_found<E: fmt::Display>(error: &E) -> bool {
+    let message = error.to_string().to_lowercase();
+    [
+        "block not found",
+        "header not found",
+        "unknown block",
+        "block does not exist",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Checks that the contract was deployed by the given transaction, mined at
+/// `deployed_at`, so that a redeployed contract whose `deployed_at` was not
+/// updated is not watched from the wrong block.
+///
+/// Nothing is checked without a transaction. Only a
+/// [`DeploymentMismatch`](webb_relayer_utils::Error::DeploymentMismatch)
+/// means the config is wrong, the other errors are worth a retry.
+pub async fn verify_deployment<M>(
+    client: &M,
+    address: types::Address,
+    tx_hash: Option<types::H256>,
+    deployed_at: u64,
+) -> webb_relayer_utils::Result<()>
+where
+    M: Middleware,
+    M::Error: Into<webb_relayer_utils::Error>,
+{
+    let Some(tx_hash) = tx_hash else {
+        return Ok(());
+    };
+    let mismatch =
+        |reason: String| webb_relayer_utils::Error::DeploymentMismatch {
+            address,
+            tx_hash,
+            deployed_at,
+            reason,
+        };
+    let tx = client
+        .get_transaction(tx_hash)
+        .await
+        .map_err(Into::into)?
+        .ok_or_else(|| mismatch("the transaction was not found".into()))?;
+    // a deployment transaction has no recipient.
+    if let Some(to) = tx.to {
+        return Err(mismatch(format!("the transaction was sent to {to:?}")));
+    }
+    let block = tx
+        .block_number
+        .ok_or_else(|| mismatch("the transaction is not mined yet".into()))?;
+    if block.as_u64() != deployed_at {
+        return Err(mismatch(format!(
+            "the transaction was mined at block {block}"
+        )));
+    }
+    // the transaction may have deployed another contract.
+    let receipt = client
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(Into::into)?
+        .ok_or(webb_relayer_utils::Error::Generic(
+            "the receipt of the deployment transaction was not found",
+        ))?;
+    match receipt.contract_address {
+        Some(deployed) if deployed == address => Ok(()),
+        Some(deployed) => {
+            Err(mismatch(format!("the transaction deployed {deployed:?}")))
+        }
+        None => Err(mismatch("the transaction deployed no contract".into())),
+    }
+}
+
+/// Restricts the filter to the logs with one of the given event
+/// signatures, as their first topic.
+///
+/// An empty list leaves the filter matching every event.
+pub fn with_event_topics(
+    filter: types::Filter,
+    topics: Vec<types::H256>,
+) -> types::Filter {
+    if topics.is_empty() {
+        return filter;
+    }
+    filter.topic0(types::ValueOrArray::Array(topics))
+}
+
+/// Queries the logs matching the filter in the given range of blocks.
+///
+/// Behind a load balancer, the replica answering the query may lag behind
+/// the one the chain head came from, and not have the last blocks of the
+/// range yet. It either fails with a "block not found" error, or answers
+/// with the logs it has. So at the chain head, the head of the provider is
+/// checked once the logs are in, and the query is retried with `backoff` in
+/// both cases.
+pub async fn get_logs_in_range<M>(
+    client: &M,
+    filter: &types::Filter,
+    range: BlockRange,
+    backoff: impl backoff::backoff::Backoff,
+) -> webb_relayer_utils::Result<Vec<types::Log>>
+where
+    M: Middleware,
+    M::Error: Into<webb_relayer_utils::Error>,
+{
+    if range.from > range.to {
+        return Ok(Vec::new());
+    }
+    let filter = filter.clone().from_block(range.from).to_block(range.to);
+    let query = || async {
+        let logs = client.get_logs(&filter).await.map_err(|e| {
+            if is_block_not_found(&e) {
+                tracing::debug!("Provider lags behind, retrying: {e}");
+                backoff::Error::transient(e.into())
+            } else {
+                backoff::Error::permanent(e.into())
+            }
+        })?;
+        let out_of_range = logs
+            .iter()
+            .filter_map(|log| log.block_number)
+            .any(|block| !(range.from..=range.to).contains(&block.as_u64()));
+        if out_of_range {
+            return Err(backoff::Error::permanent(
+                webb_relayer_utils::Error::Generic(
+                    "The provider answered with logs out of the block range",
+                ),
+            ));
+        }
+        if range.is_synced() {
+            let head = client
+                .get_block_number()
+                .await
+                .map_err(Into::into)
+                .map_err(backoff::Error::permanent)?
+                .as_u64();
+            if head < range.to {
+                tracing::debug!(head, to = range.to, "Provider lags behind");
+                return Err(backoff::Error::transient(
+                    webb_relayer_utils::Error::ProviderLagging {
+                        head,
+                        to: range.to,
+                    },
+                ));
+            }
+        }
+        Ok(logs)
+    };
+    backoff::future::retry(backoff, query).await
 }
 
 /// A helper type to extract the [`EventHandler`] from the [`EventWatcher`] trait.
@@ -56,6 +278,39 @@ pub type EventHandlerFor<W> = Box<
         + Sync,
 >;
 
+/// Fetches the log emitted at `log_index` in the block of the given
+/// transaction.
+///
+/// Like in block explorers, the log index is the position of the log in
+/// the whole block, not in the transaction.
+pub async fn fetch_log<M>(
+    client: &M,
+    tx_hash: types::H256,
+    log_index: types::U256,
+) -> webb_relayer_utils::Result<types::Log>
+where
+    M: Middleware,
+    M::Error: Into<webb_relayer_utils::Error>,
+{
+    let receipt = client
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(Into::into)?
+        .ok_or(webb_relayer_utils::Error::Generic(
+            "transaction receipt not found",
+        ))?;
+    receipt
+        .logs
+        .into_iter()
+        .find(|log| log.log_index == Some(log_index))
+        .ok_or(webb_relayer_utils::Error::Generic(
+            "log index not found in the transaction",
+        ))
+}
+
+/// How many times a logs query is retried while the provider lags behind.
+const MAX_LOGS_RETRY_COUNT: usize = 10;
+
 /// A trait for watching events from a watchable contract.
 /// EventWatcher trait exists for deployments that are smart-contract / EVM based
 #[async_trait::async_trait]
@@ -69,11 +324,106 @@ pub trait EventWatcher {
     type Events: contract::EthLogDecode + Clone;
     /// The Storage backend that will be used to store the required state for this event watcher
     type Store: HistoryStore + EventHashStore;
+
+    /// The signatures of the events the handlers of this watcher handle,
+    /// which are the only logs queried from the node.
+    ///
+    /// Empty to query every event of the contract.
+    fn event_topics(&self) -> Vec<types::H256> {
+        Vec::new()
+    }
+
+    /// A hook called before the events of the given block range are queried.
+    ///
+    /// Returning an error restarts the watcher, after a backoff.
+    async fn pre_step(
+        &self,
+        _store: &Arc<Self::Store>,
+        _contract: &Self::Contract,
+        _range: BlockRange,
+    ) -> webb_relayer_utils::Result<()> {
+        Ok(())
+    }
+
+    /// A hook called once all the events of the given block range are handled,
+    /// and the range is checkpointed in the store.
+    ///
+    /// Returning an error restarts the watcher, after a backoff.
+    async fn post_step(
+        &self,
+        _store: &Arc<Self::Store>,
+        _contract: &Self::Contract,
+        _range: BlockRange,
+        _number_of_events: usize,
+    ) -> webb_relayer_utils::Result<()> {
+        Ok(())
+    }
+
+    /// Decodes the given log and hands it to every handler once, without
+    /// retrying failed handlers and without moving the block checkpoint.
+    ///
+    /// This is meant for debugging a single event, see [`fetch_log`].
+    ///
+    /// Returns the outcome of every handler, in order, which is `Ok(false)`
+    /// if the handler is not interested in the event.
+    #[tracing::instrument(
+        skip_all,
+        fields(
+            address = %contract.address(),
+            tag = %Self::TAG,
+        ),
+    )]
+    async fn replay_log(
+        &self,
+        store: Arc<Self::Store>,
+        contract: &Self::Contract,
+        handlers: &[EventHandlerFor<Self>],
+        log: types::Log,
+        metrics: Arc<Mutex<metric::Metrics>>,
+    ) -> webb_relayer_utils::Result<Vec<webb_relayer_utils::Result<bool>>> {
+        if log.address != contract.address() {
+            return Err(webb_relayer_utils::Error::Generic(
+                "the log was not emitted by the watched contract",
+            ));
+        }
+        let raw_log = webb::evm::ethers::abi::RawLog {
+            topics: log.topics.clone(),
+            data: log.data.to_vec(),
+        };
+        let event =
+            <Self::Events as contract::EthLogDecode>::decode_log(&raw_log)?;
+        let meta = contract::LogMeta::from(&log);
+        let mut outcomes = Vec::with_capacity(handlers.len());
+        for handler in handlers {
+            let outcome = async {
+                let interested = handler
+                    .can_handle_events((event.clone(), meta.clone()), contract)
+                    .await?;
+                if !interested {
+                    return Ok(false);
+                }
+                handler
+                    .handle_event(
+                        store.clone(),
+                        contract,
+                        (event.clone(), meta.clone()),
+                        metrics.clone(),
+                    )
+                    .await?;
+                Ok(true)
+            }
+            .await;
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+
     /// Returns a task that should be running in the background
     /// that will watch events
     #[tracing::instrument(
         skip_all,
         fields(
+            chain_id = tracing::field::Empty,
             address = %contract.address(),
             tag = %Self::TAG,
         ),
@@ -87,6 +437,7 @@ pub trait EventWatcher {
         ctx: &RelayerContext,
     ) -> webb_relayer_utils::Result<()> {
         let backoff = backoff::backoff::Constant::new(Duration::from_secs(1));
+        let watchdog = Watchdog::new(contract.watchdog_timeout());
         let task = || async {
             let step = contract.max_blocks_per_step().as_u64();
             let metrics = &ctx.metrics;
@@ -97,6 +448,7 @@ pub trait EventWatcher {
                 .map_err(backoff::Error::transient)
                 .await?
                 .as_u32();
+            tracing::Span::current().record("chain_id", chain_id);
             // now we start polling for new events.
             // create history store key
             let src_target_system = TargetSystem::new_contract_address(
@@ -117,6 +469,7 @@ pub trait EventWatcher {
                 .map_err(backoff::Error::transient)
                 .await?
                 .as_u64();
+            watchdog.feed();
 
             // Save the target block number in the store
             // so other things can use it.
@@ -130,22 +483,66 @@ pub trait EventWatcher {
                     history_store_key,
                     contract.deployed_at().as_u64(),
                 )?;
+                // the last blocks are left for the next steps, since the
+                // replica answering the logs query may not have them yet.
+                let safe_head =
+                    target_block_number.saturating_sub(contract.safety_lag());
                 let dest_block =
-                    core::cmp::min(block + step, target_block_number);
-
-                let events_filter = contract
-                    .event_with_filter::<Self::Events>(Default::default())
-                    .from_block(block + 1)
-                    .to_block(dest_block);
-                let found_events = events_filter
-                    .query_with_meta()
-                    .map_err(Into::into)
+                    core::cmp::min(block + step, safe_head).max(block);
+                let range = BlockRange {
+                    from: block + 1,
+                    to: dest_block,
+                    target: safe_head,
+                };
+                self.pre_step(&store, &contract, range)
                     .map_err(backoff::Error::transient)
                     .await?;
 
+                let filter = with_event_topics(
+                    contract
+                        .event_with_filter::<Self::Events>(Default::default())
+                        .filter,
+                    self.event_topics(),
+                );
+                let logs_backoff = retry::ConstantWithMaxRetryCount::new(
+                    Duration::from_millis(500),
+                    MAX_LOGS_RETRY_COUNT,
+                );
+                let logs =
+                    get_logs_in_range(&*client, &filter, range, logs_backoff)
+                        .map_err(backoff::Error::transient)
+                        .await?;
+                watchdog.feed();
+                let found_events = logs
+                    .into_iter()
+                    .map(|log| {
+                        let meta = contract::LogMeta::from(&log);
+                        let event_type =
+                            to_event_type(contract.abi(), &log.topics);
+                        let raw_log = webb::evm::ethers::abi::RawLog {
+                            topics: log.topics,
+                            data: log.data.to_vec(),
+                        };
+                        let event = <Self::Events as contract::EthLogDecode>::decode_log(
+                            &raw_log,
+                        )?;
+                        Ok::<_, webb_relayer_utils::Error>((event, meta, event_type))
+                    })
+                    .collect::<webb_relayer_utils::Result<Vec<_>>>()
+                    .map_err(backoff::Error::transient)?;
+
                 let number_of_events = found_events.len();
                 tracing::trace!("Found #{number_of_events} events");
-                for (event, log) in found_events {
+                for (event, log, event_type) in found_events {
+                    if !is_event_type_allowed(
+                        contract.event_type_allowlist(),
+                        event_type,
+                        metrics,
+                    )
+                    .await
+                    {
+                        continue;
+                    }
                     // wraps each handler future in a retry logic, that will retry the handler
                     // if it fails, up to `MAX_RETRY_COUNT`, after this it will ignore that event for
                     // that specific handler.
@@ -177,10 +574,16 @@ pub trait EventWatcher {
                             }
                         });
                     if mark_as_handled {
+                        metrics.lock().await.observe_event_handled(
+                            chain_id,
+                            contract.address(),
+                            event_type,
+                        );
                         store.set_last_block_number(
                             history_store_key,
                             log.block_number.as_u64(),
                         )?;
+                        watchdog.feed();
                         tracing::trace!(
                             %chain_id,
                             %log.block_number,
@@ -198,8 +601,23 @@ pub trait EventWatcher {
 
                 // move the block pointer to the destination block
                 store.set_last_block_number(history_store_key, dest_block)?;
+                store.set_last_synced_at(
+                    history_store_key,
+                    ctx.clock().unix_timestamp(),
+                )?;
+                ctx.update_chain_metrics(
+                    chain_id,
+                    ChainMetricsUpdate::EventsProcessed {
+                        count: number_of_events as u64,
+                        current_block_number: target_block_number,
+                    },
+                )
+                .await;
+                self.post_step(&store, &contract, range, number_of_events)
+                    .map_err(backoff::Error::transient)
+                    .await?;
                 // if we fully synced, we can update the target block number
-                let should_cooldown = dest_block == target_block_number;
+                let should_cooldown = range.is_synced();
                 if should_cooldown {
                     let duration = contract.polling_interval();
                     tracing::trace!(
@@ -214,6 +632,7 @@ pub trait EventWatcher {
                         .map_err(backoff::Error::transient)
                         .await?
                         .as_u64();
+                    watchdog.feed();
                     store.set_target_block_number(
                         history_store_key,
                         target_block_number,
@@ -229,9 +648,8 @@ pub trait EventWatcher {
                         contract.deployed_at().as_u64(),
                     )?;
                     let diff = currently_at.saturating_sub(block);
-                    let progress = currently_at as f64
-                        / target_block_number as f64
-                        * 100.0;
+                    let progress =
+                        crate::sync_progress(currently_at, target_block_number);
                     let is_syncing = progress < 99.99;
                     tracing::info!(
                         target_block = target_block_number,
@@ -253,7 +671,43 @@ pub trait EventWatcher {
                 }
             }
         };
-        backoff::future::retry(backoff, task).await?;
+        // the watchdog drops the task if it gets stuck, and restarts it.
+        let guarded_task = || async {
+            match watchdog.guard(task()).await {
+                Some(result) => result,
+                None => {
+                    let stalled_for = watchdog.elapsed();
+                    tracing::warn!(
+                        ?stalled_for,
+                        "Event watcher made no progress, restarting it",
+                    );
+                    ctx.metrics.lock().await.event_watcher_stalls.inc();
+                    Err(backoff::Error::transient(
+                        webb_relayer_utils::Error::WatcherStalled(stalled_for),
+                    ))
+                }
+            }
+        };
+        // only a mismatch stops the watcher, the provider errors are retried.
+        let verify = || async {
+            verify_deployment(
+                client.inner().as_ref(),
+                contract.address(),
+                contract.deployment_tx_hash(),
+                contract.deployed_at().as_u64(),
+            )
+            .await
+            .map_err(|e| match e {
+                e @ webb_relayer_utils::Error::DeploymentMismatch { .. } => {
+                    backoff::Error::permanent(e)
+                }
+                e => backoff::Error::transient(e),
+            })
+        };
+        let verify_backoff =
+            backoff::backoff::Constant::new(Duration::from_secs(1));
+        backoff::future::retry(verify_backoff, verify).await?;
+        backoff::future::retry(backoff, guarded_task).await?;
         Ok(())
     }
 }
@@ -341,3 +795,429 @@ pub trait EventHandlerWithRetry: EventHandler {
 }
 
 impl<T> EventHandlerWithRetry for T where T: EventHandler + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::providers::{JsonRpcError, MockResponse};
+
+    fn test_abi() -> abi::Abi {
+        abi::parse_abi(&[
+            "event ProposalEvent(uint32 nonce)",
+            "event Deposit(uint256 amount)",
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn event_type_should_be_the_abi_name() {
+        let abi = test_abi();
+        let deposit = abi.event("Deposit").unwrap().signature();
+        assert_eq!(to_event_type(&abi, &[deposit]), "Deposit");
+        let proposal = abi.event("ProposalEvent").unwrap().signature();
+        assert_eq!(to_event_type(&abi, &[proposal]), "ProposalEvent");
+        // an event of another contract, or an anonymous one.
+        let other = types::H256::repeat_byte(1);
+        assert_eq!(to_event_type(&abi, &[other]), UNKNOWN_EVENT_TYPE);
+        assert_eq!(to_event_type(&abi, &[]), UNKNOWN_EVENT_TYPE);
+    }
+
+    #[tokio::test]
+    async fn events_not_in_the_allowlist_should_be_counted() {
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+        let allowlist = vec![String::from("ProposalEvent")];
+
+        assert!(
+            !is_event_type_allowed(Some(&allowlist), "Deposit", &metrics).await
+        );
+        assert_eq!(metrics.lock().await.unhandled_event_types.get(), 1.0);
+
+        assert!(
+            is_event_type_allowed(Some(&allowlist), "ProposalEvent", &metrics)
+                .await
+        );
+        // without an allowlist, all the events pass through.
+        assert!(is_event_type_allowed(None, "Deposit", &metrics).await);
+        assert_eq!(metrics.lock().await.unhandled_event_types.get(), 1.0);
+    }
+
+    fn log_at(block: u64) -> types::Log {
+        types::Log {
+            block_number: Some(block.into()),
+            ..Default::default()
+        }
+    }
+
+    fn backoff() -> retry::ConstantWithMaxRetryCount {
+        retry::ConstantWithMaxRetryCount::new(Duration::from_millis(1), 3)
+    }
+
+    #[tokio::test]
+    async fn lagging_replicas_should_be_retried() {
+        let (provider, mock) = providers::Provider::mocked();
+        let filter = types::Filter::new();
+        let range = BlockRange {
+            from: 91,
+            to: 100,
+            target: 100,
+        };
+        // the responses are served last in, first out: a replica without
+        // the block, then one answering without the last logs, then one
+        // that caught up.
+        mock.push(types::U64::from(100)).unwrap();
+        mock.push(vec![log_at(95), log_at(100)]).unwrap();
+        mock.push(types::U64::from(99)).unwrap();
+        mock.push(Vec::<types::Log>::new()).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".into(),
+            data: None,
+        }));
+        let logs = get_logs_in_range(&provider, &filter, range, backoff())
+            .await
+            .unwrap();
+        assert_eq!(logs, vec![log_at(95), log_at(100)]);
+
+        // away from the chain head, the head of the provider is not checked.
+        let syncing = BlockRange {
+            target: 1_000,
+            ..range
+        };
+        mock.push(vec![log_at(91)]).unwrap();
+        let logs = get_logs_in_range(&provider, &filter, syncing, backoff())
+            .await
+            .unwrap();
+        assert_eq!(logs, vec![log_at(91)]);
+
+        mock.push(vec![log_at(101)]).unwrap();
+        assert!(get_logs_in_range(&provider, &filter, syncing, backoff())
+            .await
+            .is_err());
+
+        // an empty range, left for the next steps, is not queried.
+        let empty = BlockRange {
+            from: 101,
+            to: 100,
+            target: 100,
+        };
+        assert!(get_logs_in_range(&provider, &filter, empty, backoff())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    fn deployment(
+        block: Option<u64>,
+        to: Option<types::Address>,
+    ) -> types::Transaction {
+        types::Transaction {
+            block_number: block.map(Into::into),
+            to,
+            ..Default::default()
+        }
+    }
+
+    fn receipt(contract_address: types::Address) -> types::TransactionReceipt {
+        types::TransactionReceipt {
+            contract_address: Some(contract_address),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn deployments_at_the_configured_block_should_be_accepted() {
+        let (provider, mock) = providers::Provider::mocked();
+        let address = types::Address::repeat_byte(1);
+        let tx_hash = Some(types::H256::repeat_byte(2));
+        // served last in, first out.
+        mock.push(receipt(address)).unwrap();
+        mock.push(deployment(Some(42), None)).unwrap();
+        verify_deployment(&provider, address, tx_hash, 42)
+            .await
+            .unwrap();
+        // without a transaction, nothing is queried.
+        verify_deployment(&provider, address, None, 42)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn deployments_at_another_block_should_be_rejected() {
+        let (provider, mock) = providers::Provider::mocked();
+        let address = types::Address::repeat_byte(1);
+        let tx_hash = Some(types::H256::repeat_byte(2));
+        mock.push(deployment(Some(43), None)).unwrap();
+        let result = verify_deployment(&provider, address, tx_hash, 42).await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::DeploymentMismatch {
+                deployed_at: 42,
+                ..
+            })
+        ));
+
+        // a transaction calling a contract did not deploy one.
+        mock.push(deployment(Some(42), Some(address))).unwrap();
+        let result = verify_deployment(&provider, address, tx_hash, 42).await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::DeploymentMismatch { .. })
+        ));
+
+        // nor did one deploying another contract.
+        mock.push(receipt(types::Address::repeat_byte(3))).unwrap();
+        mock.push(deployment(Some(42), None)).unwrap();
+        let result = verify_deployment(&provider, address, tx_hash, 42).await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::DeploymentMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn deployments_should_be_checked_again_on_provider_errors() {
+        let (provider, mock) = providers::Provider::mocked();
+        let address = types::Address::repeat_byte(1);
+        let tx_hash = Some(types::H256::repeat_byte(2));
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "too many requests".into(),
+            data: None,
+        }));
+        let result = verify_deployment(&provider, address, tx_hash, 42).await;
+        assert!(!matches!(
+            result,
+            Ok(()) | Err(webb_relayer_utils::Error::DeploymentMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn only_the_event_topics_should_be_queried() {
+        let filter = types::Filter::new();
+        assert_eq!(with_event_topics(filter.clone(), Vec::new()), filter);
+
+        let topics =
+            vec![types::H256::repeat_byte(1), types::H256::repeat_byte(2)];
+        let filter = with_event_topics(filter, topics.clone());
+        let expected: types::Topic = types::ValueOrArray::Array(topics).into();
+        assert_eq!(filter.topics[0], Some(expected));
+        assert!(filter.topics[1..].iter().all(Option::is_none));
+    }
+
+    #[tokio::test]
+    async fn other_errors_should_not_be_retried() {
+        let (provider, mock) = providers::Provider::mocked();
+        let range = BlockRange {
+            from: 1,
+            to: 10,
+            target: 1_000,
+        };
+        mock.push(vec![log_at(5)]).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32005,
+            message: "query returned more than 10000 results".into(),
+            data: None,
+        }));
+        let result = get_logs_in_range(
+            &provider,
+            &types::Filter::new(),
+            range,
+            backoff(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(is_block_not_found(&"Requested Block Not Found"));
+        assert!(!is_block_not_found(&"execution reverted"));
+    }
+
+    fn log_with_index(log_index: u64) -> types::Log {
+        types::Log {
+            address: types::Address::repeat_byte(1),
+            topics: vec![types::H256::repeat_byte(log_index as u8)],
+            block_number: Some(42.into()),
+            block_hash: Some(types::H256::repeat_byte(3)),
+            transaction_hash: Some(types::H256::repeat_byte(2)),
+            transaction_index: Some(0.into()),
+            log_index: Some(log_index.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_should_be_fetched_by_their_index_in_the_block() {
+        let (provider, mock) = providers::Provider::mocked();
+        let tx_hash = types::H256::repeat_byte(2);
+        let receipt = types::TransactionReceipt {
+            transaction_hash: tx_hash,
+            logs: vec![log_with_index(7), log_with_index(8)],
+            ..Default::default()
+        };
+        mock.push(receipt.clone()).unwrap();
+        mock.push(receipt).unwrap();
+
+        let log = fetch_log(&provider, tx_hash, 8.into()).await.unwrap();
+        assert_eq!(log.log_index, Some(8.into()));
+        // the index is not the position of the log in the transaction.
+        assert!(fetch_log(&provider, tx_hash, 1.into()).await.is_err());
+    }
+
+    /// An event made of the first topic of its log.
+    #[derive(Debug, Clone)]
+    struct TopicEvent(types::H256);
+
+    impl contract::EthLogDecode for TopicEvent {
+        fn decode_log(
+            log: &webb::evm::ethers::abi::RawLog,
+        ) -> Result<Self, webb::evm::ethers::abi::Error> {
+            log.topics
+                .first()
+                .copied()
+                .map(TopicEvent)
+                .ok_or(webb::evm::ethers::abi::Error::InvalidData)
+        }
+    }
+
+    struct TestContract(contract::Contract<EthersTimeLagClient>);
+
+    impl Deref for TestContract {
+        type Target = contract::Contract<EthersTimeLagClient>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl WatchableContract for TestContract {
+        fn deployed_at(&self) -> types::U64 {
+            0.into()
+        }
+
+        fn polling_interval(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+
+        fn max_blocks_per_step(&self) -> types::U64 {
+            100.into()
+        }
+
+        fn print_progress_interval(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+    }
+
+    /// The provider is never reached, since replaying a log does not query
+    /// the chain.
+    #[derive(Debug)]
+    struct NeverRetry;
+
+    impl providers::RetryPolicy<providers::ProviderError> for NeverRetry {
+        fn should_retry(&self, _error: &providers::ProviderError) -> bool {
+            false
+        }
+
+        fn backoff_hint(
+            &self,
+            _error: &providers::ProviderError,
+        ) -> Option<Duration> {
+            None
+        }
+    }
+
+    fn test_contract(address: types::Address) -> TestContract {
+        let http: providers::Http = "http://localhost:8545".parse().unwrap();
+        let retry_client = providers::RetryClientBuilder::default().build(
+            MultiProvider::new(Arc::new(vec![http])),
+            Box::new(NeverRetry),
+        );
+        let client =
+            TimeLag::new(Arc::new(providers::Provider::new(retry_client)), 0);
+        TestContract(contract::Contract::new(
+            address,
+            webb::evm::ethers::abi::Abi::default(),
+            Arc::new(client),
+        ))
+    }
+
+    struct TestWatcher;
+
+    #[async_trait::async_trait]
+    impl EventWatcher for TestWatcher {
+        const TAG: &'static str = "Test Watcher";
+        type Contract = TestContract;
+        type Events = TopicEvent;
+        type Store = webb_relayer_store::SledStore;
+    }
+
+    /// Handles the events with the given topic, failing if asked to.
+    struct TestHandler {
+        topic: types::H256,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl EventHandler for TestHandler {
+        type Contract = TestContract;
+        type Events = TopicEvent;
+        type Store = webb_relayer_store::SledStore;
+
+        async fn handle_event(
+            &self,
+            _store: Arc<Self::Store>,
+            _contract: &Self::Contract,
+            _event: (Self::Events, contract::LogMeta),
+            _metrics: Arc<Mutex<metric::Metrics>>,
+        ) -> webb_relayer_utils::Result<()> {
+            if self.fail {
+                return Err(webb_relayer_utils::Error::Generic("failed"));
+            }
+            Ok(())
+        }
+
+        async fn can_handle_events(
+            &self,
+            (event, _): (Self::Events, contract::LogMeta),
+            _wrapper: &Self::Contract,
+        ) -> webb_relayer_utils::Result<bool> {
+            Ok(event.0 == self.topic)
+        }
+    }
+
+    #[tokio::test]
+    async fn replayed_logs_should_be_handed_to_every_handler_once() {
+        let store =
+            Arc::new(webb_relayer_store::SledStore::temporary().unwrap());
+        let metrics = Arc::new(Mutex::new(metric::Metrics::new().unwrap()));
+        let contract = test_contract(types::Address::repeat_byte(1));
+        let log = log_with_index(7);
+        let topic = log.topics[0];
+        let handlers: Vec<EventHandlerFor<TestWatcher>> = vec![
+            Box::new(TestHandler { topic, fail: false }),
+            Box::new(TestHandler {
+                topic: types::H256::zero(),
+                fail: false,
+            }),
+            Box::new(TestHandler { topic, fail: true }),
+        ];
+
+        let outcomes = TestWatcher
+            .replay_log(
+                store.clone(),
+                &contract,
+                &handlers,
+                log.clone(),
+                metrics.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(outcomes[0], Ok(true)));
+        assert!(matches!(outcomes[1], Ok(false)), "not interested");
+        assert!(outcomes[2].is_err(), "failed handlers are not retried");
+
+        // logs of other contracts are rejected.
+        let other = test_contract(types::Address::repeat_byte(9));
+        let result = TestWatcher
+            .replay_log(store, &other, &handlers, log, metrics)
+            .await;
+        assert!(result.is_err());
+    }
+}
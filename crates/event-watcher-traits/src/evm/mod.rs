@@ -30,10 +30,13 @@ use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
 use webb_relayer_context::RelayerContext;
 use webb_relayer_store::sled::SledQueueKey;
 use webb_relayer_store::{
-    BridgeCommand, BridgeKey, EventHashStore, HistoryStore, QueueStore,
+    BridgeCommand, BridgeCommandChannels, BridgeKey, EventHashStore,
+    HistoryStore, QueueStore,
 };
 use webb_relayer_utils::metric;
 
+use crate::{BlockRange, Watchdog};
+
 /// Event watching traits
 mod event_watcher;
 pub use event_watcher::*;
@@ -23,6 +23,12 @@
 //! The event watcher calls into a storage for handling of important state. The run implementation
 //! of an event watcher polls for blocks. Implementations of the event watcher trait define an
 //! action to take when the specified event is found in a block at the `handle_event` api.
+//! Watchers may also hook into every step of the run loop, before and after a [`BlockRange`]
+//! is processed.
+
+/// Shared types for the event watchers run loop
+pub mod step;
+pub use step::*;
 
 #[cfg(feature = "evm")]
 pub mod evm;
@@ -0,0 +1,238 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use webb_relayer_utils::metric;
+
+/// The range of blocks processed by an event watcher in a single step of
+/// its run loop, passed to the watcher hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRange {
+    /// The first block of the range (inclusive).
+    pub from: u64,
+    /// The last block of the range (inclusive).
+    ///
+    /// Once the watcher caught up with the chain, the range may be
+    /// empty, with `to` being lower than `from`.
+    pub to: u64,
+    /// The block number the watcher is currently syncing to.
+    pub target: u64,
+}
+
+impl BlockRange {
+    /// Returns true if the watcher reached its target block.
+    pub fn is_synced(&self) -> bool {
+        self.to >= self.target
+    }
+
+    /// Returns the sync progress at the end of this range, in percent.
+    pub fn progress(&self) -> f64 {
+        sync_progress(self.to, self.target)
+    }
+}
+
+/// Returns the sync progress of a watcher, in percent, given the block it
+/// is currently at and the block it is syncing to.
+pub fn sync_progress(currently_at: u64, target: u64) -> f64 {
+    if target == 0 {
+        return 100.0;
+    }
+    (currently_at as f64 / target as f64 * 100.0).min(100.0)
+}
+
+/// How long an event watcher waits before it starts, so that the watchers
+/// of a chain do not all query the RPC provider at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StartDelay {
+    /// The delay between the startups of two consecutive watchers.
+    pub per_watcher: Option<Duration>,
+    /// The order in which the watcher is started, starting from zero.
+    pub watcher_index: usize,
+}
+
+impl StartDelay {
+    /// Creates the start delay of the `watcher_index`-th watcher, given the
+    /// `event-watcher-start-delay-secs` config.
+    pub fn new(delay_secs: Option<f64>, watcher_index: usize) -> Self {
+        let per_watcher = delay_secs
+            .filter(|secs| secs.is_finite() && *secs > 0.0)
+            .map(Duration::from_secs_f64);
+        Self {
+            per_watcher,
+            watcher_index,
+        }
+    }
+
+    /// How long the watcher has to wait before it starts.
+    pub fn duration(&self) -> Duration {
+        self.per_watcher
+            .map(|d| d * self.watcher_index as u32)
+            .unwrap_or_default()
+    }
+
+    /// Waits until the watcher is allowed to start.
+    pub async fn wait(&self, metrics: &Mutex<metric::Metrics>) {
+        let delay = self.duration();
+        if delay.is_zero() {
+            return;
+        }
+        tracing::debug!(
+            watcher_index = self.watcher_index,
+            ?delay,
+            "Delaying the start of the event watcher",
+        );
+        metrics.lock().await.watcher_start_delay_applied.inc();
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Detects a wedged run loop, such as one waiting on a half-open connection
+/// to the RPC provider, which neither answers nor times out.
+///
+/// The run loop [feeds](Watchdog::feed) the watchdog whenever it makes
+/// progress, and runs under its [guard](Watchdog::guard).
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    timeout: Duration,
+    last_progress: Arc<std::sync::Mutex<Instant>>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog that fires once there was no progress for
+    /// `timeout`, a zero timeout disables it.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_progress: Arc::new(std::sync::Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Records that the watcher made progress.
+    pub fn feed(&self) {
+        *self.last_progress.lock().expect("watchdog lock poisoned") =
+            Instant::now();
+    }
+
+    /// How long it has been since the watcher made progress.
+    pub fn elapsed(&self) -> Duration {
+        self.last_progress
+            .lock()
+            .expect("watchdog lock poisoned")
+            .elapsed()
+    }
+
+    /// Runs the future until it completes, or until the watcher makes no
+    /// progress for the timeout.
+    ///
+    /// Returns `None` if the watchdog fired. The future is dropped then, along
+    /// with the requests it had in flight, so their connections are closed
+    /// and the next requests connect again.
+    pub async fn guard<F: Future>(&self, fut: F) -> Option<F::Output> {
+        if self.timeout.is_zero() {
+            return Some(fut.await);
+        }
+        self.feed();
+        tokio::pin!(fut);
+        loop {
+            let deadline =
+                *self.last_progress.lock().expect("watchdog lock poisoned")
+                    + self.timeout;
+            tokio::select! {
+                output = &mut fut => return Some(output),
+                _ = tokio::time::sleep_until(deadline) => {
+                    if self.elapsed() >= self.timeout {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn sync_progress_should_be_capped() {
+        assert_eq!(sync_progress(50, 200), 25.0);
+        assert_eq!(sync_progress(200, 200), 100.0);
+        // the target may be stale, by the time we reach it.
+        assert_eq!(sync_progress(210, 200), 100.0);
+        assert_eq!(sync_progress(0, 0), 100.0);
+
+        let range = BlockRange {
+            from: 101,
+            to: 200,
+            target: 200,
+        };
+        assert!(range.is_synced());
+        assert_eq!(range.progress(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn watchers_should_start_staggered() {
+        let metrics = Arc::new(Mutex::new(metric::Metrics::new().unwrap()));
+        let started_at = Instant::now();
+        let watchers = (0..3).map(|i| {
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                StartDelay::new(Some(0.01), i).wait(&metrics).await;
+                started_at.elapsed()
+            })
+        });
+        let mut starts = Vec::new();
+        for watcher in watchers.collect::<Vec<_>>() {
+            starts.push(watcher.await.unwrap());
+        }
+        // sleeping may take longer than asked, so the starts are checked
+        // against the startup instead of against each other.
+        for (i, start) in starts.iter().enumerate() {
+            assert!(*start >= Duration::from_millis(10) * i as u32);
+        }
+        // the first watcher does not wait.
+        assert_eq!(metrics.lock().await.watcher_start_delay_applied.get(), 2.0);
+        assert!(StartDelay::new(None, 5).duration().is_zero());
+    }
+
+    #[tokio::test]
+    async fn watchdog_should_fire_without_progress() {
+        let watchdog = Watchdog::new(Duration::from_millis(20));
+        let stuck = watchdog.guard(futures::future::pending::<()>()).await;
+        assert_eq!(stuck, None);
+        assert!(watchdog.elapsed() >= Duration::from_millis(20));
+
+        // a slow future is fine, as long as it keeps making progress.
+        let slow = watchdog.guard(async {
+            for _ in 0..6 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                watchdog.feed();
+            }
+            42
+        });
+        assert_eq!(slow.await, Some(42));
+
+        let disabled = Watchdog::new(Duration::ZERO);
+        let slow = disabled.guard(async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+        });
+        assert_eq!(slow.await, Some(()));
+    }
+}
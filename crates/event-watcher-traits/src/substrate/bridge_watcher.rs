@@ -16,6 +16,8 @@ use super::{event_watcher::SubstrateEventWatcher, *};
 use sp_core::sr25519::Pair as Sr25519Pair;
 use webb::substrate::subxt::OnlineClient;
 use webb_relayer_context::RelayerContext;
+use webb_relayer_store::QUEUED_TXS_TIMEOUT;
+use webb_relayer_utils::static_tx_payload::TypeErasedStaticTxPayload;
 // A Substrate Bridge Watcher is a trait for Signature Bridge Pallet that is not specific for watching events from that pallet,
 /// instead it watches for commands sent from other event watchers or services, it helps decouple the event watchers
 /// from the actual action that should be taken depending on the event.
@@ -24,7 +26,8 @@ pub trait SubstrateBridgeWatcher<RuntimeConfig>:
     SubstrateEventWatcher<RuntimeConfig>
 where
     RuntimeConfig: subxt::Config + Send + Sync + 'static,
-    Self::Store: QueueStore<BridgeCommand, Key = SledQueueKey>,
+    Self::Store: QueueStore<BridgeCommand, Key = SledQueueKey>
+        + QueueStore<TypeErasedStaticTxPayload, Key = SledQueueKey>,
 {
     /// A method that is called when a command is received that needs to be
     /// handled and executed.
@@ -39,6 +42,10 @@ where
 
     /// Returns a task that should be running in the background
     /// that will watch events
+    ///
+    /// The commands are taken one at a time from the channel of the bridge,
+    /// once the transactions queued for the previous one are sent, so the
+    /// event handlers sending them are held back while the signer is behind.
     #[tracing::instrument(
         skip_all,
         fields(
@@ -60,36 +67,40 @@ where
                 webb_proposals::TypedChainId::Substrate(chain_id);
             let bridge_key = BridgeKey::new(typed_chain_id);
             let key = SledQueueKey::from_bridge_key(bridge_key);
+            let tx_key = SledQueueKey::from_substrate_chain_id(chain_id);
             let client =
                 ctx.substrate_provider::<RuntimeConfig, _>(chain_id).await?;
             let client = Arc::new(client);
+            let commands = ctx.bridge_commands().subscribe(bridge_key);
 
             loop {
-                let result = match store.dequeue_item(key)? {
-                    Some(cmd) => {
-                        self.handle_cmd(
-                            chain_id,
-                            store.clone(),
-                            client.clone(),
-                            pair.clone(),
-                            cmd,
-                        )
-                        .await
-                    }
-                    None => {
-                        // yeild back to the runtime, to allow for other tasks
-                        // to make progress, instead of this busy loop.
-                        tokio::task::yield_now().await;
-                        // the small sleep here just in case the runtime decides to
-                        // run this task again immediately.
-                        tokio::time::sleep(Duration::from_millis(10)).await;
-                        continue;
-                    }
+                let next =
+                    commands.recv(store.as_ref(), Duration::from_secs(1));
+                let Some(cmd) = next.await? else {
+                    continue;
                 };
+                let result = self
+                    .handle_cmd(
+                        chain_id,
+                        store.clone(),
+                        client.clone(),
+                        pair.clone(),
+                        cmd,
+                    )
+                    .await;
 
                 match result {
                     Ok(_) => {
                         tracing::debug!(?key, "Handled command successfully");
+                        // hand the next command over once the signer sent
+                        // the transactions queued so far.
+                        commands
+                            .wait_for_queued_txs::<TypeErasedStaticTxPayload, _>(
+                                store.as_ref(),
+                                tx_key,
+                                QUEUED_TXS_TIMEOUT,
+                            )
+                            .await?;
                         continue;
                     }
                     Err(e) => {
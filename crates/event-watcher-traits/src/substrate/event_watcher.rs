@@ -130,6 +130,30 @@ where
     /// The Storage backend, used by the event watcher to store its state.
     type Store: HistoryStore;
 
+    /// A hook called before the events of the given block range are queried.
+    ///
+    /// Returning an error restarts the watcher, after a backoff.
+    async fn pre_step(
+        &self,
+        _store: &Arc<Self::Store>,
+        _range: BlockRange,
+    ) -> webb_relayer_utils::Result<()> {
+        Ok(())
+    }
+
+    /// A hook called once all the events of the given block range are handled,
+    /// and the range is checkpointed in the store.
+    ///
+    /// Returning an error restarts the watcher, after a backoff.
+    async fn post_step(
+        &self,
+        _store: &Arc<Self::Store>,
+        _range: BlockRange,
+        _number_of_events: usize,
+    ) -> webb_relayer_utils::Result<()> {
+        Ok(())
+    }
+
     /// Returns a task that should be running in the background
     /// that will watch events
     #[tracing::instrument(
@@ -239,6 +263,14 @@ where
                 tracing::trace!("Reading from #{} to #{}", block, dest_block);
                 // Only handle events from found blocks if they are new
                 if dest_block != block {
+                    let range = BlockRange {
+                        from: block + 1,
+                        to: dest_block,
+                        target: current_block_number,
+                    };
+                    self.pre_step(&store, range)
+                        .map_err(backoff::Error::transient)
+                        .await?;
                     // we need to query the node for the events that happened in the
                     // range [block, dest_block].
                     // so first we get the hash of the block we want to start from.
@@ -298,6 +330,9 @@ where
                             "event handled successfully at block #{}",
                             dest_block
                         );
+                        self.post_step(&store, range, events.len() as usize)
+                            .map_err(backoff::Error::transient)
+                            .await?;
                     } else {
                         tracing::error!(
                             "Error while handling event, all handlers failed."
@@ -310,6 +345,15 @@ where
                     }
                 }
                 tracing::trace!("Polled from #{} to #{}", block, dest_block);
+                // the freshness of the leaves is derived from these.
+                store.set_target_block_number(
+                    history_store_key,
+                    current_block_number,
+                )?;
+                store.set_last_synced_at(
+                    history_store_key,
+                    ctx.clock().unix_timestamp(),
+                )?;
                 if should_cooldown {
                     let duration = Duration::from_secs(6);
                     tracing::trace!(
@@ -326,14 +370,8 @@ where
                 if print_progress_interval != Duration::from_millis(0)
                     && instant.elapsed() > print_progress_interval
                 {
-                    // calculate sync progress.
-                    let total = current_block_number as f64;
-                    let current_value = dest_block as f64;
-                    let diff = total - current_value;
-                    let percentage = (diff / current_value) * 100.0;
-                    // should be always less that 100.
-                    // and this would be our current progress.
-                    let sync_progress = 100.0 - percentage;
+                    let sync_progress =
+                        crate::sync_progress(dest_block, current_block_number);
                     tracing::info!(
                         "🔄 #{} of #{} ({:.4}%)",
                         dest_block,
@@ -25,6 +25,8 @@ use webb_proposals::{
 use webb_relayer_store::sled::SledQueueKey;
 use webb_relayer_store::{BridgeCommand, BridgeKey, HistoryStore, QueueStore};
 
+use crate::BlockRange;
+
 /// Event watching traits
 mod event_watcher;
 pub use event_watcher::*;
@@ -10,7 +10,10 @@ use webb_proposals::ProposalTrait;
 use webb::substrate::scale::{Encode, Decode};
 use webb_relayer_utils::metric;
 use webb::substrate::tangle_runtime::api as RuntimeApi;
-use webb_relayer_store::{QueueStore, SledStore};
+use webb_relayer_store::{
+    compute_proposal_hash, ProposalEventKind, ProposalEventStore, QueueStore,
+    SledStore,
+};
 use webb_relayer_store::sled::SledQueueKey;
 use webb_relayer_utils::static_tx_payload::TypeErasedStaticTxPayload;
 
@@ -128,6 +131,20 @@ impl super::ProposalSigningBackend for DkgProposalSigningBackend {
         let tx = TypeErasedStaticTxPayload::try_from(acknowledge_proposal_tx)?;
         // Enqueue transaction in protocol-substrate transaction queue
         QueueStore::enqueue_item(&self.store, tx_key, tx)?;
+        let proposal_data = proposal.to_vec();
+        let proposal_data_hash = compute_proposal_hash(&proposal_data);
+        self.store.record_proposal(
+            ProposalEventKind::VoteQueued,
+            &proposal_data,
+            proposal_data_hash,
+            None,
+        );
+        self.store.link_proposal(
+            data_hash,
+            ProposalEventKind::VoteMined,
+            &proposal_data,
+            proposal_data_hash,
+        );
         Ok(())
     }
 }
@@ -4,10 +4,12 @@ use tokio::sync::Mutex;
 use typed_builder::TypedBuilder;
 use webb::evm::ethers::core::k256::SecretKey;
 use webb::evm::ethers::prelude::*;
-use webb::evm::ethers::utils::keccak256;
 use webb_proposals::{ProposalTrait, ResourceId, TypedChainId};
 use webb_relayer_store::sled::SledQueueKey;
-use webb_relayer_store::{BridgeCommand, BridgeKey, QueueStore};
+use webb_relayer_store::{
+    compute_proposal_hash, BridgeCommand, BridgeCommandChannels, BridgeKey,
+    ProposalEventKind, ProposalEventStore, QueueStore,
+};
 use webb_relayer_types::private_key::PrivateKey;
 use webb_relayer_utils::metric;
 
@@ -22,6 +24,8 @@ where
     signature_bridges: HashSet<ResourceId>,
     /// Something that implements the QueueStore trait.
     store: Arc<S>,
+    /// Hands the signed proposals over to the signature bridges.
+    commands: BridgeCommandChannels,
     /// The private key of the governor.
     /// **NOTE**: This must be the same for all signature bridges.
     private_key: PrivateKey,
@@ -45,7 +49,11 @@ where
 #[async_trait::async_trait]
 impl<S> super::ProposalSigningBackend for MockedProposalSigningBackend<S>
 where
-    S: QueueStore<BridgeCommand, Key = SledQueueKey> + Send + Sync + 'static,
+    S: QueueStore<BridgeCommand, Key = SledQueueKey>
+        + ProposalEventStore
+        + Send
+        + Sync
+        + 'static,
 {
     async fn can_handle_proposal(
         &self,
@@ -67,7 +75,7 @@ where
         let dest_chain_id = resource_id.typed_chain_id();
         let signer = self.signer(dest_chain_id)?;
         let proposal_bytes = proposal.to_vec();
-        let hash = keccak256(&proposal_bytes);
+        let hash = compute_proposal_hash(&proposal_bytes);
         let signature = signer.sign_hash(TxHash(hash))?;
         let bridge_key = BridgeKey::new(dest_chain_id);
         tracing::debug!(
@@ -88,13 +96,23 @@ where
         // Proposal signed metric
         metrics.lock().await.proposals_signed.inc();
         // now all we have to do is to send the data and the signature to the signature bridge.
-        self.store.enqueue_item(
-            SledQueueKey::from_bridge_key(bridge_key),
-            BridgeCommand::ExecuteProposalWithSignature {
-                data: proposal_bytes.clone(),
-                signature: signature_bytes,
-            },
-        )?;
+        self.commands
+            .send(
+                &self.store,
+                bridge_key,
+                BridgeCommand::ExecuteProposalWithSignature {
+                    data: proposal_bytes.clone(),
+                    signature: signature_bytes,
+                },
+            )
+            .await?;
+        // signed by the governor alone, so it passed right away.
+        self.store.record_proposal(
+            ProposalEventKind::Passed,
+            &proposal_bytes,
+            hash,
+            None,
+        );
 
         Ok(())
     }
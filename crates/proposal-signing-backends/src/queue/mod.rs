@@ -1,14 +1,16 @@
 use std::sync::{atomic, Arc};
 
 use tokio::sync::Mutex;
-use webb::evm::ethers;
 use webb_proposals::ProposalTrait;
+use webb_relayer_utils::clock::SharedClock;
 use webb_relayer_utils::metric;
 
 /// A module for in-memory Proposals Queue.
 pub mod mem;
 /// A module for Proposals Polices.
 pub mod policy;
+/// A module for keeping the merkle root of queued proposals fresh.
+pub mod root;
 
 /// A Proposal Queue is a simple Queue that holds the proposals that are going to be
 /// signed or already signed and need to be sent to the target system to be executed.
@@ -189,6 +191,12 @@ impl QueuedProposalMetadata {
         self.should_be_dequeued_at
             .store(should_be_dequeued_at, atomic::Ordering::SeqCst);
     }
+
+    /// Set the time at which the proposal was enqueued.
+    /// The value is the number of secs since the UNIX epoch.
+    pub fn set_queued_at(&self, queued_at: u64) {
+        self.queued_at.store(queued_at, atomic::Ordering::SeqCst);
+    }
 }
 
 impl Default for QueuedProposalMetadata {
@@ -248,7 +256,7 @@ where
     P: ProposalTrait,
 {
     fn full_hash(&self) -> [u8; 32] {
-        ethers::utils::keccak256(self.to_vec())
+        webb_relayer_store::compute_proposal_hash(&self.to_vec())
     }
 }
 
@@ -288,15 +296,23 @@ impl ProposalMetadata for QueuedAnchorUpdateProposal {
 /// This function will loop forever and should be run in a separate task.
 /// it will never end unless the task is cancelled.
 #[tracing::instrument(skip_all)]
-pub async fn run<Queue, Policy, PSB>(
+///
+/// Before handing a proposal to the signing backend, its merkle root is checked
+/// against the `root_history_depth` of the source anchor, and re-fetched from
+/// the `root_source` if it is too old to still be valid once executed.
+pub async fn run<Queue, Policy, PSB, Root>(
     queue: Queue,
     dequeue_policy: Policy,
     proposal_signing_backend: PSB,
+    root_source: Root,
+    root_history_depth: u32,
     metrics: Arc<Mutex<metric::Metrics>>,
+    clock: SharedClock,
 ) where
-    Queue: ProposalsQueue,
+    Queue: ProposalsQueue<Proposal = QueuedAnchorUpdateProposal>,
     Policy: policy::ProposalPolicy + Clone,
     PSB: super::ProposalSigningBackend,
+    Root: root::AnchorRootSource,
 {
     loop {
         let proposal = match queue.dequeue(dequeue_policy.clone()) {
@@ -304,8 +320,7 @@ pub async fn run<Queue, Policy, PSB>(
             Ok(None) => {
                 tracing::trace!("No proposal to dequeue");
                 // Sleep for a bit to avoid busy looping
-                tokio::time::sleep(core::time::Duration::from_millis(1100))
-                    .await;
+                clock.sleep(core::time::Duration::from_millis(1100)).await;
                 continue;
             }
             Err(e) => {
@@ -315,6 +330,23 @@ pub async fn run<Queue, Policy, PSB>(
             }
         };
 
+        let proposal = match root::refresh_stale_root(
+            proposal,
+            &root_source,
+            root_history_depth,
+        )
+        .await
+        {
+            Ok(proposal) => proposal,
+            Err(e) => {
+                tracing::error!(
+                    error = ?e,
+                    "failed to check the proposal merkle root",
+                );
+                continue;
+            }
+        };
+
         let result = crate::proposal_handler::handle_proposal(
             &proposal,
             &proposal_signing_backend,
@@ -342,6 +374,7 @@ pub async fn run<Queue, Policy, PSB>(
 #[cfg(test)]
 pub(crate) mod test_utils {
     use super::*;
+    use webb::evm::ethers;
 
     pub fn setup_tracing() -> tracing::subscriber::DefaultGuard {
         // Setup tracing for tests
@@ -406,6 +439,36 @@ pub(crate) mod test_utils {
         Arc::new(Mutex::new(metric::Metrics::new().unwrap()))
     }
 
+    /// Waits for `f` to hold, letting the other tasks run meanwhile.
+    ///
+    /// Panics if it still does not after a second.
+    pub async fn eventually(mut f: impl FnMut() -> bool) {
+        let wait = async {
+            while !f() {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(1), wait)
+            .await
+            .expect("the condition should hold by now");
+    }
+
+    /// A root source that never reports a newer root.
+    #[derive(Clone, Debug, Default)]
+    pub struct DummyRootSource;
+
+    #[async_trait::async_trait]
+    impl root::AnchorRootSource for DummyRootSource {
+        async fn current_root(
+            &self,
+        ) -> webb_relayer_utils::Result<root::CurrentRoot> {
+            Ok(root::CurrentRoot {
+                merkle_root: [0u8; 32],
+                next_index: 0,
+            })
+        }
+    }
+
     #[derive(Clone, Debug, Default)]
     pub struct DummySigningBackend {
         pub handled_proposals_count: Arc<atomic::AtomicU32>,
@@ -440,6 +503,9 @@ mod tests {
     use std::time::Duration;
 
     use rand::Rng;
+    use webb::evm::ethers;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+    use webb_relayer_utils::clock::SystemClock;
 
     use super::test_utils::*;
     use super::*;
@@ -447,11 +513,13 @@ mod tests {
     #[tokio::test]
     async fn simulation() {
         let _guard = setup_tracing();
+        let clock = FrozenClock::default();
         let queue = mem::InMemoryProposalsQueue::new();
         let time_delay_policy = policy::TimeDelayPolicy::builder()
             .initial_delay(1)
             .min_delay(1)
             .max_delay(5)
+            .clock(clock.shared())
             .build();
         let nonce_policy = policy::AlwaysHigherNoncePolicy;
         let enqueue_policy = (nonce_policy, time_delay_policy.clone());
@@ -463,7 +531,10 @@ mod tests {
             queue.clone(),
             dequeue_policy,
             signing_backend.clone(),
+            DummyRootSource,
+            30,
             metrics,
+            clock.shared(),
         ));
 
         let target_system = mock_target_system(ethers::types::Address::zero());
@@ -478,18 +549,22 @@ mod tests {
             let header = mock_proposal_header(r_id, nonce);
             let proposal = mock_evm_anchor_update_proposal(header, src_r_id);
             queue.enqueue(proposal, enqueue_policy.clone()).unwrap();
-            tokio::time::sleep(Duration::from_millis(10)).await;
+            clock.advance(Duration::from_millis(10));
+            tokio::task::yield_now().await;
         }
-        tokio::time::sleep(time_delay_policy.max_delay()).await;
+        // nothing is due yet.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!queue.is_empty().unwrap(), "the queue should wait");
+        clock.advance(time_delay_policy.max_delay());
+        let handled = &signing_backend.handled_proposals_count;
+        eventually(|| handled.load(atomic::Ordering::SeqCst) > 0).await;
         // all proposals should be handled by now.
         // the queue should be empty.
         assert!(queue.is_empty().unwrap(), "the queue should be empty");
         // we should only have handled one proposal.
         // the rest should be removed from the queue.
         assert_eq!(
-            signing_backend
-                .handled_proposals_count
-                .load(atomic::Ordering::SeqCst),
+            handled.load(atomic::Ordering::SeqCst),
             1,
             "we should only have handled one proposal",
         );
@@ -519,7 +594,10 @@ mod tests {
             queue.clone(),
             dequeue_policy,
             signing_backend.clone(),
+            DummyRootSource,
+            30,
             metrics,
+            SystemClock::shared(),
         ));
 
         let target_system = mock_target_system(ethers::types::Address::zero());
@@ -588,7 +666,10 @@ mod tests {
             queue.clone(),
             dequeue_policy,
             signing_backend.clone(),
+            DummyRootSource,
+            30,
             metrics,
+            SystemClock::shared(),
         ));
 
         let target_system = mock_target_system(ethers::types::Address::zero());
@@ -629,4 +710,24 @@ mod tests {
         handle.abort();
         handle2.abort();
     }
+
+    #[test]
+    fn compute_proposal_hash_should_match_the_inline_keccak256() {
+        let target_system = mock_target_system(ethers::types::Address::zero());
+        let src_system =
+            mock_target_system(ethers::types::Address::repeat_byte(0x11));
+        let r_id = mock_resourc_id(target_system, mock_typed_chain_id(1));
+        let src_r_id = mock_resourc_id(src_system, mock_typed_chain_id(42));
+        let header = mock_proposal_header(r_id, 7);
+        let proposal = mock_evm_anchor_update_proposal(header, src_r_id);
+        let data = proposal.to_vec();
+
+        let hash = webb_relayer_store::compute_proposal_hash(&data);
+        assert_eq!(hash, ethers::utils::keccak256(&data));
+        assert_eq!(hash, proposal.full_hash());
+        assert_eq!(
+            hex::encode(webb_relayer_store::compute_proposal_hash(&[])),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use webb_proposals::{ProposalTrait, ResourceId};
+use webb_relayer_utils::clock::{SharedClock, SystemClock};
+
+use crate::queue::ProposalsQueue;
+
+/// A dequeue policy that holds back the anchor update proposals of a linked
+/// anchor for a `cooldown` after one got dequeued for it, bounding the
+/// updates, hence the gas spent, on its chain whatever the deposit rate.
+///
+/// The proposals queue of an anchor only carries the updates of its linked
+/// anchors, so the cooldown applies per edge, from the source anchor to each
+/// linked anchor. With the [`AlwaysHigherNoncePolicy`] on enqueue, the
+/// proposals held back coalesce to the one with the latest root.
+///
+/// [`AlwaysHigherNoncePolicy`]: super::AlwaysHigherNoncePolicy
+///
+/// # Example
+///
+/// ```rust
+/// # use webb_proposal_signing_backends::queue::policy::EdgeCooldownPolicy;
+/// use std::time::Duration;
+///
+/// let policy = EdgeCooldownPolicy::builder()
+///     .cooldown(Duration::from_secs(600))
+///     .build();
+/// assert_eq!(policy.cooldown(), Duration::from_secs(600));
+/// ```
+#[derive(Debug, Clone, typed_builder::TypedBuilder)]
+pub struct EdgeCooldownPolicy {
+    /// How long the proposals of a linked anchor are held back for
+    cooldown: Duration,
+    /// The clock the cooldowns are measured with
+    #[builder(default = SystemClock::shared())]
+    clock: SharedClock,
+    /// When a proposal was last dequeued, for each linked anchor
+    #[builder(setter(skip), default)]
+    last_dequeued: Arc<Mutex<HashMap<ResourceId, SystemTime>>>,
+}
+
+impl EdgeCooldownPolicy {
+    /// Returns the cooldown of the edges
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+}
+
+impl super::ProposalPolicy for EdgeCooldownPolicy {
+    #[tracing::instrument(skip_all)]
+    fn check<Q: ProposalsQueue>(
+        &self,
+        proposal: &Q::Proposal,
+        _queue: &Q,
+    ) -> webb_relayer_utils::Result<()> {
+        let target = proposal.header().resource_id();
+        let now = self.clock.now();
+        let mut last_dequeued = self.last_dequeued.lock();
+        if let Some(at) = last_dequeued.get(&target) {
+            let elapsed = now.duration_since(*at).unwrap_or_default();
+            if elapsed < self.cooldown {
+                tracing::trace!(
+                    ?target,
+                    wait_secs = (self.cooldown - elapsed).as_secs(),
+                    "Edge is cooling down, holding back the proposal",
+                );
+                return Err(webb_relayer_utils::Error::Generic(
+                    "Edge is cooling down",
+                ));
+            }
+        }
+        last_dequeued.insert(target, now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use webb::evm::ethers;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    use crate::queue::policy::AlwaysHigherNoncePolicy;
+    use crate::queue::{mem::InMemoryProposalsQueue, test_utils::*};
+
+    type TestQueue = InMemoryProposalsQueue;
+
+    #[test]
+    fn should_coalesce_the_updates_of_an_edge_until_it_cooled_down() {
+        let _guard = setup_tracing();
+        let clock = FrozenClock::default();
+        let policy = EdgeCooldownPolicy::builder()
+            .cooldown(Duration::from_secs(60))
+            .clock(clock.shared())
+            .build();
+        let queue = TestQueue::new();
+
+        let target_system = mock_target_system(ethers::types::Address::zero());
+        let src_system = mock_target_system(ethers::types::Address::zero());
+        let src_r_id = mock_resourc_id(src_system, mock_typed_chain_id(42));
+        let r_id = mock_resourc_id(target_system, mock_typed_chain_id(1));
+        let other_r_id = mock_resourc_id(target_system, mock_typed_chain_id(2));
+
+        let header = mock_proposal_header(r_id, 1);
+        let proposal = mock_evm_anchor_update_proposal(header, src_r_id);
+        queue.enqueue(proposal, AlwaysHigherNoncePolicy).unwrap();
+        let proposal = queue.dequeue(policy.clone()).unwrap();
+        assert!(proposal.is_some(), "the first update goes through");
+
+        for nonce in 2..=5 {
+            let header = mock_proposal_header(r_id, nonce);
+            let proposal = mock_evm_anchor_update_proposal(header, src_r_id);
+            queue.enqueue(proposal, AlwaysHigherNoncePolicy).unwrap();
+        }
+        let header = mock_proposal_header(other_r_id, 1);
+        let proposal = mock_evm_anchor_update_proposal(header, src_r_id);
+        queue.enqueue(proposal, AlwaysHigherNoncePolicy).unwrap();
+
+        // the other edge is not held back.
+        let proposal = queue.dequeue(policy.clone()).unwrap().unwrap();
+        assert_eq!(proposal.header().resource_id(), other_r_id);
+        clock.advance(Duration::from_secs(59));
+        assert!(
+            queue.dequeue(policy.clone()).unwrap().is_none(),
+            "the edge is still cooling down"
+        );
+        assert_eq!(queue.len().unwrap(), 1, "should keep the latest update");
+
+        clock.advance(Duration::from_secs(1));
+        let proposal = queue.dequeue(policy.clone()).unwrap().unwrap();
+        assert_eq!(proposal.header().resource_id(), r_id);
+        assert_eq!(
+            proposal.header().nonce().to_u32(),
+            5,
+            "should dequeue the latest update"
+        );
+        assert!(queue.is_empty().unwrap());
+    }
+}
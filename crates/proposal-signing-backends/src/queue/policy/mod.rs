@@ -1,6 +1,8 @@
+mod cooldown;
 mod nonce;
 mod time;
 
+pub use cooldown::*;
 pub use nonce::*;
 pub use time::*;
 
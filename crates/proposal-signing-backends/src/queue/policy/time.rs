@@ -5,6 +5,8 @@ use std::{
     time::Duration,
 };
 
+use webb_relayer_utils::clock::{SharedClock, SystemClock};
+
 use crate::queue::{ProposalHash, ProposalMetadata, ProposalsQueue};
 
 /// Initial delay in seconds
@@ -54,6 +56,9 @@ pub struct TimeDelayPolicy {
     /// Sliding window size
     #[builder(default = WINDOW_SIZE)]
     window_size: usize,
+    /// The clock the delays are measured with
+    #[builder(default = SystemClock::shared())]
+    clock: SharedClock,
     /// Sliding window of delays
     /// The sliding window is used to calculate the average delay
     /// The average delay is used to adjust the current delay
@@ -144,10 +149,7 @@ impl super::ProposalPolicy for TimeDelayPolicy {
         let delay_changed = self.update_delay(size + 1)?;
         let delay = self.delay().as_secs();
         tracing::debug!(delay_changed, delay, queue_size = size);
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        let now = self.clock.unix_timestamp();
         let metadata = proposal.metadata();
         // check if the proposal should be dequeued
         let ret = match metadata.should_be_dequeued_at() {
@@ -173,9 +175,10 @@ impl super::ProposalPolicy for TimeDelayPolicy {
                 ))
             }
             None => {
-                let queued_at = metadata.queued_at();
-                let expected_to_be_dequeued_at = queued_at.add(delay);
                 // this means we are trying to queue a proposal.
+                metadata.set_queued_at(now);
+                let queued_at = now;
+                let expected_to_be_dequeued_at = queued_at.add(delay);
                 // we set the should_be_dequeued_at value
                 metadata.set_should_be_dequeued_at(expected_to_be_dequeued_at);
                 tracing::trace!(
@@ -218,6 +221,7 @@ mod tests {
     use std::time::Duration;
 
     use webb::evm::ethers;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
 
     use crate::queue::{mem::InMemoryProposalsQueue, test_utils::*};
 
@@ -316,10 +320,12 @@ mod tests {
     #[test]
     fn should_dequeue_proposal_at_the_right_time() {
         let _guard = setup_tracing();
+        let clock = FrozenClock::default();
         let policy = TimeDelayPolicy::builder()
             .initial_delay(1)
             .min_delay(0)
             .max_delay(2)
+            .clock(clock.shared())
             .build();
         assert_eq!(policy.delay(), Duration::from_secs(1));
 
@@ -344,7 +350,7 @@ mod tests {
             "should accept proposal"
         );
         // wait until the proposal is expected to be dequeued
-        std::thread::sleep(policy.delay());
+        clock.advance(policy.delay());
         let proposal = queue.dequeue(policy).unwrap();
         assert!(proposal.is_some(), "should dequeue proposal");
     }
@@ -352,10 +358,12 @@ mod tests {
     #[test]
     fn should_increase_delay_as_we_see_more_proposals() {
         let _guard = setup_tracing();
+        let clock = FrozenClock::default();
         let policy = TimeDelayPolicy::builder()
             .initial_delay(1)
             .min_delay(1)
             .max_delay(3)
+            .clock(clock.shared())
             .build();
         assert_eq!(policy.delay(), Duration::from_secs(1));
 
@@ -392,7 +400,7 @@ mod tests {
         let proposal = queue.dequeue(policy.clone()).unwrap();
         assert!(proposal.is_none(), "Cannot dequeue proposal yet");
         // wait for the delay to expire
-        std::thread::sleep(policy.delay());
+        clock.advance(policy.delay());
         // we should be able to dequeue a proposal now
         let proposal = queue.dequeue(policy).unwrap();
         assert!(proposal.is_some(), "should dequeue proposal");
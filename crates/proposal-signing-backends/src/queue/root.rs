@@ -0,0 +1,218 @@
+use webb_proposals::{ProposalTrait, TargetSystem};
+
+use super::QueuedAnchorUpdateProposal;
+use crate::proposal_handler;
+
+/// The current state of the source anchor merkle tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentRoot {
+    /// The latest merkle root of the anchor.
+    pub merkle_root: [u8; 32],
+    /// The number of leaves inserted in the anchor (`nextIndex`),
+    /// at the same block as the `merkle_root`.
+    pub next_index: u32,
+}
+
+/// A source of the current merkle root of the anchor that the queued
+/// anchor update proposals are created for.
+#[async_trait::async_trait]
+pub trait AnchorRootSource: Send + Sync {
+    /// Returns the current merkle root of the anchor.
+    async fn current_root(&self) -> webb_relayer_utils::Result<CurrentRoot>;
+
+    /// Called when a proposal got re-created with a fresh root, with the
+    /// old and the new proposal.
+    async fn on_proposal_refreshed(
+        &self,
+        _old_proposal: &QueuedAnchorUpdateProposal,
+        _new_proposal: &QueuedAnchorUpdateProposal,
+    ) -> webb_relayer_utils::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns how many roots the anchor went through since the root of the
+/// anchor update proposal with the given nonce.
+///
+/// The nonce of an anchor update proposal is the index of the leaf that
+/// triggered it, and every `transact` inserts two leaves with a single root.
+pub fn root_age(nonce: u32, next_index: u32) -> u32 {
+    next_index.saturating_sub(nonce.saturating_add(1)) / 2
+}
+
+/// Returns true if the root of the anchor update proposal with the given
+/// nonce already rotated out of the anchor's root history.
+pub fn is_root_stale(
+    nonce: u32,
+    next_index: u32,
+    root_history_depth: u32,
+) -> bool {
+    root_age(nonce, next_index) >= root_history_depth
+}
+
+/// Makes sure the root of the given anchor update proposal is still within
+/// the source anchor's root history, otherwise re-creates the proposal with
+/// the current root of the anchor.
+#[tracing::instrument(skip_all)]
+pub async fn refresh_stale_root<S>(
+    proposal: QueuedAnchorUpdateProposal,
+    root_source: &S,
+    root_history_depth: u32,
+) -> webb_relayer_utils::Result<QueuedAnchorUpdateProposal>
+where
+    S: AnchorRootSource + ?Sized,
+{
+    let header = proposal.header();
+    let nonce = header.nonce().to_u32();
+    let current = root_source.current_root().await?;
+    if !is_root_stale(nonce, current.next_index, root_history_depth) {
+        return Ok(proposal);
+    }
+    // the proposal is the header (40 bytes), the merkle root,
+    // and the source resource id.
+    let bytes = proposal.to_vec();
+    if bytes.len() != 40 + 32 + 32 {
+        return Err(webb_relayer_utils::Error::Generic(
+            "not an anchor update proposal",
+        ));
+    }
+    let mut src_resource_id = [0u8; 32];
+    src_resource_id.copy_from_slice(&bytes[bytes.len() - 32..]);
+    let src_resource_id = webb_proposals::ResourceId::from(src_resource_id);
+    let target_resource_id = header.resource_id();
+    let leaf_index = current.next_index.saturating_sub(1);
+    tracing::warn!(
+        nonce,
+        next_index = current.next_index,
+        root_age = root_age(nonce, current.next_index),
+        root_history_depth,
+        "proposal root is too old, re-creating it with the current root",
+    );
+    let refreshed = match target_resource_id.target_system() {
+        TargetSystem::ContractAddress(_) => QueuedAnchorUpdateProposal::new(
+            proposal_handler::evm_anchor_update_proposal(
+                current.merkle_root,
+                leaf_index,
+                target_resource_id,
+                src_resource_id,
+            ),
+        ),
+        TargetSystem::Substrate(_) => QueuedAnchorUpdateProposal::new(
+            proposal_handler::substrate_anchor_update_proposal(
+                current.merkle_root,
+                leaf_index,
+                target_resource_id,
+                src_resource_id,
+            ),
+        ),
+    };
+    root_source
+        .on_proposal_refreshed(&proposal, &refreshed)
+        .await?;
+    Ok(refreshed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_utils::*;
+    use super::*;
+    use parking_lot::Mutex;
+    use webb::evm::ethers;
+
+    struct MockRootSource {
+        current: CurrentRoot,
+        refreshed: Mutex<Vec<([u8; 32], [u8; 32])>>,
+    }
+
+    impl MockRootSource {
+        fn new(next_index: u32) -> Self {
+            Self {
+                current: CurrentRoot {
+                    merkle_root: [0x99; 32],
+                    next_index,
+                },
+                refreshed: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AnchorRootSource for MockRootSource {
+        async fn current_root(
+            &self,
+        ) -> webb_relayer_utils::Result<CurrentRoot> {
+            Ok(self.current)
+        }
+
+        async fn on_proposal_refreshed(
+            &self,
+            old_proposal: &QueuedAnchorUpdateProposal,
+            new_proposal: &QueuedAnchorUpdateProposal,
+        ) -> webb_relayer_utils::Result<()> {
+            self.refreshed.lock().push((
+                super::super::ProposalHash::full_hash(old_proposal),
+                super::super::ProposalHash::full_hash(new_proposal),
+            ));
+            Ok(())
+        }
+    }
+
+    fn proposal(nonce: u32) -> QueuedAnchorUpdateProposal {
+        let target_system = mock_target_system(ethers::types::Address::zero());
+        let target = mock_resourc_id(target_system, mock_typed_chain_id(1));
+        let src_system =
+            mock_target_system(ethers::types::Address::repeat_byte(0x11));
+        let src = mock_resourc_id(src_system, mock_typed_chain_id(2));
+        QueuedAnchorUpdateProposal::new(
+            proposal_handler::evm_anchor_update_proposal(
+                [0x42; 32], nonce, target, src,
+            ),
+        )
+    }
+
+    #[test]
+    fn root_age_should_count_transacts() {
+        // the root of the last transact.
+        assert_eq!(root_age(9, 10), 0);
+        assert_eq!(root_age(7, 10), 1);
+        assert_eq!(root_age(1, 10), 4);
+        // the anchor did not catch up yet.
+        assert_eq!(root_age(11, 10), 0);
+        assert!(!is_root_stale(1, 10, 5));
+        assert!(is_root_stale(1, 12, 5));
+    }
+
+    #[tokio::test]
+    async fn recent_root_should_be_kept() {
+        let source = MockRootSource::new(8);
+        let p = proposal(5);
+        let refreshed =
+            refresh_stale_root(p.clone(), &source, 30).await.unwrap();
+        assert_eq!(refreshed.to_vec(), p.to_vec());
+        assert!(source.refreshed.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stale_root_should_be_refetched() {
+        // 3 transacts happened since, with a root history of 2.
+        let source = MockRootSource::new(12);
+        let p = proposal(5);
+        let refreshed =
+            refresh_stale_root(p.clone(), &source, 2).await.unwrap();
+        let header = refreshed.header();
+        assert_eq!(header.nonce().to_u32(), 11);
+        assert!(header.resource_id() == p.header().resource_id());
+        assert!(header.function_signature() == p.header().function_signature());
+        let bytes = refreshed.to_vec();
+        // header, then the new root, then the same source resource id.
+        assert_eq!(&bytes[40..72], &[0x99; 32]);
+        assert_eq!(&bytes[72..], &p.to_vec()[72..]);
+        assert_eq!(
+            *source.refreshed.lock(),
+            vec![(
+                super::super::ProposalHash::full_hash(&p),
+                super::super::ProposalHash::full_hash(&refreshed)
+            )]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use ethereum_types::Address;
+use serde::{de::Error, Deserialize, Deserializer};
+use webb::evm::ethers::utils::to_checksum;
+
+/// Parses an address, given as `0x` followed by 40 hex digits.
+///
+/// An address in mixed case is checked against its
+/// [EIP-55](https://eips.ethereum.org/EIPS/eip-55) checksum, an address all
+/// in lower or upper case has none.
+pub fn parse(value: &str) -> Result<Address, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .filter(|digits| {
+            digits.len() == 40 && digits.chars().all(|c| c.is_ascii_hexdigit())
+        })
+        .ok_or_else(|| {
+            format!(
+                "invalid address `{value}`, expected 0x followed by 40 hex \
+                digits"
+            )
+        })?;
+    let address = digits
+        .parse::<Address>()
+        .map_err(|e| format!("invalid address `{value}`: {e}"))?;
+    let mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case {
+        let checksummed = to_checksum(&address, None);
+        if checksummed != value {
+            return Err(format!(
+                "invalid address `{value}`, the checksum does not match, \
+                expected `{checksummed}`"
+            ));
+        }
+    }
+    Ok(address)
+}
+
+/// Deserializes an address, see [`parse`].
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Address, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse(&value).map_err(D::Error::custom)
+}
+
+/// Deserializes an optional address, see [`parse`].
+pub fn deserialize_option<'de, D>(
+    deserializer: D,
+) -> Result<Option<Address>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse(&value).map_err(D::Error::custom))
+        .transpose()
+}
+
+/// Deserializes an optional list of addresses, see [`parse`].
+pub fn deserialize_option_vec<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<Address>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| parse(value).map_err(D::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+/// Deserializes a map keyed by addresses, see [`parse`].
+pub fn deserialize_keys<'de, D, V>(
+    deserializer: D,
+) -> Result<HashMap<Address, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    HashMap::<String, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let address = parse(&key).map_err(D::Error::custom)?;
+            Ok((address, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_check_the_checksum_of_mixed_case_addresses() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let address = parse(checksummed).unwrap();
+        assert_eq!(parse(&checksummed.to_lowercase()).unwrap(), address);
+        assert_eq!(
+            parse(&format!("0x{}", checksummed[2..].to_uppercase())).unwrap(),
+            address
+        );
+
+        let error =
+            parse("0x5aaeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap_err();
+        assert!(error.contains(checksummed), "{error}");
+    }
+
+    #[test]
+    fn should_reject_malformed_addresses() {
+        for value in [
+            "5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAedff",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeg",
+        ] {
+            assert!(parse(value).is_err(), "{value}");
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use ethereum_types::H256;
+use std::collections::HashSet;
+use webb_proposals::ResourceId;
 
 use crate::{
     evm::EvmLinkedAnchorConfig, substrate::SubstrateLinkedAnchorConfig,
@@ -71,4 +73,48 @@ impl LinkedAnchorConfig {
             _ => self,
         }
     }
+    /// The resource id of the linked anchor.
+    pub fn resource_id(&self) -> ResourceId {
+        match self.clone().into_raw_resource_id() {
+            LinkedAnchorConfig::Raw(raw) => {
+                let bytes: [u8; 32] = raw.resource_id.into();
+                ResourceId::from(bytes)
+            }
+            _ => unreachable!("converted to a raw resource id"),
+        }
+    }
+}
+
+/// Resolves the linked anchors of the `src` anchor to their resource ids.
+///
+/// Linked anchors on the same chain as `src`, and the ones listed more than
+/// once, are skipped with a warning, since the bridge rejects anchor updates
+/// from a chain to itself.
+pub fn resolve_linked_anchors(
+    src: ResourceId,
+    linked_anchors: impl IntoIterator<Item = LinkedAnchorConfig>,
+) -> Vec<ResourceId> {
+    let mut seen = HashSet::new();
+    linked_anchors
+        .into_iter()
+        .map(|linked_anchor| linked_anchor.resource_id())
+        .filter(|target| {
+            let reason = if target.typed_chain_id() == src.typed_chain_id() {
+                "same_chain"
+            } else if !seen.insert(target.to_bytes()) {
+                "duplicate"
+            } else {
+                return true;
+            };
+            tracing::event!(
+                target: webb_relayer_utils::probe::TARGET,
+                tracing::Level::WARN,
+                kind = %webb_relayer_utils::probe::Kind::DepositPropagation,
+                src = ?src,
+                target = ?target,
+                skipped = reason,
+            );
+            false
+        })
+        .collect()
 }
@@ -0,0 +1,272 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use webb_relayer_utils::probe;
+
+use crate::AuditLogConfig;
+
+/// The probe kinds written to the audit log: the signed and executed
+/// proposals, and the relayed transactions.
+pub const AUDITED_KINDS: [probe::Kind; 5] = [
+    probe::Kind::SigningBackend,
+    probe::Kind::SignatureBridge,
+    probe::Kind::ProposalExecuted,
+    probe::Kind::TxQueue,
+    probe::Kind::PrivateTx,
+];
+
+/// Returns true if the probe events of this kind go to the audit log.
+fn is_audited(kind: &str) -> bool {
+    AUDITED_KINDS.iter().any(|k| k.to_string() == kind)
+}
+
+/// A [`Layer`] that appends the audited [`probe`] events to a file, as
+/// newline-delimited JSON.
+///
+/// It writes nothing until a file is opened with [`AuditLog::open`], since
+/// the logger is set up before the config is loaded.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    file: Arc<Mutex<Option<RotatingFile>>>,
+}
+
+impl AuditLog {
+    /// Opens the audit log file, appending to it if it already exists.
+    pub fn open(&self, config: &AuditLogConfig) -> io::Result<()> {
+        let file = RotatingFile::open(config)?;
+        *self.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+        Ok(())
+    }
+
+    fn write(&self, record: &Value) {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(file) = guard.as_mut() else {
+            return;
+        };
+        let mut line = record.to_string();
+        line.push('\n');
+        // logging the failure would come back to this layer.
+        if let Err(e) = file.write_line(line.as_bytes()) {
+            eprintln!(
+                "failed to write to the audit log {}: {e}",
+                file.path.display()
+            );
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for AuditLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() != probe::TARGET {
+            return;
+        }
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let mut record = visitor.0;
+        match record.get("kind").and_then(Value::as_str) {
+            Some(kind) if is_audited(kind) => {}
+            _ => return,
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        record.insert("timestamp".into(), timestamp.into());
+        record.insert("level".into(), metadata.level().as_str().into());
+        self.write(&Value::Object(record));
+    }
+}
+
+/// Collects the fields of an event into a JSON object.
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
+
+/// An append-only file, rotated once it grows past a given size.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_file_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(config: &AuditLogConfig) -> io::Result<Self> {
+        if let Some(parent) = config.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = Self::append(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: config.path.clone(),
+            file,
+            size,
+            max_file_size: config.max_file_size,
+            max_files: config.max_files,
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.max_file_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Shifts `<path>.N` to `<path>.N+1`, dropping the oldest file, then
+    /// moves the current file to `<path>.1` and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = Self::append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// The path of the `n`th rotated file, `<path>.<n>`.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{n}"));
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn should_append_audited_probe_events_and_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let audit_log = AuditLog::default();
+        let subscriber = tracing_subscriber::registry().with(audit_log.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            // nothing is written before the file is opened.
+            tracing::debug!(
+                target: probe::TARGET,
+                kind = %probe::Kind::TxQueue,
+                "early"
+            );
+            audit_log
+                .open(&AuditLogConfig {
+                    path: path.clone(),
+                    max_file_size: 512,
+                    max_files: 2,
+                })
+                .unwrap();
+            tracing::debug!(
+                target: probe::TARGET,
+                kind = %probe::Kind::TxQueue,
+                ty = "EVM",
+                chain_id = 5u64,
+                finalized = true,
+            );
+            // not audited, either by kind or by target.
+            tracing::debug!(
+                target: probe::TARGET,
+                kind = %probe::Kind::Sync,
+                block = 1u64,
+            );
+            tracing::info!(kind = "tx_queue", "console only");
+        });
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["kind"], "tx_queue");
+        assert_eq!(lines[0]["chain_id"], 5);
+        assert_eq!(lines[0]["finalized"], true);
+        assert_eq!(lines[0]["level"], "DEBUG");
+        assert!(lines[0]["timestamp"].is_u64());
+
+        let subscriber = tracing_subscriber::registry().with(audit_log);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..20u64 {
+                tracing::debug!(
+                    target: probe::TARGET,
+                    kind = %probe::Kind::PrivateTx,
+                    nonce = i,
+                );
+            }
+        });
+        assert!(fs::metadata(&path).unwrap().len() <= 512);
+        assert!(rotated_path(&path, 1).exists());
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+    }
+}
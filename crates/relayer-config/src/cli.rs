@@ -1,8 +1,26 @@
-use crate::WebbRelayerConfig;
+use crate::audit_log::AuditLog;
+use crate::log_filter::ChainLogFilter;
+use crate::{AuditLogConfig, WebbRelayerConfig};
 use anyhow::Context;
 use directories_next::ProjectDirs;
+use ethereum_types::{Address, H256};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use structopt::StructOpt;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{reload, Registry};
+
+/// A handle to reload the per chain log filter, see [`apply_log_filter_by_chain`].
+pub type ChainLogFilterHandle = reload::Handle<ChainLogFilter, Registry>;
+
+/// The parts of the logger that are configured once the config is loaded.
+#[derive(Clone)]
+pub struct LoggerHandle {
+    /// See [`apply_log_filter_by_chain`].
+    pub chain_filter: ChainLogFilterHandle,
+    /// See [`apply_audit_log`].
+    pub audit_log: AuditLog,
+}
 
 /// Package identifier, where the default configuration & database are defined.
 /// If the user does not start the relayer with the `--config-dir`
@@ -15,8 +33,15 @@ pub const PACKAGE_ID: [&str; 3] = ["tools", "webb", "webb-relayer"];
 ///
 /// $ webb-relayer -vvv -c <CONFIG_FILE_PATH>
 #[derive(StructOpt)]
-#[structopt(name = "Webb Relayer")]
+#[structopt(
+    name = "Webb Relayer",
+    global_settings = &[structopt::clap::AppSettings::DisableVersion]
+)]
 pub struct Opts {
+    /// Prints the version, the git commit and the build date of the
+    /// relayer, then exits.
+    #[structopt(short = "V", long)]
+    pub version: bool,
     /// A level of verbosity, and can be used multiple times
     #[allow(dead_code)]
     #[structopt(short, long, parse(from_occurrences))]
@@ -33,6 +58,143 @@ pub struct Opts {
     /// and will be deleted when the process exits.
     #[structopt(long)]
     pub tmp: bool,
+    /// Number of worker threads used by the async runtime.
+    ///
+    /// Defaults to the number of CPU cores. Every enabled chain spawns its own
+    /// set of event watchers and transaction queues as tasks on this runtime,
+    /// so relayers serving many high-throughput chains may want to raise it.
+    #[structopt(long = "worker-threads", value_name = "N")]
+    pub worker_threads: Option<usize>,
+    /// An admin command to run instead of starting the relayer.
+    #[structopt(subcommand)]
+    pub command: Option<AdminCommand>,
+}
+
+/// Admin commands, used to inspect or debug the relayer.
+///
+/// They run against the same configuration and store as the relayer, so the
+/// relayer should be stopped first, since the store can only be opened once.
+#[derive(Debug, StructOpt)]
+pub enum AdminCommand {
+    /// Re-handle a single EVM event, as the events watcher would.
+    ///
+    /// The event is fetched from the chain, decoded and handed once to every
+    /// handler of the contract that emitted it, then the outcome of each
+    /// handler is printed. The block checkpoint of the watcher is left as is.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> replay-event --chain goerli --tx-hash 0x... --log-index 3
+    ReplayEvent {
+        /// The name or the chain id of the EVM chain.
+        #[structopt(long)]
+        chain: String,
+        /// The hash of the transaction that emitted the event.
+        #[structopt(long = "tx-hash")]
+        tx_hash: H256,
+        /// The index of the log in its block, as shown by block explorers.
+        #[structopt(long = "log-index")]
+        log_index: u64,
+    },
+    /// Import a signed leaf snapshot into the leaf cache.
+    ///
+    /// Snapshots are served by other relayers at
+    /// `/api/v1/snapshot/<chain_id>/<contract>`, and let a new relayer skip
+    /// syncing the leaves from the deployment block.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> import-snapshot ./snapshot.bin --verify-signer 0x... --verify-root-onchain
+    ImportSnapshot {
+        /// The file holding the leaf snapshot.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Only import the snapshot if it is signed by this relayer address.
+        #[structopt(long = "verify-signer", value_name = "ADDRESS")]
+        verify_signer: Option<Address>,
+        /// Only import the snapshot if its Merkle root is known by the
+        /// contract on chain.
+        #[structopt(long = "verify-root-onchain")]
+        verify_root_onchain: bool,
+    },
+    /// Sign and submit the anchor update proposal of a linked anchor, at
+    /// the current root of the source anchor.
+    ///
+    /// Recovers an update the relayer missed without waiting for a new
+    /// deposit. The proposal skips the proposals queue, so it is signed
+    /// right away, even if the linked anchor already has the root.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> force-anchor-update --src-chain goerli --src-anchor 0x... --dest-chain sepolia
+    ForceAnchorUpdate {
+        /// The name or the chain id of the EVM chain of the source anchor.
+        #[structopt(long = "src-chain")]
+        src_chain: String,
+        /// The address of the source anchor.
+        #[structopt(long = "src-anchor")]
+        src_anchor: Address,
+        /// The name or the chain id of the chain of the linked anchor.
+        #[structopt(long = "dest-chain")]
+        dest_chain: String,
+    },
+    /// Dump the whole leaf cache into a file.
+    ///
+    /// The dump keeps the chain and the contract of every leaf, along with
+    /// the blocks they got inserted in, and is loaded with `import-cache`.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> export-cache --out ./leaves.bin
+    ExportCache {
+        /// The file to write the dump to.
+        #[structopt(long = "out", parse(from_os_str))]
+        out: PathBuf,
+    },
+    /// Load a dump made by `export-cache` into the leaf cache.
+    ///
+    /// The dump is checked for corruption, and its leaves have to be
+    /// contiguous and match the leaves already cached, before any of it
+    /// gets imported.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> import-cache --in ./leaves.bin
+    ImportCache {
+        /// The file holding the dump.
+        #[structopt(long = "in", parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Write every proposal the relayer recorded into a JSON file, along
+    /// with the states it went through: created, voted, signed, executed...
+    ///
+    /// Only the last 10000 proposal events are kept in the store, the
+    /// states of the oldest proposals may be missing.
+    ///
+    /// $ webb-relayer -c <CONFIG_FILE_PATH> export-proposals --out ./proposals.json
+    ExportProposals {
+        /// The file to write the proposals to.
+        #[structopt(long = "out", parse(from_os_str))]
+        out: PathBuf,
+    },
+}
+
+/// Returns the directory the configuration is loaded from, the given one or
+/// the default config directory of the relayer.
+///
+/// Returns an error if it is not a directory.
+///
+/// # Arguments
+///
+/// * `config_dir` - An optional `PathBuf` representing the directory that contains the configuration.
+pub fn resolve_config_dir<P>(
+    config_dir: Option<P>,
+) -> Result<PathBuf, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    tracing::debug!("Getting default dirs for webb relayer");
+    let dirs = ProjectDirs::from(PACKAGE_ID[0], PACKAGE_ID[1], PACKAGE_ID[2])
+        .context("failed to get config")?;
+    let path = match config_dir {
+        Some(p) => p.as_ref().to_path_buf(),
+        None => dirs.config_dir().to_path_buf(),
+    };
+    // return an error if the path is not a directory.
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("{} is not a directory", path.display()));
+    }
+    Ok(path)
 }
 
 /// Loads the configuration from the given directory.
@@ -58,17 +220,7 @@ pub fn load_config<P>(
 where
     P: AsRef<Path>,
 {
-    tracing::debug!("Getting default dirs for webb relayer");
-    let dirs = ProjectDirs::from(PACKAGE_ID[0], PACKAGE_ID[1], PACKAGE_ID[2])
-        .context("failed to get config")?;
-    let path = match config_dir {
-        Some(p) => p.as_ref().to_path_buf(),
-        None => dirs.config_dir().to_path_buf(),
-    };
-    // return an error if the path is not a directory.
-    if !path.is_dir() {
-        return Err(anyhow::anyhow!("{} is not a directory", path.display()));
-    }
+    let path = resolve_config_dir(config_dir)?;
     tracing::trace!("Loading Config from {} ..", path.display());
     let v = crate::utils::load(path)?;
     tracing::trace!("Config loaded..");
@@ -77,14 +229,21 @@ where
 
 /// Sets up the logger for the relayer, based on the verbosity level passed in.
 ///
-/// Returns `Ok(())` on success, or `Err(anyhow::Error)` on failure.
+/// Returns `Ok(LoggerHandle)` on success, which is used to apply the per chain
+/// log filter and the audit log once the config is loaded, or `Err(anyhow::Error)` on failure.
 ///
 /// # Arguments
 ///
 /// * `verbosity` - An i32 integer representing the verbosity level.
 /// * `filter` -  An &str representing filtering directive for EnvFilter
-pub fn setup_logger(verbosity: i32, filter: &str) -> anyhow::Result<()> {
+pub fn setup_logger(
+    verbosity: i32,
+    filter: &str,
+) -> anyhow::Result<LoggerHandle> {
     use tracing::Level;
+    use tracing_subscriber::filter::{filter_fn, FilterExt};
+    use tracing_subscriber::layer::{Layer, SubscriberExt};
+    use tracing_subscriber::util::SubscriberInitExt;
     let log_level = match verbosity {
         0 => Level::ERROR,
         1 => Level::WARN,
@@ -101,10 +260,10 @@ pub fn setup_logger(verbosity: i32, filter: &str) -> anyhow::Result<()> {
     let env_filter = tracing_subscriber::EnvFilter::from_default_env()
         .add_directive(directive_1)
         .add_directive(directive_2);
-    let logger = tracing_subscriber::fmt()
-        .with_target(true)
-        .with_max_level(log_level)
-        .with_env_filter(env_filter);
+    // the per chain filter is only known once the config is loaded,
+    // so we start with an empty one and reload it later.
+    let (chain_filter, handle) = reload::Layer::new(ChainLogFilter::default());
+    let logger = tracing_subscriber::fmt::layer().with_target(true);
     // if we are not compiling for integration tests, we should use pretty logs
     #[cfg(not(feature = "integration-tests"))]
     let logger = logger.pretty();
@@ -112,7 +271,63 @@ pub fn setup_logger(verbosity: i32, filter: &str) -> anyhow::Result<()> {
     #[cfg(feature = "integration-tests")]
     let logger = logger.json().flatten_event(true).with_current_span(false);
 
-    logger.init();
+    // the verbosity only applies to the console, the audit log gets every
+    // probe event whatever the level.
+    let console_filter =
+        FilterExt::and(LevelFilter::from_level(log_level), env_filter);
+    let audit_log = AuditLog::default();
+    let audit_filter = filter_fn(|metadata| {
+        metadata.target() == webb_relayer_utils::probe::TARGET
+    });
+
+    tracing_subscriber::registry()
+        .with(chain_filter)
+        .with(logger.with_filter(console_filter))
+        .with(audit_log.clone().with_filter(audit_filter))
+        .init();
+    Ok(LoggerHandle {
+        chain_filter: handle,
+        audit_log,
+    })
+}
+
+/// Applies the `log-filter-by-chain` configuration to the logger
+/// setup by [`setup_logger`].
+///
+/// # Arguments
+///
+/// * `handle` - The handle returned by [`setup_logger`].
+/// * `directives` - A map between chain names and their max log level.
+pub fn apply_log_filter_by_chain(
+    handle: &LoggerHandle,
+    directives: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let filter = ChainLogFilter::new(directives)?;
+    handle
+        .chain_filter
+        .reload(filter)
+        .context("failed to apply the log filter by chain")?;
+    Ok(())
+}
+
+/// Starts writing the audit log configured by `audit-log`, if any, to the
+/// logger setup by [`setup_logger`].
+///
+/// # Arguments
+///
+/// * `handle` - The handle returned by [`setup_logger`].
+/// * `config` - The audit log configuration.
+pub fn apply_audit_log(
+    handle: &LoggerHandle,
+    config: Option<&AuditLogConfig>,
+) -> anyhow::Result<()> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+    handle.audit_log.open(config).with_context(|| {
+        format!("failed to open the audit log {}", config.path.display())
+    })?;
+    tracing::info!(path = %config.path.display(), "Audit log enabled");
     Ok(())
 }
 
@@ -146,3 +361,20 @@ pub async fn create_store(
     let store = webb_relayer_store::SledStore::open(db_path)?;
     Ok(store)
 }
+
+/// Returns the number of worker threads the async runtime should be started with.
+///
+/// Returns `Ok(None)` when the runtime default (number of CPU cores) should be used,
+/// or `Err(anyhow::Error)` if an invalid value was passed in.
+///
+/// # Arguments
+///
+/// * `opts` - The command line options passed to the relayer.
+pub fn runtime_worker_threads(opts: &Opts) -> anyhow::Result<Option<usize>> {
+    match opts.worker_threads {
+        Some(0) => Err(anyhow::anyhow!(
+            "--worker-threads must be greater than zero"
+        )),
+        v => Ok(v),
+    }
+}
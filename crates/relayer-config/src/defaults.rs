@@ -4,6 +4,14 @@ use std::collections::HashMap;
 pub const fn relayer_port() -> u16 {
     9955
 }
+/// The default host the relayer will listen on, all the interfaces.
+pub fn relayer_host() -> String {
+    String::from("0.0.0.0")
+}
+/// The admin endpoints are only served on localhost by default.
+pub fn admin_listener_host() -> String {
+    String::from("127.0.0.1")
+}
 /// Leaves watcher is set to `true` by default.
 pub const fn enable_leaves_watcher() -> bool {
     true
@@ -12,6 +20,10 @@ pub const fn enable_leaves_watcher() -> bool {
 pub const fn enable_data_query() -> bool {
     true
 }
+/// Withdrawals query access is set to `true` by default.
+pub const fn enable_withdrawals_query() -> bool {
+    true
+}
 /// The maximum events per step is set to `100` by default.
 pub const fn max_blocks_per_step() -> u64 {
     500
@@ -21,6 +33,175 @@ pub const fn print_progress_interval() -> u64 {
     7_000
 }
 
+/// Up to `1024` leaves are buffered after a missing leaf by default.
+pub const fn max_pending_leaves() -> u32 {
+    webb_relayer_store::DEFAULT_MAX_PENDING_LEAVES
+}
+
+/// The event watchers are restarted after `5` minutes without progress by default.
+pub const fn watchdog_timeout() -> u64 {
+    300_000
+}
+
+/// The logs are queried `1` block behind the chain head by default.
+pub const fn safety_lag() -> u64 {
+    1
+}
+
+/// A leaf needs `12` confirmations to be considered final by default.
+pub const fn leaf_final_confirmations() -> u64 {
+    12
+}
+
+/// The root history depth is set to `30` by default, same as the anchor contracts.
+pub const fn root_history_depth() -> u32 {
+    30
+}
+
+/// Linked anchors that already have a root are not updated again by default.
+pub const fn skip_if_already_updated() -> bool {
+    true
+}
+
+/// Leaf snapshots are regenerated at most every `10` minutes by default.
+pub const fn leaf_snapshot_interval() -> u64 {
+    10
+}
+
+/// Up to `512` HTTP requests are handled at the same time by default.
+pub const fn max_concurrent_requests() -> usize {
+    512
+}
+
+/// Up to `16` commands wait for each bridge watcher by default.
+pub const fn bridge_command_buffer() -> usize {
+    16
+}
+
+/// Up to `10` proposals are in flight on each chain by default.
+pub const fn max_concurrent_proposals_per_chain() -> u8 {
+    10
+}
+
+/// The proposals hold their permit for `300` seconds at most by default.
+pub const fn proposal_permit_timeout_secs() -> u64 {
+    300
+}
+
+/// Websocket connections are pinged every `30` seconds by default.
+pub const fn ws_ping_interval_secs() -> u64 {
+    30
+}
+
+/// Websocket messages are limited to `64` KiB by default.
+pub const fn ws_max_message_size_bytes() -> usize {
+    64 * 1024
+}
+
+/// Up to `50` responses wait to be sent to a websocket client by default.
+pub const fn ws_send_queue_size() -> usize {
+    50
+}
+
+/// The info responses may be cached for `10` seconds by default.
+pub const fn info_max_age_secs() -> u64 {
+    10
+}
+
+/// The leaves responses may be cached for `2` seconds by default.
+pub const fn leaves_max_age_secs() -> u64 {
+    2
+}
+
+/// The leaves are lagging once their watcher did not sync for `60` seconds
+/// by default.
+pub const fn leaves_lagging_after_secs() -> u64 {
+    60
+}
+
+/// The leaves are stale once their watcher did not sync for `600` seconds by
+/// default.
+pub const fn leaves_stale_after_secs() -> u64 {
+    600
+}
+
+/// The leaves are lagging once their watcher is `100` blocks behind the head
+/// of the chain by default.
+pub const fn leaves_lagging_after_blocks() -> u64 {
+    100
+}
+
+/// Up to `10` proposal executions are batched in a multicall by default.
+pub const fn multicall_max_batch_size() -> usize {
+    10
+}
+
+/// The gas price is checked every `60` seconds by default, while it is too
+/// high to send transactions.
+pub const fn gas_price_check_interval_secs() -> u64 {
+    60
+}
+
+/// The providers poll every `1000` milliseconds by default, when the block
+/// time of the chain is not configured.
+pub const fn provider_interval_ms() -> u64 {
+    1000
+}
+
+/// The providers poll at least every `250` milliseconds, whatever the block
+/// time of the chain.
+pub const fn min_provider_interval_ms() -> u64 {
+    250
+}
+
+/// The relayer account of every Substrate node is checked every `60`
+/// seconds by default.
+pub const fn account_check_interval_secs() -> u64 {
+    60
+}
+
+/// A batch of extrinsics holds up to `10` withdrawals by default.
+pub const fn max_batch_size() -> usize {
+    10
+}
+
+/// A batch of extrinsics is sent at most `6` seconds after its first
+/// withdrawal by default, about a block.
+pub const fn batch_flush_timeout_ms() -> u64 {
+    6_000
+}
+
+/// The audit log is rotated once it reaches `100` MiB by default.
+pub const fn audit_log_max_file_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+/// Up to `10` rotated audit log files are kept by default.
+pub const fn audit_log_max_files() -> usize {
+    10
+}
+
+/// The reverts worth retrying by default, the ones of a block too full.
+pub fn transient_revert_reasons() -> Vec<String> {
+    vec![String::from("block gas limit")]
+}
+
+/// A reverted transaction is retried up to `3` times by default.
+pub const fn max_revert_retries() -> u32 {
+    3
+}
+
+/// A reverted transaction is retried after `5` seconds by default, doubled
+/// on each retry after it.
+pub const fn revert_retry_delay_secs() -> u64 {
+    5
+}
+
+/// A metric has up to `1000` label sets by default.
+pub fn max_metric_label_sets() -> usize {
+    webb_relayer_utils::metric::CardinalityPolicy::default().max_label_sets
+}
+
 /// The default unlisted assets.
 pub fn unlisted_assets() -> HashMap<String, crate::UnlistedAssetConfig> {
     HashMap::from_iter([
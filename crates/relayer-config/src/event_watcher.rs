@@ -1,7 +1,7 @@
 use super::*;
 
 /// EventsWatchConfig is the configuration for the events watch.
-#[derive(Debug, Clone, Serialize, Deserialize, Default, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
 pub struct EventsWatcherConfig {
     /// A flag for enabling API endpoints for querying data from the relayer.
@@ -22,4 +22,65 @@ pub struct EventsWatcherConfig {
     /// Sync blocks from
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sync_blocks_from: Option<u64>,
+    /// Staggers the startup of the event watchers of a chain, to avoid
+    /// hitting the RPC provider with all their historical queries at once.
+    ///
+    /// The n-th watcher of the chain waits `n * delay` seconds before it
+    /// starts, the first one starts right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_watcher_start_delay_secs: Option<f64>,
+    /// The maximum number of leaves that are buffered after a missing leaf,
+    /// waiting for it to show up.
+    ///
+    /// Beyond it, the gap is reported, and the missing leaves are fetched
+    /// again from the contract. It cannot be zero.
+    #[serde(default = "defaults::max_pending_leaves")]
+    pub max_pending_leaves: u32,
+    /// How long, in milliseconds, the watcher may go without making any
+    /// progress before it gets restarted, dropping its in-flight requests.
+    ///
+    /// It has to be longer than the polling interval.
+    /// If it is zero, the watcher is never restarted.
+    #[serde(default = "defaults::watchdog_timeout")]
+    pub watchdog_timeout: u64,
+    /// How many blocks behind the chain head the logs are queried.
+    ///
+    /// Behind a load balancer, the replica answering the logs query may
+    /// not have the last blocks of the replica that gave the chain head.
+    #[serde(default = "defaults::safety_lag")]
+    pub safety_lag: u64,
+    /// The types of the events the watcher handles, named after the
+    /// events of the contract ABI, such as `NewCommitment`.
+    ///
+    /// The events of the other types are skipped, with a warning.
+    /// If it is not set, all the events are handled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type_allowlist: Option<Vec<String>>,
+    /// How the data of the contract is kept up to date.
+    ///
+    /// default to `watch`
+    #[serde(default)]
+    pub sync: EventsSyncMode,
+}
+
+/// EventsSyncMode is how the data cached for a contract is kept up to date.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EventsSyncMode {
+    /// The events of the contract are watched.
+    #[default]
+    Watch,
+    /// The contract is not watched, its data is served as found in the
+    /// store, such as after importing a snapshot. Relaying to it is refused.
+    Static,
+}
+
+impl EventsWatcherConfig {
+    /// Returns true if the contract is not watched, and its data does not
+    /// advance.
+    pub fn is_static(&self) -> bool {
+        self.sync == EventsSyncMode::Static
+    }
 }
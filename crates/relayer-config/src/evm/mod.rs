@@ -1,8 +1,13 @@
 use core::fmt;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
 use url::Url;
-use webb_relayer_types::{private_key::PrivateKey, rpc_url::RpcUrl};
+use webb_relayer_types::{
+    amount::Amount, private_key::PrivateKey, rpc_url::RpcUrl,
+};
 
 use crate::{
     anchor::LinkedAnchorConfig, block_poller::BlockPollerConfig,
@@ -24,12 +29,22 @@ pub struct EvmChainConfig {
     /// Http(s) Endpoint for quick Req/Res
     #[serde(skip_serializing)]
     pub http_endpoint: HttpEndpoint,
+    /// The labels of the http endpoints, in the same order, used to
+    /// attribute the calls to each RPC vendor.
+    ///
+    /// default to the host of each endpoint.
+    #[serde(skip_serializing, default)]
+    pub provider_labels: Vec<String>,
     /// Websocket Endpoint for long living connections
     #[serde(skip_serializing)]
     pub ws_endpoint: RpcUrl,
     /// Block confirmations
     #[serde(skip_serializing, default)]
     pub block_confirmations: u8,
+    /// The number of confirmations after which a cached leaf is considered
+    /// final, and could no longer be reorged away.
+    #[serde(skip_serializing, default = "defaults::leaf_final_confirmations")]
+    pub leaf_final_confirmations: u64,
     /// Block Explorer for this chain.
     ///
     /// Optional, and only used for printing a clickable links
@@ -63,17 +78,136 @@ pub struct EvmChainConfig {
     #[serde(skip_serializing)]
     pub private_key: Option<PrivateKey>,
     /// Optionally, a user can specify an account to receive rewards for relaying
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "crate::address::deserialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub beneficiary: Option<Address>,
     /// Supported contracts over this chain.
     #[serde(default)]
     pub contracts: Vec<Contract>,
+    /// The addresses of the contracts the relayer operates on, out of the
+    /// supported ones. The others are neither watched nor relayed to.
+    ///
+    /// default to None, all the supported contracts are enabled.
+    #[serde(
+        skip_serializing,
+        default,
+        deserialize_with = "crate::address::deserialize_option_vec"
+    )]
+    pub enabled_contracts: Option<Vec<Address>>,
     /// TxQueue configuration
     #[serde(skip_serializing, default)]
     pub tx_queue: TxQueueConfig,
+    /// The highest gas price, in Gwei, the transactions are sent at. They
+    /// are held in the queue while the gas price is higher.
+    ///
+    /// default to None, no limit.
+    #[serde(skip_serializing, default)]
+    pub max_gas_price_gwei: Option<f64>,
+    /// How often, in seconds, the gas price is checked again while it is
+    /// above [`max_gas_price_gwei`](Self::max_gas_price_gwei).
+    ///
+    /// default to 60 seconds
+    #[serde(
+        skip_serializing,
+        default = "defaults::gas_price_check_interval_secs"
+    )]
+    pub gas_price_check_interval_secs: u64,
+    /// The average time, in milliseconds, between two blocks of the chain.
+    ///
+    /// default to None, unknown.
+    #[serde(skip_serializing, default)]
+    pub block_time_ms: Option<u64>,
+    /// How often, in milliseconds, the providers of the chain poll for new
+    /// data, like the receipts of the pending transactions.
+    ///
+    /// default to a quarter of [`block_time_ms`](Self::block_time_ms), or
+    /// to 1 second if it is not set either.
+    #[serde(skip_serializing, default)]
+    pub provider_interval_ms: Option<u64>,
+    /// Batches the executions of proposals that are ready at the same time
+    /// in a single transaction, through a Multicall contract.
+    #[serde(skip_serializing, default)]
+    pub multicall: Option<MulticallConfig>,
+    /// The fee collector contract of the relayer, the spender of the fee
+    /// permits of the VAnchor withdrawals.
+    ///
+    /// Only the relayer can call it, so the permits it is given cannot be
+    /// used by anyone else.
+    #[serde(
+        skip_serializing,
+        default,
+        deserialize_with = "crate::address::deserialize_option"
+    )]
+    pub fee_collector: Option<Address>,
     /// Block poller/listening configuration
     #[serde(skip_serializing, default)]
     pub block_poller: Option<BlockPollerConfig>,
+    /// Custom ABI files (JSON) of contracts that are forks of the Webb
+    /// contracts, by contract address.
+    ///
+    /// The custom ABI has to be a superset of the original one.
+    #[serde(
+        skip_serializing,
+        default,
+        deserialize_with = "crate::address::deserialize_keys"
+    )]
+    pub contract_abi_override: HashMap<Address, PathBuf>,
+}
+
+impl EvmChainConfig {
+    /// Returns true if the relayer operates on the contract at `address`,
+    /// see [`enabled_contracts`](Self::enabled_contracts).
+    pub fn is_contract_enabled(&self, address: Address) -> bool {
+        self.enabled_contracts
+            .as_ref()
+            .map_or(true, |enabled| enabled.contains(&address))
+    }
+
+    /// Returns how often the providers of the chain poll for new data, see
+    /// [`provider_interval_ms`](Self::provider_interval_ms).
+    pub fn provider_interval(&self) -> Duration {
+        let ms = match (self.provider_interval_ms, self.block_time_ms) {
+            (Some(interval), _) => interval,
+            (None, Some(block_time)) => {
+                (block_time / 4).max(defaults::min_provider_interval_ms())
+            }
+            (None, None) => defaults::provider_interval_ms(),
+        };
+        Duration::from_millis(ms)
+    }
+
+    /// Returns the label of each http endpoint, in the same order, see
+    /// [`provider_labels`](Self::provider_labels).
+    pub fn http_endpoint_labels(&self) -> Vec<String> {
+        self.http_endpoint
+            .urls()
+            .into_iter()
+            .enumerate()
+            .map(|(i, url)| match self.provider_labels.get(i) {
+                Some(label) => label.clone(),
+                None => url
+                    .as_url()
+                    .host_str()
+                    .map_or_else(|| format!("provider-{i}"), str::to_string),
+            })
+            .collect()
+    }
+}
+
+/// MulticallConfig is the configuration of the batching of proposal
+/// executions.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct MulticallConfig {
+    /// The address of a [Multicall3](https://www.multicall3.com) contract.
+    #[serde(deserialize_with = "crate::address::deserialize")]
+    pub address: Address,
+    /// The maximum number of proposal executions in one transaction.
+    #[serde(default = "defaults::multicall_max_batch_size")]
+    pub max_batch_size: usize,
 }
 
 /// configuration for adding http endpoints.
@@ -86,6 +220,16 @@ pub enum HttpEndpoint {
     Multiple(Vec<RpcUrl>),
 }
 
+impl HttpEndpoint {
+    /// Returns the endpoints.
+    pub fn urls(&self) -> Vec<&RpcUrl> {
+        match self {
+            HttpEndpoint::Single(url) => vec![url],
+            HttpEndpoint::Multiple(urls) => urls.iter().collect(),
+        }
+    }
+}
+
 impl fmt::Display for HttpEndpoint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -112,6 +256,7 @@ pub struct EvmLinkedAnchorConfig {
     /// The chain Id
     pub chain_id: u32,
     /// The V-anchor Contract Address.
+    #[serde(deserialize_with = "crate::address::deserialize")]
     pub address: Address,
 }
 
@@ -130,9 +275,18 @@ pub enum Contract {
 #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
 pub struct CommonContractConfig {
     /// The address of this contract on this chain.
+    #[serde(deserialize_with = "crate::address::deserialize")]
     pub address: Address,
     /// the block number where this contract got deployed at.
+    #[serde(alias = "deplyed-at")]
     pub deployed_at: u64,
+    /// The hash of the transaction that deployed this contract.
+    ///
+    /// When set, the events watcher checks at startup that it got mined at
+    /// `deployed-at`, to catch a redeployed contract whose `deployed-at`
+    /// was not updated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment_tx_hash: Option<H256>,
 }
 
 /// Smart Anchor Updates applies polices to the AnchorUpdate Proposals
@@ -184,11 +338,66 @@ pub struct VAnchorContractConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proposal_signing_backend: Option<ProposalSigningBackendConfig>,
     /// A List of linked Anchor Contracts (on other chains) to this contract.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        alias = "anchors",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub linked_anchors: Option<Vec<LinkedAnchorConfig>>,
     /// For configuring the smart anchor updates
     #[serde(default)]
     pub smart_anchor_updates: SmartAnchorUpdatesConfig,
+    /// The number of roots kept in the anchor root history.
+    ///
+    /// Anchor update proposals with a root older than that are re-created
+    /// with the current root before getting signed.
+    #[serde(default = "defaults::root_history_depth")]
+    pub root_history_depth: u32,
+    /// Skips the anchor update proposals for linked anchors that already have
+    /// the new root, such as when replaying old deposits after a restart.
+    #[serde(default = "defaults::skip_if_already_updated")]
+    pub skip_if_already_updated: bool,
+    /// How often, in secs, the edges the linked anchors have of this anchor
+    /// are checked against its leaves, to propose an update to those that
+    /// are stale, such as after a missed event.
+    ///
+    /// default to None, the edges are not checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_sync_interval_secs: Option<u64>,
+    /// How long, in secs, the anchor updates of a linked anchor are held
+    /// back after one got proposed to it, coalescing to the latest root.
+    ///
+    /// default to None, the updates are proposed as the deposits come.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_update_cooldown_secs: Option<u64>,
+    /// The most the relayer withdraws from this anchor over any hour, in the
+    /// smallest unit of its token.
+    ///
+    /// default to None, withdrawals are not limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_amount_per_hour: Option<Amount>,
+    /// The longest delay, in secs, a client may ask the relayer to hold its
+    /// withdrawal through this anchor for.
+    ///
+    /// default to None, withdrawals could not be delayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delay: Option<u64>,
+}
+
+/// The gas limit of the calls to a contract method.
+///
+/// The methods are listed in an array rather than as the keys of a table,
+/// since the keys of the config get lowercased when loaded, which would
+/// break the Solidity signatures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct GasLimitOverride {
+    /// The contract method, either named like the relayer calls it, such as
+    /// `execute_proposal`, or by its Solidity signature, such as
+    /// `executeProposalWithSignature(bytes,bytes)`.
+    pub method: String,
+    /// The gas limit to use for the calls to this method.
+    pub gas_limit: u64,
 }
 
 /// Signature Bridge contract configuration.
@@ -200,4 +409,22 @@ pub struct SignatureBridgeContractConfig {
     pub common: CommonContractConfig,
     /// Controls the events watcher
     pub events_watcher: EventsWatcherConfig,
+    /// Gas limits of the calls to this bridge, by contract method, used
+    /// instead of estimating them.
+    #[serde(skip_serializing, default)]
+    pub gas_limit_overrides: Vec<GasLimitOverride>,
+    /// The percentage added on top of the estimated gas of the calls to this
+    /// bridge that have no gas limit override.
+    ///
+    /// default to 0, the estimate is used as is.
+    #[serde(skip_serializing, default)]
+    pub gas_limit_padding: u64,
+    /// How many blocks behind the chain head the governor and the proposal
+    /// signatures are checked at, before executing a proposal, so the
+    /// relayer does not act on a state that gets reverted by a reorg.
+    ///
+    /// default to None, they are checked `block-confirmations` behind the
+    /// chain head, like the events.
+    #[serde(skip_serializing, default)]
+    pub status_confirmations: Option<u64>,
 }
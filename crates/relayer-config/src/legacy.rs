@@ -0,0 +1,280 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use config::{Config, File, FileFormat};
+use serde_json::Value;
+
+/// A config key that got renamed, or moved, over time.
+struct DeprecatedKey {
+    /// The old key, as written in the config files.
+    key: &'static str,
+    /// Where the setting lives now.
+    replacement: &'static str,
+}
+
+/// The keys that old config files may still use.
+///
+/// Renamed keys are also kept as serde aliases, so they still get loaded;
+/// the others are no longer read at all.
+const DEPRECATED_KEYS: &[DeprecatedKey] = &[
+    DeprecatedKey {
+        key: "deplyed-at",
+        replacement: "deployed-at",
+    },
+    DeprecatedKey {
+        key: "anchors",
+        replacement: "linked-anchors",
+    },
+    DeprecatedKey {
+        key: "experimental",
+        replacement: "smart-anchor-updates of each VAnchor contract",
+    },
+];
+
+/// A table whose settings are ignored while one of its flags is off.
+struct GatedTable {
+    /// The key of the table.
+    table: &'static str,
+    /// The flag that has to be on for the rest of the table to be used.
+    flag: &'static str,
+}
+
+const GATED_TABLES: &[GatedTable] = &[
+    GatedTable {
+        table: "smart-anchor-updates",
+        flag: "enabled",
+    },
+    GatedTable {
+        table: "experimental",
+        flag: "smart-anchor-updates",
+    },
+];
+
+/// A single entry of the [`MigrationReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// A deprecated key is used.
+    Deprecated {
+        /// The file the key came from.
+        file: PathBuf,
+        /// The full path of the key, such as `evm.goerli.contracts[0].deplyed-at`.
+        key: String,
+        /// What to use instead.
+        replacement: &'static str,
+    },
+    /// A setting is configured, but has no effect since a flag disables it.
+    Ignored {
+        /// The file the setting came from.
+        file: PathBuf,
+        /// The full path of the ignored setting.
+        key: String,
+        /// The full path of the flag that disables it.
+        flag: String,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Deprecated {
+                file,
+                key,
+                replacement,
+            } => write!(
+                f,
+                "{}: `{}` is deprecated, use `{}` instead",
+                file.display(),
+                key,
+                replacement
+            ),
+            Finding::Ignored { file, key, flag } => write!(
+                f,
+                "{}: `{}` is ignored, since `{}` is false",
+                file.display(),
+                key,
+                flag
+            ),
+        }
+    }
+}
+
+/// Lists the deprecated keys and the ignored settings of the config files,
+/// to help migrating old config files.
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    findings: Vec<Finding>,
+}
+
+impl MigrationReport {
+    /// Checks the raw content of the given config file.
+    pub fn check_file(
+        &mut self,
+        file: &Path,
+        format: FileFormat,
+    ) -> webb_relayer_utils::Result<()> {
+        let raw: Value = Config::builder()
+            .add_source(File::from(file).format(format))
+            .build()?
+            .try_deserialize()?;
+        self.walk(file, "", &raw);
+        Ok(())
+    }
+
+    /// All the findings, in the order they were found.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Returns true if the config files need no migration.
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Prints every finding as a warning.
+    pub fn log(&self) {
+        for finding in &self.findings {
+            tracing::warn!("{}", finding);
+        }
+    }
+
+    fn walk(&mut self, file: &Path, path: &str, value: &Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let key_path = join(path, key);
+                    if let Some(deprecated) =
+                        DEPRECATED_KEYS.iter().find(|d| d.key == key)
+                    {
+                        self.findings.push(Finding::Deprecated {
+                            file: file.to_path_buf(),
+                            key: key_path.clone(),
+                            replacement: deprecated.replacement,
+                        });
+                    }
+                    if let Some(gated) =
+                        GATED_TABLES.iter().find(|g| g.table == key)
+                    {
+                        self.check_gated(file, &key_path, gated, value);
+                    }
+                    self.walk(file, &key_path, value);
+                }
+            }
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.walk(file, &format!("{path}[{i}]"), value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_gated(
+        &mut self,
+        file: &Path,
+        path: &str,
+        gated: &GatedTable,
+        value: &Value,
+    ) {
+        let Value::Object(table) = value else {
+            return;
+        };
+        if table.get(gated.flag) != Some(&Value::Bool(false)) {
+            return;
+        }
+        let ignored = table.keys().filter(|k| *k != gated.flag);
+        for key in ignored {
+            self.findings.push(Finding::Ignored {
+                file: file.to_path_buf(),
+                key: join(path, key),
+                flag: join(path, gated.flag),
+            });
+        }
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{finding}")?;
+        }
+        Ok(())
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::Contract;
+
+    fn fixtures(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn old_keys_should_still_be_loaded() {
+        let config = crate::utils::load(fixtures("legacy")).unwrap();
+        let chain = config.evm.get("5").expect("chain 5 is configured");
+        let Contract::VAnchor(anchor) = &chain.contracts[0] else {
+            panic!("expected a VAnchor contract");
+        };
+        assert_eq!(anchor.common.deployed_at, 8188267);
+        assert_eq!(anchor.linked_anchors.as_ref().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn report_should_list_deprecated_and_ignored_keys() {
+        let main = fixtures("legacy").join("main.toml");
+        let goerli = fixtures("legacy").join("goerli.toml");
+        let mut report = MigrationReport::default();
+        report.check_file(&main, FileFormat::Toml).unwrap();
+        report.check_file(&goerli, FileFormat::Toml).unwrap();
+
+        let findings = report.findings();
+        assert_eq!(findings.len(), 5);
+        assert!(findings.contains(&Finding::Deprecated {
+            file: main.clone(),
+            key: "experimental".into(),
+            replacement: "smart-anchor-updates of each VAnchor contract",
+        }));
+        assert!(findings.contains(&Finding::Ignored {
+            file: main,
+            key: "experimental.smart-anchor-updates-retries".into(),
+            flag: "experimental.smart-anchor-updates".into(),
+        }));
+        assert!(findings.contains(&Finding::Deprecated {
+            file: goerli.clone(),
+            key: "evm.goerli.contracts[0].deplyed-at".into(),
+            replacement: "deployed-at",
+        }));
+        assert!(findings.contains(&Finding::Deprecated {
+            file: goerli.clone(),
+            key: "evm.goerli.contracts[0].anchors".into(),
+            replacement: "linked-anchors",
+        }));
+        assert!(findings.contains(&Finding::Ignored {
+            file: goerli,
+            key: "evm.goerli.contracts[0].smart-anchor-updates.min-time-delay"
+                .into(),
+            flag: "evm.goerli.contracts[0].smart-anchor-updates.enabled".into(),
+        }));
+    }
+
+    #[test]
+    fn strict_config_should_reject_old_keys() {
+        let result = crate::utils::load(fixtures("legacy-strict"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::StrictConfig(_))
+        ));
+    }
+}
@@ -27,8 +27,13 @@
 //! for an example.
 //! * `substrate`: Substrate based networks and the configuration. See [config/local-substrate](./config/local-substrate) for an example.
 
+/// Parsing of the configured addresses
+pub mod address;
 /// Generic anchor configuration
 pub mod anchor;
+/// Audit log of the relayed transactions and proposals
+#[cfg(feature = "cli")]
+pub mod audit_log;
 /// Block poller configuration
 pub mod block_poller;
 /// CLI configuration
@@ -40,6 +45,11 @@ pub mod defaults;
 pub mod event_watcher;
 /// EVM configuration
 pub mod evm;
+/// Detection of deprecated and ignored config keys
+pub mod legacy;
+/// Per chain log filtering
+#[cfg(feature = "cli")]
+pub mod log_filter;
 /// Signing backend configuration
 pub mod signing_backend;
 /// Substrate configuration
@@ -50,9 +60,11 @@ pub mod utils;
 use evm::EvmChainConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use substrate::SubstrateConfig;
 use webb::evm::ethers::types::Chain;
 use webb_relayer_types::etherscan_api::EtherscanApiKey;
+use webb_relayer_utils::metric::{CardinalityPolicy, MetricLabels};
 
 /// WebbRelayerConfig is the configuration for the webb relayer.
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -63,6 +75,30 @@ pub struct WebbRelayerConfig {
     /// default to 9955
     #[serde(default = "defaults::relayer_port", skip_serializing)]
     pub port: u16,
+    /// The host the relayer listens on, an IP address (v4 or v6) or a
+    /// hostname resolved at startup.
+    ///
+    /// default to 0.0.0.0
+    #[serde(default = "defaults::relayer_host", skip_serializing)]
+    pub host: String,
+    /// The hosts the relayer listens on, all on `port`, such as
+    /// `["0.0.0.0", "::"]` to serve both the IPv4 and the IPv6 clients of a
+    /// dual-stack host.
+    ///
+    /// default to empty, the relayer listens on `host`.
+    #[serde(default, skip_serializing)]
+    pub hosts: Vec<String>,
+    /// The public URL of the relayer, returned by `/info` and in the
+    /// websocket handshake, so the dApps register it rather than the address
+    /// they connected to.
+    ///
+    /// default to None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advertised_url: Option<url::Url>,
+    /// An optional second listener that serves the admin endpoints, such as
+    /// the metrics, which are then no longer served on the main listener.
+    #[serde(default, skip_serializing)]
+    pub admin_listener: Option<AdminListenerConfig>,
     /// EVM based networks and the configuration.
     ///
     /// a map between chain name and its configuration.
@@ -96,9 +132,188 @@ pub struct WebbRelayerConfig {
     /// it is a simple map between the asset symbol and its configuration.
     #[serde(default = "defaults::unlisted_assets")]
     pub assets: HashMap<String, UnlistedAssetConfig>,
+    /// Caps the log level of specific chains, to keep busy chains from
+    /// flooding the logs.
+    ///
+    /// a map between chain name and its max log level, such as `warn`.
+    #[serde(default, skip_serializing)]
+    pub log_filter_by_chain: HashMap<String, String>,
+    /// Writes the relayed transactions and the signed or executed proposals
+    /// to a separate, append-only audit log.
+    ///
+    /// default to None, no audit log is written.
+    #[serde(default, skip_serializing)]
+    pub audit_log: Option<AuditLogConfig>,
+    /// Fails loading the config if it uses deprecated keys, or settings that
+    /// are ignored, instead of only warning about them.
+    ///
+    /// default to false
+    #[serde(default, skip_serializing)]
+    pub strict_config: bool,
+    /// Checks that every configured EVM contract is deployed before
+    /// starting the relayer, and fails to start otherwise.
+    ///
+    /// default to false
+    #[serde(default, skip_serializing)]
+    pub fail_fast_startup: bool,
+    /// How often, in minutes, the signed leaf snapshots served over HTTP
+    /// can be regenerated; in between, the cached snapshot is served.
+    ///
+    /// default to 10 minutes
+    #[serde(default = "defaults::leaf_snapshot_interval", skip_serializing)]
+    pub leaf_snapshot_interval: u64,
+    /// How often, in milliseconds, the store is flushed to disk, on top of
+    /// the flushing done by the store itself.
+    ///
+    /// default to None, the store is only flushed on shutdown.
+    #[serde(default, skip_serializing)]
+    pub flush_interval_ms: Option<u64>,
+    /// The gRPC server, used by the orchestration of the relayer.
+    ///
+    /// default to None, no gRPC server is started.
+    #[serde(default, skip_serializing)]
+    pub grpc: Option<GrpcConfig>,
+    /// The reverse proxies in front of the relayer, as CIDR ranges.
+    ///
+    /// The `X-Forwarded-For` and `Forwarded` headers are only trusted when
+    /// the request comes from one of them.
+    ///
+    /// default to none, the headers are ignored.
+    #[serde(default, skip_serializing)]
+    pub trusted_proxies: Vec<ipnet::IpNet>,
+    /// How many HTTP requests are handled at the same time, the requests
+    /// over that limit are answered with `503 Service Unavailable`, `0`
+    /// disables the limit.
+    ///
+    /// default to 512
+    #[serde(default = "defaults::max_concurrent_requests", skip_serializing)]
+    pub max_concurrent_requests: usize,
+    /// How many commands, such as the signed proposals to execute, may wait
+    /// for each bridge watcher. Once full, the event handlers sending more
+    /// commands wait for the bridge watcher to catch up.
+    ///
+    /// default to 16
+    #[serde(default = "defaults::bridge_command_buffer", skip_serializing)]
+    pub bridge_command_buffer: usize,
+    /// How many proposals may be in flight on each chain, from their
+    /// enqueueing to their execution, so they do not fight over the nonces
+    /// of the relayer account.
+    ///
+    /// default to 10
+    #[serde(
+        default = "defaults::max_concurrent_proposals_per_chain",
+        skip_serializing
+    )]
+    pub max_concurrent_proposals_per_chain: u8,
+    /// How long, in seconds, a proposal counts as in flight at most, when
+    /// its execution is never seen, such as when its transaction reverts.
+    ///
+    /// default to 300 seconds
+    #[serde(
+        default = "defaults::proposal_permit_timeout_secs",
+        skip_serializing
+    )]
+    pub proposal_permit_timeout_secs: u64,
+    /// How often, in seconds, the websocket connections are pinged, so NAT
+    /// gateways and load balancers do not drop them while they are idle.
+    /// Connections that do not answer two intervals in a row are closed.
+    ///
+    /// default to 30 seconds
+    #[serde(default = "defaults::ws_ping_interval_secs", skip_serializing)]
+    pub ws_ping_interval_secs: u64,
+    /// The largest message, in bytes, the websocket clients may send.
+    /// Larger messages are answered with an error, and the connection is
+    /// closed with `1009` (message too big).
+    ///
+    /// default to 65536 bytes, `0` for no limit.
+    #[serde(default = "defaults::ws_max_message_size_bytes", skip_serializing)]
+    pub ws_max_message_size_bytes: usize,
+    /// How many responses may wait to be sent to a websocket client, such
+    /// as the updates of its subscriptions while it reads slowly.
+    ///
+    /// default to 50
+    #[serde(default = "defaults::ws_send_queue_size", skip_serializing)]
+    pub ws_send_queue_size: usize,
+    /// What to do when the send queue of a websocket client is full.
+    ///
+    /// default to `Backpressure`, the responses wait for room in the queue.
+    #[serde(default, skip_serializing)]
+    pub ws_slow_client_policy: WsSlowClientPolicy,
+    /// Reloads the config files on `SIGHUP`, starting the chains added to
+    /// them and stopping the removed ones, without a restart.
+    ///
+    /// default to false, `SIGHUP` stops the relayer.
+    #[serde(default, skip_serializing)]
+    pub reload_on_sighup: bool,
+    /// Watches the config files, and reloads them once they change, like
+    /// on `SIGHUP` with `reload_on_sighup`.
+    ///
+    /// default to false, the config files are only read at startup.
+    #[serde(default, skip_serializing)]
+    pub hot_reload: bool,
+    /// What to do when one of the background tasks, such as an events
+    /// watcher or a transaction queue, fails or panics.
+    ///
+    /// default to `AbortAll`, the relayer shuts down.
+    #[serde(default, skip_serializing)]
+    pub task_failure_policy: TaskFailurePolicy,
+    /// How long the clients may cache the responses of the read endpoints,
+    /// such as the info and the leaves ones.
+    #[serde(default, skip_serializing)]
+    pub http_cache: HttpCacheConfig,
+    /// The token the admin websocket commands, such as
+    /// `subscribeProposals`, have to carry.
+    ///
+    /// default to None, the admin commands are refused.
+    #[serde(default, skip_serializing)]
+    pub admin_token: Option<String>,
+    /// The labels of the metrics of the watched contracts, and how many
+    /// label sets they may have.
+    #[serde(default, skip_serializing)]
+    pub metrics: MetricsConfig,
+    /// When the leaves served by the leaves endpoints are reported as
+    /// lagging or stale.
+    #[serde(default, skip_serializing)]
+    pub leaves_freshness: LeavesFreshnessConfig,
+}
+
+/// WsSlowClientPolicy is what the relayer does when a websocket client reads
+/// its responses slower than they are produced.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize,
+)]
+pub enum WsSlowClientPolicy {
+    /// The commands of the client wait for room in its send queue.
+    #[default]
+    Backpressure,
+    /// The connection is closed with `1008` (policy violation).
+    Disconnect,
+}
+
+/// TaskFailurePolicy is what the relayer does when a background task fails.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize,
+)]
+pub enum TaskFailurePolicy {
+    /// Shuts the relayer down.
+    #[default]
+    AbortAll,
+    /// Starts the failed task again.
+    Restart,
+    /// Logs the failure, the other tasks keep running.
+    Ignore,
 }
 
 impl WebbRelayerConfig {
+    /// The hosts the relayer listens on, `hosts` if any, `host` otherwise.
+    pub fn listen_hosts(&self) -> Vec<String> {
+        if self.hosts.is_empty() {
+            vec![self.host.clone()]
+        } else {
+            self.hosts.clone()
+        }
+    }
+
     /// Makes sure that the config is valid, by going
     /// through the whole config and doing some basic checks.
     #[allow(unused)] // TODO(@shekohex): remove this once we convert the relayer into a crate.
@@ -129,6 +344,161 @@ impl WebbRelayerConfig {
     }
 }
 
+/// AdminListenerConfig is the configuration of the listener of the admin endpoints.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct AdminListenerConfig {
+    /// The host of the admin listener, an IP address (v4 or v6) or a
+    /// hostname resolved at startup.
+    ///
+    /// default to 127.0.0.1
+    #[serde(default = "defaults::admin_listener_host")]
+    pub host: String,
+    /// The port of the admin listener.
+    pub port: u16,
+}
+
+/// GrpcConfig is the configuration of the gRPC server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct GrpcConfig {
+    /// Whether the gRPC server is started.
+    ///
+    /// default to false
+    #[serde(default)]
+    pub enabled: bool,
+    /// The host of the gRPC server, an IP address (v4 or v6) or a hostname
+    /// resolved at startup.
+    ///
+    /// default to 127.0.0.1
+    #[serde(default = "defaults::admin_listener_host")]
+    pub host: String,
+    /// The port of the gRPC server.
+    pub port: u16,
+    /// The token every request has to carry in its `authorization`
+    /// metadata, as `Bearer <token>`.
+    pub token: String,
+}
+
+/// HttpCacheConfig is how long the clients may cache the responses of the
+/// read endpoints, which they revalidate with their `ETag` afterwards.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct HttpCacheConfig {
+    /// The `max-age`, in seconds, of the `/api/v1/info` responses.
+    ///
+    /// default to 10 seconds
+    #[serde(default = "defaults::info_max_age_secs")]
+    pub info_max_age_secs: u64,
+    /// The `max-age`, in seconds, of the leaves responses.
+    ///
+    /// default to 2 seconds
+    #[serde(default = "defaults::leaves_max_age_secs")]
+    pub leaves_max_age_secs: u64,
+}
+
+impl Default for HttpCacheConfig {
+    fn default() -> Self {
+        Self {
+            info_max_age_secs: defaults::info_max_age_secs(),
+            leaves_max_age_secs: defaults::leaves_max_age_secs(),
+        }
+    }
+}
+
+/// LeavesFreshnessConfig is when the leaves served by the leaves endpoints
+/// are reported as `lagging` or `stale`, from the last sync of their events
+/// watcher.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct LeavesFreshnessConfig {
+    /// The leaves are `lagging` once their watcher did not sync for that
+    /// many seconds.
+    ///
+    /// default to 60 seconds
+    #[serde(default = "defaults::leaves_lagging_after_secs")]
+    pub lagging_after_secs: u64,
+    /// The leaves are `stale` once their watcher did not sync for that many
+    /// seconds, more than `lagging_after_secs`.
+    ///
+    /// default to 600 seconds
+    #[serde(default = "defaults::leaves_stale_after_secs")]
+    pub stale_after_secs: u64,
+    /// The leaves are `lagging` once their watcher is that many blocks
+    /// behind the head of the chain, as of its last sync, besides the
+    /// `safety-lag` of the watcher.
+    ///
+    /// default to 100 blocks
+    #[serde(default = "defaults::leaves_lagging_after_blocks")]
+    pub lagging_after_blocks: u64,
+}
+
+impl Default for LeavesFreshnessConfig {
+    fn default() -> Self {
+        Self {
+            lagging_after_secs: defaults::leaves_lagging_after_secs(),
+            stale_after_secs: defaults::leaves_stale_after_secs(),
+            lagging_after_blocks: defaults::leaves_lagging_after_blocks(),
+        }
+    }
+}
+
+/// MetricsConfig keeps the number of series of the metrics of the watched
+/// contracts in check, on relayers watching many contracts.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct MetricsConfig {
+    /// The labels of the metrics.
+    ///
+    /// default to `chain-contract`
+    #[serde(default)]
+    pub labels: MetricLabels,
+    /// The most label sets of a metric, the ones over it are counted under
+    /// the `other` label.
+    ///
+    /// default to 1000
+    #[serde(default = "defaults::max_metric_label_sets")]
+    pub max_label_sets: usize,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            labels: MetricLabels::default(),
+            max_label_sets: defaults::max_metric_label_sets(),
+        }
+    }
+}
+
+impl MetricsConfig {
+    /// The policy the metrics enforce.
+    pub fn policy(&self) -> CardinalityPolicy {
+        CardinalityPolicy {
+            labels: self.labels,
+            max_label_sets: self.max_label_sets,
+        }
+    }
+}
+
+/// AuditLogConfig is the configuration of the audit log.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct AuditLogConfig {
+    /// The file the audit records are appended to, one JSON object per line.
+    pub path: std::path::PathBuf,
+    /// The size, in bytes, after which the file is rotated.
+    ///
+    /// default to 100 MiB
+    #[serde(default = "defaults::audit_log_max_file_size")]
+    pub max_file_size: u64,
+    /// How many rotated files are kept, as `<path>.1` to `<path>.<max-files>`,
+    /// the oldest being removed first.
+    ///
+    /// default to 10
+    #[serde(default = "defaults::audit_log_max_files")]
+    pub max_files: usize,
+}
+
 /// FeaturesConfig is the configuration for running relayer with option.
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
@@ -139,6 +509,17 @@ pub struct FeaturesConfig {
     pub governance_relay: bool,
     /// Enable private tx relaying
     pub private_tx_relay: bool,
+    /// Enable listing the recently relayed withdrawals
+    #[serde(default = "defaults::enable_withdrawals_query")]
+    pub withdrawals_query: bool,
+    /// Dry run the private transactions before sending them, to reject
+    /// invalid proofs without paying for a reverted transaction
+    #[serde(default)]
+    pub proof_preverification: bool,
+    /// Keep the fingerprints of the recently accepted withdrawals, to
+    /// reject their retries even across restarts
+    #[serde(default)]
+    pub relay_deduplication: bool,
 }
 
 impl Default for FeaturesConfig {
@@ -147,6 +528,9 @@ impl Default for FeaturesConfig {
             data_query: true,
             governance_relay: true,
             private_tx_relay: true,
+            withdrawals_query: true,
+            proof_preverification: false,
+            relay_deduplication: false,
         }
     }
 }
@@ -175,16 +559,74 @@ pub struct TxQueueConfig {
     /// Maximum number of milliseconds to wait before dequeuing a transaction from
     /// the queue.
     pub max_sleep_interval: u64,
+    /// Which reverted transactions are queued again, EVM chains only.
+    #[serde(default)]
+    pub retry_on_revert: RetryOnRevertConfig,
 }
 
 impl Default for TxQueueConfig {
     fn default() -> Self {
         Self {
             max_sleep_interval: 10_000,
+            retry_on_revert: Default::default(),
         }
     }
 }
 
+/// RetryOnRevertConfig sorts the revert reasons of the queued transactions
+/// into the transient ones, worth retrying, and the permanent ones, such as
+/// a proposal already executed, that would only waste gas.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+pub struct RetryOnRevertConfig {
+    /// Parts of the transient revert reasons, matched case insensitively.
+    #[serde(default = "defaults::transient_revert_reasons")]
+    pub retry: Vec<String>,
+    /// Parts of the permanent revert reasons, matched before `retry`.
+    #[serde(default)]
+    pub no_retry: Vec<String>,
+    /// How many times a reverted transaction is retried, at most.
+    #[serde(default = "defaults::max_revert_retries")]
+    pub max_retries: u32,
+    /// How long a reverted transaction waits before its first retry, in
+    /// seconds, doubled on each retry after it.
+    #[serde(default = "defaults::revert_retry_delay_secs")]
+    pub retry_delay_secs: u64,
+}
+
+impl Default for RetryOnRevertConfig {
+    fn default() -> Self {
+        Self {
+            retry: defaults::transient_revert_reasons(),
+            no_retry: Vec::new(),
+            max_retries: defaults::max_revert_retries(),
+            retry_delay_secs: defaults::revert_retry_delay_secs(),
+        }
+    }
+}
+
+impl RetryOnRevertConfig {
+    /// Whether a transaction that reverted with `reason` is worth retrying.
+    ///
+    /// The reasons matching neither list are permanent.
+    pub fn should_retry(&self, reason: &str) -> bool {
+        let reason = reason.to_lowercase();
+        let matches = |parts: &[String]| {
+            parts
+                .iter()
+                .any(|part| reason.contains(&part.to_lowercase()))
+        };
+        !matches(&self.no_retry) && matches(&self.retry)
+    }
+
+    /// How long a transaction retried `retries` times already waits before
+    /// it is retried once more.
+    pub fn retry_delay(&self, retries: u32) -> Duration {
+        let factor = 1u64.checked_shl(retries).unwrap_or(u64::MAX);
+        Duration::from_secs(self.retry_delay_secs.saturating_mul(factor))
+    }
+}
+
 /// UnlistedAssetConfig is the configuration for the assets that are not listed on any exchange.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
@@ -246,4 +688,48 @@ mod tests {
                 .expect("Failed to set current directory");
         }
     }
+
+    #[test]
+    fn only_transient_reverts_should_be_retried() {
+        let policy: RetryOnRevertConfig =
+            serde_json::from_value(serde_json::json!({
+                "retry": ["block gas limit", "too many"],
+                "no-retry": ["already executed"],
+            }))
+            .unwrap();
+        assert_eq!(policy.max_retries, 3);
+        assert!(policy.should_retry("Exceeds Block Gas Limit"));
+        assert!(!policy.should_retry("Proposal already executed"));
+        // the permanent reasons win over the transient ones.
+        assert!(!policy.should_retry("too many: already executed"));
+        // as do the unknown ones.
+        assert!(!policy.should_retry("Invalid transaction proof"));
+        assert!(RetryOnRevertConfig::default().should_retry("block gas limit"));
+        // backing off exponentially.
+        assert_eq!(policy.retry_delay(0), Duration::from_secs(5));
+        assert_eq!(policy.retry_delay(2), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn proofs_should_not_be_preverified_by_default() {
+        let features: FeaturesConfig =
+            serde_json::from_value(serde_json::json!({
+                "data-query": true,
+                "governance-relay": true,
+                "private-tx-relay": true,
+            }))
+            .unwrap();
+        assert!(!features.proof_preverification);
+        assert!(!FeaturesConfig::default().proof_preverification);
+
+        let features: FeaturesConfig =
+            serde_json::from_value(serde_json::json!({
+                "data-query": true,
+                "governance-relay": true,
+                "private-tx-relay": true,
+                "proof-preverification": true,
+            }))
+            .unwrap();
+        assert!(features.proof_preverification);
+    }
 }
@@ -0,0 +1,217 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context as _;
+use std::collections::HashMap;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+use tracing::{span, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use webb_relayer_utils::probe;
+
+/// The span field used to tag the spans of a chain's background services.
+pub const CHAIN_FIELD: &str = "chain_name";
+
+/// Creates the span every background service of a chain runs in, tagged
+/// with the [`CHAIN_FIELD`] the [`ChainLogFilter`] filters on.
+pub fn chain_span(chain_name: &str, chain_id: u32) -> tracing::Span {
+    tracing::info_span!("watcher", chain_name = %chain_name, chain_id)
+}
+
+/// The chain name recorded on a span, kept in the span extensions.
+struct ChainName(String);
+
+/// Looks for the [`CHAIN_FIELD`] in the span fields.
+#[derive(Default)]
+struct ChainNameVisitor(Option<String>);
+
+impl Visit for ChainNameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == CHAIN_FIELD {
+            self.0 = Some(value.to_lowercase());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == CHAIN_FIELD {
+            self.0 = Some(format!("{value:?}").to_lowercase());
+        }
+    }
+}
+
+/// A [`Layer`] that caps the level of the events emitted inside a span
+/// tagged with the [`CHAIN_FIELD`], using a max level per chain name.
+///
+/// Events outside of any chain span, or inside the span of a chain without
+/// a configured level, are left untouched, and so are the [`probe`] events.
+#[derive(Debug, Clone, Default)]
+pub struct ChainLogFilter {
+    levels: HashMap<String, LevelFilter>,
+}
+
+impl ChainLogFilter {
+    /// Creates a new filter from a map between chain names and their
+    /// level directive, such as `"warn"`. Chain names are case insensitive.
+    pub fn new(directives: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let levels = directives
+            .iter()
+            .map(|(chain, level)| {
+                let level =
+                    level.parse::<LevelFilter>().with_context(|| {
+                        format!("invalid log level {level} for chain {chain}")
+                    })?;
+                Ok((chain.to_lowercase(), level))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { levels })
+    }
+
+    /// Returns the configured level of the closest chain span.
+    fn chain_level<S>(&self, ctx: &Context<'_, S>) -> Option<LevelFilter>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let current = ctx.lookup_current()?;
+        for span in current.scope() {
+            let extensions = span.extensions();
+            if let Some(ChainName(chain)) = extensions.get::<ChainName>() {
+                return self.levels.get(chain).copied();
+            }
+        }
+        None
+    }
+}
+
+impl<S> Layer<S> for ChainLogFilter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(
+        &self,
+        metadata: &'static Metadata<'static>,
+    ) -> Interest {
+        // probe events feed the audit log, which must not miss any.
+        if metadata.target() == probe::TARGET {
+            return Interest::always();
+        }
+        // whether an event is enabled depends on the span it is emitted in,
+        // so it has to be checked every time if any chain could filter it.
+        let filtered = self.levels.values().any(|max| max < metadata.level());
+        if metadata.is_event() && filtered {
+            Interest::sometimes()
+        } else {
+            Interest::always()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        if !metadata.is_event()
+            || self.levels.is_empty()
+            || metadata.target() == probe::TARGET
+        {
+            return true;
+        }
+        match self.chain_level(&ctx) {
+            Some(max) => max >= *metadata.level(),
+            None => true,
+        }
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        let mut visitor = ChainNameVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(chain), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(ChainName(chain));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Counts the events that made it through the filters.
+    #[derive(Clone, Default)]
+    struct EventCounter(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for EventCounter {
+        fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl EventCounter {
+        fn take(&self) -> usize {
+            self.0.swap(0, Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn should_filter_debug_events_of_chain_set_to_warn() {
+        let directives = [("Harmony".to_string(), "warn".to_string())]
+            .into_iter()
+            .collect();
+        let filter = ChainLogFilter::new(&directives).unwrap();
+        let counter = EventCounter::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(LevelFilter::TRACE)
+            .with(counter.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let harmony = chain_span("Harmony", 1666600000);
+            harmony.in_scope(|| {
+                tracing::debug!("new block");
+                tracing::info!("synced");
+                // nested spans are filtered too.
+                tracing::info_span!("vanchor").in_scope(|| {
+                    tracing::debug!("new commitment");
+                });
+            });
+            assert_eq!(counter.take(), 0);
+
+            harmony.in_scope(|| {
+                tracing::warn!("backing off");
+                tracing::error!("rpc error");
+            });
+            assert_eq!(counter.take(), 2);
+
+            let goerli = chain_span("Goerli", 5);
+            goerli.in_scope(|| {
+                tracing::debug!("new block");
+            });
+            tracing::debug!("outside of any chain");
+            assert_eq!(counter.take(), 2);
+        });
+    }
+
+    #[test]
+    fn should_reject_invalid_levels() {
+        let directives = [("Harmony".to_string(), "loud".to_string())]
+            .into_iter()
+            .collect();
+        assert!(ChainLogFilter::new(&directives).is_err());
+    }
+}
@@ -69,6 +69,38 @@ pub struct SubstrateConfig {
     /// TxQueue configuration
     #[serde(skip_serializing, default)]
     pub tx_queue: TxQueueConfig,
+    /// The free balance, in the native unit of the chain, under which the
+    /// relayer account is reported as low on funds.
+    ///
+    /// default to None, never reported.
+    #[serde(skip_serializing, default)]
+    pub min_balance: Option<f64>,
+    /// How often, in seconds, the balance and the nonce of the relayer
+    /// account are checked.
+    ///
+    /// default to 60 seconds
+    #[serde(
+        skip_serializing,
+        default = "defaults::account_check_interval_secs"
+    )]
+    pub account_check_interval_secs: u64,
+    /// Whether the withdrawals relayed to this node are sent together, in a
+    /// `utility.batch` extrinsic, instead of one extrinsic each.
+    ///
+    /// default to false
+    #[serde(skip_serializing, default)]
+    pub batch_extrinsics: bool,
+    /// The most withdrawals sent in a single batch.
+    ///
+    /// default to 10
+    #[serde(skip_serializing, default = "defaults::max_batch_size")]
+    pub max_batch_size: usize,
+    /// How long, in millis, a withdrawal waits for the batch it is part of
+    /// to fill up, before the batch is sent anyway.
+    ///
+    /// default to 6000 millis
+    #[serde(skip_serializing, default = "defaults::batch_flush_timeout_ms")]
+    pub batch_flush_timeout_ms: u64,
 }
 
 /// Linked anchor config for Substrate based target system
@@ -100,6 +132,30 @@ pub enum Pallet {
     VAnchorBn254(VAnchorBn254PalletConfig),
 }
 
+impl Pallet {
+    /// The name of the pallet, as in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pallet::Dkg(_) => "DKG",
+            Pallet::DKGProposals(_) => "DKGProposals",
+            Pallet::DKGProposalHandler(_) => "DKGProposalHandler",
+            Pallet::SignatureBridge(_) => "SignatureBridge",
+            Pallet::VAnchorBn254(_) => "VAnchorBn254",
+        }
+    }
+
+    /// The events watcher of the pallet.
+    pub fn events_watcher(&self) -> &EventsWatcherConfig {
+        match self {
+            Pallet::Dkg(c) => &c.events_watcher,
+            Pallet::DKGProposals(c) => &c.events_watcher,
+            Pallet::DKGProposalHandler(c) => &c.events_watcher,
+            Pallet::SignatureBridge(c) => &c.events_watcher,
+            Pallet::VAnchorBn254(c) => &c.events_watcher,
+        }
+    }
+}
+
 /// DKGProposalsPalletConfig represents the configuration for the DKGProposals pallet.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
@@ -140,6 +196,9 @@ pub struct VAnchorBn254PalletConfig {
     /// The type of the optional signing backend used for signing proposals. It can be None for pure Tx relayers
     pub proposal_signing_backend: Option<ProposalSigningBackendConfig>,
     /// A List of linked Anchor on this chain.
-    #[serde(default)]
+    #[serde(default, alias = "anchors")]
     pub linked_anchors: Option<Vec<LinkedAnchorConfig>>,
+    /// The merkle trees of the pallet to watch, all of them if not set.
+    #[serde(default)]
+    pub tree_ids: Option<Vec<u32>>,
 }
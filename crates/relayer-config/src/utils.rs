@@ -1,7 +1,13 @@
 use config::{Config, File};
 use std::path::{Path, PathBuf};
+use webb_relayer_utils::ConfigParseError;
 
-use crate::{anchor::LinkedAnchorConfig, evm::Contract, substrate::Pallet};
+use crate::{
+    anchor::LinkedAnchorConfig,
+    evm::{Contract, VAnchorContractConfig},
+    legacy::MigrationReport,
+    substrate::Pallet,
+};
 
 use super::*;
 
@@ -36,6 +42,7 @@ pub fn parse_from_files(
 ) -> webb_relayer_utils::Result<WebbRelayerConfig> {
     let mut builder = Config::builder();
     let contracts: HashMap<String, Vec<Contract>> = HashMap::new();
+    let mut report = MigrationReport::default();
     // read through all config files for the first time
     // build up a collection of [contracts]
     for config_file in files {
@@ -46,16 +53,21 @@ pub fn parse_from_files(
             .map(|e| e.to_str().unwrap_or(""))
             .unwrap_or("");
         let format = match ext {
-            "toml" => config::FileFormat::Toml,
+            "toml" => {
+                check_toml_syntax(config_file)?;
+                config::FileFormat::Toml
+            }
             "json" => config::FileFormat::Json,
             _ => {
                 tracing::warn!("Unknown file extension: {}", ext);
                 continue;
             }
         };
+        report.check_file(config_file, format)?;
         builder = builder
             .add_source(File::from(config_file.as_path()).format(format));
     }
+    report.log();
 
     // also merge in the environment (with a prefix of WEBB).
     let builder = builder
@@ -68,6 +80,11 @@ pub fn parse_from_files(
     > = serde_path_to_error::deserialize(cfg);
     match config {
         Ok(mut c) => {
+            if c.strict_config && !report.is_empty() {
+                return Err(webb_relayer_utils::Error::StrictConfig(
+                    report.to_string(),
+                ));
+            }
             // merge in all of the contracts into the config
             for (network_name, network_chain) in c.evm.iter_mut() {
                 if let Some(stored_contracts) = contracts.get(network_name) {
@@ -79,9 +96,138 @@ pub fn parse_from_files(
         }
         Err(e) => {
             tracing::error!("{}", e);
-            Err(e.into())
+            match locate_error(files, &e) {
+                Some(located) => Err(located.into()),
+                None => Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Parses a TOML config file on its own, so that a syntax error points at
+/// the line it is on.
+fn check_toml_syntax(file: &Path) -> webb_relayer_utils::Result<()> {
+    let contents = std::fs::read_to_string(file)?;
+    let Err(e) = toml::from_str::<toml::Value>(&contents) else {
+        return Ok(());
+    };
+    let (line, column) = e.line_col().unwrap_or_default();
+    // the position is already part of the error.
+    let message = e.to_string();
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(message, _)| message);
+    Err(
+        ConfigParseError::new(file, &contents, line + 1, column + 1, message)
+            .into(),
+    )
+}
+
+/// Finds the TOML config file, and the line in it, of the key that failed
+/// to deserialize.
+///
+/// The error may be about a key that is not in any file, such as a missing
+/// field, in which case the closest table that is in a file is used.
+fn locate_error(
+    files: &[PathBuf],
+    e: &serde_path_to_error::Error<config::ConfigError>,
+) -> Option<ConfigParseError> {
+    let path: Vec<String> = e
+        .path()
+        .iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+            serde_path_to_error::Segment::Seq { index } => {
+                Some(format!("[{index}]"))
+            }
+            _ => None,
+        })
+        .collect();
+    files
+        .iter()
+        .filter(|file| file.extension().map_or(false, |ext| ext == "toml"))
+        .filter_map(|file| {
+            let contents = std::fs::read_to_string(file).ok()?;
+            let (depth, line, column) = locate_key(&contents, &path)?;
+            Some((depth, file, contents, line, column))
+        })
+        .max_by_key(|(depth, ..)| *depth)
+        .map(|(_, file, contents, line, column)| {
+            ConfigParseError::new(
+                file,
+                &contents,
+                line,
+                column,
+                e.inner().to_string(),
+            )
+        })
+}
+
+/// Finds the line and column of the key at `path` in a TOML document, or of
+/// the longest part of the path found in it.
+///
+/// Returns the number of matched path segments along with the position.
+fn locate_key(
+    contents: &str,
+    path: &[String],
+) -> Option<(usize, usize, usize)> {
+    let split_key = |key: &str| -> Option<Vec<String>> {
+        key.split('.')
+            .map(|part| part.trim().trim_matches('"').to_lowercase())
+            .map(|part| {
+                let bare = !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+                bare.then_some(part)
+            })
+            .collect()
+    };
+    let matched = |full: &[String]| {
+        full.len() <= path.len()
+            && full
+                .iter()
+                .zip(path)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    };
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut table = Vec::new();
+    let mut array_tables: HashMap<Vec<String>, usize> = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        let column = line.len() - line.trim_start().len() + 1;
+        let full = if let Some(header) = trimmed.strip_prefix("[[") {
+            let Some(header) = header.split("]]").next().and_then(split_key)
+            else {
+                continue;
+            };
+            // the tables of an array are numbered like the array items.
+            let count = array_tables.entry(header.clone()).or_default();
+            table = header;
+            table.push(format!("[{count}]"));
+            *count += 1;
+            table.clone()
+        } else if let Some(header) = trimmed.strip_prefix('[') {
+            let Some(header) = header.split(']').next().and_then(split_key)
+            else {
+                continue;
+            };
+            table = header;
+            table.clone()
+        } else if let Some((key, _)) = trimmed.split_once('=') {
+            let Some(key) = split_key(key) else {
+                continue;
+            };
+            table.iter().cloned().chain(key).collect()
+        } else {
+            continue;
+        };
+        if matched(&full) && best.map_or(true, |(depth, ..)| full.len() > depth)
+        {
+            best = Some((full.len(), number + 1, column));
         }
     }
+    best
 }
 
 /// Load the configuration files and
@@ -176,6 +322,116 @@ pub fn postloading_process(
             }
         })
     }
+    // reject linked anchors that point back at the anchor itself, or that
+    // are listed twice, the bridge would reject their anchor updates.
+    for network_chain in config.evm.values() {
+        for c in &network_chain.contracts {
+            if let Contract::VAnchor(cfg) = c {
+                let own = webb_proposals::ResourceId::new(
+                    webb_proposals::TargetSystem::new_contract_address(
+                        cfg.common.address.to_fixed_bytes(),
+                    ),
+                    webb_proposals::TypedChainId::Evm(network_chain.chain_id),
+                );
+                check_linked_anchors(
+                    Some(own),
+                    || {
+                        format!(
+                            "{:?} on chain {}",
+                            cfg.common.address, network_chain.chain_id
+                        )
+                    },
+                    cfg.linked_anchors.as_deref().unwrap_or_default(),
+                )?;
+            }
+        }
+    }
+    for network_chain in config.substrate.values() {
+        for c in &network_chain.pallets {
+            if let Pallet::VAnchorBn254(cfg) = c {
+                // the tree id of the pallet is only known from its events.
+                check_linked_anchors(
+                    None,
+                    || {
+                        format!(
+                            "VAnchorBn254 pallet on chain {}",
+                            network_chain.chain_id
+                        )
+                    },
+                    cfg.linked_anchors.as_deref().unwrap_or_default(),
+                )?;
+            }
+        }
+    }
+    // the data of the Substrate pallets is only served while they are
+    // watched.
+    for network_chain in config.substrate.values() {
+        for pallet in &network_chain.pallets {
+            if pallet.events_watcher().is_static() {
+                return Err(webb_relayer_utils::Error::InvalidStaticContract {
+                    contract: format!(
+                        "{} pallet on chain {}",
+                        pallet.name(),
+                        network_chain.chain_id
+                    ),
+                    reason: "only the EVM contracts can be static",
+                });
+            }
+        }
+    }
+    // static contracts are not watched, nothing would be relayed for them.
+    for network_chain in config.evm.values() {
+        for c in &network_chain.contracts {
+            if let Contract::VAnchor(cfg) = c {
+                check_static_contract(cfg).map_err(|reason| {
+                    webb_relayer_utils::Error::InvalidStaticContract {
+                        contract: format!(
+                            "{:?} on chain {}",
+                            cfg.common.address, network_chain.chain_id
+                        ),
+                        reason,
+                    }
+                })?;
+                // a gap would never be waited for, the leaves after it
+                // would be fetched again and again.
+                if cfg.events_watcher.max_pending_leaves == 0 {
+                    return Err(webb_relayer_utils::Error::Generic(
+                        "max-pending-leaves cannot be zero",
+                    ));
+                }
+            }
+        }
+    }
+    // the event types are the names of the events in the contract ABI, one
+    // that is not would never be handled.
+    #[cfg(feature = "evm-runtime")]
+    for network_chain in config.evm.values() {
+        for c in &network_chain.contracts {
+            check_event_type_allowlist(
+                network_chain.chain_id,
+                c,
+                &network_chain.contract_abi_override,
+            )?;
+        }
+    }
+    check_listen_addresses(&config)?;
+    // the leaves would turn stale without ever being reported as lagging.
+    let freshness = &config.leaves_freshness;
+    if freshness.lagging_after_secs >= freshness.stale_after_secs {
+        return Err(webb_relayer_utils::Error::Generic(
+            "leaves-freshness lagging-after-secs must be less than stale-after-secs",
+        ));
+    }
+    // an empty token would let anyone in.
+    let empty_grpc_token = config
+        .grpc
+        .as_ref()
+        .map_or(false, |grpc| grpc.enabled && grpc.token.is_empty());
+    if empty_grpc_token {
+        return Err(webb_relayer_utils::Error::Generic(
+            "the token of the gRPC server cannot be empty",
+        ));
+    }
     // check that all required chains are already present in the config.
     for (chain_id, chain_config) in &config.evm {
         let vanchors = chain_config.contracts.iter().filter_map(|c| match c {
@@ -186,8 +442,11 @@ pub fn postloading_process(
         for anchor in vanchors {
             // validate config for data querying
             if config.features.data_query {
-                // check if events watcher is enabled
-                if !anchor.events_watcher.enabled {
+                // check if events watcher is enabled, static contracts
+                // are served without it.
+                if !anchor.events_watcher.enabled
+                    && !anchor.events_watcher.is_static()
+                {
                     tracing::warn!(
                         "!!WARNING!!: In order to enable data querying,
                         event-watcher should also be enabled for ({})",
@@ -273,3 +532,314 @@ pub fn postloading_process(
 
     Ok(config)
 }
+
+/// Makes sure that an anchor is not linked to itself, and that none of its
+/// linked anchors is listed twice.
+fn check_linked_anchors(
+    own: Option<webb_proposals::ResourceId>,
+    anchor: impl Fn() -> String,
+    linked_anchors: &[LinkedAnchorConfig],
+) -> webb_relayer_utils::Result<()> {
+    let mut seen = HashSet::new();
+    for resource_id in
+        linked_anchors.iter().map(LinkedAnchorConfig::resource_id)
+    {
+        if Some(resource_id) == own {
+            return Err(webb_relayer_utils::Error::InvalidLinkedAnchors {
+                anchor: anchor(),
+                reason: "an anchor cannot be linked to itself",
+            });
+        }
+        if !seen.insert(resource_id.to_bytes()) {
+            return Err(webb_relayer_utils::Error::InvalidLinkedAnchors {
+                anchor: anchor(),
+                reason: "the same anchor is linked more than once",
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure that none of the listeners of the relayer, the admin listener
+/// and the gRPC server is configured twice on the same host and port.
+fn check_listen_addresses(
+    config: &WebbRelayerConfig,
+) -> webb_relayer_utils::Result<()> {
+    let port = config.port;
+    let admin = config
+        .admin_listener
+        .iter()
+        .map(|admin| (admin.host.clone(), admin.port));
+    let grpc = config
+        .grpc
+        .iter()
+        .filter(|grpc| grpc.enabled)
+        .map(|grpc| (grpc.host.clone(), grpc.port));
+    let mut seen = HashSet::new();
+    for (host, port) in config
+        .listen_hosts()
+        .into_iter()
+        .map(|host| (host, port))
+        .chain(admin)
+        .chain(grpc)
+    {
+        // the ports picked by the system never collide.
+        if port == 0 {
+            continue;
+        }
+        // `::0` and `::` are the same address.
+        let address = match host.parse::<std::net::IpAddr>() {
+            Ok(ip) => std::net::SocketAddr::new(ip, port).to_string(),
+            Err(_) => format!("{}:{port}", host.to_lowercase()),
+        };
+        if !seen.insert(address.clone()) {
+            return Err(webb_relayer_utils::Error::Listen {
+                address,
+                reason: String::from("configured more than once"),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure that the `event-type-allowlist` of the contract only names
+/// events of its ABI, the custom one if it has an override.
+///
+/// A custom ABI that cannot be loaded is left to the events watcher, which
+/// reports it.
+#[cfg(feature = "evm-runtime")]
+fn check_event_type_allowlist(
+    chain_id: u32,
+    contract: &Contract,
+    abi_overrides: &HashMap<ethereum_types::Address, PathBuf>,
+) -> webb_relayer_utils::Result<()> {
+    use webb::evm::contract::protocol_solidity::{
+        signature_bridge_contract, v_anchor_contract,
+    };
+    use webb::evm::ethers::abi::Abi;
+
+    let (address, events_watcher, abi) = match contract {
+        Contract::VAnchor(cfg) => (
+            cfg.common.address,
+            &cfg.events_watcher,
+            &*v_anchor_contract::VANCHORCONTRACT_ABI,
+        ),
+        Contract::SignatureBridge(cfg) => (
+            cfg.common.address,
+            &cfg.events_watcher,
+            &*signature_bridge_contract::SIGNATUREBRIDGECONTRACT_ABI,
+        ),
+    };
+    let Some(allowlist) = &events_watcher.event_type_allowlist else {
+        return Ok(());
+    };
+    let custom_abi = abi_overrides.get(&address).and_then(|path| {
+        let file = std::fs::File::open(path).ok()?;
+        Abi::load(std::io::BufReader::new(file)).ok()
+    });
+    let abi = custom_abi.as_ref().unwrap_or(abi);
+    match allowlist.iter().find(|name| abi.event(name).is_err()) {
+        Some(event_type) => Err(webb_relayer_utils::Error::UnknownEventType {
+            contract: format!("{address:?} on chain {chain_id}"),
+            event_type: event_type.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Makes sure that a VAnchor synced as `static` has none of the features
+/// that need its events to be watched, or that relay to it.
+fn check_static_contract(
+    cfg: &VAnchorContractConfig,
+) -> Result<(), &'static str> {
+    if !cfg.events_watcher.is_static() {
+        return Ok(());
+    }
+    if cfg.proposal_signing_backend.is_some() {
+        return Err("a static contract cannot have a proposal-signing-backend");
+    }
+    if cfg.smart_anchor_updates.enabled {
+        return Err("a static contract cannot have smart-anchor-updates");
+    }
+    if cfg.max_amount_per_hour.is_some() || cfg.max_delay.is_some() {
+        return Err("withdrawals cannot be relayed to a static contract");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn anchors_linked_to_themselves_should_be_rejected() {
+        let result = load(fixtures("self-linked"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::InvalidLinkedAnchors {
+                reason: "an anchor cannot be linked to itself",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn static_contracts_with_relaying_features_should_be_rejected() {
+        let result = load(fixtures("static-relaying"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::InvalidStaticContract {
+                reason:
+                    "a static contract cannot have a proposal-signing-backend",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn static_substrate_pallets_should_be_rejected() {
+        let result = load(fixtures("static-substrate-pallet"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::InvalidStaticContract {
+                reason: "only the EVM contracts can be static",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn anchors_linked_twice_should_be_rejected() {
+        let result = load(fixtures("duplicate-linked"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::InvalidLinkedAnchors {
+                reason: "the same anchor is linked more than once",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn listeners_configured_twice_should_be_rejected() {
+        let result = load(fixtures("duplicate-hosts"));
+        let Err(webb_relayer_utils::Error::Listen { address, .. }) = result
+        else {
+            panic!("expected a listen error");
+        };
+        assert_eq!(address, "0.0.0.0:9955");
+    }
+
+    #[test]
+    fn event_types_missing_from_the_abi_should_be_rejected() {
+        let result = load(fixtures("unknown-event-type"));
+        let Err(webb_relayer_utils::Error::UnknownEventType {
+            event_type, ..
+        }) = result
+        else {
+            panic!("expected an unknown event type");
+        };
+        assert_eq!(event_type, "NewCommitmnet");
+    }
+
+    #[test]
+    fn zero_max_pending_leaves_should_be_rejected() {
+        let result = load(fixtures("zero-pending-leaves"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::Generic(
+                "max-pending-leaves cannot be zero"
+            ))
+        ));
+    }
+
+    #[test]
+    fn leaves_stale_before_lagging_should_be_rejected() {
+        let result = load(fixtures("inverted-leaves-freshness"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::Generic(
+                "leaves-freshness lagging-after-secs must be less than stale-after-secs"
+            ))
+        ));
+    }
+
+    #[test]
+    fn empty_grpc_tokens_should_be_rejected() {
+        let result = load(fixtures("empty-grpc-token"));
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::Generic(
+                "the token of the gRPC server cannot be empty"
+            ))
+        ));
+    }
+
+    #[test]
+    fn gas_limit_overrides_should_keep_the_case_of_the_methods() {
+        let config = load(fixtures("gas-limit-overrides")).unwrap();
+        let Some(Contract::SignatureBridge(bridge)) =
+            config.evm["goerli"].contracts.first()
+        else {
+            panic!("expected a signature bridge");
+        };
+        let overrides: Vec<_> = bridge
+            .gas_limit_overrides
+            .iter()
+            .map(|o| (o.method.as_str(), o.gas_limit))
+            .collect();
+        assert_eq!(
+            overrides,
+            [
+                ("execute_proposal", 900_000),
+                (
+                    "transferOwnershipWithSignaturePubKey(bytes,uint32,bytes)",
+                    300_000
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_toml_should_point_at_the_line() {
+        let Err(webb_relayer_utils::Error::ConfigParse(e)) =
+            load(fixtures("malformed"))
+        else {
+            panic!("expected a config parse error");
+        };
+        assert_eq!(e.line, 4);
+        assert!(e.file_path.ends_with("main.toml"));
+        assert!(e.to_string().contains("main.toml:4:"));
+    }
+
+    #[test]
+    fn missing_field_should_point_at_its_table() {
+        let Err(webb_relayer_utils::Error::ConfigParse(e)) =
+            load(fixtures("missing-field"))
+        else {
+            panic!("expected a config parse error");
+        };
+        assert_eq!(e.line, 9);
+        assert!(e.message.contains("chain-id"));
+        assert!(e.to_string().contains("[evm.goerli]"));
+    }
+
+    #[test]
+    fn bad_address_should_point_at_its_key() {
+        let Err(webb_relayer_utils::Error::ConfigParse(e)) =
+            load(fixtures("bad-address"))
+        else {
+            panic!("expected a config parse error");
+        };
+        assert_eq!(e.line, 8);
+        assert!(e
+            .message
+            .contains("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Groups the transactions sent to a chain into batches, to pay for a
+//! single transaction instead of one each.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, Mutex};
+use webb_relayer_utils::clock::SharedClock;
+
+use crate::{RelayerContext, Shutdown};
+
+/// Accumulates items into batches, handed to its flusher task once
+/// `max_batch_size` of them are pending, or `flush_timeout` after the first
+/// of them got picked.
+///
+/// The flusher task stops once the accumulator and all its clones are
+/// dropped, or the relayer shuts down, after flushing the pending items.
+#[derive(Debug)]
+pub struct BatchAccumulator<T> {
+    sender: mpsc::UnboundedSender<T>,
+}
+
+impl<T> Clone for BatchAccumulator<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> BatchAccumulator<T> {
+    /// Creates an accumulator, and spawns its flusher as the background
+    /// task `name`, calling `flush` with each batch, one batch at a time.
+    pub fn spawn<F, Fut>(
+        ctx: &RelayerContext,
+        name: impl Into<String>,
+        max_batch_size: usize,
+        flush_timeout: Duration,
+        flush: F,
+    ) -> Self
+    where
+        F: Fn(Vec<T>) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // a restarted flusher picks the items where the failed one left.
+        let receiver = Arc::new(Mutex::new(receiver));
+        let max_batch_size = max_batch_size.max(1);
+        let clock = ctx.clock().clone();
+        let shutdown = ctx.shutdown_signal();
+        ctx.background_tasks().spawn(name, move || {
+            let receiver = receiver.clone();
+            let clock = clock.clone();
+            let shutdown = shutdown.clone();
+            let flush = flush.clone();
+            async move {
+                let mut receiver = receiver.lock().await;
+                run_flusher(
+                    &mut receiver,
+                    max_batch_size,
+                    flush_timeout,
+                    clock,
+                    shutdown,
+                    flush,
+                )
+                .await;
+                anyhow::Ok(())
+            }
+        });
+        Self { sender }
+    }
+
+    /// Adds the item to the pending batch.
+    ///
+    /// Gives the item back if the flusher task is gone.
+    pub fn push(&self, item: T) -> Result<(), T> {
+        self.sender.send(item).map_err(|e| e.0)
+    }
+
+    /// Whether the flusher task is gone, so the items pushed are rejected.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+async fn run_flusher<T, F, Fut>(
+    receiver: &mut mpsc::UnboundedReceiver<T>,
+    max_batch_size: usize,
+    flush_timeout: Duration,
+    clock: SharedClock,
+    mut shutdown: Shutdown,
+    flush: F,
+) where
+    F: Fn(Vec<T>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        let first = tokio::select! {
+            biased;
+            _ = shutdown.recv() => break,
+            item = receiver.recv() => match item {
+                Some(item) => item,
+                None => return,
+            },
+        };
+        let mut batch = vec![first];
+        let mut deadline = clock.sleep(flush_timeout);
+        while batch.len() < max_batch_size {
+            tokio::select! {
+                biased;
+                // flushed right away on shutdown.
+                _ = shutdown.recv() => break,
+                item = receiver.recv() => match item {
+                    Some(item) => batch.push(item),
+                    None => break,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+        tracing::debug!(batch_size = batch.len(), "Flushing a batch");
+        flush(batch).await;
+    }
+    // the items pushed until now are flushed, the next ones are rejected.
+    receiver.close();
+    let mut batch = Vec::new();
+    while let Ok(item) = receiver.try_recv() {
+        batch.push(item);
+        if batch.len() == max_batch_size {
+            flush(std::mem::take(&mut batch)).await;
+        }
+    }
+    if !batch.is_empty() {
+        flush(batch).await;
+    }
+    tracing::debug!("Batch flusher stopped on shutdown");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_store::SledStore;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    /// A context running its background tasks.
+    fn context(clock: &FrozenClock) -> RelayerContext {
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store)
+            .unwrap()
+            .with_clock(clock.shared());
+        let mut supervisor = ctx.background_tasks().supervisor().unwrap();
+        let supervisor_ctx = ctx.clone();
+        tokio::spawn(
+            async move { supervisor.supervise(&supervisor_ctx).await },
+        );
+        ctx
+    }
+
+    fn accumulator(
+        ctx: &RelayerContext,
+    ) -> (BatchAccumulator<u32>, mpsc::UnboundedReceiver<Vec<u32>>) {
+        let (flushed, batches) = mpsc::unbounded_channel();
+        let accumulator = BatchAccumulator::spawn(
+            ctx,
+            "batch",
+            3,
+            Duration::from_secs(6),
+            move |batch| {
+                let _ = flushed.send(batch);
+                async {}
+            },
+        );
+        (accumulator, batches)
+    }
+
+    /// Lets the background tasks start, and pick the pending items.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn full_batches_should_be_flushed_right_away() {
+        let clock = FrozenClock::default();
+        let ctx = context(&clock);
+        let (accumulator, mut batches) = accumulator(&ctx);
+        for item in 0..7 {
+            accumulator.push(item).unwrap();
+        }
+        assert_eq!(batches.recv().await, Some(vec![0, 1, 2]));
+        assert_eq!(batches.recv().await, Some(vec![3, 4, 5]));
+        // the last one waits for more items, or the timeout.
+        settle().await;
+        assert!(batches.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn pending_batches_should_be_flushed_after_the_timeout() {
+        let clock = FrozenClock::default();
+        let ctx = context(&clock);
+        let (accumulator, mut batches) = accumulator(&ctx);
+        accumulator.push(1).unwrap();
+        accumulator.push(2).unwrap();
+        settle().await;
+        clock.advance(Duration::from_secs(5));
+        settle().await;
+        assert!(batches.try_recv().is_err());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(batches.recv().await, Some(vec![1, 2]));
+
+        // the pending items are flushed once the accumulator is dropped.
+        accumulator.push(3).unwrap();
+        drop(accumulator);
+        assert_eq!(batches.recv().await, Some(vec![3]));
+        settle().await;
+        assert!(batches.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn pending_batches_should_be_flushed_on_shutdown() {
+        let clock = FrozenClock::default();
+        let ctx = context(&clock);
+        let (accumulator, mut batches) = accumulator(&ctx);
+        accumulator.push(1).unwrap();
+        accumulator.push(2).unwrap();
+        settle().await;
+        ctx.shutdown();
+
+        // without waiting for the timeout.
+        assert_eq!(batches.recv().await, Some(vec![1, 2]));
+        assert!(accumulator.is_closed());
+        assert_eq!(accumulator.push(3), Err(3));
+    }
+}
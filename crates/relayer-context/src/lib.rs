@@ -16,9 +16,11 @@
 //! # Relayer Context Module 🕸️
 //!
 //! A module for managing the context of the relayer.
+use serde::Serialize;
 use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
 
 use webb::evm::ethers;
 #[cfg(feature = "evm")]
@@ -38,29 +40,81 @@ use webb::substrate::subxt;
 use webb_price_oracle_backends::{
     CachedPriceBackend, CoinGeckoBackend, DummyPriceBackend, PriceOracleMerger,
 };
-use webb_relayer_store::SledStore;
-use webb_relayer_utils::metric::{self, Metrics};
+use webb_proposals::TypedChainId;
+use webb_relayer_store::{BridgeCommandChannels, SledStore};
+use webb_relayer_utils::clock::{RelayerRng, SharedClock, SystemClock};
+use webb_relayer_utils::metric::{
+    self, ChainMetrics, ChainMetricsUpdate, Metrics,
+};
 
 mod ethers_retry_policy;
 use ethers_retry_policy::WebbHttpRetryPolicy;
 use webb_relayer_utils::multi_provider::MultiProvider;
 
+mod provider_usage;
+use provider_usage::ProviderUsageRecorder;
+
+/// The relayer accounts on the Substrate nodes.
+pub mod substrate_accounts;
+use substrate_accounts::SubstrateAccounts;
+
+/// The background tasks of the relayer.
+pub mod tasks;
+use tasks::BackgroundTasks;
+
+/// Batching of the transactions sent to a chain.
+pub mod batch;
+use batch::BatchAccumulator;
+
+/// The limit of the proposals in flight on each chain.
+pub mod proposal_permits;
+use proposal_permits::ProposalPermits;
+
+/// The requests to reconnect a chain to its endpoints.
+pub mod reconnects;
+use reconnects::ChainReconnects;
+
 type EthersClient = Provider<RetryClient<MultiProvider<Http>>>;
+/// The provider of a chain, signing its transactions with the relayer wallet.
+pub type EvmSignerClient = SignerMiddleware<Arc<EthersClient>, LocalWallet>;
+
+/// A call waiting in a Substrate batch.
+#[cfg(feature = "substrate")]
+pub struct BatchedCall {
+    /// The call to send in the batch.
+    pub call: webb::substrate::tangle_runtime::api::runtime_types::tangle_standalone_runtime::RuntimeCall,
+    /// Notified once the batch got sent to the node.
+    pub sent: tokio::sync::oneshot::Sender<()>,
+    /// Where to send the hash of the batch once it is finalized, or why the
+    /// call failed.
+    pub outcome: tokio::sync::oneshot::Sender<Result<types::H256, String>>,
+}
+
+/// The last transaction the relayer successfully submitted on a chain.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastRelayedTx {
+    /// The hash of the transaction.
+    pub tx_hash: types::H256,
+    /// The time at which the transaction got finalized.
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
 
 /// RelayerContext contains Relayer's configuration and shutdown signal.
 #[derive(Clone)]
 pub struct RelayerContext {
     /// The configuration of the relayer.
     pub config: webb_relayer_config::WebbRelayerConfig,
-    /// Broadcasts a shutdown signal to all active connections.
+    /// Signals the shutdown to all active connections and tasks.
     ///
     /// The initial `shutdown` trigger is provided by the `run` caller. The
     /// server is responsible for gracefully shutting down active connections.
-    /// When a connection task is spawned, it is passed a broadcast receiver
-    /// handle. When a graceful shutdown is initiated, a `()` value is sent via
-    /// the broadcast::Sender. Each active connection receives it, reaches a
-    /// safe terminal state, and completes the task.
-    notify_shutdown: broadcast::Sender<()>,
+    /// When a connection task is spawned, it is passed a [`Shutdown`] handle.
+    /// When a graceful shutdown is initiated, the token gets cancelled. Each
+    /// active connection sees it, reaches a safe terminal state, and
+    /// completes the task. The token stays cancelled, so a task started, or
+    /// restarted, after the shutdown sees it too.
+    notify_shutdown: CancellationToken,
     /// Represents the metrics for the relayer
     pub metrics: Arc<Mutex<metric::Metrics>>,
     store: SledStore,
@@ -71,6 +125,32 @@ pub struct RelayerContext {
 
     /// Evm Providers Cache.
     evm_providers: Arc<HashMap<types::U256, Arc<EthersClient>>>,
+    /// Evm Signer Clients Cache, filled on first use.
+    evm_signer_clients: Arc<RwLock<HashMap<types::U256, Arc<EvmSignerClient>>>>,
+    /// The last transaction relayed on each chain, by the tx queues and the
+    /// private transaction handlers.
+    last_relayed_txs: Arc<RwLock<HashMap<TypedChainId, LastRelayedTx>>>,
+    /// The metrics of each EVM chain, by chain id, updated by the tx queues
+    /// and the events watchers.
+    chain_metrics: Arc<RwLock<HashMap<u32, ChainMetrics>>>,
+    /// The clock the delays and expiries are measured with.
+    clock: SharedClock,
+    /// The randomness used to pick delays.
+    rng: RelayerRng,
+    /// The relayer account on every Substrate node.
+    substrate_accounts: SubstrateAccounts,
+    /// The batches of calls sent to each Substrate node, by chain id,
+    /// created on first use.
+    #[cfg(feature = "substrate")]
+    substrate_batches: Arc<Mutex<HashMap<u32, BatchAccumulator<BatchedCall>>>>,
+    /// The background tasks, such as the events watchers.
+    background_tasks: BackgroundTasks,
+    /// Hands the bridge commands over to the bridge watchers.
+    bridge_commands: BridgeCommandChannels,
+    /// The permits of the proposals in flight on each chain.
+    proposal_permits: ProposalPermits,
+    /// The requests to reconnect a chain, kept across config reloads.
+    reconnects: ChainReconnects,
 }
 
 impl RelayerContext {
@@ -79,8 +159,18 @@ impl RelayerContext {
         config: webb_relayer_config::WebbRelayerConfig,
         store: SledStore,
     ) -> webb_relayer_utils::Result<Self> {
-        let (notify_shutdown, _) = broadcast::channel(2);
-        let metrics = Arc::new(Mutex::new(Metrics::new()?));
+        let notify_shutdown = CancellationToken::new();
+        let metrics = Metrics::with_policy(config.metrics.policy())?;
+        let (provider_calls, provider_errors) = (
+            metrics.provider_calls.clone(),
+            metrics.provider_errors.clone(),
+        );
+        let proposal_permits = ProposalPermits::new(
+            config.max_concurrent_proposals_per_chain,
+            Duration::from_secs(config.proposal_permit_timeout_secs),
+            metrics.concurrent_proposals_in_flight.clone(),
+        );
+        let metrics = Arc::new(Mutex::new(metrics));
 
         let dummy_backend = {
             let price_map = config
@@ -117,34 +207,15 @@ impl RelayerContext {
             etherscan_clients.insert(etherscan_config.chain_id.into(), client);
         }
 
-        // Create a Map for all EVM Chains
-        let mut evm_providers = HashMap::new();
-        for (_, chain_config) in config.evm.iter() {
-            let mut providers = Vec::new();
-            match chain_config.http_endpoint.clone() {
-                webb_relayer_config::evm::HttpEndpoint::Single(rpc_url) => {
-                    let provider = Http::new(rpc_url);
-                    providers.push(provider);
-                }
-                webb_relayer_config::evm::HttpEndpoint::Multiple(rpc_urls) => {
-                    rpc_urls.iter().for_each(|rpc_url| {
-                        let provider = Http::new(rpc_url.clone());
-                        providers.push(provider);
-                    });
-                }
-            }
+        let evm_providers = build_evm_providers(
+            &config,
+            &store,
+            &provider_calls,
+            &provider_errors,
+        );
 
-            let multi_provider = MultiProvider::new(Arc::new(providers));
-            // Wrap the provider with a retry client.
-            let retry_client = RetryClientBuilder::default()
-                .timeout_retries(u32::MAX)
-                .rate_limit_retries(u32::MAX)
-                .build(multi_provider, WebbHttpRetryPolicy::boxed());
-
-            let provider = Arc::new(Provider::new(retry_client));
-
-            evm_providers.insert(chain_config.chain_id.into(), provider);
-        }
+        let bridge_commands =
+            BridgeCommandChannels::new(config.bridge_command_buffer);
 
         Ok(Self {
             config,
@@ -154,15 +225,139 @@ impl RelayerContext {
             price_oracle,
             etherscan_clients: Arc::new(etherscan_clients),
             evm_providers: Arc::new(evm_providers),
+            evm_signer_clients: Default::default(),
+            last_relayed_txs: Default::default(),
+            chain_metrics: Default::default(),
+            clock: SystemClock::shared(),
+            rng: RelayerRng::default(),
+            substrate_accounts: SubstrateAccounts::default(),
+            #[cfg(feature = "substrate")]
+            substrate_batches: Default::default(),
+            background_tasks: BackgroundTasks::default(),
+            bridge_commands,
+            proposal_permits,
+            reconnects: ChainReconnects::default(),
         })
     }
-    /// Returns a broadcast receiver handle for the shutdown signal.
+    /// Replaces the clock of the context, for tests controlling the time.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Replaces the rng of the context, for tests needing deterministic
+    /// random picks.
+    pub fn with_rng(mut self, rng: RelayerRng) -> Self {
+        self.rng = rng;
+        self
+    }
+    /// Replaces the relayer accounts of the context, to share them with
+    /// the health endpoint, which is up before the context.
+    pub fn with_substrate_accounts(
+        mut self,
+        substrate_accounts: SubstrateAccounts,
+    ) -> Self {
+        self.substrate_accounts = substrate_accounts;
+        self
+    }
+    /// Replaces the background tasks of the context, to supervise them
+    /// while the context is being set up.
+    pub fn with_background_tasks(
+        mut self,
+        background_tasks: BackgroundTasks,
+    ) -> Self {
+        self.background_tasks = background_tasks;
+        self
+    }
+    /// Returns the clock the delays and expiries are measured with.
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+    /// Returns the randomness used to pick delays.
+    pub fn rng(&self) -> &RelayerRng {
+        &self.rng
+    }
+    /// Returns the relayer account on every Substrate node.
+    pub fn substrate_accounts(&self) -> &SubstrateAccounts {
+        &self.substrate_accounts
+    }
+    /// Returns the batch of calls sent to the given Substrate node, spawning
+    /// it with `spawn` if there is none yet, or its flusher stopped.
+    #[cfg(feature = "substrate")]
+    pub async fn substrate_batch(
+        &self,
+        chain_id: u32,
+        spawn: impl FnOnce() -> BatchAccumulator<BatchedCall>,
+    ) -> BatchAccumulator<BatchedCall> {
+        let mut batches = self.substrate_batches.lock().await;
+        match batches.get(&chain_id) {
+            Some(batch) if !batch.is_closed() => batch.clone(),
+            _ => {
+                let batch = spawn();
+                batches.insert(chain_id, batch.clone());
+                batch
+            }
+        }
+    }
+    /// Returns where the background tasks of the relayer are spawned.
+    pub fn background_tasks(&self) -> &BackgroundTasks {
+        &self.background_tasks
+    }
+    /// Returns the channels of the commands sent to the bridge watchers.
+    pub fn bridge_commands(&self) -> &BridgeCommandChannels {
+        &self.bridge_commands
+    }
+    /// Returns the permits of the proposals in flight on each chain.
+    pub fn proposal_permits(&self) -> &ProposalPermits {
+        &self.proposal_permits
+    }
+    /// Returns where the requests to reconnect a chain are sent.
+    pub fn reconnects(&self) -> &ChainReconnects {
+        &self.reconnects
+    }
+    /// Returns a handle for the shutdown signal, which is received even if
+    /// the shutdown happened before this call.
     pub fn shutdown_signal(&self) -> Shutdown {
-        Shutdown::new(self.notify_shutdown.subscribe())
+        Shutdown::new(self.notify_shutdown.clone())
     }
-    /// Sends a shutdown signal to all subscribed tasks/connections.
+    /// Sends a shutdown signal to all tasks/connections.
     pub fn shutdown(&self) {
-        let _ = self.notify_shutdown.send(());
+        self.notify_shutdown.cancel();
+    }
+    /// Returns a copy of the context with its own shutdown signal, for the
+    /// services that may be stopped alone, like those of a single chain.
+    ///
+    /// Shutting down this context leaves the others running, while the
+    /// shutdown of this one is forwarded to it.
+    pub fn scoped(&self) -> Self {
+        Self {
+            notify_shutdown: self.notify_shutdown.child_token(),
+            ..self.clone()
+        }
+    }
+    /// Returns a copy of the context using the reloaded `config`.
+    ///
+    /// The providers and signers are built again from the new config, the
+    /// rest, like the store, the metrics and the shutdown signal, is
+    /// shared with this context.
+    pub async fn reconfigure(
+        &self,
+        config: webb_relayer_config::WebbRelayerConfig,
+    ) -> Self {
+        let evm_providers = {
+            let metrics = self.metrics.lock().await;
+            build_evm_providers(
+                &config,
+                &self.store,
+                &metrics.provider_calls,
+                &metrics.provider_errors,
+            )
+        };
+        Self {
+            config,
+            evm_providers: Arc::new(evm_providers),
+            evm_signer_clients: Default::default(),
+            ..self.clone()
+        }
     }
     /// Returns a new `EthereumProvider` for the relayer.
     ///
@@ -211,6 +406,31 @@ impl RelayerContext {
         let wallet = LocalWallet::from(key).with_chain_id(chain_id);
         Ok(wallet)
     }
+    /// Returns the provider of the chain wrapped with the relayer wallet, so
+    /// it signs the transactions it sends.
+    ///
+    /// The client is built once per chain, and cached afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain_id` - A string representing the chain id.
+    #[cfg(feature = "evm")]
+    pub async fn evm_signer_client<I: Into<types::U256>>(
+        &self,
+        chain_id: I,
+    ) -> webb_relayer_utils::Result<Arc<EvmSignerClient>> {
+        let chain_id: types::U256 = chain_id.into();
+        if let Some(client) =
+            self.evm_signer_clients.read().await.get(&chain_id)
+        {
+            return Ok(client.clone());
+        }
+        let wallet = self.evm_wallet(chain_id).await?;
+        let provider = self.evm_provider(chain_id).await?;
+        let client = Arc::new(SignerMiddleware::new(provider, wallet));
+        let mut clients = self.evm_signer_clients.write().await;
+        Ok(clients.entry(chain_id).or_insert(client).clone())
+    }
     /// Sets up and returns a Substrate client for the relayer.
     ///
     /// # Arguments
@@ -264,6 +484,55 @@ impl RelayerContext {
         &self.store
     }
 
+    /// Records `tx_hash` as the last transaction relayed on the given chain.
+    pub async fn record_relayed_tx(
+        &self,
+        chain_id: TypedChainId,
+        tx_hash: types::H256,
+    ) {
+        let entry = LastRelayedTx {
+            tx_hash,
+            timestamp: self.clock.now().into(),
+        };
+        self.last_relayed_txs.write().await.insert(chain_id, entry);
+    }
+
+    /// Returns the last transaction relayed on every chain we relayed at least one.
+    pub async fn last_relayed_txs(
+        &self,
+    ) -> HashMap<TypedChainId, LastRelayedTx> {
+        self.last_relayed_txs.read().await.clone()
+    }
+
+    /// Updates the metrics of the given EVM chain, along with their
+    /// Prometheus counterparts.
+    pub async fn update_chain_metrics(
+        &self,
+        chain_id: u32,
+        update: ChainMetricsUpdate,
+    ) {
+        let mut chain_metrics = self.chain_metrics.write().await;
+        let entry = chain_metrics
+            .entry(chain_id)
+            .or_insert_with(|| ChainMetrics::new(chain_id));
+        entry.apply(update);
+        self.metrics
+            .lock()
+            .await
+            .record_chain_metrics(entry, update);
+    }
+
+    /// Returns the metrics of the given EVM chain, with nothing recorded if
+    /// none got updated yet.
+    pub async fn chain_metrics(&self, chain_id: u32) -> ChainMetrics {
+        self.chain_metrics
+            .read()
+            .await
+            .get(&chain_id)
+            .cloned()
+            .unwrap_or_else(|| ChainMetrics::new(chain_id))
+    }
+
     /// Returns a price oracle for fetching token prices.
     pub fn price_oracle(&self) -> Arc<PriceOracleMerger> {
         self.price_oracle.clone()
@@ -300,45 +569,77 @@ impl RelayerContext {
     }
 }
 
+/// Builds the providers of all the EVM chains of the config, labelled and
+/// observed for the provider usage metrics.
+fn build_evm_providers(
+    config: &webb_relayer_config::WebbRelayerConfig,
+    store: &SledStore,
+    provider_calls: &prometheus::CounterVec,
+    provider_errors: &prometheus::CounterVec,
+) -> HashMap<types::U256, Arc<EthersClient>> {
+    let mut evm_providers = HashMap::new();
+    for (_, chain_config) in config.evm.iter() {
+        let mut providers = Vec::new();
+        match chain_config.http_endpoint.clone() {
+            webb_relayer_config::evm::HttpEndpoint::Single(rpc_url) => {
+                let provider = Http::new(rpc_url);
+                providers.push(provider);
+            }
+            webb_relayer_config::evm::HttpEndpoint::Multiple(rpc_urls) => {
+                rpc_urls.iter().for_each(|rpc_url| {
+                    let provider = Http::new(rpc_url.clone());
+                    providers.push(provider);
+                });
+            }
+        }
+
+        let usage_recorder = ProviderUsageRecorder::new(
+            chain_config.chain_id,
+            store.clone(),
+            provider_calls.clone(),
+            provider_errors.clone(),
+        );
+        let multi_provider = MultiProvider::new(Arc::new(providers))
+            .with_labels(chain_config.http_endpoint_labels())
+            .with_observer(Arc::new(usage_recorder));
+        // Wrap the provider with a retry client.
+        let retry_client = RetryClientBuilder::default()
+            .timeout_retries(u32::MAX)
+            .rate_limit_retries(u32::MAX)
+            .build(multi_provider, WebbHttpRetryPolicy::boxed());
+
+        let provider = Arc::new(
+            Provider::new(retry_client)
+                .interval(chain_config.provider_interval()),
+        );
+
+        evm_providers.insert(chain_config.chain_id.into(), provider);
+    }
+    evm_providers
+}
+
 /// Listens for the server shutdown signal.
 ///
-/// Shutdown is signalled using a `broadcast::Receiver`. Only a single value is
-/// ever sent. Once a value has been sent via the broadcast channel, the server
-/// should shutdown.
+/// Shutdown is signalled by cancelling a `CancellationToken`, which is only
+/// ever done once. Once the token got cancelled, the server should shutdown.
 ///
-/// The `Shutdown` struct listens for the signal and tracks that the signal has
-/// been received. Callers may query for whether the shutdown signal has been
-/// received or not.
-#[derive(Debug)]
+/// The `Shutdown` struct listens for the signal, and sees it even if it got
+/// sent before the struct was created.
+#[derive(Debug, Clone)]
 pub struct Shutdown {
-    /// `true` if the shutdown signal has been received
-    shutdown: bool,
-
-    /// The receive half of the channel used to listen for shutdown.
-    notify: broadcast::Receiver<()>,
+    /// The token cancelled on shutdown.
+    notify: CancellationToken,
 }
 
 impl Shutdown {
-    /// Create a new `Shutdown` backed by the given `broadcast::Receiver`.
-    pub fn new(notify: broadcast::Receiver<()>) -> Shutdown {
-        Shutdown {
-            shutdown: false,
-            notify,
-        }
+    /// Create a new `Shutdown` backed by the given `CancellationToken`.
+    pub fn new(notify: CancellationToken) -> Shutdown {
+        Shutdown { notify }
     }
 
     /// Receive the shutdown notice, waiting if necessary.
     pub async fn recv(&mut self) {
-        // If the shutdown signal has already been received, then return
-        // immediately.
-        if self.shutdown {
-            return;
-        }
-
-        // Cannot receive a "lag error" as only one value is ever sent.
-        let _ = self.notify.recv().await;
-
-        // Remember that the signal has been received.
-        self.shutdown = true;
+        // returns immediately once the token got cancelled.
+        self.notify.cancelled().await;
     }
 }
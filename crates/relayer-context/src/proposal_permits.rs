@@ -0,0 +1,196 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Limits the proposals in flight on each chain, from their enqueueing to
+//! their execution, so they do not fight over the nonces of the relayer
+//! account.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use prometheus::core::{AtomicF64, GenericGauge};
+use prometheus::GaugeVec;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The permits of the proposals in flight, up to `max_per_chain` on each
+/// chain.
+#[derive(Debug, Clone)]
+pub struct ProposalPermits {
+    max_per_chain: usize,
+    timeout: Duration,
+    semaphores: Arc<Mutex<HashMap<u32, Arc<Semaphore>>>>,
+    in_flight: GaugeVec,
+}
+
+/// A permit to have a proposal in flight on a chain, given back once
+/// dropped.
+#[derive(Debug)]
+pub struct ProposalPermit {
+    _permit: OwnedSemaphorePermit,
+    in_flight: GenericGauge<AtomicF64>,
+}
+
+impl Drop for ProposalPermit {
+    fn drop(&mut self) {
+        self.in_flight.dec();
+    }
+}
+
+impl ProposalPermits {
+    /// Creates the permits, held for `timeout` at most, reporting the
+    /// proposals in flight on each chain with the `in_flight` gauge.
+    pub fn new(
+        max_per_chain: u8,
+        timeout: Duration,
+        in_flight: GaugeVec,
+    ) -> Self {
+        Self {
+            max_per_chain: usize::from(max_per_chain.max(1)),
+            timeout,
+            semaphores: Default::default(),
+            in_flight,
+        }
+    }
+
+    /// Waits for a permit to have a proposal in flight on the chain.
+    ///
+    /// A warning is logged once it waited for half the timeout, as the
+    /// proposals in flight are likely not getting executed.
+    pub async fn acquire(&self, chain_id: u32) -> ProposalPermit {
+        let acquire = self.semaphore(chain_id).acquire_owned();
+        tokio::pin!(acquire);
+        let permit = tokio::select! {
+            permit = &mut acquire => permit,
+            _ = tokio::time::sleep(self.timeout / 2) => {
+                tracing::warn!(
+                    chain_id,
+                    max_per_chain = self.max_per_chain,
+                    "Still waiting for the proposals in flight to be executed",
+                );
+                acquire.await
+            }
+        };
+        let in_flight =
+            self.in_flight.with_label_values(&[&chain_id.to_string()]);
+        in_flight.inc();
+        ProposalPermit {
+            _permit: permit.expect("the semaphores are never closed"),
+            in_flight,
+        }
+    }
+
+    /// Holds the `permit` in the background until `executed` completes, or
+    /// for the timeout at most.
+    pub fn release_once<F>(&self, permit: ProposalPermit, executed: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let timeout = self.timeout;
+        tokio::spawn(async move {
+            if tokio::time::timeout(timeout, executed).await.is_err() {
+                tracing::warn!(
+                    "Released the permit of a proposal not executed in time"
+                );
+            }
+            drop(permit);
+        });
+    }
+
+    /// The number of proposals in flight on the chain.
+    pub fn in_flight(&self, chain_id: u32) -> usize {
+        self.max_per_chain - self.semaphore(chain_id).available_permits()
+    }
+
+    fn semaphore(&self, chain_id: u32) -> Arc<Semaphore> {
+        let mut semaphores =
+            self.semaphores.lock().unwrap_or_else(|e| e.into_inner());
+        semaphores
+            .entry(chain_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_chain)))
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Opts;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn permits(max_per_chain: u8, timeout: Duration) -> ProposalPermits {
+        let in_flight = GaugeVec::new(
+            Opts::new("concurrent_proposals_in_flight", "test"),
+            &["chain_id"],
+        )
+        .unwrap();
+        ProposalPermits::new(max_per_chain, timeout, in_flight)
+    }
+
+    #[tokio::test]
+    async fn proposals_in_flight_should_be_limited_per_chain() {
+        let permits = permits(10, Duration::from_secs(60));
+        let holding = Arc::new(AtomicUsize::new(0));
+        let max_holding = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..15)
+            .map(|_| {
+                let permits = permits.clone();
+                let holding = holding.clone();
+                let max_holding = max_holding.clone();
+                tokio::spawn(async move {
+                    let permit = permits.acquire(5).await;
+                    let now = holding.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_holding.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    holding.fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                })
+            })
+            .collect();
+        // another chain is not held up.
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let other = permits.acquire(6).await;
+        assert_eq!(permits.in_flight(6), 1);
+        drop(other);
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(max_holding.load(Ordering::SeqCst) <= 10);
+        assert_eq!(permits.in_flight(5), 0);
+        let gauge = permits.in_flight.with_label_values(&["5"]);
+        assert_eq!(gauge.get(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn permits_should_be_released_once_executed_or_timed_out() {
+        let permits = permits(1, Duration::from_millis(50));
+        let (executed, rx) = tokio::sync::oneshot::channel::<()>();
+        let permit = permits.acquire(5).await;
+        permits.release_once(permit, async move {
+            let _ = rx.await;
+        });
+        assert_eq!(permits.in_flight(5), 1);
+        executed.send(()).unwrap();
+        let permit = permits.acquire(5).await;
+        assert_eq!(permits.in_flight(5), 1);
+
+        // never executed.
+        permits.release_once(permit, std::future::pending());
+        let started = std::time::Instant::now();
+        let _permit = permits.acquire(5).await;
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use prometheus::CounterVec;
+use webb_relayer_store::{ProviderUsage, ProviderUsageStore, SledStore};
+use webb_relayer_utils::multi_provider::{
+    RpcCall, RpcCallKind, RpcCallObserver,
+};
+use webb_relayer_utils::probe;
+
+/// How often the calls counted in memory get added to the store.
+pub const PROVIDER_USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Attributes the calls served by the providers of an EVM chain to their
+/// provider label, in the metrics, the store and the audit log.
+///
+/// The calls are counted in memory, and added to the store at most every
+/// [`PROVIDER_USAGE_FLUSH_INTERVAL`], rather than writing to the store on
+/// every call.
+#[derive(Debug)]
+pub struct ProviderUsageRecorder {
+    chain_id: u32,
+    store: SledStore,
+    calls: CounterVec,
+    errors: CounterVec,
+    flush_interval: Duration,
+    pending: Mutex<PendingUsage>,
+}
+
+/// The calls counted since the last flush, by provider and day.
+#[derive(Debug)]
+struct PendingUsage {
+    usage: HashMap<(String, u64), ProviderUsage>,
+    flushed_at: Instant,
+}
+
+impl ProviderUsageRecorder {
+    pub fn new(
+        chain_id: u32,
+        store: SledStore,
+        calls: CounterVec,
+        errors: CounterVec,
+    ) -> Self {
+        Self {
+            chain_id,
+            store,
+            calls,
+            errors,
+            flush_interval: PROVIDER_USAGE_FLUSH_INTERVAL,
+            pending: Mutex::new(PendingUsage {
+                usage: HashMap::new(),
+                flushed_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Adds the calls counted so far to the store.
+    pub fn flush(&self) {
+        let usage: Vec<_> = {
+            let mut pending =
+                self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.flushed_at = Instant::now();
+            pending.usage.drain().map(|(_, usage)| usage).collect()
+        };
+        if usage.is_empty() {
+            return;
+        }
+        if let Err(e) = self.store.add_provider_usage(&usage) {
+            tracing::warn!(
+                chain_id = self.chain_id,
+                "Failed to record the provider usage: {e}"
+            );
+        }
+    }
+}
+
+impl RpcCallObserver for ProviderUsageRecorder {
+    fn on_call(&self, call: RpcCall<'_>) {
+        let chain_id = self.chain_id.to_string();
+        self.calls
+            .with_label_values(&[&chain_id, call.provider, call.kind.as_str()])
+            .inc();
+        if call.failed {
+            self.errors
+                .with_label_values(&[&chain_id, call.provider])
+                .inc();
+        }
+        let day = chrono::Utc::now().timestamp() as u64 / (24 * 60 * 60);
+        let should_flush = {
+            let mut pending =
+                self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending
+                .usage
+                .entry((call.provider.to_string(), day))
+                .or_insert_with(|| ProviderUsage {
+                    provider: call.provider.to_string(),
+                    day,
+                    ..Default::default()
+                })
+                .count_call(call.kind, call.failed);
+            pending.flushed_at.elapsed() >= self.flush_interval
+        };
+        if should_flush {
+            self.flush();
+        }
+        if call.kind == RpcCallKind::Submission {
+            tracing::debug!(
+                target: probe::TARGET,
+                kind = %probe::Kind::TxQueue,
+                ty = "EVM",
+                chain_id = self.chain_id,
+                provider = call.provider,
+                method = call.method,
+                tx_hash = ?call.tx_hash,
+                submitted = !call.failed,
+            );
+        }
+    }
+}
+
+impl Drop for ProviderUsageRecorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Opts;
+
+    fn new_recorder(
+        store: SledStore,
+        flush_interval: Duration,
+    ) -> ProviderUsageRecorder {
+        let calls = CounterVec::new(
+            Opts::new("calls", "calls"),
+            &["chain_id", "provider", "kind"],
+        )
+        .unwrap();
+        let errors = CounterVec::new(
+            Opts::new("errors", "errors"),
+            &["chain_id", "provider"],
+        )
+        .unwrap();
+        let mut recorder = ProviderUsageRecorder::new(1, store, calls, errors);
+        recorder.flush_interval = flush_interval;
+        recorder
+    }
+
+    fn call(provider: &str, kind: RpcCallKind, failed: bool) -> RpcCall<'_> {
+        RpcCall {
+            provider,
+            method: "eth_call",
+            kind,
+            failed,
+            tx_hash: None,
+        }
+    }
+
+    #[test]
+    fn provider_calls_should_be_added_to_the_store_in_batches() {
+        let store = SledStore::temporary().unwrap();
+        let recorder = new_recorder(store.clone(), Duration::from_secs(3600));
+        recorder.on_call(call("infura", RpcCallKind::Request, false));
+        recorder.on_call(call("infura", RpcCallKind::LogQuery, true));
+        recorder.on_call(call("alchemy", RpcCallKind::Submission, false));
+        assert!(
+            store.get_provider_usage(0).unwrap().is_empty(),
+            "the calls are only counted in memory until the next flush"
+        );
+
+        drop(recorder);
+        let usage = store.get_provider_usage(0).unwrap();
+        let counts: Vec<_> = usage
+            .iter()
+            .map(|u| (u.provider.as_str(), u.requests, u.errors))
+            .collect();
+        assert_eq!(counts, [("alchemy", 1, 0), ("infura", 2, 1)]);
+
+        // once the interval elapsed, the next call flushes them.
+        let recorder = new_recorder(store.clone(), Duration::ZERO);
+        recorder.on_call(call("infura", RpcCallKind::Request, false));
+        let usage = store.get_provider_usage(0).unwrap();
+        assert_eq!(usage[1].requests, 3);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The requests to reconnect the services of a chain to its endpoints,
+//! such as after the RPC provider of the chain got rotated.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, oneshot};
+use webb_proposals::TypedChainId;
+
+/// A request to reconnect the services of a chain, answered once they are
+/// restarted, or failed to be.
+#[derive(Debug)]
+pub struct ReconnectRequest {
+    /// The chain to reconnect.
+    pub chain: TypedChainId,
+    reply: oneshot::Sender<Result<(), String>>,
+}
+
+impl ReconnectRequest {
+    /// Answers the request with the outcome of the reconnection.
+    pub fn respond(self, result: Result<(), String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Hands the requests to reconnect a chain over to the relayer, which
+/// restarts the services of the chain with new connections.
+#[derive(Debug, Clone)]
+pub struct ChainReconnects {
+    requests: mpsc::UnboundedSender<ReconnectRequest>,
+    pending: Arc<Mutex<Option<mpsc::UnboundedReceiver<ReconnectRequest>>>>,
+}
+
+impl Default for ChainReconnects {
+    fn default() -> Self {
+        let (requests, pending) = mpsc::unbounded_channel();
+        Self {
+            requests,
+            pending: Arc::new(Mutex::new(Some(pending))),
+        }
+    }
+}
+
+impl ChainReconnects {
+    /// Asks for the services of the `chain` to be reconnected, and waits
+    /// for the outcome.
+    ///
+    /// Returns `None` if nothing handles the requests anymore.
+    pub async fn request(
+        &self,
+        chain: TypedChainId,
+    ) -> Option<Result<(), String>> {
+        let (reply, outcome) = oneshot::channel();
+        self.requests.send(ReconnectRequest { chain, reply }).ok()?;
+        outcome.await.ok()
+    }
+
+    /// Returns the receiver of the requests, only the first time it is
+    /// called.
+    pub fn receiver(
+        &self,
+    ) -> Option<mpsc::UnboundedReceiver<ReconnectRequest>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reconnects_should_reach_the_single_receiver() {
+        let reconnects = ChainReconnects::default();
+        let shared = reconnects.clone();
+        let mut receiver = reconnects.receiver().unwrap();
+        assert!(shared.receiver().is_none());
+
+        let handler = tokio::spawn(async move {
+            let request = receiver.recv().await.unwrap();
+            assert_eq!(request.chain, TypedChainId::Evm(5));
+            request.respond(Ok(()));
+            let request = receiver.recv().await.unwrap();
+            assert_eq!(request.chain, TypedChainId::Substrate(1080));
+            request.respond(Err("no endpoint".into()));
+        });
+        assert_eq!(shared.request(TypedChainId::Evm(5)).await, Some(Ok(())));
+        assert_eq!(
+            reconnects.request(TypedChainId::Substrate(1080)).await,
+            Some(Err("no endpoint".into()))
+        );
+
+        // the receiver got dropped with the handler.
+        handler.await.unwrap();
+        assert_eq!(reconnects.request(TypedChainId::Evm(5)).await, None);
+    }
+}
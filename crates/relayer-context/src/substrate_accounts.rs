@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+/// The relayer account on a Substrate node, as last read from it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrateAccountStatus {
+    /// The SS58 address of the account.
+    pub address: String,
+    /// The free balance, in the smallest unit of the chain.
+    #[serde(serialize_with = "serialize_u128")]
+    pub free_balance: u128,
+    /// The decimals of the native unit of the chain.
+    pub decimals: u8,
+    /// The nonce of the next transaction of the account.
+    pub nonce: u32,
+    /// Whether the free balance is under the configured `min-balance`.
+    pub low_balance: bool,
+    /// When the account was read, in seconds since the unix epoch.
+    pub checked_at: u64,
+}
+
+impl SubstrateAccountStatus {
+    /// The free balance, in the native unit of the chain.
+    pub fn balance(&self) -> f64 {
+        to_native_unit(self.free_balance, self.decimals)
+    }
+}
+
+/// Converts an amount in the smallest unit of a chain to its native unit.
+pub fn to_native_unit(amount: u128, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(i32::from(decimals))
+}
+
+/// Converts an amount in the native unit of a chain to its smallest unit.
+pub fn from_native_unit(amount: f64, decimals: u8) -> u128 {
+    (amount * 10f64.powi(i32::from(decimals))) as u128
+}
+
+// the balances overflow the `f64` of the JSON numbers.
+fn serialize_u128<S: serde::Serializer>(
+    value: &u128,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// How long, in secs, the nonce the relayer expects may stay ahead of the
+/// one of the node, while its last transactions reach the transaction pool.
+/// Past that, they are taken as dropped.
+pub const PENDING_NONCE_TTL_SECS: u64 = 60;
+
+/// The nonce the next transaction of the relayer should use.
+#[derive(Debug, Clone, Copy)]
+struct ExpectedNonce {
+    nonce: u32,
+    /// When the last transaction got submitted, in secs since the unix
+    /// epoch.
+    at: u64,
+}
+
+#[derive(Debug, Default)]
+struct Accounts {
+    status: HashMap<u32, SubstrateAccountStatus>,
+    /// The nonce the next transaction of the relayer should use, by chain.
+    next_nonce: HashMap<u32, ExpectedNonce>,
+}
+
+/// Keeps track of the relayer account on every Substrate node, for the
+/// health endpoint and the transactions the relayer submits.
+#[derive(Debug, Clone, Default)]
+pub struct SubstrateAccounts(Arc<RwLock<Accounts>>);
+
+impl SubstrateAccounts {
+    /// Records the account read from the node of `chain_id`.
+    pub fn record(&self, chain_id: u32, status: SubstrateAccountStatus) {
+        let mut accounts = self.0.write().unwrap_or_else(|e| e.into_inner());
+        accounts.status.insert(chain_id, status);
+    }
+
+    /// The account last read from the node of `chain_id`, if any.
+    pub fn get(&self, chain_id: u32) -> Option<SubstrateAccountStatus> {
+        let accounts = self.0.read().unwrap_or_else(|e| e.into_inner());
+        accounts.status.get(&chain_id).cloned()
+    }
+
+    /// Returns the nonce to sign the next transaction on `chain_id` with,
+    /// given the one the node expects, at `now` in secs since the unix
+    /// epoch.
+    ///
+    /// The relayer is ahead of the node while its last transactions reach
+    /// the transaction pool, its nonce is used then, for up to
+    /// [`PENDING_NONCE_TTL_SECS`]. Otherwise the node is right: if the nonce
+    /// the relayer expected differs, because of a transaction sent with the
+    /// same account outside of the relayer or one that got dropped, a
+    /// warning is logged and the relayer starts again from the node's one.
+    pub fn sync_nonce(&self, chain_id: u32, on_chain: u32, now: u64) -> u32 {
+        let mut accounts = self.0.write().unwrap_or_else(|e| e.into_inner());
+        let Some(expected) = accounts.next_nonce.get(&chain_id).copied() else {
+            return on_chain;
+        };
+        let pending = now.saturating_sub(expected.at) < PENDING_NONCE_TTL_SECS;
+        if expected.nonce > on_chain && pending {
+            return expected.nonce;
+        }
+        if expected.nonce != on_chain {
+            tracing::warn!(
+                chain_id,
+                expected = expected.nonce,
+                on_chain,
+                "The nonce of the relayer account is out of sync, \
+                using the one of the node",
+            );
+            accounts.next_nonce.insert(
+                chain_id,
+                ExpectedNonce {
+                    nonce: on_chain,
+                    at: now,
+                },
+            );
+        }
+        on_chain
+    }
+
+    /// Records that a transaction signed with `nonce` got submitted on
+    /// `chain_id`, at `now` in secs since the unix epoch.
+    pub fn record_submitted(&self, chain_id: u32, nonce: u32, now: u64) {
+        let mut accounts = self.0.write().unwrap_or_else(|e| e.into_inner());
+        let next = nonce.saturating_add(1);
+        let expected =
+            accounts
+                .next_nonce
+                .entry(chain_id)
+                .or_insert(ExpectedNonce {
+                    nonce: next,
+                    at: now,
+                });
+        // the transactions sent concurrently may be recorded out of order.
+        expected.nonce = expected.nonce.max(next);
+        expected.at = now;
+    }
+
+    /// The accounts as reported by the health endpoint, by chain id.
+    pub fn to_json(&self) -> serde_json::Value {
+        let accounts = self.0.read().unwrap_or_else(|e| e.into_inner());
+        let status = accounts
+            .status
+            .iter()
+            .map(|(chain_id, status)| {
+                let mut value = serde_json::to_value(status)
+                    .unwrap_or(serde_json::Value::Null);
+                if let Some(value) = value.as_object_mut() {
+                    value.insert("balance".into(), status.balance().into());
+                }
+                (chain_id.to_string(), value)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::Value::Object(status)
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The background tasks of the relayer, such as the events watchers and the
+//! transaction queues, and what happens when one of them fails.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+use webb_relayer_config::TaskFailurePolicy;
+
+use crate::RelayerContext;
+
+type TaskFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type TaskFactory = Arc<dyn Fn() -> TaskFuture + Send + Sync>;
+
+/// How long a failed task waits before it is started again.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// A background task, which can be started again if it fails.
+#[derive(Clone)]
+pub struct Task {
+    name: Arc<str>,
+    factory: TaskFactory,
+}
+
+impl Task {
+    /// The name of the task, as it appears in the logs.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn run(
+        self,
+        delay: Option<Duration>,
+        ctx: RelayerContext,
+    ) -> TaskExit {
+        if let Some(delay) = delay {
+            ctx.clock().sleep(delay).await;
+        }
+        // the task runs on its own, so a panic is reported as the failure
+        // of this task, instead of an anonymous one of the join set.
+        let result = match tokio::task::spawn((self.factory)()).await {
+            Ok(result) => result,
+            Err(e) => Err(anyhow::anyhow!("task panicked: {e}")),
+        };
+        TaskExit { task: self, result }
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Task").field(&self.name).finish()
+    }
+}
+
+/// A background task that stopped, successfully or not.
+#[derive(Debug)]
+pub struct TaskExit {
+    /// The task that stopped.
+    pub task: Task,
+    /// What the task returned, the error it failed with if any.
+    pub result: anyhow::Result<()>,
+}
+
+/// Spawns the background tasks of the relayer, which are run by the
+/// [`TaskSupervisor`].
+#[derive(Debug, Clone)]
+pub struct BackgroundTasks {
+    spawns: mpsc::UnboundedSender<Task>,
+    supervisor: Arc<Mutex<Option<mpsc::UnboundedReceiver<Task>>>>,
+}
+
+impl Default for BackgroundTasks {
+    fn default() -> Self {
+        let (spawns, pending) = mpsc::unbounded_channel();
+        Self {
+            spawns,
+            supervisor: Arc::new(Mutex::new(Some(pending))),
+        }
+    }
+}
+
+impl BackgroundTasks {
+    /// Spawns the task built by `factory`, which is called again to build
+    /// the task once more if it fails and the `task-failure-policy` is
+    /// `Restart`.
+    ///
+    /// The task starts once the supervisor runs.
+    pub fn spawn<F, Fut, E>(&self, name: impl Into<String>, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<anyhow::Error> + 'static,
+    {
+        // the task keeps the span it got spawned in, even when restarted.
+        let span = tracing::Span::current();
+        let task = Task {
+            name: name.into().into(),
+            factory: Arc::new(move || {
+                let task = factory();
+                let task = async move { task.await.map_err(Into::into) };
+                Box::pin(task.instrument(span.clone()))
+            }),
+        };
+        if let Err(e) = self.spawns.send(task) {
+            tracing::warn!(
+                task = %e.0.name(),
+                "Background task not started, the supervisor is gone",
+            );
+        }
+    }
+
+    /// Returns the supervisor running the tasks, only the first time it is
+    /// called.
+    pub fn supervisor(&self) -> Option<TaskSupervisor> {
+        let pending = self
+            .supervisor
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()?;
+        Some(TaskSupervisor {
+            pending,
+            running: JoinSet::new(),
+        })
+    }
+}
+
+/// Runs the background tasks, and applies the `task-failure-policy` when
+/// one of them fails.
+#[derive(Debug)]
+pub struct TaskSupervisor {
+    pending: mpsc::UnboundedReceiver<Task>,
+    running: JoinSet<TaskExit>,
+}
+
+impl TaskSupervisor {
+    /// Starts the spawned tasks, until one of them stops.
+    pub async fn next_exit(&mut self, ctx: &RelayerContext) -> TaskExit {
+        loop {
+            tokio::select! {
+                Some(task) = self.pending.recv() => {
+                    self.running.spawn(task.run(None, ctx.clone()));
+                }
+                Some(joined) = self.running.join_next() => match joined {
+                    Ok(exit) => return exit,
+                    Err(e) => {
+                        tracing::error!("Background task got cancelled: {e}");
+                    }
+                },
+                else => std::future::pending::<()>().await,
+            }
+        }
+    }
+
+    /// Runs the tasks until one of them fails while the `task-failure-policy`
+    /// is `AbortAll`, which shuts the relayer down.
+    ///
+    /// Returns the error of the failed task.
+    pub async fn supervise(&mut self, ctx: &RelayerContext) -> anyhow::Error {
+        loop {
+            let TaskExit { task, result } = self.next_exit(ctx).await;
+            let error = match result {
+                Ok(()) => {
+                    tracing::debug!(
+                        task = %task.name(),
+                        "Background task stopped",
+                    );
+                    continue;
+                }
+                Err(error) => error,
+            };
+            tracing::error!(
+                task = %task.name(),
+                "Background task failed: {error:#}",
+            );
+            match ctx.config.task_failure_policy {
+                TaskFailurePolicy::AbortAll => {
+                    ctx.shutdown();
+                    return error.context(format!(
+                        "background task {} failed",
+                        task.name()
+                    ));
+                }
+                TaskFailurePolicy::Restart => {
+                    tracing::warn!(
+                        task = %task.name(),
+                        "Restarting background task",
+                    );
+                    self.running
+                        .spawn(task.run(Some(RESTART_DELAY), ctx.clone()));
+                }
+                TaskFailurePolicy::Ignore => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_store::SledStore;
+
+    fn context() -> RelayerContext {
+        let store = SledStore::temporary().unwrap();
+        RelayerContext::new(Default::default(), store).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_shut_the_relayer_down_when_a_task_fails() {
+        let ctx = context();
+        let mut supervisor = ctx.background_tasks().supervisor().unwrap();
+        assert!(ctx.background_tasks().supervisor().is_none());
+        let mut shutdown = ctx.shutdown_signal();
+        let my_ctx = ctx.clone();
+        ctx.background_tasks().spawn("watcher", move || {
+            let mut shutdown = my_ctx.shutdown_signal();
+            async move {
+                shutdown.recv().await;
+                anyhow::Ok(())
+            }
+        });
+        ctx.background_tasks().spawn("failing", || async {
+            Err(anyhow::anyhow!("connection lost"))
+        });
+
+        let error = tokio::time::timeout(
+            Duration::from_secs(5),
+            supervisor.supervise(&ctx),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            format!("{error:#}"),
+            "background task failing failed: connection lost"
+        );
+        // the rest of the relayer got the shutdown signal.
+        tokio::time::timeout(Duration::from_secs(1), shutdown.recv())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn tasks_started_after_the_shutdown_should_still_stop() {
+        let ctx = context();
+        let mut supervisor = ctx.background_tasks().supervisor().unwrap();
+        ctx.shutdown();
+        let my_ctx = ctx.clone();
+        ctx.background_tasks().spawn("watcher", move || {
+            // subscribes once it runs, after the shutdown got sent.
+            let mut shutdown = my_ctx.shutdown_signal();
+            async move {
+                shutdown.recv().await;
+                anyhow::Ok(())
+            }
+        });
+
+        let exit = tokio::time::timeout(
+            Duration::from_secs(1),
+            supervisor.next_exit(&ctx),
+        )
+        .await
+        .unwrap();
+        assert_eq!(exit.task.name(), "watcher");
+        assert!(exit.result.is_ok());
+        // nor does a scoped context miss it.
+        let mut scoped = ctx.scoped().shutdown_signal();
+        tokio::time::timeout(Duration::from_secs(1), scoped.recv())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_report_a_panic_as_the_failure_of_its_task() {
+        let ctx = context();
+        let mut supervisor = ctx.background_tasks().supervisor().unwrap();
+        ctx.background_tasks().spawn("panicking", || async {
+            None::<()>.expect("unreachable state");
+            anyhow::Ok(())
+        });
+
+        let exit = supervisor.next_exit(&ctx).await;
+        assert_eq!(exit.task.name(), "panicking");
+        assert!(exit.result.is_err());
+    }
+}
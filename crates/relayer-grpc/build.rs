@@ -0,0 +1,10 @@
+// build.rs
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // the vendored protoc, so building does not need one installed.
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/relayer.proto"], &["proto"])?;
+    Ok(())
+}
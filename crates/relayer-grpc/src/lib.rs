@@ -0,0 +1,292 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Relayer gRPC Module 🛰️
+//!
+//! A gRPC server exposing the state of the relayer, for orchestration.
+//!
+//! The services are thin adapters over the store and the configuration,
+//! the same ones the HTTP handlers use, see `proto/relayer.proto`.
+
+#![warn(missing_docs)]
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::service::Interceptor;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_config::evm::Contract;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::HistoryStore;
+use webb_relayer_utils::auth::token_matches;
+
+/// The generated types of `proto/relayer.proto`.
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("webb.relayer.v1");
+}
+
+use proto::queue_inspection_server::{QueueInspection, QueueInspectionServer};
+use proto::sync_status_server::{SyncStatus, SyncStatusServer};
+
+/// How often the sync progress is streamed, if the client did not ask.
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the sync progress may be streamed at most, each update reads
+/// the store for every watcher.
+const MIN_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Rejects the requests that do not carry the token of the gRPC server.
+#[derive(Clone)]
+struct TokenInterceptor {
+    expected: Arc<str>,
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match token {
+            Some(token) if token_matches(&self.expected, token) => Ok(request),
+            _ => Err(Status::unauthenticated("Invalid or missing token")),
+        }
+    }
+}
+
+/// Returns the sync progress of every EVM events watcher.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration and database
+pub fn sync_status(
+    ctx: &RelayerContext,
+) -> webb_relayer_utils::Result<proto::SyncStatusResponse> {
+    let mut watchers = Vec::new();
+    for chain in ctx.config.evm.values() {
+        for contract in &chain.contracts {
+            let (common, events_watcher) = match contract {
+                Contract::VAnchor(c) => (&c.common, &c.events_watcher),
+                Contract::SignatureBridge(c) => (&c.common, &c.events_watcher),
+            };
+            if !events_watcher.enabled || events_watcher.is_static() {
+                continue;
+            }
+            let history_store_key = ResourceId::new(
+                TargetSystem::new_contract_address(
+                    common.address.to_fixed_bytes(),
+                ),
+                TypedChainId::Evm(chain.chain_id),
+            );
+            let last_block = ctx
+                .store()
+                .get_last_block_number(history_store_key, common.deployed_at)?;
+            let target_block =
+                ctx.store().get_target_block_number(history_store_key, 0)?;
+            watchers.push(proto::WatcherSyncStatus {
+                chain_id: chain.chain_id,
+                contract: format!("{:?}", common.address),
+                last_block,
+                target_block,
+                progress: webb_event_watcher_traits::sync_progress(
+                    last_block,
+                    target_block,
+                ),
+            });
+        }
+    }
+    watchers.sort_by(|a, b| {
+        (a.chain_id, &a.contract).cmp(&(b.chain_id, &b.contract))
+    });
+    Ok(proto::SyncStatusResponse { watchers })
+}
+
+/// Returns the number of items waiting in the queues of each chain.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration and database
+pub fn queue_depths(ctx: &RelayerContext) -> proto::QueueDepthsResponse {
+    let metrics = ctx.store().export_metrics();
+    proto::QueueDepthsResponse {
+        queue_depth_by_chain: metrics
+            .queue_depth_by_chain
+            .into_iter()
+            .collect(),
+        proposal_count: metrics.proposal_count,
+    }
+}
+
+/// Reads the sync progress off the async runtime, it reads the store for
+/// every watcher.
+async fn read_sync_status(
+    ctx: Arc<RelayerContext>,
+) -> Result<proto::SyncStatusResponse, Status> {
+    tokio::task::spawn_blocking(move || sync_status(&ctx))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+struct SyncStatusService {
+    ctx: Arc<RelayerContext>,
+}
+
+#[tonic::async_trait]
+impl SyncStatus for SyncStatusService {
+    type WatchSyncStatusStream =
+        ReceiverStream<Result<proto::SyncStatusResponse, Status>>;
+
+    async fn get_sync_status(
+        &self,
+        _request: Request<proto::SyncStatusRequest>,
+    ) -> Result<Response<proto::SyncStatusResponse>, Status> {
+        read_sync_status(self.ctx.clone()).await.map(Response::new)
+    }
+
+    async fn watch_sync_status(
+        &self,
+        request: Request<proto::WatchSyncStatusRequest>,
+    ) -> Result<Response<Self::WatchSyncStatusStream>, Status> {
+        let period = match request.into_inner().interval_ms {
+            0 => DEFAULT_WATCH_INTERVAL,
+            ms => Duration::from_millis(ms).max(MIN_WATCH_INTERVAL),
+        };
+        let ctx = self.ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let status = read_sync_status(ctx.clone()).await;
+                        // the client went away.
+                        if tx.send(status).await.is_err() {
+                            break;
+                        }
+                    }
+                    // ends the stream, so the server can shut down.
+                    _ = shutdown_signal.recv() => break,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+struct QueueInspectionService {
+    ctx: Arc<RelayerContext>,
+}
+
+#[tonic::async_trait]
+impl QueueInspection for QueueInspectionService {
+    async fn get_queue_depths(
+        &self,
+        _request: Request<proto::QueueDepthsRequest>,
+    ) -> Result<Response<proto::QueueDepthsResponse>, Status> {
+        // exporting the metrics of the store walks its trees.
+        let ctx = self.ctx.clone();
+        tokio::task::spawn_blocking(move || queue_depths(&ctx))
+            .await
+            .map(Response::new)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+/// Serves the gRPC services of the relayer on the `listener`, until the
+/// relayer shuts down.
+///
+/// # Arguments
+///
+/// * `listener` - The bound (non-blocking) socket of the gRPC server
+/// * `ctx` - RelayContext reference that holds the configuration and database
+/// * `token` - The token every request has to carry
+pub async fn serve(
+    listener: TcpListener,
+    ctx: RelayerContext,
+    token: String,
+) -> webb_relayer_utils::Result<()> {
+    let mut shutdown_signal = ctx.shutdown_signal();
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let address = listener.local_addr()?;
+    let interceptor = TokenInterceptor {
+        expected: token.into(),
+    };
+    let ctx = Arc::new(ctx);
+
+    tracing::info!("Starting the gRPC server on {address}");
+    Server::builder()
+        .add_service(SyncStatusServer::with_interceptor(
+            SyncStatusService { ctx: ctx.clone() },
+            interceptor.clone(),
+        ))
+        .add_service(QueueInspectionServer::with_interceptor(
+            QueueInspectionService { ctx },
+            interceptor,
+        ))
+        .serve_with_incoming_shutdown(
+            TcpListenerStream::new(listener),
+            async move { shutdown_signal.recv().await },
+        )
+        .await
+        .map_err(|e| webb_relayer_utils::Error::Listen {
+            address: address.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_without_the_token_should_be_rejected() {
+        let mut interceptor = TokenInterceptor {
+            expected: "secret".into(),
+        };
+        let with_token = |token: &str| {
+            let mut request = Request::new(());
+            request
+                .metadata_mut()
+                .insert("authorization", token.parse().unwrap());
+            request
+        };
+        assert!(interceptor.call(with_token("Bearer secret")).is_ok());
+        assert!(interceptor.call(with_token("Bearer other")).is_err());
+        assert!(interceptor.call(with_token("secret")).is_err());
+        assert!(interceptor.call(Request::new(())).is_err());
+
+        let mut interceptor = TokenInterceptor {
+            expected: "".into(),
+        };
+        assert!(interceptor.call(with_token("Bearer ")).is_err());
+    }
+
+    #[test]
+    fn queue_depths_should_come_from_the_store() {
+        let store = webb_relayer_store::SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store).unwrap();
+        let depths = queue_depths(&ctx);
+        assert!(depths.queue_depth_by_chain.is_empty());
+        assert_eq!(depths.proposal_count, 0);
+        assert!(sync_status(&ctx).unwrap().watchers.is_empty());
+    }
+}
@@ -15,7 +15,7 @@
 #![allow(clippy::large_enum_variant)]
 #![allow(missing_docs)]
 
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio::sync::mpsc;
 use webb::evm::ethers::abi::Address;
 use webb::evm::ethers::prelude::{ContractError, I256, U128};
@@ -23,22 +23,64 @@ use webb::evm::ethers::providers::Middleware;
 use webb::evm::ethers::types::Bytes;
 use webb::evm::ethers::types::{H256, U256};
 use webb::substrate::subxt::utils::AccountId32;
+use webb_relayer_store::{ProposalEventKind, ProposalEventRecord};
 use webb_relayer_tx_relay_utils::VAnchorRelayTransaction;
+pub use webb_relayer_tx_relay_utils::{FeePermit, RelayDelay};
 
 /// Representation for IP address response
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IpInformationResponse {
     pub ip: String,
+    pub family: IpFamily,
+    pub source: IpSource,
+}
+
+/// The family of a client IP address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<std::net::IpAddr> for IpFamily {
+    fn from(ip: std::net::IpAddr) -> Self {
+        match ip {
+            std::net::IpAddr::V4(_) => Self::Ipv4,
+            std::net::IpAddr::V6(_) => Self::Ipv6,
+        }
+    }
+}
+
+/// Where a client IP address was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpSource {
+    /// The address of the peer of the connection.
+    Socket,
+    /// A forwarding header, set by a trusted proxy.
+    Forwarded,
 }
 
 /// A wrapper type around [`I256`] that implements a correct way for [`Serialize`] and [`Deserialize`].
 ///
 /// This supports the signed integer hex values that are not originally supported by the [`I256`] type.
-#[derive(Debug, Clone, Serialize)]
-#[serde(transparent)]
+#[derive(Debug, Clone)]
 pub struct WebbI256(pub I256);
 
+impl Serialize for WebbI256 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // the same signed hex format it is deserialized from.
+        let (sign, abs) = self.0.into_sign_and_abs();
+        let sign = if sign.is_negative() { "-" } else { "" };
+        serializer.collect_str(&format_args!("{sign}{abs:#x}"))
+    }
+}
+
 impl<'de> Deserialize<'de> for WebbI256 {
     fn deserialize<D>(deserializer: D) -> Result<WebbI256, D::Error>
     where
@@ -69,16 +111,40 @@ impl<'de> Deserialize<'de> for WebbI128 {
     }
 }
 
-/// Type of Command to use
+/// Cancels the transaction held by the relayer under the given key.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelScheduledCommand {
+    /// The idempotency key the transaction was sent with.
+    pub idempotency_key: String,
+}
+
+/// Subscribes to the events of the proposals going through their lifecycle.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscribeProposalsCommand {
+    /// The `admin-token` of the relayer.
+    pub token: String,
+    /// The sequence number of the last event the client got, to resume
+    /// from after it; only the new events are sent without one.
+    #[serde(default)]
+    pub cursor: Option<u64>,
+    /// Only the proposals from or to these chains, all of them if empty.
+    #[serde(default)]
+    pub chain_ids: Vec<u32>,
+    /// Only the proposals targeting these contracts, all of them if empty.
+    #[serde(default)]
+    pub addresses: Vec<Address>,
+}
+
+/// Subscribes to the progress of the proposal with the given data hash.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub enum Command {
-    /// Substrate specific subcommand.
-    Substrate(SubstrateCommandType),
-    /// EVM specific subcommand.
-    Evm(EvmCommandType),
-    /// Ping?
-    Ping(),
+pub struct SubscribeProposalCommand {
+    /// The underlying chain id of the source or the target of the proposal.
+    pub chain_id: u32,
+    /// The keccak256 hash of the proposal data.
+    pub data_hash: H256,
 }
 
 /// Enumerates the supported evm commands for relaying transactions
@@ -107,9 +173,75 @@ pub enum CommandResponse {
     Network(NetworkStatus),
     /// Withdrawal Status
     Withdraw(WithdrawStatus),
+    /// A proposal went through a new state of its lifecycle.
+    ProposalEvent(ProposalEventRecord),
+    /// The progress of a single proposal.
+    ProposalProgress(ProposalProgress),
+    /// The subscriber fell behind, and missed some proposal events.
+    ProposalEventsDropped {
+        /// How many events got missed.
+        dropped: u64,
+        /// The cursor to subscribe with again, to get them back.
+        #[serde(rename = "resumeCursor")]
+        resume_cursor: u64,
+    },
+    /// The relayer has no handler for the tag of the command.
+    #[serde(rename = "UNKNOWN_COMMAND")]
+    UnknownCommand {
+        /// The tag of the command.
+        tag: String,
+        /// The tags of the commands the relayer handles.
+        supported: Vec<String>,
+    },
     /// An error occurred
     Error(String),
 }
+/// How far a proposal went, as seen by the relayer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalProgress {
+    /// The keccak256 hash of the proposal data.
+    pub data_hash: H256,
+    /// The last state the proposal went through.
+    pub status: ProposalEventKind,
+    /// How many votes for the proposal got included on the DKG.
+    pub votes: u32,
+    /// The transaction of the last state, if any.
+    pub tx_hash: Option<H256>,
+    /// Whether the proposal reached its final state: executed, cancelled
+    /// or expired.
+    pub done: bool,
+}
+
+impl ProposalProgress {
+    /// The progress of a proposal that just got created.
+    pub fn new(data_hash: H256) -> Self {
+        Self {
+            data_hash,
+            status: ProposalEventKind::Created,
+            votes: 0,
+            tx_hash: None,
+            done: false,
+        }
+    }
+
+    /// Moves the proposal on to the state of `record`.
+    pub fn apply(&mut self, record: &ProposalEventRecord) {
+        let event = &record.event;
+        self.status = event.kind;
+        if event.kind == ProposalEventKind::VoteMined {
+            self.votes = self.votes.saturating_add(1);
+        }
+        self.tx_hash = event.tx_hash.or(self.tx_hash);
+        self.done = matches!(
+            event.kind,
+            ProposalEventKind::Executed
+                | ProposalEventKind::Cancelled
+                | ProposalEventKind::Expired
+        );
+    }
+}
+
 /// Enumerates the network status response of the relayer
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -127,6 +259,11 @@ pub enum NetworkStatus {
     Disconnected,
     /// This contract is not supported by the relayer.
     UnsupportedContract,
+    /// This contract is supported, but the relayer is configured not to
+    /// relay to it.
+    DisabledContract,
+    /// This contract is not watched, the relayer only serves its data.
+    StaticContract,
     /// This network (chain) is not supported by the relayer.
     UnsupportedChain,
     /// Invalid Relayer address in the proof
@@ -136,6 +273,15 @@ pub enum NetworkStatus {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum WithdrawStatus {
+    /// The transaction is held by the relayer, and gets submitted after
+    /// the given time.
+    Scheduled {
+        /// When the transaction gets submitted, in secs since the UNIX epoch.
+        #[serde(rename = "executeAfter")]
+        execute_after: u64,
+    },
+    /// The held transaction got cancelled, it will not be submitted.
+    Cancelled,
     /// The transaction is sent to the network.
     Sent,
     /// The transaction is submitted to the network.
@@ -154,8 +300,52 @@ pub enum WithdrawStatus {
     Valid,
     /// Invalid Merkle roots.
     InvalidMerkleRoots,
+    /// The proof got rejected by a dry run, so the transaction was not sent.
+    InvalidProof {
+        /// Why the proof got rejected.
+        reason: String,
+    },
     /// Transaction dropped from mempool, send it again.
     DroppedFromMemPool,
+    /// The withdrawal would take the anchor over its hourly amount limit.
+    RateLimitAmount {
+        /// How long until enough of the limit frees up, in secs, `None` if
+        /// the amount is over the limit on its own.
+        #[serde(rename = "retryAfterSecs")]
+        retry_after_secs: Option<u64>,
+    },
+    /// The relayer account has not enough funds left to pay for the
+    /// transaction, so it was not sent.
+    #[serde(rename = "RELAYER_OUT_OF_FUNDS")]
+    RelayerOutOfFunds {
+        /// The free balance of the relayer account.
+        balance: U256,
+        /// The balance needed to pay for the transaction, and keep the
+        /// account alive.
+        required: U256,
+    },
+    /// The fee permit expired, so the transaction was not sent.
+    #[serde(rename = "FEE_PERMIT_EXPIRED")]
+    FeePermitExpired {
+        /// When the permit expired, in secs since the UNIX epoch.
+        deadline: u64,
+    },
+    /// The recipient has not enough of the token to pay the fee permit,
+    /// so the transaction was not sent.
+    #[serde(rename = "FEE_PERMIT_UNDERFUNDED")]
+    FeePermitUnderfunded {
+        /// The balance of the recipient, with the withdrawn amount.
+        balance: U256,
+        /// The amount of the permit.
+        required: U256,
+    },
+    /// The fee permit could not be taken, such as when it is not signed
+    /// by the recipient.
+    #[serde(rename = "FEE_PERMIT_REJECTED")]
+    FeePermitRejected {
+        /// Why the permit got rejected.
+        reason: String,
+    },
     /// Invalid transaction.
     Errored {
         /// Error Code.
@@ -165,6 +355,13 @@ pub enum WithdrawStatus {
     },
 }
 
+/// The fee permit of the EVM vanchor transactions
+pub type EvmFeePermit = FeePermit<
+    Address, // Token Address
+    U256,    // Balance type
+    H256,    // Signature values
+>;
+
 /// Type alias for mpsc::Sender<CommandResponse>
 pub type CommandStream = mpsc::Sender<CommandResponse>;
 /// The command type for EVM vanchor transactions
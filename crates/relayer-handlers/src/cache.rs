@@ -0,0 +1,117 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use webb::evm::ethers::utils::keccak256;
+use webb_relayer_utils::HandlerError;
+
+/// The entity tag of a response (RFC 9110), which changes with every
+/// version of the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ETag(String);
+
+impl ETag {
+    /// Tags the version of a response, as identified by `version`.
+    pub fn new(version: impl AsRef<[u8]>) -> Self {
+        // half of the hash is plenty to tell the versions apart.
+        let hash: String = keccak256(version)[..16]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Self(format!("\"{hash}\""))
+    }
+
+    /// The tag, quoted, as sent in the `ETag` header.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the client already has this version, according to its
+    /// `If-None-Match` header.
+    ///
+    /// The tags are compared weakly, since the relayer does not tell the
+    /// strong and the weak ones apart.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == self.as_str()
+            })
+    }
+}
+
+/// Responds with `304 Not Modified`, without a body, when the client
+/// already has the version `etag` of the response, and with the response
+/// built by `respond` otherwise.
+///
+/// Both carry the `ETag`, and a `Cache-Control` letting the clients cache
+/// the response for `max_age_secs`.
+pub fn respond_cached<T, F>(
+    headers: &HeaderMap,
+    etag: &ETag,
+    max_age_secs: u64,
+    respond: F,
+) -> Result<Response, HandlerError>
+where
+    T: IntoResponse,
+    F: FnOnce() -> Result<T, HandlerError>,
+{
+    let mut response = if etag.matches(headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        respond()?.into_response()
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(etag.as_str()) {
+        response_headers.insert(ETAG, value);
+    }
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("public, max-age={max_age_secs}"))
+    {
+        response_headers.insert(CACHE_CONTROL, value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn etag_should_match_the_tags_the_client_has() {
+        let etag = ETag::new("leaves/12/100");
+        assert_eq!(etag, ETag::new("leaves/12/100"));
+        assert_ne!(etag, ETag::new("leaves/13/100"));
+        assert!(etag.as_str().starts_with('"') && etag.as_str().ends_with('"'));
+
+        assert!(!etag.matches(&HeaderMap::new()));
+        assert!(etag.matches(&if_none_match(etag.as_str())));
+        assert!(etag.matches(&if_none_match("*")));
+        let weak = format!("\"other\", W/{}", etag.as_str());
+        assert!(etag.matches(&if_none_match(&weak)));
+        assert!(!etag.matches(&if_none_match("\"other\"")));
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::IpAddr;
+
+use axum::http::header::{HeaderMap, FORWARDED};
+use ipnet::IpNet;
+use webb_relayer_handler_utils::IpSource;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Resolves the IP address of the client of a request.
+///
+/// The forwarding headers are only looked at when the `peer` is one of the
+/// `trusted` proxies, since anyone else can set them to anything. The hops
+/// are then walked from the nearest one, skipping the trusted proxies, and
+/// the first untrusted hop is the client. `X-Forwarded-For` is preferred
+/// over `Forwarded` when both are set.
+///
+/// # Arguments
+///
+/// * `peer` - The address of the peer of the connection
+/// * `headers` - The headers of the request
+/// * `trusted` - The reverse proxies in front of the relayer
+pub fn resolve_client_ip(
+    peer: IpAddr,
+    headers: &HeaderMap,
+    trusted: &[IpNet],
+) -> (IpAddr, IpSource) {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    let peer = canonical(peer);
+    if !is_trusted(&peer) {
+        return (peer, IpSource::Socket);
+    }
+    let mut hops = forwarded_for(headers, X_FORWARDED_FOR, Some);
+    if hops.is_empty() {
+        hops = forwarded_for(headers, FORWARDED.as_str(), forwarded_node);
+    }
+    let mut client = (peer, IpSource::Socket);
+    for hop in hops.into_iter().rev() {
+        // a garbled hop can not be trusted to tell anything further.
+        let Some(ip) = parse_node(hop) else { break };
+        client = (ip, IpSource::Forwarded);
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// Collects the hops of a forwarding header, across all of its values,
+/// from the farthest to the nearest one.
+fn forwarded_for<'a>(
+    headers: &'a HeaderMap,
+    name: &str,
+    node: impl Fn(&'a str) -> Option<&'a str>,
+) -> Vec<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(node)
+        .map(str::trim)
+        .collect()
+}
+
+/// The `for=` node of an element of the `Forwarded` header (RFC 7239).
+fn forwarded_node(element: &str) -> Option<&str> {
+    element.split(';').find_map(|pair| {
+        let (key, value) = pair.trim().split_once('=')?;
+        key.eq_ignore_ascii_case("for").then_some(value)
+    })
+}
+
+/// Parses a node of a forwarding header, which may be quoted and may
+/// carry a port, such as `"[2001:db8::1]:4711"` or `192.0.2.1:80`.
+///
+/// Obfuscated and `unknown` nodes are not addresses, so they are rejected.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    let ip = if let Some(rest) = node.strip_prefix('[') {
+        rest.split_once(']')?.0
+    } else if node.matches(':').count() == 1 {
+        node.split_once(':')?.0
+    } else {
+        node
+    };
+    // the zone of a link-local address means nothing to us.
+    let ip = ip.split_once('%').map_or(ip, |(ip, _zone)| ip);
+    ip.parse().ok().map(canonical)
+}
+
+/// Unmaps the IPv4 addresses of dual-stack sockets (`::ffff:a.b.c.d`), so
+/// they are reported, and matched against the proxies, as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => {
+            v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4)
+        }
+        v4 => v4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use std::net::Ipv6Addr;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn nets(nets: &[&str]) -> Vec<IpNet> {
+        nets.iter().map(|n| n.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn headers_of_untrusted_peers_should_be_ignored() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        let spoofed = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("forwarded", "for=5.6.7.8"),
+        ]);
+        let resolved = resolve_client_ip(peer, &spoofed, &[]);
+        assert_eq!(resolved, (peer, IpSource::Socket));
+        let trusted = nets(&["10.0.0.0/8"]);
+        let resolved = resolve_client_ip(peer, &spoofed, &trusted);
+        assert_eq!(resolved, (peer, IpSource::Socket));
+    }
+
+    #[test]
+    fn nearest_untrusted_hop_should_be_the_client() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let trusted = nets(&["10.0.0.0/8"]);
+        // the client can prepend whatever it wants.
+        let h = headers(&[
+            ("x-forwarded-for", "1.1.1.1, 198.51.100.2"),
+            ("x-forwarded-for", "10.0.0.2"),
+        ]);
+        let (ip, source) = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(ip.to_string(), "198.51.100.2");
+        assert_eq!(source, IpSource::Forwarded);
+
+        // only trusted hops, the farthest one is the client.
+        let h = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        let (ip, _) = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(ip.to_string(), "10.0.0.3");
+
+        // a garbled hop stops the walk.
+        let h = headers(&[("x-forwarded-for", "1.1.1.1, garbage")]);
+        let resolved = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(resolved, (peer, IpSource::Socket));
+    }
+
+    #[test]
+    fn forwarded_header_should_be_parsed() {
+        let peer: IpAddr = "127.0.0.1".parse().unwrap();
+        let trusted = nets(&["127.0.0.1/32"]);
+        let h = headers(&[(
+            "forwarded",
+            "for=\"[2001:DB8:0:0::1%eth0]:4711\";proto=https, For=127.0.0.1",
+        )]);
+        let (ip, source) = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(ip.to_string(), "2001:db8::1");
+        assert_eq!(source, IpSource::Forwarded);
+
+        let h = headers(&[("forwarded", "for=192.0.2.60:80;by=203.0.113.43")]);
+        let (ip, _) = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(ip.to_string(), "192.0.2.60");
+
+        let h = headers(&[("forwarded", "for=unknown")]);
+        let resolved = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(resolved, (peer, IpSource::Socket));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_should_be_unmapped() {
+        let peer = IpAddr::V6(Ipv6Addr::from([
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 127, 0, 0, 1,
+        ]));
+        let trusted = nets(&["127.0.0.0/8"]);
+        let h = headers(&[("x-forwarded-for", "::ffff:198.51.100.9")]);
+        let (ip, source) = resolve_client_ip(peer, &h, &trusted);
+        assert_eq!(ip.to_string(), "198.51.100.9");
+        assert_eq!(source, IpSource::Forwarded);
+    }
+}
@@ -16,132 +16,201 @@
 
 #![allow(clippy::large_enum_variant)]
 #![warn(missing_docs)]
-use axum::extract::{Path, State, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Extension, Path, State, WebSocketUpgrade};
 use ethereum_types::{Address, U256};
-use std::error::Error;
+use std::borrow::Cow;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::prelude::*;
 
-use axum::extract::ws::{Message, WebSocket};
-use axum::http::StatusCode;
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::Response;
 use axum::Json;
-use axum_client_ip::InsecureClientIp;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
 use webb_proposals::TypedChainId;
+use websocket::{OutboundQueue, WebSocketHeartbeat, WebSocketMessageSizeLimit};
 
+use router::CommandRouter;
 use webb_relayer_context::RelayerContext;
-use webb_relayer_handler_utils::{
-    Command, CommandResponse, CommandStream, EvmCommandType,
-    IpInformationResponse, SubstrateCommandType,
-};
+use webb_relayer_handler_utils::IpInformationResponse;
 use webb_relayer_tx_relay::evm::fees::{get_evm_fee_info, EvmFeeInfo};
 
-use webb_relayer_tx_relay::evm::vanchor::handle_vanchor_relay_tx;
 use webb_relayer_tx_relay::substrate::fees::{
     get_substrate_fee_info, SubstrateFeeInfo,
 };
-use webb_relayer_tx_relay::substrate::vanchor::handle_substrate_vanchor_relay_tx;
 use webb_relayer_utils::HandlerError;
 
+/// Module handles the HTTP caching of the read endpoints
+pub mod cache;
+/// Module resolves the IP address of the clients
+pub mod client_ip;
+/// Module streams the proposal lifecycle events to the governance clients
+pub mod proposal_events;
+/// Module routes the websocket commands to their handlers
+pub mod router;
 /// Module handles relayer API
 pub mod routes;
+/// Module handles the websocket connections
+pub mod websocket;
 
 /// Wait for websocket connection upgrade
+///
+/// The commands are handled by the [`CommandRouter`] extension of the route.
+/// The handshake carries the advertised URL of the relayer, if any, as a
+/// canonical `Link` header.
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(ctx): State<Arc<RelayerContext>>,
+    Extension(router): Extension<Arc<CommandRouter>>,
 ) -> Response {
-    ws.on_upgrade(move |socket| accept_websocket_connection(socket, ctx))
+    let link = ctx.config.advertised_url.as_ref().and_then(|url| {
+        HeaderValue::from_str(&format!("<{url}>; rel=\"canonical\"")).ok()
+    });
+    let limit =
+        WebSocketMessageSizeLimit::new(ctx.config.ws_max_message_size_bytes);
+    let mut response = limit.limit_upgrade(ws).on_upgrade(move |socket| {
+        accept_websocket_connection(socket, ctx, router)
+    });
+    if let Some(link) = link {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    response
 }
 
 /// Sets up a websocket connection.
 ///
 /// # Arguments
 ///
+/// * `ws` - Websocket stream
 /// * `ctx` - RelayContext reference that holds the configuration
-/// * `stream` - Websocket stream
-async fn accept_websocket_connection(ws: WebSocket, ctx: Arc<RelayerContext>) {
-    let (mut tx, mut rx) = ws.split();
+/// * `router` - The handlers of the commands
+async fn accept_websocket_connection(
+    ws: WebSocket,
+    ctx: Arc<RelayerContext>,
+    router: Arc<CommandRouter>,
+) {
+    let (tx, rx) = ws.split();
+    let (my_tx, my_rx) = mpsc::channel(ctx.config.ws_send_queue_size.max(1));
+    let outbound = OutboundQueue::new(my_tx, ctx.config.ws_slow_client_policy);
+    let overflowed = outbound.overflowed().clone();
+    let (ping_tx, ping_rx) = mpsc::channel(1);
+    let cancel = CancellationToken::new();
+    let heartbeat = match ctx.config.ws_ping_interval_secs {
+        0 => None,
+        secs => Some(WebSocketHeartbeat::new(Duration::from_secs(secs))),
+    };
+    let pongs = heartbeat.clone();
+    let closed = cancel.clone();
+    let limit =
+        WebSocketMessageSizeLimit::new(ctx.config.ws_max_message_size_bytes);
+    // the answers to the rejected messages, sent before closing the
+    // connection if needed, so they go through a single channel.
+    let (rejections_tx, rejections_rx) = mpsc::channel(2);
+    let close_tx = rejections_tx.clone();
+    let transport_rejections = rejections_tx.clone();
 
     // Wait for client to send over text (such as relay transaction requests)
-    while let Some(msg) = rx.next().await {
-        match msg {
-            Ok(msg) => {
-                if let Ok(text) = msg.to_text() {
-                    // Use inspect_err() here once stabilized
+    let inbound = rx
+        .take_until(async move { closed.cancelled().await })
+        .take_while(move |msg| {
+            if let Err(e) = msg {
+                tracing::warn!("Websocket error: {e}");
+                // the transport already refused a message over the limit.
+                if let Some(rejected) = limit.rejected_by_transport(e) {
+                    let response = rejected.response();
+                    if let Ok(v) = websocket::response_to_json(None, &response)
+                    {
+                        let _ = transport_rejections.try_send(Message::Text(v));
+                    }
+                    let close = rejected.close_frame();
                     let _ =
-                        handle_text(&ctx, text, &mut tx).await.map_err(|e| {
-                            tracing::warn!("Websocket handler error: {e}")
-                        });
+                        transport_rejections.try_send(Message::Close(close));
                 }
             }
-            Err(e) => {
-                tracing::warn!("Websocket error: {e}");
-                return;
+            future::ready(msg.is_ok())
+        })
+        .inspect(move |msg| {
+            if let (Ok(Message::Pong(_)), Some(heartbeat)) = (msg, &pongs) {
+                heartbeat.pong();
             }
-        }
-    }
-}
-
-/// Sets up a websocket channels for message sending.
-///
-/// This is primarily used for transaction relaying. The intention is
-/// that a user will send formatted relay requests to the relayer using
-/// the websocket. The command will be extracted and sent to `handle_cmd`
-/// if successfully deserialized.
-///
-/// Returns `Ok(())` on success
-///
-/// # Arguments
-///
-/// * `ctx` - RelayContext reference that holds the configuration
-/// * `v` - The text (usually in a JSON form) message to be handled.
-/// * `tx` - A mutable Trait implementation of the `warp::ws::Sender` trait
-pub async fn handle_text<TX>(
-    ctx: &RelayerContext,
-    v: &str,
-    tx: &mut TX,
-) -> webb_relayer_utils::Result<()>
-where
-    TX: Sink<Message> + Unpin,
-    TX::Error: Error + Send + Sync + 'static,
-{
-    // for every connection, we create a new channel, where we will use to send messages
-    // over it.
-    let (my_tx, my_rx) = mpsc::channel(50);
-    let res_stream = ReceiverStream::new(my_rx);
-    match serde_json::from_str(v) {
-        Ok(cmd) => {
-            if let Err(e) = handle_cmd(ctx.clone(), cmd, my_tx.clone()).await {
-                tracing::error!("{:?}", e);
-                let _ = my_tx.send(e).await;
+        })
+        .filter_map(|msg| future::ready(msg.ok()))
+        .scan((), move |_, msg| {
+            let checked = limit.check(msg);
+            let rejections = rejections_tx.clone();
+            async move {
+                let rejected = match checked {
+                    // `None` for the control frames, not commands.
+                    Ok(text) => return Some(text),
+                    Err(rejected) => rejected,
+                };
+                tracing::warn!("Rejected a websocket message: {rejected:?}");
+                let close = rejected.close_frame();
+                let response = rejected.response();
+                if let Ok(v) = websocket::response_to_json(None, &response) {
+                    let _ = rejections.send(Message::Text(v)).await;
+                }
+                match close {
+                    Some(frame) => {
+                        let _ =
+                            rejections.send(Message::Close(Some(frame))).await;
+                        // nothing is read after the connection is closed.
+                        None
+                    }
+                    None => Some(None),
+                }
             }
-            // Send back the response, usually a transaction hash
-            // from processing the transaction relaying command.
-            res_stream
-                .fuse()
-                .map(|v| serde_json::to_string(&v).expect("bad value"))
-                .inspect(|v| tracing::trace!("Sending: {}", v))
-                .map(Message::Text)
-                .map(Result::Ok)
-                .forward(tx)
-                .map_err(|_| webb_relayer_utils::Error::FailedToSendResponse)
-                .await?;
+        })
+        .filter_map(future::ready);
+    let handler = move |cmd, stream| {
+        let ctx = ctx.as_ref().clone();
+        let router = router.clone();
+        async move { router.dispatch(ctx, cmd, stream).await }
+    };
+    let reader = async {
+        websocket::serve_connection(inbound, outbound, handler).await;
+        // the client is gone, so is the need for pings.
+        cancel.cancel();
+    };
+    // drops the client once its send queue is full, if configured to.
+    let dropped = cancel.clone();
+    let dropper = async move {
+        tokio::select! {
+            _ = overflowed.cancelled() => {}
+            _ = dropped.cancelled() => return,
         }
-        Err(e) => {
-            tracing::warn!("Got invalid payload: {:?}", e);
-            tracing::debug!("Invalid payload: {:?}", v);
-            let error = CommandResponse::Error(e.to_string());
-            let value = serde_json::to_string(&error)?;
-            tx.send(Message::Text(value))
-                .map_err(|_| webb_relayer_utils::Error::FailedToSendResponse)
-                .await?;
+        let frame = CloseFrame {
+            code: close_code::POLICY,
+            reason: Cow::from("reading too slowly"),
+        };
+        let _ = close_tx.send(Message::Close(Some(frame))).await;
+        dropped.cancel();
+    };
+    let pinger = async {
+        match heartbeat {
+            Some(heartbeat) => heartbeat.run(ping_tx, cancel.clone()).await,
+            None => drop(ping_tx),
         }
     };
-    Ok(())
+    // Send back the responses, usually the transaction status
+    // from processing the transaction relaying commands.
+    let responses = ReceiverStream::new(my_rx).map(Message::Text);
+    let control = stream::select(
+        ReceiverStream::new(ping_rx),
+        ReceiverStream::new(rejections_rx),
+    );
+    let writer = stream::select(responses, control)
+        .map(Result::Ok)
+        .forward(tx);
+    let (_, _, _, sent) = tokio::join!(reader, pinger, dropper, writer);
+    if let Err(e) = sent {
+        tracing::debug!("Websocket handler error: {e}");
+    }
 }
 
 /// Handles the socket address response
@@ -150,47 +219,25 @@ where
 ///
 /// # Arguments
 ///
-/// * `ip` - Extractor for client IP, taking into account x-forwarded-for and similar headers
+/// * `ctx` - RelayContext reference that holds the trusted proxies
+/// * `peer` - The address of the peer of the connection
+/// * `headers` - The headers of the request, the forwarding headers are only
+///   used when the peer is a trusted proxy
 pub async fn handle_socket_info(
-    InsecureClientIp(ip): InsecureClientIp,
+    State(ctx): State<Arc<RelayerContext>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
 ) -> Json<IpInformationResponse> {
-    Json(IpInformationResponse { ip: ip.to_string() })
-}
-
-/// Handles the command prompts for EVM and Substrate chains
-///
-/// # Arguments
-///
-/// * `ctx` - RelayContext reference that holds the configuration
-/// * `cmd` - The command to execute
-/// * `stream` - The stream to write the response to
-pub async fn handle_cmd(
-    ctx: RelayerContext,
-    cmd: Command,
-    stream: CommandStream,
-) -> Result<(), CommandResponse> {
-    if !ctx.config.features.private_tx_relay {
-        return Err(CommandResponse::Error(
-            "Private transaction relaying is not enabled.".to_string(),
-        ));
-    }
-
-    match cmd {
-        Command::Substrate(substrate) => match substrate {
-            SubstrateCommandType::VAnchor(vanchor) => {
-                handle_substrate_vanchor_relay_tx(ctx, vanchor, stream).await
-            }
-        },
-        Command::Evm(evm) => match evm {
-            EvmCommandType::VAnchor(vanchor) => {
-                handle_vanchor_relay_tx(ctx, vanchor, stream).await
-            }
-        },
-        Command::Ping() => {
-            let _ = stream.send(CommandResponse::Pong()).await;
-            Ok(())
-        }
-    }
+    let (ip, source) = client_ip::resolve_client_ip(
+        peer.ip(),
+        &headers,
+        &ctx.config.trusted_proxies,
+    );
+    Json(IpInformationResponse {
+        ip: ip.to_string(),
+        family: ip.into(),
+        source,
+    })
 }
 
 /// Handler for fee estimation
@@ -0,0 +1,480 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use tokio::sync::broadcast::error::RecvError;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::{
+    CommandResponse, CommandStream, ProposalProgress, SubscribeProposalCommand,
+    SubscribeProposalsCommand,
+};
+use webb_relayer_store::{
+    ProposalEventRecord, ProposalEventStore, SledStore, MAX_PROPOSAL_EVENTS,
+};
+use webb_relayer_utils::auth::token_matches;
+
+/// How many stored events are read at once, when resuming from a cursor.
+const REPLAY_PAGE_SIZE: usize = 256;
+
+/// How many stored events a single proposal subscriber reads at most, when
+/// it subscribes and each time it falls behind.
+const MAX_RESCANNED_EVENTS: usize = MAX_PROPOSAL_EVENTS as usize;
+
+/// Which proposal events a subscriber gets.
+#[derive(Debug, Clone, Default)]
+pub struct ProposalEventFilter {
+    chain_ids: Vec<u32>,
+    addresses: Vec<ethereum_types::Address>,
+}
+
+impl ProposalEventFilter {
+    /// Whether the subscriber asked for this event.
+    ///
+    /// An empty list lets everything through, the chains match the source
+    /// or the destination of the proposal.
+    pub fn matches(&self, record: &ProposalEventRecord) -> bool {
+        let event = &record.event;
+        let chain_matches = self.chain_ids.is_empty()
+            || self.chain_ids.contains(&event.dest_chain_id)
+            || event
+                .src_chain_id
+                .map(|id| self.chain_ids.contains(&id))
+                .unwrap_or(false);
+        let address_matches = self.addresses.is_empty()
+            || event
+                .target_address
+                .map(|address| self.addresses.contains(&address))
+                .unwrap_or(false);
+        chain_matches && address_matches
+    }
+}
+
+impl From<&SubscribeProposalsCommand> for ProposalEventFilter {
+    fn from(cmd: &SubscribeProposalsCommand) -> Self {
+        Self {
+            chain_ids: cmd.chain_ids.clone(),
+            addresses: cmd.addresses.clone(),
+        }
+    }
+}
+
+/// Streams the proposal events to the subscriber, until it goes away.
+///
+/// The subscriber resumes after its `cursor`, from the stored events, then
+/// gets the new ones as they are recorded. If it falls behind, the events
+/// it missed are counted, and the subscription ends with the cursor to
+/// subscribe with again to get them back, so none of the events after it
+/// is sent twice; the watchers are never held up by it.
+///
+/// Otherwise the subscription never ends on its own, so it should come with
+/// a `requestId`, for the connection to keep serving the other commands.
+pub async fn subscribe_proposals(
+    ctx: RelayerContext,
+    cmd: SubscribeProposalsCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    match &ctx.config.admin_token {
+        Some(token) if token_matches(token, &cmd.token) => {}
+        _ => {
+            return Err(CommandResponse::Error(
+                "Invalid admin token".to_string(),
+            ))
+        }
+    }
+    let filter = ProposalEventFilter::from(&cmd);
+    let store = ctx.store();
+    // subscribed first, so nothing gets lost between the replay and the
+    // live events.
+    let mut live = store.subscribe_proposal_events();
+    // the sequence number of the last event the subscriber got.
+    let mut cursor = match cmd.cursor {
+        Some(cursor) => cursor,
+        None => store
+            .last_proposal_event_seq()
+            .map_err(|e| CommandResponse::Error(e.to_string()))?,
+    };
+    loop {
+        let page = next_page(store, &mut cursor)?;
+        if page.is_empty() {
+            break;
+        }
+        for record in page.into_iter().filter(|r| filter.matches(r)) {
+            let response = CommandResponse::ProposalEvent(record);
+            if stream.send(response).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    loop {
+        let received = tokio::select! {
+            received = live.recv() => received,
+            _ = stream.closed() => return Ok(()),
+        };
+        let response = match received {
+            // already replayed.
+            Ok(record) if record.seq <= cursor => continue,
+            Ok(record) => {
+                cursor = record.seq;
+                if !filter.matches(&record) {
+                    continue;
+                }
+                CommandResponse::ProposalEvent(record)
+            }
+            Err(RecvError::Lagged(dropped)) => {
+                tracing::warn!(
+                    dropped,
+                    "A proposal events subscriber fell behind",
+                );
+                ctx.metrics
+                    .lock()
+                    .await
+                    .proposal_events_dropped
+                    .inc_by(dropped as f64);
+                let response = CommandResponse::ProposalEventsDropped {
+                    dropped,
+                    resume_cursor: cursor,
+                };
+                // the subscriber resumes from the cursor, the events after
+                // it would be sent twice otherwise.
+                let _ = stream.send(response).await;
+                return Ok(());
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if stream.send(response).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Streams the progress of a single proposal to the subscriber, until the
+/// proposal is done or the subscriber goes away.
+///
+/// The progress so far comes first, from the stored events, even if the
+/// proposal is already done. A subscriber that falls behind catches up from
+/// the stored events too, since it only follows a handful of them; each
+/// rescan reads at most [`MAX_RESCANNED_EVENTS`] of them, and skips the
+/// rest.
+pub async fn subscribe_proposal(
+    ctx: RelayerContext,
+    cmd: SubscribeProposalCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    let store = ctx.store();
+    let mut live = store.subscribe_proposal_events();
+    let followed = |record: &ProposalEventRecord| {
+        let event = &record.event;
+        event.data_hash == cmd.data_hash
+            && (event.dest_chain_id == cmd.chain_id
+                || event.src_chain_id == Some(cmd.chain_id))
+    };
+    let mut progress: Option<ProposalProgress> = None;
+    let mut cursor = 0;
+    loop {
+        // the stored events, at first and whenever live ones got missed.
+        let mut changed = false;
+        let mut rescanned = 0;
+        loop {
+            if rescanned >= MAX_RESCANNED_EVENTS {
+                let last = store
+                    .last_proposal_event_seq()
+                    .map_err(|e| CommandResponse::Error(e.to_string()))?;
+                tracing::warn!(
+                    skipped = last.saturating_sub(cursor),
+                    "A proposal subscriber fell too far behind, skipping \
+                     the stored events",
+                );
+                cursor = cursor.max(last);
+                break;
+            }
+            let page = next_page(store, &mut cursor)?;
+            if page.is_empty() {
+                break;
+            }
+            rescanned += page.len();
+            for record in page.iter().filter(|r| followed(r)) {
+                advance(&mut progress, record);
+                changed = true;
+            }
+        }
+        if let (true, Some(progress)) = (changed, &progress) {
+            let response = CommandResponse::ProposalProgress(progress.clone());
+            // the final progress is sent too, for a proposal already done.
+            if stream.send(response).await.is_err() || progress.done {
+                return Ok(());
+            }
+        }
+        loop {
+            let received = tokio::select! {
+                received = live.recv() => received,
+                _ = stream.closed() => return Ok(()),
+            };
+            let record = match received {
+                Ok(record) if record.seq <= cursor => continue,
+                Ok(record) => record,
+                Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            cursor = record.seq;
+            if !followed(&record) {
+                continue;
+            }
+            let current = advance(&mut progress, &record);
+            let done = current.done;
+            let response = CommandResponse::ProposalProgress(current);
+            if stream.send(response).await.is_err() || done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Reads the stored events after `cursor`, a page at a time, and moves the
+/// cursor past them; empty once there are none left.
+fn next_page(
+    store: &SledStore,
+    cursor: &mut u64,
+) -> Result<Vec<ProposalEventRecord>, CommandResponse> {
+    let page = store
+        .get_proposal_events_after(*cursor, REPLAY_PAGE_SIZE)
+        .map_err(|e| CommandResponse::Error(e.to_string()))?;
+    if let Some(last) = page.last() {
+        *cursor = last.seq;
+    }
+    Ok(page)
+}
+
+fn advance(
+    progress: &mut Option<ProposalProgress>,
+    record: &ProposalEventRecord,
+) -> ProposalProgress {
+    let progress = progress
+        .get_or_insert_with(|| ProposalProgress::new(record.event.data_hash));
+    progress.apply(record);
+    progress.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_store::{ProposalEvent, ProposalEventKind, SledStore};
+
+    fn event(dest_chain_id: u32, src_chain_id: Option<u32>) -> ProposalEvent {
+        ProposalEvent {
+            kind: ProposalEventKind::Created,
+            data_hash: Default::default(),
+            resource_id: Default::default(),
+            nonce: 1,
+            src_chain_id,
+            dest_chain_id,
+            target_address: Some([1u8; 20].into()),
+            tx_hash: None,
+        }
+    }
+
+    fn context(admin_token: Option<&str>) -> RelayerContext {
+        let mut config = webb_relayer_config::WebbRelayerConfig::default();
+        config.admin_token = admin_token.map(ToOwned::to_owned);
+        let store = SledStore::temporary().unwrap();
+        RelayerContext::new(config, store).unwrap()
+    }
+
+    fn command(token: &str, cursor: Option<u64>) -> SubscribeProposalsCommand {
+        SubscribeProposalsCommand {
+            token: token.to_string(),
+            cursor,
+            chain_ids: vec![5],
+            addresses: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn should_refuse_the_subscribers_without_the_admin_token() {
+        for ctx in [context(None), context(Some("secret"))] {
+            let (tx, _rx) = tokio::sync::mpsc::channel(10);
+            let result =
+                subscribe_proposals(ctx, command("guess", None), tx).await;
+            assert!(matches!(result, Err(CommandResponse::Error(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_refuse_an_empty_admin_token() {
+        let ctx = context(Some(""));
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let result = subscribe_proposals(ctx, command("", None), tx).await;
+        assert!(matches!(result, Err(CommandResponse::Error(_))));
+    }
+
+    #[tokio::test]
+    async fn should_end_the_subscription_of_a_subscriber_that_fell_behind() {
+        let ctx = context(Some("secret"));
+        let store = ctx.store().clone();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let cmd = command("secret", None);
+        let subscription = tokio::spawn(subscribe_proposals(ctx, cmd, tx));
+        // until the subscriber gets the live events.
+        loop {
+            store.record_proposal_event(event(5, None)).unwrap();
+            tokio::task::yield_now().await;
+            if rx.try_recv().is_ok() {
+                break;
+            }
+        }
+        // it does not run meanwhile, so it falls behind.
+        for _ in 0..2048 {
+            store.record_proposal_event(event(5, None)).unwrap();
+        }
+        let mut responses = Vec::new();
+        while let Some(response) = rx.recv().await {
+            responses.push(response);
+        }
+        let Some(CommandResponse::ProposalEventsDropped {
+            resume_cursor, ..
+        }) = responses.pop()
+        else {
+            panic!("expected the cursor to resume from");
+        };
+        // the events sent before falling behind are all before the cursor.
+        for response in responses {
+            let CommandResponse::ProposalEvent(record) = response else {
+                panic!("expected a proposal event");
+            };
+            assert!(record.seq <= resume_cursor);
+        }
+        subscription
+            .await
+            .unwrap()
+            .unwrap_or_else(|e| panic!("{e:?}"));
+    }
+
+    #[tokio::test]
+    async fn should_stream_the_progress_of_a_proposal_until_it_is_done() {
+        let ctx = context(None);
+        let store = ctx.store().clone();
+        let created = ProposalEvent {
+            data_hash: [7u8; 32].into(),
+            ..event(5, Some(1))
+        };
+        let with_kind = |kind| created.clone().with_tx(kind, None);
+        store.record_proposal_event(created.clone()).unwrap();
+        store
+            .record_proposal_event(with_kind(ProposalEventKind::VoteMined))
+            .unwrap();
+        // another proposal, not followed.
+        store.record_proposal_event(event(5, Some(1))).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let cmd = SubscribeProposalCommand {
+            chain_id: 1,
+            data_hash: [7u8; 32].into(),
+        };
+        let subscription = tokio::spawn(subscribe_proposal(ctx, cmd, tx));
+        let first = rx.recv().await;
+        let Some(CommandResponse::ProposalProgress(progress)) = first else {
+            panic!("expected the progress so far");
+        };
+        assert_eq!(progress.status, ProposalEventKind::VoteMined);
+        assert_eq!(progress.votes, 1);
+        assert!(!progress.done);
+
+        store
+            .record_proposal_event(with_kind(ProposalEventKind::Passed))
+            .unwrap();
+        let executed = created
+            .clone()
+            .with_tx(ProposalEventKind::Executed, Some([9u8; 32].into()));
+        store.record_proposal_event(executed).unwrap();
+        let mut last = None;
+        while let Some(response) = rx.recv().await {
+            last = Some(response);
+        }
+        let Some(CommandResponse::ProposalProgress(progress)) = last else {
+            panic!("expected the final progress");
+        };
+        assert_eq!(progress.status, ProposalEventKind::Executed);
+        assert_eq!(progress.tx_hash, Some([9u8; 32].into()));
+        assert!(progress.done);
+        subscription
+            .await
+            .unwrap()
+            .unwrap_or_else(|e| panic!("{e:?}"));
+    }
+
+    #[tokio::test]
+    async fn should_send_the_final_progress_of_a_proposal_already_done() {
+        let ctx = context(None);
+        let store = ctx.store().clone();
+        let created = ProposalEvent {
+            data_hash: [7u8; 32].into(),
+            ..event(5, Some(1))
+        };
+        store.record_proposal_event(created.clone()).unwrap();
+        let executed = created
+            .clone()
+            .with_tx(ProposalEventKind::Executed, Some([9u8; 32].into()));
+        store.record_proposal_event(executed).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let cmd = SubscribeProposalCommand {
+            chain_id: 5,
+            data_hash: [7u8; 32].into(),
+        };
+        subscribe_proposal(ctx, cmd, tx)
+            .await
+            .unwrap_or_else(|e| panic!("{e:?}"));
+        let Some(CommandResponse::ProposalProgress(progress)) = rx.recv().await
+        else {
+            panic!("expected the final progress");
+        };
+        assert_eq!(progress.status, ProposalEventKind::Executed);
+        assert!(progress.done);
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn should_resume_after_the_cursor_then_stream_the_new_events() {
+        let ctx = context(Some("secret"));
+        let store = ctx.store().clone();
+        let first = store.record_proposal_event(event(5, Some(1))).unwrap();
+        // filtered out, the subscriber only wants the chain 5.
+        store.record_proposal_event(event(4, Some(3))).unwrap();
+        let third = store.record_proposal_event(event(4, Some(5))).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let cmd = command("secret", Some(0));
+        let subscription = tokio::spawn(subscribe_proposals(ctx, cmd, tx));
+        assert_eq!(
+            rx.recv().await,
+            Some(CommandResponse::ProposalEvent(first))
+        );
+        assert_eq!(
+            rx.recv().await,
+            Some(CommandResponse::ProposalEvent(third))
+        );
+        let fourth = store.record_proposal_event(event(5, None)).unwrap();
+        assert_eq!(
+            rx.recv().await,
+            Some(CommandResponse::ProposalEvent(fourth))
+        );
+
+        // the subscription stops once the client is gone.
+        drop(rx);
+        store.record_proposal_event(event(5, None)).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), subscription)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_or_else(|e| panic!("{e:?}"));
+    }
+}
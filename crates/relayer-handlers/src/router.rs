@@ -0,0 +1,312 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routes the websocket commands to their handlers, by the tag of the
+//! command: the single field of its JSON object, such as `ping` in
+//! `{"ping": []}`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+use webb_relayer_config::WebbRelayerConfig;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::{
+    CancelScheduledCommand, CommandResponse, CommandStream, EvmCommandType,
+    SubscribeProposalCommand, SubscribeProposalsCommand, SubstrateCommandType,
+    WithdrawStatus,
+};
+use webb_relayer_tx_relay::evm::scheduled::cancel_scheduled_relay;
+use webb_relayer_tx_relay::evm::vanchor::handle_vanchor_relay_tx;
+use webb_relayer_tx_relay::substrate::vanchor::handle_substrate_vanchor_relay_tx;
+
+use crate::proposal_events;
+
+/// A command received over the websocket, not parsed yet past its tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedCommand {
+    /// The tag the handler of the command is registered with.
+    pub tag: String,
+    /// The payload of the command, parsed by its handler.
+    pub payload: serde_json::Value,
+}
+
+impl RoutedCommand {
+    /// Splits the JSON object of a command into its tag and payload.
+    pub fn from_value(value: serde_json::Value) -> serde_json::Result<Self> {
+        use serde::de::Error;
+        let serde_json::Value::Object(fields) = value else {
+            return Err(Error::custom("A command must be a JSON object"));
+        };
+        if fields.len() != 1 {
+            return Err(Error::custom(
+                "A command must have a single tag, such as {\"ping\": []}",
+            ));
+        }
+        let (tag, payload) = fields.into_iter().next().unwrap();
+        Ok(Self { tag, payload })
+    }
+}
+
+type BoxedHandler = Arc<
+    dyn Fn(
+            RelayerContext,
+            serde_json::Value,
+            CommandStream,
+        ) -> BoxFuture<'static, Result<(), CommandResponse>>
+        + Send
+        + Sync,
+>;
+
+/// The handlers of the websocket commands, by the tag of their command.
+///
+/// The built-in commands are registered by [`CommandRouter::from_config`],
+/// with the same tags and payloads as in the first version of the protocol,
+/// such as `{"evm": {"vAnchor": {..}}}`, so that its clients keep working as
+/// is.
+#[derive(Clone, Default)]
+pub struct CommandRouter {
+    handlers: BTreeMap<String, BoxedHandler>,
+}
+
+impl CommandRouter {
+    /// A router without any command.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A router with the built-in commands enabled in the config.
+    ///
+    /// The transaction relaying commands are answered with an error, rather
+    /// than relayed, without the `private-tx-relay` feature enabled.
+    pub fn from_config(config: &WebbRelayerConfig) -> Self {
+        let mut router = Self::new();
+        router
+            .register("ping", |_, _: IgnoredAny, stream| async move {
+                let _ = stream.send(CommandResponse::Pong()).await;
+                Ok(())
+            })
+            .register("subscribeProposals", subscribe_proposals)
+            .register("subscribeProposal", subscribe_proposal);
+        if config.features.private_tx_relay {
+            router
+                .register("evm", evm)
+                .register("substrate", substrate)
+                .register("cancelScheduled", cancel_scheduled);
+        } else {
+            for tag in ["evm", "substrate", "cancelScheduled"] {
+                router.register(tag, |_, _: IgnoredAny, _| async {
+                    Err(CommandResponse::Error(
+                        "Private transaction relaying is not enabled."
+                            .to_string(),
+                    ))
+                });
+            }
+        }
+        router
+    }
+
+    /// Registers the handler of the commands tagged with `tag`, replacing
+    /// the one registered before, if any.
+    ///
+    /// The payload of the command is deserialized into `T` before it gets
+    /// handed to `handler`; an invalid payload is answered with an error.
+    pub fn register<T, H, Fut>(
+        &mut self,
+        tag: impl Into<String>,
+        handler: H,
+    ) -> &mut Self
+    where
+        T: DeserializeOwned + Send + 'static,
+        H: Fn(RelayerContext, T, CommandStream) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), CommandResponse>> + Send + 'static,
+    {
+        let handler: BoxedHandler = Arc::new(move |ctx, payload, stream| {
+            let cmd = match serde_json::from_value(payload) {
+                Ok(cmd) => cmd,
+                Err(e) => {
+                    let error = CommandResponse::Error(e.to_string());
+                    return future::ready(Err(error)).boxed();
+                }
+            };
+            handler(ctx, cmd, stream).boxed()
+        });
+        self.handlers.insert(tag.into(), handler);
+        self
+    }
+
+    /// The tags of the registered commands, sorted.
+    pub fn tags(&self) -> Vec<String> {
+        self.handlers.keys().cloned().collect()
+    }
+
+    /// Hands the command to the handler registered with its tag.
+    ///
+    /// Answers with [`CommandResponse::UnknownCommand`] if there is none.
+    pub async fn dispatch(
+        &self,
+        ctx: RelayerContext,
+        cmd: RoutedCommand,
+        stream: CommandStream,
+    ) -> Result<(), CommandResponse> {
+        match self.handlers.get(&cmd.tag) {
+            Some(handler) => handler(ctx, cmd.payload, stream).await,
+            None => Err(CommandResponse::UnknownCommand {
+                tag: cmd.tag,
+                supported: self.tags(),
+            }),
+        }
+    }
+}
+
+async fn evm(
+    ctx: RelayerContext,
+    cmd: EvmCommandType,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    match cmd {
+        EvmCommandType::VAnchor(vanchor) => {
+            handle_vanchor_relay_tx(ctx, vanchor, stream).await
+        }
+    }
+}
+
+async fn substrate(
+    ctx: RelayerContext,
+    cmd: SubstrateCommandType,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    match cmd {
+        SubstrateCommandType::VAnchor(vanchor) => {
+            handle_substrate_vanchor_relay_tx(ctx, vanchor, stream).await
+        }
+    }
+}
+
+async fn cancel_scheduled(
+    ctx: RelayerContext,
+    cmd: CancelScheduledCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    let cancelled = cancel_scheduled_relay(&ctx, &cmd.idempotency_key)
+        .map_err(|e| CommandResponse::Error(e.to_string()))?;
+    if !cancelled {
+        return Err(CommandResponse::Error(
+            "No withdrawal is scheduled with this key".to_string(),
+        ));
+    }
+    let _ = stream
+        .send(CommandResponse::Withdraw(WithdrawStatus::Cancelled))
+        .await;
+    Ok(())
+}
+
+async fn subscribe_proposals(
+    ctx: RelayerContext,
+    cmd: SubscribeProposalsCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    proposal_events::subscribe_proposals(ctx, cmd, stream).await
+}
+
+async fn subscribe_proposal(
+    ctx: RelayerContext,
+    cmd: SubscribeProposalCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    proposal_events::subscribe_proposal(ctx, cmd, stream).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_store::SledStore;
+
+    fn context(private_tx_relay: bool) -> RelayerContext {
+        let mut config = WebbRelayerConfig::default();
+        config.features.private_tx_relay = private_tx_relay;
+        let store = SledStore::temporary().unwrap();
+        RelayerContext::new(config, store).unwrap()
+    }
+
+    fn command(v: serde_json::Value) -> RoutedCommand {
+        RoutedCommand::from_value(v).unwrap()
+    }
+
+    #[test]
+    fn commands_should_have_a_single_tag() {
+        let cmd = command(serde_json::json!({"ping": []}));
+        assert_eq!(cmd.tag, "ping");
+        assert_eq!(cmd.payload, serde_json::json!([]));
+        let two = serde_json::json!({"ping": [], "evm": {}});
+        assert!(RoutedCommand::from_value(two).is_err());
+        assert!(RoutedCommand::from_value(serde_json::json!([])).is_err());
+    }
+
+    #[tokio::test]
+    async fn relaying_commands_should_only_be_handled_when_enabled() {
+        let ctx = context(false);
+        let router = CommandRouter::from_config(&ctx.config);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let ping = command(serde_json::json!({"ping": []}));
+        router
+            .dispatch(ctx.clone(), ping, tx.clone())
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await, Some(CommandResponse::Pong()));
+
+        let cancel = serde_json::json!({"cancelScheduled": {
+            "idempotencyKey": "a"
+        }});
+        let result = router
+            .dispatch(ctx.clone(), command(cancel.clone()), tx.clone())
+            .await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::Error(
+                "Private transaction relaying is not enabled.".to_string()
+            ))
+        );
+        let unknown = command(serde_json::json!({"pong": []}));
+        let result = router.dispatch(ctx, unknown, tx).await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::UnknownCommand {
+                tag: "pong".to_string(),
+                supported: vec![
+                    "cancelScheduled".to_string(),
+                    "evm".to_string(),
+                    "ping".to_string(),
+                    "subscribeProposal".to_string(),
+                    "subscribeProposals".to_string(),
+                    "substrate".to_string(),
+                ],
+            })
+        );
+
+        let ctx = context(true);
+        let router = CommandRouter::from_config(&ctx.config);
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let result = router.dispatch(ctx, command(cancel), tx).await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::Error(
+                "No withdrawal is scheduled with this key".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+use ethereum_types::{Address, H256};
+use serde::Serialize;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{CommitmentIndexStore, CommitmentLocation};
+use webb_relayer_utils::HandlerError;
+
+/// Commitment location response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitmentLocationResponse {
+    /// The typed chain id of the anchor.
+    typed_chain_id: u64,
+    /// The chain id of the anchor, without its type.
+    chain_id: u32,
+    /// The address of the anchor.
+    contract: Address,
+    /// The index of the commitment in the anchor merkle tree.
+    leaf_index: u32,
+}
+
+impl From<CommitmentLocation> for CommitmentLocationResponse {
+    fn from(location: CommitmentLocation) -> Self {
+        Self {
+            typed_chain_id: location.chain_id.chain_id(),
+            chain_id: location.chain_id.underlying_chain_id(),
+            contract: location.contract,
+            leaf_index: location.leaf_index,
+        }
+    }
+}
+
+/// Handles commitment lookup requests
+///
+/// Returns a Result with the `CommitmentLocationResponse` on success
+///
+/// # Arguments
+///
+/// * `commitment` - The commitment to look up, as a `0x` prefixed hex string
+pub async fn handle_commitment_lookup(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path(commitment): Path<H256>,
+) -> Result<Json<CommitmentLocationResponse>, HandlerError> {
+    let location = ctx
+        .store()
+        .lookup_commitment(commitment.to_fixed_bytes())?
+        .ok_or_else(|| {
+            HandlerError(
+                StatusCode::NOT_FOUND,
+                format!("Commitment not found: {commitment:?}"),
+            )
+        })?;
+    Ok(Json(location.into()))
+}
@@ -0,0 +1,110 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+use ethereum_types::Address;
+use serde::Serialize;
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{
+    DepositCorrelationId, DepositPropagationRecord, DepositPropagationStore,
+};
+use webb_relayer_utils::HandlerError;
+
+/// The overall propagation status of a deposit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DepositPropagationStatus {
+    /// Proposals were not yet created or none of them got executed.
+    Pending,
+    /// Executed on some, but not all, of the linked chains.
+    Partial,
+    /// Executed on all of the linked chains.
+    Propagated,
+}
+
+impl From<&DepositPropagationRecord> for DepositPropagationStatus {
+    fn from(record: &DepositPropagationRecord) -> Self {
+        let executed = record
+            .destinations
+            .values()
+            .filter(|d| d.is_executed())
+            .count();
+        match executed {
+            0 => Self::Pending,
+            n if n == record.destinations.len() => Self::Propagated,
+            _ => Self::Partial,
+        }
+    }
+}
+
+/// Deposit propagation status response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositStatusResponse {
+    correlation_id: DepositCorrelationId,
+    status: DepositPropagationStatus,
+    #[serde(flatten)]
+    record: DepositPropagationRecord,
+}
+
+/// Handles deposit propagation status requests for evm
+///
+/// Returns a Result with the `DepositStatusResponse` on success
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the source chain
+/// * `anchor` - An address of the source anchor
+/// * `leaf_index` - The leaf index of the deposit
+pub async fn handle_deposit_status_evm(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((chain_id, anchor, leaf_index)): Path<(u32, Address, u32)>,
+) -> Result<Json<DepositStatusResponse>, HandlerError> {
+    // check if chain is supported
+    if !ctx.config.evm.contains_key(&chain_id.to_string()) {
+        tracing::warn!("Unsupported Chain: {chain_id}");
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Chain: {chain_id}"),
+        ));
+    }
+    let src_target_system =
+        TargetSystem::new_contract_address(anchor.to_fixed_bytes());
+    let src_resource_id =
+        ResourceId::new(src_target_system, TypedChainId::Evm(chain_id));
+    // A VAnchor transaction inserts two leaves, and the anchor update proposal
+    // is only created for the second (odd) one, which carries both.
+    let proposal_leaf_index = leaf_index | 1;
+    let correlation_id =
+        DepositCorrelationId::new(src_resource_id, proposal_leaf_index);
+    let record = ctx
+        .store()
+        .get_deposit_propagation(correlation_id)?
+        .ok_or_else(|| {
+            HandlerError(
+                StatusCode::NOT_FOUND,
+                format!("Deposit not found: {anchor} at leaf {leaf_index}"),
+            )
+        })?;
+    Ok(Json(DepositStatusResponse {
+        correlation_id,
+        status: DepositPropagationStatus::from(&record),
+        record,
+    }))
+}
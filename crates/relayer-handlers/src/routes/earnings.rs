@@ -0,0 +1,212 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Query, State};
+use axum::Json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{FeeAccountingStore, FeeEarnings};
+use webb_relayer_utils::HandlerError;
+
+/// The period the earnings are grouped by.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum EarningsPeriod {
+    /// Per day.
+    #[default]
+    Day,
+    /// Per 7 days.
+    Week,
+    /// Per 30 days.
+    Month,
+}
+
+impl EarningsPeriod {
+    /// The length of the period, in days.
+    pub fn days(self) -> u64 {
+        match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+        }
+    }
+}
+
+/// Earnings query
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct EarningsQuery {
+    /// The period the earnings are grouped by.
+    ///
+    /// default: `day`
+    #[serde(default)]
+    pub period: EarningsPeriod,
+}
+
+/// The fees collected on one chain, in one token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsTotal {
+    /// The typed chain id of the chain the fees were collected on.
+    pub typed_chain_id: u64,
+    /// The token the fees were paid in.
+    pub token: String,
+    /// The sum of the fees.
+    pub total: U256,
+    /// The number of relayed withdrawals that paid these fees.
+    pub count: u64,
+}
+
+/// The fees collected on one chain, in one token, during one period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodEarnings {
+    /// The start of the period, in secs since the UNIX epoch.
+    pub period_start: u64,
+    /// The fees collected during the period.
+    #[serde(flatten)]
+    pub earnings: EarningsTotal,
+}
+
+/// Fee earnings response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsResponse {
+    /// The period the earnings are grouped by.
+    pub period: EarningsPeriod,
+    /// All the fees collected so far.
+    pub cumulative: Vec<EarningsTotal>,
+    /// The fees collected per period, oldest first.
+    pub periods: Vec<PeriodEarnings>,
+}
+
+type Totals<K> = BTreeMap<K, (U256, u64)>;
+
+fn add(totals: &mut (U256, u64), earnings: &FeeEarnings) {
+    totals.0 = totals.0.saturating_add(earnings.total);
+    totals.1 += earnings.count;
+}
+
+impl EarningsResponse {
+    /// Groups the daily earnings recorded by the store by `period`.
+    pub fn new(earnings: &[FeeEarnings], period: EarningsPeriod) -> Self {
+        let mut cumulative: Totals<(u64, String)> = BTreeMap::new();
+        let mut periods: Totals<(u64, u64, String)> = BTreeMap::new();
+        for e in earnings {
+            let token = (e.typed_chain_id, e.token.clone());
+            add(cumulative.entry(token).or_default(), e);
+            let period_start = e.day / period.days() * period.days() * 86_400;
+            let key = (period_start, e.typed_chain_id, e.token.clone());
+            add(periods.entry(key).or_default(), e);
+        }
+        let to_total = |typed_chain_id, token, (total, count)| EarningsTotal {
+            typed_chain_id,
+            token,
+            total,
+            count,
+        };
+        Self {
+            period,
+            cumulative: cumulative
+                .into_iter()
+                .map(|((chain, token), v)| to_total(chain, token, v))
+                .collect(),
+            periods: periods
+                .into_iter()
+                .map(|((period_start, chain, token), v)| PeriodEarnings {
+                    period_start,
+                    earnings: to_total(chain, token, v),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Handles relayer fee earnings requests
+///
+/// Returns a Result with the `EarningsResponse` on success
+///
+/// # Arguments
+///
+/// * `query` - The period to group the earnings by
+pub async fn handle_earnings(
+    State(ctx): State<Arc<RelayerContext>>,
+    Query(query): Query<EarningsQuery>,
+) -> Result<Json<EarningsResponse>, HandlerError> {
+    let earnings = ctx.store().get_fee_earnings()?;
+    Ok(Json(EarningsResponse::new(&earnings, query.period)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn earnings(chain: u64, day: u64, total: u64) -> FeeEarnings {
+        FeeEarnings {
+            typed_chain_id: chain,
+            token: String::from("0x01"),
+            day,
+            total: total.into(),
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn earnings_should_be_grouped_by_period() {
+        let recorded = vec![
+            earnings(1, 0, 10),
+            earnings(1, 6, 20),
+            earnings(1, 7, 5),
+            earnings(2, 3, 1),
+        ];
+        let response = EarningsResponse::new(&recorded, EarningsPeriod::Week);
+        assert_eq!(
+            response.cumulative,
+            vec![
+                EarningsTotal {
+                    typed_chain_id: 1,
+                    token: String::from("0x01"),
+                    total: 35.into(),
+                    count: 3,
+                },
+                EarningsTotal {
+                    typed_chain_id: 2,
+                    token: String::from("0x01"),
+                    total: 1.into(),
+                    count: 1,
+                },
+            ]
+        );
+        let periods = response
+            .periods
+            .iter()
+            .map(|p| {
+                (
+                    p.period_start,
+                    p.earnings.typed_chain_id,
+                    p.earnings.total.as_u64(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(periods, vec![(0, 1, 30), (0, 2, 1), (7 * 86_400, 1, 5)]);
+
+        let response = EarningsResponse::new(&recorded, EarningsPeriod::Day);
+        assert_eq!(response.periods.len(), 4);
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+use ethereum_types::Address;
+use serde::Serialize;
+use webb::evm::contract::protocol_solidity::VAnchorContract;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types;
+use webb_proposals::{
+    ResourceId, SubstrateTargetSystem, TargetSystem, TypedChainId,
+};
+use webb_relayer_config::evm::Contract;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{AnchorEdge, EdgeStore};
+use webb_relayer_utils::HandlerError;
+
+/// Edge list response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgesResponse {
+    /// One edge per neighbor anchor, sorted by the neighbor chain id.
+    edges: Vec<AnchorEdge>,
+    /// Whether the anchor is not watched, so the edges cached for it, and
+    /// the roots they carry, do not advance.
+    #[serde(rename = "static")]
+    is_static: bool,
+}
+
+/// Handles edge list requests for evm
+///
+/// The edges are read from the anchor itself, at the latest block, so they
+/// tell what the anchor knows about its neighbors right now.
+///
+/// Returns a Result with the `EdgesResponse` on success
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain to query
+/// * `contract` - An address of the contract to query
+pub async fn handle_edges_evm(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((chain_id, contract)): Path<(u32, Address)>,
+) -> Result<Json<EdgesResponse>, HandlerError> {
+    // check if chain is supported
+    let Some(chain) = ctx.config.evm.get(&chain_id.to_string()) else {
+        tracing::warn!("Unsupported Chain: {chain_id}");
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Chain: {chain_id}"),
+        ));
+    };
+    // check if contract is supported
+    let anchor = chain.contracts.iter().find_map(|c| match c {
+        Contract::VAnchor(c) if c.common.address == contract => Some(c),
+        _ => None,
+    });
+    let Some(anchor) = anchor else {
+        tracing::warn!(
+            "Unsupported Contract: {contract} for chain: {chain_id}"
+        );
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Contract: {contract} for chain: {chain_id}"),
+        ));
+    };
+
+    let provider = ctx.evm_provider(chain_id).await?;
+    let block_number = provider
+        .get_block_number()
+        .await
+        .map_err(|e| HandlerError(StatusCode::BAD_GATEWAY, e.to_string()))?;
+    let edges = VAnchorContract::new(contract, Arc::new(provider))
+        .get_latest_neighbor_edges()
+        .block(block_number)
+        .call()
+        .await?;
+    let mut edges: Vec<_> = edges
+        .into_iter()
+        .map(|edge| AnchorEdge {
+            src_chain_id: edge.chain_id.as_u64(),
+            root: types::H256(edge.root),
+            latest_leaf_index: edge.latest_leaf_index.as_u32(),
+            block_number: block_number.as_u64(),
+        })
+        .collect();
+    edges.sort_by_key(|edge| edge.src_chain_id);
+    Ok(Json(EdgesResponse {
+        edges,
+        is_static: anchor.events_watcher.is_static(),
+    }))
+}
+
+/// Handles edge list requests for substrate
+///
+/// The edges are the ones recorded by the edges watcher of the tree, each
+/// with the block it got updated at.
+///
+/// Returns a Result with the `EdgesResponse` on success
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain to query
+/// * `tree_id` - Tree id of the the source system to query
+/// * `pallet_id` - Pallet id of the the source system to query
+pub async fn handle_edges_substrate(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((chain_id, tree_id, pallet_id)): Path<(u32, u32, u8)>,
+) -> Result<Json<EdgesResponse>, HandlerError> {
+    let target = SubstrateTargetSystem::builder()
+        .pallet_index(pallet_id)
+        .tree_id(tree_id)
+        .build();
+    let history_store_key = ResourceId::new(
+        TargetSystem::Substrate(target),
+        TypedChainId::Substrate(chain_id),
+    );
+    let edges = ctx.store().get_edges(history_store_key)?;
+    // the Substrate pallets cannot be static.
+    Ok(Json(EdgesResponse {
+        edges,
+        is_static: false,
+    }))
+}
@@ -31,6 +31,9 @@ use super::OptionalRangeQuery;
 pub struct EncryptedOutputsCacheResponse {
     encrypted_outputs: Vec<Vec<u8>>,
     last_queried_block: u64,
+    /// Whether the contract is not watched, so its outputs do not advance.
+    #[serde(rename = "static")]
+    is_static: bool,
 }
 
 /// Handles encrypted outputs data requests for evm
@@ -124,5 +127,6 @@ pub async fn handle_encrypted_outputs_cache_evm(
     Ok(Json(EncryptedOutputsCacheResponse {
         encrypted_outputs: encrypted_output,
         last_queried_block,
+        is_static: event_watcher_config.is_static(),
     }))
 }
@@ -1,5 +1,9 @@
 use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Response;
 use axum::Json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::sync::Arc;
 
 use serde::Serialize;
@@ -8,7 +12,14 @@ use webb::evm::ethers::{
     prelude::k256::SecretKey,
     signers::{LocalWallet, Signer},
 };
-use webb_relayer_context::RelayerContext;
+use webb_proposals::TypedChainId;
+use webb_relayer_config::evm::{Contract, EvmChainConfig};
+use webb_relayer_config::substrate::{Pallet, SubstrateConfig};
+use webb_relayer_config::{FeaturesConfig, WebbRelayerConfig};
+use webb_relayer_context::{LastRelayedTx, RelayerContext};
+use webb_relayer_utils::HandlerError;
+
+use crate::cache::{respond_cached, ETag};
 
 /// Build info data
 #[derive(Debug, Serialize)]
@@ -21,6 +32,31 @@ pub struct BuildInfo {
     pub timestamp: String,
 }
 
+impl BuildInfo {
+    /// Returns the build info of the running relayer, embedded at compile
+    /// time by the build script.
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: option_env!("GIT_COMMIT").unwrap_or("unknown").to_string(),
+            timestamp: option_env!("SOURCE_TIMESTAMP")
+                .unwrap_or("unknown")
+                .to_string(),
+        }
+    }
+}
+
+/// Formats the build info as printed by `webb-relayer --version`.
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "webb-relayer {} ({} {})",
+            self.version, self.commit, self.timestamp
+        )
+    }
+}
+
 /// Relayer config data
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -32,20 +68,175 @@ pub struct RelayerConfig {
     pub build: BuildInfo,
 }
 
+/// The last transaction relayed per chain, grouped like the chains in the config.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastRelayedTxs {
+    /// Last relayed transaction per EVM chain id.
+    pub evm: BTreeMap<String, LastRelayedTx>,
+    /// Last relayed transaction per Substrate chain id.
+    pub substrate: BTreeMap<String, LastRelayedTx>,
+}
+
+/// An operation the relayer performs on a chain.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// Relays the withdrawals of the VAnchors, the `evm` and `substrate`
+    /// websocket commands.
+    VAnchorWithdraw,
+    /// Signs and relays the anchor update proposals of the VAnchors, or
+    /// executes the signed proposals on the signature bridge.
+    ProposalRelay,
+    /// Serves the leaves and encrypted outputs of the VAnchors.
+    DataQuery,
+}
+
+/// The operations the relayer performs per chain, grouped like the chains in
+/// the config.
+///
+/// Derived from the features, chains and contracts enabled in the config, so
+/// that clients pick a relayer supporting the operation they need.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// The operations per EVM chain id.
+    pub evm: BTreeMap<String, BTreeSet<Capability>>,
+    /// The operations per Substrate chain id.
+    pub substrate: BTreeMap<String, BTreeSet<Capability>>,
+}
+
+impl Capabilities {
+    /// The capabilities of a relayer running with the given config.
+    ///
+    /// The disabled chains, and the chains without any capability, are
+    /// left out.
+    pub fn from_config(config: &WebbRelayerConfig) -> Self {
+        let features = &config.features;
+        let evm = config
+            .evm
+            .values()
+            .filter(|chain| chain.enabled)
+            .map(|chain| (chain.chain_id, evm_capabilities(features, chain)));
+        let substrate = config
+            .substrate
+            .values()
+            .filter(|node| node.enabled)
+            .map(|node| {
+                (node.chain_id, substrate_capabilities(features, node))
+            });
+        let by_chain_id = |(chain_id, capabilities): (u32, BTreeSet<_>)| {
+            (!capabilities.is_empty())
+                .then(|| (chain_id.to_string(), capabilities))
+        };
+        Self {
+            evm: evm.filter_map(by_chain_id).collect(),
+            substrate: substrate.filter_map(by_chain_id).collect(),
+        }
+    }
+}
+
+fn evm_capabilities(
+    features: &FeaturesConfig,
+    chain: &EvmChainConfig,
+) -> BTreeSet<Capability> {
+    let mut capabilities = BTreeSet::new();
+    for contract in &chain.contracts {
+        match contract {
+            Contract::VAnchor(c) => {
+                if !chain.is_contract_enabled(c.common.address) {
+                    continue;
+                }
+                let watcher = &c.events_watcher;
+                // static contracts are served from the store, nothing is
+                // relayed to them.
+                if features.private_tx_relay && !watcher.is_static() {
+                    capabilities.insert(Capability::VAnchorWithdraw);
+                }
+                if features.governance_relay
+                    && watcher.enabled
+                    && !watcher.is_static()
+                    && c.proposal_signing_backend.is_some()
+                {
+                    capabilities.insert(Capability::ProposalRelay);
+                }
+                if features.data_query && watcher.enable_data_query {
+                    capabilities.insert(Capability::DataQuery);
+                }
+            }
+            Contract::SignatureBridge(c) => {
+                if features.governance_relay
+                    && chain.is_contract_enabled(c.common.address)
+                {
+                    capabilities.insert(Capability::ProposalRelay);
+                }
+            }
+        }
+    }
+    capabilities
+}
+
+fn substrate_capabilities(
+    features: &FeaturesConfig,
+    node: &SubstrateConfig,
+) -> BTreeSet<Capability> {
+    let mut capabilities = BTreeSet::new();
+    for pallet in &node.pallets {
+        match pallet {
+            Pallet::VAnchorBn254(p) => {
+                let watcher = &p.events_watcher;
+                if features.private_tx_relay && !watcher.is_static() {
+                    capabilities.insert(Capability::VAnchorWithdraw);
+                }
+                if features.governance_relay
+                    && watcher.enabled
+                    && !watcher.is_static()
+                    && p.proposal_signing_backend.is_some()
+                {
+                    capabilities.insert(Capability::ProposalRelay);
+                }
+                if features.data_query && watcher.enable_data_query {
+                    capabilities.insert(Capability::DataQuery);
+                }
+            }
+            Pallet::SignatureBridge(_) | Pallet::DKGProposalHandler(_) => {
+                if features.governance_relay {
+                    capabilities.insert(Capability::ProposalRelay);
+                }
+            }
+            Pallet::Dkg(_) | Pallet::DKGProposals(_) => {}
+        }
+    }
+    capabilities
+}
+
 /// Relayer configuration response
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RelayerInformationResponse {
+    /// Version of the relayer.
+    version: String,
+    /// Commit hash the relayer was built from.
+    git_commit: String,
     #[serde(flatten)]
     relayer_config: RelayerConfig,
+    /// The last transaction the relayer successfully submitted on each chain.
+    last_relayed_tx: LastRelayedTxs,
+    /// The operations the relayer performs on each chain.
+    capabilities: Capabilities,
 }
 
 /// Handles relayer configuration requests
 ///
-/// Returns a Result with the `RelayerConfigurationResponse` on success
+/// Returns a Result with the `RelayerConfigurationResponse` on success, or
+/// `304 Not Modified` if the client already has it, see
+/// [`respond_cached`].
 pub async fn handle_relayer_info(
     State(ctx): State<Arc<RelayerContext>>,
-) -> Json<RelayerInformationResponse> {
+    headers: HeaderMap,
+) -> Result<Response, HandlerError> {
     // clone the original config, to update it with accounts.
     let mut config = ctx.config.clone();
 
@@ -76,16 +267,160 @@ pub async fn handle_relayer_info(
             webb_relayer_utils::Result::Ok(())
         });
 
-    // Build info
-    let build_info = BuildInfo {
-        version: std::env::var("CARGO_PKG_VERSION").unwrap_or_default(),
-        commit: std::env::var("GIT_COMMIT").unwrap_or_default(),
-        timestamp: std::env::var("SOURCE_TIMESTAMP").unwrap_or_default(),
-    };
+    let capabilities = Capabilities::from_config(&config);
     let relayer_config = RelayerConfig {
         config,
-        build: build_info,
+        build: BuildInfo::current(),
+    };
+
+    let mut last_relayed_tx = LastRelayedTxs::default();
+    for (chain_id, tx) in ctx.last_relayed_txs().await {
+        let chain_id_string = chain_id.underlying_chain_id().to_string();
+        match chain_id {
+            TypedChainId::Evm(_) => {
+                last_relayed_tx.evm.insert(chain_id_string, tx);
+            }
+            _ => {
+                last_relayed_tx.substrate.insert(chain_id_string, tx);
+            }
+        }
+    }
+
+    let response = RelayerInformationResponse {
+        version: relayer_config.build.version.clone(),
+        git_commit: relayer_config.build.commit.clone(),
+        relayer_config,
+        last_relayed_tx,
+        capabilities,
     };
+    // the revision of the effective config, with the accounts filled in,
+    // and of the last relayed transactions.
+    let etag = ETag::new(format!("{response:?}"));
+    respond_cached(
+        &headers,
+        &etag,
+        ctx.config.http_cache.info_max_age_secs,
+        || Ok(Json(response)),
+    )
+}
+
+/// Handles relayer version requests
+///
+/// Does not need the relayer context, so it is served while the relayer
+/// is still starting.
+pub async fn handle_build_info() -> Json<BuildInfo> {
+    Json(BuildInfo::current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn version_should_name_the_commit_and_build_date() {
+        use axum::body::HttpBody;
+        use axum::response::IntoResponse;
+
+        let mut body = handle_build_info().await.into_response().into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let served: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(served["version"], env!("CARGO_PKG_VERSION"));
+        // embedded by the build script, from the git checkout.
+        let commit = served["commit"].as_str().unwrap();
+        assert_eq!(commit.len(), 40, "not a commit hash: {commit}");
+        assert!(commit.chars().all(|c| c.is_ascii_hexdigit()));
+        let timestamp = served["timestamp"].as_str().unwrap();
+        assert!(timestamp.starts_with("20"), "not a date: {timestamp}");
+
+        let version = BuildInfo::current().to_string();
+        assert_eq!(
+            version,
+            format!(
+                "webb-relayer {} ({commit} {timestamp})",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    fn evm_chain(contracts: serde_json::Value) -> EvmChainConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "hermes",
+            "enabled": true,
+            "http-endpoint": "http://localhost:8545",
+            "ws-endpoint": "ws://localhost:8545",
+            "chain-id": 5001,
+            "contracts": contracts,
+        }))
+        .unwrap()
+    }
+
+    fn vanchor(address: u8, sync: &str) -> serde_json::Value {
+        serde_json::json!({
+            "contract": "VAnchor",
+            "address": format!("0x{address:040x}"),
+            "deployed-at": 1,
+            "events-watcher": {
+                "enabled": true,
+                "enable-data-query": true,
+                "polling-interval": 1000,
+                "sync": sync,
+            },
+            "proposal-signing-backend": {
+                "type": "Mocked",
+                "private-key": format!("0x{}", "01".repeat(32)),
+            },
+        })
+    }
+
+    #[test]
+    fn capabilities_should_follow_the_enabled_features_and_contracts() {
+        let mut config = WebbRelayerConfig::default();
+        config.features.private_tx_relay = true;
+        config.features.governance_relay = false;
+        config.features.data_query = true;
+        let chain = evm_chain(serde_json::json!([vanchor(1, "static")]));
+        config.evm.insert(chain.name.clone(), chain);
+
+        let capabilities = Capabilities::from_config(&config);
+        // static contracts are only queried.
+        assert_eq!(
+            capabilities.evm["5001"],
+            BTreeSet::from([Capability::DataQuery])
+        );
+        assert!(capabilities.substrate.is_empty());
+
+        config.features.governance_relay = true;
+        let chain = evm_chain(serde_json::json!([
+            vanchor(1, "static"),
+            vanchor(2, "watch"),
+        ]));
+        config.evm.insert(chain.name.clone(), chain);
+        let capabilities = Capabilities::from_config(&config);
+        assert_eq!(
+            capabilities.evm["5001"],
+            BTreeSet::from([
+                Capability::VAnchorWithdraw,
+                Capability::ProposalRelay,
+                Capability::DataQuery,
+            ])
+        );
+        let v = serde_json::to_value(&capabilities).unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!({
+                "evm": {"5001": ["vAnchorWithdraw", "proposalRelay", "dataQuery"]},
+                "substrate": {},
+            })
+        );
 
-    Json(RelayerInformationResponse { relayer_config })
+        // disabled chains are left out.
+        config
+            .evm
+            .values_mut()
+            .for_each(|chain| chain.enabled = false);
+        assert!(Capabilities::from_config(&config).evm.is_empty());
+    }
 }
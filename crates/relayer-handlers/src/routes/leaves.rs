@@ -13,44 +13,243 @@
 // limitations under the License.
 
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use std::{collections::HashMap, sync::Arc};
 use webb::evm::ethers::types;
+use webb::substrate::subxt::PolkadotConfig;
 
 use ethereum_types::Address;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use webb_ew_substrate::{
+    reconcile_leaves, tree_history_store_key, StorageLeaves,
+};
 use webb_proposals::{
     ResourceId, SubstrateTargetSystem, TargetSystem, TypedChainId,
 };
+use webb_relayer_config::LeavesFreshnessConfig;
 use webb_relayer_context::RelayerContext;
-use webb_relayer_store::LeafCacheStore;
+use webb_relayer_store::{HistoryStore, LeafCacheStore};
 use webb_relayer_utils::HandlerError;
 
 use super::OptionalRangeQuery;
+use crate::cache::{respond_cached, ETag};
+
+/// Leaves cache query
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct LeavesQuery {
+    /// Only return the leaves that could no longer be reorged away, see
+    /// `leaf-final-confirmations` in the EVM chain config.
+    ///
+    /// default: `false`
+    #[serde(default)]
+    pub final_only: bool,
+    /// The encoding of the leaves in the response.
+    ///
+    /// default: `hex`
+    #[serde(default)]
+    pub format: LeafFormat,
+    /// Respond with `503 Service Unavailable`, and the leaves anyway, when
+    /// the leaves are `stale`, see [`FreshnessStatus`].
+    ///
+    /// default: `false`
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// The encoding of the leaves returned by the leaves endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeafFormat {
+    /// `0x` prefixed hex of the 32 bytes of the leaf.
+    #[default]
+    Hex,
+    /// The leaf as a field element, in decimal, as used by the circuit inputs.
+    Decimal,
+    /// Standard base64 of the 32 bytes of the leaf.
+    Base64,
+}
+
+impl LeafFormat {
+    /// Encodes the leaf in this format.
+    pub fn encode(self, leaf: &types::H256) -> String {
+        match self {
+            LeafFormat::Hex => format!("{leaf:?}"),
+            LeafFormat::Decimal => {
+                types::U256::from_big_endian(leaf.as_bytes()).to_string()
+            }
+            LeafFormat::Base64 => STANDARD.encode(leaf.as_bytes()),
+        }
+    }
+}
+
+/// How up to date the leaves are, see [`LeavesFreshnessConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FreshnessStatus {
+    /// The events watcher synced recently, and kept up with the chain.
+    Live,
+    /// The events watcher did not sync for a while, or fell behind the
+    /// chain, so the latest leaves may be missing.
+    Lagging,
+    /// The events watcher did not sync for long, the leaves should not be
+    /// relied upon.
+    Stale,
+    /// The events watcher never synced, such as when the contract is not
+    /// watched.
+    NeverSynced,
+}
+
+impl FreshnessStatus {
+    /// The status of leaves last synced `secs_since_last_sync` ago, when
+    /// their events watcher was `blocks_behind` its target block.
+    pub fn new(
+        config: &LeavesFreshnessConfig,
+        secs_since_last_sync: u64,
+        blocks_behind: u64,
+    ) -> Self {
+        if secs_since_last_sync >= config.stale_after_secs {
+            FreshnessStatus::Stale
+        } else if secs_since_last_sync >= config.lagging_after_secs
+            || blocks_behind > config.lagging_after_blocks
+        {
+            FreshnessStatus::Lagging
+        } else {
+            FreshnessStatus::Live
+        }
+    }
+}
+
+/// The freshness of the leaves, as of the last sync of their events watcher.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeavesFreshness {
+    /// The last block the events watcher went through.
+    last_synced_block: Option<u64>,
+    /// The head of the chain, as seen by the events watcher on its last sync.
+    chain_head_at_sync: Option<u64>,
+    seconds_since_last_sync: Option<u64>,
+    status: FreshnessStatus,
+}
+
+impl LeavesFreshness {
+    /// Reads the freshness of the leaves of `history_store_key`, whose
+    /// events watcher targets the block `head_offset` blocks before the
+    /// head of the chain, and stays `safety_lag` blocks behind it.
+    fn read(
+        ctx: &RelayerContext,
+        history_store_key: ResourceId,
+        head_offset: u64,
+        safety_lag: u64,
+    ) -> Result<Self, HandlerError> {
+        let store = ctx.store();
+        let synced_at = match store.get_last_synced_at(history_store_key)? {
+            Some(synced_at) => synced_at,
+            None => {
+                return Ok(Self {
+                    last_synced_block: None,
+                    chain_head_at_sync: None,
+                    seconds_since_last_sync: None,
+                    status: FreshnessStatus::NeverSynced,
+                })
+            }
+        };
+        let last_synced_block =
+            store.get_last_block_number(history_store_key, 0)?;
+        let target_block =
+            store.get_target_block_number(history_store_key, 0)?;
+        let seconds_since_last_sync =
+            ctx.clock().unix_timestamp().saturating_sub(synced_at);
+        Ok(Self {
+            last_synced_block: Some(last_synced_block),
+            chain_head_at_sync: Some(target_block.saturating_add(head_offset)),
+            seconds_since_last_sync: Some(seconds_since_last_sync),
+            status: FreshnessStatus::new(
+                &ctx.config.leaves_freshness,
+                seconds_since_last_sync,
+                target_block
+                    .saturating_sub(safety_lag)
+                    .saturating_sub(last_synced_block),
+            ),
+        })
+    }
+}
 
 /// Leaves cache response
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LeavesCacheResponse {
-    leaves: Vec<types::H256>,
+    leaves: Vec<String>,
     last_queried_block: u64,
+    /// Whether the contract is not watched, so its leaves do not advance.
+    #[serde(rename = "static")]
+    is_static: bool,
+    freshness: LeavesFreshness,
+}
+
+impl LeavesCacheResponse {
+    fn new(
+        leaves: impl IntoIterator<Item = types::H256>,
+        format: LeafFormat,
+        last_queried_block: u64,
+        is_static: bool,
+        freshness: LeavesFreshness,
+    ) -> Self {
+        Self {
+            leaves: leaves.into_iter().map(|l| format.encode(&l)).collect(),
+            last_queried_block,
+            is_static,
+            freshness,
+        }
+    }
+}
+
+/// Responds with the leaves built by `respond`, see [`respond_cached`], or
+/// with `503 Service Unavailable` and the leaves anyway when the client
+/// asked for `strict` leaves and they are stale.
+fn respond_leaves<F>(
+    ctx: &RelayerContext,
+    headers: &HeaderMap,
+    etag: &ETag,
+    query: LeavesQuery,
+    freshness: LeavesFreshness,
+    respond: F,
+) -> Result<Response, HandlerError>
+where
+    F: FnOnce() -> Result<Json<LeavesCacheResponse>, HandlerError>,
+{
+    if query.strict && freshness.status == FreshnessStatus::Stale {
+        tracing::warn!(?freshness, "Serving stale leaves");
+        return Ok(
+            (StatusCode::SERVICE_UNAVAILABLE, respond()?).into_response()
+        );
+    }
+    respond_cached(headers, etag, leaves_max_age_secs(ctx), respond)
 }
 
 /// Handles leaf data requests for evm
 ///
-/// Returns a Result with the `LeafDataResponse` on success
+/// Returns a Result with the `LeafDataResponse` on success, or
+/// `304 Not Modified` if the client already has it, see [`respond_cached`].
 ///
 /// # Arguments
 ///
+/// * `headers` - The headers of the request, with its `If-None-Match`
 /// * `chain_id` - An u32 representing the chain id of the chain to query
 /// * `contract` - An address of the contract to query
 /// * `query_range` - An Optinal Query range.
+/// * `query` - Whether to only return the final leaves, their format, and
+///   whether to fail on stale leaves.
 pub async fn handle_leaves_cache_evm(
     State(ctx): State<Arc<RelayerContext>>,
+    headers: HeaderMap,
     Path((chain_id, contract)): Path<(u32, Address)>,
     Query(query_range): Query<OptionalRangeQuery>,
-) -> Result<Json<LeavesCacheResponse>, HandlerError> {
+    Query(query): Query<LeavesQuery>,
+) -> Result<Response, HandlerError> {
     let config = ctx.config.clone();
     // check if data query is enabled for relayer
     if !config.features.data_query {
@@ -114,36 +313,81 @@ pub async fn handle_leaves_cache_evm(
     let src_typed_chain_id = TypedChainId::Evm(chain_id);
     let history_store_key =
         ResourceId::new(src_target_system, src_typed_chain_id);
-    let leaves = ctx
-        .store()
-        .get_leaves_with_range(history_store_key, query_range.into())
-        .map(|tree| tree.into_values().collect::<Vec<_>>())?;
-    let last_queried_block = ctx
-        .store()
-        .get_last_deposit_block_number(history_store_key)?;
-
-    Ok(Json(LeavesCacheResponse {
-        leaves,
-        last_queried_block,
-    }))
+    // the watcher target block lags `block-confirmations` behind the chain
+    // head.
+    let final_block_number = if query.final_only {
+        let head = ctx
+            .store()
+            .get_target_block_number(history_store_key, 0)?
+            .saturating_add(chain.block_confirmations.into());
+        Some(head.saturating_sub(chain.leaf_final_confirmations))
+    } else {
+        None
+    };
+    let freshness = LeavesFreshness::read(
+        &ctx,
+        history_store_key,
+        chain.block_confirmations.into(),
+        event_watcher_config.safety_lag,
+    )?;
+    let range: core::ops::Range<u32> = query_range.into();
+    let etag = leaves_etag(
+        &ctx,
+        history_store_key,
+        &range,
+        query,
+        final_block_number,
+        freshness.status,
+    )?;
+    respond_leaves(&ctx, &headers, &etag, query, freshness, || {
+        let leaves = match final_block_number {
+            Some(final_block_number) => {
+                ctx.store().get_final_leaves_with_range(
+                    history_store_key,
+                    range,
+                    final_block_number,
+                )?
+            }
+            None => ctx
+                .store()
+                .get_leaves_with_range(history_store_key, range)?,
+        };
+        let last_queried_block = ctx
+            .store()
+            .get_last_deposit_block_number(history_store_key)?;
+        Ok(Json(LeavesCacheResponse::new(
+            leaves.into_values(),
+            query.format,
+            last_queried_block,
+            event_watcher_config.is_static(),
+            freshness,
+        )))
+    })
 }
 
 /// Handles leaf data requests for substrate
 ///
-/// Returns a Result with the `LeafDataResponse` on success
+/// Returns a Result with the `LeafDataResponse` on success, or
+/// `304 Not Modified` if the client already has it, see [`respond_cached`].
 ///
 /// # Arguments
 ///
+/// * `headers` - The headers of the request, with its `If-None-Match`
 /// * `chain_id` - An u32 representing the chain id of the chain to query
 /// * `tree_id` - Tree id of the the source system to query
 /// * `pallet_id` - Pallet id of the the source system to query
 /// * `query_range` - An Optional Query range.
+/// * `query` - The format of the leaves, and whether to fail on stale
+///   leaves. Whether to only return the final leaves is ignored, the
+///   Substrate leaves are read from finalized blocks so they are all final.
 /// * `ctx` - RelayContext reference that holds the configuration
 pub async fn handle_leaves_cache_substrate(
     State(ctx): State<Arc<RelayerContext>>,
+    headers: HeaderMap,
     Path((chain_id, tree_id, pallet_id)): Path<(u32, u32, u8)>,
     Query(query_range): Query<OptionalRangeQuery>,
-) -> Result<Json<LeavesCacheResponse>, HandlerError> {
+    Query(query): Query<LeavesQuery>,
+) -> Result<Response, HandlerError> {
     let config = ctx.config.clone();
     // check if data querying is enabled
     if !config.features.data_query {
@@ -164,17 +408,363 @@ pub async fn handle_leaves_cache_substrate(
     let history_store_key =
         ResourceId::new(src_target_system, src_typed_chain_id);
 
-    let leaves = ctx
-        .store()
-        .get_leaves_with_range(history_store_key, query_range.into())
-        .map(|tree| tree.into_values().collect::<Vec<_>>())?;
+    let range: core::ops::Range<u32> = query_range.into();
+    // the Substrate events watchers keep no safety lag.
+    let freshness = LeavesFreshness::read(&ctx, history_store_key, 0, 0)?;
+    let etag = leaves_etag(
+        &ctx,
+        history_store_key,
+        &range,
+        query,
+        None,
+        freshness.status,
+    )?;
+    respond_leaves(&ctx, &headers, &etag, query, freshness, || {
+        let leaves = ctx
+            .store()
+            .get_leaves_with_range(history_store_key, range)?;
+        let last_queried_block = ctx
+            .store()
+            .get_last_deposit_block_number(history_store_key)?;
+        Ok(Json(LeavesCacheResponse::new(
+            leaves.into_values(),
+            query.format,
+            last_queried_block,
+            false,
+            freshness,
+        )))
+    })
+}
 
-    let last_queried_block = ctx
+/// The version of the leaves of `history_store_key` served for `range`
+/// and `query`, which changes as soon as a leaf is cached, or their
+/// freshness `status` changes.
+///
+/// The seconds since the last sync are left out, they would change the
+/// version on every request.
+fn leaves_etag(
+    ctx: &RelayerContext,
+    history_store_key: ResourceId,
+    range: &core::ops::Range<u32>,
+    query: LeavesQuery,
+    final_block_number: Option<u64>,
+    status: FreshnessStatus,
+) -> Result<ETag, HandlerError> {
+    let leaf_count = ctx.store().next_leaf_index(history_store_key)?;
+    let last_block = ctx
         .store()
         .get_last_deposit_block_number(history_store_key)?;
+    Ok(ETag::new(format!(
+        "{history_store_key:?}/{leaf_count}/{last_block}/{range:?}/{query:?}/\
+        {final_block_number:?}/{status:?}"
+    )))
+}
+
+fn leaves_max_age_secs(ctx: &RelayerContext) -> u64 {
+    ctx.config.http_cache.leaves_max_age_secs
+}
+
+/// Leaf reconciliation response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileLeavesResponse {
+    /// The number of leaves that were missing from the cache.
+    reconciled: usize,
+}
+
+/// Handles on-demand leaf reconciliation requests for substrate
+///
+/// Fetches the leaves of the tree that are missing from the leaf cache,
+/// such as after a long disconnection from the node.
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain
+/// * `tree_id` - Tree id of the VAnchor pallet to reconcile
+/// * `ctx` - RelayContext reference that holds the configuration
+pub async fn handle_reconcile_leaves_substrate(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((chain_id, tree_id)): Path<(u32, u32)>,
+) -> Result<Json<ReconcileLeavesResponse>, HandlerError> {
+    if !ctx.config.substrate.contains_key(&chain_id.to_string()) {
+        tracing::warn!("Unsupported Chain: {chain_id}");
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Chain: {chain_id}"),
+        ));
+    }
+    let api = ctx
+        .substrate_provider::<PolkadotConfig, _>(chain_id)
+        .await?;
+    let history_store_key = tree_history_store_key(&api, tree_id)?;
+    let reconciled = reconcile_leaves(
+        ctx.store(),
+        &StorageLeaves::new(&api, None),
+        history_store_key,
+        tree_id,
+        &ctx.metrics,
+    )
+    .await?;
+    tracing::info!(chain_id, tree_id, reconciled, "Reconciled the leaf cache");
+    Ok(Json(ReconcileLeavesResponse { reconciled }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::HttpBody;
+    use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+    use std::time::Duration;
+    use webb_relayer_store::SledStore;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    async fn get_leaves(
+        ctx: &Arc<RelayerContext>,
+        if_none_match: Option<&str>,
+    ) -> Response {
+        get_leaves_with(ctx, if_none_match, LeavesQuery::default()).await
+    }
+
+    async fn get_leaves_with(
+        ctx: &Arc<RelayerContext>,
+        if_none_match: Option<&str>,
+        query: LeavesQuery,
+    ) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = if_none_match {
+            headers.insert(IF_NONE_MATCH, etag.parse().unwrap());
+        }
+        handle_leaves_cache_substrate(
+            State(ctx.clone()),
+            headers,
+            Path((1081, 5, 44)),
+            Query(OptionalRangeQuery::default()),
+            Query(query),
+        )
+        .await
+        .unwrap_or_else(|HandlerError(code, e)| panic!("{code}: {e}"))
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let body = response.into_body().data().await.unwrap().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn tree_key() -> ResourceId {
+        let target = SubstrateTargetSystem::builder()
+            .pallet_index(44)
+            .tree_id(5)
+            .build();
+        ResourceId::new(
+            TargetSystem::Substrate(target),
+            TypedChainId::Substrate(1081),
+        )
+    }
+
+    fn etag(response: &Response) -> &str {
+        response.headers()[ETAG].to_str().unwrap()
+    }
+
+    #[test]
+    fn leaves_should_be_encoded_in_the_requested_format() {
+        let leaf = types::H256::from_low_u64_be(256);
+        assert_eq!(
+            LeafFormat::Hex.encode(&leaf),
+            format!("0x{}0100", "0".repeat(60))
+        );
+        assert_eq!(LeafFormat::Decimal.encode(&leaf), "256");
+        assert_eq!(
+            LeafFormat::Base64.encode(&leaf),
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQA="
+        );
+
+        let query = |uri: &str| {
+            Query::<LeavesQuery>::try_from_uri(&uri.parse().unwrap())
+                .map(|Query(query)| query)
+        };
+        assert_eq!(query("/").unwrap().format, LeafFormat::Hex);
+        let decimal = query("/?final_only=true&format=decimal").unwrap();
+        assert_eq!(decimal.format, LeafFormat::Decimal);
+        assert!(query("/?format=hexa").is_err());
+    }
+
+    #[tokio::test]
+    async fn leaves_etag_should_change_once_a_leaf_is_cached() {
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store).unwrap();
+        let ctx = Arc::new(ctx);
+        let key = tree_key();
+        let store = ctx.store();
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                key,
+                &[(0, vec![1; 32])],
+                10,
+            )
+            .unwrap();
+
+        let first = get_leaves(&ctx, None).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers()[CACHE_CONTROL], "public, max-age=2");
+        let first_etag = etag(&first).to_string();
+
+        let unchanged = get_leaves(&ctx, Some(&first_etag)).await;
+        assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(etag(&unchanged), first_etag);
+        assert_eq!(unchanged.body().size_hint().exact(), Some(0));
+
+        // a second leaf, in the same block as the first one.
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                key,
+                &[(1, vec![2; 32])],
+                10,
+            )
+            .unwrap();
+        let changed = get_leaves(&ctx, Some(&first_etag)).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(etag(&changed), first_etag);
+    }
+
+    #[tokio::test]
+    async fn leaves_freshness_should_follow_the_last_sync() {
+        let clock = FrozenClock::default();
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store)
+            .unwrap()
+            .with_clock(clock.shared());
+        let ctx = Arc::new(ctx);
+        let key = tree_key();
+        let freshness = |json: serde_json::Value| json["freshness"].clone();
+
+        let never = freshness(json_body(get_leaves(&ctx, None).await).await);
+        assert_eq!(never["status"], "never_synced");
+        assert!(never["lastSyncedBlock"].is_null());
+        assert!(never["secondsSinceLastSync"].is_null());
+
+        let store = ctx.store();
+        store.set_target_block_number(key, 1_000).unwrap();
+        store.set_last_block_number(key, 1_000).unwrap();
+        store
+            .set_last_synced_at(key, ctx.clock().unix_timestamp())
+            .unwrap();
+        clock.advance(Duration::from_secs(5));
+        let live = freshness(json_body(get_leaves(&ctx, None).await).await);
+        assert_eq!(live["status"], "live");
+        assert_eq!(live["lastSyncedBlock"], 1_000);
+        assert_eq!(live["chainHeadAtSync"], 1_000);
+        assert_eq!(live["secondsSinceLastSync"], 5);
 
-    Ok(Json(LeavesCacheResponse {
-        leaves,
-        last_queried_block,
-    }))
+        // far behind the chain, though it synced recently.
+        store.set_target_block_number(key, 1_500).unwrap();
+        let behind = freshness(json_body(get_leaves(&ctx, None).await).await);
+        assert_eq!(behind["status"], "lagging");
+        // unless the blocks are the safety lag of the watcher.
+        let freshness = LeavesFreshness::read(&ctx, key, 0, 500).unwrap();
+        assert_eq!(freshness.status, FreshnessStatus::Live);
+        store.set_target_block_number(key, 1_000).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        let lagging = freshness(json_body(get_leaves(&ctx, None).await).await);
+        assert_eq!(lagging["status"], "lagging");
+        assert_eq!(lagging["secondsSinceLastSync"], 65);
+
+        clock.advance(Duration::from_secs(600));
+        let stale = freshness(json_body(get_leaves(&ctx, None).await).await);
+        assert_eq!(stale["status"], "stale");
+    }
+
+    #[tokio::test]
+    async fn strict_requests_should_fail_on_stale_leaves() {
+        let clock = FrozenClock::default();
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store)
+            .unwrap()
+            .with_clock(clock.shared());
+        let ctx = Arc::new(ctx);
+        let key = tree_key();
+        let store = ctx.store();
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                key,
+                &[(0, vec![1; 32])],
+                10,
+            )
+            .unwrap();
+        store
+            .set_last_synced_at(key, ctx.clock().unix_timestamp())
+            .unwrap();
+        let strict = LeavesQuery {
+            strict: true,
+            ..Default::default()
+        };
+
+        let live = get_leaves_with(&ctx, None, strict).await;
+        assert_eq!(live.status(), StatusCode::OK);
+        let live_etag = etag(&live).to_string();
+
+        clock.advance(Duration::from_secs(600));
+        let lenient = get_leaves(&ctx, None).await;
+        assert_eq!(lenient.status(), StatusCode::OK);
+        let stale = get_leaves_with(&ctx, Some(&live_etag), strict).await;
+        assert_eq!(stale.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let json = json_body(stale).await;
+        assert_eq!(json["freshness"]["status"], "stale");
+        assert_eq!(json["leaves"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn leaves_of_static_contracts_should_be_flagged() {
+        let contract = types::Address::repeat_byte(1);
+        let chain = serde_json::from_value(serde_json::json!({
+            "name": "hermes",
+            "http-endpoint": "http://localhost:5001",
+            "ws-endpoint": "ws://localhost:5001",
+            "chain-id": 5001,
+            "contracts": [{
+                "contract": "VAnchor",
+                "address": contract,
+                "deployed-at": 1,
+                "events-watcher": {
+                    "enabled": false,
+                    "enable-data-query": true,
+                    "polling-interval": 1000,
+                    "sync": "static",
+                },
+            }],
+        }))
+        .unwrap();
+        let mut config = webb_relayer_config::WebbRelayerConfig::default();
+        config.evm.insert("5001".to_string(), chain);
+        let store = SledStore::temporary().unwrap();
+        let ctx = Arc::new(RelayerContext::new(config, store).unwrap());
+        let key = ResourceId::new(
+            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+            TypedChainId::Evm(5001),
+        );
+        ctx.store()
+            .insert_leaves_and_last_deposit_block_number(
+                key,
+                &[(0, vec![1; 32])],
+                10,
+            )
+            .unwrap();
+
+        let response = handle_leaves_cache_evm(
+            State(ctx.clone()),
+            HeaderMap::new(),
+            Path((5001, contract)),
+            Query(OptionalRangeQuery::default()),
+            Query(LeavesQuery::default()),
+        )
+        .await
+        .unwrap_or_else(|HandlerError(code, e)| panic!("{code}: {e}"));
+        let json = json_body(response).await;
+        assert_eq!(json["static"], true);
+        assert_eq!(json["leaves"].as_array().unwrap().len(), 1);
+
+        // the Substrate trees cannot be static.
+        let json = json_body(get_leaves(&ctx, None).await).await;
+        assert_eq!(json["static"], false);
+    }
 }
@@ -3,12 +3,14 @@ use axum::http::StatusCode;
 use axum::Json;
 use ethereum_types::Address;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use webb_proposals::{
     ResourceId, SubstrateTargetSystem, TargetSystem, TypedChainId,
 };
 use webb_relayer_context::RelayerContext;
-use webb_relayer_utils::metric::Metrics;
+use webb_relayer_store::StoreMetrics;
+use webb_relayer_utils::metric::{ChainMetrics, Metrics};
 use webb_relayer_utils::HandlerError;
 
 /// Response with resource metrics data
@@ -23,11 +25,106 @@ pub struct ResourceMetricResponse {
     pub account_balance: String,
 }
 
+/// A contract's cached leaves count.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractLeafCount {
+    /// The typed chain id of the contract.
+    pub typed_chain_id: u64,
+    /// The contract address.
+    pub address: Address,
+    /// The number of cached leaves.
+    pub leaf_count: u64,
+}
+
+/// Response with the store-level statistics
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreMetricsResponse {
+    /// Number of anchor update proposals tracked by the store.
+    pub proposal_count: u64,
+    /// Number of queued items, per chain id.
+    pub queue_depth_by_chain: BTreeMap<u32, u64>,
+    /// Number of cached leaves, per contract.
+    pub leaf_count_by_contract: Vec<ContractLeafCount>,
+    /// Total amount of data stored on disk.
+    pub db_size_bytes: u64,
+    /// Number of bytes that were pending to be persisted.
+    pub pending_writes: u64,
+}
+
+impl From<StoreMetrics> for StoreMetricsResponse {
+    fn from(metrics: StoreMetrics) -> Self {
+        let mut leaf_count_by_contract = metrics
+            .leaf_count_by_contract
+            .into_iter()
+            .map(
+                |((typed_chain_id, address), leaf_count)| ContractLeafCount {
+                    typed_chain_id: typed_chain_id.chain_id(),
+                    address,
+                    leaf_count,
+                },
+            )
+            .collect::<Vec<_>>();
+        leaf_count_by_contract.sort_by_key(|v| (v.typed_chain_id, v.address));
+        Self {
+            proposal_count: metrics.proposal_count,
+            queue_depth_by_chain: metrics
+                .queue_depth_by_chain
+                .into_iter()
+                .collect(),
+            leaf_count_by_contract,
+            db_size_bytes: metrics.db_size_bytes,
+            pending_writes: metrics.pending_writes,
+        }
+    }
+}
+
+/// Updates the prometheus gauges with the given store metrics.
+fn record_store_metrics(metrics: &mut Metrics, store_metrics: &StoreMetrics) {
+    metrics
+        .total_amount_of_data_stored
+        .set(store_metrics.db_size_bytes as f64);
+    metrics
+        .store_proposal_count
+        .set(store_metrics.proposal_count as f64);
+    metrics
+        .store_pending_writes
+        .set(store_metrics.pending_writes as f64);
+    for (chain_id, depth) in &store_metrics.queue_depth_by_chain {
+        metrics
+            .store_queue_depth_entry(*chain_id)
+            .set(*depth as f64);
+    }
+    for ((chain, contract), count) in &store_metrics.leaf_count_by_contract {
+        metrics
+            .store_leaf_count_entry(*chain, *contract)
+            .set(*count as f64);
+    }
+}
+
+/// Handles store metrics requests
+///
+/// Returns a Result with the `StoreMetricsResponse` on success
+pub async fn handle_store_metrics(
+    State(ctx): State<Arc<RelayerContext>>,
+) -> Json<StoreMetricsResponse> {
+    let store_metrics = ctx.store().export_metrics();
+    record_store_metrics(&mut *ctx.metrics.lock().await, &store_metrics);
+    Json(store_metrics.into())
+}
+
 /// Handles relayer metric requests
 ///
 /// Returns a Result with the `MetricResponse` on success
-pub async fn handle_metric_info() -> Result<String, HandlerError> {
-    let metric_gathered = Metrics::gather_metrics().map_err(|e| {
+pub async fn handle_metric_info(
+    State(ctx): State<Arc<RelayerContext>>,
+) -> Result<String, HandlerError> {
+    // scrape the store on each collection.
+    let store_metrics = ctx.store().export_metrics();
+    let mut metrics = ctx.metrics.lock().await;
+    record_store_metrics(&mut metrics, &store_metrics);
+    let metric_gathered = metrics.gather_metrics().map_err(|e| {
         HandlerError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
     Ok(metric_gathered)
@@ -60,6 +157,48 @@ pub async fn handle_evm_metric_info(
     })
 }
 
+/// Handles the metrics requests of an EVM chain
+///
+/// Returns a Result with the `ChainMetrics` of the chain on success
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain to query
+pub async fn handle_chain_metrics(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path(chain_id): Path<u32>,
+) -> Result<Json<ChainMetrics>, HandlerError> {
+    if !ctx.config.evm.contains_key(&chain_id.to_string()) {
+        tracing::warn!("Unsupported Chain: {chain_id}");
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Chain: {chain_id}"),
+        ));
+    }
+    Ok(Json(ctx.chain_metrics(chain_id).await))
+}
+
+/// Handles the metrics requests of all the EVM chains
+///
+/// Returns the `ChainMetrics` of every configured EVM chain, sorted by
+/// chain id
+pub async fn handle_all_chain_metrics(
+    State(ctx): State<Arc<RelayerContext>>,
+) -> Json<Vec<ChainMetrics>> {
+    let mut chain_ids = ctx
+        .config
+        .evm
+        .values()
+        .map(|chain| chain.chain_id)
+        .collect::<Vec<_>>();
+    chain_ids.sort_unstable();
+    let mut all = Vec::with_capacity(chain_ids.len());
+    for chain_id in chain_ids {
+        all.push(ctx.chain_metrics(chain_id).await);
+    }
+    Json(all)
+}
+
 /// Handles relayer metric requests for substrate based resource
 ///
 /// Returns a Result with the `ResourceMetricResponse` on success
@@ -90,3 +229,96 @@ pub async fn handle_substrate_metric_info(
         account_balance,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use webb_relayer_config::evm::EvmChainConfig;
+    use webb_relayer_config::WebbRelayerConfig;
+    use webb_relayer_store::SledStore;
+    use webb_relayer_utils::metric::ChainMetricsUpdate;
+
+    fn context_with_chains(chain_ids: &[u32]) -> Arc<RelayerContext> {
+        let mut config = WebbRelayerConfig::default();
+        for chain_id in chain_ids {
+            let chain: EvmChainConfig =
+                serde_json::from_value(serde_json::json!({
+                    "name": format!("chain-{chain_id}"),
+                    "http-endpoint": "http://localhost:5001",
+                    "ws-endpoint": "ws://localhost:5001",
+                    "chain-id": chain_id,
+                }))
+                .unwrap();
+            config.evm.insert(chain_id.to_string(), chain);
+        }
+        let store = SledStore::temporary().unwrap();
+        Arc::new(RelayerContext::new(config, store).unwrap())
+    }
+
+    #[tokio::test]
+    async fn chain_metrics_should_be_reported_per_chain() {
+        let ctx = context_with_chains(&[5002, 5001]);
+        let updates = [
+            ChainMetricsUpdate::TxSent,
+            ChainMetricsUpdate::TxSent,
+            ChainMetricsUpdate::TxSent,
+            ChainMetricsUpdate::TxConfirmed {
+                confirmation_time: Duration::from_secs(10),
+            },
+            ChainMetricsUpdate::TxConfirmed {
+                confirmation_time: Duration::from_secs(20),
+            },
+            ChainMetricsUpdate::TxReverted,
+            ChainMetricsUpdate::EventsProcessed {
+                count: 3,
+                current_block_number: 120,
+            },
+            ChainMetricsUpdate::EventsProcessed {
+                count: 2,
+                current_block_number: 130,
+            },
+        ];
+        for update in updates {
+            ctx.update_chain_metrics(5001, update).await;
+        }
+
+        let Ok(Json(metrics)) =
+            handle_chain_metrics(State(ctx.clone()), Path(5001)).await
+        else {
+            panic!("expected the metrics of the chain");
+        };
+        assert_eq!(
+            metrics,
+            ChainMetrics {
+                chain_id: 5001,
+                tx_sent_total: 3,
+                tx_confirmed_total: 2,
+                tx_reverted_total: 1,
+                average_confirmation_time_secs: 15.0,
+                current_block_number: 130,
+                events_processed_total: 5,
+            }
+        );
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(json["txSentTotal"], 3);
+        assert_eq!(json["averageConfirmationTimeSecs"], 15.0);
+
+        let Json(all) = handle_all_chain_metrics(State(ctx.clone())).await;
+        assert_eq!(all, vec![metrics, ChainMetrics::new(5002)]);
+
+        let unknown =
+            handle_chain_metrics(State(ctx.clone()), Path(5003)).await;
+        assert!(matches!(
+            unknown,
+            Err(HandlerError(StatusCode::BAD_REQUEST, _))
+        ));
+
+        let Ok(prometheus) = handle_metric_info(State(ctx)).await else {
+            panic!("expected the prometheus metrics");
+        };
+        assert!(prometheus.contains("chain_tx_sent_total{chain_id=\"5001\"} 3"));
+        assert!(prometheus
+            .contains("chain_current_block_number{chain_id=\"5001\"} 130"));
+    }
+}
@@ -12,6 +12,30 @@ pub mod metric;
 /// Module for handling relayer info API
 pub mod info;
 
+/// Module for handling deposit propagation status API
+pub mod deposits;
+
+/// Module for handling commitment lookup API
+pub mod commitments;
+
+/// Module for handling relayer fee earnings API
+pub mod earnings;
+
+/// Module for handling RPC provider usage API
+pub mod provider_usage;
+
+/// Module for handling relayed withdrawals API
+pub mod withdrawals;
+
+/// Module for handling signed leaf cache snapshots API
+pub mod snapshot;
+
+/// Module for handling anchor edge list API
+pub mod edges;
+
+/// Module for handling chain reconnection API
+pub mod reconnect;
+
 /// A (half-open) range bounded inclusively below and exclusively above
 /// (`start..end`).
 ///
@@ -0,0 +1,87 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Query, State};
+use axum::Json;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{ProviderUsage, ProviderUsageStore};
+use webb_relayer_utils::HandlerError;
+
+/// Provider usage query
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ProviderUsageQuery {
+    /// The number of days to list the usage of, today included.
+    ///
+    /// default: `30`
+    #[serde(default = "default_days")]
+    pub days: u64,
+}
+
+const fn default_days() -> u64 {
+    30
+}
+
+/// Provider usage response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderUsageResponse {
+    /// The RPC calls served by each provider, per day, sorted by provider
+    /// label, then by day.
+    pub usage: Vec<ProviderUsage>,
+}
+
+/// Returns the first day, in days since the UNIX epoch, of the last `days`
+/// days up to `today`.
+fn since_day(today: u64, days: u64) -> u64 {
+    today.saturating_sub(days.saturating_sub(1))
+}
+
+/// Handles the provider usage requests, the RPC calls served by each
+/// labelled EVM provider, to attribute them to the RPC vendor accounts.
+///
+/// Returns a Result with the `ProviderUsageResponse` on success
+///
+/// # Arguments
+///
+/// * `query` - The number of days to list the usage of
+pub async fn handle_provider_usage(
+    State(ctx): State<Arc<RelayerContext>>,
+    Query(query): Query<ProviderUsageQuery>,
+) -> Result<Json<ProviderUsageResponse>, HandlerError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let today = now.as_secs() / 86_400;
+    let usage = ctx
+        .store()
+        .get_provider_usage(since_day(today, query.days))?;
+    Ok(Json(ProviderUsageResponse { usage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_should_include_today() {
+        assert_eq!(since_day(100, 1), 100);
+        assert_eq!(since_day(100, 30), 71);
+        assert_eq!(since_day(100, 0), 100);
+        assert_eq!(since_day(10, 30), 0);
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use webb_proposals::TypedChainId;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_utils::HandlerError;
+
+/// The kind of the chain to reconnect, as in the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainKind {
+    /// An EVM chain.
+    Evm,
+    /// A Substrate node.
+    Substrate,
+}
+
+/// Reconnect chain response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconnectChainResponse {
+    /// The name of the chain getting reconnected.
+    pub chain_name: String,
+    /// The chain id of the chain getting reconnected.
+    pub chain_id: u32,
+}
+
+/// Handles the requests to reconnect the services of a chain to its
+/// endpoints, such as after its RPC provider got rotated.
+///
+/// The services of the chain are restarted with providers built again
+/// from the config, and the request is answered once they are. If they
+/// failed to restart, they are restarted with the previous providers, and
+/// the request fails with `500 Internal Server Error`.
+///
+/// # Arguments
+///
+/// * `kind` - Either `evm` or `substrate`
+/// * `chain_id` - An u32 representing the chain id of the chain
+pub async fn handle_reconnect_chain(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((kind, chain_id)): Path<(ChainKind, u32)>,
+) -> Result<(StatusCode, Json<ReconnectChainResponse>), HandlerError> {
+    let (chain, chain_name) = match kind {
+        ChainKind::Evm => (
+            TypedChainId::Evm(chain_id),
+            ctx.config
+                .evm
+                .values()
+                .find(|c| c.enabled && c.chain_id == chain_id)
+                .map(|c| c.name.clone()),
+        ),
+        ChainKind::Substrate => (
+            TypedChainId::Substrate(chain_id),
+            ctx.config
+                .substrate
+                .values()
+                .find(|c| c.enabled && c.chain_id == chain_id)
+                .map(|c| c.name.clone()),
+        ),
+    };
+    let chain_name = chain_name.ok_or_else(|| {
+        HandlerError(
+            StatusCode::NOT_FOUND,
+            format!("Unsupported Chain: {chain_id}"),
+        )
+    })?;
+    tracing::info!(%chain_name, chain_id, "Requested to reconnect the chain");
+    match ctx.reconnects().request(chain).await {
+        Some(Ok(())) => {}
+        Some(Err(e)) => {
+            return Err(HandlerError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to reconnect {chain_name}: {e}"),
+            ))
+        }
+        None => {
+            return Err(HandlerError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The relayer is not running the chains".to_string(),
+            ))
+        }
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ReconnectChainResponse {
+            chain_name,
+            chain_id,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use webb_relayer_store::SledStore;
+
+    fn context() -> Arc<RelayerContext> {
+        let chain = serde_json::from_value(serde_json::json!({
+            "name": "hermes",
+            "enabled": true,
+            "http-endpoint": "http://localhost:8545",
+            "ws-endpoint": "ws://localhost:8545",
+            "chain-id": 5,
+        }))
+        .unwrap();
+        let mut config = webb_relayer_config::WebbRelayerConfig::default();
+        config.evm.insert("5".into(), chain);
+        let store = SledStore::temporary().unwrap();
+        Arc::new(RelayerContext::new(config, store).unwrap())
+    }
+
+    #[tokio::test]
+    async fn reconnects_should_be_requested_for_the_enabled_chains() {
+        let ctx = context();
+        let mut requests = ctx.reconnects().receiver().unwrap();
+        let (chains_tx, mut chains) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut outcomes =
+                [Ok(()), Err("no endpoint".to_string())].into_iter();
+            while let Some(request) = requests.recv().await {
+                let _ = chains_tx.send(request.chain);
+                request.respond(outcomes.next().unwrap());
+            }
+        });
+
+        let (status, Json(response)) = handle_reconnect_chain(
+            State(ctx.clone()),
+            Path((ChainKind::Evm, 5)),
+        )
+        .await
+        .map_err(|e| e.1)
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.chain_name, "hermes");
+        assert_eq!(chains.try_recv().unwrap(), TypedChainId::Evm(5));
+
+        // the services that failed to restart are reported.
+        let failed = handle_reconnect_chain(
+            State(ctx.clone()),
+            Path((ChainKind::Evm, 5)),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(failed.0, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(failed.1, "Failed to reconnect hermes: no endpoint");
+        assert_eq!(chains.try_recv().unwrap(), TypedChainId::Evm(5));
+
+        let unknown = handle_reconnect_chain(
+            State(ctx.clone()),
+            Path((ChainKind::Substrate, 5)),
+        )
+        .await
+        .unwrap_err()
+        .into_response();
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        assert!(chains.try_recv().is_err());
+    }
+}
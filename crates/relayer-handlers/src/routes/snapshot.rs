@@ -0,0 +1,166 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Extension, Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use webb::evm::contract::protocol_solidity::VAnchorContract;
+
+use ethereum_types::Address;
+use webb_ew_evm::vanchor::LeafSnapshot;
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::LeafCacheStore;
+use webb_relayer_utils::HandlerError;
+
+/// Caches the encoded leaf snapshots, so that walking the leaf cache and
+/// signing happens at most once per `leaf-snapshot-interval`.
+#[derive(Debug, Clone, Default)]
+pub struct LeafSnapshotCache {
+    inner: Arc<Mutex<HashMap<(u32, Address), (Instant, Arc<Vec<u8>>)>>>,
+}
+
+/// Handles signed leaf snapshot requests for evm
+///
+/// Returns the binary leaf snapshot, see [`LeafSnapshot`] for its layout.
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain to query
+/// * `contract` - An address of the contract to query
+pub async fn handle_leaf_snapshot_evm(
+    State(ctx): State<Arc<RelayerContext>>,
+    Extension(cache): Extension<LeafSnapshotCache>,
+    Path((chain_id, contract)): Path<(u32, Address)>,
+) -> Result<impl IntoResponse, HandlerError> {
+    let config = ctx.config.clone();
+    // check if data query is enabled for relayer
+    if !config.features.data_query {
+        tracing::warn!("Data query is not enabled for relayer.");
+        return Err(HandlerError(
+            StatusCode::FORBIDDEN,
+            "Data query is not enabled for relayer.".to_string(),
+        ));
+    }
+
+    // check if chain is supported
+    let chain = match ctx.config.evm.get(&chain_id.to_string()) {
+        Some(v) => v,
+        None => {
+            tracing::warn!("Unsupported Chain: {chain_id}");
+            return Err(HandlerError(
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported Chain: {chain_id}"),
+            ));
+        }
+    };
+
+    // check if contract is supported
+    let event_watcher_config = chain
+        .contracts
+        .iter()
+        .find_map(|c| match c {
+            webb_relayer_config::evm::Contract::VAnchor(c)
+                if c.common.address == contract =>
+            {
+                Some(c.events_watcher.clone())
+            }
+            _ => None,
+        })
+        .ok_or_else(|| {
+            tracing::warn!(
+                "Unsupported Contract: {contract} for chaind : {chain_id}"
+            );
+            HandlerError(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Unsupported Contract: {contract} for chaind : {chain_id}",
+                ),
+            )
+        })?;
+    // check if data query is enabled for contract
+    if !event_watcher_config.enable_data_query {
+        tracing::warn!("Enbable data query for contract : ({contract})");
+        return Err(HandlerError(
+            StatusCode::FORBIDDEN,
+            format!("Enbable data query for contract : ({contract})"),
+        ));
+    }
+
+    let max_age = Duration::from_secs(config.leaf_snapshot_interval * 60);
+    // holding the lock while generating, so concurrent requests wait for
+    // the same snapshot instead of generating their own.
+    let mut cache = cache.inner.lock().await;
+    let snapshot = match cache.get(&(chain_id, contract)) {
+        Some((at, snapshot)) if at.elapsed() < max_age => snapshot.clone(),
+        _ => {
+            let snapshot =
+                Arc::new(generate_snapshot(&ctx, chain_id, contract).await?);
+            cache.insert(
+                (chain_id, contract),
+                (Instant::now(), snapshot.clone()),
+            );
+            snapshot
+        }
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        snapshot.as_ref().clone(),
+    ))
+}
+
+async fn generate_snapshot(
+    ctx: &RelayerContext,
+    chain_id: u32,
+    contract: Address,
+) -> webb_relayer_utils::Result<Vec<u8>> {
+    let history_store_key = ResourceId::new(
+        TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+        TypedChainId::Evm(chain_id),
+    );
+    let leaves = ctx.store().get_leaves(history_store_key)?;
+    let last_block = ctx
+        .store()
+        .get_last_deposit_block_number(history_store_key)?;
+
+    let provider = ctx.evm_provider(chain_id).await?;
+    let zero_hash = VAnchorContract::new(contract, Arc::new(provider))
+        .get_zero_hash(0)
+        .call()
+        .await?;
+    let mut empty_leaf = [0u8; 32];
+    zero_hash.to_big_endian(&mut empty_leaf);
+
+    let wallet = ctx.evm_wallet(chain_id).await?;
+    let snapshot = LeafSnapshot::new(
+        chain_id,
+        contract,
+        leaves,
+        last_block,
+        &empty_leaf,
+        &wallet,
+    )?;
+    tracing::debug!(
+        chain_id,
+        %contract,
+        leaf_count = snapshot.header.leaf_count,
+        last_block,
+        "Generated a new leaf snapshot",
+    );
+    Ok(snapshot.to_bytes())
+}
@@ -0,0 +1,216 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::Arc;
+
+use ethereum_types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_config::evm::Contract;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{
+    RelayedWithdrawal, RelayedWithdrawalStore, WithdrawalLimitStore,
+    MAX_RELAYED_WITHDRAWALS,
+};
+use webb_relayer_tx_relay::evm::vanchor::AMOUNT_LIMIT_WINDOW_SECS;
+use webb_relayer_utils::HandlerError;
+
+/// The maximum number of withdrawals returned at once.
+const MAX_LIMIT: usize = 100;
+
+/// Relayed withdrawals query
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WithdrawalsQuery {
+    /// The number of withdrawals to return, at most `100`.
+    ///
+    /// default: `20`
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+const fn default_limit() -> usize {
+    20
+}
+
+/// Aggregate statistics of the relayed withdrawals of a contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalStats {
+    /// The number of withdrawals relayed in the last 24 hours.
+    pub count_last_24h: usize,
+    /// The median confirmation latency of the withdrawals relayed in the
+    /// last 24 hours, in millis.
+    pub median_confirmation_latency_ms: Option<u64>,
+}
+
+impl WithdrawalStats {
+    /// Computes the statistics of the given withdrawals, at `now` in secs
+    /// since the UNIX epoch.
+    pub fn new(withdrawals: &[RelayedWithdrawal], now: u64) -> Self {
+        let since = now.saturating_sub(24 * 60 * 60);
+        let mut latencies = withdrawals
+            .iter()
+            .filter(|w| w.finalized_at >= since)
+            .map(|w| w.confirmation_latency_ms)
+            .collect::<Vec<_>>();
+        latencies.sort_unstable();
+        let median = match latencies.len() {
+            0 => None,
+            n if n % 2 == 0 => {
+                Some((latencies[n / 2 - 1] + latencies[n / 2]) / 2)
+            }
+            n => Some(latencies[n / 2]),
+        };
+        Self {
+            count_last_24h: latencies.len(),
+            median_confirmation_latency_ms: median,
+        }
+    }
+}
+
+/// The hourly withdrawal amount limit of a contract, and how much of it is
+/// taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AmountLimit {
+    /// The most the relayer withdraws from the contract over any hour.
+    pub max_amount_per_hour: U256,
+    /// The amount withdrawn over the last hour.
+    pub relayed_last_hour: U256,
+}
+
+/// Relayed withdrawals response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayedWithdrawalsResponse {
+    withdrawals: Vec<RelayedWithdrawal>,
+    stats: WithdrawalStats,
+    /// `None` if the withdrawals from the contract are not limited.
+    amount_limit: Option<AmountLimit>,
+}
+
+/// Handles relayed withdrawals requests for evm
+///
+/// Returns a Result with the `RelayedWithdrawalsResponse` on success
+///
+/// # Arguments
+///
+/// * `chain_id` - An u32 representing the chain id of the chain to query
+/// * `contract` - An address of the contract to query
+/// * `query` - The number of withdrawals to return
+pub async fn handle_relayed_withdrawals_evm(
+    State(ctx): State<Arc<RelayerContext>>,
+    Path((chain_id, contract)): Path<(u32, Address)>,
+    Query(query): Query<WithdrawalsQuery>,
+) -> Result<Json<RelayedWithdrawalsResponse>, HandlerError> {
+    // check if withdrawals query is enabled for relayer
+    if !ctx.config.features.withdrawals_query {
+        tracing::warn!("Withdrawals query is not enabled for relayer.");
+        return Err(HandlerError(
+            StatusCode::FORBIDDEN,
+            "Withdrawals query is not enabled for relayer.".to_string(),
+        ));
+    }
+    // check if chain is supported
+    let Some(chain) = ctx.config.evm.get(&chain_id.to_string()) else {
+        tracing::warn!("Unsupported Chain: {chain_id}");
+        return Err(HandlerError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported Chain: {chain_id}"),
+        ));
+    };
+    let target_system =
+        TargetSystem::new_contract_address(contract.to_fixed_bytes());
+    let history_store_key =
+        ResourceId::new(target_system, TypedChainId::Evm(chain_id));
+    // the store is bounded, so the stats cover all we have.
+    let all = ctx
+        .store()
+        .get_relayed_withdrawals(history_store_key, MAX_RELAYED_WITHDRAWALS)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let stats = WithdrawalStats::new(&all, now);
+    let withdrawals =
+        all.into_iter().take(query.limit.min(MAX_LIMIT)).collect();
+    let limit = chain.contracts.iter().find_map(|c| match c {
+        Contract::VAnchor(c) if c.common.address == contract => {
+            c.max_amount_per_hour
+        }
+        _ => None,
+    });
+    let amount_limit = match limit {
+        Some(limit) => Some(AmountLimit {
+            max_amount_per_hour: *limit,
+            relayed_last_hour: ctx.store().get_relayed_amount(
+                history_store_key,
+                AMOUNT_LIMIT_WINDOW_SECS,
+                now,
+            )?,
+        }),
+        None => None,
+    };
+    Ok(Json(RelayedWithdrawalsResponse {
+        withdrawals,
+        stats,
+        amount_limit,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::types;
+
+    fn withdrawal(finalized_at: u64, latency: u64) -> RelayedWithdrawal {
+        RelayedWithdrawal {
+            tx_hash: types::H256::random(),
+            finalized_at,
+            confirmation_latency_ms: latency,
+            fee: 1.into(),
+            gas_used: 21_000.into(),
+        }
+    }
+
+    #[test]
+    fn stats_should_only_count_the_last_day() {
+        let now = 100_000;
+        let withdrawals = vec![
+            withdrawal(now - 10, 4_000),
+            withdrawal(now - 60, 1_000),
+            withdrawal(now - 3_600, 3_000),
+            // older than a day.
+            withdrawal(now - 90_000, 60_000),
+        ];
+        let stats = WithdrawalStats::new(&withdrawals, now);
+        assert_eq!(stats.count_last_24h, 3);
+        assert_eq!(stats.median_confirmation_latency_ms, Some(3_000));
+
+        let stats = WithdrawalStats::new(&withdrawals[..2], now);
+        assert_eq!(stats.median_confirmation_latency_ms, Some(2_500));
+
+        let stats = WithdrawalStats::new(&withdrawals[3..], now);
+        assert_eq!(
+            stats,
+            WithdrawalStats {
+                count_last_24h: 0,
+                median_confirmation_latency_ms: None,
+            }
+        );
+    }
+}
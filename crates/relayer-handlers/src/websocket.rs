@@ -0,0 +1,751 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocketUpgrade};
+use futures::prelude::*;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+use webb_relayer_config::WsSlowClientPolicy;
+use webb_relayer_handler_utils::{CommandResponse, CommandStream};
+
+use crate::router::RoutedCommand;
+
+/// The maximum number of commands with a `requestId` that are processed
+/// concurrently on a single websocket connection.
+pub const MAX_CONCURRENT_COMMANDS: usize = 8;
+
+/// The JSON field of the id that the client may attach to a command.
+pub const REQUEST_ID_FIELD: &str = "requestId";
+
+/// Parses a command received over the websocket.
+///
+/// Returns the `requestId` of the command, if any, even if the command
+/// itself is invalid, so that the error can still be attributed to it.
+pub fn parse_command(
+    v: &str,
+) -> (Option<String>, serde_json::Result<RoutedCommand>) {
+    let mut value = match serde_json::from_str::<serde_json::Value>(v) {
+        Ok(value) => value,
+        Err(e) => return (None, Err(e)),
+    };
+    let request_id = match value
+        .as_object_mut()
+        .and_then(|fields| fields.remove(REQUEST_ID_FIELD))
+    {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(id)) => Some(id),
+        Some(_) => {
+            return (
+                None,
+                Err(serde::de::Error::custom("requestId must be a string")),
+            )
+        }
+    };
+    (request_id, RoutedCommand::from_value(value))
+}
+
+/// Serializes a command response, tagging it with the `requestId` of the
+/// command it belongs to.
+pub fn response_to_json(
+    request_id: Option<&str>,
+    response: &CommandResponse,
+) -> serde_json::Result<String> {
+    let mut value = serde_json::to_value(response)?;
+    if let (Some(id), Some(fields)) = (request_id, value.as_object_mut()) {
+        fields.insert(REQUEST_ID_FIELD.to_string(), id.into());
+    }
+    serde_json::to_string(&value)
+}
+
+/// Why a message of a websocket client got rejected, before being parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageRejected {
+    /// The message is larger than the limit, in bytes.
+    TooLarge {
+        /// The size of the message.
+        size: usize,
+        /// The largest message allowed.
+        max_size: usize,
+    },
+    /// The binary message is not valid UTF-8, so it can not be JSON.
+    NotUtf8,
+}
+
+impl MessageRejected {
+    /// The response sent back to the client.
+    pub fn response(&self) -> CommandResponse {
+        match self {
+            Self::TooLarge { .. } => {
+                CommandResponse::Error("message too large".to_string())
+            }
+            Self::NotUtf8 => {
+                CommandResponse::Error("message is not valid UTF-8".to_string())
+            }
+        }
+    }
+
+    /// The frame closing the connection after the response, `None` if the
+    /// connection stays open.
+    pub fn close_frame(&self) -> Option<CloseFrame<'static>> {
+        match self {
+            Self::TooLarge { .. } => Some(CloseFrame {
+                code: close_code::SIZE,
+                reason: Cow::from("message too large"),
+            }),
+            Self::NotUtf8 => None,
+        }
+    }
+}
+
+/// Rejects the messages of the websocket clients larger than a limit, so an
+/// oversized JSON payload never reaches the parser.
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketMessageSizeLimit {
+    max_size: usize,
+}
+
+impl WebSocketMessageSizeLimit {
+    /// Creates a limit of `max_size` bytes, `0` for no limit.
+    pub fn new(max_size: usize) -> Self {
+        Self { max_size }
+    }
+
+    /// Applies the limit to the websocket transport too, so the frames of a
+    /// larger message are not buffered before the message gets rejected.
+    pub fn limit_upgrade(&self, ws: WebSocketUpgrade) -> WebSocketUpgrade {
+        match self.max_size {
+            0 => ws,
+            max_size => ws.max_message_size(max_size).max_frame_size(max_size),
+        }
+    }
+
+    /// Returns the rejection of a message the transport failed to read
+    /// because of the limit, see [`Self::limit_upgrade`].
+    ///
+    /// tungstenite is not a direct dependency, so its capacity errors are
+    /// told apart by their message, such as
+    /// `Space limit exceeded: Message too long: 70000 > 65536`.
+    pub fn rejected_by_transport(
+        &self,
+        error: &axum::Error,
+    ) -> Option<MessageRejected> {
+        if self.max_size == 0 {
+            return None;
+        }
+        let error = error.to_string();
+        let size = error
+            .strip_prefix("Space limit exceeded: Message too long: ")?
+            .split(" > ")
+            .next()?
+            .parse()
+            .ok()?;
+        Some(MessageRejected::TooLarge {
+            size,
+            max_size: self.max_size,
+        })
+    }
+
+    /// Returns the text of the message, or `None` for the control frames,
+    /// which are not commands.
+    pub fn check(
+        &self,
+        message: Message,
+    ) -> Result<Option<String>, MessageRejected> {
+        let size = match &message {
+            Message::Text(text) => text.len(),
+            Message::Binary(bytes) => bytes.len(),
+            Message::Ping(_) | Message::Pong(_) | Message::Close(_) => {
+                return Ok(None)
+            }
+        };
+        if self.max_size != 0 && size > self.max_size {
+            return Err(MessageRejected::TooLarge {
+                size,
+                max_size: self.max_size,
+            });
+        }
+        match message {
+            Message::Text(text) => Ok(Some(text)),
+            Message::Binary(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| MessageRejected::NotUtf8),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The bounded queue of the responses waiting to be sent to a websocket
+/// client.
+#[derive(Debug, Clone)]
+pub struct OutboundQueue {
+    sender: mpsc::Sender<String>,
+    policy: WsSlowClientPolicy,
+    overflowed: CancellationToken,
+}
+
+impl OutboundQueue {
+    /// Creates a queue sending to `sender`, which holds the queued
+    /// responses, handling a full queue with `policy`.
+    pub fn new(
+        sender: mpsc::Sender<String>,
+        policy: WsSlowClientPolicy,
+    ) -> Self {
+        Self {
+            sender,
+            policy,
+            overflowed: CancellationToken::new(),
+        }
+    }
+
+    /// Cancelled once the queue got full with the `Disconnect` policy, the
+    /// connection should then be closed.
+    pub fn overflowed(&self) -> &CancellationToken {
+        &self.overflowed
+    }
+
+    /// Queues the message.
+    ///
+    /// Returns false once the client is gone, or got dropped for reading
+    /// too slowly.
+    pub async fn send(&self, message: String) -> bool {
+        if self.overflowed.is_cancelled() {
+            return false;
+        }
+        match self.policy {
+            WsSlowClientPolicy::Backpressure => {
+                self.sender.send(message).await.is_ok()
+            }
+            WsSlowClientPolicy::Disconnect => {
+                match self.sender.try_send(message) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        tracing::warn!(
+                            "Websocket client reads too slowly, dropping it"
+                        );
+                        self.overflowed.cancel();
+                        false
+                    }
+                    Err(TrySendError::Closed(_)) => false,
+                }
+            }
+        }
+    }
+}
+
+/// Keeps track of the commands with a `requestId` that are running on a
+/// websocket connection.
+#[derive(Clone)]
+struct CommandJobs {
+    in_flight: Arc<Mutex<HashSet<String>>>,
+    permits: Arc<Semaphore>,
+}
+
+/// Marks a command as finished and frees its slot once dropped.
+struct JobGuard {
+    jobs: CommandJobs,
+    request_id: String,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl CommandJobs {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            in_flight: Default::default(),
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+
+    /// Waits for a free slot, then registers the command.
+    ///
+    /// Returns `None` if a command with the same id is still running.
+    async fn start(&self, request_id: &str) -> Option<JobGuard> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let mut in_flight = self.in_flight.lock().expect("lock poisoned");
+        if !in_flight.insert(request_id.to_string()) {
+            return None;
+        }
+        Some(JobGuard {
+            jobs: self.clone(),
+            request_id: request_id.to_string(),
+            _permit: permit,
+        })
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.jobs.in_flight.lock() {
+            in_flight.remove(&self.request_id);
+        }
+    }
+}
+
+/// Serves the commands of a single websocket connection.
+///
+/// Commands without a `requestId` are processed strictly one after the
+/// other: the next message is not read before all the responses of the
+/// current command are sent.
+///
+/// Commands with a `requestId` are processed concurrently, up to
+/// [`MAX_CONCURRENT_COMMANDS`] at a time, and every response they produce
+/// carries their `requestId`. The responses of a single command keep their
+/// order, but may be interleaved with the responses of other commands.
+///
+/// # Arguments
+///
+/// * `inbound` - The text messages received from the client
+/// * `outbound` - Where to send the serialized responses to the client
+/// * `handler` - Processes a single command, see
+///   [`CommandRouter::dispatch`](crate::router::CommandRouter::dispatch)
+pub async fn serve_connection<S, H, Fut>(
+    inbound: S,
+    outbound: OutboundQueue,
+    handler: H,
+) where
+    S: Stream<Item = String>,
+    H: Fn(RoutedCommand, CommandStream) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), CommandResponse>> + Send + 'static,
+{
+    tokio::pin!(inbound);
+    let jobs = CommandJobs::new(MAX_CONCURRENT_COMMANDS);
+    while let Some(text) = inbound.next().await {
+        let (request_id, command) = parse_command(&text);
+        let command = match command {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::warn!("Got invalid payload: {:?}", e);
+                tracing::debug!("Invalid payload: {:?}", text);
+                let error = CommandResponse::Error(e.to_string());
+                send_response(&outbound, request_id.as_deref(), &error).await;
+                continue;
+            }
+        };
+        let request_id = match request_id {
+            Some(request_id) => request_id,
+            None => {
+                run_command(handler.clone(), command, None, outbound.clone())
+                    .await;
+                continue;
+            }
+        };
+        let guard = match jobs.start(&request_id).await {
+            Some(guard) => guard,
+            None => {
+                let error = CommandResponse::Error(format!(
+                    "A command with requestId {request_id} is already running"
+                ));
+                send_response(&outbound, Some(&request_id), &error).await;
+                continue;
+            }
+        };
+        let handler = handler.clone();
+        let outbound = outbound.clone();
+        tokio::spawn(async move {
+            run_command(handler, command, Some(request_id), outbound).await;
+            drop(guard);
+        });
+    }
+}
+
+/// Pings a websocket connection periodically, so that NAT gateways and
+/// load balancers do not drop it while it is idle, and closes it once the
+/// client stops answering.
+#[derive(Debug, Clone)]
+pub struct WebSocketHeartbeat {
+    interval: Duration,
+    last_pong: Arc<Mutex<Instant>>,
+}
+
+impl WebSocketHeartbeat {
+    /// Creates a heartbeat that pings every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_pong: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Records a pong from the client.
+    pub fn pong(&self) {
+        *self.last_pong.lock().unwrap_or_else(|e| e.into_inner()) =
+            Instant::now();
+    }
+
+    /// Pings the client every interval, until `cancel` is cancelled.
+    ///
+    /// If no pong came back for two intervals, the connection is closed
+    /// with `1001` (going away). The heartbeat cancels `cancel` once it
+    /// stops on its own, so the rest of the connection stops too.
+    ///
+    /// # Arguments
+    ///
+    /// * `outbound` - Where to send the pings to the client
+    /// * `cancel` - Stops the heartbeat once the connection is done
+    pub async fn run(
+        self,
+        outbound: mpsc::Sender<Message>,
+        cancel: CancellationToken,
+    ) {
+        let start = tokio::time::Instant::now() + self.interval;
+        let mut interval = tokio::time::interval_at(start, self.interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = cancel.cancelled() => return,
+            }
+            let last_pong =
+                *self.last_pong.lock().unwrap_or_else(|e| e.into_inner());
+            let message = if last_pong.elapsed() > 2 * self.interval {
+                tracing::debug!("No pong received, closing the websocket");
+                Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: Cow::from("No pong received"),
+                }))
+            } else {
+                Message::Ping(Vec::new())
+            };
+            let closing = matches!(message, Message::Close(_));
+            // the connection is closed.
+            if outbound.send(message).await.is_err() || closing {
+                cancel.cancel();
+                return;
+            }
+        }
+    }
+}
+
+/// Runs a single command, forwarding its responses as they are produced.
+async fn run_command<H, Fut>(
+    handler: H,
+    command: RoutedCommand,
+    request_id: Option<String>,
+    outbound: OutboundQueue,
+) where
+    H: Fn(RoutedCommand, CommandStream) -> Fut,
+    Fut: Future<Output = Result<(), CommandResponse>>,
+{
+    let (my_tx, my_rx) = mpsc::channel(50);
+    let handle = async move {
+        if let Err(e) = handler(command, my_tx.clone()).await {
+            tracing::error!("{:?}", e);
+            let _ = my_tx.send(e).await;
+        }
+    };
+    let forward = async {
+        let mut responses = ReceiverStream::new(my_rx);
+        while let Some(response) = responses.next().await {
+            if !send_response(&outbound, request_id.as_deref(), &response).await
+            {
+                // the client is gone, which closes the stream of the
+                // handler, so the long running ones stop too.
+                break;
+            }
+        }
+    };
+    tokio::join!(handle, forward);
+}
+
+// returns false once the client is gone.
+async fn send_response(
+    outbound: &OutboundQueue,
+    request_id: Option<&str>,
+    response: &CommandResponse,
+) -> bool {
+    match response_to_json(request_id, response) {
+        Ok(v) => {
+            tracing::trace!("Sending: {}", v);
+            outbound.send(v).await
+        }
+        Err(e) => {
+            tracing::error!("Failed to serialize the response: {e}");
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use webb_relayer_handler_utils::WithdrawStatus;
+
+    type HandlerFuture =
+        future::BoxFuture<'static, Result<(), CommandResponse>>;
+
+    /// Answers every command with `Sent` then `Pong`, after a delay.
+    /// The first command is the slowest one.
+    fn slow_then_fast_handler(
+    ) -> impl Fn(RoutedCommand, CommandStream) -> HandlerFuture
+           + Clone
+           + Send
+           + 'static {
+        let calls = Arc::new(Mutex::new(0u64));
+        move |_cmd, stream| {
+            let call = {
+                let mut calls = calls.lock().unwrap();
+                *calls += 1;
+                *calls
+            };
+            async move {
+                let delay = if call == 1 { 200 } else { 10 };
+                let _ = stream
+                    .send(CommandResponse::Withdraw(WithdrawStatus::Sent))
+                    .await;
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                let _ = stream.send(CommandResponse::Pong()).await;
+                Ok(())
+            }
+            .boxed()
+        }
+    }
+
+    async fn serve(messages: Vec<&str>) -> Vec<serde_json::Value> {
+        let (outbound, responses) = mpsc::channel(50);
+        let inbound = stream::iter(
+            messages
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>(),
+        );
+        let outbound =
+            OutboundQueue::new(outbound, WsSlowClientPolicy::Backpressure);
+        serve_connection(inbound, outbound, slow_then_fast_handler()).await;
+        ReceiverStream::new(responses)
+            .map(|v| serde_json::from_str(&v).unwrap())
+            .collect()
+            .await
+    }
+
+    #[test]
+    fn request_id_should_be_echoed() {
+        let (request_id, command) =
+            parse_command(r#"{"requestId": "a", "ping": []}"#);
+        assert_eq!(request_id.as_deref(), Some("a"));
+        assert_eq!(command.unwrap().tag, "ping");
+
+        let (request_id, command) = parse_command(r#"{"ping": []}"#);
+        assert_eq!(request_id, None);
+        assert!(command.is_ok());
+
+        let (request_id, command) =
+            parse_command(r#"{"requestId": "b", "ping": [], "pong": []}"#);
+        assert_eq!(request_id.as_deref(), Some("b"));
+        assert!(command.is_err());
+
+        let v = response_to_json(Some("a"), &CommandResponse::Pong()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&v).unwrap();
+        assert_eq!(v, serde_json::json!({"pong": [], "requestId": "a"}));
+        let v = response_to_json(None, &CommandResponse::Pong()).unwrap();
+        assert_eq!(v, r#"{"pong":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn commands_without_request_id_should_be_sequential() {
+        let responses = serve(vec![r#"{"ping": []}"#, r#"{"ping": []}"#]).await;
+        let sent = serde_json::json!({"withdraw": "sent"});
+        let pong = serde_json::json!({"pong": []});
+        // the second command only starts once the first one is done,
+        // even though it is the faster one.
+        assert_eq!(responses, vec![sent.clone(), pong.clone(), sent, pong]);
+    }
+
+    #[tokio::test]
+    async fn commands_with_request_id_should_interleave() {
+        let responses = serve(vec![
+            r#"{"requestId": "slow", "ping": []}"#,
+            r#"{"requestId": "fast", "ping": []}"#,
+        ])
+        .await;
+        let ids = responses
+            .iter()
+            .map(|v| {
+                let kind = if v.get("pong").is_some() {
+                    "pong"
+                } else {
+                    "sent"
+                };
+                (v["requestId"].as_str().unwrap().to_string(), kind)
+            })
+            .collect::<Vec<_>>();
+        // both commands started right away, and the fast one finished
+        // first. Each command still sees its responses in order.
+        assert_eq!(
+            ids,
+            vec![
+                ("slow".to_string(), "sent"),
+                ("fast".to_string(), "sent"),
+                ("fast".to_string(), "pong"),
+                ("slow".to_string(), "pong"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_clients_should_be_disconnected() {
+        // the client reads nothing, so the queue holds a single response.
+        let (sender, mut responses) = mpsc::channel(1);
+        let outbound =
+            OutboundQueue::new(sender, WsSlowClientPolicy::Disconnect);
+        let inbound = stream::iter(vec![
+            r#"{"ping": []}"#.to_string(),
+            r#"{"ping": []}"#.to_string(),
+        ]);
+        serve_connection(inbound, outbound.clone(), slow_then_fast_handler())
+            .await;
+        assert!(outbound.overflowed().is_cancelled());
+        assert_eq!(
+            responses.recv().await.as_deref(),
+            Some(r#"{"withdraw":"sent"}"#)
+        );
+        // nothing is queued once the client got dropped.
+        assert!(!outbound.send("{}".to_string()).await);
+        assert!(responses.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn heartbeat_should_ping_every_interval() {
+        let heartbeat = WebSocketHeartbeat::new(Duration::from_millis(30));
+        let (outbound, mut pings) = mpsc::channel(8);
+        let cancel = CancellationToken::new();
+        tokio::spawn(heartbeat.clone().run(outbound, cancel.clone()));
+
+        let ping =
+            tokio::time::timeout(Duration::from_millis(35), pings.recv())
+                .await
+                .expect("a ping within the interval");
+        assert!(matches!(ping, Some(Message::Ping(_))));
+        heartbeat.pong();
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn heartbeat_should_close_silent_connections() {
+        let heartbeat = WebSocketHeartbeat::new(Duration::from_millis(10));
+        let (outbound, mut messages) = mpsc::channel(8);
+        let cancel = CancellationToken::new();
+        tokio::spawn(heartbeat.run(outbound, cancel.clone()));
+
+        let mut pings = 0;
+        let close = loop {
+            match messages.recv().await {
+                Some(Message::Ping(_)) => pings += 1,
+                other => break other,
+            }
+        };
+        assert!(pings >= 1);
+        match close {
+            Some(Message::Close(Some(frame))) => {
+                assert_eq!(frame.code, close_code::AWAY)
+            }
+            other => panic!("expected a close frame, got {other:?}"),
+        }
+        assert!(cancel.is_cancelled());
+    }
+
+    #[test]
+    fn message_at_the_size_limit_should_pass() {
+        let limit = WebSocketMessageSizeLimit::new(12);
+        let text = r#"{"ping": []}"#;
+        assert_eq!(text.len(), 12);
+        let checked = limit.check(Message::Text(text.to_string()));
+        assert_eq!(checked, Ok(Some(text.to_string())));
+        let checked = limit.check(Message::Binary(text.as_bytes().to_vec()));
+        assert_eq!(checked, Ok(Some(text.to_string())));
+    }
+
+    #[test]
+    fn message_over_the_size_limit_should_be_rejected() {
+        let limit = WebSocketMessageSizeLimit::new(11);
+        let rejected = limit
+            .check(Message::Text(r#"{"ping": []}"#.to_string()))
+            .unwrap_err();
+        assert_eq!(
+            rejected,
+            MessageRejected::TooLarge {
+                size: 12,
+                max_size: 11
+            }
+        );
+        assert_eq!(
+            rejected.response(),
+            CommandResponse::Error("message too large".to_string())
+        );
+        assert_eq!(rejected.close_frame().unwrap().code, close_code::SIZE);
+        // no limit at all.
+        let unlimited = WebSocketMessageSizeLimit::new(0);
+        let large = "x".repeat(1 << 20);
+        assert!(unlimited.check(Message::Text(large)).is_ok());
+    }
+
+    #[test]
+    fn message_rejected_by_the_transport_should_be_too_large() {
+        let limit = WebSocketMessageSizeLimit::new(65536);
+        let error = axum::Error::new(
+            "Space limit exceeded: Message too long: 70000 > 65536",
+        );
+        assert_eq!(
+            limit.rejected_by_transport(&error),
+            Some(MessageRejected::TooLarge {
+                size: 70000,
+                max_size: 65536
+            })
+        );
+        let error = axum::Error::new("Connection reset without closing");
+        assert_eq!(limit.rejected_by_transport(&error), None);
+    }
+
+    #[test]
+    fn non_utf8_binary_message_should_be_rejected() {
+        let limit = WebSocketMessageSizeLimit::new(64);
+        let rejected = limit
+            .check(Message::Binary(vec![b'{', 0xff, 0xfe, b'}']))
+            .unwrap_err();
+        assert_eq!(rejected, MessageRejected::NotUtf8);
+        assert!(rejected.close_frame().is_none());
+        assert_eq!(limit.check(Message::Ping(vec![0xff])), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn duplicate_request_id_should_be_rejected() {
+        let responses = serve(vec![
+            r#"{"requestId": "a", "ping": []}"#,
+            r#"{"requestId": "a", "ping": []}"#,
+        ])
+        .await;
+        assert_eq!(responses.len(), 3);
+        assert!(responses
+            .iter()
+            .all(|v| v["requestId"] == serde_json::json!("a")));
+        assert_eq!(
+            responses
+                .iter()
+                .filter(|v| v.get("error").is_some())
+                .count(),
+            1
+        );
+    }
+}
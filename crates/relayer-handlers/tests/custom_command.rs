@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use futures::prelude::*;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use webb_relayer_config::{WebbRelayerConfig, WsSlowClientPolicy};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::{CommandResponse, CommandStream};
+use webb_relayer_handlers::router::CommandRouter;
+use webb_relayer_handlers::websocket::{serve_connection, OutboundQueue};
+use webb_relayer_store::SledStore;
+
+#[derive(Deserialize)]
+struct Echo {
+    message: String,
+}
+
+async fn echo(
+    _ctx: RelayerContext,
+    cmd: Echo,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    let _ = stream.send(CommandResponse::Error(cmd.message)).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_commands_should_be_routed_next_to_the_builtin_ones() {
+    let store = SledStore::temporary().unwrap();
+    let ctx = RelayerContext::new(WebbRelayerConfig::default(), store).unwrap();
+    let mut router = CommandRouter::from_config(&ctx.config);
+    router.register("echo", echo);
+    let router = Arc::new(router);
+
+    let messages = vec![
+        r#"{"requestId": "a", "echo": {"message": "hello"}}"#,
+        r#"{"echo": {"text": "hello"}}"#,
+        r#"{"ping": []}"#,
+        r#"{"pong": []}"#,
+    ];
+    let inbound = stream::iter(messages.into_iter().map(ToOwned::to_owned));
+    let (tx, rx) = mpsc::channel(50);
+    let outbound = OutboundQueue::new(tx, WsSlowClientPolicy::Backpressure);
+    serve_connection(inbound, outbound, move |cmd, stream| {
+        let ctx = ctx.clone();
+        let router = router.clone();
+        async move { router.dispatch(ctx, cmd, stream).await }
+    })
+    .await;
+    let responses: Vec<serde_json::Value> = ReceiverStream::new(rx)
+        .map(|v| serde_json::from_str(&v).unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(responses.len(), 4);
+    assert!(responses
+        .contains(&serde_json::json!({"error": "hello", "requestId": "a"})));
+    assert!(responses.contains(&serde_json::json!({"pong": []})));
+    assert!(responses.contains(&serde_json::json!({"UNKNOWN_COMMAND": {
+        "tag": "pong",
+        "supported": [
+            "cancelScheduled",
+            "echo",
+            "evm",
+            "ping",
+            "subscribeProposal",
+            "subscribeProposals",
+            "substrate",
+        ],
+    }})));
+    // the payload of the custom command is checked by the router.
+    let invalid = responses
+        .iter()
+        .filter_map(|v| v["error"].as_str())
+        .find(|e| e.contains("missing field `message`"));
+    assert!(invalid.is_some());
+}
@@ -0,0 +1,253 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::{Notify, Semaphore};
+
+use crate::sled::SledQueueKey;
+use crate::{BridgeCommand, BridgeKey, QueueStore};
+
+/// How long a bridge watcher waits for the transactions queued for a command
+/// to be sent, before it takes the next command anyway, see
+/// [`BridgeCommandReceiver::wait_for_queued_txs`].
+pub const QUEUED_TXS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The commands of a bridge, as taken by its bridge watcher, shared by the
+/// restarts of the watcher.
+#[derive(Debug, Clone)]
+pub struct BridgeCommandReceiver {
+    bridge_key: BridgeKey,
+    capacity: usize,
+    /// The room left for the commands of the bridge.
+    permits: Arc<Semaphore>,
+    /// Wakes the watcher up once a command got queued.
+    queued: Arc<Notify>,
+}
+
+impl BridgeCommandReceiver {
+    /// Takes the next command of the bridge out of the store, waiting up to
+    /// `timeout` for one to be sent if there is none.
+    pub async fn recv<S>(
+        &self,
+        store: &S,
+        timeout: Duration,
+    ) -> crate::Result<Option<BridgeCommand>>
+    where
+        S: QueueStore<BridgeCommand, Key = SledQueueKey>,
+    {
+        if let Some(cmd) = self.dequeue(store)? {
+            return Ok(Some(cmd));
+        }
+        let _ = tokio::time::timeout(timeout, self.queued.notified()).await;
+        self.dequeue(store)
+    }
+
+    /// Waits for the items still in the queue of `key`, such as the
+    /// transactions queued for the last command, to be taken out of it.
+    ///
+    /// Gives up with a warning after `timeout`, so that a stuck queue does
+    /// not hold back the commands of the bridge forever.
+    pub async fn wait_for_queued_txs<T, S>(
+        &self,
+        store: &S,
+        key: SledQueueKey,
+        timeout: Duration,
+    ) -> crate::Result<()>
+    where
+        S: QueueStore<T, Key = SledQueueKey>,
+    {
+        let started = tokio::time::Instant::now();
+        while store.peek_item(key)?.is_some() {
+            if started.elapsed() >= timeout {
+                tracing::warn!(
+                    bridge_key = %self.bridge_key,
+                    ?key,
+                    ?timeout,
+                    "The queued transactions are still not sent, \
+                    taking the next command anyway",
+                );
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    fn dequeue<S>(&self, store: &S) -> crate::Result<Option<BridgeCommand>>
+    where
+        S: QueueStore<BridgeCommand, Key = SledQueueKey>,
+    {
+        let key = SledQueueKey::from_bridge_key(self.bridge_key);
+        let cmd = store.dequeue_item(key)?;
+        // the commands queued before the watcher subscribed took no room.
+        if cmd.is_some() && self.permits.available_permits() < self.capacity {
+            self.permits.add_permits(1);
+        }
+        Ok(cmd)
+    }
+}
+
+/// Hands the [`BridgeCommand`]s from the event handlers over to the bridge
+/// watchers, one per bridge.
+///
+/// The commands are always queued in the store, so that they survive a
+/// restart; the channels only bound how many of them wait for the watcher
+/// of a bridge. A handler sending a command to a bridge that has `capacity`
+/// commands waiting already waits for its watcher to catch up. The commands
+/// of the bridges without a watcher are queued without a bound, until one
+/// subscribes.
+#[derive(Debug, Clone)]
+pub struct BridgeCommandChannels {
+    capacity: usize,
+    channels: Arc<Mutex<HashMap<BridgeKey, BridgeCommandReceiver>>>,
+}
+
+impl BridgeCommandChannels {
+    /// Creates the channels, each holding up to `capacity` commands.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            channels: Default::default(),
+        }
+    }
+
+    /// Subscribes the bridge watcher of `bridge_key` to its commands.
+    pub fn subscribe(&self, bridge_key: BridgeKey) -> BridgeCommandReceiver {
+        let mut channels = self.channels.lock();
+        channels
+            .entry(bridge_key)
+            .or_insert_with(|| BridgeCommandReceiver {
+                bridge_key,
+                capacity: self.capacity,
+                permits: Arc::new(Semaphore::new(self.capacity)),
+                queued: Default::default(),
+            })
+            .clone()
+    }
+
+    /// Queues `cmd` for the bridge watcher of `bridge_key` in the `store`,
+    /// waiting for room while it is behind.
+    pub async fn send<S>(
+        &self,
+        store: &S,
+        bridge_key: BridgeKey,
+        cmd: BridgeCommand,
+    ) -> crate::Result<()>
+    where
+        S: QueueStore<BridgeCommand, Key = SledQueueKey>,
+    {
+        let key = SledQueueKey::from_bridge_key(bridge_key);
+        let receiver = self.channels.lock().get(&bridge_key).cloned();
+        let Some(receiver) = receiver else {
+            return store.enqueue_item(key, cmd);
+        };
+        let permit = match receiver.permits.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                tracing::debug!(
+                    %bridge_key,
+                    "The bridge watcher is behind, waiting for it to catch up",
+                );
+                // the semaphores are never closed.
+                receiver.permits.acquire().await.map_err(|_| {
+                    webb_relayer_utils::Error::Generic(
+                        "the bridge command channel is closed",
+                    )
+                })?
+            }
+        };
+        store.enqueue_item(key, cmd)?;
+        // given back once the watcher takes the command out of the store.
+        permit.forget();
+        receiver.queued.notify_one();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SledStore;
+
+    fn cmd(nonce: u32) -> BridgeCommand {
+        BridgeCommand::TransferOwnershipWithSignature {
+            public_key: vec![1],
+            nonce,
+            signature: vec![2],
+        }
+    }
+
+    #[tokio::test]
+    async fn full_channels_should_hold_back_the_handlers() {
+        let store = SledStore::temporary().unwrap();
+        let channels = BridgeCommandChannels::new(1);
+        let bridge_key = BridgeKey::new(webb_proposals::TypedChainId::Evm(5));
+        let queue_key = SledQueueKey::from_bridge_key(bridge_key);
+        let timeout = Duration::from_millis(50);
+
+        // no watcher yet, the command is queued without a bound.
+        channels.send(&store, bridge_key, cmd(1)).await.unwrap();
+        let rx = channels.subscribe(bridge_key);
+        channels.send(&store, bridge_key, cmd(2)).await.unwrap();
+        let send = channels.send(&store, bridge_key, cmd(3));
+        tokio::pin!(send);
+        let blocked = tokio::time::timeout(timeout, &mut send).await;
+        assert!(blocked.is_err());
+
+        // the commands are kept in the store, in the order they got sent.
+        assert_eq!(rx.recv(&store, timeout).await.unwrap(), Some(cmd(1)));
+        send.await.unwrap();
+        assert_eq!(rx.recv(&store, timeout).await.unwrap(), Some(cmd(2)));
+        assert_eq!(
+            QueueStore::<BridgeCommand>::peek_item(&store, queue_key).unwrap(),
+            Some(cmd(3))
+        );
+        assert_eq!(rx.recv(&store, timeout).await.unwrap(), Some(cmd(3)));
+        assert_eq!(rx.recv(&store, timeout).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_the_queued_txs_should_time_out() {
+        let store = SledStore::temporary().unwrap();
+        let channels = BridgeCommandChannels::new(1);
+        let bridge_key = BridgeKey::new(webb_proposals::TypedChainId::Evm(5));
+        let rx = channels.subscribe(bridge_key);
+        let tx_key = SledQueueKey::from_evm_chain_id(5);
+        store.enqueue_item(tx_key, cmd(1)).unwrap();
+
+        let wait = rx.wait_for_queued_txs::<BridgeCommand, _>(
+            &store,
+            tx_key,
+            Duration::from_millis(300),
+        );
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("gave up waiting")
+            .unwrap();
+        store.dequeue_item(tx_key).unwrap();
+        let wait = rx.wait_for_queued_txs::<BridgeCommand, _>(
+            &store,
+            tx_key,
+            Duration::from_secs(60),
+        );
+        tokio::time::timeout(Duration::from_millis(50), wait)
+            .await
+            .expect("nothing left to wait for")
+            .unwrap();
+    }
+}
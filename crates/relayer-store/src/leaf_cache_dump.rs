@@ -0,0 +1,235 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use webb::evm::ethers::{types, utils};
+use webb_proposals::ResourceId;
+use webb_relayer_utils::Error;
+
+/// Every leaf cache dump starts with these bytes.
+const MAGIC: &[u8; 4] = b"WRLC";
+/// The version of the leaf cache dump format.
+pub const LEAF_CACHE_DUMP_VERSION: u8 = 1;
+/// magic + version + contract count.
+const HEADER_LEN: usize = 4 + 1 + 4;
+/// resource id + last block + last deposit block + leaf count.
+const CONTRACT_HEADER_LEN: usize = 32 + 8 + 8 + 4;
+/// index + leaf + block number.
+const LEAF_LEN: usize = 4 + 32 + 8;
+/// The keccak256 hash of everything before it.
+const CHECKSUM_LEN: usize = 32;
+
+/// A leaf of a [`ContractLeafCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedLeaf {
+    /// The index of the leaf in the Merkle tree.
+    pub index: u32,
+    /// The leaf itself.
+    pub leaf: types::H256,
+    /// The block in which the leaf got inserted.
+    pub block_number: u64,
+}
+
+/// The cached leaves of a single contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractLeafCache {
+    /// The contract, and the chain it is deployed on.
+    pub resource_id: ResourceId,
+    /// The last block the events watcher of the contract went through.
+    pub last_block: u64,
+    /// The block of the last deposit of the contract.
+    pub last_deposit_block: u64,
+    /// The leaves, in the order of their indices.
+    pub leaves: Vec<CachedLeaf>,
+}
+
+/// A dump of the whole leaf cache of a relayer, used to seed another
+/// relayer without syncing the leaves from the chains again.
+///
+/// The binary layout is the header, then every contract with its header
+/// followed by its leaves, then the keccak256 checksum of all of it. The
+/// numbers are big endian.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeafCacheDump {
+    /// The contracts with cached leaves.
+    pub contracts: Vec<ContractLeafCache>,
+}
+
+impl LeafCacheDump {
+    /// The total number of leaves in the dump.
+    pub fn leaf_count(&self) -> usize {
+        self.contracts.iter().map(|c| c.leaves.len()).sum()
+    }
+
+    /// Encodes the dump into its binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = HEADER_LEN
+            + self.contracts.len() * CONTRACT_HEADER_LEN
+            + self.leaf_count() * LEAF_LEN
+            + CHECKSUM_LEN;
+        let mut bytes = Vec::with_capacity(len);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(LEAF_CACHE_DUMP_VERSION);
+        bytes.extend_from_slice(&(self.contracts.len() as u32).to_be_bytes());
+        for contract in &self.contracts {
+            bytes.extend_from_slice(&contract.resource_id.to_bytes());
+            bytes.extend_from_slice(&contract.last_block.to_be_bytes());
+            bytes.extend_from_slice(&contract.last_deposit_block.to_be_bytes());
+            bytes.extend_from_slice(
+                &(contract.leaves.len() as u32).to_be_bytes(),
+            );
+            for leaf in &contract.leaves {
+                bytes.extend_from_slice(&leaf.index.to_be_bytes());
+                bytes.extend_from_slice(leaf.leaf.as_bytes());
+                bytes.extend_from_slice(&leaf.block_number.to_be_bytes());
+            }
+        }
+        let checksum = utils::keccak256(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// Decodes a dump, making sure it is intact and
+    /// [valid](LeafCacheDump::validate).
+    pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN || &bytes[..4] != MAGIC {
+            return Err(Error::Generic("Not a leaf cache dump"));
+        }
+        if bytes[4] != LEAF_CACHE_DUMP_VERSION {
+            return Err(Error::Generic("Unsupported leaf cache dump version"));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if utils::keccak256(body) != checksum {
+            return Err(Error::Generic("The leaf cache dump is corrupted"));
+        }
+        let mut at = 5;
+        let mut take = |len: usize| {
+            let chunk = body
+                .get(at..at + len)
+                .ok_or(Error::Generic("Truncated leaf cache dump"))?;
+            at += len;
+            Ok::<_, Error>(chunk)
+        };
+        let contract_count = u32::from_be_bytes(take(4)?.try_into().unwrap());
+        let mut contracts = Vec::new();
+        for _ in 0..contract_count {
+            let resource_id: [u8; 32] = take(32)?.try_into().unwrap();
+            let last_block = u64::from_be_bytes(take(8)?.try_into().unwrap());
+            let last_deposit_block =
+                u64::from_be_bytes(take(8)?.try_into().unwrap());
+            let leaf_count = u32::from_be_bytes(take(4)?.try_into().unwrap());
+            let mut leaves = Vec::new();
+            for _ in 0..leaf_count {
+                let index = u32::from_be_bytes(take(4)?.try_into().unwrap());
+                let leaf = types::H256::from_slice(take(32)?);
+                let block_number =
+                    u64::from_be_bytes(take(8)?.try_into().unwrap());
+                leaves.push(CachedLeaf {
+                    index,
+                    leaf,
+                    block_number,
+                });
+            }
+            contracts.push(ContractLeafCache {
+                resource_id: ResourceId::from(resource_id),
+                last_block,
+                last_deposit_block,
+                leaves,
+            });
+        }
+        if at != body.len() {
+            return Err(Error::Generic(
+                "Trailing bytes in the leaf cache dump",
+            ));
+        }
+        let dump = Self { contracts };
+        dump.validate()?;
+        Ok(dump)
+    }
+
+    /// Makes sure every contract shows up once, and that its leaves start
+    /// at index zero without any gap, as they are in the leaf cache.
+    pub fn validate(&self) -> crate::Result<()> {
+        for (i, contract) in self.contracts.iter().enumerate() {
+            let is_duplicate = self.contracts[..i]
+                .iter()
+                .any(|c| c.resource_id == contract.resource_id);
+            if is_duplicate {
+                return Err(Error::Generic(
+                    "A contract shows up twice in the leaf cache dump",
+                ));
+            }
+            let has_gaps = contract
+                .leaves
+                .iter()
+                .zip(0u32..)
+                .any(|(leaf, expected)| leaf.index != expected);
+            if has_gaps {
+                return Err(Error::Generic(
+                    "The leaves of the leaf cache dump are not contiguous",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_proposals::{TargetSystem, TypedChainId};
+
+    fn dump() -> LeafCacheDump {
+        let leaves = (0..3u32)
+            .map(|index| CachedLeaf {
+                index,
+                leaf: types::H256::from_low_u64_be(index.into()),
+                block_number: 100 + u64::from(index),
+            })
+            .collect();
+        LeafCacheDump {
+            contracts: vec![ContractLeafCache {
+                resource_id: ResourceId::new(
+                    TargetSystem::new_contract_address([0x11; 20]),
+                    TypedChainId::Evm(1),
+                ),
+                last_block: 120,
+                last_deposit_block: 102,
+                leaves,
+            }],
+        }
+    }
+
+    #[test]
+    fn leaf_cache_dump_should_round_trip() {
+        let dump = dump();
+        let decoded = LeafCacheDump::from_bytes(&dump.to_bytes()).unwrap();
+        assert_eq!(decoded, dump);
+        assert_eq!(decoded.leaf_count(), 3);
+    }
+
+    #[test]
+    fn invalid_leaf_cache_dumps_should_be_rejected() {
+        let mut bytes = dump().to_bytes();
+        bytes[HEADER_LEN + CONTRACT_HEADER_LEN] ^= 1;
+        assert!(LeafCacheDump::from_bytes(&bytes).is_err());
+
+        let mut with_gap = dump();
+        with_gap.contracts[0].leaves.remove(1);
+        assert!(LeafCacheDump::from_bytes(&with_gap.to_bytes()).is_err());
+
+        let mut duplicated = dump();
+        duplicated.contracts.push(duplicated.contracts[0].clone());
+        assert!(duplicated.validate().is_err());
+    }
+}
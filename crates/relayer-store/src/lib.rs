@@ -21,26 +21,42 @@
 //! The relayer store module stores the history of events. Manages the setting
 //! and retrieving operations of events.
 //!
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Display};
 use std::sync::Arc;
 
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use webb::evm::ethers::types;
+use webb::evm::ethers::{types, utils};
 use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_utils::multi_provider::RpcCallKind;
 use webb_relayer_utils::Result;
+/// A module for handing the bridge commands over to the bridge watchers.
+#[cfg(feature = "sled")]
+pub mod bridge_commands;
+/// A module for dumping the leaf cache, to move it to another relayer.
+pub mod leaf_cache_dump;
 /// A module for managing in-memory storage of the relayer.
 pub mod mem;
+/// A module for exporting the proposals, with the states they went through.
+pub mod proposal_audit;
 /// A module for setting up and managing a [Sled](https://sled.rs)-based database.
 #[cfg(feature = "sled")]
 pub mod sled;
 
 /// A store that uses [`sled`](https://sled.rs) as the backend.
 #[cfg(feature = "sled")]
-pub use self::sled::SledStore;
+pub use self::sled::{SledStore, StoreMetrics};
 /// A store that uses in memory data structures as the backend.
 pub use mem::InMemoryStore;
 
+#[cfg(feature = "sled")]
+pub use bridge_commands::{
+    BridgeCommandChannels, BridgeCommandReceiver, QUEUED_TXS_TIMEOUT,
+};
+pub use leaf_cache_dump::{CachedLeaf, ContractLeafCache, LeafCacheDump};
+pub use proposal_audit::{ProposalAudit, ProposalStatusChange};
+
 /// HistoryStoreKey contains the keys used to store the history of events.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum HistoryStoreKey {
@@ -241,6 +257,20 @@ pub trait HistoryStore: Clone + Send + Sync {
     ) -> crate::Result<u64> {
         self.get_target_block_number(key, 1u64)
     }
+
+    /// Sets when the events watcher of that contract last moved its block
+    /// number, in secs since the UNIX epoch.
+    fn set_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        timestamp: u64,
+    ) -> crate::Result<()>;
+    /// Get when the events watcher of that contract last moved its block
+    /// number, if it ever did.
+    fn get_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<Option<u64>>;
 }
 
 /// A Simple Event Store, that does not store the events, instead it store the hash of the event as the key
@@ -261,6 +291,38 @@ pub trait EventHashStore: Send + Sync + Clone {
     fn delete_event(&self, event: &[u8]) -> crate::Result<()>;
 }
 
+/// The number of leaves that could be buffered after a missing leaf by
+/// default, see [`LeafCacheStore::insert_leaves_with_max_pending`].
+pub const DEFAULT_MAX_PENDING_LEAVES: u32 = 1024;
+
+/// The outcome of inserting leaves in the leaf cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeafInsertion {
+    /// The leaves that got cached, including the pending leaves they
+    /// unblocked.
+    pub inserted: BTreeMap<u32, types::H256>,
+    /// The leaves that are ahead of the cache, and got buffered until the
+    /// leaves before them are inserted.
+    pub pending: Vec<u32>,
+    /// The inserted leaves whose commitment was already cached at another
+    /// index, which should never happen.
+    pub duplicates: Vec<DuplicateCommitment>,
+    /// The first missing leaf index, when more leaves than allowed are
+    /// pending after it, so the missing range should be fetched again.
+    pub missing_from: Option<u32>,
+}
+
+/// A commitment cached at two different leaf indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateCommitment {
+    /// The commitment.
+    pub commitment: types::H256,
+    /// The index the commitment got cached at first.
+    pub cached_index: u32,
+    /// The index the commitment got inserted at again.
+    pub index: u32,
+}
+
 /// A Leaf Cache Store is a simple trait that would help in
 /// getting the leaves and insert them with a simple API.
 pub trait LeafCacheStore: HistoryStore {
@@ -293,7 +355,29 @@ pub trait LeafCacheStore: HistoryStore {
         key: K,
     ) -> crate::Result<u64>;
 
-    /// Insert leaves and last deposit block number for the given key.
+    /// Get the leaves for the given key that are buffered until the leaves
+    /// before them are inserted, with the block they got inserted at.
+    fn get_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<BTreeMap<u32, (types::H256, u64)>>;
+
+    /// Drop the leaves for the given key that are buffered, such as the ones
+    /// that do not add up to a known root, so they get fetched again.
+    fn remove_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<()>;
+
+    /// Get the index the next leaf has to be inserted at for the given key,
+    /// which is the number of leaves cached without any gap.
+    fn next_leaf_index<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<u32>;
+
+    /// Insert leaves and last deposit block number for the given key, with
+    /// [`DEFAULT_MAX_PENDING_LEAVES`] pending leaves at most.
     fn insert_leaves_and_last_deposit_block_number<
         K: Into<HistoryStoreKey> + Debug + Clone,
     >(
@@ -301,7 +385,96 @@ pub trait LeafCacheStore: HistoryStore {
         key: K,
         leaves: &[(u32, Vec<u8>)],
         block_number: u64,
-    ) -> crate::Result<()>;
+    ) -> crate::Result<LeafInsertion> {
+        self.insert_leaves_with_max_pending(
+            key,
+            leaves,
+            block_number,
+            DEFAULT_MAX_PENDING_LEAVES,
+        )
+    }
+
+    /// Insert leaves and last deposit block number for the given key.
+    ///
+    /// The leaves are inserted in the order of their indices, without gaps. A
+    /// leaf that is ahead of the [next leaf index](Self::next_leaf_index) is
+    /// buffered, and inserted once the leaves before it are. A leaf that is
+    /// already cached or buffered is skipped, unless it does not match the
+    /// one there, which fails the whole insertion with `LeafCacheCorrupted`
+    /// rather than overwriting it. A leaf whose commitment is cached at
+    /// another index is still inserted, and reported in
+    /// [`LeafInsertion::duplicates`].
+    ///
+    /// If more than `max_pending` leaves are pending, the leaves are still
+    /// kept, and the first missing leaf index is reported in
+    /// [`LeafInsertion::missing_from`], so the missing range could be fetched
+    /// again.
+    fn insert_leaves_with_max_pending<
+        K: Into<HistoryStoreKey> + Debug + Clone,
+    >(
+        &self,
+        key: K,
+        leaves: &[(u32, Vec<u8>)],
+        block_number: u64,
+        max_pending: u32,
+    ) -> crate::Result<LeafInsertion>;
+
+    /// Get the block numbers in which the leaves with the given range of
+    /// indices got inserted, for the leaves cached with their block number.
+    fn get_leaf_block_numbers<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        range: core::ops::Range<u32>,
+    ) -> crate::Result<BTreeMap<u32, u64>>;
+
+    /// Get the leaves for the given key and range of indices that are final,
+    /// i.e. inserted at or before `final_block_number`.
+    ///
+    /// The leaves stop at the first leaf that is not final yet, since a proof
+    /// needs all the leaves before it. Leaves cached without their block
+    /// number, before it was tracked, are considered final.
+    fn get_final_leaves_with_range<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        range: core::ops::Range<u32>,
+        final_block_number: u64,
+    ) -> crate::Result<BTreeMap<u32, types::H256>> {
+        let key: HistoryStoreKey = key.into();
+        let block_numbers = self.get_leaf_block_numbers(key, range.clone())?;
+        let leaves = self
+            .get_leaves_with_range(key, range)?
+            .into_iter()
+            .take_while(|(index, _)| {
+                block_numbers
+                    .get(index)
+                    .map_or(true, |block| *block <= final_block_number)
+            })
+            .collect();
+        Ok(leaves)
+    }
+
+    /// Get the leaf indices in `0..expected_count` that are missing from the
+    /// cache for the given key, in ascending order.
+    fn get_missing_leaf_indices<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        expected_count: u32,
+    ) -> crate::Result<Vec<u32>> {
+        let key: HistoryStoreKey = key.into();
+        // the pending leaves are not missing, only the leaves before them.
+        let pending = self.get_pending_leaves(key)?;
+        let stored = self
+            .get_leaves(key)?
+            .into_iter()
+            .map(|(index, _)| index)
+            .chain(pending.into_keys())
+            .filter(|index| *index < expected_count)
+            .collect::<BTreeSet<_>>();
+        let missing = (0..expected_count)
+            .filter(|index| !stored.contains(index))
+            .collect();
+        Ok(missing)
+    }
 }
 
 /// An Encrypted Output Cache Store is a simple trait that would help in
@@ -449,3 +622,858 @@ where
         value: CachedTokenPrice,
     ) -> crate::Result<()>;
 }
+
+/// A Deposit Correlation Id links a deposit on a source anchor to every proposal
+/// and execution it caused on the linked anchors.
+///
+/// It is the `keccak256` hash of the source resource id (chain id + anchor)
+/// and the leaf index of the deposit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DepositCorrelationId(pub types::H256);
+
+impl DepositCorrelationId {
+    /// Creates a new correlation id from the source anchor resource id and the leaf index.
+    pub fn new(src_resource_id: ResourceId, leaf_index: u32) -> Self {
+        let mut bytes = Vec::with_capacity(36);
+        bytes.extend_from_slice(&src_resource_id.to_bytes());
+        bytes.extend_from_slice(&leaf_index.to_be_bytes());
+        Self(utils::keccak256(bytes).into())
+    }
+}
+
+impl Display for DepositCorrelationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// The propagation state of a deposit to one of the linked anchors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationPropagation {
+    /// The underlying chain id of the linked anchor.
+    pub chain_id: u32,
+    /// The hash of the anchor update proposal data.
+    pub proposal_data_hash: types::H256,
+    /// The time at which the proposal was created, in secs since the UNIX epoch.
+    pub proposed_at: u64,
+    /// The hash of the transaction that executed the proposal, if any.
+    pub execution_tx: Option<types::H256>,
+    /// The time at which the execution got included, in secs since the UNIX epoch.
+    pub executed_at: Option<u64>,
+}
+
+impl DestinationPropagation {
+    /// Returns true if the proposal got executed on the linked anchor.
+    pub fn is_executed(&self) -> bool {
+        self.execution_tx.is_some()
+    }
+}
+
+/// A record of a deposit and its propagation to the linked anchors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositPropagationRecord {
+    /// The transaction hash of the deposit on the source chain.
+    pub deposit_tx: types::H256,
+    /// The block number of the deposit on the source chain.
+    pub deposit_block: u64,
+    /// The time at which the relayer first saw the deposit, in secs since the UNIX epoch.
+    pub seen_at: u64,
+    /// The propagation state per linked anchor, keyed by the hex encoded target resource id.
+    pub destinations: BTreeMap<String, DestinationPropagation>,
+}
+
+impl DepositPropagationRecord {
+    /// Returns true if the deposit was executed on every linked anchor it was proposed to.
+    pub fn is_fully_propagated(&self) -> bool {
+        !self.destinations.is_empty()
+            && self.destinations.values().all(|d| d.is_executed())
+    }
+}
+
+/// A Deposit Propagation Store keeps track of deposits from the moment they are seen
+/// on the source chain until the anchor update proposals they caused are executed on
+/// the linked chains.
+///
+/// Executions are linked back through the hash of the call data that got enqueued in the
+/// transaction queue, since this is all the transaction queue knows about.
+pub trait DepositPropagationStore: Send + Sync + Clone {
+    /// Records a newly seen deposit, if it is not already recorded.
+    fn insert_deposit(
+        &self,
+        id: DepositCorrelationId,
+        deposit_tx: types::H256,
+        deposit_block: u64,
+    ) -> crate::Result<()>;
+
+    /// Records that an anchor update proposal was created for the given deposit.
+    fn insert_deposit_proposal(
+        &self,
+        id: DepositCorrelationId,
+        target_resource_id: ResourceId,
+        proposal_data_hash: [u8; 32],
+    ) -> crate::Result<()>;
+
+    /// Moves the proposal record of a deposit to a proposal that replaced it,
+    /// for example after its merkle root got refreshed.
+    ///
+    /// Returns `true` if a deposit was updated.
+    fn relink_deposit_proposal(
+        &self,
+        old_proposal_data_hash: [u8; 32],
+        new_proposal_data_hash: [u8; 32],
+    ) -> crate::Result<bool>;
+
+    /// Links the hash of the call data of an enqueued transaction to the proposal it executes.
+    fn link_execution_call(
+        &self,
+        call_data_hash: [u8; 32],
+        proposal_data_hash: [u8; 32],
+    ) -> crate::Result<()>;
+
+    /// Marks the proposal that is executed by the given call data as executed in `tx_hash`.
+    ///
+    /// Returns `true` if a deposit was updated.
+    fn mark_execution(
+        &self,
+        call_data_hash: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<bool>;
+
+    /// Marks the proposal with the given data hash as executed in `tx_hash`, for
+    /// executions seen on-chain rather than sent by the transaction queue.
+    ///
+    /// Returns `true` if a deposit was updated, and `false` if the proposal is
+    /// unknown or was already marked as executed.
+    fn mark_proposal_execution(
+        &self,
+        proposal_data_hash: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<bool>;
+
+    /// Get the propagation record of a deposit, if any.
+    fn get_deposit_propagation(
+        &self,
+        id: DepositCorrelationId,
+    ) -> crate::Result<Option<DepositPropagationRecord>>;
+
+    /// Get the hashes of the anchor update proposals that were not executed yet.
+    fn list_pending_proposals(&self) -> crate::Result<Vec<[u8; 32]>>;
+}
+
+/// The maximum number of withdrawals kept per contract in a [`RelayedWithdrawalStore`].
+pub const MAX_RELAYED_WITHDRAWALS: usize = 1_000;
+
+/// A withdrawal the relayer successfully relayed.
+///
+/// This only holds what is needed to show the relayer activity, never the
+/// recipient or the nullifiers of the withdrawal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayedWithdrawal {
+    /// The hash of the withdrawal transaction.
+    pub tx_hash: types::H256,
+    /// The time at which the transaction got finalized, in secs since the UNIX epoch.
+    pub finalized_at: u64,
+    /// The time it took from accepting the withdrawal to its finalization, in millis.
+    pub confirmation_latency_ms: u64,
+    /// The fee charged for relaying the withdrawal.
+    pub fee: types::U256,
+    /// The gas used by the transaction.
+    pub gas_used: types::U256,
+}
+
+/// A Relayed Withdrawal Store keeps the most recent withdrawals relayed on each
+/// contract, up to [`MAX_RELAYED_WITHDRAWALS`].
+pub trait RelayedWithdrawalStore: Send + Sync + Clone {
+    /// Records a withdrawal relayed on the given contract, dropping the
+    /// oldest ones over [`MAX_RELAYED_WITHDRAWALS`].
+    fn insert_relayed_withdrawal<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        withdrawal: RelayedWithdrawal,
+    ) -> crate::Result<()>;
+
+    /// Get the most recent withdrawals relayed on the given contract, newest first.
+    fn get_relayed_withdrawals<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        limit: usize,
+    ) -> crate::Result<Vec<RelayedWithdrawal>>;
+}
+
+/// The fees the relayer collected on one chain, in one token, during one day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEarnings {
+    /// The typed chain id of the chain the fees were collected on.
+    pub typed_chain_id: u64,
+    /// The token the fees were paid in, its address on EVM chains or its
+    /// asset id on Substrate chains.
+    pub token: String,
+    /// The day the fees were collected, in days since the UNIX epoch.
+    pub day: u64,
+    /// The sum of the fees.
+    pub total: types::U256,
+    /// The number of relayed withdrawals that paid these fees.
+    pub count: u64,
+}
+
+/// A Fee Accounting Store keeps the fees collected by the relayer, per chain,
+/// token and day.
+pub trait FeeAccountingStore: Send + Sync + Clone {
+    /// Records the fee collected on a relayed withdrawal, at `timestamp` in
+    /// secs since the UNIX epoch.
+    fn record_fee(
+        &self,
+        chain_id: TypedChainId,
+        token: &str,
+        fee: types::U256,
+        timestamp: u64,
+    ) -> crate::Result<()>;
+
+    /// Get all the recorded fees, sorted by chain, then by day.
+    fn get_fee_earnings(&self) -> crate::Result<Vec<FeeEarnings>>;
+}
+
+/// The RPC calls served by one provider during one day.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderUsage {
+    /// The label of the provider.
+    pub provider: String,
+    /// The day of the calls, in days since the UNIX epoch.
+    pub day: u64,
+    /// The number of calls, of any kind.
+    pub requests: u64,
+    /// The number of event log queries.
+    pub log_queries: u64,
+    /// The number of transaction submissions.
+    pub submissions: u64,
+    /// The number of failed calls.
+    pub errors: u64,
+}
+
+impl ProviderUsage {
+    /// Counts a call of the given kind.
+    pub fn count_call(&mut self, kind: RpcCallKind, failed: bool) {
+        self.requests += 1;
+        match kind {
+            RpcCallKind::Submission => self.submissions += 1,
+            RpcCallKind::LogQuery => self.log_queries += 1,
+            RpcCallKind::Request => {}
+        }
+        if failed {
+            self.errors += 1;
+        }
+    }
+}
+
+/// A Provider Usage Store counts the RPC calls served by each provider, per
+/// day, to attribute them to the right RPC vendor account.
+pub trait ProviderUsageStore: Send + Sync + Clone {
+    /// Adds the calls counted in `usage` to the ones recorded for the same
+    /// providers and days.
+    fn add_provider_usage(&self, usage: &[ProviderUsage]) -> crate::Result<()>;
+
+    /// Get the recorded calls since `since_day`, in days since the UNIX
+    /// epoch, sorted by provider, then by day.
+    fn get_provider_usage(
+        &self,
+        since_day: u64,
+    ) -> crate::Result<Vec<ProviderUsage>>;
+}
+
+/// An amount relayed on a contract, counted against its withdrawal limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayedAmount {
+    /// Identifies the amount, to release it if the withdrawal fails.
+    pub id: u64,
+    /// When the amount got reserved, in secs since the UNIX epoch.
+    pub at: u64,
+    /// The amount, in the smallest unit of the token.
+    pub amount: types::U256,
+}
+
+/// The outcome of reserving an amount against a withdrawal limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountReservation {
+    /// The amount fits in the limit and is now counted against it.
+    Reserved {
+        /// The id to release the amount with.
+        id: u64,
+    },
+    /// The amount does not fit in the limit.
+    Exceeded {
+        /// How long until enough of the limit frees up for the amount, in
+        /// secs, `None` if the amount is over the limit on its own.
+        retry_after_secs: Option<u64>,
+    },
+}
+
+/// Returns how long, in secs from `now`, until `amount` fits in `limit`
+/// along with the amounts relayed over the last `window_secs`.
+///
+/// `Some(0)` means that it fits right away.
+pub fn amount_fits_in(
+    relayed: &[RelayedAmount],
+    amount: types::U256,
+    limit: types::U256,
+    window_secs: u64,
+    now: u64,
+) -> Option<u64> {
+    if amount > limit {
+        return None;
+    }
+    let since = now.saturating_sub(window_secs);
+    let mut in_window: Vec<_> =
+        relayed.iter().filter(|r| r.at > since).collect();
+    in_window.sort_by_key(|r| r.at);
+    let mut used = in_window
+        .iter()
+        .fold(types::U256::zero(), |acc, r| acc.saturating_add(r.amount));
+    let mut retry_after = 0;
+    // the oldest amounts leave the window first.
+    for r in in_window {
+        if used.saturating_add(amount) <= limit {
+            break;
+        }
+        used = used.saturating_sub(r.amount);
+        retry_after = (r.at + window_secs).saturating_sub(now);
+    }
+    Some(retry_after)
+}
+
+/// A Withdrawal Limit Store keeps the amounts relayed on each contract over
+/// a sliding window, to cap how much the relayer moves.
+pub trait WithdrawalLimitStore: Send + Sync + Clone {
+    /// Counts `amount` against the `limit` of the given contract, unless the
+    /// amounts relayed over the last `window_secs` leave no room for it.
+    fn reserve_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        amount: types::U256,
+        limit: types::U256,
+        window_secs: u64,
+        now: u64,
+    ) -> crate::Result<AmountReservation>;
+
+    /// Stops counting a reserved amount, such as when its withdrawal failed.
+    fn release_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        id: u64,
+    ) -> crate::Result<()>;
+
+    /// Get the sum of the amounts relayed on the given contract over the
+    /// last `window_secs`.
+    fn get_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        window_secs: u64,
+        now: u64,
+    ) -> crate::Result<types::U256>;
+}
+
+/// The latest known state of a neighbor anchor, as recorded by the anchor
+/// it is linked to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnchorEdge {
+    /// The typed chain id of the neighbor anchor.
+    pub src_chain_id: u64,
+    /// The latest merkle root of the neighbor anchor.
+    pub root: types::H256,
+    /// The index of the latest leaf of the neighbor anchor behind that root.
+    pub latest_leaf_index: u32,
+    /// The block at which the edge got updated.
+    pub block_number: u64,
+}
+
+/// An Edge Store keeps the edges of each anchor, one per neighbor anchor.
+pub trait EdgeStore: Send + Sync + Clone {
+    /// Records the edge of the given anchor to the neighbor anchor of
+    /// `edge.src_chain_id`, unless a more recent edge is already stored.
+    fn insert_edge<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        edge: AnchorEdge,
+    ) -> crate::Result<()>;
+
+    /// Get the edges of the given anchor, sorted by the neighbor chain id.
+    fn get_edges<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<Vec<AnchorEdge>>;
+}
+
+/// Where a commitment got inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentLocation {
+    /// The typed chain id of the anchor.
+    pub chain_id: TypedChainId,
+    /// The address of the anchor, see [`HistoryStoreKey::address`].
+    pub contract: types::Address,
+    /// The index of the commitment in the anchor merkle tree.
+    pub leaf_index: u32,
+}
+
+impl CommitmentLocation {
+    /// The location encoded as the resource id of the anchor followed by
+    /// the big endian leaf index.
+    pub fn to_bytes(&self) -> [u8; 36] {
+        let target_system =
+            TargetSystem::new_contract_address(self.contract.to_fixed_bytes());
+        let resource_id = ResourceId::new(target_system, self.chain_id);
+        let mut bytes = [0u8; 36];
+        bytes[..32].copy_from_slice(&resource_id.to_bytes());
+        bytes[32..].copy_from_slice(&self.leaf_index.to_be_bytes());
+        bytes
+    }
+
+    /// Decodes a location encoded with [`CommitmentLocation::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 36 {
+            return None;
+        }
+        let mut resource_id = [0u8; 32];
+        resource_id.copy_from_slice(&bytes[..32]);
+        let key = HistoryStoreKey::from(ResourceId::from(resource_id));
+        let mut leaf_index = [0u8; 4];
+        leaf_index.copy_from_slice(&bytes[32..]);
+        Some(Self {
+            chain_id: ResourceId::from(resource_id).typed_chain_id(),
+            contract: key.address(),
+            leaf_index: u32::from_be_bytes(leaf_index),
+        })
+    }
+}
+
+/// A Commitment Index Store maps every cached commitment to the anchor and
+/// the chain it got inserted in.
+pub trait CommitmentIndexStore: Send + Sync + Clone {
+    /// Records where the given commitment got inserted.
+    fn index_commitment(
+        &self,
+        commitment: [u8; 32],
+        chain_id: TypedChainId,
+        contract: types::Address,
+        leaf_index: u32,
+    ) -> crate::Result<()>;
+
+    /// Looks up where the given commitment got inserted, if it is known.
+    fn lookup_commitment(
+        &self,
+        commitment: [u8; 32],
+    ) -> crate::Result<Option<CommitmentLocation>>;
+}
+
+/// A relay command the relayer holds until `execute_after`, see
+/// [`ScheduledRelayStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRelay {
+    /// When the command gets submitted, in secs since the UNIX epoch.
+    pub execute_after: u64,
+    /// The command to submit.
+    pub command: serde_json::Value,
+}
+
+/// A Scheduled Relay Store keeps the delayed relay commands until they get
+/// submitted, so that they survive restarts.
+pub trait ScheduledRelayStore: Send + Sync + Clone {
+    /// Schedules a relay under `key`.
+    ///
+    /// Returns false, and keeps the existing one, if a relay is already
+    /// scheduled under the same key.
+    fn schedule_relay(
+        &self,
+        key: &str,
+        relay: ScheduledRelay,
+    ) -> crate::Result<bool>;
+
+    /// Removes the relay scheduled under `key` and returns it.
+    ///
+    /// Both submitting and cancelling a relay take it first, so only one of
+    /// them could happen.
+    fn take_scheduled_relay(
+        &self,
+        key: &str,
+    ) -> crate::Result<Option<ScheduledRelay>>;
+
+    /// Get all the scheduled relays, with their keys.
+    fn get_scheduled_relays(
+        &self,
+    ) -> crate::Result<Vec<(String, ScheduledRelay)>>;
+}
+
+/// How long the fingerprint of an accepted relay command is kept, in secs,
+/// see [`RelayFingerprintStore`].
+pub const RELAY_FINGERPRINT_TTL_SECS: u64 = 5 * 60;
+
+/// A relay command the relayer accepted recently, recorded under its
+/// fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayFingerprint {
+    /// The key the relay is scheduled under, if it got delayed.
+    pub key: String,
+    /// When the fingerprint expires, in secs since the UNIX epoch.
+    pub expires_at: u64,
+    /// The hash of the relay transaction, once it got finalized.
+    pub tx_hash: Option<types::H256>,
+}
+
+/// A Relay Fingerprint Store keeps the fingerprints of the recently accepted
+/// relay commands, so that a withdrawal retried by its client is not
+/// submitted twice, even across restarts.
+pub trait RelayFingerprintStore: Send + Sync + Clone {
+    /// Records the fingerprint of an accepted relay command.
+    ///
+    /// Returns the already recorded one instead, and keeps it, unless it
+    /// expired at `now`.
+    fn record_relay_fingerprint(
+        &self,
+        fingerprint: [u8; 32],
+        record: RelayFingerprint,
+        now: u64,
+    ) -> crate::Result<Option<RelayFingerprint>>;
+
+    /// Sets the hash of the transaction of the relay with the given
+    /// fingerprint, if it is still recorded.
+    fn set_relay_fingerprint_tx(
+        &self,
+        fingerprint: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<()>;
+
+    /// Forgets the given fingerprint, such as when its relay failed, so that
+    /// it could be retried.
+    fn remove_relay_fingerprint(
+        &self,
+        fingerprint: [u8; 32],
+    ) -> crate::Result<()>;
+
+    /// Removes the fingerprints expired at `now`, returning how many got
+    /// removed.
+    fn prune_relay_fingerprints(&self, now: u64) -> crate::Result<usize>;
+}
+
+/// A transaction of the transaction queue that got sent, but whose receipt
+/// the relayer did not see yet, see [`InFlightTxStore`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InFlightTx {
+    /// The hash of the sent transaction.
+    pub tx_hash: types::H256,
+    /// The transaction as it got sent, with its sender and nonce.
+    pub tx: types::transaction::eip2718::TypedTransaction,
+}
+
+/// An In-Flight Tx Store keeps the transactions the transaction queue sent
+/// until their receipt is seen, so that the ones left in flight by a crash
+/// get reclaimed on restart.
+pub trait InFlightTxStore: Send + Sync + Clone {
+    /// Records a transaction that is about to be sent on the given chain.
+    fn record_in_flight_tx(
+        &self,
+        chain_id: u32,
+        tx: InFlightTx,
+    ) -> crate::Result<()>;
+
+    /// Forgets a transaction, once its receipt is seen or it got replaced.
+    fn remove_in_flight_tx(
+        &self,
+        chain_id: u32,
+        tx_hash: types::H256,
+    ) -> crate::Result<()>;
+
+    /// Get the transactions in flight on the given chain, at most `limit` of
+    /// them.
+    fn get_in_flight_txs(
+        &self,
+        chain_id: u32,
+        limit: usize,
+    ) -> crate::Result<Vec<InFlightTx>>;
+}
+
+/// The retries of a transaction of the transaction queue that reverted for a
+/// transient reason, see [`RevertRetryStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevertRetry {
+    /// How many times it got queued again after reverting.
+    pub retries: u32,
+    /// When it can be sent again, in seconds since the Unix epoch.
+    pub retry_at: u64,
+}
+
+/// A Revert Retry Store keeps the retries of the reverted transactions along
+/// with the queue they are queued again in, by the hash of their target and
+/// call data, so that their count and backoff survive a restart.
+pub trait RevertRetryStore: Send + Sync + Clone {
+    /// Get the retries of a transaction on the given chain, if it reverted.
+    fn get_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+    ) -> crate::Result<Option<RevertRetry>>;
+
+    /// Records a retry of a transaction, replacing the previous one.
+    fn record_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+        retry: RevertRetry,
+    ) -> crate::Result<()>;
+
+    /// Forgets the retries of a transaction, once it went through or got
+    /// dropped.
+    fn remove_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+    ) -> crate::Result<()>;
+}
+
+/// The number of proposal events kept in a [`ProposalEventStore`], for the
+/// subscribers resuming from a cursor.
+pub const MAX_PROPOSAL_EVENTS: u64 = 10_000;
+
+/// How long a call linked to a proposal event is kept, in secs, waiting to
+/// be executed, see [`ProposalEventStore::link_proposal_call`]; a call still
+/// not executed by then got dropped.
+pub const PROPOSAL_CALL_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// A state of the lifecycle of a proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProposalEventKind {
+    /// The proposal got created, and queued to be signed.
+    Created,
+    /// The vote for the proposal got queued to be sent to the DKG.
+    VoteQueued,
+    /// The vote for the proposal got included on the DKG.
+    VoteMined,
+    /// The proposal got signed, and may be executed.
+    Passed,
+    /// The execution of the signed proposal got queued.
+    ExecutionQueued,
+    /// The proposal got executed on its target chain.
+    Executed,
+    /// The proposal got replaced by one with a higher nonce before it was
+    /// signed.
+    Cancelled,
+    /// The merkle root of the proposal got too old before it was signed,
+    /// so it got replaced by one with the current root.
+    Expired,
+}
+
+/// A proposal that went through a new state of its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalEvent {
+    /// The new state of the proposal.
+    pub kind: ProposalEventKind,
+    /// The hash of the proposal data.
+    pub data_hash: types::H256,
+    /// The resource id of the proposal header.
+    pub resource_id: types::H256,
+    /// The nonce of the proposal header.
+    pub nonce: u32,
+    /// The underlying chain id of the anchor the proposal comes from, for
+    /// the anchor update proposals.
+    pub src_chain_id: Option<u32>,
+    /// The underlying chain id the proposal targets.
+    pub dest_chain_id: u32,
+    /// The contract the proposal targets, for the EVM chains.
+    pub target_address: Option<types::Address>,
+    /// The transaction that got the proposal into this state, if any.
+    pub tx_hash: Option<types::H256>,
+}
+
+/// The hash of the data of a proposal, which identifies it in the queues,
+/// the store and the proposal events.
+pub fn compute_proposal_hash(proposal_data: &[u8]) -> [u8; 32] {
+    utils::keccak256(proposal_data)
+}
+
+impl ProposalEvent {
+    /// Describes the proposal with the given data, which starts with the
+    /// proposal header.
+    ///
+    /// Returns `None` if the data is too short to hold a header.
+    pub fn new(kind: ProposalEventKind, data: &[u8]) -> Option<Self> {
+        Self::with_data_hash(kind, data, compute_proposal_hash(data))
+    }
+
+    /// Same as [`ProposalEvent::new`], with the hash of the data already
+    /// computed by [`compute_proposal_hash`].
+    pub fn with_data_hash(
+        kind: ProposalEventKind,
+        data: &[u8],
+        data_hash: [u8; 32],
+    ) -> Option<Self> {
+        // the header is the resource id, the function signature and the
+        // nonce.
+        let header = data.get(..40)?;
+        let mut resource_id = [0u8; 32];
+        resource_id.copy_from_slice(&header[..32]);
+        let mut nonce = [0u8; 4];
+        nonce.copy_from_slice(&header[36..40]);
+        let target = ResourceId::from(resource_id);
+        let target_address = match target.target_system() {
+            TargetSystem::ContractAddress(address) => {
+                Some(types::Address::from(address))
+            }
+            TargetSystem::Substrate(_) => None,
+        };
+        // an anchor update proposal ends with the source resource id.
+        let src_chain_id = (data.len() == 40 + 32 + 32).then(|| {
+            let mut src_resource_id = [0u8; 32];
+            src_resource_id.copy_from_slice(&data[data.len() - 32..]);
+            ResourceId::from(src_resource_id)
+                .typed_chain_id()
+                .underlying_chain_id()
+        });
+        Some(Self {
+            kind,
+            data_hash: data_hash.into(),
+            resource_id: resource_id.into(),
+            nonce: u32::from_be_bytes(nonce),
+            src_chain_id,
+            dest_chain_id: target.typed_chain_id().underlying_chain_id(),
+            target_address,
+            tx_hash: None,
+        })
+    }
+
+    /// The same proposal, in the state `kind` that `tx_hash` got it into.
+    pub fn with_tx(
+        self,
+        kind: ProposalEventKind,
+        tx_hash: Option<types::H256>,
+    ) -> Self {
+        Self {
+            kind,
+            tx_hash,
+            ..self
+        }
+    }
+}
+
+/// A [`ProposalEvent`], as recorded by a [`ProposalEventStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalEventRecord {
+    /// The sequence number of the event, which only ever increases.
+    pub seq: u64,
+    /// The time at which the event got recorded, in secs since the UNIX
+    /// epoch.
+    pub timestamp: u64,
+    /// The event.
+    #[serde(flatten)]
+    pub event: ProposalEvent,
+}
+
+/// A Proposal Event Store records the lifecycle of the proposals, and
+/// broadcasts it to the live subscribers.
+///
+/// The last [`MAX_PROPOSAL_EVENTS`] events are kept, so a subscriber could
+/// resume after the sequence number of the last event it got.
+pub trait ProposalEventStore: Send + Sync + Clone {
+    /// Records the event, with the next sequence number, and broadcasts
+    /// it.
+    fn record_proposal_event(
+        &self,
+        event: ProposalEvent,
+    ) -> crate::Result<ProposalEventRecord>;
+
+    /// Records `event`, with the hash of the transaction, once the call
+    /// with the given call data hash is executed by a transaction queue.
+    fn link_proposal_call(
+        &self,
+        call_data_hash: [u8; 32],
+        event: ProposalEvent,
+    ) -> crate::Result<()>;
+
+    /// Forgets the calls linked more than [`PROPOSAL_CALL_TTL_SECS`] before
+    /// `now`, in secs since the UNIX epoch, and returns how many.
+    fn prune_proposal_calls(&self, now: u64) -> crate::Result<usize>;
+
+    /// Get up to `limit` of the events recorded after the sequence number
+    /// `cursor`, oldest first.
+    fn get_proposal_events_after(
+        &self,
+        cursor: u64,
+        limit: usize,
+    ) -> crate::Result<Vec<ProposalEventRecord>>;
+
+    /// The sequence number of the last recorded event, `0` if there is
+    /// none.
+    fn last_proposal_event_seq(&self) -> crate::Result<u64>;
+
+    /// Every proposal with a recorded event, along with the states it went
+    /// through, for audits.
+    ///
+    /// Only the last [`MAX_PROPOSAL_EVENTS`] events are kept, so the states
+    /// of the oldest proposals may be missing.
+    fn export_proposals(&self) -> crate::Result<Vec<ProposalAudit>> {
+        let records =
+            self.get_proposal_events_after(0, MAX_PROPOSAL_EVENTS as usize)?;
+        Ok(ProposalAudit::from_events(records))
+    }
+
+    /// Subscribes to the events recorded from now on.
+    ///
+    /// A subscriber that falls too far behind misses events, and gets told
+    /// how many instead; it never holds up the recording.
+    fn subscribe_proposal_events(
+        &self,
+    ) -> tokio::sync::broadcast::Receiver<ProposalEventRecord>;
+
+    /// Records that the proposal with the given data, and its
+    /// `data_hash`, went through `kind`, by the transaction `tx_hash` if
+    /// any.
+    ///
+    /// The failures are only logged, so they never hold up the proposal.
+    fn record_proposal(
+        &self,
+        kind: ProposalEventKind,
+        data: &[u8],
+        data_hash: [u8; 32],
+        tx_hash: Option<types::H256>,
+    ) {
+        let Some(event) = ProposalEvent::with_data_hash(kind, data, data_hash)
+        else {
+            return;
+        };
+        let event = event.with_tx(kind, tx_hash);
+        if let Err(e) = self.record_proposal_event(event) {
+            tracing::warn!("Failed to record the proposal event: {e}");
+        }
+    }
+
+    /// Records that the proposal with the given data, and its
+    /// `data_hash`, went through `kind`, once the call with the given call
+    /// data hash gets executed.
+    ///
+    /// The failures are only logged, so they never hold up the proposal.
+    fn link_proposal(
+        &self,
+        call_data_hash: [u8; 32],
+        kind: ProposalEventKind,
+        data: &[u8],
+        data_hash: [u8; 32],
+    ) {
+        let Some(event) = ProposalEvent::with_data_hash(kind, data, data_hash)
+        else {
+            return;
+        };
+        if let Err(e) = self.link_proposal_call(call_data_hash, event) {
+            tracing::warn!("Failed to link the proposal event: {e}");
+        }
+    }
+}
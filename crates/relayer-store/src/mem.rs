@@ -22,20 +22,27 @@ use webb::evm::ethers::types;
 use crate::TokenPriceCacheStore;
 
 use super::{
-    EncryptedOutputCacheStore, HistoryStore, HistoryStoreKey, LeafCacheStore,
+    DuplicateCommitment, EncryptedOutputCacheStore, HistoryStore,
+    HistoryStoreKey, LeafCacheStore, LeafInsertion,
 };
 
 type MemStore = HashMap<HistoryStoreKey, Vec<types::H256>>;
 type MemStoreForVec = HashMap<HistoryStoreKey, Vec<Vec<u8>>>;
 type MemStoreForMap = HashMap<HistoryStoreKey, BTreeMap<u32, types::H256>>;
+type MemStoreForBlocks = HashMap<HistoryStoreKey, BTreeMap<u32, u64>>;
+type MemStoreForPending =
+    HashMap<HistoryStoreKey, BTreeMap<u32, (types::H256, u64)>>;
 /// InMemoryStore is a store that stores the history of events in memory.
 #[derive(Clone, Default)]
 pub struct InMemoryStore {
     _store: Arc<RwLock<MemStore>>,
     leaf_store: Arc<RwLock<MemStoreForMap>>,
+    leaf_block_numbers: Arc<RwLock<MemStoreForBlocks>>,
+    pending_leaves: Arc<RwLock<MemStoreForPending>>,
     encrypted_output_store: Arc<RwLock<MemStoreForVec>>,
     last_block_numbers: Arc<RwLock<HashMap<HistoryStoreKey, u64>>>,
     target_block_numbers: Arc<RwLock<HashMap<HistoryStoreKey, u64>>>,
+    last_synced_at: Arc<RwLock<HashMap<HistoryStoreKey, u64>>>,
     last_deposit_block_numbers: Arc<RwLock<HashMap<HistoryStoreKey, u64>>>,
     encrypted_output_last_deposit_block_numbers:
         Arc<RwLock<HashMap<HistoryStoreKey, u64>>>,
@@ -109,6 +116,24 @@ impl HistoryStore for InMemoryStore {
             .unwrap_or(default_block_number);
         Ok(val)
     }
+
+    #[tracing::instrument(skip(self))]
+    fn set_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        timestamp: u64,
+    ) -> crate::Result<()> {
+        self.last_synced_at.write().insert(key.into(), timestamp);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<Option<u64>> {
+        Ok(self.last_synced_at.read().get(&key.into()).copied())
+    }
 }
 
 impl LeafCacheStore for InMemoryStore {
@@ -121,6 +146,8 @@ impl LeafCacheStore for InMemoryStore {
     ) -> crate::Result<()> {
         let mut guard = self.leaf_store.write();
         guard.clear();
+        self.leaf_block_numbers.write().clear();
+        self.pending_leaves.write().clear();
         Ok(())
     }
 
@@ -148,6 +175,20 @@ impl LeafCacheStore for InMemoryStore {
         Ok(iter.collect())
     }
 
+    #[tracing::instrument(skip(self))]
+    fn get_leaf_block_numbers<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        range: core::ops::Range<u32>,
+    ) -> crate::Result<BTreeMap<u32, u64>> {
+        let guard = self.leaf_block_numbers.read();
+        let val = guard
+            .get(&key.into())
+            .map(|blocks| blocks.range(range).map(|(k, v)| (*k, *v)).collect())
+            .unwrap_or_default();
+        Ok(val)
+    }
+
     #[tracing::instrument(skip(self))]
     fn get_last_deposit_block_number<K: Into<HistoryStoreKey> + Debug>(
         &self,
@@ -163,42 +204,156 @@ impl LeafCacheStore for InMemoryStore {
     }
 
     #[tracing::instrument(skip(self))]
-    fn insert_leaves_and_last_deposit_block_number<
+    fn get_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<BTreeMap<u32, (types::H256, u64)>> {
+        let guard = self.pending_leaves.read();
+        let val = guard.get(&key.into()).cloned().unwrap_or_default();
+        Ok(val)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn remove_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<()> {
+        self.pending_leaves.write().remove(&key.into());
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn next_leaf_index<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<u32> {
+        let guard = self.leaf_store.read();
+        let next_index = guard.get(&key.into()).map_or(0, |leaves| {
+            leaves
+                .keys()
+                .zip(0u32..)
+                .take_while(|(index, expected)| **index == *expected)
+                .count()
+        });
+        Ok(next_index as u32)
+    }
+
+    #[tracing::instrument(skip(self, leaves))]
+    fn insert_leaves_with_max_pending<
         K: Into<HistoryStoreKey> + Debug + Clone,
     >(
         &self,
         key: K,
         leaves: &[(u32, Vec<u8>)],
         block_number: u64,
-    ) -> crate::Result<()> {
+        max_pending: u32,
+    ) -> crate::Result<LeafInsertion> {
+        let key: HistoryStoreKey = key.into();
         let mut guard1 = self.leaf_store.write();
         let mut guard2 = self.last_deposit_block_numbers.write();
         let mut guard3 = self.last_block_numbers.write();
-        {
-            // 1. Insert leaves
-            guard1
-                .entry(key.clone().into())
-                .and_modify(|v| {
-                    for (index, leaf) in leaves {
-                        v.insert(*index, types::H256::from_slice(leaf));
+        let mut guard4 = self.leaf_block_numbers.write();
+        let mut guard5 = self.pending_leaves.write();
+        // work on copies, so nothing changes if the insertion fails.
+        let mut cached = guard1.get(&key).cloned().unwrap_or_default();
+        let mut blocks = guard4.get(&key).cloned().unwrap_or_default();
+        let mut pending = guard5.get(&key).cloned().unwrap_or_default();
+        let mut next = cached
+            .keys()
+            .zip(0u32..)
+            .take_while(|(index, expected)| **index == *expected)
+            .count() as u32;
+        let mut outcome = LeafInsertion::default();
+        let mut leaves = leaves.to_vec();
+        leaves.sort_by_key(|(index, _)| *index);
+        for (index, leaf) in leaves {
+            let leaf = types::H256::from_slice(&leaf);
+            if let Some(cached) = cached.get(&index) {
+                if *cached != leaf {
+                    return Err(
+                        webb_relayer_utils::Error::LeafCacheCorrupted {
+                            index,
+                            cached: hex::encode(cached),
+                            got: hex::encode(leaf),
+                        },
+                    );
+                }
+                continue;
+            }
+            if index > next {
+                // the root of a pending leaf can not be checked yet, so the
+                // one already there is kept.
+                if let Some((buffered, _)) = pending.get(&index) {
+                    if *buffered != leaf {
+                        return Err(
+                            webb_relayer_utils::Error::LeafCacheCorrupted {
+                                index,
+                                cached: hex::encode(buffered),
+                                got: hex::encode(leaf),
+                            },
+                        );
                     }
-                })
-                .or_insert_with(|| {
-                    let mut map = BTreeMap::new();
-                    for (index, leaf) in leaves {
-                        map.insert(*index, types::H256::from_slice(leaf));
-                    }
-                    map
-                });
-            // 2. Insert last deposit block number
-            guard2.insert(key.clone().into(), block_number);
-            // 3. Insert last block number
-            guard3.entry(key.into()).or_insert(block_number);
+                    continue;
+                }
+                pending.insert(index, (leaf, block_number));
+                outcome.pending.push(index);
+                continue;
+            }
+            outcome
+                .duplicates
+                .extend(find_duplicate(&cached, index, leaf));
+            cached.insert(index, leaf);
+            blocks.insert(index, block_number);
+            outcome.inserted.insert(index, leaf);
+            next = index + 1;
+            // skip the leaves we already have, and flush the pending ones
+            // that are not ahead anymore.
+            loop {
+                if cached.contains_key(&next) {
+                    next += 1;
+                    continue;
+                }
+                let Some((leaf, block)) = pending.remove(&next) else {
+                    break;
+                };
+                outcome
+                    .duplicates
+                    .extend(find_duplicate(&cached, next, leaf));
+                cached.insert(next, leaf);
+                blocks.insert(next, block);
+                outcome.inserted.insert(next, leaf);
+                outcome.pending.retain(|i| *i != next);
+                next += 1;
+            }
         }
-        Ok(())
+        // the leaves are kept, the missing ones get fetched again.
+        if pending.len() > max_pending as usize {
+            outcome.missing_from = Some(next);
+        }
+        guard1.insert(key, cached);
+        guard4.insert(key, blocks);
+        guard5.insert(key, pending);
+        guard2.insert(key, block_number);
+        guard3.entry(key).or_insert(block_number);
+        Ok(outcome)
     }
 }
 
+/// The commitment of the leaf, if it is already cached at another index.
+fn find_duplicate(
+    cached: &BTreeMap<u32, types::H256>,
+    index: u32,
+    leaf: types::H256,
+) -> Option<DuplicateCommitment> {
+    cached.iter().find(|(_, cached)| **cached == leaf).map(
+        |(cached_index, _)| DuplicateCommitment {
+            commitment: leaf,
+            cached_index: *cached_index,
+            index,
+        },
+    )
+}
+
 impl EncryptedOutputCacheStore for InMemoryStore {
     type Output = Vec<Vec<u8>>;
 
@@ -0,0 +1,155 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use webb::evm::ethers::types;
+
+use crate::{ProposalEventKind, ProposalEventRecord};
+
+/// A state a proposal went through, as recorded by a
+/// [`ProposalEventStore`](crate::ProposalEventStore).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalStatusChange {
+    /// The sequence number of the proposal event.
+    pub seq: u64,
+    /// The time at which the state got recorded, in secs since the UNIX
+    /// epoch.
+    pub timestamp: u64,
+    /// The state.
+    pub kind: ProposalEventKind,
+    /// The transaction that got the proposal into the state, if any.
+    pub tx_hash: Option<types::H256>,
+}
+
+/// A proposal, with the states it went through, as exported for audits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalAudit {
+    /// The hash of the proposal data.
+    pub data_hash: types::H256,
+    /// The resource id of the proposal header.
+    pub resource_id: types::H256,
+    /// The nonce of the proposal header.
+    pub nonce: u32,
+    /// The underlying chain id of the anchor the proposal comes from, for
+    /// the anchor update proposals.
+    pub src_chain_id: Option<u32>,
+    /// The underlying chain id the proposal targets.
+    pub dest_chain_id: u32,
+    /// The contract the proposal targets, for the EVM chains.
+    pub target_address: Option<types::Address>,
+    /// The latest state of the proposal.
+    pub status: ProposalEventKind,
+    /// The states the proposal went through, oldest first.
+    pub history: Vec<ProposalStatusChange>,
+}
+
+impl ProposalAudit {
+    /// Groups the proposal events by proposal, in the order of their first
+    /// event.
+    pub fn from_events(
+        records: impl IntoIterator<Item = ProposalEventRecord>,
+    ) -> Vec<Self> {
+        let mut proposals: Vec<Self> = Vec::new();
+        let mut positions = HashMap::new();
+        for record in records {
+            let event = record.event;
+            let change = ProposalStatusChange {
+                seq: record.seq,
+                timestamp: record.timestamp,
+                kind: event.kind,
+                tx_hash: event.tx_hash,
+            };
+            match positions.get(&event.data_hash) {
+                Some(&position) => {
+                    let proposal = &mut proposals[position];
+                    proposal.status = event.kind;
+                    proposal.history.push(change);
+                }
+                None => {
+                    positions.insert(event.data_hash, proposals.len());
+                    proposals.push(Self {
+                        data_hash: event.data_hash,
+                        resource_id: event.resource_id,
+                        nonce: event.nonce,
+                        src_chain_id: event.src_chain_id,
+                        dest_chain_id: event.dest_chain_id,
+                        target_address: event.target_address,
+                        status: event.kind,
+                        history: vec![change],
+                    });
+                }
+            }
+        }
+        proposals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProposalEvent;
+
+    fn record(
+        seq: u64,
+        kind: ProposalEventKind,
+        hash: u8,
+    ) -> ProposalEventRecord {
+        ProposalEventRecord {
+            seq,
+            timestamp: 1_000 + seq,
+            event: ProposalEvent {
+                kind,
+                data_hash: types::H256::repeat_byte(hash),
+                resource_id: types::H256::repeat_byte(0xff),
+                nonce: hash.into(),
+                src_chain_id: Some(1),
+                dest_chain_id: 5,
+                target_address: None,
+                tx_hash: None,
+            },
+        }
+    }
+
+    #[test]
+    fn proposal_events_should_be_grouped_by_proposal() {
+        let proposals = ProposalAudit::from_events([
+            record(1, ProposalEventKind::Created, 1),
+            record(2, ProposalEventKind::Created, 2),
+            record(3, ProposalEventKind::Passed, 1),
+            record(4, ProposalEventKind::Cancelled, 2),
+            record(5, ProposalEventKind::Executed, 1),
+        ]);
+        assert_eq!(proposals.len(), 2);
+        assert_eq!(proposals[0].data_hash, types::H256::repeat_byte(1));
+        assert_eq!(proposals[0].status, ProposalEventKind::Executed);
+        let seqs: Vec<_> = proposals[0]
+            .history
+            .iter()
+            .map(|change| change.seq)
+            .collect();
+        assert_eq!(seqs, vec![1, 3, 5]);
+        assert_eq!(proposals[1].nonce, 2);
+        assert_eq!(proposals[1].status, ProposalEventKind::Cancelled);
+        assert_eq!(proposals[1].history.len(), 2);
+
+        let json = serde_json::to_value(&proposals[1]).unwrap();
+        assert_eq!(json["status"], "cancelled");
+        assert_eq!(json["history"][0]["kind"], "created");
+        assert_eq!(json["history"][1]["timestamp"], 1_004);
+    }
+}
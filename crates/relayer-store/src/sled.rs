@@ -14,22 +14,74 @@
 
 use super::HistoryStoreKey;
 use super::{
-    EncryptedOutputCacheStore, EventHashStore, HistoryStore, LeafCacheStore,
-    QueueStore, TokenPriceCacheStore,
+    AmountReservation, AnchorEdge, CommitmentIndexStore, CommitmentLocation,
+    DepositCorrelationId, DepositPropagationRecord, DepositPropagationStore,
+    DestinationPropagation, DuplicateCommitment, EdgeStore,
+    EncryptedOutputCacheStore, EventHashStore, FeeAccountingStore, FeeEarnings,
+    HistoryStore, InFlightTx, InFlightTxStore, LeafCacheStore, LeafInsertion,
+    ProposalEvent, ProposalEventKind, ProposalEventRecord, ProposalEventStore,
+    ProviderUsage, ProviderUsageStore, QueueStore, RelayFingerprint,
+    RelayFingerprintStore, RelayedAmount, RelayedWithdrawal,
+    RelayedWithdrawalStore, RevertRetry, RevertRetryStore, ScheduledRelay,
+    ScheduledRelayStore, TokenPriceCacheStore, WithdrawalLimitStore,
+    MAX_PROPOSAL_EVENTS, MAX_RELAYED_WITHDRAWALS, PROPOSAL_CALL_TTL_SECS,
+};
+use crate::{
+    BridgeKey, CachedLeaf, ContractLeafCache, LeafCacheDump, QueueKey,
 };
-use crate::{BridgeKey, QueueKey};
 use core::fmt;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sled::transaction::{TransactionError, UnabortableTransactionError};
 use sled::Transactional;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 use webb::evm::ethers::{self, types};
+use webb_proposals::TypedChainId;
+use webb_relayer_utils::Error;
+/// Store-level statistics of a [`SledStore`], see [`SledStore::export_metrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoreMetrics {
+    /// The number of anchor update proposals tracked by the store.
+    pub proposal_count: u64,
+    /// The number of items waiting in the queues of each chain.
+    pub queue_depth_by_chain: HashMap<u32, u64>,
+    /// The number of cached leaves of each contract.
+    pub leaf_count_by_contract: HashMap<(TypedChainId, types::Address), u64>,
+    /// The total amount of data stored on disk.
+    pub db_size_bytes: u64,
+    /// The number of bytes that were buffered in memory and not yet
+    /// persisted to disk when the metrics were exported.
+    pub pending_writes: u64,
+}
+
 /// SledStore is a store that stores the history of events in  a [Sled](https://sled.rs)-based database.
 #[derive(Clone)]
 pub struct SledStore {
     db: sled::Db,
+    /// Broadcasts the recorded proposal events, the lock keeps their
+    /// sequence numbers in the order they are sent.
+    proposal_events:
+        Arc<parking_lot::Mutex<broadcast::Sender<ProposalEventRecord>>>,
+}
+
+/// How many proposal events a live subscriber could fall behind, before it
+/// misses some.
+const PROPOSAL_EVENTS_CAPACITY: usize = 1024;
+
+/// A proposal event waiting for its call to be executed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LinkedProposalCall {
+    /// When the call got linked, in secs since the UNIX epoch, `0` for the
+    /// calls linked before it was recorded.
+    #[serde(default)]
+    linked_at: u64,
+    #[serde(flatten)]
+    event: ProposalEvent,
 }
 
 impl std::fmt::Debug for SledStore {
@@ -46,7 +98,11 @@ impl SledStore {
             .temporary(cfg!(test))
             .mode(sled::Mode::HighThroughput)
             .open()?;
-        Ok(Self { db })
+        let (proposal_events, _) = broadcast::channel(PROPOSAL_EVENTS_CAPACITY);
+        Ok(Self {
+            db,
+            proposal_events: Arc::new(parking_lot::Mutex::new(proposal_events)),
+        })
     }
     /// Creates a temporary SledStore.
     pub fn temporary() -> crate::Result<Self> {
@@ -58,6 +114,197 @@ impl SledStore {
     pub fn get_data_stored_size(&self) -> u64 {
         self.db.size_on_disk().unwrap_or_default()
     }
+
+    /// Writes all the dirty data to disk, returning how many bytes were
+    /// flushed.
+    pub fn flush(&self) -> crate::Result<usize> {
+        let flushed = self.db.flush()?;
+        Ok(flushed)
+    }
+
+    /// Exports store-level statistics by going over the known trees.
+    ///
+    /// **Note**: this flushes the database, to find out how much data
+    /// was pending to be written.
+    pub fn export_metrics(&self) -> StoreMetrics {
+        let mut metrics = StoreMetrics {
+            db_size_bytes: self.get_data_stored_size(),
+            ..Default::default()
+        };
+        metrics.proposal_count = self
+            .db
+            .open_tree("deposit_propagation_by_proposal")
+            .map(|tree| tree.len() as u64)
+            .unwrap_or_default();
+        // queues are named `queue_<kind>_<chain_id>`.
+        for name in self.db.tree_names() {
+            let name = String::from_utf8_lossy(&name);
+            let queue_name = match name.strip_prefix("queue_") {
+                Some(v) => v,
+                None => continue,
+            };
+            let chain_id = match queue_name
+                .rsplit('_')
+                .next()
+                .and_then(|v| v.parse::<u32>().ok())
+            {
+                Some(v) => v,
+                None => continue,
+            };
+            let tree = match self.db.open_tree(name.as_bytes()) {
+                Ok(tree) => tree,
+                Err(_) => continue,
+            };
+            let prefix = tree
+                .get("key_prefix")
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| b"item".into());
+            let depth = tree.scan_prefix(prefix).count() as u64;
+            *metrics.queue_depth_by_chain.entry(chain_id).or_default() += depth;
+        }
+        // every contract with cached leaves has a last deposit block number.
+        let contracts = self
+            .db
+            .open_tree("last_deposit_block_number")
+            .map(|tree| tree.iter().keys().flatten().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for key in contracts {
+            if key.len() != 32 {
+                continue;
+            }
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&key);
+            let resource_id = webb_proposals::ResourceId::from(bytes);
+            let key = HistoryStoreKey::from(resource_id);
+            let leaf_count = self
+                .db
+                .open_tree(format!(
+                    "leaves/{}/{}",
+                    key.chain_id(),
+                    key.address()
+                ))
+                .map(|tree| tree.len() as u64)
+                .unwrap_or_default();
+            metrics.leaf_count_by_contract.insert(
+                (resource_id.typed_chain_id(), key.address()),
+                leaf_count,
+            );
+        }
+        metrics.pending_writes =
+            self.db.flush().map(|v| v as u64).unwrap_or_default();
+        metrics
+    }
+
+    /// Dumps the cached leaves of every contract, along with the blocks
+    /// their events watchers went through.
+    ///
+    /// The pending leaves are left out, they are fetched again by the
+    /// relayer importing the dump.
+    pub fn export_leaf_cache(&self) -> crate::Result<LeafCacheDump> {
+        // every contract with cached leaves has a last deposit block number.
+        let keys = self
+            .db
+            .open_tree("last_deposit_block_number")?
+            .iter()
+            .keys()
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut contracts = Vec::new();
+        for key in keys {
+            let Ok(bytes) = <[u8; 32]>::try_from(key.as_ref()) else {
+                continue;
+            };
+            let resource_id = webb_proposals::ResourceId::from(bytes);
+            let leaves = self.get_leaves(resource_id)?;
+            if leaves.is_empty() {
+                continue;
+            }
+            let last_deposit_block =
+                self.get_last_deposit_block_number(resource_id)?;
+            let block_numbers =
+                self.get_leaf_block_numbers(resource_id, 0..u32::MAX)?;
+            let leaves = leaves
+                .into_iter()
+                .map(|(index, leaf)| CachedLeaf {
+                    index,
+                    leaf,
+                    // the leaves cached before their block got tracked.
+                    block_number: block_numbers
+                        .get(&index)
+                        .copied()
+                        .unwrap_or(last_deposit_block),
+                })
+                .collect();
+            contracts.push(ContractLeafCache {
+                resource_id,
+                last_block: self.get_last_block_number(resource_id, 0)?,
+                last_deposit_block,
+                leaves,
+            });
+        }
+        Ok(LeafCacheDump { contracts })
+    }
+
+    /// Imports a dump made by [`SledStore::export_leaf_cache`].
+    ///
+    /// The whole dump is checked against the leaves already cached before
+    /// anything gets written, so a dump that does not match the cache is
+    /// rejected with `LeafCacheCorrupted` without importing any of it.
+    pub fn import_leaf_cache(&self, dump: &LeafCacheDump) -> crate::Result<()> {
+        dump.validate()?;
+        for contract in &dump.contracts {
+            let cached = self.get_leaves_with_range(
+                contract.resource_id,
+                0..contract.leaves.len() as u32,
+            )?;
+            for (index, cached) in cached {
+                let got = contract.leaves[index as usize].leaf;
+                if cached != got {
+                    return Err(Error::LeafCacheCorrupted {
+                        index,
+                        cached: hex::encode(cached),
+                        got: hex::encode(got),
+                    });
+                }
+            }
+        }
+        for contract in &dump.contracts {
+            let key = contract.resource_id;
+            // the leaves are inserted block by block, to keep the block
+            // each of them got inserted in.
+            let mut start = 0;
+            while start < contract.leaves.len() {
+                let block_number = contract.leaves[start].block_number;
+                let end = contract.leaves[start..]
+                    .iter()
+                    .position(|leaf| leaf.block_number != block_number)
+                    .map_or(contract.leaves.len(), |len| start + len);
+                let leaves = contract.leaves[start..end]
+                    .iter()
+                    .map(|leaf| (leaf.index, leaf.leaf.as_bytes().to_vec()))
+                    .collect::<Vec<_>>();
+                self.insert_leaves_and_last_deposit_block_number(
+                    key,
+                    &leaves,
+                    block_number,
+                )?;
+                start = end;
+            }
+            // the insertions moved both block numbers to the block of the
+            // last leaf, and the cache may already be further.
+            let last_deposit_block = self
+                .get_last_deposit_block_number(key)?
+                .max(contract.last_deposit_block);
+            self.db.open_tree("last_deposit_block_number")?.insert(
+                HistoryStoreKey::from(key).to_bytes(),
+                &last_deposit_block.to_le_bytes(),
+            )?;
+            let last_block =
+                self.get_last_block_number(key, 0)?.max(contract.last_block);
+            self.set_last_block_number(key, last_block)?;
+        }
+        Ok(())
+    }
 }
 
 impl HistoryStore for SledStore {
@@ -138,6 +385,33 @@ impl HistoryStore for SledStore {
             None => Ok(default_block_number),
         }
     }
+
+    #[tracing::instrument(skip(self))]
+    fn set_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        timestamp: u64,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("last_synced_at")?;
+        let key: HistoryStoreKey = key.into();
+        tree.insert(key.to_bytes(), &timestamp.to_le_bytes())?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_last_synced_at<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<Option<u64>> {
+        let tree = self.db.open_tree("last_synced_at")?;
+        let key: HistoryStoreKey = key.into();
+        let val = tree.get(key.to_bytes())?;
+        Ok(val.map(|v| {
+            let mut output = [0u8; 8];
+            output.copy_from_slice(&v);
+            u64::from_le_bytes(output)
+        }))
+    }
 }
 
 impl LeafCacheStore for SledStore {
@@ -154,6 +428,24 @@ impl LeafCacheStore for SledStore {
             key.chain_id(),
             key.address()
         ))?;
+        self.db.drop_tree(format!(
+            "leaf_block_numbers/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        self.db.drop_tree(format!(
+            "pending_leaves/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        self.db.drop_tree(format!(
+            "leaf_commitment_indices/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        self.db
+            .open_tree("next_leaf_indices")?
+            .remove(key.to_bytes())?;
         Ok(())
     }
 
@@ -214,6 +506,33 @@ impl LeafCacheStore for SledStore {
         Ok(leaves)
     }
 
+    #[tracing::instrument(skip(self))]
+    fn get_leaf_block_numbers<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        range: core::ops::Range<u32>,
+    ) -> crate::Result<BTreeMap<u32, u64>> {
+        let key: HistoryStoreKey = key.into();
+        let tree = self.db.open_tree(format!(
+            "leaf_block_numbers/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        tree.range(range.start.to_be_bytes()..range.end.to_be_bytes())
+            .map(|entry| {
+                let (k, v) = entry?;
+                let mut index = [0u8; 4];
+                index.copy_from_slice(&k);
+                let mut block_number = [0u8; 8];
+                block_number.copy_from_slice(&v);
+                Ok((
+                    u32::from_be_bytes(index),
+                    u64::from_be_bytes(block_number),
+                ))
+            })
+            .collect()
+    }
+
     #[tracing::instrument(skip(self))]
     fn get_last_deposit_block_number<K: Into<HistoryStoreKey> + Debug>(
         &self,
@@ -233,41 +552,379 @@ impl LeafCacheStore for SledStore {
     }
 
     #[tracing::instrument(skip(self))]
-    fn insert_leaves_and_last_deposit_block_number<
-        K: Into<HistoryStoreKey> + Debug,
-    >(
+    fn get_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<BTreeMap<u32, (types::H256, u64)>> {
+        let key: HistoryStoreKey = key.into();
+        let tree = self.db.open_tree(format!(
+            "pending_leaves/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        tree.iter()
+            .map(|entry| {
+                let (k, v) = entry?;
+                let (index, leaf, block) = decode_pending_leaf(&k, &v);
+                Ok((index, (leaf, block)))
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn remove_pending_leaves<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<()> {
+        let key: HistoryStoreKey = key.into();
+        self.db.drop_tree(format!(
+            "pending_leaves/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn next_leaf_index<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<u32> {
+        let key: HistoryStoreKey = key.into();
+        let tree = self.db.open_tree("next_leaf_indices")?;
+        if let Some(v) = tree.get(key.to_bytes())? {
+            let mut index = [0u8; 4];
+            index.copy_from_slice(&v);
+            return Ok(u32::from_be_bytes(index));
+        }
+        // the leaves cached before the next index got tracked.
+        let next_index = self
+            .get_leaves(key)?
+            .into_keys()
+            .zip(0u32..)
+            .take_while(|(index, expected)| index == expected)
+            .count();
+        Ok(next_index as u32)
+    }
+
+    #[tracing::instrument(skip(self, leaves))]
+    fn insert_leaves_with_max_pending<K: Into<HistoryStoreKey> + Debug>(
         &self,
         key: K,
         leaves: &[(u32, Vec<u8>)],
         block_number: u64,
-    ) -> crate::Result<()> {
+        max_pending: u32,
+    ) -> crate::Result<LeafInsertion> {
         let key: HistoryStoreKey = key.into();
+        self.backfill_commitment_indices(key)?;
+        let next_index = self.next_leaf_index(key)?;
 
         let leaf_tree = self.db.open_tree(format!(
             "leaves/{}/{}",
             key.chain_id(),
             key.address()
         ))?;
+        // The leaves that are ahead of the cache, keyed by the big endian
+        // leaf index, with the block they got inserted at.
+        let pending_tree = self.db.open_tree(format!(
+            "pending_leaves/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        let next_index_tree = self.db.open_tree("next_leaf_indices")?;
         // This is last deposit event block number
         let set_block_tree1 = self.db.open_tree("last_deposit_block_number")?;
         // This will be used by event watcher to track the block number has been processed
         let set_block_tree2 = self.db.open_tree("last_block_numbers")?;
+        // The block in which each leaf got inserted, keyed by the big endian
+        // leaf index so it could be queried by range.
+        let leaf_block_tree = self.db.open_tree(format!(
+            "leaf_block_numbers/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        let commitments_tree = self.db.open_tree("commitments")?;
+        // The index each commitment got cached at first, to catch the ones
+        // cached twice.
+        let commitment_index_tree = self.db.open_tree(format!(
+            "leaf_commitment_indices/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
         let block_number_bytes = block_number.to_le_bytes();
+        let block_number_be_bytes = block_number.to_be_bytes();
+        // only the anchors are indexed, not the per chain keys.
+        let resource_id = match key {
+            HistoryStoreKey::ResourceId { resource_id } => Some(resource_id),
+            HistoryStoreKey::Block { .. } => None,
+        };
+        let pending_before = pending_tree.len();
+        let mut leaves = leaves.to_vec();
+        leaves.sort_by_key(|(index, _)| *index);
 
-        (&leaf_tree, &set_block_tree1, &set_block_tree2).transaction(
-            |(leaf_tree, set_block_tree1, set_block_tree2)| {
-                for (k, v) in leaves {
-                    leaf_tree.insert(&k.to_le_bytes(), v.as_slice())?;
+        let result = (
+            &leaf_tree,
+            &pending_tree,
+            &next_index_tree,
+            &set_block_tree1,
+            &set_block_tree2,
+            &leaf_block_tree,
+            &commitments_tree,
+            &commitment_index_tree,
+        )
+            .transaction(
+                |(
+                    leaf_tree,
+                    pending_tree,
+                    next_index_tree,
+                    set_block_tree1,
+                    set_block_tree2,
+                    leaf_block_tree,
+                    commitments_tree,
+                    commitment_index_tree,
+                )| {
+                    let mut outcome = LeafInsertion::default();
+                    let mut next = next_index;
+                    let mut pending = pending_before;
+                    let insert_leaf = |index: u32, leaf: &[u8], block: u64| {
+                        leaf_tree.insert(&index.to_le_bytes(), leaf)?;
+                        let block = block.to_be_bytes();
+                        leaf_block_tree.insert(&index.to_be_bytes(), &block)?;
+                        if let Some(resource_id) = resource_id {
+                            let location = CommitmentLocation {
+                                chain_id: resource_id.typed_chain_id(),
+                                contract: key.address(),
+                                leaf_index: index,
+                            };
+                            commitments_tree
+                                .insert(leaf, &location.to_bytes()[..])?;
+                        }
+                        // the index the commitment was already cached at,
+                        // if it is another one.
+                        let cached_index =
+                            match commitment_index_tree.get(leaf)? {
+                                Some(v) => {
+                                    let mut cached_index = [0u8; 4];
+                                    cached_index.copy_from_slice(&v);
+                                    Some(u32::from_be_bytes(cached_index))
+                                        .filter(|cached| *cached != index)
+                                }
+                                None => {
+                                    commitment_index_tree
+                                        .insert(leaf, &index.to_be_bytes())?;
+                                    None
+                                }
+                            };
+                        let duplicate = cached_index.map(|cached_index| {
+                            DuplicateCommitment {
+                                commitment: types::H256::from_slice(leaf),
+                                cached_index,
+                                index,
+                            }
+                        });
+                        Ok::<_, UnabortableTransactionError>(duplicate)
+                    };
+                    for (index, leaf) in &leaves {
+                        let index = *index;
+                        if let Some(cached) =
+                            leaf_tree.get(index.to_le_bytes())?
+                        {
+                            if cached.as_ref() != leaf.as_slice() {
+                                return sled::transaction::abort(
+                                    Error::LeafCacheCorrupted {
+                                        index,
+                                        cached: hex::encode(cached),
+                                        got: hex::encode(leaf),
+                                    },
+                                );
+                            }
+                            // such as a leaf imported again, its commitment
+                            // stays indexed at the index it got cached at.
+                            if commitment_index_tree.get(leaf)?.is_none() {
+                                commitment_index_tree.insert(
+                                    leaf.as_slice(),
+                                    &index.to_be_bytes(),
+                                )?;
+                            }
+                            continue;
+                        }
+                        if index > next {
+                            let pending_key = index.to_be_bytes();
+                            // the root of a pending leaf can not be checked
+                            // yet, so the one already there is kept.
+                            if let Some(v) = pending_tree.get(pending_key)? {
+                                let (_, buffered, _) =
+                                    decode_pending_leaf(&pending_key, &v);
+                                if buffered.as_bytes() != leaf.as_slice() {
+                                    return sled::transaction::abort(
+                                        Error::LeafCacheCorrupted {
+                                            index,
+                                            cached: hex::encode(buffered),
+                                            got: hex::encode(leaf),
+                                        },
+                                    );
+                                }
+                                continue;
+                            }
+                            let mut value = leaf.clone();
+                            value.extend_from_slice(&block_number_be_bytes);
+                            pending_tree.insert(&pending_key, value)?;
+                            pending += 1;
+                            outcome.pending.push(index);
+                            continue;
+                        }
+                        let duplicate = insert_leaf(index, leaf, block_number)?;
+                        outcome.duplicates.extend(duplicate);
+                        outcome
+                            .inserted
+                            .insert(index, types::H256::from_slice(leaf));
+                        next = index + 1;
+                        // skip the leaves we already have, and flush the
+                        // pending ones that are not ahead anymore.
+                        loop {
+                            if leaf_tree.get(next.to_le_bytes())?.is_some() {
+                                next += 1;
+                                continue;
+                            }
+                            let Some(v) =
+                                pending_tree.remove(&next.to_be_bytes())?
+                            else {
+                                break;
+                            };
+                            let (_, pending_leaf, block) =
+                                decode_pending_leaf(&next.to_be_bytes(), &v);
+                            let duplicate = insert_leaf(
+                                next,
+                                pending_leaf.as_bytes(),
+                                block,
+                            )?;
+                            outcome.duplicates.extend(duplicate);
+                            outcome.inserted.insert(next, pending_leaf);
+                            outcome.pending.retain(|i| *i != next);
+                            pending -= 1;
+                            next += 1;
+                        }
+                    }
+                    // the leaves are kept, the missing ones get fetched
+                    // again.
+                    if pending > max_pending as usize {
+                        outcome.missing_from = Some(next);
+                    }
+                    next_index_tree
+                        .insert(key.to_bytes(), &next.to_be_bytes())?;
+                    set_block_tree1
+                        .insert(key.to_bytes(), &block_number_bytes)?;
+                    set_block_tree2
+                        .insert(key.to_bytes(), &block_number_bytes)?;
+                    Ok(outcome)
+                },
+            );
+        match result {
+            Ok(outcome) => {
+                if let Some(from) = outcome.missing_from {
+                    let pending = pending_tree.len();
+                    tracing::warn!(
+                        %key,
+                        "{}",
+                        Error::MissingLeaves { from, pending },
+                    );
                 }
-                set_block_tree1.insert(key.to_bytes(), &block_number_bytes)?;
-                set_block_tree2.insert(key.to_bytes(), &block_number_bytes)?;
-                Ok(())
-            },
-        )?;
+                for duplicate in &outcome.duplicates {
+                    tracing::error!(
+                        %key,
+                        commitment = ?duplicate.commitment,
+                        cached_index = duplicate.cached_index,
+                        index = duplicate.index,
+                        "The same commitment got cached at two leaf indices",
+                    );
+                }
+                Ok(outcome)
+            }
+            Err(TransactionError::Abort(e)) => {
+                if let Error::LeafCacheCorrupted { index, .. } = &e {
+                    tracing::error!(
+                        %key,
+                        index,
+                        "Refusing to overwrite a cached leaf: {e}",
+                    );
+                }
+                Err(e)
+            }
+            Err(TransactionError::Storage(e)) => Err(e.into()),
+        }
+    }
+}
+
+impl SledStore {
+    /// Indexes the commitments of the leaves cached before the index of
+    /// their commitment got kept, once per contract, so that a commitment
+    /// cached again is reported even if it got cached first by an older
+    /// relayer.
+    fn backfill_commitment_indices(
+        &self,
+        key: HistoryStoreKey,
+    ) -> crate::Result<()> {
+        let backfilled_tree =
+            self.db.open_tree("leaf_commitment_indices_backfilled")?;
+        if backfilled_tree.contains_key(key.to_bytes())? {
+            return Ok(());
+        }
+        let commitment_index_tree = self.db.open_tree(format!(
+            "leaf_commitment_indices/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        let mut backfilled = 0;
+        // in the order of their indices, the first one is kept.
+        for (index, leaf) in self.get_leaves(key)? {
+            match commitment_index_tree.get(leaf.as_bytes())? {
+                Some(v) => {
+                    let mut cached_index = [0u8; 4];
+                    cached_index.copy_from_slice(&v);
+                    let cached_index = u32::from_be_bytes(cached_index);
+                    if cached_index != index {
+                        tracing::error!(
+                            %key,
+                            commitment = ?leaf,
+                            cached_index,
+                            index,
+                            "The same commitment got cached at two leaf indices",
+                        );
+                    }
+                }
+                None => {
+                    commitment_index_tree
+                        .insert(leaf.as_bytes(), &index.to_be_bytes())?;
+                    backfilled += 1;
+                }
+            }
+        }
+        if backfilled > 0 {
+            tracing::info!(
+                %key,
+                backfilled,
+                "Indexed the commitments of the cached leaves",
+            );
+        }
+        backfilled_tree.insert(key.to_bytes(), &b""[..])?;
         Ok(())
     }
 }
 
+/// Decodes an entry of the pending leaves tree, into the leaf index, the
+/// leaf, and the block it got inserted at.
+fn decode_pending_leaf(key: &[u8], value: &[u8]) -> (u32, types::H256, u64) {
+    let mut index = [0u8; 4];
+    index.copy_from_slice(key);
+    let mut block_number = [0u8; 8];
+    block_number.copy_from_slice(&value[32..]);
+    (
+        u32::from_be_bytes(index),
+        types::H256::from_slice(&value[..32]),
+        u64::from_be_bytes(block_number),
+    )
+}
+
 impl EncryptedOutputCacheStore for SledStore {
     type Output = Vec<Vec<u8>>;
 
@@ -645,269 +1302,2132 @@ where
         Ok(())
     }
 }
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
-    use webb::evm::ethers::types;
-    use webb::evm::ethers::types::transaction::request::TransactionRequest;
-    use webb::evm::{
-        contract::protocol_solidity::v_anchor_contract::NewNullifierFilter,
-        ethers::types::U64,
-    };
-    use webb_proposals::{TargetSystem, TypedChainId};
+impl DepositPropagationStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn insert_deposit(
+        &self,
+        id: DepositCorrelationId,
+        deposit_tx: types::H256,
+        deposit_block: u64,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        if tree.contains_key(id.0.as_bytes())? {
+            return Ok(());
+        }
+        let record = DepositPropagationRecord {
+            deposit_tx,
+            deposit_block,
+            seen_at: unix_timestamp(),
+            destinations: Default::default(),
+        };
+        tree.insert(id.0.as_bytes(), serde_json::to_vec(&record)?)?;
+        Ok(())
+    }
 
-    impl SledQueueKey {
-        pub fn from_evm_tx(chain_id: u32, tx: &TypedTransaction) -> Self {
-            let key = {
-                let mut bytes = [0u8; 64];
-                let tx_hash =
-                    tx.clone().set_chain_id(U64::from(chain_id)).sighash();
-                bytes[..32].copy_from_slice(tx_hash.as_fixed_bytes());
-                bytes
+    #[tracing::instrument(skip(self))]
+    fn insert_deposit_proposal(
+        &self,
+        id: DepositCorrelationId,
+        target_resource_id: webb_proposals::ResourceId,
+        proposal_data_hash: [u8; 32],
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        let by_proposal_tree =
+            self.db.open_tree("deposit_propagation_by_proposal")?;
+        let target_key = hex::encode(target_resource_id.to_bytes());
+        let maybe_record = tree.get(id.0.as_bytes())?;
+        let mut record: DepositPropagationRecord = match maybe_record {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => {
+                tracing::warn!("deposit not found, skipping proposal record");
+                return Ok(());
+            }
+        };
+        record
+            .destinations
+            .entry(target_key.clone())
+            .or_insert_with(|| DestinationPropagation {
+                chain_id: target_resource_id
+                    .typed_chain_id()
+                    .underlying_chain_id(),
+                proposal_data_hash: proposal_data_hash.into(),
+                proposed_at: unix_timestamp(),
+                execution_tx: None,
+                executed_at: None,
+            });
+        let record_bytes = serde_json::to_vec(&record)?;
+        let index_bytes = serde_json::to_vec(&(id, target_key))?;
+        (&tree, &by_proposal_tree).transaction(
+            |(tree, by_proposal_tree)| {
+                tree.insert(id.0.as_bytes(), record_bytes.as_slice())?;
+                by_proposal_tree
+                    .insert(&proposal_data_hash, index_bytes.as_slice())?;
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn relink_deposit_proposal(
+        &self,
+        old_proposal_data_hash: [u8; 32],
+        new_proposal_data_hash: [u8; 32],
+    ) -> crate::Result<bool> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        let by_proposal_tree =
+            self.db.open_tree("deposit_propagation_by_proposal")?;
+        let index_bytes = match by_proposal_tree.get(old_proposal_data_hash)? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let (id, target_key): (DepositCorrelationId, String) =
+            serde_json::from_slice(&index_bytes)?;
+        let mut record: DepositPropagationRecord =
+            match tree.get(id.0.as_bytes())? {
+                Some(v) => serde_json::from_slice(&v)?,
+                None => return Ok(false),
             };
-            Self::from_evm_with_custom_key(chain_id, key)
+        match record.destinations.get_mut(&target_key) {
+            Some(destination) => {
+                destination.proposal_data_hash = new_proposal_data_hash.into();
+            }
+            None => return Ok(false),
         }
+        let record_bytes = serde_json::to_vec(&record)?;
+        (&tree, &by_proposal_tree).transaction(
+            |(tree, by_proposal_tree)| {
+                tree.insert(id.0.as_bytes(), record_bytes.as_slice())?;
+                by_proposal_tree.remove(&old_proposal_data_hash)?;
+                by_proposal_tree
+                    .insert(&new_proposal_data_hash, index_bytes.clone())?;
+                Ok(())
+            },
+        )?;
+        Ok(true)
     }
 
-    #[test]
-    fn get_last_block_number_should_work() {
-        let tmp = tempfile::tempdir().unwrap();
-        let store = SledStore::open(tmp.path()).unwrap();
-        let chain_id = 1u32;
-        let contract =
-            types::H160::from_slice("11111111111111111111".as_bytes());
-        let block_number = 20u64;
-        let default_block_number = 1u64;
-        let history_store_key = (
-            TypedChainId::Evm(chain_id),
-            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
-        );
-        store
-            .set_last_block_number(history_store_key, block_number)
-            .unwrap();
+    #[tracing::instrument(skip(self))]
+    fn link_execution_call(
+        &self,
+        call_data_hash: [u8; 32],
+        proposal_data_hash: [u8; 32],
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("deposit_propagation_by_call")?;
+        tree.insert(call_data_hash, &proposal_data_hash)?;
+        Ok(())
+    }
 
-        let block = store
-            .get_last_block_number(history_store_key, default_block_number);
+    #[tracing::instrument(skip(self))]
+    fn mark_execution(
+        &self,
+        call_data_hash: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<bool> {
+        let events_tree = self.db.open_tree("proposal_event_calls")?;
+        if let Some(v) = events_tree.remove(call_data_hash)? {
+            let linked: LinkedProposalCall = serde_json::from_slice(&v)?;
+            let event = linked.event;
+            let kind = event.kind;
+            self.record_proposal_event(event.with_tx(kind, Some(tx_hash)))?;
+        }
+        let by_call_tree = self.db.open_tree("deposit_propagation_by_call")?;
+        match by_call_tree.remove(call_data_hash)? {
+            Some(v) => {
+                let mut proposal_data_hash = [0u8; 32];
+                proposal_data_hash.copy_from_slice(&v);
+                self.mark_proposal_execution(proposal_data_hash, tx_hash)
+            }
+            None => Ok(false),
+        }
+    }
 
-        match block {
-            Ok(b) => {
-                println!("retrieved block {block:?}");
-                assert_eq!(b, 20u64);
+    #[tracing::instrument(skip(self))]
+    fn mark_proposal_execution(
+        &self,
+        proposal_data_hash: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<bool> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        let by_proposal_tree =
+            self.db.open_tree("deposit_propagation_by_proposal")?;
+        let (id, target_key): (DepositCorrelationId, String) =
+            match by_proposal_tree.get(proposal_data_hash)? {
+                Some(v) => serde_json::from_slice(&v)?,
+                None => return Ok(false),
+            };
+        let mut record: DepositPropagationRecord =
+            match tree.get(id.0.as_bytes())? {
+                Some(v) => serde_json::from_slice(&v)?,
+                None => return Ok(false),
+            };
+        match record.destinations.get_mut(&target_key) {
+            Some(destination) if !destination.is_executed() => {
+                destination.execution_tx = Some(tx_hash);
+                destination.executed_at = Some(unix_timestamp());
             }
-            Err(e) => {
-                println!("Error encountered {e:?}");
+            _ => return Ok(false),
+        }
+        tree.insert(id.0.as_bytes(), serde_json::to_vec(&record)?)?;
+        tracing::event!(
+            target: webb_relayer_utils::probe::TARGET,
+            tracing::Level::DEBUG,
+            kind = %webb_relayer_utils::probe::Kind::DepositPropagation,
+            correlation_id = %id,
+            target = %target_key,
+            %tx_hash,
+            executed = true,
+        );
+        Ok(true)
+    }
+
+    fn get_deposit_propagation(
+        &self,
+        id: DepositCorrelationId,
+    ) -> crate::Result<Option<DepositPropagationRecord>> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        match tree.get(id.0.as_bytes())? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn list_pending_proposals(&self) -> crate::Result<Vec<[u8; 32]>> {
+        let tree = self.db.open_tree("deposit_propagation")?;
+        let by_proposal_tree =
+            self.db.open_tree("deposit_propagation_by_proposal")?;
+        let mut pending = Vec::new();
+        for entry in by_proposal_tree.iter() {
+            let (proposal_data_hash, index) = entry?;
+            let (id, target_key): (DepositCorrelationId, String) =
+                serde_json::from_slice(&index)?;
+            let executed = match tree.get(id.0.as_bytes())? {
+                Some(bytes) => {
+                    let record: DepositPropagationRecord =
+                        serde_json::from_slice(&bytes)?;
+                    record
+                        .destinations
+                        .get(&target_key)
+                        .map(DestinationPropagation::is_executed)
+                        .unwrap_or_default()
+                }
+                None => false,
+            };
+            if !executed {
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&proposal_data_hash);
+                pending.push(hash);
             }
         }
+        Ok(pending)
     }
+}
 
-    #[test]
-    fn get_leaves_with_range_should_work() {
-        let tmp = tempfile::tempdir().unwrap();
-        let store = SledStore::open(tmp.path()).unwrap();
-        let chain_id = 1u32;
-        let block_number = 20u64;
-        let contract =
-            types::H160::from_slice("11111111111111111111".as_bytes());
-        let history_store_key = (
-            TypedChainId::Evm(chain_id),
-            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
-        );
-        let generated_leaves = (0..20u32)
-            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
-            .collect::<Vec<_>>();
+impl RelayedWithdrawalStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn insert_relayed_withdrawal<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        withdrawal: RelayedWithdrawal,
+    ) -> crate::Result<()> {
+        let key = key.into();
+        let tree = self.db.open_tree(format!(
+            "relayed_withdrawals/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        // ids are monotonic, so the records are sorted from the oldest.
+        let id = self.db.generate_id()?;
+        tree.insert(id.to_be_bytes(), serde_json::to_vec(&withdrawal)?)?;
+        while tree.len() > MAX_RELAYED_WITHDRAWALS {
+            tree.pop_min()?;
+        }
+        Ok(())
+    }
+
+    fn get_relayed_withdrawals<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        limit: usize,
+    ) -> crate::Result<Vec<RelayedWithdrawal>> {
+        let key = key.into();
+        let tree = self.db.open_tree(format!(
+            "relayed_withdrawals/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        tree.iter()
+            .values()
+            .rev()
+            .take(limit)
+            .map(|v| Ok(serde_json::from_slice(&v?)?))
+            .collect()
+    }
+}
+
+impl FeeAccountingStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn record_fee(
+        &self,
+        chain_id: TypedChainId,
+        token: &str,
+        fee: types::U256,
+        timestamp: u64,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("fee_earnings")?;
+        let day = timestamp / (24 * 60 * 60);
+        // sorted by chain, then by day.
+        let mut key = Vec::with_capacity(16 + token.len());
+        key.extend_from_slice(&chain_id.chain_id().to_be_bytes());
+        key.extend_from_slice(&day.to_be_bytes());
+        key.extend_from_slice(token.as_bytes());
+        // updated in place, since the fees of concurrent withdrawals are
+        // recorded concurrently.
+        let mut corrupted = None;
+        tree.update_and_fetch(key, |old| {
+            let mut earnings = match old.map(serde_json::from_slice) {
+                Some(Ok(earnings)) => earnings,
+                // keep a corrupted entry as is, rather than resetting it.
+                Some(Err(e)) => {
+                    corrupted = Some(e);
+                    return old.map(<[u8]>::to_vec);
+                }
+                None => FeeEarnings {
+                    typed_chain_id: chain_id.chain_id(),
+                    token: token.to_string(),
+                    day,
+                    total: types::U256::zero(),
+                    count: 0,
+                },
+            };
+            earnings.total = earnings.total.saturating_add(fee);
+            earnings.count += 1;
+            serde_json::to_vec(&earnings).ok()
+        })?;
+        match corrupted {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn get_fee_earnings(&self) -> crate::Result<Vec<FeeEarnings>> {
+        let tree = self.db.open_tree("fee_earnings")?;
+        tree.iter()
+            .values()
+            .map(|v| Ok(serde_json::from_slice(&v?)?))
+            .collect()
+    }
+}
+
+impl ProviderUsageStore for SledStore {
+    fn add_provider_usage(&self, usage: &[ProviderUsage]) -> crate::Result<()> {
+        let tree = self.db.open_tree("provider_usage")?;
+        for added in usage {
+            // sorted by provider, then by day.
+            let mut key = Vec::with_capacity(added.provider.len() + 9);
+            key.extend_from_slice(added.provider.as_bytes());
+            key.push(0);
+            key.extend_from_slice(&added.day.to_be_bytes());
+            // updated in place, since the providers of every chain record
+            // their calls concurrently.
+            tree.update_and_fetch(key, |old| {
+                let mut usage: ProviderUsage =
+                    match old.map(serde_json::from_slice) {
+                        Some(Ok(usage)) => usage,
+                        // keep a corrupted entry as is, rather than
+                        // resetting it.
+                        Some(Err(_)) => return old.map(<[u8]>::to_vec),
+                        None => ProviderUsage {
+                            provider: added.provider.clone(),
+                            day: added.day,
+                            ..Default::default()
+                        },
+                    };
+                usage.requests += added.requests;
+                usage.log_queries += added.log_queries;
+                usage.submissions += added.submissions;
+                usage.errors += added.errors;
+                serde_json::to_vec(&usage).ok()
+            })?;
+        }
+        Ok(())
+    }
+
+    fn get_provider_usage(
+        &self,
+        since_day: u64,
+    ) -> crate::Result<Vec<ProviderUsage>> {
+        let tree = self.db.open_tree("provider_usage")?;
+        let mut usage = Vec::new();
+        for value in tree.iter().values() {
+            let day_usage: ProviderUsage = serde_json::from_slice(&value?)?;
+            if day_usage.day >= since_day {
+                usage.push(day_usage);
+            }
+        }
+        Ok(usage)
+    }
+}
+
+impl SledStore {
+    /// Updates the amounts relayed on the given contract with `f`, pruning
+    /// the ones older than `window_secs` first.
+    ///
+    /// `f` may run more than once, if the amounts got updated concurrently.
+    fn update_relayed_amounts<K, T>(
+        &self,
+        key: K,
+        window_secs: u64,
+        now: u64,
+        mut f: impl FnMut(&mut Vec<RelayedAmount>) -> T,
+    ) -> crate::Result<T>
+    where
+        K: Into<HistoryStoreKey> + Debug,
+    {
+        let key = key.into();
+        let tree = self.db.open_tree("relayed_amounts")?;
+        let tree_key = key.to_bytes();
+        let since = now.saturating_sub(window_secs);
+        loop {
+            let current = tree.get(&tree_key)?;
+            let mut amounts: Vec<RelayedAmount> = match &current {
+                Some(bytes) => serde_json::from_slice(bytes)?,
+                None => Vec::new(),
+            };
+            amounts.retain(|r| r.at > since);
+            let out = f(&mut amounts);
+            let new = serde_json::to_vec(&amounts)?;
+            // compare and swap, so concurrent withdrawals can not both
+            // take the last of the limit.
+            if tree
+                .compare_and_swap(&tree_key, current, Some(new))?
+                .is_ok()
+            {
+                return Ok(out);
+            }
+        }
+    }
+}
+
+impl WithdrawalLimitStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn reserve_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        amount: types::U256,
+        limit: types::U256,
+        window_secs: u64,
+        now: u64,
+    ) -> crate::Result<AmountReservation> {
+        let id = self.db.generate_id()?;
+        self.update_relayed_amounts(key, window_secs, now, |amounts| {
+            match super::amount_fits_in(
+                amounts,
+                amount,
+                limit,
+                window_secs,
+                now,
+            ) {
+                Some(0) => {
+                    amounts.push(RelayedAmount {
+                        id,
+                        at: now,
+                        amount,
+                    });
+                    AmountReservation::Reserved { id }
+                }
+                retry_after_secs => {
+                    AmountReservation::Exceeded { retry_after_secs }
+                }
+            }
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn release_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        id: u64,
+    ) -> crate::Result<()> {
+        // nothing older than the id is pruned.
+        self.update_relayed_amounts(key, u64::MAX, 0, |amounts| {
+            amounts.retain(|r| r.id != id)
+        })
+    }
+
+    fn get_relayed_amount<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        window_secs: u64,
+        now: u64,
+    ) -> crate::Result<types::U256> {
+        let key = key.into();
+        let tree = self.db.open_tree("relayed_amounts")?;
+        let amounts: Vec<RelayedAmount> = match tree.get(key.to_bytes())? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => return Ok(types::U256::zero()),
+        };
+        let since = now.saturating_sub(window_secs);
+        Ok(amounts
+            .iter()
+            .filter(|r| r.at > since)
+            .fold(types::U256::zero(), |acc, r| acc.saturating_add(r.amount)))
+    }
+}
+
+impl CommitmentIndexStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn index_commitment(
+        &self,
+        commitment: [u8; 32],
+        chain_id: TypedChainId,
+        contract: types::Address,
+        leaf_index: u32,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("commitments")?;
+        let location = CommitmentLocation {
+            chain_id,
+            contract,
+            leaf_index,
+        };
+        tree.insert(commitment, &location.to_bytes()[..])?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn lookup_commitment(
+        &self,
+        commitment: [u8; 32],
+    ) -> crate::Result<Option<CommitmentLocation>> {
+        let tree = self.db.open_tree("commitments")?;
+        match tree.get(commitment)? {
+            Some(v) => CommitmentLocation::from_bytes(&v)
+                .map(Some)
+                .ok_or(Error::Generic("invalid commitment location")),
+            None => Ok(None),
+        }
+    }
+}
+
+impl EdgeStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn insert_edge<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+        edge: AnchorEdge,
+    ) -> crate::Result<()> {
+        let key = key.into();
+        let tree = self.db.open_tree(format!(
+            "edges/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        let edge_key = edge.src_chain_id.to_be_bytes();
+        if let Some(existing) = tree.get(edge_key)? {
+            let existing: AnchorEdge = serde_json::from_slice(&existing)?;
+            if existing.latest_leaf_index > edge.latest_leaf_index {
+                tracing::debug!(?existing, "Ignoring an outdated edge");
+                return Ok(());
+            }
+        }
+        tree.insert(edge_key, serde_json::to_vec(&edge)?)?;
+        Ok(())
+    }
+
+    fn get_edges<K: Into<HistoryStoreKey> + Debug>(
+        &self,
+        key: K,
+    ) -> crate::Result<Vec<AnchorEdge>> {
+        let key = key.into();
+        let tree = self.db.open_tree(format!(
+            "edges/{}/{}",
+            key.chain_id(),
+            key.address()
+        ))?;
+        // keys are big endian chain ids, so they are sorted.
+        tree.iter()
+            .values()
+            .map(|v| Ok(serde_json::from_slice(&v?)?))
+            .collect()
+    }
+}
+
+impl ScheduledRelayStore for SledStore {
+    #[tracing::instrument(skip(self, relay))]
+    fn schedule_relay(
+        &self,
+        key: &str,
+        relay: ScheduledRelay,
+    ) -> crate::Result<bool> {
+        let tree = self.db.open_tree("scheduled_relays")?;
+        let value = serde_json::to_vec(&relay)?;
+        let swapped =
+            tree.compare_and_swap(key, None as Option<&[u8]>, Some(value))?;
+        Ok(swapped.is_ok())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn take_scheduled_relay(
+        &self,
+        key: &str,
+    ) -> crate::Result<Option<ScheduledRelay>> {
+        let tree = self.db.open_tree("scheduled_relays")?;
+        match tree.remove(key)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_scheduled_relays(
+        &self,
+    ) -> crate::Result<Vec<(String, ScheduledRelay)>> {
+        let tree = self.db.open_tree("scheduled_relays")?;
+        tree.iter()
+            .map(|entry| {
+                let (key, value) = entry?;
+                let key = String::from_utf8_lossy(&key).into_owned();
+                Ok((key, serde_json::from_slice(&value)?))
+            })
+            .collect()
+    }
+}
+
+impl RelayFingerprintStore for SledStore {
+    #[tracing::instrument(skip(self, record))]
+    fn record_relay_fingerprint(
+        &self,
+        fingerprint: [u8; 32],
+        record: RelayFingerprint,
+        now: u64,
+    ) -> crate::Result<Option<RelayFingerprint>> {
+        let tree = self.db.open_tree("relay_fingerprints")?;
+        let value = serde_json::to_vec(&record)?;
+        loop {
+            let current = tree.get(fingerprint)?;
+            if let Some(bytes) = &current {
+                let existing: RelayFingerprint = serde_json::from_slice(bytes)?;
+                if existing.expires_at > now {
+                    return Ok(Some(existing));
+                }
+            }
+            // compare and swap, so only one of two concurrent retries of
+            // the same relay gets accepted.
+            if tree
+                .compare_and_swap(fingerprint, current, Some(value.clone()))?
+                .is_ok()
+            {
+                return Ok(None);
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn set_relay_fingerprint_tx(
+        &self,
+        fingerprint: [u8; 32],
+        tx_hash: types::H256,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("relay_fingerprints")?;
+        tree.fetch_and_update(fingerprint, |current| {
+            let mut record: RelayFingerprint =
+                serde_json::from_slice(current?).ok()?;
+            record.tx_hash = Some(tx_hash);
+            serde_json::to_vec(&record).ok()
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn remove_relay_fingerprint(
+        &self,
+        fingerprint: [u8; 32],
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("relay_fingerprints")?;
+        tree.remove(fingerprint)?;
+        Ok(())
+    }
+
+    fn prune_relay_fingerprints(&self, now: u64) -> crate::Result<usize> {
+        let tree = self.db.open_tree("relay_fingerprints")?;
+        let mut removed = 0;
+        for entry in tree.iter() {
+            let (key, value) = entry?;
+            let record: RelayFingerprint = serde_json::from_slice(&value)?;
+            if record.expires_at <= now {
+                tree.remove(key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl InFlightTxStore for SledStore {
+    #[tracing::instrument(skip(self, tx), fields(tx_hash = %tx.tx_hash))]
+    fn record_in_flight_tx(
+        &self,
+        chain_id: u32,
+        tx: InFlightTx,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("in_flight_txs")?;
+        let key = [&chain_id.to_be_bytes()[..], tx.tx_hash.as_bytes()].concat();
+        tree.insert(key, serde_json::to_vec(&tx)?)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn remove_in_flight_tx(
+        &self,
+        chain_id: u32,
+        tx_hash: types::H256,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("in_flight_txs")?;
+        let key = [&chain_id.to_be_bytes()[..], tx_hash.as_bytes()].concat();
+        tree.remove(key)?;
+        Ok(())
+    }
+
+    fn get_in_flight_txs(
+        &self,
+        chain_id: u32,
+        limit: usize,
+    ) -> crate::Result<Vec<InFlightTx>> {
+        let tree = self.db.open_tree("in_flight_txs")?;
+        tree.scan_prefix(chain_id.to_be_bytes())
+            .values()
+            .take(limit)
+            .map(|v| Ok(serde_json::from_slice(&v?)?))
+            .collect()
+    }
+}
+
+impl RevertRetryStore for SledStore {
+    fn get_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+    ) -> crate::Result<Option<RevertRetry>> {
+        let tree = self.db.open_tree("revert_retries")?;
+        let key = [&chain_id.to_be_bytes()[..], &call_key].concat();
+        match tree.get(key)? {
+            Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn record_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+        retry: RevertRetry,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("revert_retries")?;
+        let key = [&chain_id.to_be_bytes()[..], &call_key].concat();
+        tree.insert(key, serde_json::to_vec(&retry)?)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn remove_revert_retry(
+        &self,
+        chain_id: u32,
+        call_key: [u8; 32],
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("revert_retries")?;
+        let key = [&chain_id.to_be_bytes()[..], &call_key].concat();
+        tree.remove(key)?;
+        Ok(())
+    }
+}
+
+impl ProposalEventStore for SledStore {
+    #[tracing::instrument(skip(self))]
+    fn record_proposal_event(
+        &self,
+        event: ProposalEvent,
+    ) -> crate::Result<ProposalEventRecord> {
+        let tree = self.db.open_tree("proposal_events")?;
+        let sender = self.proposal_events.lock();
+        let seq = self.last_proposal_event_seq()? + 1;
+        let record = ProposalEventRecord {
+            seq,
+            timestamp: unix_timestamp(),
+            event,
+        };
+        tree.insert(seq.to_be_bytes(), serde_json::to_vec(&record)?)?;
+        if let Some(oldest) = seq.checked_sub(MAX_PROPOSAL_EVENTS) {
+            tree.remove(oldest.to_be_bytes())?;
+        }
+        // nobody may be listening, the event is stored anyway.
+        let _ = sender.send(record.clone());
+        Ok(record)
+    }
+
+    #[tracing::instrument(skip(self, event))]
+    fn link_proposal_call(
+        &self,
+        call_data_hash: [u8; 32],
+        event: ProposalEvent,
+    ) -> crate::Result<()> {
+        let tree = self.db.open_tree("proposal_event_calls")?;
+        let linked = LinkedProposalCall {
+            linked_at: unix_timestamp(),
+            event,
+        };
+        tree.insert(call_data_hash, serde_json::to_vec(&linked)?)?;
+        Ok(())
+    }
+
+    fn prune_proposal_calls(&self, now: u64) -> crate::Result<usize> {
+        let tree = self.db.open_tree("proposal_event_calls")?;
+        let mut removed = 0;
+        for entry in tree.iter() {
+            let (key, value) = entry?;
+            let linked: LinkedProposalCall = serde_json::from_slice(&value)?;
+            if linked.linked_at + PROPOSAL_CALL_TTL_SECS <= now {
+                tree.remove(key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn get_proposal_events_after(
+        &self,
+        cursor: u64,
+        limit: usize,
+    ) -> crate::Result<Vec<ProposalEventRecord>> {
+        let tree = self.db.open_tree("proposal_events")?;
+        let Some(start) = cursor.checked_add(1) else {
+            return Ok(Vec::new());
+        };
+        tree.range(start.to_be_bytes()..)
+            .take(limit)
+            .map(|entry| {
+                let (_, value) = entry?;
+                Ok(serde_json::from_slice(&value)?)
+            })
+            .collect()
+    }
+
+    fn last_proposal_event_seq(&self) -> crate::Result<u64> {
+        let tree = self.db.open_tree("proposal_events")?;
+        let seq = match tree.last()? {
+            Some((key, _)) => {
+                let mut seq = [0u8; 8];
+                seq.copy_from_slice(&key);
+                u64::from_be_bytes(seq)
+            }
+            None => 0,
+        };
+        Ok(seq)
+    }
+
+    fn subscribe_proposal_events(
+        &self,
+    ) -> broadcast::Receiver<ProposalEventRecord> {
+        self.proposal_events.lock().subscribe()
+    }
+}
+
+/// Returns the number of secs since the UNIX epoch.
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
+    use webb::evm::ethers::types;
+    use webb::evm::ethers::types::transaction::request::TransactionRequest;
+    use webb::evm::{
+        contract::protocol_solidity::v_anchor_contract::NewNullifierFilter,
+        ethers::types::U64,
+    };
+    use webb_proposals::TargetSystem;
+    use webb_relayer_utils::multi_provider::RpcCallKind;
+
+    impl SledQueueKey {
+        pub fn from_evm_tx(chain_id: u32, tx: &TypedTransaction) -> Self {
+            let key = {
+                let mut bytes = [0u8; 64];
+                let tx_hash =
+                    tx.clone().set_chain_id(U64::from(chain_id)).sighash();
+                bytes[..32].copy_from_slice(tx_hash.as_fixed_bytes());
+                bytes
+            };
+            Self::from_evm_with_custom_key(chain_id, key)
+        }
+    }
+
+    #[test]
+    fn get_last_block_number_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let chain_id = 1u32;
+        let contract =
+            types::H160::from_slice("11111111111111111111".as_bytes());
+        let block_number = 20u64;
+        let default_block_number = 1u64;
+        let history_store_key = (
+            TypedChainId::Evm(chain_id),
+            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+        );
+        store
+            .set_last_block_number(history_store_key, block_number)
+            .unwrap();
+
+        let block = store
+            .get_last_block_number(history_store_key, default_block_number);
+
+        match block {
+            Ok(b) => {
+                println!("retrieved block {block:?}");
+                assert_eq!(b, 20u64);
+            }
+            Err(e) => {
+                println!("Error encountered {e:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn get_leaves_with_range_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let chain_id = 1u32;
+        let block_number = 20u64;
+        let contract =
+            types::H160::from_slice("11111111111111111111".as_bytes());
+        let history_store_key = (
+            TypedChainId::Evm(chain_id),
+            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+        );
+        let generated_leaves = (0..20u32)
+            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                history_store_key,
+                &generated_leaves,
+                block_number,
+            )
+            .unwrap();
+        let leaves = store
+            .get_leaves_with_range(history_store_key, 5..10)
+            .unwrap();
+        assert_eq!(leaves.len(), 5);
+        assert_eq!(
+            leaves
+                .values()
+                .map(|v| v.to_fixed_bytes().to_vec())
+                .collect::<Vec<_>>(),
+            generated_leaves
+                .into_iter()
+                .skip(5)
+                .take(5)
+                .map(|(_, v)| v)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn evm_and_substrate_queues_should_not_collide() {
+        let store = SledStore::temporary().unwrap();
+        let chain_id = 1080u32;
+        let custom_key = [7u8; 64];
+        let evm_key =
+            SledQueueKey::from_evm_with_custom_key(chain_id, custom_key);
+        let substrate_key =
+            SledQueueKey::from_substrate_with_custom_key(chain_id, custom_key);
+        // the same item key, in a queue of their own.
+        assert_eq!(evm_key.item_key(), substrate_key.item_key());
+        assert_ne!(evm_key.queue_name(), substrate_key.queue_name());
+
+        store.enqueue_item(evm_key, String::from("evm")).unwrap();
+        assert!(QueueStore::<String>::has_item(&store, evm_key).unwrap());
+        assert!(!QueueStore::<String>::has_item(&store, substrate_key).unwrap());
+        store
+            .enqueue_item(substrate_key, String::from("substrate"))
+            .unwrap();
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_substrate_chain_id(chain_id))
+                .unwrap(),
+            Some(String::from("substrate"))
+        );
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Some(String::from("evm"))
+        );
+    }
+
+    #[test]
+    fn tx_queue_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let chain_id = 1u32;
+        // it is now empty
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Option::<TypedTransaction>::None
+        );
+
+        let tx1: TypedTransaction = TransactionRequest::pay(
+            types::Address::random(),
+            types::U256::one(),
+        )
+        .from(types::Address::random())
+        .into();
+        store
+            .enqueue_item(
+                SledQueueKey::from_evm_tx(chain_id, &tx1),
+                tx1.clone(),
+            )
+            .unwrap();
+        let tx2: TypedTransaction = TransactionRequest::pay(
+            types::Address::random(),
+            types::U256::one(),
+        )
+        .from(types::Address::random())
+        .into();
+        store
+            .enqueue_item(
+                SledQueueKey::from_evm_tx(chain_id, &tx2),
+                tx2.clone(),
+            )
+            .unwrap();
+
+        // now let's dequeue transactions.
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Some(tx1)
+        );
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Some(tx2)
+        );
+
+        let tx3: TypedTransaction = TransactionRequest::pay(
+            types::Address::random(),
+            types::U256::one(),
+        )
+        .from(types::Address::random())
+        .into();
+        store
+            .enqueue_item(
+                SledQueueKey::from_evm_tx(chain_id, &tx3),
+                tx3.clone(),
+            )
+            .unwrap();
+        assert_eq!(
+            store
+                .peek_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Some(tx3.clone())
+        );
+        assert!(QueueStore::<TypedTransaction>::has_item(
+            &store,
+            SledQueueKey::from_evm_tx(chain_id, &tx3)
+        )
+        .unwrap());
+        let _: Option<TypedTransaction> = store
+            .remove_item(SledQueueKey::from_evm_tx(chain_id, &tx3))
+            .unwrap();
+        assert!(!QueueStore::<TypedTransaction>::has_item(
+            &store,
+            SledQueueKey::from_evm_tx(chain_id, &tx3)
+        )
+        .unwrap());
+        assert_eq!(
+            store
+                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .unwrap(),
+            Option::<TypedTransaction>::None
+        );
+    }
+
+    #[test]
+    fn events_hash_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+
+        let events = (0..20)
+            .map(|_| NewNullifierFilter {
+                nullifier: types::H256::random().to_fixed_bytes().into(),
+            })
+            .collect::<Vec<_>>();
+
+        for event in &events {
+            let event_bytes = serde_json::to_vec(&event).unwrap();
+            // check if the event is already in the store
+            assert!(!store.contains_event(&event_bytes).unwrap());
+            // add the event
+            store.store_event(&event_bytes).unwrap();
+        }
+
+        for event in &events {
+            let event_bytes = serde_json::to_vec(&event).unwrap();
+            // check if the event is in the store
+            assert!(store.contains_event(&event_bytes).unwrap());
+            // remove the event
+            store.delete_event(&event_bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn insert_leaves_and_last_deposit_block_number_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let chain_id = 1u32;
+        let contract =
+            types::H160::from_slice("11111111111111111111".as_bytes());
+        let history_store_key = (
+            TypedChainId::Evm(chain_id),
+            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+        );
+        let generated_leaves = (0..2u32)
+            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        let block_number = 20u64;
+        let default_block_number = 1u64;
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                history_store_key,
+                &generated_leaves,
+                block_number,
+            )
+            .unwrap();
+        let leaves = store
+            .get_leaves_with_range(history_store_key, 0..2)
+            .unwrap();
+        assert_eq!(leaves.len(), 2);
+        assert_eq!(
+            leaves
+                .values()
+                .map(|v| v.to_fixed_bytes().to_vec())
+                .collect::<Vec<_>>(),
+            generated_leaves
+                .into_iter()
+                .map(|(_, v)| v)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            store
+                .get_last_deposit_block_number(history_store_key)
+                .unwrap(),
+            block_number
+        );
+        assert_eq!(
+            store
+                .get_last_block_number(history_store_key, default_block_number)
+                .unwrap(),
+            block_number
+        );
+    }
+
+    #[test]
+    fn deposit_propagation_should_track_partial_execution() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let anchor = |chain_id: u32, byte: u8| {
+            webb_proposals::ResourceId::new(
+                TargetSystem::new_contract_address([byte; 20]),
+                TypedChainId::Evm(chain_id),
+            )
+        };
+        let src = anchor(1, 0x11);
+        let id = DepositCorrelationId::new(src, 3);
+        // same anchor on another chain or another leaf is another deposit.
+        assert_ne!(id, DepositCorrelationId::new(anchor(2, 0x11), 3));
+        assert_ne!(id, DepositCorrelationId::new(src, 5));
+
+        let deposit_tx = types::H256::random();
+        store.insert_deposit(id, deposit_tx, 42).unwrap();
+        // inserting it again keeps the first record.
+        store.insert_deposit(id, types::H256::random(), 43).unwrap();
+
+        let targets = [anchor(2, 0x22), anchor(3, 0x33), anchor(4, 0x44)];
+        for (i, target) in targets.iter().enumerate() {
+            let proposal_data_hash = [i as u8 + 1; 32];
+            store
+                .insert_deposit_proposal(id, *target, proposal_data_hash)
+                .unwrap();
+            let call_data_hash = [i as u8 + 0xa0; 32];
+            store
+                .link_execution_call(call_data_hash, proposal_data_hash)
+                .unwrap();
+        }
+        // execute on only 2 of the 3 linked chains.
+        let execution_tx = types::H256::random();
+        assert!(store.mark_execution([0xa0; 32], execution_tx).unwrap());
+        assert!(store.mark_execution([0xa1; 32], execution_tx).unwrap());
+        // unknown call data is ignored.
+        assert!(!store.mark_execution([0xff; 32], execution_tx).unwrap());
+
+        let record = store.get_deposit_propagation(id).unwrap().unwrap();
+        assert_eq!(record.deposit_tx, deposit_tx);
+        assert_eq!(record.deposit_block, 42);
+        assert_eq!(record.destinations.len(), 3);
+        let executed = record
+            .destinations
+            .values()
+            .filter(|d| d.is_executed())
+            .map(|d| d.chain_id)
+            .collect::<Vec<_>>();
+        assert_eq!(executed, vec![2, 3]);
+        assert!(!record.is_fully_propagated());
+        assert_eq!(store.list_pending_proposals().unwrap(), vec![[3u8; 32]]);
+
+        // the last one got executed by someone else, and seen on-chain.
+        let seen_tx = types::H256::random();
+        assert!(store.mark_proposal_execution([3u8; 32], seen_tx).unwrap());
+        assert!(!store.mark_proposal_execution([3u8; 32], seen_tx).unwrap());
+        assert!(!store.mark_proposal_execution([0xff; 32], seen_tx).unwrap());
+        // so our own execution of it does not override it.
+        assert!(!store.mark_execution([0xa2; 32], execution_tx).unwrap());
+        let record = store.get_deposit_propagation(id).unwrap().unwrap();
+        assert!(record.is_fully_propagated());
+        assert_eq!(
+            record.destinations[&hex::encode(targets[2].to_bytes())]
+                .execution_tx,
+            Some(seen_tx)
+        );
+    }
+
+    #[test]
+    fn export_metrics_should_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let anchor = |chain_id: u32, byte: u8| {
+            webb_proposals::ResourceId::new(
+                TargetSystem::new_contract_address([byte; 20]),
+                TypedChainId::Evm(chain_id),
+            )
+        };
+        // an empty store has nothing to report.
+        let metrics = store.export_metrics();
+        assert_eq!(metrics.proposal_count, 0);
+        assert!(metrics.queue_depth_by_chain.is_empty());
+        assert!(metrics.leaf_count_by_contract.is_empty());
+
+        let (a, b) = (anchor(1, 0x11), anchor(2, 0x22));
+        let leaves = |n: u32| {
+            (0..n)
+                .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
+                .collect::<Vec<_>>()
+        };
+        store
+            .insert_leaves_and_last_deposit_block_number(a, &leaves(5), 10)
+            .unwrap();
+        store
+            .insert_leaves_and_last_deposit_block_number(b, &leaves(3), 10)
+            .unwrap();
+
+        for _ in 0..3 {
+            let tx: TypedTransaction = TransactionRequest::pay(
+                types::Address::random(),
+                types::U256::one(),
+            )
+            .from(types::Address::random())
+            .into();
+            store
+                .enqueue_item(SledQueueKey::from_evm_tx(1, &tx), tx)
+                .unwrap();
+        }
+        store
+            .enqueue_item(SledQueueKey::from_substrate_chain_id(1), 1u32)
+            .unwrap();
+        store
+            .enqueue_item(SledQueueKey::from_substrate_chain_id(7), 7u32)
+            .unwrap();
+
+        let id = DepositCorrelationId::new(a, 1);
+        store.insert_deposit(id, types::H256::random(), 42).unwrap();
+        store.insert_deposit_proposal(id, b, [1u8; 32]).unwrap();
+
+        let metrics = store.export_metrics();
+        assert_eq!(metrics.proposal_count, 1);
+        assert_eq!(
+            metrics.queue_depth_by_chain,
+            [(1, 4), (7, 1)].into_iter().collect()
+        );
+        assert_eq!(
+            metrics.leaf_count_by_contract,
+            [
+                ((TypedChainId::Evm(1), types::Address::from([0x11; 20])), 5),
+                ((TypedChainId::Evm(2), types::Address::from([0x22; 20])), 3),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert!(metrics.db_size_bytes > 0);
+    }
+
+    #[test]
+    fn relinked_deposit_proposal_should_be_marked_executed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let anchor = |chain_id: u32, byte: u8| {
+            webb_proposals::ResourceId::new(
+                TargetSystem::new_contract_address([byte; 20]),
+                TypedChainId::Evm(chain_id),
+            )
+        };
+        let (src, target) = (anchor(1, 0x11), anchor(2, 0x22));
+        let id = DepositCorrelationId::new(src, 1);
+        store.insert_deposit(id, types::H256::random(), 42).unwrap();
+        store
+            .insert_deposit_proposal(id, target, [1u8; 32])
+            .unwrap();
+        // unknown proposals are ignored.
+        assert!(!store.relink_deposit_proposal([9u8; 32], [8u8; 32]).unwrap());
+        // the proposal got re-created with a fresh root.
+        assert!(store.relink_deposit_proposal([1u8; 32], [2u8; 32]).unwrap());
+        let record = store.get_deposit_propagation(id).unwrap().unwrap();
+        let destination = &record.destinations[&hex::encode(target.to_bytes())];
+        assert_eq!(destination.proposal_data_hash, [2u8; 32].into());
+
+        // executing the old proposal does not count anymore.
+        store.link_execution_call([0xa0; 32], [1u8; 32]).unwrap();
+        assert!(!store
+            .mark_execution([0xa0; 32], types::H256::random())
+            .unwrap());
+        store.link_execution_call([0xa1; 32], [2u8; 32]).unwrap();
+        assert!(store
+            .mark_execution([0xa1; 32], types::H256::random())
+            .unwrap());
+        let record = store.get_deposit_propagation(id).unwrap().unwrap();
+        assert!(record.is_fully_propagated());
+        assert!(store.list_pending_proposals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn relayed_withdrawals_should_be_capped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let withdrawal = |i: u64| RelayedWithdrawal {
+            tx_hash: types::H256::from_low_u64_be(i),
+            finalized_at: 1_000 + i,
+            confirmation_latency_ms: 500,
+            fee: i.into(),
+            gas_used: 21_000.into(),
+        };
+        let total = MAX_RELAYED_WITHDRAWALS as u64 + 5;
+        for i in 0..total {
+            store.insert_relayed_withdrawal(key, withdrawal(i)).unwrap();
+        }
+        let recent = store.get_relayed_withdrawals(key, 3).unwrap();
+        assert_eq!(
+            recent,
+            vec![
+                withdrawal(total - 1),
+                withdrawal(total - 2),
+                withdrawal(total - 3)
+            ]
+        );
+        let all = store.get_relayed_withdrawals(key, usize::MAX).unwrap();
+        assert_eq!(all.len(), MAX_RELAYED_WITHDRAWALS);
+        // the oldest ones got dropped.
+        assert_eq!(all.last(), Some(&withdrawal(5)));
+        // other contracts are not affected.
+        let other = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x22; 20]),
+            TypedChainId::Evm(1),
+        );
+        assert!(store.get_relayed_withdrawals(other, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fees_should_add_up_per_chain_token_and_day() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let day = 24 * 60 * 60;
+        let (evm, substrate) =
+            (TypedChainId::Evm(5), TypedChainId::Substrate(1080));
+        let usdc = "0x38e7aa90c77f86747fab355eecaa0c2e4c3a463d";
+        store.record_fee(evm, usdc, 10.into(), day + 5).unwrap();
+        store.record_fee(evm, usdc, 20.into(), day + 60).unwrap();
+        store.record_fee(evm, usdc, 5.into(), 3 * day).unwrap();
+        store.record_fee(substrate, "0", 7.into(), day).unwrap();
+        let earnings = |chain: TypedChainId,
+                        token: &str,
+                        day: u64,
+                        total: u64,
+                        count: u64| FeeEarnings {
+            typed_chain_id: chain.chain_id(),
+            token: token.to_string(),
+            day,
+            total: total.into(),
+            count,
+        };
+        assert_eq!(
+            store.get_fee_earnings().unwrap(),
+            vec![
+                earnings(evm, usdc, 1, 30, 2),
+                earnings(evm, usdc, 3, 5, 1),
+                earnings(substrate, "0", 1, 7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn concurrent_fees_should_all_be_recorded() {
+        let store = SledStore::temporary().unwrap();
+        let chain = TypedChainId::Evm(5);
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..50 {
+                        store.record_fee(chain, "0", 1.into(), 0).unwrap();
+                    }
+                });
+            }
+        });
+        let earnings = store.get_fee_earnings().unwrap();
+        assert_eq!(earnings.len(), 1);
+        assert_eq!(earnings[0].count, 400);
+        assert_eq!(earnings[0].total, 400.into());
+    }
+
+    #[test]
+    fn provider_calls_should_add_up_per_provider_and_day() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let record = |provider: &str, kind, failed, day| {
+            let mut usage = ProviderUsage {
+                provider: provider.to_string(),
+                day,
+                ..Default::default()
+            };
+            usage.count_call(kind, failed);
+            store.add_provider_usage(&[usage]).unwrap()
+        };
+        record("infura", RpcCallKind::Request, false, 1);
+        record("infura", RpcCallKind::LogQuery, true, 1);
+        record("infura", RpcCallKind::Submission, false, 3);
+        record("alchemy", RpcCallKind::Submission, false, 2);
+        let usage =
+            |provider: &str, day, requests, log_queries, submissions| {
+                ProviderUsage {
+                    provider: provider.to_string(),
+                    day,
+                    requests,
+                    log_queries,
+                    submissions,
+                    errors: u64::from(log_queries > 0),
+                }
+            };
+        assert_eq!(
+            store.get_provider_usage(0).unwrap(),
+            vec![
+                usage("alchemy", 2, 1, 0, 1),
+                usage("infura", 1, 2, 1, 0),
+                usage("infura", 3, 1, 0, 1),
+            ]
+        );
+        assert_eq!(store.get_provider_usage(3).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn commitments_should_be_indexed_with_the_leaves() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let contract = types::Address::repeat_byte(0x11);
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+            TypedChainId::Evm(5001),
+        );
+        let commitment = |i: u64| types::H256::from_low_u64_be(i + 1);
+        let leaves = (0..3)
+            .map(|i| (i, commitment(i.into()).to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 100)
+            .unwrap();
+        assert_eq!(
+            store
+                .lookup_commitment(commitment(2).to_fixed_bytes())
+                .unwrap(),
+            Some(CommitmentLocation {
+                chain_id: TypedChainId::Evm(5001),
+                contract,
+                leaf_index: 2,
+            })
+        );
+        // unknown commitments are not found.
+        assert_eq!(
+            store
+                .lookup_commitment(commitment(3).to_fixed_bytes())
+                .unwrap(),
+            None
+        );
+
+        let other = types::Address::repeat_byte(0x22);
+        store
+            .index_commitment(
+                commitment(3).to_fixed_bytes(),
+                TypedChainId::Evm(5002),
+                other,
+                7,
+            )
+            .unwrap();
+        assert_eq!(
+            store
+                .lookup_commitment(commitment(3).to_fixed_bytes())
+                .unwrap(),
+            Some(CommitmentLocation {
+                chain_id: TypedChainId::Evm(5002),
+                contract: other,
+                leaf_index: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn edges_should_keep_the_latest_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let target = webb_proposals::SubstrateTargetSystem::builder()
+            .pallet_index(44)
+            .tree_id(9)
+            .build();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::Substrate(target),
+            TypedChainId::Substrate(1080),
+        );
+        let edge = |src_chain_id: u64, latest_leaf_index: u32| AnchorEdge {
+            src_chain_id,
+            root: types::H256::from_low_u64_be(latest_leaf_index.into()),
+            latest_leaf_index,
+            block_number: 100 + u64::from(latest_leaf_index),
+        };
+        store.insert_edge(key, edge(5, 3)).unwrap();
+        store.insert_edge(key, edge(2, 1)).unwrap();
+        store.insert_edge(key, edge(5, 7)).unwrap();
+        // an older update of the same neighbor is ignored.
+        store.insert_edge(key, edge(5, 4)).unwrap();
+        assert_eq!(store.get_edges(key).unwrap(), vec![edge(2, 1), edge(5, 7)]);
+    }
+
+    #[test]
+    fn relayed_amounts_should_be_capped_over_the_window() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let (limit, hour) = (types::U256::from(100), 3_600);
+        let reserve = |amount: u64, now: u64| {
+            store
+                .reserve_relayed_amount(key, amount.into(), limit, hour, now)
+                .unwrap()
+        };
+        assert!(matches!(
+            reserve(60, 1_000),
+            AmountReservation::Reserved { .. }
+        ));
+        let second = reserve(30, 1_500);
+        assert!(matches!(second, AmountReservation::Reserved { .. }));
+        // frees up once the first amount leaves the window.
+        assert_eq!(
+            reserve(20, 2_000),
+            AmountReservation::Exceeded {
+                retry_after_secs: Some(2_600)
+            }
+        );
+        assert_eq!(
+            reserve(101, 2_000),
+            AmountReservation::Exceeded {
+                retry_after_secs: None
+            }
+        );
+        assert_eq!(
+            store.get_relayed_amount(key, hour, 2_000).unwrap(),
+            90.into()
+        );
+
+        // a failed withdrawal gives its amount back.
+        if let AmountReservation::Reserved { id } = second {
+            store.release_relayed_amount(key, id).unwrap();
+        }
+        assert!(matches!(
+            reserve(20, 2_000),
+            AmountReservation::Reserved { .. }
+        ));
+        assert_eq!(
+            store.get_relayed_amount(key, hour, 4_700).unwrap(),
+            20.into()
+        );
+    }
+
+    #[test]
+    fn final_leaves_should_stop_at_the_first_unconfirmed_leaf() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledStore::open(tmp.path()).unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf = |i: u32| (i, types::H256::from_low_u64_be(i.into()));
+        for (block, indices) in [(100, 0..2), (105, 2..3), (110, 3..5)] {
+            let leaves = indices
+                .map(|i| (i, leaf(i).1.to_fixed_bytes().to_vec()))
+                .collect::<Vec<_>>();
+            store
+                .insert_leaves_and_last_deposit_block_number(
+                    key, &leaves, block,
+                )
+                .unwrap();
+        }
+        let blocks = store.get_leaf_block_numbers(key, 1..4).unwrap();
+        assert_eq!(
+            blocks.into_iter().collect::<Vec<_>>(),
+            vec![(1, 100), (2, 105), (3, 110)]
+        );
+        let final_leaves = store
+            .get_final_leaves_with_range(key, 0..u32::MAX, 105)
+            .unwrap();
+        assert_eq!(
+            final_leaves.into_iter().collect::<Vec<_>>(),
+            vec![leaf(0), leaf(1), leaf(2)]
+        );
+        let all = store
+            .get_final_leaves_with_range(key, 0..u32::MAX, 200)
+            .unwrap();
+        assert_eq!(all.len(), 5);
+        assert!(store
+            .get_final_leaves_with_range(key, 0..u32::MAX, 99)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[tracing_test::traced_test]
+    fn commitments_cached_twice_should_be_reported() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let commitment = types::H256::repeat_byte(0xc);
+        let leaves =
+            [(0, commitment.to_fixed_bytes().to_vec()), (1, vec![1; 32])];
+        let insertion = store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 10)
+            .unwrap();
+        assert!(insertion.duplicates.is_empty());
+        // the same leaf again is skipped without a word.
+        let insertion = store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 10)
+            .unwrap();
+        assert!(insertion.duplicates.is_empty());
+        assert!(!logs_contain("two leaf indices"));
+
+        let again = [(2, commitment.to_fixed_bytes().to_vec())];
+        let insertion = store
+            .insert_leaves_and_last_deposit_block_number(key, &again, 11)
+            .unwrap();
+        assert_eq!(
+            insertion.duplicates,
+            vec![DuplicateCommitment {
+                commitment,
+                cached_index: 0,
+                index: 2,
+            }]
+        );
+        assert!(logs_contain(
+            "The same commitment got cached at two leaf indices"
+        ));
+        assert!(logs_contain("cached_index=0 index=2"));
+        assert_eq!(store.next_leaf_index(key).unwrap(), 3);
+    }
+
+    #[test]
+    fn commitments_cached_before_being_indexed_should_be_backfilled() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let commitment = types::H256::repeat_byte(0xc);
+        let leaves =
+            [(0, commitment.to_fixed_bytes().to_vec()), (1, vec![1; 32])];
         store
-            .insert_leaves_and_last_deposit_block_number(
-                history_store_key,
-                &generated_leaves,
-                block_number,
-            )
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 10)
             .unwrap();
-        let leaves = store
-            .get_leaves_with_range(history_store_key, 5..10)
+        // as cached by a relayer that did not index the commitments.
+        let history_key = HistoryStoreKey::from(key);
+        store
+            .db
+            .drop_tree(format!(
+                "leaf_commitment_indices/{}/{}",
+                history_key.chain_id(),
+                history_key.address()
+            ))
+            .unwrap();
+        store
+            .db
+            .drop_tree("leaf_commitment_indices_backfilled")
+            .unwrap();
+
+        let again = [(2, commitment.to_fixed_bytes().to_vec())];
+        let insertion = store
+            .insert_leaves_and_last_deposit_block_number(key, &again, 11)
             .unwrap();
-        assert_eq!(leaves.len(), 5);
         assert_eq!(
-            leaves
-                .values()
-                .map(|v| v.to_fixed_bytes().to_vec())
-                .collect::<Vec<_>>(),
-            generated_leaves
-                .into_iter()
-                .skip(5)
-                .take(5)
-                .map(|(_, v)| v)
-                .collect::<Vec<_>>()
+            insertion.duplicates,
+            vec![DuplicateCommitment {
+                commitment,
+                cached_index: 0,
+                index: 2,
+            }]
         );
     }
 
     #[test]
-    fn tx_queue_should_work() {
+    fn imported_leaves_should_be_indexed() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let commitment = types::H256::repeat_byte(0xc);
+        let leaves =
+            [(0, commitment.to_fixed_bytes().to_vec()), (1, vec![1; 32])];
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 10)
+            .unwrap();
+        let dump = store.export_leaf_cache().unwrap();
+
+        let imported = SledStore::temporary().unwrap();
+        imported.import_leaf_cache(&dump).unwrap();
+        // imported again over itself, the leaves are skipped.
+        imported.import_leaf_cache(&dump).unwrap();
+        let again = [(2, commitment.to_fixed_bytes().to_vec())];
+        let insertion = imported
+            .insert_leaves_and_last_deposit_block_number(key, &again, 11)
+            .unwrap();
+        assert_eq!(insertion.duplicates.len(), 1);
+        assert_eq!(insertion.duplicates[0].cached_index, 0);
+    }
+
+    #[test]
+    fn leaf_cache_should_be_exported_and_imported() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf = |i: u32| types::H256::from_low_u64_be(i.into());
+        for (block, indices) in [(100, 0..2), (105, 2..5)] {
+            let leaves = indices
+                .map(|i| (i, leaf(i).to_fixed_bytes().to_vec()))
+                .collect::<Vec<_>>();
+            store
+                .insert_leaves_and_last_deposit_block_number(
+                    key, &leaves, block,
+                )
+                .unwrap();
+        }
+        store.set_last_block_number(key, 120).unwrap();
+        let bytes = store.export_leaf_cache().unwrap().to_bytes();
+
+        let other = SledStore::temporary().unwrap();
+        let dump = LeafCacheDump::from_bytes(&bytes).unwrap();
+        other.import_leaf_cache(&dump).unwrap();
+        assert_eq!(
+            other.get_leaves(key).unwrap(),
+            store.get_leaves(key).unwrap()
+        );
+        assert_eq!(
+            other.get_leaf_block_numbers(key, 0..5).unwrap(),
+            store.get_leaf_block_numbers(key, 0..5).unwrap()
+        );
+        assert_eq!(other.get_last_deposit_block_number(key).unwrap(), 105);
+        assert_eq!(other.get_last_block_number(key, 0).unwrap(), 120);
+        assert_eq!(other.next_leaf_index(key).unwrap(), 5);
+
+        // a dump that does not match the cache is not imported at all.
+        let mut conflicting = dump.clone();
+        conflicting.contracts[0].leaves[1].leaf = types::H256::random();
+        conflicting.contracts[0].leaves.push(CachedLeaf {
+            index: 5,
+            leaf: leaf(5),
+            block_number: 130,
+        });
+        assert!(matches!(
+            other.import_leaf_cache(&conflicting),
+            Err(Error::LeafCacheCorrupted { index: 1, .. })
+        ));
+        assert_eq!(other.next_leaf_index(key).unwrap(), 5);
+    }
+
+    #[test]
+    fn missing_leaf_indices_should_be_detected() {
         let tmp = tempfile::tempdir().unwrap();
         let store = SledStore::open(tmp.path()).unwrap();
-        let chain_id = 1u32;
-        // it is now empty
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaves = (0..10u32)
+            .filter(|i| *i != 5)
+            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 42)
+            .unwrap();
+        assert_eq!(store.get_missing_leaf_indices(key, 10).unwrap(), vec![5]);
+        // leaves the chain has, but we did not see yet.
         assert_eq!(
+            store.get_missing_leaf_indices(key, 12).unwrap(),
+            vec![5, 10, 11]
+        );
+        assert!(store.get_missing_leaf_indices(key, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn shuffled_leaves_should_be_inserted_in_order() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf = |i: u32| types::H256::from_low_u64_be(u64::from(i) + 1);
+        // a permutation of 0..20, one leaf at a time.
+        for i in (0..20u32).map(|i| i * 7 % 20) {
             store
-                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
-                .unwrap(),
-            Option::<TypedTransaction>::None
+                .insert_leaves_and_last_deposit_block_number(
+                    key,
+                    &[(i, leaf(i).to_fixed_bytes().to_vec())],
+                    100 + u64::from(i),
+                )
+                .unwrap();
+            // the cache never has gaps.
+            let next = store.next_leaf_index(key).unwrap();
+            let cached = store.get_leaves(key).unwrap();
+            assert!(cached.into_keys().eq(0..next));
+        }
+        assert_eq!(store.next_leaf_index(key).unwrap(), 20);
+        assert!(store.get_pending_leaves(key).unwrap().is_empty());
+        let leaves = store.get_leaves(key).unwrap();
+        assert!(leaves.into_iter().eq((0..20).map(|i| (i, leaf(i)))));
+        // the buffered leaves keep the block they were seen at.
+        let blocks = store.get_leaf_block_numbers(key, 0..20).unwrap();
+        assert!(blocks
+            .into_iter()
+            .eq((0..20).map(|i| (i, 100 + u64::from(i)))));
+
+        // the same goes for the leaves of a single batch.
+        let other = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x22; 20]),
+            TypedChainId::Evm(1),
         );
+        let batch = (0..10u32)
+            .rev()
+            .map(|i| (i, leaf(i).to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        let outcome = store
+            .insert_leaves_and_last_deposit_block_number(other, &batch, 42)
+            .unwrap();
+        assert!(outcome.inserted.into_keys().eq(0..10));
+        assert!(outcome.pending.is_empty());
+        assert_eq!(store.next_leaf_index(other).unwrap(), 10);
+    }
 
-        let tx1: TypedTransaction = TransactionRequest::pay(
-            types::Address::random(),
-            types::U256::one(),
-        )
-        .from(types::Address::random())
-        .into();
+    #[test]
+    fn mismatched_leaves_should_not_overwrite_the_cache() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf = |i: u64| types::H256::from_low_u64_be(i).0.to_vec();
+        let leaves = (0..3u32).map(|i| (i, leaf(i.into()))).collect::<Vec<_>>();
         store
-            .enqueue_item(
-                SledQueueKey::from_evm_tx(chain_id, &tx1),
-                tx1.clone(),
-            )
+            .insert_leaves_and_last_deposit_block_number(key, &leaves, 10)
             .unwrap();
-        let tx2: TypedTransaction = TransactionRequest::pay(
-            types::Address::random(),
-            types::U256::one(),
-        )
-        .from(types::Address::random())
-        .into();
+        // seeing a leaf again is fine.
+        let outcome = store
+            .insert_leaves_and_last_deposit_block_number(key, &leaves[1..2], 11)
+            .unwrap();
+        assert_eq!(outcome, LeafInsertion::default());
+
+        let result = store.insert_leaves_and_last_deposit_block_number(
+            key,
+            &[(3, leaf(3)), (1, leaf(42))],
+            12,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::LeafCacheCorrupted { index: 1, .. })
+        ));
+        // the whole batch is rejected.
+        assert_eq!(store.next_leaf_index(key).unwrap(), 3);
+        let cached = store.get_leaves(key).unwrap();
+        assert_eq!(cached[&1], types::H256::from_low_u64_be(1));
+        assert_eq!(store.get_last_deposit_block_number(key).unwrap(), 11);
+    }
+
+    #[test]
+    fn too_many_pending_leaves_should_be_kept_and_reported() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf =
+            |i: u32| (i, types::H256::from_low_u64_be(i.into()).0.to_vec());
         store
-            .enqueue_item(
-                SledQueueKey::from_evm_tx(chain_id, &tx2),
-                tx2.clone(),
-            )
+            .insert_leaves_with_max_pending(key, &[leaf(0)], 10, 2)
+            .unwrap();
+        let outcome = store
+            .insert_leaves_with_max_pending(key, &[leaf(2), leaf(3)], 11, 2)
             .unwrap();
+        assert!(outcome.inserted.is_empty());
+        assert_eq!(outcome.pending, vec![2, 3]);
+        assert_eq!(outcome.missing_from, None);
 
-        // now let's dequeue transactions.
+        let outcome = store
+            .insert_leaves_with_max_pending(key, &[leaf(5)], 12, 2)
+            .unwrap();
+        assert_eq!(outcome.pending, vec![5]);
+        assert_eq!(outcome.missing_from, Some(1));
+        let pending = store.get_pending_leaves(key).unwrap();
+        assert!(pending.keys().copied().eq([2, 3, 5]));
+        assert_eq!(pending[&5].1, 12);
+        // only the leaves before the pending ones have to be fetched again.
+        assert_eq!(store.get_missing_leaf_indices(key, 6).unwrap(), vec![1, 4]);
+
+        let outcome = store
+            .insert_leaves_with_max_pending(key, &[leaf(1)], 10, 2)
+            .unwrap();
+        assert!(outcome.inserted.into_keys().eq(1..4));
+        assert_eq!(outcome.missing_from, None);
+        assert!(store.get_pending_leaves(key).unwrap().contains_key(&5));
+        let blocks = store.get_leaf_block_numbers(key, 0..4).unwrap();
+        assert!(blocks.into_values().eq([10, 10, 11, 11]));
+
+        store.remove_pending_leaves(key).unwrap();
+        assert!(store.get_pending_leaves(key).unwrap().is_empty());
+        assert_eq!(store.get_missing_leaf_indices(key, 6).unwrap(), vec![4, 5]);
+    }
+
+    #[test]
+    fn pending_leaves_should_not_be_overwritten() {
+        let store = SledStore::temporary().unwrap();
+        let key = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([0x11; 20]),
+            TypedChainId::Evm(1),
+        );
+        let leaf =
+            |i: u32| (i, types::H256::from_low_u64_be(i.into()).0.to_vec());
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &[leaf(2)], 10)
+            .unwrap();
+        // the same leaf again is skipped.
+        let outcome = store
+            .insert_leaves_and_last_deposit_block_number(key, &[leaf(2)], 11)
+            .unwrap();
+        assert!(outcome.pending.is_empty());
+
+        let result = store.insert_leaves_and_last_deposit_block_number(
+            key,
+            &[(2, leaf(42).1)],
+            12,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::LeafCacheCorrupted { index: 2, .. })
+        ));
+        let pending = store.get_pending_leaves(key).unwrap();
+        assert_eq!(pending[&2], (types::H256::from_low_u64_be(2), 10));
+    }
+
+    #[test]
+    fn scheduled_relays_should_be_taken_once() {
+        let store = SledStore::temporary().unwrap();
+        let relay = |execute_after| ScheduledRelay {
+            execute_after,
+            command: serde_json::json!({ "chainId": 5 }),
+        };
+        assert!(store.schedule_relay("a", relay(100)).unwrap());
+        assert!(store.schedule_relay("b", relay(200)).unwrap());
+        // the key is taken until the relay is submitted or cancelled.
+        assert!(!store.schedule_relay("a", relay(300)).unwrap());
         assert_eq!(
-            store
-                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
-                .unwrap(),
-            Some(tx1)
+            store.get_scheduled_relays().unwrap(),
+            vec![("a".to_string(), relay(100)), ("b".to_string(), relay(200))]
         );
+
+        assert_eq!(store.take_scheduled_relay("a").unwrap(), Some(relay(100)));
+        assert_eq!(store.take_scheduled_relay("a").unwrap(), None);
+        assert_eq!(store.get_scheduled_relays().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn relay_fingerprints_should_expire() {
+        let store = SledStore::temporary().unwrap();
+        let record = |expires_at| RelayFingerprint {
+            key: "a".to_string(),
+            expires_at,
+            tx_hash: None,
+        };
         assert_eq!(
             store
-                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .record_relay_fingerprint([1u8; 32], record(100), 10)
                 .unwrap(),
-            Some(tx2)
+            None
         );
-
-        let tx3: TypedTransaction = TransactionRequest::pay(
-            types::Address::random(),
-            types::U256::one(),
-        )
-        .from(types::Address::random())
-        .into();
         store
-            .enqueue_item(
-                SledQueueKey::from_evm_tx(chain_id, &tx3),
-                tx3.clone(),
-            )
+            .set_relay_fingerprint_tx([1u8; 32], types::H256::repeat_byte(2))
             .unwrap();
+        // the first relay is returned to its retries, until it expires.
+        let existing = store
+            .record_relay_fingerprint([1u8; 32], record(200), 50)
+            .unwrap()
+            .unwrap();
+        assert_eq!(existing.expires_at, 100);
+        assert_eq!(existing.tx_hash, Some(types::H256::repeat_byte(2)));
         assert_eq!(
             store
-                .peek_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .record_relay_fingerprint([1u8; 32], record(200), 100)
                 .unwrap(),
-            Some(tx3.clone())
+            None
         );
-        assert!(QueueStore::<TypedTransaction>::has_item(
-            &store,
-            SledQueueKey::from_evm_tx(chain_id, &tx3)
-        )
-        .unwrap());
-        let _: Option<TypedTransaction> = store
-            .remove_item(SledQueueKey::from_evm_tx(chain_id, &tx3))
+
+        store
+            .record_relay_fingerprint([3u8; 32], record(150), 100)
             .unwrap();
-        assert!(!QueueStore::<TypedTransaction>::has_item(
-            &store,
-            SledQueueKey::from_evm_tx(chain_id, &tx3)
-        )
-        .unwrap());
+        assert_eq!(store.prune_relay_fingerprints(150).unwrap(), 1);
+        store.remove_relay_fingerprint([1u8; 32]).unwrap();
         assert_eq!(
             store
-                .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))
+                .record_relay_fingerprint([1u8; 32], record(300), 150)
                 .unwrap(),
-            Option::<TypedTransaction>::None
+            None
         );
     }
 
     #[test]
-    fn events_hash_should_work() {
+    fn in_flight_txs_should_be_kept_per_chain() {
         let tmp = tempfile::tempdir().unwrap();
         let store = SledStore::open(tmp.path()).unwrap();
+        let in_flight = |byte| InFlightTx {
+            tx_hash: types::H256::repeat_byte(byte),
+            tx: types::TransactionRequest::new().nonce(byte).into(),
+        };
+        store.record_in_flight_tx(5, in_flight(1)).unwrap();
+        store.record_in_flight_tx(5, in_flight(2)).unwrap();
+        store.record_in_flight_tx(6, in_flight(3)).unwrap();
+        assert_eq!(
+            store.get_in_flight_txs(5, 10).unwrap(),
+            vec![in_flight(1), in_flight(2)]
+        );
+        assert_eq!(store.get_in_flight_txs(5, 1).unwrap(), vec![in_flight(1)]);
 
-        let events = (0..20)
-            .map(|_| NewNullifierFilter {
-                nullifier: types::H256::random().to_fixed_bytes().into(),
-            })
-            .collect::<Vec<_>>();
-
-        for event in &events {
-            let event_bytes = serde_json::to_vec(&event).unwrap();
-            // check if the event is already in the store
-            assert!(!store.contains_event(&event_bytes).unwrap());
-            // add the event
-            store.store_event(&event_bytes).unwrap();
-        }
-
-        for event in &events {
-            let event_bytes = serde_json::to_vec(&event).unwrap();
-            // check if the event is in the store
-            assert!(store.contains_event(&event_bytes).unwrap());
-            // remove the event
-            store.delete_event(&event_bytes).unwrap();
-        }
+        store
+            .remove_in_flight_tx(5, types::H256::repeat_byte(1))
+            .unwrap();
+        drop(store);
+        // they survive restarts.
+        let store = SledStore::open(tmp.path()).unwrap();
+        assert_eq!(store.get_in_flight_txs(5, 10).unwrap(), vec![in_flight(2)]);
+        assert_eq!(store.get_in_flight_txs(6, 10).unwrap(), vec![in_flight(3)]);
     }
 
     #[test]
-    fn insert_leaves_and_last_deposit_block_number_should_work() {
+    fn revert_retries_should_be_kept_per_chain() {
         let tmp = tempfile::tempdir().unwrap();
         let store = SledStore::open(tmp.path()).unwrap();
-        let chain_id = 1u32;
-        let contract =
-            types::H160::from_slice("11111111111111111111".as_bytes());
-        let history_store_key = (
-            TypedChainId::Evm(chain_id),
-            TargetSystem::new_contract_address(contract.to_fixed_bytes()),
+        let retry = RevertRetry {
+            retries: 2,
+            retry_at: 100,
+        };
+        store.record_revert_retry(5, [1u8; 32], retry).unwrap();
+        assert_eq!(store.get_revert_retry(6, [1u8; 32]).unwrap(), None);
+        drop(store);
+        // they survive restarts.
+        let store = SledStore::open(tmp.path()).unwrap();
+        assert_eq!(store.get_revert_retry(5, [1u8; 32]).unwrap(), Some(retry));
+        store.remove_revert_retry(5, [1u8; 32]).unwrap();
+        assert_eq!(store.get_revert_retry(5, [1u8; 32]).unwrap(), None);
+    }
+
+    #[test]
+    fn proposal_events_should_be_resumed_after_a_cursor() {
+        let store = SledStore::temporary().unwrap();
+        let resource_id = webb_proposals::ResourceId::new(
+            TargetSystem::new_contract_address([1u8; 20]),
+            TypedChainId::Evm(5),
         );
-        let generated_leaves = (0..2u32)
-            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
-            .collect::<Vec<_>>();
-        let block_number = 20u64;
-        let default_block_number = 1u64;
-        store
-            .insert_leaves_and_last_deposit_block_number(
-                history_store_key,
-                &generated_leaves,
-                block_number,
+        let mut data = resource_id.to_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 4]);
+        data.extend_from_slice(&7u32.to_be_bytes());
+        data.extend_from_slice(&[2u8; 32]);
+        data.extend_from_slice(
+            &webb_proposals::ResourceId::new(
+                TargetSystem::new_contract_address([3u8; 20]),
+                TypedChainId::Evm(1),
             )
-            .unwrap();
-        let leaves = store
-            .get_leaves_with_range(history_store_key, 0..2)
-            .unwrap();
-        assert_eq!(leaves.len(), 2);
-        assert_eq!(
-            leaves
-                .values()
-                .map(|v| v.to_fixed_bytes().to_vec())
-                .collect::<Vec<_>>(),
-            generated_leaves
-                .into_iter()
-                .map(|(_, v)| v)
-                .collect::<Vec<_>>()
+            .to_bytes(),
         );
+        let created =
+            ProposalEvent::new(ProposalEventKind::Created, &data).unwrap();
+        assert_eq!(created.nonce, 7);
+        assert_eq!(created.src_chain_id, Some(1));
+        assert_eq!(created.dest_chain_id, 5);
+        assert_eq!(created.target_address, Some([1u8; 20].into()));
+
+        let mut live = store.subscribe_proposal_events();
+        let first = store.record_proposal_event(created.clone()).unwrap();
+        let executed =
+            created.clone().with_tx(ProposalEventKind::Executed, None);
+        store.link_proposal_call([9u8; 32], executed).unwrap();
+        // the execution is recorded once the call gets mined.
+        store.mark_execution([9u8; 32], [4u8; 32].into()).unwrap();
+        assert_eq!(live.try_recv().unwrap(), first);
+        let second = live.try_recv().unwrap();
+        assert_eq!(second.seq, first.seq + 1);
+        assert_eq!(second.event.kind, ProposalEventKind::Executed);
+        assert_eq!(second.event.tx_hash, Some([4u8; 32].into()));
+
         assert_eq!(
-            store
-                .get_last_deposit_block_number(history_store_key)
-                .unwrap(),
-            block_number
+            store.get_proposal_events_after(0, 10).unwrap(),
+            vec![first.clone(), second.clone()]
         );
         assert_eq!(
-            store
-                .get_last_block_number(history_store_key, default_block_number)
-                .unwrap(),
-            block_number
+            store.get_proposal_events_after(first.seq, 10).unwrap(),
+            vec![second]
         );
+        assert_eq!(store.get_proposal_events_after(0, 1).unwrap(), vec![first]);
+        assert_eq!(store.last_proposal_event_seq().unwrap(), 2);
+
+        // a call that never gets executed is forgotten after a while.
+        store.link_proposal_call([8u8; 32], created).unwrap();
+        let now = unix_timestamp();
+        assert_eq!(store.prune_proposal_calls(now).unwrap(), 0);
+        let expired = now + PROPOSAL_CALL_TTL_SECS;
+        assert_eq!(store.prune_proposal_calls(expired).unwrap(), 1);
+        store.mark_execution([8u8; 32], [4u8; 32].into()).unwrap();
+        assert_eq!(store.last_proposal_event_seq().unwrap(), 2);
     }
 }
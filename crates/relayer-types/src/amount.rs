@@ -0,0 +1,88 @@
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+
+/// A token amount, in the smallest unit of the token.
+///
+/// Deserialized from a decimal string, a `0x` prefixed hex string or an
+/// integer, since amounts in wei rarely fit in the integers of the config
+/// formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount(U256);
+
+impl From<U256> for Amount {
+    fn from(amount: U256) -> Self {
+        Self(amount)
+    }
+}
+
+impl std::ops::Deref for Amount {
+    type Target = U256;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct AmountVisitor;
+        impl<'de> serde::de::Visitor<'de> for AmountVisitor {
+            type Value = U256;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter.write_str(
+                    "an amount as an integer, a decimal string or a hex string",
+                )
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(value.into())
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                u64::try_from(value).map(Into::into).map_err(|_| {
+                    serde::de::Error::custom("an amount can not be negative")
+                })
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let parsed = match value.strip_prefix("0x") {
+                    Some(hex) => U256::from_str_radix(hex, 16),
+                    None => U256::from_dec_str(value),
+                };
+                parsed.map_err(|e| {
+                    serde::de::Error::custom(format!(
+                        "invalid amount {value}: {e:?}"
+                    ))
+                })
+            }
+        }
+
+        let amount = deserializer.deserialize_any(AmountVisitor)?;
+        Ok(Self(amount))
+    }
+}
@@ -1,3 +1,4 @@
+pub mod amount;
 pub mod etherscan_api;
 pub mod mnemonic;
 pub mod private_key;
@@ -0,0 +1,204 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Time and randomness, injected so tests can control them.
+//!
+//! The relayer uses [`SystemClock`] and a thread local rng; the tests use
+//! [`test_utils::FrozenClock`] and [`RelayerRng::seeded`] so the delays and
+//! random picks they go through are deterministic, and take no real time.
+
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::BoxFuture;
+use rand::distributions::uniform::{SampleRange, SampleUniform};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A source of the current time, and of delays.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+    /// Returns a future completing once `duration` has passed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+    /// Returns the current time, as secs since the UNIX epoch.
+    fn unix_timestamp(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs()
+    }
+}
+
+/// A clock shared by the relayer tasks.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real clock, delegating to `std` and `tokio`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// Returns the system clock, as a [`SharedClock`].
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A cheaply cloneable source of randomness.
+///
+/// It uses the thread local rng, unless it was [`seeded`](Self::seeded).
+#[derive(Debug, Clone, Default)]
+pub struct RelayerRng {
+    seeded: Option<Arc<Mutex<StdRng>>>,
+}
+
+impl RelayerRng {
+    /// Creates an rng producing the same values for the same `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seeded: Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))),
+        }
+    }
+
+    /// Returns a random value in `range`.
+    ///
+    /// # Panics
+    ///
+    /// If the range is empty.
+    pub fn gen_range<T, R>(&self, range: R) -> T
+    where
+        T: SampleUniform,
+        R: SampleRange<T>,
+    {
+        match &self.seeded {
+            Some(rng) => rng
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .gen_range(range),
+            None => rand::thread_rng().gen_range(range),
+        }
+    }
+
+    /// Returns a random duration of `range` millis.
+    pub fn gen_millis(&self, range: RangeInclusive<u64>) -> Duration {
+        Duration::from_millis(self.gen_range(range))
+    }
+}
+
+/// Clocks for tests.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils {
+    use super::*;
+    use tokio::sync::watch;
+
+    /// A clock that only moves when told to, with [`FrozenClock::advance`].
+    ///
+    /// Sleeps complete once the clock was advanced past their deadline, so
+    /// a test can go through minutes of delays in a few millis.
+    #[derive(Debug, Clone)]
+    pub struct FrozenClock {
+        now: Arc<watch::Sender<SystemTime>>,
+    }
+
+    impl FrozenClock {
+        /// Creates a clock stopped at `now`.
+        pub fn new(now: SystemTime) -> Self {
+            let (tx, _) = watch::channel(now);
+            Self { now: Arc::new(tx) }
+        }
+
+        /// Moves the clock forward by `duration`, waking up the sleeps
+        /// due by then.
+        pub fn advance(&self, duration: Duration) {
+            self.now.send_modify(|now| *now += duration);
+        }
+
+        /// Returns this clock, as a [`SharedClock`].
+        pub fn shared(&self) -> SharedClock {
+            Arc::new(self.clone())
+        }
+    }
+
+    impl Default for FrozenClock {
+        /// A clock stopped at 2023-01-01T00:00:00Z.
+        fn default() -> Self {
+            Self::new(UNIX_EPOCH + Duration::from_secs(1_672_531_200))
+        }
+    }
+
+    impl Clock for FrozenClock {
+        fn now(&self) -> SystemTime {
+            *self.now.borrow()
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            let deadline = self.now() + duration;
+            let mut rx = self.now.subscribe();
+            Box::pin(async move {
+                while *rx.borrow_and_update() < deadline {
+                    if rx.changed().await.is_err() {
+                        // the clock is gone, it will never get there.
+                        futures::future::pending::<()>().await;
+                    }
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::FrozenClock;
+    use super::*;
+
+    #[tokio::test]
+    async fn frozen_clock_sleeps_until_advanced_past_the_deadline() {
+        let clock = FrozenClock::default();
+        let start = clock.unix_timestamp();
+        let sleep = tokio::spawn(clock.sleep(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(59));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(1));
+        tokio::time::timeout(Duration::from_secs(1), sleep)
+            .await
+            .expect("the sleep should be over")
+            .unwrap();
+        assert_eq!(clock.unix_timestamp(), start + 60);
+    }
+
+    #[test]
+    fn seeded_rng_is_deterministic() {
+        let a = RelayerRng::seeded(42);
+        let b = RelayerRng::seeded(42);
+        let picks_a: Vec<u64> = (0..8).map(|_| a.gen_range(0..1000)).collect();
+        let picks_b: Vec<u64> = (0..8).map(|_| b.gen_range(0..1000)).collect();
+        assert_eq!(picks_a, picks_b);
+        assert!(picks_a.iter().all(|v| *v < 1000));
+    }
+}
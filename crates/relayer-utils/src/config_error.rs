@@ -0,0 +1,96 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// An error in a config file, at the line and column it was found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigParseError {
+    /// The config file with the error.
+    pub file_path: PathBuf,
+    /// The line of the error, starting from 1.
+    pub line: usize,
+    /// The column of the error, starting from 1.
+    pub column: usize,
+    /// The line of the error, with the lines right before and after it.
+    pub snippet: String,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl ConfigParseError {
+    /// Creates the error at `line` and `column` of the config file, which
+    /// has the given `contents`.
+    pub fn new(
+        file_path: impl Into<PathBuf>,
+        contents: &str,
+        line: usize,
+        column: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        let line = line.max(1);
+        let snippet = contents
+            .lines()
+            .skip(Self::first_snippet_line(line) - 1)
+            .take(line + 2 - Self::first_snippet_line(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            file_path: file_path.into(),
+            line,
+            column: column.max(1),
+            snippet,
+            message: message.into(),
+        }
+    }
+
+    /// The line the snippet starts at.
+    fn first_snippet_line(line: usize) -> usize {
+        line.saturating_sub(1).max(1)
+    }
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = Self::first_snippet_line(self.line);
+        let last = first + self.snippet.lines().count().saturating_sub(1);
+        let width = last.to_string().len();
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(
+            f,
+            "{:width$}--> {}:{}:{}",
+            "",
+            self.file_path.display(),
+            self.line,
+            self.column,
+        )?;
+        write!(f, "{:width$} |", "")?;
+        for (number, text) in (first..).zip(self.snippet.lines()) {
+            write!(f, "\n{number:>width$} | {text}")?;
+            if number == self.line {
+                write!(
+                    f,
+                    "\n{:width$} | {:>col$}",
+                    "",
+                    "^",
+                    col = self.column
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigParseError {}
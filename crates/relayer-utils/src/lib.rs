@@ -20,7 +20,15 @@ use multi_provider::MultiProvider;
 use webb::{evm::ethers, substrate::subxt};
 use webb_proposals::ResourceId;
 
+pub use config_error::ConfigParseError;
+
+/// Checking the tokens of the admin endpoints.
+pub mod auth;
 pub mod clickable_link;
+/// Time and randomness, injected so tests can control them.
+pub mod clock;
+/// Errors in the config files, pointing at where they are.
+pub mod config_error;
 
 /// Metrics functionality
 pub mod metric;
@@ -167,6 +175,9 @@ pub enum Error {
     /// Ethers currency conversion error
     #[error(transparent)]
     Conversion(#[from] ethers::utils::ConversionError),
+    /// Failed to decode an ABI encoded value, such as an event log.
+    #[error(transparent)]
+    EthersAbi(#[from] ethers::abi::Error),
     /// Failed to convert string to float
     #[error(transparent)]
     ParseFloatError(#[from] std::num::ParseFloatError),
@@ -184,6 +195,60 @@ pub enum Error {
     /// Error while parsing the config files.
     #[error("Config parse error: {}", _0)]
     ParseConfig(#[from] serde_path_to_error::Error<config::ConfigError>),
+    /// An error at a known line of a config file.
+    #[error(transparent)]
+    ConfigParse(#[from] ConfigParseError),
+    /// The config uses deprecated keys, or ignored settings, while
+    /// `strict-config` is on.
+    #[error("Config needs migrating (strict-config is on):\n{}", _0)]
+    StrictConfig(String),
+    /// Some configured contracts have no code, while `fail-fast-startup`
+    /// is on.
+    #[error("Contracts not deployed (fail-fast-startup is on):\n{}", _0)]
+    UndeployedContracts(String),
+    /// A config reload changes settings that are only read at startup.
+    #[error(
+        "Restart the relayer to change {}, the config was not reloaded",
+        _0
+    )]
+    RestartRequired(String),
+    /// A VAnchor lists itself, or the same anchor twice, as linked anchor.
+    #[error("Invalid linked-anchors of {}: {}", anchor, reason)]
+    InvalidLinkedAnchors {
+        /// The anchor, as configured.
+        anchor: String,
+        /// What is wrong with its linked anchors.
+        reason: &'static str,
+    },
+    /// A contract synced as `static` has relaying features configured.
+    #[error("Invalid static contract {}: {}", contract, reason)]
+    InvalidStaticContract {
+        /// The contract, as configured.
+        contract: String,
+        /// The relaying feature configured for it.
+        reason: &'static str,
+    },
+    /// The `event-type-allowlist` of a contract names an event that is not
+    /// in its ABI, none of its events would ever be handled.
+    #[error(
+        "Unknown event type {} in the event-type-allowlist of {}",
+        event_type,
+        contract
+    )]
+    UnknownEventType {
+        /// The contract, as configured.
+        contract: String,
+        /// The event type missing from the ABI.
+        event_type: String,
+    },
+    /// The relayer could not listen on the configured address.
+    #[error("Could not listen on {}: {}", address, reason)]
+    Listen {
+        /// The address, as configured.
+        address: String,
+        /// Why it could not be used.
+        reason: String,
+    },
     /// EVM Chain not found.
     #[error("Chain Not Found: {}", chain_id)]
     ChainNotFound {
@@ -231,14 +296,61 @@ pub enum Error {
     /// Invalid Merkle root
     #[error("Invalid Merkle root at index {}", _0)]
     InvalidMerkleRootError(u32),
+    /// A leaf does not match the one already cached at the same index.
+    #[error(
+        "Leaf cache corrupted at index {index}: cached {cached}, got {got}"
+    )]
+    LeafCacheCorrupted {
+        /// The index of the leaf.
+        index: u32,
+        /// The hex encoded cached leaf.
+        cached: String,
+        /// The hex encoded leaf that was being inserted.
+        got: String,
+    },
+    /// Too many leaves are waiting for the missing ones before them.
+    #[error("Missing leaves from index {from}, {pending} leaves pending")]
+    MissingLeaves {
+        /// The first missing leaf index.
+        from: u32,
+        /// The number of leaves pending after the missing ones.
+        pending: usize,
+    },
     /// Missing Static Transaction Validation Details
     /// This error is raised when the static transaction validation details
     /// are missing.
     #[error("Missing Substrate Static Transaction Validation Details")]
     MissingValidationDetails,
+    /// The event watcher made no progress for too long, and got restarted.
+    #[error("Event watcher made no progress for {0:?}")]
+    WatcherStalled(std::time::Duration),
     /// Provider not found error.
     #[error("Provider not found for index {0}")]
     ProviderNotFound(usize),
+    /// The provider answering a logs query lags behind the queried blocks.
+    #[error("Provider at block {head} lags behind the queried block {to}")]
+    ProviderLagging {
+        /// The head block of the provider.
+        head: u64,
+        /// The last block of the query.
+        to: u64,
+    },
+    /// The deployment transaction of a contract does not match its config.
+    #[error(
+        "Contract {address:?} was not deployed by {tx_hash:?} at block \
+        {deployed_at}: {reason}, check its deployed-at and \
+        deployment-tx-hash"
+    )]
+    DeploymentMismatch {
+        /// The address of the contract.
+        address: ethers::types::Address,
+        /// The configured deployment transaction.
+        tx_hash: ethers::types::H256,
+        /// The configured deployment block.
+        deployed_at: u64,
+        /// What does not match.
+        reason: String,
+    },
 }
 
 /// A type alias for the result for webb relayer, that uses the `Error` enum.
@@ -12,12 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use prometheus::core::{AtomicF64, GenericCounter, GenericGauge};
 use prometheus::labels;
 use prometheus::opts;
-use prometheus::{register_counter, register_gauge, Encoder, TextEncoder};
+use prometheus::{
+    register_counter_vec_with_registry, register_counter_with_registry,
+    register_gauge_vec_with_registry, register_gauge_with_registry, CounterVec,
+    Encoder, GaugeVec, Opts, Registry, TextEncoder,
+};
+use serde::{Deserialize, Serialize};
+use webb::evm::ethers::types::Address;
 use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
 
 /// A struct for collecting metrics for particular resource.
@@ -29,6 +36,190 @@ pub struct ResourceMetric {
     pub total_fee_earned: GenericCounter<AtomicF64>,
 }
 
+/// The metrics of a single EVM chain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainMetrics {
+    /// The chain id.
+    pub chain_id: u32,
+    /// The number of transactions sent by the tx queue.
+    pub tx_sent_total: u64,
+    /// The number of sent transactions that got confirmed.
+    pub tx_confirmed_total: u64,
+    /// The number of sent transactions that got reverted.
+    pub tx_reverted_total: u64,
+    /// The average time from sending a transaction to its confirmation.
+    pub average_confirmation_time_secs: f64,
+    /// The latest block number seen by the events watchers.
+    pub current_block_number: u64,
+    /// The number of events the events watchers went through.
+    pub events_processed_total: u64,
+}
+
+/// An update of the [`ChainMetrics`] of a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainMetricsUpdate {
+    /// A transaction got sent.
+    TxSent,
+    /// A sent transaction got confirmed.
+    TxConfirmed {
+        /// The time from sending the transaction to its confirmation.
+        confirmation_time: Duration,
+    },
+    /// A sent transaction got reverted.
+    TxReverted,
+    /// An events watcher went through a step.
+    EventsProcessed {
+        /// The number of events found during the step.
+        count: u64,
+        /// The latest block number of the chain.
+        current_block_number: u64,
+    },
+}
+
+impl ChainMetrics {
+    /// Creates the metrics of a chain, with nothing recorded yet.
+    pub fn new(chain_id: u32) -> Self {
+        Self {
+            chain_id,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the update to the metrics.
+    pub fn apply(&mut self, update: ChainMetricsUpdate) {
+        match update {
+            ChainMetricsUpdate::TxSent => self.tx_sent_total += 1,
+            ChainMetricsUpdate::TxConfirmed { confirmation_time } => {
+                self.tx_confirmed_total += 1;
+                // a running average, there is no need to keep the times.
+                let delta = confirmation_time.as_secs_f64()
+                    - self.average_confirmation_time_secs;
+                self.average_confirmation_time_secs +=
+                    delta / self.tx_confirmed_total as f64;
+            }
+            ChainMetricsUpdate::TxReverted => self.tx_reverted_total += 1,
+            ChainMetricsUpdate::EventsProcessed {
+                count,
+                current_block_number,
+            } => {
+                self.events_processed_total += count;
+                self.current_block_number =
+                    self.current_block_number.max(current_block_number);
+            }
+        }
+    }
+}
+
+/// The label value the label sets over the cap of a metric family are
+/// counted under.
+pub const OVERFLOW_LABEL: &str = "other";
+
+/// The labels the metrics of the watched contracts are exported with, from
+/// the coarsest to the finest.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricLabels {
+    /// By chain only.
+    Chain,
+    /// By chain and contract.
+    #[default]
+    ChainContract,
+    /// By chain, contract and method, or event type.
+    ChainContractMethod,
+}
+
+impl MetricLabels {
+    /// The names of the labels.
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            Self::Chain => &["chain_id"],
+            Self::ChainContract => &["chain_id", "contract"],
+            Self::ChainContractMethod => &["chain_id", "contract", "method"],
+        }
+    }
+}
+
+/// How many label sets the metrics of the watched contracts may have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardinalityPolicy {
+    /// The labels of the metrics.
+    pub labels: MetricLabels,
+    /// The most label sets of a metric family, the ones over it are
+    /// counted under a label set of [`OVERFLOW_LABEL`] values.
+    pub max_label_sets: usize,
+}
+
+impl Default for CardinalityPolicy {
+    fn default() -> Self {
+        Self {
+            labels: MetricLabels::default(),
+            max_label_sets: 1000,
+        }
+    }
+}
+
+/// A counter of the watched contracts, keeping its label sets under the cap
+/// of its [`CardinalityPolicy`].
+#[derive(Debug, Clone)]
+pub struct ContractCounter {
+    counter: CounterVec,
+    policy: CardinalityPolicy,
+    label_sets: HashSet<Vec<String>>,
+}
+
+impl ContractCounter {
+    /// Creates the counter, with its overflow label set already there so
+    /// that the family shows up from the first scrape.
+    pub fn new(
+        opts: Opts,
+        policy: CardinalityPolicy,
+    ) -> Result<Self, prometheus::Error> {
+        let counter = CounterVec::new(opts, policy.labels.names())?;
+        let overflow = vec![OVERFLOW_LABEL; policy.labels.names().len()];
+        counter.with_label_values(&overflow);
+        Ok(Self {
+            counter,
+            policy,
+            label_sets: HashSet::new(),
+        })
+    }
+
+    /// The underlying counter, to be registered.
+    pub fn counter(&self) -> &CounterVec {
+        &self.counter
+    }
+
+    /// Counts one more for the contract of the chain, and its method if
+    /// the method is one of the labels.
+    pub fn inc(&mut self, chain_id: u32, contract: Address, method: &str) {
+        let labels = self.label_values(chain_id, contract, method);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.counter.with_label_values(&labels).inc();
+    }
+
+    fn label_values(
+        &mut self,
+        chain_id: u32,
+        contract: Address,
+        method: &str,
+    ) -> Vec<String> {
+        let mut values =
+            vec![chain_id.to_string(), hex::encode(contract), method.into()];
+        values.truncate(self.policy.labels.names().len());
+        if self.label_sets.contains(&values) {
+            return values;
+        }
+        if self.label_sets.len() < self.policy.max_label_sets {
+            self.label_sets.insert(values.clone());
+            return values;
+        }
+        vec![OVERFLOW_LABEL.into(); values.len()]
+    }
+}
+
 /// A struct definition for collecting metrics in the relayer.
 #[derive(Debug, Clone)]
 pub struct Metrics {
@@ -58,79 +249,365 @@ pub struct Metrics {
     pub gas_spent: GenericCounter<AtomicF64>,
     /// Total amount of data stored metric
     pub total_amount_of_data_stored: GenericGauge<AtomicF64>,
+    /// How many times gaps were found in the cached leaf indices
+    pub leaf_index_gaps_detected: GenericCounter<AtomicF64>,
+    /// How many missing leaves got fetched from the chain to fill the leaf cache
+    pub leaves_reconciled: GenericCounter<AtomicF64>,
+    /// How many commitments got cached at a second leaf index
+    pub duplicate_commitments: GenericCounter<AtomicF64>,
+    /// How many event watchers delayed their startup, to stagger their first queries
+    pub watcher_start_delay_applied: GenericCounter<AtomicF64>,
+    /// How many proposal executions were sent in multicall batches
+    pub proposals_batched: GenericCounter<AtomicF64>,
+    /// How many contracts got loaded with a custom ABI
+    pub custom_abi_loaded: GenericCounter<AtomicF64>,
+    /// How many transactions used a recommended gas limit instead of an estimate
+    pub using_recommended_gas_limit: GenericCounter<AtomicF64>,
+    /// How many transactions used a gas limit override from the config
+    pub using_gas_limit_override: GenericCounter<AtomicF64>,
+    /// How many anchor updates got skipped, since the linked anchor already had the root
+    pub skipped_already_updated: GenericCounter<AtomicF64>,
+    /// How many stale edges of the linked anchors got found, and proposed an update for
+    pub cross_chain_stale_edges_detected: GenericCounter<AtomicF64>,
+    /// How many times an event watcher got restarted by its watchdog, after making no progress
+    pub event_watcher_stalls: GenericCounter<AtomicF64>,
+    /// How many edges got added to the linkable trees of Substrate chains
+    pub edge_additions_processed: GenericCounter<AtomicF64>,
+    /// How many times the tx queue held its transactions, since the gas price was too high
+    pub transactions_deferred_high_gas: GenericCounter<AtomicF64>,
+    /// How many proposal events the websocket subscribers missed, since they fell behind
+    pub proposal_events_dropped: GenericCounter<AtomicF64>,
+    /// How many events the event watchers skipped, since their type is not in the allowlist
+    pub unhandled_event_types: GenericCounter<AtomicF64>,
     /// Resource metric
     resource_metric_map: HashMap<ResourceId, ResourceMetric>,
-    /// Metric for account balance (in gwei) on specific chain
+    /// Metric for account balance on specific chain, in gwei on the EVM
+    /// chains and in the native unit on the Substrate ones
     account_balance: HashMap<TypedChainId, GenericGauge<AtomicF64>>,
+    /// Metric for the nonce of the relayer account on specific chain
+    account_nonce: HashMap<TypedChainId, GenericGauge<AtomicF64>>,
+    /// Number of anchor update proposals tracked by the store
+    pub store_proposal_count: GenericGauge<AtomicF64>,
+    /// Number of bytes pending to be persisted by the store
+    pub store_pending_writes: GenericGauge<AtomicF64>,
+    /// Number of configured contracts without code, found at startup
+    pub undeployed_contracts: GenericGauge<AtomicF64>,
+    /// RPC calls served by each EVM provider, by chain, provider label and
+    /// kind of call
+    pub provider_calls: CounterVec,
+    /// Failed RPC calls of each EVM provider, by chain and provider label
+    pub provider_errors: CounterVec,
+    /// Transactions sent by the tx queue, by chain
+    pub chain_tx_sent: CounterVec,
+    /// Transactions confirmed, by chain
+    pub chain_tx_confirmed: CounterVec,
+    /// Transactions reverted, by chain
+    pub chain_tx_reverted: CounterVec,
+    /// Average time from sending a transaction to its confirmation, by chain
+    pub chain_average_confirmation_time_secs: GaugeVec,
+    /// Latest block number seen by the events watchers, by chain
+    pub chain_current_block_number: GaugeVec,
+    /// Events the events watchers went through, by chain
+    pub chain_events_processed: CounterVec,
+    /// Proposals enqueued and not executed yet, by chain
+    pub concurrent_proposals_in_flight: GaugeVec,
+    /// Events handled by the events watchers, by contract
+    contract_events_handled: ContractCounter,
+    /// Transactions sent by the tx queue, by contract
+    contract_tx_sent: ContractCounter,
+    /// Metric for the number of queued items on specific chain
+    store_queue_depth: HashMap<u32, GenericGauge<AtomicF64>>,
+    /// Metric for the number of cached leaves of specific contract
+    store_leaf_count: HashMap<(TypedChainId, Address), GenericGauge<AtomicF64>>,
+    /// Metric for the share of the gas limit override used by the last call
+    /// of a method on specific chain
+    gas_limit_override_usage: HashMap<(u32, String), GenericGauge<AtomicF64>>,
+    /// The registry of all the metrics above, so that every relayer context
+    /// gathers its own metrics
+    registry: Registry,
 }
 
 impl Metrics {
     /// Instantiates the various metrics and their counters, also creates a registry for the counters and
     /// registers the counters
     pub fn new() -> Result<Self, prometheus::Error> {
-        let bridge_watcher_back_off = register_counter!(
+        Self::with_policy(CardinalityPolicy::default())
+    }
+
+    /// Same as [`Metrics::new`], with the labels of the metrics of the
+    /// watched contracts kept in check by `policy`.
+    pub fn with_policy(
+        policy: CardinalityPolicy,
+    ) -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let bridge_watcher_back_off = register_counter_with_registry!(
             "bridge_watcher_back_off",
-            "specifies how many times the bridge watcher backed off"
+            "specifies how many times the bridge watcher backed off",
+            &registry,
         )?;
 
-        let total_transaction_made = register_counter!(
+        let total_transaction_made = register_counter_with_registry!(
             "total_transaction_made",
             "The total number of transaction made",
+            &registry,
         )?;
 
-        let anchor_update_proposals = register_counter!(
+        let anchor_update_proposals = register_counter_with_registry!(
             "anchor_update_proposals",
             "The total number of anchor update proposal proposed by relayer",
+            &registry,
         )?;
 
-        let proposals_signed = register_counter!(
+        let proposals_signed = register_counter_with_registry!(
             "proposals_signed",
             "The total number of proposal signed by dkg/mocked backend",
+            &registry,
         )?;
 
-        let proposals_processed_tx_queue = register_counter!(
+        let proposals_processed_tx_queue = register_counter_with_registry!(
             "proposals_processed_tx_queue",
             "Total number of signed proposals processed by transaction queue",
+            &registry,
         )?;
 
-        let proposals_processed_substrate_tx_queue = register_counter!(
+        let proposals_processed_substrate_tx_queue = register_counter_with_registry!(
             "proposals_processed_substrate_tx_queue",
             "Total number of signed proposals processed by substrate transaction queue",
+            &registry,
         )?;
 
-        let proposals_processed_evm_tx_queue = register_counter!(
+        let proposals_processed_evm_tx_queue = register_counter_with_registry!(
             "proposals_processed_evm_tx_queue",
             "Total number of signed proposals processed by evm transaction queue",
+            &registry,
         )?;
 
-        let transaction_queue_back_off = register_counter!(
+        let transaction_queue_back_off = register_counter_with_registry!(
             "transaction_queue_back_off",
             "How many times the transaction queue backed off",
+            &registry,
         )?;
 
-        let substrate_transaction_queue_back_off = register_counter!(
+        let substrate_transaction_queue_back_off = register_counter_with_registry!(
             "substrate_transaction_queue_back_off",
             "How many times the substrate transaction queue backed off",
+            &registry,
         )?;
 
-        let evm_transaction_queue_back_off = register_counter!(
+        let evm_transaction_queue_back_off = register_counter_with_registry!(
             "evm_transaction_queue_back_off",
             "How many times the evm transaction queue backed off",
+            &registry,
         )?;
 
-        let total_fee_earned = register_counter!(
+        let total_fee_earned = register_counter_with_registry!(
             "total_fee_earned",
             "The total number of fees earned",
+            &registry,
         )?;
 
-        let gas_spent =
-            register_counter!("gas_spent", "The total number of gas spent")?;
+        let gas_spent = register_counter_with_registry!(
+            "gas_spent",
+            "The total number of gas spent",
+            &registry
+        )?;
 
-        let total_amount_of_data_stored = register_gauge!(
+        let total_amount_of_data_stored = register_gauge_with_registry!(
             "total_amount_of_data_stored",
             "The Total number of data stored",
+            &registry,
+        )?;
+
+        let leaf_index_gaps_detected = register_counter_with_registry!(
+            "leaf_index_gaps_detected_total",
+            "How many times gaps were found in the cached leaf indices",
+            &registry,
+        )?;
+
+        let leaves_reconciled = register_counter_with_registry!(
+            "leaves_reconciled_total",
+            "How many missing leaves got fetched from the chain to fill the leaf cache",
+            &registry,
+        )?;
+
+        let duplicate_commitments = register_counter_with_registry!(
+            "duplicate_commitment_total",
+            "How many commitments got cached at a second leaf index",
+            &registry,
+        )?;
+
+        let watcher_start_delay_applied = register_counter_with_registry!(
+            "watcher_start_delay_applied_total",
+            "How many event watchers delayed their startup, to stagger their first queries",
+            &registry,
+        )?;
+
+        let proposals_batched = register_counter_with_registry!(
+            "proposals_batched_total",
+            "How many proposal executions were sent in multicall batches",
+            &registry,
+        )?;
+
+        let custom_abi_loaded = register_counter_with_registry!(
+            "custom_abi_loaded_total",
+            "How many contracts got loaded with a custom ABI",
+            &registry,
+        )?;
+
+        let using_recommended_gas_limit = register_counter_with_registry!(
+            "using_recommended_gas_limit_total",
+            "How many transactions used a recommended gas limit instead of an estimate",
+            &registry,
+        )?;
+
+        let using_gas_limit_override = register_counter_with_registry!(
+            "using_gas_limit_override_total",
+            "How many transactions used a gas limit override from the config",
+            &registry,
         )?;
 
+        let skipped_already_updated = register_counter_with_registry!(
+            "skipped_already_updated_total",
+            "How many anchor updates got skipped, since the linked anchor already had the root",
+            &registry,
+        )?;
+
+        let cross_chain_stale_edges_detected = register_counter_with_registry!(
+            "cross_chain_stale_edges_detected_total",
+            "How many stale edges of the linked anchors got found, and proposed an update for",
+            &registry,
+        )?;
+
+        let event_watcher_stalls = register_counter_with_registry!(
+            "event_watcher_stalls_total",
+            "How many times an event watcher got restarted by its watchdog, after making no progress",
+            &registry,
+        )?;
+
+        let edge_additions_processed = register_counter_with_registry!(
+            "edge_additions_processed_total",
+            "How many edges got added to the linkable trees of Substrate chains",
+            &registry,
+        )?;
+
+        let transactions_deferred_high_gas = register_counter_with_registry!(
+            "transactions_deferred_high_gas_total",
+            "How many times the tx queue held its transactions, since the gas price was too high",
+            &registry,
+        )?;
+
+        let proposal_events_dropped = register_counter_with_registry!(
+            "proposal_events_dropped_total",
+            "How many proposal events the websocket subscribers missed, since they fell behind",
+            &registry,
+        )?;
+
+        let unhandled_event_types = register_counter_with_registry!(
+            "unhandled_event_types_total",
+            "How many events the event watchers skipped, since their type is not in the allowlist",
+            &registry,
+        )?;
+
+        let store_proposal_count = register_gauge_with_registry!(
+            "store_proposal_count",
+            "The total number of anchor update proposals tracked by the store",
+            &registry,
+        )?;
+
+        let store_pending_writes = register_gauge_with_registry!(
+            "store_pending_writes",
+            "The number of bytes pending to be persisted by the store",
+            &registry,
+        )?;
+
+        let undeployed_contracts = register_gauge_with_registry!(
+            "undeployed_contracts_total",
+            "Number of configured contracts without code, found at startup",
+            &registry,
+        )?;
+
+        let provider_calls = register_counter_vec_with_registry!(
+            "provider_calls_total",
+            "The number of RPC calls served by each EVM provider",
+            &["chain_id", "provider", "kind"],
+            &registry,
+        )?;
+
+        let provider_errors = register_counter_vec_with_registry!(
+            "provider_errors_total",
+            "The number of failed RPC calls of each EVM provider",
+            &["chain_id", "provider"],
+            &registry,
+        )?;
+
+        let chain_tx_sent = register_counter_vec_with_registry!(
+            "chain_tx_sent_total",
+            "The number of transactions sent by the tx queue of each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let chain_tx_confirmed = register_counter_vec_with_registry!(
+            "chain_tx_confirmed_total",
+            "The number of transactions confirmed on each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let chain_tx_reverted = register_counter_vec_with_registry!(
+            "chain_tx_reverted_total",
+            "The number of transactions reverted on each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let chain_average_confirmation_time_secs = register_gauge_vec_with_registry!(
+            "chain_average_confirmation_time_secs",
+            "The average time from sending a transaction to its confirmation on each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let chain_current_block_number = register_gauge_vec_with_registry!(
+            "chain_current_block_number",
+            "The latest block number seen by the events watchers of each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let chain_events_processed = register_counter_vec_with_registry!(
+            "chain_events_processed_total",
+            "The number of events the events watchers of each chain went through",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let concurrent_proposals_in_flight = register_gauge_vec_with_registry!(
+            "concurrent_proposals_in_flight",
+            "The number of proposals enqueued and not executed yet on each chain",
+            &["chain_id"],
+            &registry,
+        )?;
+
+        let contract_events_handled = ContractCounter::new(
+            opts!(
+                "contract_events_handled_total",
+                "The number of events of each contract handled by the events watchers",
+            ),
+            policy,
+        )?;
+        registry
+            .register(Box::new(contract_events_handled.counter().clone()))?;
+
+        let contract_tx_sent = ContractCounter::new(
+            opts!(
+                "contract_tx_sent_total",
+                "The number of transactions sent to each contract by the tx queue",
+            ),
+            policy,
+        )?;
+        registry.register(Box::new(contract_tx_sent.counter().clone()))?;
+
         Ok(Self {
             bridge_watcher_back_off,
             total_transaction_made,
@@ -145,17 +622,51 @@ impl Metrics {
             total_fee_earned,
             gas_spent,
             total_amount_of_data_stored,
+            leaf_index_gaps_detected,
+            leaves_reconciled,
+            duplicate_commitments,
+            watcher_start_delay_applied,
+            proposals_batched,
+            custom_abi_loaded,
+            using_recommended_gas_limit,
+            using_gas_limit_override,
+            skipped_already_updated,
+            cross_chain_stale_edges_detected,
+            event_watcher_stalls,
+            edge_additions_processed,
+            transactions_deferred_high_gas,
+            proposal_events_dropped,
+            unhandled_event_types,
             resource_metric_map: Default::default(),
             account_balance: Default::default(),
+            account_nonce: Default::default(),
+            store_proposal_count,
+            store_pending_writes,
+            undeployed_contracts,
+            provider_calls,
+            provider_errors,
+            chain_tx_sent,
+            chain_tx_confirmed,
+            chain_tx_reverted,
+            chain_average_confirmation_time_secs,
+            chain_current_block_number,
+            chain_events_processed,
+            concurrent_proposals_in_flight,
+            contract_events_handled,
+            contract_tx_sent,
+            store_queue_depth: Default::default(),
+            store_leaf_count: Default::default(),
+            gas_limit_override_usage: Default::default(),
+            registry,
         })
     }
 
     /// Gathers the whole relayer metrics
-    pub fn gather_metrics() -> Result<String, GatherMetricsError> {
+    pub fn gather_metrics(&self) -> Result<String, GatherMetricsError> {
         let mut buffer = Vec::new();
         let encoder = TextEncoder::new();
         // Gather the metrics.
-        let metric_families = prometheus::gather();
+        let metric_families = self.registry.gather();
         // Encode them to send.
         encoder.encode(&metric_families, &mut buffer)?;
 
@@ -181,10 +692,11 @@ impl Metrics {
         &mut self,
         resource_id: ResourceId,
     ) -> &mut ResourceMetric {
+        let registry = &self.registry;
         self.resource_metric_map
             .entry(resource_id)
             .or_insert_with(|| {
-                Metrics::register_resource_id_counters(resource_id)
+                Metrics::register_resource_id_counters(registry, resource_id)
             })
     }
 
@@ -192,22 +704,168 @@ impl Metrics {
         &mut self,
         chain: TypedChainId,
     ) -> &mut GenericGauge<AtomicF64> {
+        let registry = &self.registry;
         self.account_balance.entry(chain).or_insert_with(|| {
             let chain_id = chain.underlying_chain_id().to_string();
-            register_gauge!(opts!(
-                "chain_account_balance",
-                "Total account balance on chain",
-                labels!(
-                    "chain_type" => Self::chain_name(chain),
-                    "chain_id" => &chain_id,
-                )
-            ))
+            register_gauge_with_registry!(
+                opts!(
+                    "chain_account_balance",
+                    "Total account balance on chain",
+                    labels!(
+                        "chain_type" => Self::chain_name(chain),
+                        "chain_id" => &chain_id,
+                    )
+                ),
+                registry
+            )
             .expect("create gauge for account balance")
         })
     }
 
+    pub fn account_nonce_entry(
+        &mut self,
+        chain: TypedChainId,
+    ) -> &mut GenericGauge<AtomicF64> {
+        let registry = &self.registry;
+        self.account_nonce.entry(chain).or_insert_with(|| {
+            let chain_id = chain.underlying_chain_id().to_string();
+            register_gauge_with_registry!(
+                opts!(
+                    "chain_account_nonce",
+                    "Nonce of the relayer account on chain",
+                    labels!(
+                        "chain_type" => Self::chain_name(chain),
+                        "chain_id" => &chain_id,
+                    )
+                ),
+                registry
+            )
+            .expect("create gauge for account nonce")
+        })
+    }
+
+    pub fn store_queue_depth_entry(
+        &mut self,
+        chain_id: u32,
+    ) -> &mut GenericGauge<AtomicF64> {
+        let registry = &self.registry;
+        self.store_queue_depth.entry(chain_id).or_insert_with(|| {
+            register_gauge_with_registry!(
+                opts!(
+                    "store_queue_depth",
+                    "Total number of queued items on chain",
+                    labels!("chain_id" => &chain_id.to_string())
+                ),
+                registry
+            )
+            .expect("create gauge for store queue depth")
+        })
+    }
+
+    pub fn store_leaf_count_entry(
+        &mut self,
+        chain: TypedChainId,
+        contract: Address,
+    ) -> &mut GenericGauge<AtomicF64> {
+        let registry = &self.registry;
+        self.store_leaf_count
+            .entry((chain, contract))
+            .or_insert_with(|| {
+                let chain_id = chain.underlying_chain_id().to_string();
+                register_gauge_with_registry!(
+                    opts!(
+                        "store_leaf_count",
+                        "Total number of cached leaves of contract",
+                        labels!(
+                            "chain_type" => Self::chain_name(chain),
+                            "chain_id" => &chain_id,
+                            "contract" => &hex::encode(contract),
+                        )
+                    ),
+                    registry
+                )
+                .expect("create gauge for store leaf count")
+            })
+    }
+
+    pub fn gas_limit_override_usage_entry(
+        &mut self,
+        chain_id: u32,
+        method: &str,
+    ) -> &mut GenericGauge<AtomicF64> {
+        let registry = &self.registry;
+        self.gas_limit_override_usage
+            .entry((chain_id, method.to_string()))
+            .or_insert_with(|| {
+                register_gauge_with_registry!(opts!(
+                    "gas_limit_override_usage",
+                    "Share of the gas limit override used by the last call of method on chain",
+                    labels!(
+                        "chain_id" => &chain_id.to_string(),
+                        "method" => method,
+                    )
+                ), registry)
+                .expect("create gauge for gas limit override usage")
+            })
+    }
+
+    /// Counts an event of the contract handled by the events watchers.
+    pub fn observe_event_handled(
+        &mut self,
+        chain_id: u32,
+        contract: Address,
+        event_type: &str,
+    ) {
+        self.contract_events_handled
+            .inc(chain_id, contract, event_type);
+    }
+
+    /// Counts a transaction sent to the contract by the tx queue.
+    pub fn observe_tx_sent(
+        &mut self,
+        chain_id: u32,
+        contract: Address,
+        method: &str,
+    ) {
+        self.contract_tx_sent.inc(chain_id, contract, method);
+    }
+
+    /// Records the update of the metrics of a chain, `chain_metrics` being
+    /// the metrics with the update applied.
+    pub fn record_chain_metrics(
+        &self,
+        chain_metrics: &ChainMetrics,
+        update: ChainMetricsUpdate,
+    ) {
+        let chain_id = chain_metrics.chain_id.to_string();
+        let labels = [chain_id.as_str()];
+        match update {
+            ChainMetricsUpdate::TxSent => {
+                self.chain_tx_sent.with_label_values(&labels).inc()
+            }
+            ChainMetricsUpdate::TxConfirmed { .. } => {
+                self.chain_tx_confirmed.with_label_values(&labels).inc();
+                self.chain_average_confirmation_time_secs
+                    .with_label_values(&labels)
+                    .set(chain_metrics.average_confirmation_time_secs);
+            }
+            ChainMetricsUpdate::TxReverted => {
+                self.chain_tx_reverted.with_label_values(&labels).inc()
+            }
+            ChainMetricsUpdate::EventsProcessed { count, .. } => {
+                self.chain_events_processed
+                    .with_label_values(&labels)
+                    .inc_by(count as f64);
+                self.chain_current_block_number
+                    .with_label_values(&labels)
+                    .set(chain_metrics.current_block_number as f64);
+            }
+        }
+    }
+
     /// Registers new counters to track metric for individual resources.
     fn register_resource_id_counters(
+        registry: &Registry,
         resource_id: ResourceId,
     ) -> ResourceMetric {
         let chain_id = resource_id
@@ -235,19 +893,25 @@ impl Metrics {
         );
 
         // Total gas fee spent on particular resource.
-        let total_gas_spent = register_counter!(opts!(
-            "resource_total_gas_spent",
-            "Total number of gas spent on resource",
-            labels
-        ))
+        let total_gas_spent = register_counter_with_registry!(
+            opts!(
+                "resource_total_gas_spent",
+                "Total number of gas spent on resource",
+                labels
+            ),
+            registry
+        )
         .expect("create counter for gas spent");
 
         // Total fee earned on particular resource.
-        let total_fee_earned = register_counter!(opts!(
-            "resource_total_fees_earned",
-            "Total number of fees earned on resource",
-            labels
-        ))
+        let total_fee_earned = register_counter_with_registry!(
+            opts!(
+                "resource_total_fees_earned",
+                "Total number of fees earned on resource",
+                labels
+            ),
+            registry
+        )
         .expect("create counter for fees earned");
 
         ResourceMetric {
@@ -264,3 +928,71 @@ pub enum GatherMetricsError {
     #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::core::Collector;
+
+    fn counter(labels: MetricLabels) -> ContractCounter {
+        let policy = CardinalityPolicy {
+            labels,
+            max_label_sets: 2,
+        };
+        ContractCounter::new(opts!("test_total", "test"), policy).unwrap()
+    }
+
+    fn count(counter: &ContractCounter, labels: &[&str]) -> f64 {
+        counter.counter().with_label_values(labels).get()
+    }
+
+    #[test]
+    fn label_sets_over_the_cap_should_be_counted_as_other() {
+        let mut counter = counter(MetricLabels::ChainContract);
+        let (a, b, c) = (
+            Address::repeat_byte(0xa),
+            Address::repeat_byte(0xb),
+            Address::repeat_byte(0xc),
+        );
+        counter.inc(1, a, "deposit");
+        counter.inc(1, b, "deposit");
+        // the method is not a label, so this is the same label set.
+        counter.inc(1, a, "withdraw");
+        counter.inc(1, c, "deposit");
+        counter.inc(2, a, "deposit");
+
+        assert_eq!(count(&counter, &["1", hex::encode(a).as_str()]), 2.0);
+        assert_eq!(count(&counter, &["1", hex::encode(b).as_str()]), 1.0);
+        assert_eq!(count(&counter, &[OVERFLOW_LABEL, OVERFLOW_LABEL]), 2.0);
+        // the label sets over the cap never get a series of their own.
+        let series: usize = counter
+            .counter()
+            .collect()
+            .iter()
+            .map(|family| family.get_metric().len())
+            .sum();
+        assert_eq!(series, 3);
+    }
+
+    #[test]
+    fn the_overflow_series_should_exist_from_the_start() {
+        let mut counter = counter(MetricLabels::Chain);
+        let families = counter.counter().collect();
+        let metrics = families[0].get_metric();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].get_label()[0].get_value(), OVERFLOW_LABEL);
+
+        counter.inc(5, Address::zero(), "deposit");
+        assert_eq!(count(&counter, &["5"]), 1.0);
+    }
+
+    #[test]
+    fn every_instance_should_gather_its_own_metrics() {
+        let first = Metrics::new().unwrap();
+        let second = Metrics::new().unwrap();
+        first.gas_spent.inc_by(2.0);
+        assert_eq!(second.gas_spent.get(), 0.0);
+        assert!(first.gather_metrics().unwrap().contains("gas_spent 2"));
+        assert!(second.gather_metrics().unwrap().contains("gas_spent 0"));
+    }
+}
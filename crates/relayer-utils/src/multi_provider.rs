@@ -2,15 +2,77 @@ use crate::Error as WebbRelayerError;
 use core::fmt::Debug;
 use futures::prelude::*;
 use serde::{de::DeserializeOwned, Serialize};
+use std::str::FromStr;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
 use webb::evm::ethers::providers::{JsonRpcClient, ProviderError};
+use webb::evm::ethers::types::{Bytes, H256};
+use webb::evm::ethers::utils::keccak256;
+
+/// What an RPC call is used for, as counted per provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpcCallKind {
+    /// A transaction submission.
+    Submission,
+    /// A query of the event logs.
+    LogQuery,
+    /// Any other call.
+    Request,
+}
+
+impl RpcCallKind {
+    /// Returns the kind of a call to the JSON-RPC `method`.
+    pub fn of(method: &str) -> Self {
+        match method {
+            "eth_sendRawTransaction" | "eth_sendTransaction" => {
+                Self::Submission
+            }
+            "eth_getLogs" => Self::LogQuery,
+            _ => Self::Request,
+        }
+    }
+
+    /// The name of the kind, as used in the metric labels.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Submission => "submission",
+            Self::LogQuery => "log_query",
+            Self::Request => "request",
+        }
+    }
+}
+
+/// An RPC call served by one of the providers of a [`MultiProvider`].
+#[derive(Debug, Clone, Copy)]
+pub struct RpcCall<'a> {
+    /// The label of the provider that served the call.
+    pub provider: &'a str,
+    /// The JSON-RPC method.
+    pub method: &'a str,
+    /// What the call is used for.
+    pub kind: RpcCallKind,
+    /// Whether the call failed.
+    pub failed: bool,
+    /// The hash of the submitted transaction, for raw transaction
+    /// submissions.
+    pub tx_hash: Option<H256>,
+}
+
+/// Gets notified of every call served by the providers of a
+/// [`MultiProvider`], to attribute them to the right provider.
+pub trait RpcCallObserver: Debug + Send + Sync {
+    /// Called once the call completed.
+    fn on_call(&self, call: RpcCall<'_>);
+}
+
 /// MultiProvider is a JsonRpcClient that will round-robin requests to the underlying providers.
 #[derive(Debug, Clone)]
 pub struct MultiProvider<P> {
     providers: Arc<Vec<P>>,
+    labels: Arc<Vec<String>>,
+    observer: Option<Arc<dyn RpcCallObserver>>,
     last_used: Arc<AtomicUsize>,
 }
 
@@ -18,9 +80,44 @@ impl<P> MultiProvider<P> {
     pub fn new(providers: Arc<Vec<P>>) -> Self {
         Self {
             providers,
+            labels: Default::default(),
+            observer: None,
             last_used: Default::default(),
         }
     }
+
+    /// Labels the providers, in the same order.
+    ///
+    /// The providers without a label are labelled by their index.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = Arc::new(labels);
+        self
+    }
+
+    /// Notifies the `observer` of every call served by the providers.
+    pub fn with_observer(mut self, observer: Arc<dyn RpcCallObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Returns the label of the provider at `index`.
+    pub fn label(&self, index: usize) -> String {
+        self.labels
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| format!("provider-{index}"))
+    }
+}
+
+/// Returns the hash of the transaction submitted by an
+/// `eth_sendRawTransaction` call, the hash of its first parameter.
+fn raw_tx_hash<T: Serialize>(method: &str, params: &T) -> Option<H256> {
+    if method != "eth_sendRawTransaction" {
+        return None;
+    }
+    let params = serde_json::to_value(params).ok()?;
+    let raw_tx = Bytes::from_str(params.get(0)?.as_str()?).ok()?;
+    Some(H256(keccak256(raw_tx)))
 }
 
 #[async_trait::async_trait]
@@ -48,10 +145,25 @@ where
             .unwrap_or_default();
 
         if let Some(provider) = self.providers.get(next_provider_idx) {
-            provider
+            let Some(observer) = self.observer.as_ref() else {
+                return provider
+                    .request(method, params)
+                    .map_err(P::Error::into)
+                    .await;
+            };
+            let tx_hash = raw_tx_hash(method, &params);
+            let result: Result<R, Self::Error> = provider
                 .request(method, params)
                 .map_err(P::Error::into)
-                .await
+                .await;
+            observer.on_call(RpcCall {
+                provider: &self.label(next_provider_idx),
+                method,
+                kind: RpcCallKind::of(method),
+                failed: result.is_err(),
+                tx_hash,
+            });
+            result
         } else {
             Err(ProviderError::CustomError(
                 WebbRelayerError::ProviderNotFound(next_provider_idx)
@@ -64,7 +176,6 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::str::FromStr;
     use webb::evm::ethers::providers::{self, Http, Middleware};
 
     #[tokio::test]
@@ -80,4 +191,26 @@ mod tests {
         assert_eq!(multi_provider.last_used.load(Ordering::SeqCst), 1);
         provider.get_block_number().await.expect("should work");
     }
+
+    #[test]
+    fn should_attribute_calls_to_their_kind() {
+        assert_eq!(
+            RpcCallKind::of("eth_sendRawTransaction"),
+            RpcCallKind::Submission
+        );
+        assert_eq!(RpcCallKind::of("eth_getLogs"), RpcCallKind::LogQuery);
+        assert_eq!(RpcCallKind::of("eth_blockNumber"), RpcCallKind::Request);
+
+        let multi_provider = MultiProvider::new(Arc::new(vec![(), ()]))
+            .with_labels(vec![String::from("alchemy")]);
+        assert_eq!(multi_provider.label(0), "alchemy");
+        assert_eq!(multi_provider.label(1), "provider-1");
+
+        let raw_tx = "0x02f8";
+        assert_eq!(
+            raw_tx_hash("eth_sendRawTransaction", &[raw_tx]),
+            Some(H256(keccak256([0x02, 0xf8])))
+        );
+        assert_eq!(raw_tx_hash("eth_call", &[raw_tx]), None);
+    }
 }
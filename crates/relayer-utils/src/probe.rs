@@ -40,6 +40,9 @@ pub enum Kind {
     /// Relayer Leaves Store state on a specific chain/node.
     #[display(fmt = "leaves_store")]
     LeavesStore,
+    /// Relayer Edges Store state on a specific chain/node.
+    #[display(fmt = "edges_store")]
+    EdgesStore,
     /// When the Relayer sees a new Merkle Tree insertion event on a specific chain/node.
     #[display(fmt = "mt_insert")]
     MerkleTreeInsertion,
@@ -49,4 +52,13 @@ pub enum Kind {
     /// When the relayer will retry to do something.
     #[display(fmt = "retry")]
     Retry,
+    /// When a deposit propagates (or fails to propagate) to one of its linked anchors.
+    #[display(fmt = "deposit_propagation")]
+    DepositPropagation,
+    /// When an anchor update proposal is seen executed on its target anchor.
+    #[display(fmt = "proposal_executed")]
+    ProposalExecuted,
+    /// When a leaf does not match the one cached at the same index.
+    #[display(fmt = "leaf_cache_corrupted")]
+    LeafCacheCorrupted,
 }
@@ -0,0 +1,161 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use webb::evm::contract::protocol_solidity::signature_bridge_contract::{
+    ExecuteProposalWithSignatureCall, TransferOwnershipWithSignaturePubKeyCall,
+};
+use webb::evm::ethers::prelude::EthCall;
+use webb::evm::ethers::utils;
+use webb_relayer_config::evm::{EvmChainConfig, GasLimitOverride};
+
+/// Chain specific behaviour of the EVM [`TxQueue`](super::TxQueue).
+pub trait EvmChain {
+    /// The gas limit to use for a call to the given contract method, instead
+    /// of estimating it with `eth_estimateGas`.
+    ///
+    /// Returns `None` to estimate it, which is the default.
+    fn recommended_gas_limit(_method_name: &str) -> Option<u64> {
+        None
+    }
+}
+
+impl EvmChain for EvmChainConfig {
+    fn recommended_gas_limit(method_name: &str) -> Option<u64> {
+        match method_name {
+            "vote_proposal" => Some(100_000),
+            "execute_proposal" | "execute_proposal_with_signature" => {
+                Some(200_000)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The bridge methods the relayer sends, with their selectors.
+fn known_methods() -> [([u8; 4], &'static str); 2] {
+    [
+        (
+            ExecuteProposalWithSignatureCall::selector(),
+            "execute_proposal_with_signature",
+        ),
+        (
+            TransferOwnershipWithSignaturePubKeyCall::selector(),
+            "transfer_ownership_with_signature_pub_key",
+        ),
+    ]
+}
+
+/// Returns the name of the contract method called by the given call data,
+/// if it is one of the bridge methods the relayer sends.
+pub fn method_name(call_data: &[u8]) -> Option<&'static str> {
+    let selector = call_data.get(..4)?;
+    known_methods()
+        .into_iter()
+        .find(|(known, _)| known[..] == *selector)
+        .map(|(_, name)| name)
+}
+
+/// Returns the selector of a contract method named in the config, either
+/// like the relayer calls it (`execute_proposal`) or by its Solidity
+/// signature (`executeProposalWithSignature(bytes,bytes)`).
+pub fn method_selector(name: &str) -> Option<[u8; 4]> {
+    if name.contains('(') {
+        return Some(utils::id(name.replace(' ', "")));
+    }
+    let name = match name {
+        "execute_proposal" => "execute_proposal_with_signature",
+        name => name,
+    };
+    known_methods()
+        .into_iter()
+        .find(|(_, known)| *known == name)
+        .map(|(selector, _)| selector)
+}
+
+/// Returns the gas limit override for the method called by the given call
+/// data, along with the name of the method in the overrides.
+pub fn gas_limit_override<'a>(
+    overrides: &'a [GasLimitOverride],
+    call_data: &[u8],
+) -> Option<(&'a str, u64)> {
+    let selector = call_data.get(..4)?;
+    overrides
+        .iter()
+        .find(|o| {
+            method_selector(&o.method)
+                .map_or(false, |known| known[..] == *selector)
+        })
+        .map(|o| (o.method.as_str(), o.gas_limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_recommend_the_gas_limit_of_known_methods() {
+        assert_eq!(
+            EvmChainConfig::recommended_gas_limit("vote_proposal"),
+            Some(100_000)
+        );
+        assert_eq!(
+            EvmChainConfig::recommended_gas_limit("execute_proposal"),
+            Some(200_000)
+        );
+        assert_eq!(EvmChainConfig::recommended_gas_limit("transact"), None);
+    }
+
+    #[test]
+    fn should_find_the_method_of_the_call_data() {
+        let mut call_data =
+            ExecuteProposalWithSignatureCall::selector().to_vec();
+        call_data.extend_from_slice(&[0u8; 64]);
+        assert_eq!(
+            method_name(&call_data),
+            Some("execute_proposal_with_signature")
+        );
+        assert_eq!(method_name(&[0xde, 0xad, 0xbe, 0xef]), None);
+        assert_eq!(method_name(&[]), None);
+    }
+
+    #[test]
+    fn should_find_the_gas_limit_override_of_the_call_data() {
+        let overrides = [
+            ("execute_proposal", 900_000),
+            (
+                "transferOwnershipWithSignaturePubKey(bytes, uint32, bytes)",
+                300_000,
+            ),
+            ("vote_proposal", 200_000),
+        ]
+        .map(|(method, gas_limit)| GasLimitOverride {
+            method: method.to_string(),
+            gas_limit,
+        });
+        let mut call_data =
+            ExecuteProposalWithSignatureCall::selector().to_vec();
+        call_data.extend_from_slice(&[0u8; 64]);
+        assert_eq!(
+            gas_limit_override(&overrides, &call_data),
+            Some(("execute_proposal", 900_000))
+        );
+        let call_data = TransferOwnershipWithSignaturePubKeyCall::selector();
+        assert_eq!(
+            gas_limit_override(&overrides, &call_data).map(|(_, gas)| gas),
+            Some(300_000)
+        );
+        assert_eq!(gas_limit_override(&overrides, &[0xde, 0xad]), None);
+        assert_eq!(method_selector("vote_proposal"), None);
+    }
+}
@@ -12,22 +12,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 
 use ethereum_types::{H256, U64};
 use futures::TryFutureExt;
-use rand::Rng;
 use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
 use webb::evm::ethers::middleware::SignerMiddleware;
 use webb::evm::ethers::prelude::TimeLag;
 use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::signers::Signer;
 
 use webb::evm::ethers::types;
+use webb::evm::ethers::utils;
+use webb_relayer_config::evm::{Contract, EvmChainConfig};
 use webb_relayer_context::RelayerContext;
 use webb_relayer_store::sled::SledQueueKey;
-use webb_relayer_store::QueueStore;
+use webb_relayer_store::{
+    DepositPropagationStore, InFlightTx, InFlightTxStore, QueueStore,
+    RevertRetryStore,
+};
 use webb_relayer_utils::clickable_link::ClickableLink;
+use webb_relayer_utils::metric::ChainMetricsUpdate;
+
+use super::{
+    batch_tx, clear_fill, gas_limit_override, is_batchable, method_name,
+    method_selector, reclaim_in_flight_txs, revert_reason, watch_in_flight_tx,
+    EvmChain, GasPriceDecision, GasPriceGuard, ReclaimOutcome, RevertRetries,
+};
 
 /// The TxQueue stores transaction requests so the relayer can process them later.
 /// This prevents issues such as creating transactions with the same nonce.
@@ -42,7 +55,11 @@ pub struct TxQueue<S: QueueStore<TypedTransaction>> {
 
 impl<S> TxQueue<S>
 where
-    S: QueueStore<TypedTransaction, Key = SledQueueKey>,
+    S: QueueStore<TypedTransaction, Key = SledQueueKey>
+        + DepositPropagationStore
+        + InFlightTxStore
+        + RevertRetryStore
+        + 'static,
 {
     /// Creates a new TxQueue instance.
     ///
@@ -67,7 +84,7 @@ where
     /// Starts the TxQueue service.
     ///
     /// Returns a future that resolves `Ok(())` on success, otherwise returns an error.
-    #[tracing::instrument(skip_all, fields(chain = %self.chain_id))]
+    #[tracing::instrument(skip_all, fields(chain_id = %self.chain_id))]
     pub async fn run(self) -> webb_relayer_utils::Result<()> {
         let provider = self.ctx.evm_provider(&self.chain_id).await?;
         let wallet = self.ctx.evm_wallet(self.chain_id).await?;
@@ -108,16 +125,343 @@ where
             chain_id = %chain_id,
             starting = true,
         );
+        let bridges: Vec<_> = chain_config
+            .contracts
+            .iter()
+            .filter_map(|contract| match contract {
+                Contract::SignatureBridge(bridge) => Some(bridge),
+                _ => None,
+            })
+            .collect();
+        for bridge in &bridges {
+            let unknown = bridge
+                .gas_limit_overrides
+                .iter()
+                .map(|o| o.method.as_str())
+                .filter(|name| method_selector(name).is_none());
+            for method in unknown {
+                tracing::warn!(
+                    bridge = %bridge.common.address,
+                    method,
+                    "Unknown method in the gas limit overrides, \
+                    name it by its Solidity signature instead",
+                );
+            }
+        }
+        // the transactions left in flight by a crash are reclaimed before
+        // sending anything, so that their nonces do not conflict.
+        let reclaimed =
+            reclaim_in_flight_txs(&client, store.as_ref(), chain_id).await?;
+        let provider = self.ctx.evm_provider(chain_id).await?;
+        for outcome in reclaimed {
+            match outcome {
+                ReclaimOutcome::Pending(in_flight)
+                | ReclaimOutcome::Resubmitted(in_flight) => {
+                    let provider = provider.clone();
+                    let store = store.clone();
+                    let name = format!(
+                        "In-flight tx watcher ({chain_id}, {:?})",
+                        in_flight.tx_hash
+                    );
+                    self.ctx.background_tasks().spawn(name, move || {
+                        let watched = watch_in_flight_tx(
+                            provider.clone(),
+                            store.clone(),
+                            chain_id,
+                            in_flight.clone(),
+                        );
+                        async move {
+                            watched.await;
+                            Ok::<_, webb_relayer_utils::Error>(())
+                        }
+                    });
+                }
+                ReclaimOutcome::Mined(_)
+                | ReclaimOutcome::Requeued
+                | ReclaimOutcome::Failed => {}
+            }
+        }
         let metrics_clone = self.ctx.metrics.clone();
         let task = || async {
+            let queue_key = SledQueueKey::from_evm_chain_id(chain_id);
+            // the transactions of a batch that failed, they are sent one by
+            // one instead.
+            let mut unbatchable = HashSet::new();
+            let revert_retries = RevertRetries::new(
+                store.clone(),
+                chain_id,
+                self.ctx.clock().clone(),
+            );
+            let retry_on_revert = &chain_config.tx_queue.retry_on_revert;
+            let mut gas_guard =
+                GasPriceGuard::new(chain_config.max_gas_price_gwei);
+            // whether the deferred transactions are being sent.
+            let mut draining = false;
             loop {
-                let maybe_tx = store
-                    .dequeue_item(SledQueueKey::from_evm_chain_id(chain_id))?;
+                // the transactions stay queued while the gas is too high.
+                if gas_guard.is_enabled()
+                    && store.peek_item(queue_key)?.is_some()
+                {
+                    // a provider hiccup is retried, rather than stopping the
+                    // queue.
+                    let gas_price = client
+                        .get_gas_price()
+                        .map_err(|e| {
+                            tracing::warn!(
+                                "Failed to fetch the gas price: {e}"
+                            );
+                            backoff::Error::transient(
+                                webb_relayer_utils::Error::Generic(
+                                    "Failed to fetch the gas price",
+                                ),
+                            )
+                        })
+                        .await?;
+                    match gas_guard.check(gas_price) {
+                        GasPriceDecision::Defer {
+                            gas_price_gwei,
+                            max_gas_price_gwei,
+                        } => {
+                            tracing::warn!(
+                                "gas price {} Gwei exceeds max {} Gwei, deferring transaction",
+                                gas_price_gwei,
+                                max_gas_price_gwei,
+                            );
+                            metrics_clone
+                                .lock()
+                                .await
+                                .transactions_deferred_high_gas
+                                .inc();
+                            // a zero interval would poll the provider in a
+                            // busy loop.
+                            let interval = Duration::from_secs(
+                                chain_config
+                                    .gas_price_check_interval_secs
+                                    .max(1),
+                            );
+                            self.ctx.clock().sleep(interval).await;
+                            continue;
+                        }
+                        GasPriceDecision::Resume => {
+                            tracing::info!(
+                                "Gas price is below the max again, sending the deferred transactions",
+                            );
+                            draining = true;
+                        }
+                        GasPriceDecision::Send => {}
+                    }
+                }
+                let maybe_tx = store.dequeue_item(queue_key)?;
                 let maybe_explorer = &chain_config.explorer;
                 let mut tx_hash: H256;
                 if let Some(mut raw_tx) = maybe_tx {
-                    let raw_tx =
+                    // held back after a revert, the others go first.
+                    if let Some(left) = revert_retries.backoff_left(&raw_tx)? {
+                        tracing::trace!(
+                            ?left,
+                            "Tx is backing off after a revert"
+                        );
+                        store.enqueue_item(queue_key, raw_tx)?;
+                        let s = left.min(Duration::from_secs(1));
+                        self.ctx.clock().sleep(s).await;
+                        continue;
+                    }
+                    let multicall = chain_config
+                        .multicall
+                        .as_ref()
+                        .filter(|_| is_batchable(&raw_tx))
+                        .filter(|_| !unbatchable.remove(&raw_tx.sighash()));
+                    if let Some(multicall) = multicall {
+                        // drain the executions that are ready right after it.
+                        let mut batch = vec![raw_tx.clone()];
+                        while batch.len() < multicall.max_batch_size {
+                            match store.peek_item(queue_key)? {
+                                Some(next)
+                                    if is_batchable(&next)
+                                        && !unbatchable
+                                            .contains(&next.sighash()) =>
+                                {
+                                    store.dequeue_item(queue_key)?;
+                                    batch.push(next);
+                                }
+                                _ => break,
+                            }
+                        }
+                        if batch.len() > 1 {
+                            let tx = batch_tx(&batch, multicall.address)
+                                .set_chain_id(U64::from(chain_id))
+                                .clone();
+                            let sent_at = std::time::Instant::now();
+                            match send_batch(&client, tx).await {
+                                Ok(receipt) => {
+                                    self.ctx
+                                        .update_chain_metrics(
+                                            chain_id,
+                                            ChainMetricsUpdate::TxSent,
+                                        )
+                                        .await;
+                                    self.ctx
+                                        .update_chain_metrics(
+                                            chain_id,
+                                            ChainMetricsUpdate::TxConfirmed {
+                                                confirmation_time: sent_at
+                                                    .elapsed(),
+                                            },
+                                        )
+                                        .await;
+                                    tracing::info!(
+                                        batch_size = batch.len(),
+                                        tx_hash = ?receipt.transaction_hash,
+                                        "Multicall batch finalized",
+                                    );
+                                    let gas_used =
+                                        receipt.gas_used.unwrap_or_default();
+                                    let metrics = metrics_clone.lock().await;
+                                    metrics
+                                        .proposals_batched
+                                        .inc_by(batch.len() as f64);
+                                    metrics
+                                        .proposals_processed_tx_queue
+                                        .inc_by(batch.len() as f64);
+                                    metrics
+                                        .proposals_processed_evm_tx_queue
+                                        .inc_by(batch.len() as f64);
+                                    metrics
+                                        .gas_spent
+                                        .inc_by(gas_used.as_u64() as f64);
+                                    drop(metrics);
+                                    tracing::event!(
+                                        target: webb_relayer_utils::probe::TARGET,
+                                        tracing::Level::DEBUG,
+                                        kind = %webb_relayer_utils::probe::Kind::TxQueue,
+                                        ty = "EVM",
+                                        chain_id = %chain_id,
+                                        finalized = true,
+                                        batch_size = batch.len(),
+                                        tx_hash = %receipt.transaction_hash,
+                                    );
+                                    self.ctx
+                                        .record_relayed_tx(
+                                            webb_proposals::TypedChainId::Evm(
+                                                chain_id,
+                                            ),
+                                            receipt.transaction_hash,
+                                        )
+                                        .await;
+                                    for call_data in
+                                        batch.iter().filter_map(|tx| tx.data())
+                                    {
+                                        let result = store.mark_execution(
+                                            utils::keccak256(call_data),
+                                            receipt.transaction_hash,
+                                        );
+                                        if let Err(e) = result {
+                                            tracing::warn!(
+                                                "Failed to record deposit propagation: {}",
+                                                e
+                                            );
+                                        }
+                                    }
+                                }
+                                Err(reason) => {
+                                    tracing::warn!(
+                                        batch_size = batch.len(),
+                                        "Multicall batch failed, sending its transactions one by one: {}",
+                                        reason,
+                                    );
+                                    tracing::event!(
+                                        target: webb_relayer_utils::probe::TARGET,
+                                        tracing::Level::DEBUG,
+                                        kind = %webb_relayer_utils::probe::Kind::TxQueue,
+                                        ty = "EVM",
+                                        chain_id = %chain_id,
+                                        errored = true,
+                                        batch_size = batch.len(),
+                                        error = %reason,
+                                    );
+                                    for tx in batch {
+                                        unbatchable.insert(tx.sighash());
+                                        store.enqueue_item(queue_key, tx)?;
+                                    }
+                                }
+                            }
+                            continue;
+                        }
+                    }
+                    let mut raw_tx =
                         raw_tx.set_chain_id(U64::from(chain_id)).clone();
+                    let bridge = raw_tx.to_addr().and_then(|to| {
+                        bridges.iter().find(|b| b.common.address == *to)
+                    });
+                    // the config can override the gas limit of bridge calls
+                    // that tend to run out of gas.
+                    let gas_override = bridge
+                        .zip(raw_tx.data())
+                        .filter(|_| raw_tx.gas().is_none())
+                        .and_then(|(bridge, data)| {
+                            gas_limit_override(
+                                &bridge.gas_limit_overrides,
+                                data,
+                            )
+                        });
+                    if let Some((method, gas_limit)) = gas_override {
+                        tracing::info!(
+                            method,
+                            gas_limit,
+                            "Using the gas limit override"
+                        );
+                        raw_tx.set_gas(gas_limit);
+                        metrics_clone
+                            .lock()
+                            .await
+                            .using_gas_limit_override
+                            .inc();
+                    }
+                    // otherwise the estimate is padded, when configured.
+                    let gas_limit_padding = bridge
+                        .map(|bridge| bridge.gas_limit_padding)
+                        .filter(|padding| *padding > 0)
+                        .filter(|_| raw_tx.gas().is_none());
+                    if let Some(padding) = gas_limit_padding {
+                        match client.estimate_gas(&raw_tx, None).await {
+                            Ok(estimate) => {
+                                let gas_limit = estimate
+                                    .saturating_mul((100 + padding).into())
+                                    / 100;
+                                tracing::debug!(
+                                    %estimate,
+                                    %gas_limit,
+                                    "Padding the estimated gas limit"
+                                );
+                                raw_tx.set_gas(gas_limit);
+                            }
+                            Err(e) => {
+                                tracing::warn!(
+                                    "Failed to estimate the gas limit: {}",
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    // known methods have a recommended gas limit, which
+                    // saves estimating it.
+                    let recommended_gas_limit = raw_tx
+                        .data()
+                        .filter(|_| raw_tx.gas().is_none())
+                        .and_then(|data| method_name(data))
+                        .and_then(EvmChainConfig::recommended_gas_limit);
+                    if let Some(gas_limit) = recommended_gas_limit {
+                        tracing::debug!(
+                            gas_limit,
+                            "Using the recommended gas limit"
+                        );
+                        raw_tx.set_gas(gas_limit);
+                        metrics_clone
+                            .lock()
+                            .await
+                            .using_recommended_gas_limit
+                            .inc();
+                    }
                     let my_tx_hash = raw_tx.sighash();
                     tx_hash = my_tx_hash;
                     tracing::debug!(?tx_hash, tx = ?raw_tx, "Found tx in queue");
@@ -148,15 +492,91 @@ where
                                 dry_run = "failed",
                                 %tx_hash,
                             );
+                            let reason = revert_reason(&err);
+                            if revert_retries.should_retry(
+                                retry_on_revert,
+                                &raw_tx,
+                                &reason,
+                            )? {
+                                tracing::warn!(
+                                    ?tx_hash,
+                                    %reason,
+                                    "Dry run of tx failed, retrying it later"
+                                );
+                                store.enqueue_item(queue_key, raw_tx)?;
+                            } else {
+                                tracing::warn!(
+                                    ?tx_hash,
+                                    %reason,
+                                    "Dry run of tx failed, dropping it"
+                                );
+                            }
                             continue; // keep going.
                         }
                     }
 
-                    let pending_tx =
-                        client.send_transaction(raw_tx.clone(), None);
+                    // filled in before it is sent, so that the nonce it got
+                    // sent with is known, if it has to be reclaimed.
+                    if let Err(e) =
+                        client.fill_transaction(&mut raw_tx, None).await
+                    {
+                        tracing::error!(?tx_hash, "Failed to fill tx: {}", e);
+                        continue; // keep going.
+                    }
+                    // signed here rather than by the client, so that the tx
+                    // is recorded in flight with its hash before it is sent:
+                    // a crash in between leaves it to be reclaimed.
+                    let signature = match client
+                        .inner()
+                        .signer()
+                        .sign_transaction(&raw_tx)
+                        .await
+                    {
+                        Ok(signature) => signature,
+                        Err(e) => {
+                            tracing::error!(
+                                ?tx_hash,
+                                "Failed to sign tx: {}",
+                                e
+                            );
+                            continue; // keep going.
+                        }
+                    };
+                    tx_hash = raw_tx.hash(&signature);
+                    let in_flight = InFlightTx {
+                        tx_hash,
+                        tx: raw_tx.clone(),
+                    };
+                    if let Err(e) =
+                        store.record_in_flight_tx(chain_id, in_flight)
+                    {
+                        tracing::warn!(
+                            %tx_hash,
+                            "Failed to record the tx in flight: {}",
+                            e
+                        );
+                    }
+                    let sent_at = std::time::Instant::now();
+                    let pending_tx = client
+                        .send_raw_transaction(raw_tx.rlp_signed(&signature));
                     let tx = match pending_tx.await {
                         Ok(pending) => {
-                            tx_hash = *pending;
+                            self.ctx
+                                .update_chain_metrics(
+                                    chain_id,
+                                    ChainMetricsUpdate::TxSent,
+                                )
+                                .await;
+                            if let Some(to) = raw_tx.to_addr() {
+                                let method = raw_tx
+                                    .data()
+                                    .and_then(|data| method_name(data))
+                                    .unwrap_or("unknown");
+                                metrics_clone
+                                    .lock()
+                                    .await
+                                    .observe_tx_sent(chain_id, *to, method);
+                            }
                             tracing::event!(
                                 target: webb_relayer_utils::probe::TARGET,
                                 tracing::Level::DEBUG,
@@ -184,7 +604,8 @@ where
                                     tx_hash_string,
                                 );
                             }
-                            pending.interval(Duration::from_millis(1000)).await
+                            // polled at the interval of the provider.
+                            pending.await
                         }
                         Err(e) => {
                             let tx_hash_string = format!("0x{tx_hash:x}");
@@ -216,10 +637,32 @@ where
                                 %tx_hash,
                                 error = %e,
                             );
-
+                            // rejected by the node, it is not in flight.
+                            if let Err(e) =
+                                store.remove_in_flight_tx(chain_id, tx_hash)
+                            {
+                                tracing::warn!(
+                                    %tx_hash,
+                                    "Failed to forget the tx in flight: {}",
+                                    e
+                                );
+                            }
                             continue; // keep going.
                         }
                     };
+                    // the ones erroring may still get mined, they are left
+                    // to be reclaimed on the next start.
+                    if tx.is_ok() {
+                        if let Err(e) =
+                            store.remove_in_flight_tx(chain_id, tx_hash)
+                        {
+                            tracing::warn!(
+                                %tx_hash,
+                                "Failed to forget the tx in flight: {}",
+                                e
+                            );
+                        }
+                    }
                     match tx {
                         Ok(Some(receipt)) => {
                             let tx_hash_string =
@@ -230,10 +673,57 @@ where
                                         "Tx {} Failed",
                                         tx_hash_string,
                                     );
+                                    self.ctx
+                                        .update_chain_metrics(
+                                            chain_id,
+                                            ChainMetricsUpdate::TxReverted,
+                                        )
+                                        .await;
+                                    // replayed where it got mined, for the
+                                    // reason it reverted with.
+                                    let reason = match receipt.block_number {
+                                        Some(block) => client
+                                            .call(&raw_tx, Some(block.into()))
+                                            .await
+                                            .err()
+                                            .map(|e| revert_reason(&e)),
+                                        None => None,
+                                    }
+                                    .unwrap_or_default();
+                                    if revert_retries.should_retry(
+                                        retry_on_revert,
+                                        &raw_tx,
+                                        &reason,
+                                    )? {
+                                        tracing::warn!(
+                                            %reason,
+                                            "Tx {} reverted, retrying it",
+                                            tx_hash_string,
+                                        );
+                                        store.enqueue_item(
+                                            queue_key,
+                                            clear_fill(raw_tx),
+                                        )?;
+                                    } else {
+                                        tracing::warn!(
+                                            %reason,
+                                            "Tx {} reverted, dropping it",
+                                            tx_hash_string,
+                                        );
+                                    }
                                     continue;
                                 }
                                 _ => {}
                             }
+                            revert_retries.forget(&raw_tx)?;
+                            self.ctx
+                                .update_chain_metrics(
+                                    chain_id,
+                                    ChainMetricsUpdate::TxConfirmed {
+                                        confirmation_time: sent_at.elapsed(),
+                                    },
+                                )
+                                .await;
 
                             if let Some(mut url) = maybe_explorer.clone() {
                                 url.set_path(&format!("tx/{tx_hash_string}"));
@@ -254,11 +744,37 @@ where
                             let gas_price =
                                 receipt.gas_used.unwrap_or_default();
                             // metrics for  transaction processed by evm tx queue
-                            let metrics = metrics_clone.lock().await;
+                            let mut metrics = metrics_clone.lock().await;
                             metrics.proposals_processed_tx_queue.inc();
                             metrics.proposals_processed_evm_tx_queue.inc();
                             // gas spent metric
                             metrics.gas_spent.inc_by(gas_price.as_u64() as f64);
+                            if let Some((method, gas_limit)) = gas_override {
+                                let usage = gas_price.as_u64() as f64
+                                    / gas_limit as f64;
+                                metrics
+                                    .gas_limit_override_usage_entry(
+                                        chain_id, method,
+                                    )
+                                    .set(usage);
+                                if usage >= 0.9 {
+                                    tracing::warn!(
+                                        method,
+                                        gas_limit,
+                                        gas_used = %gas_price,
+                                        "Tx {} came close to its gas limit override",
+                                        tx_hash_string,
+                                    );
+                                } else {
+                                    tracing::debug!(
+                                        method,
+                                        gas_limit,
+                                        gas_used = %gas_price,
+                                        "Tx {} stayed within its gas limit override",
+                                        tx_hash_string,
+                                    );
+                                }
+                            }
                             tracing::event!(
                                 target: webb_relayer_utils::probe::TARGET,
                                 tracing::Level::DEBUG,
@@ -268,6 +784,27 @@ where
                                 finalized = true,
                                 %tx_hash,
                             );
+                            self.ctx
+                                .record_relayed_tx(
+                                    webb_proposals::TypedChainId::Evm(chain_id),
+                                    receipt.transaction_hash,
+                                )
+                                .await;
+                            // if this tx executed an anchor update proposal, record it
+                            // against the deposit that caused it.
+                            if let Some(call_data) = raw_tx.data() {
+                                let result = store.mark_execution(
+                                    utils::keccak256(call_data),
+                                    receipt.transaction_hash,
+                                );
+                                if let Err(e) = result {
+                                    tracing::warn!(
+                                        %tx_hash,
+                                        "Failed to record deposit propagation: {}",
+                                        e
+                                    );
+                                }
+                            }
                         }
                         Ok(None) => {
                             // this should never happen
@@ -280,10 +817,10 @@ where
                                 "Tx {} Dropped from Mempool!!",
                                 tx_hash_string
                             );
-                            // enquing the tx again
+                            // enquing the tx again, to be filled in again.
                             store.enqueue_item(
                                 SledQueueKey::from_evm_chain_id(chain_id),
-                                raw_tx,
+                                clear_fill(raw_tx),
                             )?;
                         }
                         Err(e) => {
@@ -321,13 +858,17 @@ where
                         }
                     };
                 }
+                // the deferred transactions are sent one after the other.
+                if draining && store.peek_item(queue_key)?.is_some() {
+                    continue;
+                }
+                draining = false;
                 // sleep for a random amount of time.
                 let max_sleep_interval =
                     chain_config.tx_queue.max_sleep_interval;
-                let s =
-                    rand::thread_rng().gen_range(1_000..=max_sleep_interval);
-                tracing::trace!("next queue round after {} ms", s);
-                tokio::time::sleep(Duration::from_millis(s)).await;
+                let s = self.ctx.rng().gen_millis(1_000..=max_sleep_interval);
+                tracing::trace!("next queue round after {:?}", s);
+                self.ctx.clock().sleep(s).await;
             }
         };
         // transaction queue backoff metric
@@ -339,3 +880,29 @@ where
         Ok(())
     }
 }
+
+/// Sends a multicall batch, after making sure it would not revert, and
+/// waits for its receipt.
+///
+/// Returns why it failed, if it did.
+async fn send_batch<M: Middleware>(
+    client: &M,
+    tx: TypedTransaction,
+) -> Result<types::TransactionReceipt, String> {
+    client
+        .call(&tx, None)
+        .await
+        .map_err(|e| format!("dry run failed: {e}"))?;
+    let pending = client
+        .send_transaction(tx, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let receipt = pending
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| String::from("dropped from the mempool"))?;
+    match receipt.status {
+        Some(status) if status.is_zero() => Err(String::from("reverted")),
+        _ => Ok(receipt),
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use webb::evm::ethers::types::U256;
+
+/// What the tx queue does with its transactions at the current gas price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GasPriceDecision {
+    /// Send them.
+    Send,
+    /// Send them all right away, the gas price dropped after they got
+    /// deferred.
+    Resume,
+    /// Keep them in the queue, the gas price is too high.
+    Defer {
+        /// The gas price, in Gwei.
+        gas_price_gwei: f64,
+        /// The highest gas price allowed, in Gwei.
+        max_gas_price_gwei: f64,
+    },
+}
+
+/// Holds the transactions of the tx queue while the gas price is above
+/// `max-gas-price-gwei`.
+#[derive(Debug, Clone, Default)]
+pub struct GasPriceGuard {
+    max_gas_price_gwei: Option<f64>,
+    deferring: bool,
+}
+
+impl GasPriceGuard {
+    /// Creates a guard with the given maximum gas price, in Gwei, if any.
+    pub fn new(max_gas_price_gwei: Option<f64>) -> Self {
+        Self {
+            max_gas_price_gwei,
+            deferring: false,
+        }
+    }
+
+    /// Whether the gas price has to be checked before sending.
+    pub fn is_enabled(&self) -> bool {
+        self.max_gas_price_gwei.is_some()
+    }
+
+    /// Decides what to do with the transactions at `gas_price`, in wei.
+    pub fn check(&mut self, gas_price: U256) -> GasPriceDecision {
+        let Some(max_gas_price_gwei) = self.max_gas_price_gwei else {
+            return GasPriceDecision::Send;
+        };
+        let gas_price_gwei = to_gwei(gas_price);
+        if gas_price_gwei > max_gas_price_gwei {
+            self.deferring = true;
+            GasPriceDecision::Defer {
+                gas_price_gwei,
+                max_gas_price_gwei,
+            }
+        } else if std::mem::take(&mut self.deferring) {
+            GasPriceDecision::Resume
+        } else {
+            GasPriceDecision::Send
+        }
+    }
+}
+
+/// Converts an amount of wei to Gwei.
+fn to_gwei(wei: U256) -> f64 {
+    u128::try_from(wei).unwrap_or(u128::MAX) as f64 / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(gwei: u64) -> U256 {
+        U256::from(gwei) * 1_000_000_000u64
+    }
+
+    #[test]
+    fn should_defer_while_the_gas_price_is_too_high() {
+        let mut guard = GasPriceGuard::new(Some(100.0));
+        assert!(guard.is_enabled());
+        assert_eq!(guard.check(gwei(80)), GasPriceDecision::Send);
+        let deferred = GasPriceDecision::Defer {
+            gas_price_gwei: 250.0,
+            max_gas_price_gwei: 100.0,
+        };
+        assert_eq!(guard.check(gwei(250)), deferred);
+        assert_eq!(guard.check(gwei(250)), deferred);
+        // the deferred transactions are retried once it drops.
+        assert_eq!(guard.check(gwei(100)), GasPriceDecision::Resume);
+        assert_eq!(guard.check(gwei(90)), GasPriceDecision::Send);
+    }
+
+    #[test]
+    fn should_send_whatever_the_gas_price_without_a_maximum() {
+        let mut guard = GasPriceGuard::new(None);
+        assert!(!guard.is_enabled());
+        assert_eq!(guard.check(gwei(10_000)), GasPriceDecision::Send);
+    }
+}
@@ -12,6 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod evm_chain;
 mod evm_tx_queue;
+mod gas_price;
+mod multicall;
+mod reclaim;
+mod revert;
+pub use evm_chain::*;
 #[doc(hidden)]
 pub use evm_tx_queue::*;
+pub use gas_price::*;
+pub use multicall::*;
+pub use reclaim::*;
+pub use revert::*;
@@ -0,0 +1,132 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use webb::evm::ethers::abi::{self, Token};
+use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
+use webb::evm::ethers::types::{Address, Bytes};
+use webb::evm::ethers::utils;
+use webb_relayer_config::evm::EvmChainConfig;
+
+use super::{method_name, EvmChain};
+
+/// The Multicall3 method used to batch the calls, every call has to
+/// succeed for the batch to succeed.
+const AGGREGATE3: &str = "aggregate3((address,bool,bytes)[])";
+
+/// Returns true if the transaction executes a proposal, and so could be
+/// sent as part of a multicall batch.
+pub fn is_batchable(tx: &TypedTransaction) -> bool {
+    let executes_proposal = tx
+        .data()
+        .and_then(|data| method_name(data))
+        .map_or(false, |name| name == "execute_proposal_with_signature");
+    executes_proposal && tx.to_addr().is_some()
+}
+
+/// Encodes the call data of a Multicall3 `aggregate3` call, which makes
+/// the given calls in order, reverting if any of them reverts.
+pub fn encode_aggregate3(calls: &[(Address, Bytes)]) -> Bytes {
+    let calls = calls
+        .iter()
+        .map(|(target, call_data)| {
+            Token::Tuple(vec![
+                Token::Address(*target),
+                Token::Bool(false),
+                Token::Bytes(call_data.to_vec()),
+            ])
+        })
+        .collect();
+    let mut data = utils::id(AGGREGATE3).to_vec();
+    data.extend(abi::encode(&[Token::Array(calls)]));
+    data.into()
+}
+
+/// Builds the transaction that sends all the `batch` transactions through
+/// the Multicall contract at `multicall`.
+///
+/// Every transaction of the batch has to be [batchable](is_batchable).
+pub fn batch_tx(
+    batch: &[TypedTransaction],
+    multicall: Address,
+) -> TypedTransaction {
+    let calls: Vec<_> = batch
+        .iter()
+        .filter_map(|tx| Some((*tx.to_addr()?, tx.data()?.clone())))
+        .collect();
+    let mut tx = batch[0].clone();
+    tx.set_to(multicall);
+    tx.set_data(encode_aggregate3(&calls));
+    let gas_limit = EvmChainConfig::recommended_gas_limit(
+        "execute_proposal_with_signature",
+    );
+    if let Some(gas_limit) = gas_limit {
+        tx.set_gas(gas_limit * calls.len() as u64);
+    }
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::contract::protocol_solidity::signature_bridge_contract::ExecuteProposalWithSignatureCall;
+    use webb::evm::ethers::prelude::EthCall;
+    use webb::evm::ethers::types::TransactionRequest;
+
+    fn execute_tx(bridge: Address) -> TypedTransaction {
+        let mut call_data =
+            ExecuteProposalWithSignatureCall::selector().to_vec();
+        call_data.extend_from_slice(&[0u8; 64]);
+        TransactionRequest::new().to(bridge).data(call_data).into()
+    }
+
+    #[test]
+    fn should_batch_the_executions_of_proposals() {
+        let bridges = [Address::repeat_byte(1), Address::repeat_byte(2)];
+        let batch: Vec<_> = bridges.iter().copied().map(execute_tx).collect();
+        assert!(batch.iter().all(is_batchable));
+        let other = TransactionRequest::new()
+            .to(bridges[0])
+            .data(vec![0xde, 0xad, 0xbe, 0xef])
+            .into();
+        assert!(!is_batchable(&other));
+
+        let multicall = Address::repeat_byte(0x42);
+        let tx = batch_tx(&batch, multicall);
+        assert_eq!(tx.to_addr(), Some(&multicall));
+        assert_eq!(tx.gas(), Some(&400_000u64.into()));
+
+        let data = tx.data().unwrap();
+        assert_eq!(data[..4], utils::id(AGGREGATE3));
+        let param =
+            abi::ParamType::Array(Box::new(abi::ParamType::Tuple(vec![
+                abi::ParamType::Address,
+                abi::ParamType::Bool,
+                abi::ParamType::Bytes,
+            ])));
+        let decoded = abi::decode(&[param], &data[4..]).unwrap();
+        let calls = match &decoded[0] {
+            Token::Array(calls) => calls.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[1],
+            Token::Tuple(vec![
+                Token::Address(bridges[1]),
+                Token::Bool(false),
+                Token::Bytes(batch[1].data().unwrap().to_vec()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use ethereum_types::{H256, U256};
+use webb::evm::ethers::core::TypedTransaction;
+use webb::evm::ethers::providers::{
+    JsonRpcClient, Middleware, PendingTransaction, Provider,
+};
+use webb::evm::ethers::types::{BlockNumber, TransactionReceipt};
+use webb::evm::ethers::utils;
+use webb_relayer_store::sled::SledQueueKey;
+use webb_relayer_store::{
+    DepositPropagationStore, InFlightTx, InFlightTxStore, QueueStore,
+};
+use webb_relayer_utils::Error;
+
+/// The maximum number of transactions left in flight that get reclaimed on
+/// a chain at startup.
+pub const MAX_RECLAIMED_TXS: usize = 64;
+
+/// What became of a transaction the relayer left in flight.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReclaimOutcome {
+    /// It got mined, in the given transaction.
+    Mined(H256),
+    /// It is still in the mempool, and has to be watched again, see
+    /// [`watch_in_flight_tx`].
+    Pending(InFlightTx),
+    /// It is unknown to the node, so it got sent again with the same nonce,
+    /// and has to be watched again.
+    Resubmitted(InFlightTx),
+    /// It is unknown to the node, and its nonce got used by another
+    /// transaction, so it got queued again, to be signed with a new nonce.
+    Requeued,
+    /// It could not be checked, such as on a provider error, so it is left
+    /// in flight, to be reclaimed on the next start.
+    Failed,
+}
+
+/// Finds out what became of the transactions left in flight on the chain
+/// when the relayer stopped, such as after a crash between sending one and
+/// seeing its receipt.
+///
+/// Runs before the queue sends anything on the chain, so that resubmitting
+/// a lost transaction with its nonce does not conflict with a new one. At
+/// most [`MAX_RECLAIMED_TXS`] of them are checked, each on its own: one that
+/// fails to be reclaimed does not hold back the others.
+///
+/// # Arguments
+///
+/// * `client` - The signing client of the chain
+/// * `store` - The store of the transaction queue
+/// * `chain_id` - The chain id of the queue
+pub async fn reclaim_in_flight_txs<M, S>(
+    client: &M,
+    store: &S,
+    chain_id: u32,
+) -> webb_relayer_utils::Result<Vec<ReclaimOutcome>>
+where
+    M: Middleware,
+    S: QueueStore<TypedTransaction, Key = SledQueueKey>
+        + DepositPropagationStore
+        + InFlightTxStore,
+{
+    let in_flight = store.get_in_flight_txs(chain_id, MAX_RECLAIMED_TXS)?;
+    let mut outcomes = Vec::with_capacity(in_flight.len());
+    for InFlightTx { tx_hash, tx } in in_flight {
+        let outcome = match reclaim(client, store, chain_id, tx_hash, tx).await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                tracing::warn!(
+                    %tx_hash,
+                    "Failed to reclaim a transaction in flight, \
+                    leaving it for the next start: {e}"
+                );
+                ReclaimOutcome::Failed
+            }
+        };
+        tracing::info!(%tx_hash, ?outcome, "Reclaimed a transaction in flight");
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+async fn reclaim<M, S>(
+    client: &M,
+    store: &S,
+    chain_id: u32,
+    tx_hash: H256,
+    tx: TypedTransaction,
+) -> webb_relayer_utils::Result<ReclaimOutcome>
+where
+    M: Middleware,
+    S: QueueStore<TypedTransaction, Key = SledQueueKey>
+        + DepositPropagationStore
+        + InFlightTxStore,
+{
+    let rpc_error = |e: M::Error| {
+        tracing::warn!(%tx_hash, "Failed to reclaim a transaction: {e}");
+        Error::Generic("Failed to reclaim a transaction in flight")
+    };
+    let receipt = client
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(rpc_error)?;
+    let in_flight = InFlightTx { tx_hash, tx };
+    if let Some(receipt) = receipt {
+        record_mined(store, chain_id, &in_flight, &receipt)?;
+        return Ok(ReclaimOutcome::Mined(tx_hash));
+    }
+    let known = client.get_transaction(tx_hash).await.map_err(rpc_error)?;
+    if known.is_some() {
+        return Ok(ReclaimOutcome::Pending(in_flight));
+    }
+    let InFlightTx { tx, .. } = in_flight;
+    // the node forgot it, the nonce is still valid if nothing used it since.
+    let next_nonce = match tx.from() {
+        Some(from) => client
+            .get_transaction_count(*from, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(rpc_error)?,
+        None => U256::MAX,
+    };
+    let nonce_still_valid = tx.nonce().map_or(false, |n| *n >= next_nonce);
+    if nonce_still_valid {
+        let pending = client
+            .send_transaction(tx.clone(), None)
+            .await
+            .map_err(rpc_error)?;
+        let resent = InFlightTx {
+            tx_hash: *pending,
+            tx,
+        };
+        store.record_in_flight_tx(chain_id, resent.clone())?;
+        store.remove_in_flight_tx(chain_id, tx_hash)?;
+        return Ok(ReclaimOutcome::Resubmitted(resent));
+    }
+    // re-keyed: the queue fills in a new nonce, and the gas price of now.
+    let tx = clear_fill(tx);
+    store.enqueue_item(SledQueueKey::from_evm_chain_id(chain_id), tx)?;
+    store.remove_in_flight_tx(chain_id, tx_hash)?;
+    Ok(ReclaimOutcome::Requeued)
+}
+
+/// Watches a reclaimed transaction until it gets mined.
+///
+/// A transaction dropped from the mempool meanwhile is left in flight, to be
+/// reclaimed on the next start.
+pub async fn watch_in_flight_tx<P, S>(
+    provider: Arc<Provider<P>>,
+    store: Arc<S>,
+    chain_id: u32,
+    in_flight: InFlightTx,
+) where
+    P: JsonRpcClient,
+    S: DepositPropagationStore + InFlightTxStore,
+{
+    let tx_hash = in_flight.tx_hash;
+    let pending = PendingTransaction::new(tx_hash, provider.as_ref());
+    match pending.await {
+        Ok(Some(receipt)) => {
+            tracing::info!(%tx_hash, "Reclaimed transaction got mined");
+            if let Err(e) =
+                record_mined(store.as_ref(), chain_id, &in_flight, &receipt)
+            {
+                tracing::warn!(%tx_hash, "Failed to record the receipt: {e}");
+            }
+        }
+        Ok(None) => {
+            tracing::warn!(%tx_hash, "Reclaimed transaction got dropped");
+        }
+        Err(e) => {
+            tracing::warn!(%tx_hash, "Failed to watch the transaction: {e}");
+        }
+    }
+}
+
+fn record_mined<S>(
+    store: &S,
+    chain_id: u32,
+    in_flight: &InFlightTx,
+    receipt: &TransactionReceipt,
+) -> webb_relayer_utils::Result<()>
+where
+    S: DepositPropagationStore + InFlightTxStore,
+{
+    let tx_hash = in_flight.tx_hash;
+    if receipt.status.map_or(false, |status| status.is_zero()) {
+        tracing::warn!(%tx_hash, "The transaction in flight reverted");
+    } else if let Some(call_data) = in_flight.tx.data() {
+        store.mark_execution(utils::keccak256(call_data), tx_hash)?;
+    }
+    store.remove_in_flight_tx(chain_id, tx_hash)?;
+    Ok(())
+}
+
+/// Clears the fields filled in when the transaction got sent, so that they
+/// get filled in again.
+pub(crate) fn clear_fill(tx: TypedTransaction) -> TypedTransaction {
+    use TypedTransaction::*;
+    match tx {
+        Legacy(mut tx) => {
+            tx.nonce = None;
+            tx.gas_price = None;
+            Legacy(tx)
+        }
+        Eip2930(mut tx) => {
+            tx.tx.nonce = None;
+            tx.tx.gas_price = None;
+            Eip2930(tx)
+        }
+        Eip1559(mut tx) => {
+            tx.nonce = None;
+            tx.max_fee_per_gas = None;
+            tx.max_priority_fee_per_gas = None;
+            Eip1559(tx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::types::{self, TransactionRequest};
+    use webb_relayer_store::SledStore;
+
+    const CHAIN_ID: u32 = 5001;
+
+    fn in_flight(nonce: u64) -> InFlightTx {
+        let tx = TransactionRequest::new()
+            .from(types::Address::repeat_byte(1))
+            .to(types::Address::repeat_byte(2))
+            .data(vec![0xde, 0xad])
+            .nonce(nonce)
+            .gas(21_000)
+            .gas_price(1)
+            .into();
+        InFlightTx {
+            tx_hash: H256::repeat_byte(nonce as u8),
+            tx,
+        }
+    }
+
+    fn store_with(in_flight: &InFlightTx) -> SledStore {
+        let store = SledStore::temporary().unwrap();
+        store
+            .record_in_flight_tx(CHAIN_ID, in_flight.clone())
+            .unwrap();
+        store
+    }
+
+    fn receipt() -> TransactionReceipt {
+        TransactionReceipt {
+            status: Some(1.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn mined_txs_should_be_forgotten() {
+        let (provider, mock) = Provider::mocked();
+        let tx = in_flight(7);
+        let store = store_with(&tx);
+        mock.push(receipt()).unwrap();
+        let outcomes = reclaim_in_flight_txs(&provider, &store, CHAIN_ID)
+            .await
+            .unwrap();
+        assert_eq!(outcomes, vec![ReclaimOutcome::Mined(tx.tx_hash)]);
+        assert!(store.get_in_flight_txs(CHAIN_ID, 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn txs_failing_to_be_reclaimed_should_not_hold_back_the_others() {
+        let (provider, mock) = Provider::mocked();
+        let failing = in_flight(7);
+        let mined = in_flight(8);
+        let store = store_with(&failing);
+        store.record_in_flight_tx(CHAIN_ID, mined.clone()).unwrap();
+        // the responses are served last in, first out; the first one is not
+        // a receipt, as if the provider errored.
+        mock.push(receipt()).unwrap();
+        mock.push("not a receipt").unwrap();
+        let outcomes = reclaim_in_flight_txs(&provider, &store, CHAIN_ID)
+            .await
+            .unwrap();
+        assert_eq!(
+            outcomes,
+            vec![ReclaimOutcome::Failed, ReclaimOutcome::Mined(mined.tx_hash)]
+        );
+        assert_eq!(
+            store.get_in_flight_txs(CHAIN_ID, 10).unwrap(),
+            vec![failing],
+            "left in flight, to be reclaimed on the next start"
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_txs_should_be_watched_again() {
+        let (provider, mock) = Provider::mocked();
+        let tx = in_flight(7);
+        let store = store_with(&tx);
+        // the responses are served last in, first out.
+        mock.push(types::Transaction::default()).unwrap();
+        mock.push(None::<TransactionReceipt>).unwrap();
+        let outcomes = reclaim_in_flight_txs(&provider, &store, CHAIN_ID)
+            .await
+            .unwrap();
+        assert_eq!(outcomes, vec![ReclaimOutcome::Pending(tx.clone())]);
+        assert_eq!(store.get_in_flight_txs(CHAIN_ID, 10).unwrap(), vec![tx]);
+    }
+
+    #[tokio::test]
+    async fn lost_txs_should_be_resubmitted_with_their_nonce() {
+        let (provider, mock) = Provider::mocked();
+        let tx = in_flight(7);
+        let store = store_with(&tx);
+        let resent_hash = H256::repeat_byte(0xff);
+        mock.push(resent_hash).unwrap();
+        mock.push(U256::from(7)).unwrap();
+        mock.push(None::<types::Transaction>).unwrap();
+        mock.push(None::<TransactionReceipt>).unwrap();
+        let outcomes = reclaim_in_flight_txs(&provider, &store, CHAIN_ID)
+            .await
+            .unwrap();
+        let resent = InFlightTx {
+            tx_hash: resent_hash,
+            tx: tx.tx,
+        };
+        assert_eq!(outcomes, vec![ReclaimOutcome::Resubmitted(resent.clone())]);
+        assert_eq!(
+            store.get_in_flight_txs(CHAIN_ID, 10).unwrap(),
+            vec![resent]
+        );
+        let queue_key = SledQueueKey::from_evm_chain_id(CHAIN_ID);
+        assert!(store.peek_item(queue_key).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn lost_txs_should_be_requeued_once_their_nonce_is_used() {
+        let (provider, mock) = Provider::mocked();
+        let tx = in_flight(7);
+        let store = store_with(&tx);
+        mock.push(U256::from(8)).unwrap();
+        mock.push(None::<types::Transaction>).unwrap();
+        mock.push(None::<TransactionReceipt>).unwrap();
+        let outcomes = reclaim_in_flight_txs(&provider, &store, CHAIN_ID)
+            .await
+            .unwrap();
+        assert_eq!(outcomes, vec![ReclaimOutcome::Requeued]);
+        assert!(store.get_in_flight_txs(CHAIN_ID, 10).unwrap().is_empty());
+        // queued again, to be signed with a new nonce.
+        let queue_key = SledQueueKey::from_evm_chain_id(CHAIN_ID);
+        let requeued = store.peek_item(queue_key).unwrap().unwrap();
+        assert_eq!(requeued.nonce(), None);
+        assert_eq!(requeued.data(), tx.tx.data());
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
+use webb::evm::ethers::providers::{JsonRpcError, MiddlewareError};
+use webb::evm::ethers::utils;
+use webb_relayer_config::RetryOnRevertConfig;
+use webb_relayer_store::{RevertRetry, RevertRetryStore};
+use webb_relayer_utils::clock::SharedClock;
+
+/// The reason a transaction reverted with, decoded from the `Error(string)`
+/// data of the error, or its message if there is none.
+pub fn revert_reason<E: MiddlewareError>(e: &E) -> String {
+    e.as_error_response()
+        .and_then(decode_revert_reason)
+        .unwrap_or_else(|| e.to_string())
+}
+
+fn decode_revert_reason(response: &JsonRpcError) -> Option<String> {
+    response.decode_revert_data::<String>()
+}
+
+/// Counts the retries of the reverted transactions of a queue, to queue them
+/// again while they revert for a transient reason, see
+/// [`RetryOnRevertConfig`].
+///
+/// The retries are kept in the store, by the hash of the target and call
+/// data of the transactions, which stay the same when they are queued again.
+#[derive(Debug)]
+pub struct RevertRetries<S> {
+    store: Arc<S>,
+    chain_id: u32,
+    clock: SharedClock,
+}
+
+impl<S: RevertRetryStore> RevertRetries<S> {
+    /// Creates the retries of the queue of the given chain.
+    pub fn new(store: Arc<S>, chain_id: u32, clock: SharedClock) -> Self {
+        Self {
+            store,
+            chain_id,
+            clock,
+        }
+    }
+
+    /// Whether the transaction that reverted with `reason` has to be queued
+    /// again, which counts as one of its retries, and holds it back for the
+    /// [`RetryOnRevertConfig::retry_delay`] of the retry.
+    pub fn should_retry(
+        &self,
+        policy: &RetryOnRevertConfig,
+        tx: &TypedTransaction,
+        reason: &str,
+    ) -> webb_relayer_utils::Result<bool> {
+        let key = call_key(tx);
+        if !policy.should_retry(reason) {
+            self.store.remove_revert_retry(self.chain_id, key)?;
+            return Ok(false);
+        }
+        let retries = self
+            .store
+            .get_revert_retry(self.chain_id, key)?
+            .map_or(0, |retry| retry.retries);
+        if retries >= policy.max_retries {
+            self.store.remove_revert_retry(self.chain_id, key)?;
+            return Ok(false);
+        }
+        let retry_at = self.now_secs() + policy.retry_delay(retries).as_secs();
+        let retry = RevertRetry {
+            retries: retries + 1,
+            retry_at,
+        };
+        self.store.record_revert_retry(self.chain_id, key, retry)?;
+        Ok(true)
+    }
+
+    /// How long the transaction is still held back for, if it is waiting to
+    /// be retried after a revert.
+    pub fn backoff_left(
+        &self,
+        tx: &TypedTransaction,
+    ) -> webb_relayer_utils::Result<Option<Duration>> {
+        let retry = self.store.get_revert_retry(self.chain_id, call_key(tx))?;
+        let left = retry
+            .map(|retry| retry.retry_at.saturating_sub(self.now_secs()))
+            .filter(|left| *left > 0)
+            .map(Duration::from_secs);
+        Ok(left)
+    }
+
+    /// Forgets the retries of a transaction that went through.
+    pub fn forget(
+        &self,
+        tx: &TypedTransaction,
+    ) -> webb_relayer_utils::Result<()> {
+        self.store.remove_revert_retry(self.chain_id, call_key(tx))
+    }
+
+    fn now_secs(&self) -> u64 {
+        self.clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+fn call_key(tx: &TypedTransaction) -> [u8; 32] {
+    let to = tx.to_addr().map(|to| to.as_bytes().to_vec());
+    let data = tx.data().map(|data| data.to_vec());
+    utils::keccak256(
+        [to.unwrap_or_default(), data.unwrap_or_default()].concat(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::abi::{self, Token};
+    use webb::evm::ethers::types::{Address, TransactionRequest};
+    use webb_relayer_store::SledStore;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    fn reverted(reason: &str) -> JsonRpcError {
+        // the selector of `Error(string)`.
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        data.extend(abi::encode(&[Token::String(reason.into())]));
+        JsonRpcError {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(format!("0x{}", utils::hex::encode(data)).into()),
+        }
+    }
+
+    fn call(data: u8) -> TypedTransaction {
+        TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .data(vec![data])
+            .into()
+    }
+
+    #[test]
+    fn revert_reasons_should_be_decoded() {
+        let e = reverted("Proposal already executed");
+        assert_eq!(
+            decode_revert_reason(&e).as_deref(),
+            Some("Proposal already executed")
+        );
+        let e = JsonRpcError {
+            code: -32000,
+            message: "header not found".into(),
+            data: None,
+        };
+        assert_eq!(decode_revert_reason(&e), None);
+    }
+
+    fn retries(
+        store: &SledStore,
+        clock: &FrozenClock,
+    ) -> RevertRetries<SledStore> {
+        RevertRetries::new(Arc::new(store.clone()), 5001, clock.shared())
+    }
+
+    #[test]
+    fn transient_reverts_should_be_retried_a_few_times() {
+        let policy = RetryOnRevertConfig {
+            max_retries: 2,
+            ..Default::default()
+        };
+        let store = SledStore::temporary().unwrap();
+        let clock = FrozenClock::default();
+        let retries = retries(&store, &clock);
+        let retry = |tx: &TypedTransaction, reason| {
+            retries.should_retry(&policy, tx, reason).unwrap()
+        };
+        let tx = call(1);
+        assert!(retry(&tx, "exceeds block gas limit"));
+        // queued again, with a nonce this time.
+        let mut requeued = tx.clone();
+        requeued.set_nonce(7);
+        assert!(retry(&requeued, "block gas limit"));
+        assert!(!retry(&tx, "block gas limit"));
+        // the count starts over once it gave up.
+        assert!(retry(&tx, "block gas limit"));
+
+        // another call is counted on its own, and permanent reverts are
+        // never retried.
+        assert!(!retry(&call(2), "already executed"));
+        assert!(retry(&call(2), "block gas limit"));
+        retries.forget(&call(2)).unwrap();
+        assert_eq!(retries.backoff_left(&call(2)).unwrap(), None);
+    }
+
+    #[test]
+    fn retries_should_back_off_exponentially_across_restarts() {
+        let policy = RetryOnRevertConfig {
+            retry_delay_secs: 10,
+            ..Default::default()
+        };
+        let store = SledStore::temporary().unwrap();
+        let clock = FrozenClock::default();
+        let tx = call(1);
+        let first = retries(&store, &clock);
+        assert!(first.should_retry(&policy, &tx, "block gas limit").unwrap());
+        assert_eq!(
+            first.backoff_left(&tx).unwrap(),
+            Some(Duration::from_secs(10))
+        );
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(first.backoff_left(&tx).unwrap(), None);
+        drop(first);
+
+        // the count is kept in the store, so a restarted queue goes on with
+        // the next delay.
+        let restarted = retries(&store, &clock);
+        assert!(restarted
+            .should_retry(&policy, &tx, "block gas limit")
+            .unwrap());
+        assert_eq!(
+            restarted.backoff_left(&tx).unwrap(),
+            Some(Duration::from_secs(20))
+        );
+        let retry = store.get_revert_retry(5001, call_key(&tx)).unwrap();
+        assert_eq!(retry.map(|retry| retry.retries), Some(2));
+    }
+}
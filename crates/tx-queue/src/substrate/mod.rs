@@ -76,6 +76,11 @@ mod tests {
                     beneficiary: None,
                     pallets: Default::default(),
                     tx_queue: Default::default(),
+                    min_balance: None,
+                    account_check_interval_secs: 60,
+                    batch_extrinsics: false,
+                    max_batch_size: 10,
+                    batch_flush_timeout_ms: 6_000,
                 },
             )]),
             ..Default::default()
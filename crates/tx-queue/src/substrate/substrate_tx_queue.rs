@@ -15,12 +15,13 @@
 use futures::StreamExt;
 use futures::TryFutureExt;
 use rand::Rng;
+use webb::evm::ethers::utils;
 use webb::substrate::subxt;
 use webb::substrate::subxt::config::ExtrinsicParams;
 use webb::substrate::subxt::PolkadotConfig;
 use webb_relayer_context::RelayerContext;
 use webb_relayer_store::sled::SledQueueKey;
-use webb_relayer_store::QueueStore;
+use webb_relayer_store::{DepositPropagationStore, QueueStore};
 use webb_relayer_utils::static_tx_payload::TypeErasedStaticTxPayload;
 
 use std::sync::Arc;
@@ -45,7 +46,8 @@ where
 
 impl<S> SubstrateTxQueue<S>
 where
-    S: QueueStore<TypeErasedStaticTxPayload, Key = SledQueueKey>,
+    S: QueueStore<TypeErasedStaticTxPayload, Key = SledQueueKey>
+        + DepositPropagationStore,
 {
     /// Creates a new SubstrateTxQueue instance.
     ///
@@ -66,7 +68,7 @@ where
     /// Starts the SubstrateTxQueue service.
     ///
     /// Returns a future that resolves `Ok(())` on success, otherwise returns an error.
-    #[tracing::instrument(skip_all, fields(node = %self.chain_id))]
+    #[tracing::instrument(skip_all, fields(chain_id = self.chain_id))]
     pub async fn run<X>(self) -> webb_relayer_utils::Result<()>
     where
         X: subxt::Config,
@@ -124,12 +126,30 @@ where
                     SledQueueKey::from_substrate_chain_id(chain_id),
                 )?;
                 if let Some(payload) = tx_call_data {
-                    let signed_extrinsic = client
-                        .tx()
-                        .create_signed(&payload, &signer, Default::default())
+                    // the relay handlers sign with the same account, so the
+                    // nonces are tracked together with theirs.
+                    let on_chain_nonce = client
+                        .rpc()
+                        .system_account_next_index(signer.account_id())
                         .map_err(Into::into)
                         .map_err(backoff::Error::transient)
                         .await?;
+                    let accounts = self.ctx.substrate_accounts();
+                    let nonce = accounts.sync_nonce(
+                        chain_id,
+                        on_chain_nonce,
+                        self.ctx.clock().unix_timestamp(),
+                    );
+                    let signed_extrinsic = client
+                        .tx()
+                        .create_signed_with_nonce(
+                            &payload,
+                            &signer,
+                            nonce,
+                            Default::default(),
+                        )
+                        .map_err(Into::into)
+                        .map_err(backoff::Error::transient)?;
                     // dry run test
                     let dry_run_outcome = signed_extrinsic.dry_run(None).await;
                     match dry_run_outcome {
@@ -178,6 +198,11 @@ where
                         .map_err(Into::into)
                         .map_err(backoff::Error::transient)
                         .await?;
+                    accounts.record_submitted(
+                        chain_id,
+                        nonce,
+                        self.ctx.clock().unix_timestamp(),
+                    );
 
                     while let Some(event) = progress.next().await {
                         let e = match event {
@@ -265,7 +290,7 @@ where
                                     status = "FinalityTimeout",
                                 );
                             }
-                            TransactionStatus::Finalized(_) => {
+                            TransactionStatus::Finalized(data) => {
                                 tracing::event!(
                                     target: webb_relayer_utils::probe::TARGET,
                                     tracing::Level::DEBUG,
@@ -276,6 +301,31 @@ where
                                     status = "Finalized",
                                     finalized = true,
                                 );
+                                // if this tx executed an anchor update proposal, record it
+                                // against the deposit that caused it.
+                                let extrinsic_hash =
+                                    ethereum_types::H256::from_slice(
+                                        data.extrinsic_hash().as_ref(),
+                                    );
+                                self.ctx
+                                    .record_relayed_tx(
+                                        webb_proposals::TypedChainId::Substrate(
+                                            chain_id,
+                                        ),
+                                        extrinsic_hash,
+                                    )
+                                    .await;
+                                let result = store.mark_execution(
+                                    utils::keccak256(&payload.call_data),
+                                    extrinsic_hash,
+                                );
+                                if let Err(e) = result {
+                                    tracing::warn!(
+                                        ?extrinsic_hash,
+                                        "Failed to record deposit propagation: {}",
+                                        e
+                                    );
+                                }
                                 // metrics for proposal processed by substrate tx queue
                                 let metrics = metrics_clone.lock().await;
                                 metrics.proposals_processed_tx_queue.inc();
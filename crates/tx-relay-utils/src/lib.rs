@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use serde::{Deserialize, Serialize};
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Contains data that is relayed to the Anchors
 #[derive(Debug, Clone, Deserialize)]
@@ -85,7 +88,7 @@ pub struct ExtData<E, I, B, A, T> {
 }
 
 /// Contains data that is relayed to the VAnchors
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VAnchorRelayTransaction<Id, P, R, E, I, B, A, T> {
     /// one of the supported chains of this relayer
@@ -96,4 +99,132 @@ pub struct VAnchorRelayTransaction<Id, P, R, E, I, B, A, T> {
     pub proof_data: ProofData<P, R, E>,
     /// The external data structure for arbitrary inputs
     pub ext_data: ExtData<P, I, B, A, T>,
+    /// How long the relayer holds the transaction before submitting it.
+    #[serde(
+        default,
+        alias = "delaySeconds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub delay: Option<RelayDelay>,
+    /// A key picked by the client, to cancel the transaction while it is
+    /// held by the relayer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Pays the relayer fee with a token `permit` signed by the recipient,
+    /// on top of the fee of the external data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_permit: Option<FeePermit<T, B, E>>,
+}
+
+/// A `permit` of a token implementing
+/// [EIP-2612](https://eips.ethereum.org/EIPS/eip-2612), signed by the
+/// recipient of a withdrawal to pay the relayer fee out of its own balance,
+/// instead of having it deducted from the withdrawn amount.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeePermit<T, B, E> {
+    /// The token the fee is paid in.
+    pub token: T,
+    /// The fee paid with the permit.
+    pub amount: B,
+    /// When the permit expires, in secs since the UNIX epoch.
+    pub deadline: u64,
+    /// The recovery id of the signature.
+    pub v: u8,
+    /// The `r` value of the signature.
+    pub r: E,
+    /// The `s` value of the signature.
+    pub s: E,
+}
+
+/// How long the relayer holds a transaction before submitting it, so its
+/// timing could not be correlated with the deposits it spends.
+///
+/// Given either as a number of secs, or as `"random(min,max)"` for a delay
+/// picked uniformly between `min` and `max` secs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayDelay {
+    /// A fixed delay, in secs.
+    Fixed(u64),
+    /// A random delay, in secs.
+    Random {
+        /// The shortest delay.
+        min: u64,
+        /// The longest delay.
+        max: u64,
+    },
+}
+
+impl RelayDelay {
+    /// The shortest and the longest delay, in secs.
+    pub fn bounds(&self) -> (u64, u64) {
+        match *self {
+            Self::Fixed(secs) => (secs, secs),
+            Self::Random { min, max } => (min, max),
+        }
+    }
+}
+
+impl fmt::Display for RelayDelay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(secs) => write!(f, "{secs}"),
+            Self::Random { min, max } => write!(f, "random({min},{max})"),
+        }
+    }
+}
+
+impl FromStr for RelayDelay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!("invalid delay `{s}`, expected secs or `random(min,max)`")
+        };
+        if let Ok(secs) = s.trim().parse() {
+            return Ok(Self::Fixed(secs));
+        }
+        let bounds = s
+            .trim()
+            .strip_prefix("random(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+        let (min, max) = bounds.split_once(',').ok_or_else(invalid)?;
+        let min = min.trim().parse().map_err(|_| invalid())?;
+        let max = max.trim().parse().map_err(|_| invalid())?;
+        if min > max {
+            return Err(invalid());
+        }
+        Ok(Self::Random { min, max })
+    }
+}
+
+impl Serialize for RelayDelay {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Fixed(secs) => serializer.serialize_u64(*secs),
+            Self::Random { .. } => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RelayDelay {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Secs(u64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Secs(secs) => Ok(Self::Fixed(secs)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
 }
@@ -0,0 +1,288 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ethereum_types::H256;
+use webb::evm::ethers::utils::keccak256;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::{CommandResponse, WithdrawStatus};
+use webb_relayer_store::{
+    RelayFingerprint, RelayFingerprintStore, ScheduledRelayStore,
+    RELAY_FINGERPRINT_TTL_SECS,
+};
+
+#[cfg(feature = "evm")]
+use webb_relayer_handler_utils::EvmVanchorCommand;
+#[cfg(feature = "substrate")]
+use webb_relayer_handler_utils::SubstrateVAchorCommand;
+
+#[cfg(feature = "evm")]
+use crate::evm::scheduled::schedule_key;
+
+/// The fingerprint of an EVM withdrawal, the hash of its proof, nullifiers
+/// and recipient.
+#[cfg(feature = "evm")]
+pub fn relay_fingerprint(cmd: &EvmVanchorCommand) -> [u8; 32] {
+    let mut bytes = cmd.proof_data.proof.to_vec();
+    for nullifier in &cmd.proof_data.input_nullifiers {
+        bytes.extend_from_slice(nullifier.as_bytes());
+    }
+    bytes.extend_from_slice(cmd.ext_data.recipient.as_bytes());
+    keccak256(bytes)
+}
+
+/// The fingerprint of a Substrate withdrawal, the hash of its proof,
+/// nullifiers and recipient.
+#[cfg(feature = "substrate")]
+pub fn substrate_relay_fingerprint(cmd: &SubstrateVAchorCommand) -> [u8; 32] {
+    let mut bytes = cmd.proof_data.proof.clone();
+    for nullifier in &cmd.proof_data.input_nullifiers {
+        bytes.extend_from_slice(nullifier);
+    }
+    bytes.extend_from_slice(&cmd.ext_data.recipient.0);
+    keccak256(bytes)
+}
+
+/// How long the fingerprint of the command is kept, in secs: the longest
+/// delay its anchor takes, on top of [`RELAY_FINGERPRINT_TTL_SECS`], so that
+/// a delayed withdrawal is still known when it gets relayed.
+#[cfg(feature = "evm")]
+fn relay_fingerprint_ttl(ctx: &RelayerContext, cmd: &EvmVanchorCommand) -> u64 {
+    let chain = ctx.config.evm.get(&cmd.chain_id.to_string());
+    let max_delay = chain.and_then(|chain| {
+        chain.contracts.iter().find_map(|c| match c {
+            webb_relayer_config::evm::Contract::VAnchor(c)
+                if c.common.address == cmd.id =>
+            {
+                c.max_delay
+            }
+            _ => None,
+        })
+    });
+    RELAY_FINGERPRINT_TTL_SECS + max_delay.unwrap_or_default()
+}
+
+/// Records the fingerprint of the EVM command, before relaying it.
+///
+/// Returns the status of the first relay instead, if the same withdrawal
+/// got accepted in the last [`RELAY_FINGERPRINT_TTL_SECS`], plus the
+/// `max_delay` of its anchor.
+#[cfg(feature = "evm")]
+pub fn claim_relay(
+    ctx: &RelayerContext,
+    cmd: &EvmVanchorCommand,
+) -> Result<[u8; 32], CommandResponse> {
+    let fingerprint = relay_fingerprint(cmd);
+    let ttl = relay_fingerprint_ttl(ctx, cmd);
+    claim_fingerprint(ctx, fingerprint, schedule_key(cmd), ttl)?;
+    Ok(fingerprint)
+}
+
+/// Records the fingerprint of the Substrate command, before relaying it.
+///
+/// Returns the status of the first relay instead, if the same withdrawal
+/// got accepted in the last [`RELAY_FINGERPRINT_TTL_SECS`].
+#[cfg(feature = "substrate")]
+pub fn claim_substrate_relay(
+    ctx: &RelayerContext,
+    cmd: &SubstrateVAchorCommand,
+) -> Result<[u8; 32], CommandResponse> {
+    let fingerprint = substrate_relay_fingerprint(cmd);
+    // Substrate withdrawals are never delayed.
+    let key =
+        format!("substrate:{:?}", H256::from(cmd.proof_data.ext_data_hash));
+    claim_fingerprint(ctx, fingerprint, key, RELAY_FINGERPRINT_TTL_SECS)?;
+    Ok(fingerprint)
+}
+
+/// Records the fingerprint for `ttl` secs, unless it is already known, in
+/// which case the status of the first relay is returned.
+fn claim_fingerprint(
+    ctx: &RelayerContext,
+    fingerprint: [u8; 32],
+    key: String,
+    ttl: u64,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
+    let now = ctx.clock().unix_timestamp();
+    let record = RelayFingerprint {
+        key,
+        expires_at: now + ttl,
+        tx_hash: None,
+    };
+    let existing = ctx
+        .store()
+        .record_relay_fingerprint(fingerprint, record, now)
+        .map_err(|e| Error(format!("Failed to check the withdrawal: {e}")))?;
+    let Some(existing) = existing else {
+        return Ok(());
+    };
+    tracing::debug!(key = %existing.key, "Rejected a duplicate withdrawal");
+    if let Some(tx_hash) = existing.tx_hash {
+        return Err(Withdraw(WithdrawStatus::Finalized { tx_hash }));
+    }
+    let scheduled = ctx
+        .store()
+        .get_scheduled_relays()
+        .map_err(|e| Error(format!("Failed to check the withdrawal: {e}")))?
+        .into_iter()
+        .find(|(key, _)| *key == existing.key);
+    match scheduled {
+        Some((_, relay)) => Err(Withdraw(WithdrawStatus::Scheduled {
+            execute_after: relay.execute_after,
+        })),
+        None => Err(Error(
+            "This withdrawal is already being relayed".to_string(),
+        )),
+    }
+}
+
+/// Records the transaction of the relayed withdrawal, for its retries.
+pub fn record_relay_tx(
+    ctx: &RelayerContext,
+    fingerprint: [u8; 32],
+    tx_hash: H256,
+) {
+    if let Err(e) = ctx.store().set_relay_fingerprint_tx(fingerprint, tx_hash) {
+        tracing::warn!("Failed to record the withdrawal transaction: {e}");
+    }
+}
+
+/// Forgets the fingerprint of a withdrawal that failed, so that its client
+/// could retry it.
+pub fn release_relay(ctx: &RelayerContext, fingerprint: [u8; 32]) {
+    if let Err(e) = ctx.store().remove_relay_fingerprint(fingerprint) {
+        tracing::warn!("Failed to forget the withdrawal fingerprint: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use webb_relayer_store::SledStore;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    use crate::test_utils::{
+        anchor_config, command, context_with_chain, context_with_store,
+    };
+
+    fn context(path: &std::path::Path) -> RelayerContext {
+        context_with_store(SledStore::open(path).unwrap(), |_| {})
+    }
+
+    #[test]
+    fn duplicates_should_be_rejected_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let cmd = command();
+        let ctx = context(dir.path());
+        let fingerprint = claim_relay(&ctx, &cmd).unwrap();
+        ctx.store().flush().unwrap();
+        drop(ctx);
+
+        // the relayer restarts while the withdrawal is in flight.
+        let ctx = context(dir.path());
+        assert_eq!(
+            claim_relay(&ctx, &cmd),
+            Err(CommandResponse::Error(
+                "This withdrawal is already being relayed".to_string()
+            ))
+        );
+        let tx_hash = H256::repeat_byte(9);
+        record_relay_tx(&ctx, fingerprint, tx_hash);
+        assert_eq!(
+            claim_relay(&ctx, &cmd),
+            Err(CommandResponse::Withdraw(WithdrawStatus::Finalized {
+                tx_hash
+            }))
+        );
+
+        let mut other = command();
+        other.ext_data.recipient = H256::repeat_byte(8).into();
+        assert!(claim_relay(&ctx, &other).is_ok());
+    }
+
+    #[test]
+    fn failed_withdrawals_should_be_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let cmd = command();
+        let ctx = context(dir.path());
+        let fingerprint = claim_relay(&ctx, &cmd).unwrap();
+        release_relay(&ctx, fingerprint);
+        assert_eq!(claim_relay(&ctx, &cmd), Ok(fingerprint));
+    }
+
+    #[test]
+    fn delayed_withdrawals_should_be_known_until_their_max_delay() {
+        let clock = FrozenClock::default();
+        let ctx = context_with_chain(|chain| {
+            anchor_config(chain).max_delay = Some(3600);
+        })
+        .with_clock(clock.shared());
+        let cmd = command();
+        claim_relay(&ctx, &cmd).unwrap();
+
+        // the withdrawal could still be held for its delay.
+        clock.advance(Duration::from_secs(RELAY_FINGERPRINT_TTL_SECS + 3599));
+        assert!(claim_relay(&ctx, &cmd).is_err());
+        clock.advance(Duration::from_secs(1));
+        assert!(claim_relay(&ctx, &cmd).is_ok());
+    }
+
+    #[test]
+    fn substrate_duplicates_should_be_rejected() {
+        let element = vec![1u8; 32];
+        let cmd: SubstrateVAchorCommand =
+            serde_json::from_value(serde_json::json!({
+                "chainId": 1081,
+                "id": 5,
+                "proofData": {
+                    "proof": [1, 2, 3],
+                    "publicAmount": element,
+                    "roots": [element],
+                    "inputNullifiers": [element],
+                    "outputCommitments": [element],
+                    "extDataHash": element,
+                    "extensionRoots": [],
+                },
+                "extData": {
+                    "recipient": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                    "relayer": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                    "extAmount": "-2a",
+                    "fee": "0x64",
+                    "refund": "0x0",
+                    "token": 0,
+                    "encryptedOutput1": [4, 5],
+                    "encryptedOutput2": [6],
+                },
+            }))
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(dir.path());
+        let fingerprint = claim_substrate_relay(&ctx, &cmd).unwrap();
+        assert_eq!(
+            claim_substrate_relay(&ctx, &cmd),
+            Err(CommandResponse::Error(
+                "This withdrawal is already being relayed".to_string()
+            ))
+        );
+        let tx_hash = H256::repeat_byte(9);
+        record_relay_tx(&ctx, fingerprint, tx_hash);
+        assert_eq!(
+            claim_substrate_relay(&ctx, &cmd),
+            Err(CommandResponse::Withdraw(WithdrawStatus::Finalized {
+                tx_hash
+            }))
+        );
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use webb::evm::ethers::abi::{self, Token};
+use webb::evm::ethers::core::types::transaction::eip2718::TypedTransaction;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types::{
+    Address, Bytes, Signature, TransactionRequest, H256, U256,
+};
+use webb::evm::ethers::utils;
+use webb_relayer_handler_utils::{
+    CommandResponse, EvmFeePermit, WithdrawStatus,
+};
+
+const BALANCE_OF: &str = "balanceOf(address)";
+const NONCES: &str = "nonces(address)";
+const DOMAIN_SEPARATOR: &str = "DOMAIN_SEPARATOR()";
+/// The call of the fee collector that takes the permit, makes the call to
+/// `target`, then transfers the fee from the owner to the beneficiary.
+const RELAY_WITH_FEE: &str = "relayWithFee(address,bytes,address,address,address,uint256,uint256,uint8,bytes32,bytes32)";
+/// The EIP-712 type of the permits.
+const PERMIT_TYPE: &str = "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+
+/// The hash the `owner` signs to permit the `spender` to transfer `value`
+/// of its tokens, see [EIP-2612](https://eips.ethereum.org/EIPS/eip-2612).
+pub fn permit_digest(
+    domain_separator: H256,
+    owner: Address,
+    spender: Address,
+    value: U256,
+    nonce: U256,
+    deadline: u64,
+) -> H256 {
+    let struct_hash = utils::keccak256(abi::encode(&[
+        Token::FixedBytes(utils::keccak256(PERMIT_TYPE).to_vec()),
+        Token::Address(owner),
+        Token::Address(spender),
+        Token::Uint(value),
+        Token::Uint(nonce),
+        Token::Uint(deadline.into()),
+    ]));
+    let mut bytes = vec![0x19, 0x01];
+    bytes.extend_from_slice(domain_separator.as_bytes());
+    bytes.extend_from_slice(&struct_hash);
+    utils::keccak256(bytes).into()
+}
+
+/// Checks the fee permit of a withdrawal before it gets sent: that it did
+/// not expire, that it is signed by the `owner` for the `spender`, and that
+/// the `owner` has enough tokens to pay it.
+///
+/// # Arguments
+///
+/// * `client` - The client of the chain of the token
+/// * `permit` - The fee permit
+/// * `owner` - The recipient of the withdrawal, who pays the fee
+/// * `spender` - The contract transferring the fee to the relayer
+/// * `incoming` - The tokens the `owner` gets from the withdrawal, before
+///   the fee is transferred
+/// * `now` - The current time, in secs since the UNIX epoch
+pub async fn check_fee_permit<M: Middleware>(
+    client: &M,
+    permit: &EvmFeePermit,
+    owner: Address,
+    spender: Address,
+    incoming: U256,
+    now: u64,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
+    if permit.deadline < now {
+        return Err(Withdraw(WithdrawStatus::FeePermitExpired {
+            deadline: permit.deadline,
+        }));
+    }
+    let token = permit.token;
+    let domain_separator =
+        read_word(client, token, call_data(DOMAIN_SEPARATOR, &[])).await?;
+    let nonce =
+        read_word(client, token, call_data(NONCES, &[Token::Address(owner)]))
+            .await?;
+    let digest = permit_digest(
+        u256_to_h256(domain_separator),
+        owner,
+        spender,
+        permit.amount,
+        nonce,
+        permit.deadline,
+    );
+    let signature = Signature {
+        r: U256::from_big_endian(permit.r.as_bytes()),
+        s: U256::from_big_endian(permit.s.as_bytes()),
+        v: permit.v.into(),
+    };
+    let signer = signature
+        .recover(digest)
+        .map_err(|e| rejected(format!("Invalid signature: {e}")))?;
+    if signer != owner {
+        return Err(rejected(format!(
+            "Signed by {signer:?}, not by the recipient {owner:?}"
+        )));
+    }
+    let balance = call_data(BALANCE_OF, &[Token::Address(owner)]);
+    let balance = read_word(client, token, balance)
+        .await?
+        .saturating_add(incoming);
+    if balance < permit.amount {
+        return Err(Withdraw(WithdrawStatus::FeePermitUnderfunded {
+            balance,
+            required: permit.amount,
+        }));
+    }
+    Ok(())
+}
+
+/// Makes the withdrawal `tx` go through the fee collector of the relayer at
+/// `fee_collector`, between the `permit` of the fee and its transfer from
+/// the `owner` to the `beneficiary`, so that the three of them go through,
+/// or revert, together.
+///
+/// The fee collector is the spender of the permit. Only the relayer can
+/// call it, so a permit seen in the mempool cannot be used by anyone else
+/// to take the fee.
+pub fn with_fee_permit(
+    tx: &mut TypedTransaction,
+    permit: &EvmFeePermit,
+    owner: Address,
+    beneficiary: Address,
+    fee_collector: Address,
+) {
+    let withdrawal = call_data(
+        RELAY_WITH_FEE,
+        &[
+            Token::Address(tx.to_addr().copied().unwrap_or_default()),
+            Token::Bytes(tx.data().cloned().unwrap_or_default().to_vec()),
+            Token::Address(permit.token),
+            Token::Address(owner),
+            Token::Address(beneficiary),
+            Token::Uint(permit.amount),
+            Token::Uint(permit.deadline.into()),
+            Token::Uint(permit.v.into()),
+            Token::FixedBytes(permit.r.as_bytes().to_vec()),
+            Token::FixedBytes(permit.s.as_bytes().to_vec()),
+        ],
+    );
+    tx.set_to(fee_collector);
+    tx.set_data(withdrawal);
+}
+
+/// Dry runs the `withdrawal` on its own, then `with_permit`, the same
+/// withdrawal wrapped by [`with_fee_permit`], so that a `permit` or a
+/// `transferFrom` of the fee that would revert rejects the fee permit.
+///
+/// A withdrawal that would revert on its own is left to its gas estimate.
+pub async fn simulate_fee_permit<M: Middleware>(
+    client: &M,
+    withdrawal: &TypedTransaction,
+    with_permit: &TypedTransaction,
+) -> Result<(), CommandResponse> {
+    if client.call(withdrawal, None).await.is_err() {
+        return Ok(());
+    }
+    client.call(with_permit, None).await.map_err(|e| {
+        rejected(format!("The permit or the transfer of the fee fails: {e}"))
+    })?;
+    Ok(())
+}
+
+/// Reads the first word returned by the `token` for the call `data`.
+async fn read_word<M: Middleware>(
+    client: &M,
+    token: Address,
+    data: Bytes,
+) -> Result<U256, CommandResponse> {
+    let tx: TypedTransaction =
+        TransactionRequest::new().to(token).data(data).into();
+    let output = client
+        .call(&tx, None)
+        .await
+        .map_err(|e| rejected(format!("Failed to read the fee token: {e}")))?;
+    if output.len() < 32 {
+        return Err(rejected("The fee token has no permits".into()));
+    }
+    Ok(U256::from_big_endian(&output[..32]))
+}
+
+fn rejected(reason: String) -> CommandResponse {
+    CommandResponse::Withdraw(WithdrawStatus::FeePermitRejected { reason })
+}
+
+fn call_data(signature: &str, args: &[Token]) -> Bytes {
+    let mut data = utils::id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data.into()
+}
+
+fn u256_to_h256(value: U256) -> H256 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    H256(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::providers::{
+        JsonRpcError, MockProvider, MockResponse, Provider,
+    };
+    use webb::evm::ethers::signers::{LocalWallet, Signer};
+
+    const NOW: u64 = 1_672_531_200;
+
+    fn wallet(key: &str) -> LocalWallet {
+        key.parse().unwrap()
+    }
+
+    fn owner() -> LocalWallet {
+        wallet(
+            "0x8917174396171783496173419137618235192359106130478137647163400318",
+        )
+    }
+
+    fn word(value: impl Into<U256>) -> Bytes {
+        abi::encode(&[Token::Uint(value.into())]).into()
+    }
+
+    fn signed_permit(
+        signer: &LocalWallet,
+        owner: Address,
+        spender: Address,
+    ) -> EvmFeePermit {
+        let domain_separator = H256::repeat_byte(0xd5);
+        let amount = U256::from(1_000);
+        let deadline = NOW + 60;
+        let digest = permit_digest(
+            domain_separator,
+            owner,
+            spender,
+            amount,
+            U256::from(3),
+            deadline,
+        );
+        let signature = signer.sign_hash(digest).unwrap();
+        EvmFeePermit {
+            token: Address::repeat_byte(0x70),
+            amount,
+            deadline,
+            v: signature.v as u8,
+            r: u256_to_h256(signature.r),
+            s: u256_to_h256(signature.s),
+        }
+    }
+
+    /// A token answering the reads of [`check_fee_permit`], with the
+    /// `balance` of the owner.
+    fn token(balance: u64) -> Provider<MockProvider> {
+        let (provider, mock) = Provider::mocked();
+        // the responses are served last in, first out.
+        mock.push(word(balance)).unwrap();
+        mock.push(word(3)).unwrap();
+        mock.push(Bytes::from(H256::repeat_byte(0xd5).as_bytes().to_vec()))
+            .unwrap();
+        provider
+    }
+
+    #[tokio::test]
+    async fn fee_permits_should_be_signed_by_the_recipient() {
+        let owner = owner();
+        let spender = Address::repeat_byte(0xca);
+        let permit = signed_permit(&owner, owner.address(), spender);
+        let result = check_fee_permit(
+            &token(1_000),
+            &permit,
+            owner.address(),
+            spender,
+            U256::zero(),
+            NOW,
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+
+        let other = wallet(
+            "0x000000000000000000000000000000000000000000000000000000000000000a",
+        );
+        let permit = signed_permit(&other, owner.address(), spender);
+        let result = check_fee_permit(
+            &token(1_000),
+            &permit,
+            owner.address(),
+            spender,
+            U256::zero(),
+            NOW,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(CommandResponse::Withdraw(
+                WithdrawStatus::FeePermitRejected { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_or_underfunded_fee_permits_should_be_rejected() {
+        let owner = owner();
+        let spender = Address::repeat_byte(0xca);
+        let permit = signed_permit(&owner, owner.address(), spender);
+        let (provider, _mock) = Provider::mocked();
+        let result = check_fee_permit(
+            &provider,
+            &permit,
+            owner.address(),
+            spender,
+            U256::zero(),
+            permit.deadline + 1,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::Withdraw(
+                WithdrawStatus::FeePermitExpired {
+                    deadline: permit.deadline
+                }
+            ))
+        );
+
+        let result = check_fee_permit(
+            &token(400),
+            &permit,
+            owner.address(),
+            spender,
+            U256::from(500),
+            NOW,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::Withdraw(
+                WithdrawStatus::FeePermitUnderfunded {
+                    balance: U256::from(900),
+                    required: U256::from(1_000),
+                }
+            ))
+        );
+        // the withdrawn tokens make up for the balance.
+        let result = check_fee_permit(
+            &token(400),
+            &permit,
+            owner.address(),
+            spender,
+            U256::from(600),
+            NOW,
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn withdrawals_should_be_wrapped_between_the_permit_and_the_transfer() {
+        let owner = owner();
+        let fee_collector = Address::repeat_byte(0xca);
+        let beneficiary = Address::repeat_byte(0xbe);
+        let vanchor = Address::repeat_byte(0xa1);
+        let permit = signed_permit(&owner, owner.address(), fee_collector);
+        let mut tx: TypedTransaction = TransactionRequest::new()
+            .to(vanchor)
+            .data(vec![0xde, 0xad, 0xbe, 0xef])
+            .into();
+        with_fee_permit(
+            &mut tx,
+            &permit,
+            owner.address(),
+            beneficiary,
+            fee_collector,
+        );
+        assert_eq!(tx.to_addr(), Some(&fee_collector));
+
+        let data = tx.data().unwrap();
+        assert_eq!(data[..4], utils::id(RELAY_WITH_FEE));
+        let params = [
+            abi::ParamType::Address,
+            abi::ParamType::Bytes,
+            abi::ParamType::Address,
+            abi::ParamType::Address,
+            abi::ParamType::Address,
+            abi::ParamType::Uint(256),
+            abi::ParamType::Uint(256),
+            abi::ParamType::Uint(8),
+            abi::ParamType::FixedBytes(32),
+            abi::ParamType::FixedBytes(32),
+        ];
+        let decoded = abi::decode(&params, &data[4..]).unwrap();
+        assert_eq!(
+            decoded[..7],
+            [
+                Token::Address(vanchor),
+                Token::Bytes(vec![0xde, 0xad, 0xbe, 0xef]),
+                Token::Address(permit.token),
+                Token::Address(owner.address()),
+                Token::Address(beneficiary),
+                Token::Uint(permit.amount),
+                Token::Uint(permit.deadline.into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fee_permits_failing_to_be_taken_should_be_rejected() {
+        let withdrawal: TypedTransaction = TransactionRequest::new()
+            .to(Address::repeat_byte(0xa1))
+            .data(vec![0xde, 0xad, 0xbe, 0xef])
+            .into();
+        let mut with_permit = withdrawal.clone();
+        let owner = owner();
+        let fee_collector = Address::repeat_byte(0xca);
+        let permit = signed_permit(&owner, owner.address(), fee_collector);
+        with_fee_permit(
+            &mut with_permit,
+            &permit,
+            owner.address(),
+            Address::repeat_byte(0xbe),
+            fee_collector,
+        );
+        let reverted = || {
+            MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".into(),
+                data: None,
+            })
+        };
+
+        // the responses are served last in, first out.
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::default()).unwrap();
+        mock.push(Bytes::default()).unwrap();
+        let result =
+            simulate_fee_permit(&provider, &withdrawal, &with_permit).await;
+        assert_eq!(result, Ok(()));
+
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(reverted());
+        mock.push(Bytes::default()).unwrap();
+        let result =
+            simulate_fee_permit(&provider, &withdrawal, &with_permit).await;
+        assert!(matches!(
+            result,
+            Err(CommandResponse::Withdraw(
+                WithdrawStatus::FeePermitRejected { .. }
+            ))
+        ));
+
+        // the withdrawal reverts on its own, whatever the permit.
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(reverted());
+        let result =
+            simulate_fee_permit(&provider, &withdrawal, &with_permit).await;
+        assert_eq!(result, Ok(()));
+    }
+}
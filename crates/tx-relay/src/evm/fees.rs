@@ -1,3 +1,4 @@
+use crate::evm::EvmTxContext;
 use crate::{MAX_REFUND_USD, TRANSACTION_PROFIT_USD};
 use chrono::DateTime;
 use chrono::Duration;
@@ -14,7 +15,6 @@ use webb::evm::contract::protocol_solidity::{
 use webb::evm::ethers::middleware::gas_oracle::GasOracle;
 use webb::evm::ethers::prelude::U256;
 use webb::evm::ethers::providers::Middleware;
-use webb::evm::ethers::signers::Signer;
 use webb::evm::ethers::types::Address;
 use webb::evm::ethers::utils::{format_units, parse_units};
 use webb_chains_info::chain_info_by_chain_id;
@@ -71,6 +71,7 @@ pub async fn get_evm_fee_info(
     gas_amount: U256,
     ctx: &RelayerContext,
 ) -> Result<EvmFeeInfo> {
+    let now: DateTime<Utc> = ctx.clock().now().into();
     // Retrieve cached fee info item
     let fee_info_cached = {
         let mut lock =
@@ -78,7 +79,7 @@ pub async fn get_evm_fee_info(
         // Remove all items from cache which are older than `FEE_CACHE_TIME`
         lock.retain(|_, v| {
             let fee_info_valid_time = v.timestamp.add(*FEE_CACHE_TIME);
-            fee_info_valid_time > Utc::now()
+            fee_info_valid_time > now
         });
         lock.get(&(vanchor, chain_id)).cloned()
     };
@@ -185,7 +186,7 @@ async fn generate_fee_info(
             ctx,
         )
         .await?,
-        timestamp: Utc::now(),
+        timestamp: ctx.clock().now().into(),
         native_token_price,
         native_token_decimals,
         wrapped_token_price,
@@ -199,9 +200,10 @@ async fn max_refund(
     native_token_decimals: u8,
     ctx: &RelayerContext,
 ) -> Result<U256> {
-    let wallet = ctx.evm_wallet(chain_id.underlying_chain_id()).await?;
-    let provider = ctx.evm_provider(chain_id.underlying_chain_id()).await?;
-    let relayer_balance = provider.get_balance(wallet.address(), None).await?;
+    let tx_ctx =
+        EvmTxContext::new(ctx, chain_id.underlying_chain_id().into()).await?;
+    let relayer_balance =
+        tx_ctx.client().get_balance(tx_ctx.address(), None).await?;
     // Calculate the maximum refund amount per relay transaction in `nativeToken`.
     // Ensuring that refund <= relayer balance
     let max_refund = parse_units(
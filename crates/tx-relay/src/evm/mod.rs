@@ -1,11 +1,12 @@
 use ethereum_types::U256;
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 
 use tokio::sync::Mutex;
 use webb::evm::ethers::{
     self,
     abi::Detokenize,
     prelude::{builders::ContractCall, Middleware},
+    types::TransactionReceipt,
 };
 use webb_proposals::ResourceId;
 use webb_relayer_handler_utils::{
@@ -13,22 +14,59 @@ use webb_relayer_handler_utils::{
 };
 use webb_relayer_utils::metric::{self};
 
+/// ERC20 permits paying the relayer fees of withdrawals.
+pub mod fee_permit;
 pub mod fees;
+/// Delayed relaying of withdrawals.
+pub mod scheduled;
+mod tx_context;
 /// Variable Anchor transaction relayer.
 pub mod vanchor;
 
+pub use tx_context::EvmTxContext;
+
+/// The revert reason of the VAnchor contracts, when the verifier rejects the proof.
+const INVALID_PROOF_REVERT: &str = "Invalid transaction proof";
+
+/// Dry runs the call, to reject an invalid proof before estimating the gas
+/// of the transaction, or sending it.
+pub async fn preverify_proof<M, D>(
+    call: &ContractCall<M, D>,
+) -> Result<(), CommandResponse>
+where
+    M: Middleware,
+    D: Detokenize,
+{
+    use CommandResponse::*;
+    let Err(e) = call.call().await else {
+        return Ok(());
+    };
+    match e.decode_revert::<String>() {
+        Some(reason) if reason.contains(INVALID_PROOF_REVERT) => {
+            tracing::warn!("Rejected an invalid proof: {}", reason);
+            Err(Withdraw(WithdrawStatus::InvalidProof { reason }))
+        }
+        _ => {
+            tracing::debug!("Dry run of the transaction failed: {}", e);
+            Err(Withdraw(into_withdraw_error(e)))
+        }
+    }
+}
+
 /// Submits a dry-run and then submits the actual transaction for an EVM transaction.
 ///
 /// This is meant to be reused amongst all kinds of EVM transactions that the relayer sends.
 /// The intention is that a dry-run call is made first to ensure that the transaction is valid
 /// and then the actual transaction is submitted and its progress is monitored.
+///
+/// Returns the receipt of the finalized transaction.
 pub async fn handle_evm_tx<M, D>(
     call: ContractCall<M, D>,
     stream: CommandStream,
     chain_id: u64,
     metrics: Arc<Mutex<metric::Metrics>>,
     resource_id: ResourceId,
-) -> Result<(), CommandResponse>
+) -> Result<TransactionReceipt, CommandResponse>
 where
     M: Middleware,
     D: Detokenize,
@@ -71,7 +109,6 @@ where
         .send(Withdraw(WithdrawStatus::Submitted { tx_hash }))
         .await;
     let receipt = pending
-        .interval(Duration::from_millis(1000))
         .await
         .map_err(|e| {
             let reason = e.to_string();
@@ -109,7 +146,7 @@ where
         .resource_metric_entry(resource_id)
         .total_gas_spent
         .inc_by(wei_to_gwei(gas_used));
-    Ok(())
+    Ok(receipt)
 }
 
 fn wei_to_gwei(wei: U256) -> f64 {
@@ -121,3 +158,64 @@ fn wei_to_gwei(wei: U256) -> f64 {
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::contract::protocol_solidity::VAnchorContract;
+    use webb::evm::ethers::abi::{self, Token};
+    use webb::evm::ethers::providers::{JsonRpcError, MockResponse, Provider};
+    use webb::evm::ethers::types::{Address, Bytes};
+
+    /// The `Error(string)` revert data, as returned by `eth_call`.
+    fn reverted(reason: &str) -> MockResponse {
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        data.extend(abi::encode(&[Token::String(reason.into())]));
+        MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: format!("execution reverted: {reason}"),
+            data: Some(serde_json::json!(Bytes::from(data))),
+        })
+    }
+
+    #[tokio::test]
+    async fn invalid_proofs_should_be_rejected() {
+        let (provider, mock) = Provider::mocked();
+        let contract =
+            VAnchorContract::new(Address::repeat_byte(1), Arc::new(provider));
+        let call = contract.token();
+
+        mock.push_response(reverted(INVALID_PROOF_REVERT));
+        assert_eq!(
+            preverify_proof(&call).await,
+            Err(CommandResponse::Withdraw(WithdrawStatus::InvalidProof {
+                reason: INVALID_PROOF_REVERT.into(),
+            }))
+        );
+
+        mock.push_response(reverted("Invalid merkle roots"));
+        assert!(matches!(
+            preverify_proof(&call).await,
+            Err(CommandResponse::Withdraw(WithdrawStatus::Errored { .. }))
+        ));
+
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "insufficient funds".into(),
+            data: None,
+        }));
+        assert_eq!(
+            preverify_proof(&call).await,
+            Err(CommandResponse::Withdraw(WithdrawStatus::Errored {
+                reason: "insufficient funds".into(),
+                code: -32000,
+            }))
+        );
+
+        mock.push(Bytes::from(abi::encode(&[Token::Address(
+            Address::repeat_byte(2),
+        )])))
+        .unwrap();
+        assert_eq!(preverify_proof(&call).await, Ok(()));
+    }
+}
@@ -0,0 +1,387 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::{
+    CommandResponse, CommandStream, EvmVanchorCommand, NetworkStatus,
+    RelayDelay, WithdrawStatus,
+};
+use webb_relayer_store::{ScheduledRelay, ScheduledRelayStore};
+use webb_relayer_utils::clock::RelayerRng;
+
+use super::vanchor::{check_vanchor_relay_tx, relay_vanchor_tx};
+use crate::dedup::{relay_fingerprint, release_relay};
+
+/// The most relays held at once for a chain, so that clients could not fill
+/// the store with delayed withdrawals.
+pub const MAX_SCHEDULED_RELAYS_PER_CHAIN: usize = 1024;
+
+/// Checks the delay asked for against the `max_delay` of the anchor, and
+/// picks how long to hold the transaction for, in secs.
+pub fn pick_delay(
+    delay: RelayDelay,
+    max_delay: Option<u64>,
+    rng: &RelayerRng,
+) -> Result<u64, CommandResponse> {
+    let Some(max_delay) = max_delay else {
+        return Err(CommandResponse::Error(
+            "This anchor does not support delayed withdrawals".to_string(),
+        ));
+    };
+    let (min, max) = delay.bounds();
+    if max > max_delay {
+        return Err(CommandResponse::Error(format!(
+            "The delay {delay} is longer than the maximum of {max_delay} secs"
+        )));
+    }
+    Ok(rng.gen_range(min..=max))
+}
+
+/// The key a relay is scheduled under, derived from the idempotency key of
+/// the command if any, otherwise from its external data hash.
+///
+/// Only the relays sent with an idempotency key can be cancelled, see
+/// [`cancel_scheduled_relay`], the external data hash being public once the
+/// withdrawal is sent.
+pub fn schedule_key(cmd: &EvmVanchorCommand) -> String {
+    match &cmd.idempotency_key {
+        Some(key) => idempotency_schedule_key(key),
+        None => format!("auto:{:?}", cmd.proof_data.ext_data_hash),
+    }
+}
+
+/// The key a relay sent with the given idempotency key is scheduled under.
+fn idempotency_schedule_key(idempotency_key: &str) -> String {
+    format!("key:{idempotency_key}")
+}
+
+/// Counts the relays scheduled on the given chain.
+fn count_scheduled_relays(
+    ctx: &RelayerContext,
+    chain_id: u64,
+) -> webb_relayer_utils::Result<usize> {
+    let relays = ctx.store().get_scheduled_relays()?;
+    let count = relays
+        .iter()
+        .filter(|(_, relay)| {
+            relay.command.get("chainId").and_then(|id| id.as_u64())
+                == Some(chain_id)
+        })
+        .count();
+    Ok(count)
+}
+
+/// Holds the command for the delay it asks for, then relays it.
+///
+/// The command is checked against the chain like a relay would, then stored,
+/// so that it is relayed even if the relayer restarts in the meantime, see
+/// [`resume_scheduled_relays`]. It also keeps going when the client
+/// disconnects. At most [`MAX_SCHEDULED_RELAYS_PER_CHAIN`] relays are held
+/// for a chain.
+pub async fn schedule_vanchor_relay_tx(
+    ctx: RelayerContext,
+    mut cmd: EvmVanchorCommand,
+    delay: RelayDelay,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
+    let chain = ctx
+        .config
+        .evm
+        .get(&cmd.chain_id.to_string())
+        .ok_or(Network(NetworkStatus::UnsupportedChain))?;
+    let max_delay = chain
+        .contracts
+        .iter()
+        .find_map(|c| match c {
+            webb_relayer_config::evm::Contract::VAnchor(c)
+                if c.common.address == cmd.id =>
+            {
+                Some(c.max_delay)
+            }
+            _ => None,
+        })
+        .ok_or(Network(NetworkStatus::UnsupportedContract))?;
+    let delay = pick_delay(delay, max_delay, ctx.rng())?;
+    if cmd.idempotency_key.as_deref() == Some("") {
+        return Err(Error("The idempotency key cannot be empty".to_string()));
+    }
+    // the roots, the proof and the fee are checked before holding the
+    // command, so that clients learn about a rejected withdrawal right away.
+    check_vanchor_relay_tx(&ctx, &cmd, &stream).await?;
+    let scheduled = count_scheduled_relays(&ctx, cmd.chain_id)
+        .map_err(|e| Error(format!("Failed to schedule the relay: {e}")))?;
+    if scheduled >= MAX_SCHEDULED_RELAYS_PER_CHAIN {
+        tracing::warn!(
+            chain_id = cmd.chain_id,
+            scheduled,
+            "Too many delayed withdrawals, rejecting a new one"
+        );
+        return Err(Error(
+            "Too many withdrawals are scheduled on this chain, \
+            try again later"
+                .to_string(),
+        ));
+    }
+    let key = schedule_key(&cmd);
+    cmd.delay = None;
+    let execute_after = ctx.clock().unix_timestamp() + delay;
+    let relay = ScheduledRelay {
+        execute_after,
+        command: serde_json::to_value(&cmd)
+            .map_err(|e| Error(format!("Failed to schedule the relay: {e}")))?,
+    };
+    let scheduled = ctx
+        .store()
+        .schedule_relay(&key, relay)
+        .map_err(|e| Error(format!("Failed to schedule the relay: {e}")))?;
+    if !scheduled {
+        return Err(Error(
+            "A withdrawal is already scheduled with this key".to_string(),
+        ));
+    }
+    tracing::debug!(delay, execute_after, "Scheduled a delayed withdrawal");
+    let _ = stream
+        .send(Withdraw(WithdrawStatus::Scheduled { execute_after }))
+        .await;
+    // a detached task, which keeps going if the handler is dropped.
+    let task =
+        tokio::spawn(run_scheduled_relay(ctx, key, execute_after, stream));
+    task.await
+        .map_err(|e| Error(format!("Scheduled relay failed: {e}")))?
+}
+
+/// Waits until `execute_after`, then relays the command scheduled under
+/// `key`, unless it got cancelled.
+///
+/// The fingerprint of a relay that fails is released, whether its client is
+/// still connected or not, see [`release_relay`].
+async fn run_scheduled_relay(
+    ctx: RelayerContext,
+    key: String,
+    execute_after: u64,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
+    let now = ctx.clock().unix_timestamp();
+    ctx.clock()
+        .sleep(Duration::from_secs(execute_after.saturating_sub(now)))
+        .await;
+    let relay = ctx
+        .store()
+        .take_scheduled_relay(&key)
+        .map_err(|e| Error(format!("Failed to load the scheduled relay: {e}")))?
+        .ok_or(Withdraw(WithdrawStatus::Cancelled))?;
+    let cmd: EvmVanchorCommand = serde_json::from_value(relay.command)
+        .map_err(|e| Error(format!("Invalid scheduled relay: {e}")))?;
+    let fingerprint = ctx
+        .config
+        .features
+        .relay_deduplication
+        .then(|| relay_fingerprint(&cmd));
+    let result = relay_vanchor_tx(ctx.clone(), cmd, stream).await;
+    if let (Some(fingerprint), Err(_)) = (fingerprint, &result) {
+        release_relay(&ctx, fingerprint);
+    }
+    result
+}
+
+/// Cancels the relay sent with the given idempotency key, before it gets
+/// submitted.
+///
+/// Returns false if there is no such relay, or its submission started.
+pub fn cancel_scheduled_relay(
+    ctx: &RelayerContext,
+    idempotency_key: &str,
+) -> webb_relayer_utils::Result<bool> {
+    if idempotency_key.is_empty() {
+        return Ok(false);
+    }
+    let key = idempotency_schedule_key(idempotency_key);
+    let Some(relay) = ctx.store().take_scheduled_relay(&key)? else {
+        return Ok(false);
+    };
+    tracing::debug!(key, "Cancelled a delayed withdrawal");
+    // the withdrawal can be sent again.
+    if ctx.config.features.relay_deduplication {
+        if let Ok(cmd) =
+            serde_json::from_value::<EvmVanchorCommand>(relay.command)
+        {
+            release_relay(ctx, relay_fingerprint(&cmd));
+        }
+    }
+    Ok(true)
+}
+
+/// Resumes, in the background, the relays scheduled before the relayer got
+/// restarted.
+///
+/// Their clients are gone, so their responses are only logged.
+///
+/// Returns the number of resumed relays.
+pub fn resume_scheduled_relays(
+    ctx: &RelayerContext,
+) -> webb_relayer_utils::Result<usize> {
+    let relays = ctx.store().get_scheduled_relays()?;
+    let count = relays.len();
+    for (key, relay) in relays {
+        let (stream, mut responses) = mpsc::channel(16);
+        let task = run_scheduled_relay(
+            ctx.clone(),
+            key.clone(),
+            relay.execute_after,
+            stream,
+        );
+        tokio::spawn(async move {
+            let logger = async {
+                while let Some(response) = responses.recv().await {
+                    tracing::debug!(%key, ?response, "Scheduled relay update");
+                }
+            };
+            let (result, _) = tokio::join!(task, logger);
+            match result {
+                Ok(()) => {
+                    tracing::info!(%key, "Relayed a scheduled withdrawal")
+                }
+                Err(e) => tracing::warn!(
+                    %key,
+                    ?e,
+                    "Failed to relay a scheduled withdrawal"
+                ),
+            }
+        });
+    }
+    if count > 0 {
+        tracing::info!(count, "Resumed the scheduled withdrawals");
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{
+        anchor_config, command as withdrawal, context_with_chain,
+    };
+
+    /// A context with a chain whose VAnchor takes delayed withdrawals.
+    fn context() -> RelayerContext {
+        context_with_chain(|chain| {
+            anchor_config(chain).max_delay = Some(600);
+        })
+    }
+
+    fn command(idempotency_key: Option<&str>) -> EvmVanchorCommand {
+        let mut cmd = withdrawal();
+        cmd.idempotency_key = idempotency_key.map(Into::into);
+        cmd
+    }
+
+    fn relay(chain_id: u64) -> ScheduledRelay {
+        ScheduledRelay {
+            execute_after: 100,
+            command: serde_json::json!({ "chainId": chain_id }),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_withdrawals_should_not_be_scheduled() {
+        let ctx = context();
+        let (stream, _responses) = mpsc::channel(16);
+        // the command pays another relayer.
+        let result = schedule_vanchor_relay_tx(
+            ctx.clone(),
+            command(Some("secret")),
+            RelayDelay::Fixed(60),
+            stream,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(CommandResponse::Network(
+                NetworkStatus::InvalidRelayerAddress
+            ))
+        );
+        assert!(ctx.store().get_scheduled_relays().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_the_client_should_cancel_its_withdrawal() {
+        let ctx = context();
+        let keyless = command(None);
+        ctx.store()
+            .schedule_relay(&schedule_key(&keyless), relay(5001))
+            .unwrap();
+        let keyed = command(Some("secret"));
+        ctx.store()
+            .schedule_relay(&schedule_key(&keyed), relay(5001))
+            .unwrap();
+
+        // the external data hash is public, it cancels nothing.
+        let ext_data_hash = format!("{:?}", keyless.proof_data.ext_data_hash);
+        assert!(!cancel_scheduled_relay(&ctx, &ext_data_hash).unwrap());
+        assert!(!cancel_scheduled_relay(&ctx, &schedule_key(&keyless)).unwrap());
+        assert!(!cancel_scheduled_relay(&ctx, "").unwrap());
+        assert_eq!(ctx.store().get_scheduled_relays().unwrap().len(), 2);
+
+        assert!(cancel_scheduled_relay(&ctx, "secret").unwrap());
+        assert!(!cancel_scheduled_relay(&ctx, "secret").unwrap());
+        assert_eq!(ctx.store().get_scheduled_relays().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn scheduled_relays_should_be_counted_per_chain() {
+        let ctx = context();
+        for (i, chain_id) in [5001, 5001, 5002].into_iter().enumerate() {
+            ctx.store()
+                .schedule_relay(&i.to_string(), relay(chain_id))
+                .unwrap();
+        }
+        assert_eq!(count_scheduled_relays(&ctx, 5001).unwrap(), 2);
+        assert_eq!(count_scheduled_relays(&ctx, 5002).unwrap(), 1);
+        assert_eq!(count_scheduled_relays(&ctx, 5003).unwrap(), 0);
+    }
+
+    #[test]
+    fn delays_should_be_parsed() {
+        assert_eq!("90".parse(), Ok(RelayDelay::Fixed(90)));
+        assert_eq!(
+            "random(60, 600)".parse(),
+            Ok(RelayDelay::Random { min: 60, max: 600 })
+        );
+        assert!("random(600,60)".parse::<RelayDelay>().is_err());
+        assert!("soon".parse::<RelayDelay>().is_err());
+        let delay: RelayDelay =
+            serde_json::from_value(serde_json::json!(30)).unwrap();
+        assert_eq!(delay, RelayDelay::Fixed(30));
+    }
+
+    #[test]
+    fn delays_should_be_bounded_by_the_anchor() {
+        let rng = RelayerRng::seeded(7);
+        let delay = RelayDelay::Random { min: 60, max: 600 };
+        let picked = pick_delay(delay, Some(600), &rng).unwrap();
+        assert!((60..=600).contains(&picked));
+        assert_eq!(
+            pick_delay(delay, Some(600), &RelayerRng::seeded(7)),
+            Ok(picked),
+        );
+        assert!(pick_delay(delay, Some(300), &rng).is_err());
+        assert!(pick_delay(RelayDelay::Fixed(1), None, &rng).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::*;
+use ethereum_types::Address;
+use webb::evm::ethers::prelude::Signer;
+use webb_relayer_config::evm::EvmChainConfig;
+use webb_relayer_context::{EvmSignerClient, RelayerContext};
+use webb_relayer_handler_utils::NetworkStatus;
+
+/// Everything needed to send transactions on an EVM chain on behalf of
+/// the relayer: the configuration of the chain, and its signer client.
+#[derive(Clone)]
+pub struct EvmTxContext {
+    chain: EvmChainConfig,
+    client: Arc<EvmSignerClient>,
+    metrics: Arc<Mutex<metric::Metrics>>,
+}
+
+impl EvmTxContext {
+    /// Resolves the configuration of the chain, and its signer client.
+    ///
+    /// Returns `ChainNotFound` if the relayer is not configured for the chain,
+    /// and `MissingSecrets` if it has no private key for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - RelayContext reference that holds the configuration
+    /// * `chain_id` - The chain id of the chain
+    pub async fn new(
+        ctx: &RelayerContext,
+        chain_id: u64,
+    ) -> webb_relayer_utils::Result<Self> {
+        let chain = ctx
+            .config
+            .evm
+            .get(&chain_id.to_string())
+            .cloned()
+            .ok_or_else(|| webb_relayer_utils::Error::ChainNotFound {
+                chain_id: chain_id.to_string(),
+            })?;
+        let client = ctx.evm_signer_client(chain_id).await?;
+        Ok(Self {
+            chain,
+            client,
+            metrics: ctx.metrics.clone(),
+        })
+    }
+
+    /// Same as [`EvmTxContext::new`], with the errors reported the way the
+    /// relay commands report them.
+    pub async fn for_command(
+        ctx: &RelayerContext,
+        chain_id: u64,
+    ) -> Result<Self, CommandResponse> {
+        use CommandResponse::*;
+        Self::new(ctx, chain_id).await.map_err(|e| match e {
+            webb_relayer_utils::Error::ChainNotFound { .. } => {
+                Network(NetworkStatus::UnsupportedChain)
+            }
+            e => Error(format!("Misconfigured Network: {chain_id:?}, {e}")),
+        })
+    }
+
+    /// The configuration of the chain.
+    pub fn chain(&self) -> &EvmChainConfig {
+        &self.chain
+    }
+
+    /// The signer client of the chain.
+    pub fn client(&self) -> Arc<EvmSignerClient> {
+        self.client.clone()
+    }
+
+    /// The address of the relayer wallet on the chain.
+    pub fn address(&self) -> Address {
+        self.client.signer().address()
+    }
+
+    /// The address the relayer gets its fees on, which is the beneficiary
+    /// if one is configured, or the relayer wallet otherwise.
+    pub fn reward_address(&self) -> Address {
+        self.chain.beneficiary.unwrap_or_else(|| self.address())
+    }
+
+    /// Estimates the gas the call would use.
+    pub async fn estimate_gas<D: Detokenize>(
+        &self,
+        call: &ContractCall<EvmSignerClient, D>,
+    ) -> Result<U256, CommandResponse> {
+        self.client.estimate_gas(&call.tx, None).await.map_err(|e| {
+            CommandResponse::Network(NetworkStatus::Failed {
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    /// Sends the call, and reports its progress to the `stream`, see
+    /// [`handle_evm_tx`].
+    ///
+    /// Returns the receipt of the finalized transaction.
+    pub async fn submit<D: Detokenize>(
+        &self,
+        call: ContractCall<EvmSignerClient, D>,
+        stream: CommandStream,
+        resource_id: ResourceId,
+    ) -> Result<TransactionReceipt, CommandResponse> {
+        handle_evm_tx(
+            call,
+            stream,
+            self.chain.chain_id.into(),
+            self.metrics.clone(),
+            resource_id,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::context_with_chain;
+
+    #[tokio::test]
+    async fn unknown_chain_should_be_rejected() {
+        let ctx = context_with_chain(|_| {});
+        let result = EvmTxContext::new(&ctx, 5002).await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::ChainNotFound { .. })
+        ));
+        let result = EvmTxContext::for_command(&ctx, 5002).await;
+        assert!(matches!(
+            result,
+            Err(CommandResponse::Network(NetworkStatus::UnsupportedChain))
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_key_should_be_rejected() {
+        let ctx = context_with_chain(|chain| chain.private_key = None);
+        let result = EvmTxContext::new(&ctx, 5001).await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::MissingSecrets)
+        ));
+        let result = EvmTxContext::for_command(&ctx, 5001).await;
+        assert!(matches!(result, Err(CommandResponse::Error(_))));
+    }
+
+    #[tokio::test]
+    async fn signer_client_should_be_cached() {
+        let ctx = context_with_chain(|_| {});
+        let first = EvmTxContext::new(&ctx, 5001).await.unwrap();
+        let second = EvmTxContext::new(&ctx, 5001).await.unwrap();
+        assert!(Arc::ptr_eq(&first.client(), &second.client()));
+        assert_eq!(first.reward_address(), first.address());
+    }
+}
@@ -1,24 +1,44 @@
 use super::*;
+use crate::dedup::{
+    claim_relay, record_relay_tx, relay_fingerprint, release_relay,
+};
+use crate::evm::fee_permit::{
+    check_fee_permit, simulate_fee_permit, with_fee_permit,
+};
 use crate::evm::fees::{get_evm_fee_info, EvmFeeInfo};
-use crate::evm::handle_evm_tx;
+use crate::evm::scheduled::schedule_vanchor_relay_tx;
+use crate::evm::{preverify_proof, EvmTxContext};
 use ethereum_types::U256;
 use futures::TryFutureExt;
-use std::{collections::HashMap, sync::Arc};
+use std::collections::HashMap;
 use webb::evm::ethers::utils::{format_units, parse_ether};
 use webb::evm::{
     contract::protocol_solidity::{
         variable_anchor::{CommonExtData, Encryptions, PublicInputs},
         VAnchorContract,
     },
-    ethers::prelude::{Signer, SignerMiddleware},
+    ethers::prelude::Signer,
 };
 use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
-use webb_relayer_context::RelayerContext;
-use webb_relayer_handler_utils::EvmVanchorCommand;
+use webb_relayer_config::evm::VAnchorContractConfig;
+use webb_relayer_context::{EvmSignerClient, RelayerContext};
 use webb_relayer_handler_utils::{CommandStream, NetworkStatus};
+use webb_relayer_handler_utils::{EvmFeePermit, EvmVanchorCommand};
+use webb_relayer_store::{
+    AmountReservation, FeeAccountingStore, RelayedWithdrawal,
+    RelayedWithdrawalStore, WithdrawalLimitStore,
+};
+
+/// The window over which the withdrawal amount limits apply, in secs.
+pub const AMOUNT_LIMIT_WINDOW_SECS: u64 = 60 * 60;
 
 /// Handler for VAnchor commands
 ///
+/// Commands with a delay are held for it first, see
+/// [`schedule_vanchor_relay_tx`]. With the `relay-deduplication` feature,
+/// the retries of an accepted command get its status instead, see
+/// [`claim_relay`].
+///
 /// # Arguments
 ///
 /// * `ctx` - RelayContext reference that holds the configuration
@@ -26,17 +46,51 @@ use webb_relayer_handler_utils::{CommandStream, NetworkStatus};
 /// * `stream` - The stream to write the response to
 pub async fn handle_vanchor_relay_tx<'a>(
     ctx: RelayerContext,
-    cmd: EvmVanchorCommand,
+    mut cmd: EvmVanchorCommand,
     stream: CommandStream,
 ) -> Result<(), CommandResponse> {
+    let fingerprint = if ctx.config.features.relay_deduplication {
+        Some(claim_relay(&ctx, &cmd)?)
+    } else {
+        None
+    };
+    let result = match cmd.delay.take() {
+        Some(delay) => {
+            schedule_vanchor_relay_tx(ctx.clone(), cmd, delay, stream).await
+        }
+        None => relay_vanchor_tx(ctx.clone(), cmd, stream).await,
+    };
+    if let (Some(fingerprint), Err(_)) = (fingerprint, &result) {
+        release_relay(&ctx, fingerprint);
+    }
+    result
+}
+
+/// A VAnchor command checked against its chain, ready to be submitted.
+pub(crate) struct CheckedVanchorTx {
+    tx_ctx: EvmTxContext,
+    call: ContractCall<EvmSignerClient, ()>,
+    contract_config: VAnchorContractConfig,
+    /// The fee of the relayer, paid with the permit, the external data, or
+    /// both.
+    fee: U256,
+    /// The amount moved out of the anchor.
+    withdrawn: U256,
+}
+
+/// Checks the VAnchor command against its chain, without sending it: the
+/// contract, the relayer address, the roots, the fee permit, the proof and
+/// the fee. The gas estimate dry runs the call, so it also rejects unknown
+/// roots and invalid proofs.
+pub(crate) async fn check_vanchor_relay_tx(
+    ctx: &RelayerContext,
+    cmd: &EvmVanchorCommand,
+    stream: &CommandStream,
+) -> Result<CheckedVanchorTx, CommandResponse> {
     use CommandResponse::*;
 
-    let requested_chain = cmd.chain_id;
-    let chain = ctx
-        .config
-        .evm
-        .get(&requested_chain.to_string())
-        .ok_or(Network(NetworkStatus::UnsupportedChain))?;
+    let tx_ctx = EvmTxContext::for_command(ctx, cmd.chain_id).await?;
+    let chain = tx_ctx.chain();
     let supported_contracts: HashMap<_, _> = chain
         .contracts
         .iter()
@@ -51,15 +105,16 @@ pub async fn handle_vanchor_relay_tx<'a>(
     let contract_config = supported_contracts
         .get(&cmd.id)
         .ok_or(Network(NetworkStatus::UnsupportedContract))?;
+    if !chain.is_contract_enabled(cmd.id) {
+        return Err(Network(NetworkStatus::DisabledContract));
+    }
+    if contract_config.events_watcher.is_static() {
+        return Err(Network(NetworkStatus::StaticContract));
+    }
 
-    let wallet = ctx.evm_wallet(cmd.chain_id).await.map_err(|e| {
-        Error(format!("Misconfigured Network: {:?}, {e}", cmd.chain_id))
-    })?;
     // validate the relayer address first before trying
     // send the transaction.
-    let reward_address = chain.beneficiary.unwrap_or(wallet.address());
-
-    if cmd.ext_data.relayer != reward_address {
+    if cmd.ext_data.relayer != tx_ctx.reward_address() {
         return Err(Network(NetworkStatus::InvalidRelayerAddress));
     }
 
@@ -69,20 +124,41 @@ pub async fn handle_vanchor_relay_tx<'a>(
         return Err(Withdraw(WithdrawStatus::InvalidMerkleRoots));
     }
 
+    // the fee permits go through the fee collector of the relayer on the
+    // chain, together with the withdrawal.
+    let fee_permit = match &cmd.fee_permit {
+        Some(permit) => {
+            let rejected = |reason: &str| {
+                Withdraw(WithdrawStatus::FeePermitRejected {
+                    reason: reason.to_string(),
+                })
+            };
+            let fee_collector = chain.fee_collector.ok_or_else(|| {
+                rejected("The relayer takes no fee permits on this chain")
+            })?;
+            if permit.token != cmd.ext_data.token {
+                return Err(rejected(
+                    "The fee has to be paid in the withdrawn token",
+                ));
+            }
+            // the fee collector does not forward the refund.
+            if !cmd.ext_data.refund.is_zero() {
+                return Err(rejected("No refund is paid with a fee permit"));
+            }
+            Some((permit.clone(), fee_collector))
+        }
+        None => None,
+    };
+
     tracing::debug!(
         "Connecting to chain {:?} .. at {}",
         cmd.chain_id,
         chain.http_endpoint
     );
     let _ = stream.send(Network(NetworkStatus::Connecting)).await;
-    let provider = ctx.evm_provider(cmd.chain_id).await.map_err(|e| {
-        Network(NetworkStatus::Failed {
-            reason: e.to_string(),
-        })
-    })?;
+    let client = tx_ctx.client();
     let _ = stream.send(Network(NetworkStatus::Connected)).await;
 
-    let client = Arc::new(SignerMiddleware::new(provider, wallet));
     let contract = VAnchorContract::new(cmd.id, client.clone());
 
     let common_ext_data = CommonExtData {
@@ -95,7 +171,7 @@ pub async fn handle_vanchor_relay_tx<'a>(
     };
     let public_inputs = PublicInputs {
         roots: roots.into(),
-        extension_roots: cmd.proof_data.extension_roots,
+        extension_roots: cmd.proof_data.extension_roots.clone(),
         input_nullifiers: cmd
             .proof_data
             .input_nullifiers
@@ -105,7 +181,7 @@ pub async fn handle_vanchor_relay_tx<'a>(
         output_commitments: cmd
             .proof_data
             .output_commitments
-            .into_iter()
+            .iter()
             .map(|c| U256::from(c.to_fixed_bytes()))
             .collect::<Vec<_>>()
             .try_into()
@@ -117,14 +193,14 @@ pub async fn handle_vanchor_relay_tx<'a>(
     };
 
     let encryptions = Encryptions {
-        encrypted_output_1: cmd.ext_data.encrypted_output1,
-        encrypted_output_2: cmd.ext_data.encrypted_output2,
+        encrypted_output_1: cmd.ext_data.encrypted_output1.clone(),
+        encrypted_output_2: cmd.ext_data.encrypted_output2.clone(),
     };
 
     tracing::trace!(?cmd.proof_data.proof, ?common_ext_data, "Client Proof");
 
     let mut call = contract.transact(
-        cmd.proof_data.proof,
+        cmd.proof_data.proof.clone(),
         [0u8; 32].into(),
         common_ext_data,
         public_inputs,
@@ -135,18 +211,44 @@ pub async fn handle_vanchor_relay_tx<'a>(
         call = call.value(cmd.ext_data.refund);
     }
 
-    let gas_amount =
-        client.estimate_gas(&call.tx, None).await.map_err(|e| {
-            Network(NetworkStatus::Failed {
-                reason: e.to_string(),
-            })
-        })?;
+    if ctx.config.features.proof_preverification {
+        preverify_proof(&call).await?;
+    }
+
+    // only withdrawals move value out of the anchor.
+    let withdrawn = if cmd.ext_data.ext_amount.0.is_negative() {
+        cmd.ext_data.ext_amount.0.unsigned_abs()
+    } else {
+        U256::zero()
+    };
+    if let Some((permit, fee_collector)) = &fee_permit {
+        check_fee_permit(
+            client.as_ref(),
+            permit,
+            cmd.ext_data.recipient,
+            *fee_collector,
+            withdrawn,
+            ctx.clock().unix_timestamp(),
+        )
+        .await?;
+        let withdrawal = call.tx.clone();
+        with_fee_permit(
+            &mut call.tx,
+            permit,
+            cmd.ext_data.recipient,
+            tx_ctx.reward_address(),
+            *fee_collector,
+        );
+        simulate_fee_permit(client.as_ref(), &withdrawal, &call.tx).await?;
+    }
+
+    let gas_amount = tx_ctx.estimate_gas(&call).await?;
     let typed_chain_id = TypedChainId::Evm(chain.chain_id);
     let fee_info = get_evm_fee_info(
         typed_chain_id,
         contract_config.common.address,
         gas_amount,
-        &ctx,
+        ctx,
     )
     .await
     .map_err(|e| {
@@ -164,7 +266,10 @@ pub async fn handle_vanchor_relay_tx<'a>(
         return Err(Error(msg));
     }
 
-    // check the fee
+    let fee = relayer_fee(
+        cmd.ext_data.fee,
+        fee_permit.as_ref().map(|(permit, _)| permit),
+    );
     // TODO: This adjustment could potentially be exploited
     let adjusted_fee = fee_info.estimated_fee / 100 * 96;
     let wrapped_amount =
@@ -172,23 +277,131 @@ pub async fn handle_vanchor_relay_tx<'a>(
             .map_err(|e| {
                 Error(format!("Failed to calculate wrapped refund amount: {e}"))
             })?;
-    if cmd.ext_data.fee < adjusted_fee + wrapped_amount {
+    if fee < adjusted_fee + wrapped_amount {
         let msg = format!(
             "User sent a fee that is too low {} but expected {}",
-            cmd.ext_data.fee,
+            fee,
             adjusted_fee + wrapped_amount
         );
         return Err(Error(msg));
     }
+    Ok(CheckedVanchorTx {
+        tx_ctx,
+        call,
+        contract_config: contract_config.clone(),
+        fee,
+        withdrawn,
+    })
+}
+
+/// Relays the VAnchor command right away.
+pub(crate) async fn relay_vanchor_tx(
+    ctx: RelayerContext,
+    cmd: EvmVanchorCommand,
+    stream: CommandStream,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
 
+    let fingerprint = ctx
+        .config
+        .features
+        .relay_deduplication
+        .then(|| relay_fingerprint(&cmd));
+    let CheckedVanchorTx {
+        tx_ctx,
+        call,
+        contract_config,
+        fee,
+        withdrawn,
+    } = check_vanchor_relay_tx(&ctx, &cmd, &stream).await?;
+    let client = tx_ctx.client();
+    let typed_chain_id = TypedChainId::Evm(tx_ctx.chain().chain_id);
     let target_system = TargetSystem::new_contract_address(
         contract_config.common.address.to_fixed_bytes(),
     );
     let resource_id = ResourceId::new(target_system, typed_chain_id);
 
+    let reservation = match &contract_config.max_amount_per_hour {
+        Some(limit) if !withdrawn.is_zero() => {
+            let now = ctx.clock().unix_timestamp();
+            let reservation = ctx
+                .store()
+                .reserve_relayed_amount(
+                    resource_id,
+                    withdrawn,
+                    *limit,
+                    AMOUNT_LIMIT_WINDOW_SECS,
+                    now,
+                )
+                .map_err(|e| {
+                    Error(format!("Failed to check the withdrawal limit: {e}"))
+                })?;
+            match reservation {
+                AmountReservation::Reserved { id } => Some(id),
+                AmountReservation::Exceeded { retry_after_secs } => {
+                    tracing::warn!(
+                        ?resource_id,
+                        %withdrawn,
+                        ?retry_after_secs,
+                        "Withdrawal over the hourly amount limit"
+                    );
+                    return Err(Withdraw(WithdrawStatus::RateLimitAmount {
+                        retry_after_secs,
+                    }));
+                }
+            }
+        }
+        _ => None,
+    };
+
     tracing::trace!("About to send Tx to {:?} Chain", cmd.chain_id);
-    handle_evm_tx(call, stream, cmd.chain_id, ctx.metrics.clone(), resource_id)
-        .await?;
+    let started_at = std::time::Instant::now();
+    let result = tx_ctx.submit(call, stream, resource_id).await;
+    let receipt = match result {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            // the amount never left the anchor.
+            if let Some(id) = reservation {
+                if let Err(e) =
+                    ctx.store().release_relayed_amount(resource_id, id)
+                {
+                    tracing::warn!(
+                        "Failed to release the withdrawn amount: {e}"
+                    );
+                }
+            }
+            return Err(e);
+        }
+    };
+    ctx.record_relayed_tx(typed_chain_id, receipt.transaction_hash)
+        .await;
+    if let Some(fingerprint) = fingerprint {
+        record_relay_tx(&ctx, fingerprint, receipt.transaction_hash);
+    }
+    let finalized_at = ctx.clock().unix_timestamp();
+    if let Err(e) = ctx.store().record_fee(
+        typed_chain_id,
+        &format!("{:?}", cmd.ext_data.token),
+        fee,
+        finalized_at,
+    ) {
+        tracing::warn!("Failed to record the collected fee: {e}");
+    }
+    if ctx.config.features.withdrawals_query {
+        let withdrawal = RelayedWithdrawal {
+            tx_hash: receipt.transaction_hash,
+            finalized_at,
+            confirmation_latency_ms: started_at.elapsed().as_millis() as u64,
+            fee,
+            gas_used: receipt.gas_used.unwrap_or_default(),
+        };
+        if let Err(e) = ctx
+            .store()
+            .insert_relayed_withdrawal(resource_id, withdrawal)
+        {
+            tracing::warn!("Failed to record the relayed withdrawal: {e}");
+        }
+    }
 
     // update metric
     let metrics_clone = ctx.metrics.clone();
@@ -197,12 +410,10 @@ pub async fn handle_vanchor_relay_tx<'a>(
     metrics
         .resource_metric_entry(resource_id)
         .total_fee_earned
-        .inc_by(cmd.ext_data.fee.as_u128() as f64);
+        .inc_by(fee.as_u128() as f64);
 
     // update metric for total fee earned by relayer
-    metrics
-        .total_fee_earned
-        .inc_by(cmd.ext_data.fee.as_u128() as f64);
+    metrics.total_fee_earned.inc_by(fee.as_u128() as f64);
 
     let relayer_balance = client
         .get_balance(client.signer().address(), None)
@@ -215,6 +426,14 @@ pub async fn handle_vanchor_relay_tx<'a>(
     Ok(())
 }
 
+/// The fee of the relayer, paid with the fee `permit`, the fee of the
+/// external data, or both.
+fn relayer_fee(ext_data_fee: U256, permit: Option<&EvmFeePermit>) -> U256 {
+    permit.map_or(ext_data_fee, |permit| {
+        ext_data_fee.saturating_add(permit.amount)
+    })
+}
+
 fn calculate_wrapped_refund_amount(
     refund: U256,
     fee_info: &EvmFeeInfo,
@@ -224,3 +443,116 @@ fn calculate_wrapped_refund_amount(
     let refund_amount: f32 = format_units(refund, "ether")?.parse()?;
     Ok(parse_ether(refund_amount / refund_exchange_rate)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::{Address, H256};
+    use tokio::sync::mpsc;
+    use webb::evm::ethers::signers::LocalWallet;
+    use webb_relayer_config::event_watcher::EventsSyncMode;
+
+    use crate::test_utils::*;
+
+    async fn relay(
+        ctx: &RelayerContext,
+        cmd: EvmVanchorCommand,
+    ) -> Result<(), CommandResponse> {
+        let (stream, _responses) = mpsc::channel(16);
+        relay_vanchor_tx(ctx.clone(), cmd, stream).await
+    }
+
+    #[tokio::test]
+    async fn withdrawals_through_disabled_contracts_should_be_rejected() {
+        let ctx = context_with_chain(|chain| {
+            chain.enabled_contracts = Some(vec![Address::repeat_byte(2)]);
+        });
+        assert_eq!(
+            relay(&ctx, command()).await,
+            Err(CommandResponse::Network(NetworkStatus::DisabledContract))
+        );
+
+        // the others are only rejected further on, for the relayer address.
+        let ctx = context_with_chain(|chain| {
+            chain.enabled_contracts = Some(vec![anchor()]);
+        });
+        assert_eq!(
+            relay(&ctx, command()).await,
+            Err(CommandResponse::Network(
+                NetworkStatus::InvalidRelayerAddress
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn withdrawals_through_static_contracts_should_be_rejected() {
+        let ctx = context_with_chain(|chain| {
+            anchor_config(chain).events_watcher.sync = EventsSyncMode::Static;
+        });
+        assert_eq!(
+            relay(&ctx, command()).await,
+            Err(CommandResponse::Network(NetworkStatus::StaticContract))
+        );
+    }
+
+    /// A withdrawal paying its fee with a permit, sent to the relayer.
+    fn command_with_fee_permit(
+        token: Address,
+        refund: u64,
+    ) -> EvmVanchorCommand {
+        let mut cmd = command();
+        cmd.ext_data.relayer =
+            PRIVATE_KEY.parse::<LocalWallet>().unwrap().address();
+        cmd.ext_data.refund = refund.into();
+        cmd.fee_permit = Some(EvmFeePermit {
+            token,
+            amount: U256::from(1_000),
+            deadline: 1_700_000_000,
+            v: 27,
+            r: H256::repeat_byte(8),
+            s: H256::repeat_byte(9),
+        });
+        cmd
+    }
+
+    fn permit_rejection(result: Result<(), CommandResponse>) -> String {
+        match result {
+            Err(CommandResponse::Withdraw(
+                WithdrawStatus::FeePermitRejected { reason },
+            )) => reason,
+            result => panic!("the fee permit was not rejected: {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn fee_permits_should_be_taken_through_the_fee_collector() {
+        let token = command().ext_data.token;
+        let ctx = context_with_chain(|_| {});
+        let reason = permit_rejection(
+            relay(&ctx, command_with_fee_permit(token, 0)).await,
+        );
+        assert_eq!(reason, "The relayer takes no fee permits on this chain");
+
+        let ctx = context_with_chain(|chain| {
+            chain.fee_collector = Some(Address::repeat_byte(0xfc));
+        });
+        let other_token = Address::repeat_byte(0x70);
+        let reason = permit_rejection(
+            relay(&ctx, command_with_fee_permit(other_token, 0)).await,
+        );
+        assert_eq!(reason, "The fee has to be paid in the withdrawn token");
+        let reason = permit_rejection(
+            relay(&ctx, command_with_fee_permit(token, 5)).await,
+        );
+        assert_eq!(reason, "No refund is paid with a fee permit");
+    }
+
+    #[test]
+    fn fee_permits_should_add_up_with_the_ext_data_fee() {
+        let cmd = command_with_fee_permit(command().ext_data.token, 0);
+        let permit = cmd.fee_permit.as_ref();
+        assert_eq!(relayer_fee(U256::from(250), permit), U256::from(1_250));
+        assert_eq!(relayer_fee(U256::from(250), None), U256::from(250));
+        assert_eq!(relayer_fee(U256::MAX, permit), U256::MAX);
+    }
+}
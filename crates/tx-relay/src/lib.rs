@@ -1,9 +1,13 @@
+/// Deduplication of the withdrawals retried by their clients.
+pub mod dedup;
 /// EVM Transactional Relayer.
 #[cfg(feature = "evm")]
 pub mod evm;
 /// Substrate Transactional Relayer.
 #[cfg(feature = "substrate")]
 pub mod substrate;
+#[cfg(all(test, feature = "evm"))]
+mod test_utils;
 
 /// Maximum refund amount per relay transaction in USD.
 const MAX_REFUND_USD: f64 = 5.;
@@ -0,0 +1,198 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use ethereum_types::H256;
+use tokio::sync::oneshot;
+use webb::substrate::subxt::tx::PairSigner;
+use webb::substrate::subxt::PolkadotConfig;
+use webb::substrate::tangle_runtime::api;
+use webb::substrate::tangle_runtime::api::runtime_types::tangle_standalone_runtime::RuntimeCall;
+use webb_relayer_context::batch::BatchAccumulator;
+use webb_relayer_context::{BatchedCall, RelayerContext};
+use webb_relayer_handler_utils::{
+    CommandResponse, CommandStream, WithdrawStatus,
+};
+
+/// Sends the call to the node in the next batch of the chain, and waits for
+/// the batch to be finalized.
+///
+/// The client is told the withdrawal got sent once the batch is, when it is
+/// full or after its flush timeout.
+///
+/// Returns the hash of the batch extrinsic.
+pub async fn submit_in_batch(
+    ctx: &RelayerContext,
+    chain_id: u32,
+    call: RuntimeCall,
+    stream: CommandStream,
+) -> Result<Option<H256>, CommandResponse> {
+    use CommandResponse::*;
+    let node_config = ctx
+        .config
+        .substrate
+        .get(&chain_id.to_string())
+        .ok_or_else(|| Error(format!("Unsupported chain {chain_id}")))?;
+    let batch = ctx
+        .substrate_batch(chain_id, || {
+            let flush_ctx = ctx.clone();
+            BatchAccumulator::spawn(
+                ctx,
+                format!("Substrate batch of chain {chain_id}"),
+                node_config.max_batch_size,
+                Duration::from_millis(node_config.batch_flush_timeout_ms),
+                move |batch| flush_batch(flush_ctx.clone(), chain_id, batch),
+            )
+        })
+        .await;
+    let (sent, sent_rx) = oneshot::channel();
+    let (outcome, outcome_rx) = oneshot::channel();
+    batch
+        .push(BatchedCall {
+            call,
+            sent,
+            outcome,
+        })
+        .map_err(|_| Error("The batch of the chain is closed".to_string()))?;
+    // dropped without a notice if the batch could not be sent.
+    if sent_rx.await.is_ok() {
+        let _ = stream.send(Withdraw(WithdrawStatus::Sent)).await;
+    }
+    let outcome = outcome_rx
+        .await
+        .map_err(|_| Error("The batch got dropped".to_string()))?;
+    match outcome {
+        Ok(tx_hash) => {
+            let _ = stream
+                .send(Withdraw(WithdrawStatus::Finalized { tx_hash }))
+                .await;
+            Ok(Some(tx_hash))
+        }
+        Err(reason) => {
+            Err(Withdraw(WithdrawStatus::Errored { reason, code: 4 }))
+        }
+    }
+}
+
+/// Signs and sends the batch in a single `utility.batch` extrinsic, then
+/// notifies the callers in the background, once it is finalized.
+async fn flush_batch(
+    ctx: RelayerContext,
+    chain_id: u32,
+    batch: Vec<BatchedCall>,
+) {
+    let size = batch.len();
+    let mut calls = Vec::with_capacity(size);
+    let mut senders = Vec::with_capacity(size);
+    let mut sent = Vec::with_capacity(size);
+    for call in batch {
+        calls.push(call.call);
+        senders.push(call.outcome);
+        sent.push(call.sent);
+    }
+    let progress = match submit_batch(&ctx, chain_id, calls).await {
+        Ok(progress) => progress,
+        Err(reason) => {
+            tracing::warn!(chain_id, size, "Failed to send a batch: {reason}");
+            for sender in senders {
+                let _ = sender.send(Err(reason.clone()));
+            }
+            return;
+        }
+    };
+    tracing::debug!(chain_id, size, "Sent a batch of extrinsics");
+    for sent in sent {
+        let _ = sent.send(());
+    }
+    tokio::spawn(async move {
+        let events = match progress.wait_for_finalized_success().await {
+            Ok(events) => events,
+            Err(e) => {
+                let reason = format!("Error while watching Tx: {e}");
+                for sender in senders {
+                    let _ = sender.send(Err(reason.clone()));
+                }
+                return;
+            }
+        };
+        let tx_hash = H256::from_slice(events.extrinsic_hash().as_ref());
+        // the batch stops at the first call failing, the calls after it are
+        // not executed.
+        let interrupted = events
+            .find_first::<api::utility::events::BatchInterrupted>()
+            .ok()
+            .flatten();
+        for (index, sender) in senders.into_iter().enumerate() {
+            let outcome = match &interrupted {
+                Some(event) if index == event.index as usize => {
+                    Err(format!("The transaction failed: {:?}", event.error))
+                }
+                Some(event) if index > event.index as usize => {
+                    Err("An earlier transaction of the batch failed"
+                        .to_string())
+                }
+                _ => Ok(tx_hash),
+            };
+            let _ = sender.send(outcome);
+        }
+    });
+}
+
+async fn submit_batch(
+    ctx: &RelayerContext,
+    chain_id: u32,
+    calls: Vec<RuntimeCall>,
+) -> Result<
+    webb::substrate::subxt::tx::TxProgress<
+        PolkadotConfig,
+        webb::substrate::subxt::OnlineClient<PolkadotConfig>,
+    >,
+    String,
+> {
+    let client = ctx
+        .substrate_provider::<PolkadotConfig, _>(chain_id)
+        .await
+        .map_err(|e| format!("Error while getting Substrate client: {e}"))?;
+    let pair = ctx
+        .substrate_wallet(chain_id)
+        .await
+        .map_err(|e| format!("Misconfigured Network {chain_id}: {e}"))?;
+    let signer = PairSigner::new(pair);
+    let on_chain_nonce = client
+        .rpc()
+        .system_account_next_index(signer.account_id())
+        .await
+        .map_err(|e| format!("Failed to read the nonce: {e}"))?;
+    let nonce = ctx.substrate_accounts().sync_nonce(
+        chain_id,
+        on_chain_nonce,
+        ctx.clock().unix_timestamp(),
+    );
+    let batch_tx = api::tx().utility().batch(calls);
+    let signed = client
+        .tx()
+        .create_signed_with_nonce(&batch_tx, &signer, nonce, Default::default())
+        .map_err(|e| format!("Failed to sign transaction: {e}"))?;
+    let progress = signed
+        .submit_and_watch()
+        .await
+        .map_err(|e| format!("Error while sending Tx: {e}"))?;
+    ctx.substrate_accounts().record_submitted(
+        chain_id,
+        nonce,
+        ctx.clock().unix_timestamp(),
+    );
+    Ok(progress)
+}
@@ -1,7 +1,8 @@
 use ethereum_types::H256;
 use futures::TryStreamExt;
 use sp_core::sr25519::Pair;
-use webb::substrate::subxt::tx::PairSigner;
+use webb::substrate::subxt::rpc::types::DryRunResult;
+use webb::substrate::subxt::tx::{PairSigner, SubmittableExtrinsic};
 use webb::substrate::subxt::{
     tx::TxProgress, tx::TxStatus as TransactionStatus, OnlineClient,
     PolkadotConfig,
@@ -11,21 +12,62 @@ use webb_relayer_handler_utils::{
     CommandResponse, CommandStream, WithdrawStatus,
 };
 
+/// Batching of the Substrate withdrawals in `utility.batch` extrinsics.
+pub mod batch;
 pub mod fees;
 /// Substrate Variable Anchor Transactional Relayer.
 pub mod vanchor;
 
+/// The error of the VAnchor pallet, when the verifier rejects the proof.
+const INVALID_PROOF_ERROR: &str = "InvalidTransactionProof";
+
+/// Dry runs the signed extrinsic, to reject an invalid proof before
+/// estimating the fee of the transaction, or sending it.
+pub async fn preverify_proof(
+    signed: &SubmittableExtrinsic<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<(), CommandResponse> {
+    use CommandResponse::*;
+    let outcome = signed.dry_run(None).await.map_err(|e| {
+        Error(format!("Failed to dry run the transaction: {e}"))
+    })?;
+    match outcome {
+        DryRunResult::Success => Ok(()),
+        DryRunResult::DispatchError(e)
+            if format!("{e:?}").contains(INVALID_PROOF_ERROR) =>
+        {
+            let reason = e.to_string();
+            tracing::warn!("Rejected an invalid proof: {}", reason);
+            Err(Withdraw(WithdrawStatus::InvalidProof { reason }))
+        }
+        DryRunResult::DispatchError(e) => {
+            Err(Withdraw(WithdrawStatus::Errored {
+                reason: e.to_string(),
+                code: 4,
+            }))
+        }
+        DryRunResult::TransactionValidityError => {
+            Err(Withdraw(WithdrawStatus::Errored {
+                reason: "Invalid".to_string(),
+                code: 4,
+            }))
+        }
+    }
+}
+
 /// Handles a submitted Substrate transaction by processing its `TransactionProgress`.
 ///
 /// The `TransactionProgress` is a subscription to a transaction's progress. This method
 /// is intended to be used in a variety of places for all kinds of submitted Substrate
 /// transactions.
+///
+/// Returns the hash of the extrinsic, if it got finalized.
 pub async fn handle_substrate_tx(
     mut event_stream: TxProgress<PolkadotConfig, OnlineClient<PolkadotConfig>>,
     stream: CommandStream,
     chain_id: u64,
-) -> Result<(), CommandResponse> {
+) -> Result<Option<H256>, CommandResponse> {
     use CommandResponse::*;
+    let mut finalized_tx_hash = None;
     // Listen to the withdraw transaction, and send information back to the client
     loop {
         let maybe_event = event_stream
@@ -75,12 +117,10 @@ pub async fn handle_substrate_tx(
                 })?;
                 // TODO: check if the event is actually a withdraw event
 
+                let tx_hash = H256::from_slice(info.extrinsic_hash().as_ref());
+                finalized_tx_hash = Some(tx_hash);
                 let _ = stream
-                    .send(Withdraw(WithdrawStatus::Finalized {
-                        tx_hash: H256::from_slice(
-                            info.extrinsic_hash().as_ref(),
-                        ),
-                    }))
+                    .send(Withdraw(WithdrawStatus::Finalized { tx_hash }))
                     .await;
             }
             TransactionStatus::Dropped => {
@@ -111,7 +151,7 @@ pub async fn handle_substrate_tx(
             _ => continue,
         }
     }
-    Ok(())
+    Ok(finalized_tx_hash)
 }
 
 fn wei_to_gwei(wei: u128) -> f64 {
@@ -1,9 +1,13 @@
 use super::*;
+use crate::dedup::{claim_substrate_relay, record_relay_tx, release_relay};
+use crate::substrate::batch::submit_in_batch;
 use crate::substrate::fees::get_substrate_fee_info;
-use crate::substrate::handle_substrate_tx;
+use crate::substrate::{handle_substrate_tx, preverify_proof};
 use webb::substrate::tangle_runtime::api as RuntimeApi;
 use webb::substrate::subxt::utils::AccountId32;
 use webb::substrate::tangle_runtime::api::runtime_types::tangle_standalone_runtime::protocol_substrate_config::Element;
+use webb::substrate::tangle_runtime::api::runtime_types::tangle_standalone_runtime::RuntimeCall;
+use webb::substrate::tangle_runtime::api::runtime_types::pallet_vanchor;
 use webb::substrate::{
     subxt::{PolkadotConfig, tx::PairSigner},
     tangle_runtime::api::runtime_types::webb_primitives::types::vanchor,
@@ -13,11 +17,16 @@ use webb::substrate::scale::Compact;
 use webb_proposals::{
     ResourceId, SubstrateTargetSystem, TargetSystem, TypedChainId,
 };
+use webb_relayer_context::substrate_accounts::to_native_unit;
 use webb_relayer_context::RelayerContext;
 use webb_relayer_handler_utils::SubstrateVAchorCommand;
+use webb_relayer_store::FeeAccountingStore;
 
 /// Handler for Substrate Anchor commands
 ///
+/// With the `relay-deduplication` feature, the retries of an accepted
+/// command get its status instead, see [`claim_substrate_relay`].
+///
 /// # Arguments
 ///
 /// * `ctx` - RelayContext reference that holds the configuration
@@ -30,6 +39,43 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
 ) -> Result<(), CommandResponse> {
     use CommandResponse::*;
 
+    if cmd.delay.is_some() {
+        return Err(Error(
+            "Delayed withdrawals are only supported on EVM chains".to_string(),
+        ));
+    }
+    if cmd.fee_permit.is_some() {
+        return Err(Error(
+            "Fee permits are only supported on EVM chains".to_string(),
+        ));
+    }
+
+    let fingerprint = if ctx.config.features.relay_deduplication {
+        Some(claim_substrate_relay(&ctx, &cmd)?)
+    } else {
+        None
+    };
+    let result = relay_substrate_vanchor_tx(ctx.clone(), cmd, stream).await;
+    match (fingerprint, &result) {
+        (Some(fingerprint), Ok(Some(tx_hash))) => {
+            record_relay_tx(&ctx, fingerprint, *tx_hash)
+        }
+        (Some(fingerprint), Err(_)) => release_relay(&ctx, fingerprint),
+        _ => {}
+    }
+    result.map(|_| ())
+}
+
+/// Relays the Substrate VAnchor command right away.
+///
+/// Returns the hash of the transaction, once finalized.
+async fn relay_substrate_vanchor_tx(
+    ctx: RelayerContext,
+    cmd: SubstrateVAchorCommand,
+    stream: CommandStream,
+) -> Result<Option<H256>, CommandResponse> {
+    use CommandResponse::*;
+
     let proof_elements: vanchor::ProofData<Element> = vanchor::ProofData {
         proof: cmd.proof_data.proof,
         public_amount: Element(cmd.proof_data.public_amount),
@@ -73,7 +119,36 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
     })?;
 
     let signer = PairSigner::new(pair.clone());
+    let chain_id = cmd.chain_id as u32;
+    // the nonce of the node is checked against the one we expect, so a
+    // transaction sent with the relayer account outside of the relayer
+    // gets noticed.
+    let on_chain_nonce = client
+        .rpc()
+        .system_account_next_index(signer.account_id())
+        .await
+        .map_err(|e| Error(format!("Failed to read the nonce: {e}")))?;
+    let nonce = ctx.substrate_accounts().sync_nonce(
+        chain_id,
+        on_chain_nonce,
+        ctx.clock().unix_timestamp(),
+    );
 
+    let batch_extrinsics = ctx
+        .config
+        .substrate
+        .get(&chain_id.to_string())
+        .map(|node_config| node_config.batch_extrinsics)
+        .unwrap_or_default();
+    // the fee is estimated with the extrinsic sent alone, which is a bit
+    // more than its share of a batch.
+    let batched_call = batch_extrinsics.then(|| {
+        RuntimeCall::VAnchorBn254(pallet_vanchor::pallet::Call::transact {
+            id: cmd.id,
+            proof_data: proof_elements.clone(),
+            ext_data: ext_data_elements.clone(),
+        })
+    });
     let transact_tx = RuntimeApi::tx().v_anchor_bn254().transact(
         cmd.id,
         proof_elements,
@@ -84,9 +159,16 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
     //       https://github.com/paritytech/subxt/pull/910
     let signed = client
         .tx()
-        .create_signed(&transact_tx, &signer, Default::default())
-        .await
+        .create_signed_with_nonce(
+            &transact_tx,
+            &signer,
+            nonce,
+            Default::default(),
+        )
         .map_err(|e| Error(format!("Failed to sign transaction: {e}")))?;
+    if ctx.config.features.proof_preverification {
+        preverify_proof(&signed).await?;
+    }
     let mut params = signed.encoded().to_vec();
     (signed.encoded().len() as u32).encode_to(&mut params);
     let bytes = client
@@ -111,38 +193,39 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
     .await
     .map_err(|e| Error(format!("Get substrate fee info failed: {e}")))?;
 
-    // validate refund amount
-    if U256::from(cmd.ext_data.refund) > fee_info.max_refund {
-        // TODO: use error enum for these messages so they dont have to be duplicated between
-        //       evm/substrate
-        let msg = format!(
-            "User requested a refund which is higher than the maximum of {}",
-            fee_info.max_refund
-        );
-        return Err(Error(msg));
-    }
+    validate_fee(
+        U256::from(cmd.ext_data.fee.as_u128()),
+        U256::from(cmd.ext_data.refund.as_u128()),
+        fee_info.estimated_fee,
+        fee_info.max_refund,
+    )?;
 
-    // Check that transaction fee is enough to cover network fee and relayer fee
-    // TODO: refund needs to be converted from wrapped token to native token once there
-    //       is an exchange rate
-    if U256::from(cmd.ext_data.fee)
-        < fee_info.estimated_fee + cmd.ext_data.refund
-    {
-        let msg = format!(
-            "User sent a fee that is too low ({}) but expected {}",
-            cmd.ext_data.fee,
-            fee_info.estimated_fee + cmd.ext_data.refund
+    // check the relayer can pay for the transaction, rather than letting
+    // the node reject it with a payment error.
+    let existential_deposit = client
+        .constants()
+        .at(&RuntimeApi::constants().balances().existential_deposit())
+        .map_err(|e| {
+            Error(format!("Failed to read the existential deposit: {e}"))
+        })?;
+    let free_balance = balance(client.clone(), signer.clone())
+        .await
+        .map_err(|e| Error(format!("Failed to read substrate balance: {e}")))?;
+    let required = payment_info.3.saturating_add(existential_deposit);
+    if free_balance < required {
+        tracing::warn!(
+            balance = free_balance,
+            required,
+            "The relayer account is out of funds on chain {chain_id}",
         );
-        return Err(Error(msg));
+        return Err(Withdraw(WithdrawStatus::RelayerOutOfFunds {
+            balance: free_balance.into(),
+            required: required.into(),
+        }));
     }
 
-    let transact_tx_hash = signed.submit_and_watch().await;
-
-    let event_stream = transact_tx_hash
-        .map_err(|e| Error(format!("Error while sending Tx: {e}")))?;
-
-    handle_substrate_tx(event_stream, stream, cmd.chain_id).await?;
-
+    // nothing can fail once the transaction is sent, so that its
+    // fingerprint is kept.
     let target = client
         .metadata()
         .pallet("VAnchorHandlerBn254")
@@ -155,9 +238,38 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
         .map_err(|e| Error(format!("Vanchor handler pallet not found: {e}")))?;
 
     let target_system = TargetSystem::Substrate(target);
-    let typed_chain_id = TypedChainId::Substrate(cmd.chain_id as u32);
+    let typed_chain_id = TypedChainId::Substrate(chain_id);
     let resource_id = ResourceId::new(target_system, typed_chain_id);
 
+    let maybe_tx_hash = match batched_call {
+        Some(call) => submit_in_batch(&ctx, chain_id, call, stream).await?,
+        None => {
+            let transact_tx_hash = signed.submit_and_watch().await;
+
+            let event_stream = transact_tx_hash
+                .map_err(|e| Error(format!("Error while sending Tx: {e}")))?;
+            ctx.substrate_accounts().record_submitted(
+                chain_id,
+                nonce,
+                ctx.clock().unix_timestamp(),
+            );
+
+            handle_substrate_tx(event_stream, stream, cmd.chain_id).await?
+        }
+    };
+
+    if let Some(tx_hash) = maybe_tx_hash {
+        ctx.record_relayed_tx(typed_chain_id, tx_hash).await;
+    }
+    if let Err(e) = ctx.store().record_fee(
+        typed_chain_id,
+        &cmd.ext_data.token.to_string(),
+        U256::from(cmd.ext_data.fee.as_u128()),
+        chrono::Utc::now().timestamp() as u64,
+    ) {
+        tracing::warn!("Failed to record the collected fee: {e}");
+    }
+
     // update metric
     let metrics_clone = ctx.metrics.clone();
     let mut metrics = metrics_clone.lock().await;
@@ -171,15 +283,120 @@ pub async fn handle_substrate_vanchor_relay_tx<'a>(
         .total_fee_earned
         .inc_by(wei_to_gwei(cmd.ext_data.fee.as_u128()));
 
-    let balance = balance(client, signer)
-        .await
-        .map_err(|e| Error(format!("Failed to read substrate balance: {e}")))?;
-    metrics
-        .account_balance_entry(typed_chain_id)
-        .set(wei_to_gwei(balance));
+    // the withdrawal went through, so the balance is only logged if it
+    // cannot be read.
+    match balance(client, signer).await {
+        // the balance of a Substrate account is in the native unit of its
+        // chain, with the decimals read by the account monitor.
+        Ok(balance) => {
+            if let Some(account) = ctx.substrate_accounts().get(chain_id) {
+                metrics
+                    .account_balance_entry(typed_chain_id)
+                    .set(to_native_unit(balance, account.decimals));
+            }
+        }
+        Err(e) => tracing::warn!("Failed to read substrate balance: {e}"),
+    }
+    Ok(maybe_tx_hash)
+}
+
+/// Checks that the refund the client asks for is within the maximum, and
+/// that the fee it pays covers the estimated fee of the transaction and the
+/// refund.
+fn validate_fee(
+    fee: U256,
+    refund: U256,
+    estimated_fee: U256,
+    max_refund: U256,
+) -> Result<(), CommandResponse> {
+    // validate refund amount
+    if refund > max_refund {
+        // TODO: use error enum for these messages so they dont have to be duplicated between
+        //       evm/substrate
+        let msg = format!(
+            "User requested a refund which is higher than the maximum of {max_refund}"
+        );
+        return Err(CommandResponse::Error(msg));
+    }
+
+    // Check that transaction fee is enough to cover network fee and relayer fee
+    // TODO: refund needs to be converted from wrapped token to native token once there
+    //       is an exchange rate
+    let min_fee = estimated_fee.saturating_add(refund);
+    if fee < min_fee {
+        let msg = format!(
+            "User sent a fee that is too low ({fee}) but expected {min_fee}"
+        );
+        return Err(CommandResponse::Error(msg));
+    }
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_handler_utils::SubstrateCommandType;
+
+    #[test]
+    fn should_deserialize_a_transact_command() {
+        let element = vec![1u8; 32];
+        let cmd = serde_json::json!({
+            "substrate": {
+                "vAnchor": {
+                    "chainId": 1081,
+                    "id": 5,
+                    "proofData": {
+                        "proof": [1, 2, 3],
+                        "publicAmount": element,
+                        "roots": [element, element],
+                        "inputNullifiers": [element],
+                        "outputCommitments": [element, element],
+                        "extDataHash": element,
+                        "extensionRoots": [],
+                    },
+                    "extData": {
+                        "recipient": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                        "relayer": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                        "extAmount": "-2a",
+                        "fee": "0x64",
+                        "refund": "0x0",
+                        "token": 0,
+                        "encryptedOutput1": [4, 5],
+                        "encryptedOutput2": [6],
+                    },
+                }
+            }
+        });
+        let cmd: SubstrateCommandType =
+            serde_json::from_value(cmd["substrate"].clone()).unwrap();
+        let SubstrateCommandType::VAnchor(cmd) = cmd;
+        assert_eq!(cmd.chain_id, 1081);
+        assert_eq!(cmd.id, 5);
+        assert_eq!(cmd.proof_data.roots.len(), 2);
+        assert_eq!(cmd.proof_data.public_amount, [1u8; 32]);
+        assert_eq!(cmd.ext_data.ext_amount.0, -42);
+        assert_eq!(cmd.ext_data.fee.as_u128(), 100);
+        assert_eq!(cmd.ext_data.encrypted_output1, vec![4, 5]);
+        assert!(cmd.delay.is_none());
+    }
+
+    #[test]
+    fn should_reject_a_fee_under_the_estimate_and_the_refund() {
+        let estimated_fee = U256::from(1_000);
+        let max_refund = U256::from(500);
+        let ok = |fee: u64, refund: u64| {
+            validate_fee(fee.into(), refund.into(), estimated_fee, max_refund)
+                .is_ok()
+        };
+        assert!(ok(1_000, 0));
+        assert!(ok(1_500, 500));
+        assert!(!ok(999, 0));
+        assert!(!ok(1_499, 500));
+        // over the maximum refund, whatever the fee.
+        assert!(!ok(10_000, 501));
+    }
+}
+
 #[cfg(tests)]
 mod test {
     use webb::substrate::subxt::runtime_api::RuntimeApiClient;
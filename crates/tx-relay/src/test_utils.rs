@@ -0,0 +1,104 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The chains and withdrawals the tests of the EVM relays go through.
+
+use ethereum_types::{Address, H256};
+use webb_relayer_config::evm::{
+    Contract, EvmChainConfig, VAnchorContractConfig,
+};
+use webb_relayer_config::WebbRelayerConfig;
+use webb_relayer_context::RelayerContext;
+use webb_relayer_handler_utils::EvmVanchorCommand;
+use webb_relayer_store::SledStore;
+
+pub const PRIVATE_KEY: &str =
+    "0x000000000000000000000000000000000000000000000000000000000000000a";
+
+/// The VAnchor of the test chain.
+pub fn anchor() -> Address {
+    Address::repeat_byte(1)
+}
+
+/// A context with the chain `5001`, which has a single VAnchor, as changed
+/// by `f`.
+pub fn context_with_chain(
+    f: impl FnOnce(&mut EvmChainConfig),
+) -> RelayerContext {
+    context_with_store(SledStore::temporary().unwrap(), f)
+}
+
+/// Like [`context_with_chain`], keeping its data in the `store`.
+pub fn context_with_store(
+    store: SledStore,
+    f: impl FnOnce(&mut EvmChainConfig),
+) -> RelayerContext {
+    let mut chain: EvmChainConfig = serde_json::from_value(serde_json::json!({
+        "name": "hermes",
+        "http-endpoint": "http://localhost:5001",
+        "ws-endpoint": "ws://localhost:5001",
+        "chain-id": 5001,
+        "private-key": PRIVATE_KEY,
+        "contracts": [{
+            "contract": "VAnchor",
+            "address": anchor(),
+            "deployed-at": 1,
+            "events-watcher": { "polling-interval": 1000 },
+        }],
+    }))
+    .unwrap();
+    f(&mut chain);
+    let mut config = WebbRelayerConfig::default();
+    config.evm.insert(chain.chain_id.to_string(), chain);
+    RelayerContext::new(config, store).unwrap()
+}
+
+/// The VAnchor of the test chain, in its `config`.
+pub fn anchor_config(
+    config: &mut EvmChainConfig,
+) -> &mut VAnchorContractConfig {
+    match &mut config.contracts[0] {
+        Contract::VAnchor(c) => c,
+        _ => unreachable!("the test chain only has a VAnchor"),
+    }
+}
+
+/// A withdrawal through the [`anchor`] of the chain `5001`, paying another
+/// relayer.
+pub fn command() -> EvmVanchorCommand {
+    serde_json::from_value(serde_json::json!({
+        "chainId": 5001,
+        "id": anchor(),
+        "proofData": {
+            "proof": "0x0102",
+            "publicAmount": H256::zero(),
+            "roots": "0x",
+            "inputNullifiers": [H256::repeat_byte(3)],
+            "outputCommitments": [],
+            "extDataHash": H256::repeat_byte(4),
+            "extensionRoots": "0x",
+        },
+        "extData": {
+            "recipient": "0x0000000000000000000000000000000000000005",
+            "relayer": "0x0000000000000000000000000000000000000006",
+            "extAmount": "0",
+            "fee": "0x0",
+            "refund": "0x0",
+            "token": "0x0000000000000000000000000000000000000007",
+            "encryptedOutput1": "0x",
+            "encryptedOutput2": "0x",
+        },
+    }))
+    .unwrap()
+}
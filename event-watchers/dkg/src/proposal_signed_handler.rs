@@ -20,15 +20,26 @@ use webb::substrate::tangle_runtime::api::dkg_proposal_handler;
 use webb::substrate::tangle_runtime::api::runtime_types::webb_proposals::header::TypedChainId;
 use webb::substrate::subxt::{self, OnlineClient, PolkadotConfig};
 
-use webb_relayer_store::sled::{SledQueueKey, SledStore};
-use webb_relayer_store::{BridgeCommand, BridgeKey, QueueStore};
+use webb_relayer_store::sled::SledStore;
+use webb_relayer_store::{
+    compute_proposal_hash, BridgeCommand, BridgeCommandChannels, BridgeKey,
+    ProposalEventKind, ProposalEventStore,
+};
 use webb_relayer_utils::metric;
 
 use webb_event_watcher_traits::substrate::EventHandler;
 
 /// A ProposalSignedHandler handles the `ProposalSigned` event and signals signature bridge to execute them.
-#[derive(Copy, Clone, Debug, Default)]
-pub struct ProposalSignedHandler;
+#[derive(Clone, Debug)]
+pub struct ProposalSignedHandler {
+    commands: BridgeCommandChannels,
+}
+
+impl ProposalSignedHandler {
+    pub fn new(commands: BridgeCommandChannels) -> Self {
+        Self { commands }
+    }
+}
 
 #[async_trait::async_trait]
 impl EventHandler<PolkadotConfig> for ProposalSignedHandler {
@@ -152,13 +163,22 @@ impl EventHandler<PolkadotConfig> for ProposalSignedHandler {
             );
             // Proposal signed metric
             metrics.lock().await.proposals_signed.inc();
-            store.enqueue_item(
-                SledQueueKey::from_bridge_key(bridge_key),
-                BridgeCommand::ExecuteProposalWithSignature {
-                    data: event.data.clone(),
-                    signature: event.signature,
-                },
-            )?;
+            store.record_proposal(
+                ProposalEventKind::Passed,
+                &event.data,
+                compute_proposal_hash(&event.data),
+                None,
+            );
+            self.commands
+                .send(
+                    &store,
+                    bridge_key,
+                    BridgeCommand::ExecuteProposalWithSignature {
+                        data: event.data.clone(),
+                        signature: event.signature,
+                    },
+                )
+                .await?;
         }
         Ok(())
     }
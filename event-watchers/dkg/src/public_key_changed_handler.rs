@@ -17,8 +17,8 @@ use tokio::sync::Mutex;
 use webb::substrate::subxt::{self, OnlineClient, PolkadotConfig};
 use webb::substrate::tangle_runtime::api::dkg;
 
-use webb_relayer_store::sled::{SledQueueKey, SledStore};
-use webb_relayer_store::{BridgeCommand, BridgeKey, QueueStore};
+use webb_relayer_store::sled::SledStore;
+use webb_relayer_store::{BridgeCommand, BridgeCommandChannels, BridgeKey};
 use webb_relayer_utils::metric;
 
 use webb_event_watcher_traits::substrate::EventHandler;
@@ -28,11 +28,18 @@ use webb_event_watcher_traits::substrate::EventHandler;
 #[derive(Clone, Debug)]
 pub struct DKGPublicKeyChangedHandler {
     webb_config: webb_relayer_config::WebbRelayerConfig,
+    commands: BridgeCommandChannels,
 }
 
 impl DKGPublicKeyChangedHandler {
-    pub fn new(webb_config: webb_relayer_config::WebbRelayerConfig) -> Self {
-        Self { webb_config }
+    pub fn new(
+        webb_config: webb_relayer_config::WebbRelayerConfig,
+        commands: BridgeCommandChannels,
+    ) -> Self {
+        Self {
+            webb_config,
+            commands,
+        }
     }
 }
 
@@ -107,14 +114,17 @@ impl EventHandler<PolkadotConfig> for DKGPublicKeyChangedHandler {
                     nonce = %nonce,
                     signature = %hex::encode(&event.pub_key_sig),
                 );
-                store.enqueue_item(
-                    SledQueueKey::from_bridge_key(bridge_key),
-                    BridgeCommand::TransferOwnershipWithSignature {
-                        public_key: public_key_uncompressed.clone(),
-                        nonce,
-                        signature: event.pub_key_sig.clone(),
-                    },
-                )?;
+                self.commands
+                    .send(
+                        &store,
+                        bridge_key,
+                        BridgeCommand::TransferOwnershipWithSignature {
+                            public_key: public_key_uncompressed.clone(),
+                            nonce,
+                            signature: event.pub_key_sig.clone(),
+                        },
+                    )
+                    .await?;
             }
         }
         Ok(())
@@ -12,13 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::ops;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use webb::evm::contract::protocol_solidity::v_anchor_contract::{
+    EdgeAdditionFilter, EdgeUpdateFilter, NewCommitmentFilter,
+};
 use webb::evm::contract::protocol_solidity::{
     VAnchorContract, VAnchorContractEvents,
 };
+use webb::evm::ethers::abi::Abi;
 use webb::evm::ethers::contract::Contract;
+use webb::evm::ethers::contract::EthEvent;
 use webb::evm::ethers::prelude::Middleware;
 use webb::evm::ethers::types;
 
@@ -32,8 +39,9 @@ pub mod vanchor;
 mod tests;
 
 use webb_event_watcher_traits::evm::{EventWatcher, WatchableContract};
-use webb_event_watcher_traits::EthersTimeLagClient;
-use webb_relayer_store::SledStore;
+use webb_event_watcher_traits::{BlockRange, EthersTimeLagClient};
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_store::{LeafCacheStore, SledStore};
 
 // VAnchorContractWrapper contains VAnchorContract contract along with configurations for Anchor contract, and Relayer.
 #[derive(Clone, Debug)]
@@ -44,6 +52,7 @@ where
     pub config: webb_relayer_config::evm::VAnchorContractConfig,
     pub webb_config: webb_relayer_config::WebbRelayerConfig,
     pub contract: VAnchorContract<M>,
+    custom_abi: bool,
 }
 
 impl<M> VAnchorContractWrapper<M>
@@ -51,19 +60,54 @@ where
     M: Middleware,
 {
     /// Creates a new VAnchorContractOverDKGWrapper.
+    ///
+    /// If the contract address is in `abi_overrides`, the contract is
+    /// created with the ABI loaded from that file instead of the generated one.
     pub fn new(
         config: webb_relayer_config::evm::VAnchorContractConfig,
         webb_config: webb_relayer_config::WebbRelayerConfig,
+        abi_overrides: &HashMap<types::Address, PathBuf>,
         client: Arc<M>,
-    ) -> Self {
-        Self {
-            contract: VAnchorContract::new(config.common.address, client),
+    ) -> webb_relayer_utils::Result<Self> {
+        let address = config.common.address;
+        let (contract, custom_abi) =
+            match load_abi_override(abi_overrides, address)? {
+                Some(abi) => {
+                    let contract = Contract::new(address, abi, client);
+                    (VAnchorContract::from(contract), true)
+                }
+                None => (VAnchorContract::new(address, client), false),
+            };
+        Ok(Self {
+            contract,
             config,
             webb_config,
-        }
+            custom_abi,
+        })
+    }
+
+    /// Returns true if the contract was created with a custom ABI.
+    pub fn has_custom_abi(&self) -> bool {
+        self.custom_abi
     }
 }
 
+/// Loads the custom ABI of the contract at `address`, if there is one in
+/// `abi_overrides`.
+pub fn load_abi_override(
+    abi_overrides: &HashMap<types::Address, PathBuf>,
+    address: types::Address,
+) -> webb_relayer_utils::Result<Option<Abi>> {
+    let path = match abi_overrides.get(&address) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    tracing::debug!(%address, path = %path.display(), "Loading custom ABI");
+    let file = std::fs::File::open(path)?;
+    let abi = Abi::load(std::io::BufReader::new(file))?;
+    Ok(Some(abi))
+}
+
 impl<M> ops::Deref for VAnchorContractWrapper<M>
 where
     M: Middleware,
@@ -96,6 +140,22 @@ where
             self.config.events_watcher.print_progress_interval,
         )
     }
+
+    fn watchdog_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.events_watcher.watchdog_timeout)
+    }
+
+    fn safety_lag(&self) -> u64 {
+        self.config.events_watcher.safety_lag
+    }
+
+    fn deployment_tx_hash(&self) -> Option<types::H256> {
+        self.config.common.deployment_tx_hash
+    }
+
+    fn event_type_allowlist(&self) -> Option<&[String]> {
+        self.config.events_watcher.event_type_allowlist.as_deref()
+    }
 }
 
 /// An Anchor Contract Watcher that watches for the Anchor contract events and calls the event
@@ -105,8 +165,11 @@ pub struct AnchorContractWatcher;
 
 /// An VAnchor Contract Watcher that watches for the Anchor contract events and calls the event
 /// handlers.
-#[derive(Copy, Clone, Debug, Default)]
-pub struct VAnchorContractWatcher;
+#[derive(Clone, Debug, Default)]
+pub struct VAnchorContractWatcher {
+    /// The chain id of the watched contract, only fetched once.
+    chain_id: Arc<tokio::sync::OnceCell<u32>>,
+}
 
 #[async_trait::async_trait]
 impl EventWatcher for VAnchorContractWatcher {
@@ -117,4 +180,63 @@ impl EventWatcher for VAnchorContractWatcher {
     type Events = VAnchorContractEvents;
 
     type Store = SledStore;
+
+    /// The events of the leaves, and of the edges of the linked anchors.
+    fn event_topics(&self) -> Vec<types::H256> {
+        vec![
+            NewCommitmentFilter::signature(),
+            EdgeAdditionFilter::signature(),
+            EdgeUpdateFilter::signature(),
+        ]
+    }
+
+    /// Fetches the missing leaves again if some leaves are still pending
+    /// after the step.
+    ///
+    /// All the deposits of the range were handled at this point, so the
+    /// leaves the pending ones are waiting for are in an earlier block, which
+    /// the checkpoint is walked back to.
+    async fn post_step(
+        &self,
+        store: &Arc<Self::Store>,
+        contract: &Self::Contract,
+        range: BlockRange,
+        number_of_events: usize,
+    ) -> webb_relayer_utils::Result<()> {
+        // the leaves can only be left pending by the events of a step.
+        if number_of_events == 0 {
+            return Ok(());
+        }
+        let chain_id = *self
+            .chain_id
+            .get_or_try_init(|| async {
+                let chain_id = contract.client().get_chainid().await?;
+                Ok::<_, webb_relayer_utils::Error>(chain_id.as_u32())
+            })
+            .await?;
+        let history_store_key = ResourceId::new(
+            TargetSystem::new_contract_address(
+                contract.address().to_fixed_bytes(),
+            ),
+            TypedChainId::Evm(chain_id),
+        );
+        let pending = store.get_pending_leaves(history_store_key)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        tracing::warn!(
+            %chain_id,
+            pending = pending.len(),
+            to_block = range.to,
+            "Leaves are still pending after the step, fetching the missing ones again",
+        );
+        vanchor::recover_leaf_cache_checkpoint(
+            store.as_ref(),
+            history_store_key,
+            contract,
+            contract.deployed_at().as_u64(),
+        )
+        .await?;
+        Ok(())
+    }
 }
@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::ops;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use tokio::sync::Mutex;
+use tracing::Instrument;
+use webb::evm::contract::protocol_solidity::signature_bridge_contract::GovernanceOwnershipTransferredFilter;
 use webb::evm::contract::protocol_solidity::{
     SignatureBridgeContract, SignatureBridgeContractEvents,
 };
@@ -30,15 +34,24 @@ use webb_event_watcher_traits::evm::{
     BridgeWatcher, EventHandler, EventWatcher, WatchableContract,
 };
 use webb_event_watcher_traits::EthersTimeLagClient;
+use webb_relayer_context::proposal_permits::ProposalPermits;
 use webb_relayer_store::sled::{SledQueueKey, SledStore};
-use webb_relayer_store::{BridgeCommand, QueueStore};
+use webb_relayer_store::{
+    compute_proposal_hash, BridgeCommand, BridgeCommandChannels, BridgeKey,
+    DepositPropagationStore, ProposalEventKind, ProposalEventStore, QueueStore,
+};
 use webb_relayer_utils::metric;
 
+/// How long a proposal only signed by the governor as of the latest block
+/// is held back, before its command is queued again to check it once more.
+const UNCONFIRMED_SIGNATURE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
 /// A Wrapper around the `SignatureBridgeContract` contract.
 #[derive(Debug)]
 pub struct SignatureBridgeContractWrapper<M: Middleware> {
     config: webb_relayer_config::evm::SignatureBridgeContractConfig,
     contract: Arc<SignatureBridgeContract<M>>,
+    custom_abi: bool,
 }
 
 impl<M: Middleware> Clone for SignatureBridgeContractWrapper<M> {
@@ -46,22 +59,40 @@ impl<M: Middleware> Clone for SignatureBridgeContractWrapper<M> {
         Self {
             config: self.config.clone(),
             contract: Arc::clone(&self.contract),
+            custom_abi: self.custom_abi,
         }
     }
 }
 
 impl<M: Middleware> SignatureBridgeContractWrapper<M> {
+    /// Creates a new SignatureBridgeContractWrapper.
+    ///
+    /// If the contract address is in `abi_overrides`, the contract is
+    /// created with the ABI loaded from that file instead of the generated one.
     pub fn new(
         config: webb_relayer_config::evm::SignatureBridgeContractConfig,
+        abi_overrides: &HashMap<types::Address, PathBuf>,
         client: Arc<M>,
-    ) -> Self {
-        Self {
-            contract: Arc::new(SignatureBridgeContract::new(
-                config.common.address,
-                client,
-            )),
+    ) -> webb_relayer_utils::Result<Self> {
+        let address = config.common.address;
+        let (contract, custom_abi) =
+            match crate::load_abi_override(abi_overrides, address)? {
+                Some(abi) => {
+                    let contract = Contract::new(address, abi, client);
+                    (SignatureBridgeContract::from(contract), true)
+                }
+                None => (SignatureBridgeContract::new(address, client), false),
+            };
+        Ok(Self {
+            contract: Arc::new(contract),
             config,
-        }
+            custom_abi,
+        })
+    }
+
+    /// Returns true if the contract was created with a custom ABI.
+    pub fn has_custom_abi(&self) -> bool {
+        self.custom_abi
     }
 }
 
@@ -91,11 +122,47 @@ impl<M: Middleware> WatchableContract for SignatureBridgeContractWrapper<M> {
             self.config.events_watcher.print_progress_interval,
         )
     }
+
+    fn watchdog_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.events_watcher.watchdog_timeout)
+    }
+
+    fn safety_lag(&self) -> u64 {
+        self.config.events_watcher.safety_lag
+    }
+
+    fn deployment_tx_hash(&self) -> Option<types::H256> {
+        self.config.common.deployment_tx_hash
+    }
+
+    fn event_type_allowlist(&self) -> Option<&[String]> {
+        self.config.events_watcher.event_type_allowlist.as_deref()
+    }
 }
 
 /// A SignatureBridge contract events & commands watcher.
-#[derive(Copy, Clone, Debug, Default)]
-pub struct SignatureBridgeContractWatcher;
+#[derive(Clone, Debug, Default)]
+pub struct SignatureBridgeContractWatcher {
+    /// Limits the proposals in flight on each chain, if any.
+    proposal_permits: Option<ProposalPermits>,
+    /// The channels the commands retried later are sent back through.
+    commands: Option<BridgeCommandChannels>,
+}
+
+impl SignatureBridgeContractWatcher {
+    /// Creates a watcher holding a permit for each proposal it enqueues,
+    /// until the proposal gets executed, and sending the commands it
+    /// retries later back through the `commands` channels.
+    pub fn new(
+        proposal_permits: ProposalPermits,
+        commands: BridgeCommandChannels,
+    ) -> Self {
+        Self {
+            proposal_permits: Some(proposal_permits),
+            commands: Some(commands),
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct SignatureBridgeGovernanceOwnershipTransferredHandler;
@@ -109,6 +176,12 @@ impl EventWatcher for SignatureBridgeContractWatcher {
     type Events = SignatureBridgeContractEvents;
 
     type Store = SledStore;
+
+    /// Only the changes of governor are handled, the proposals are executed
+    /// with commands.
+    fn event_topics(&self) -> Vec<types::H256> {
+        vec![GovernanceOwnershipTransferredFilter::signature()]
+    }
 }
 
 #[async_trait::async_trait]
@@ -183,6 +256,7 @@ impl BridgeWatcher for SignatureBridgeContractWatcher {
                 self.execute_proposal_with_signature(
                     store,
                     &wrapper.contract,
+                    wrapper.config.status_confirmations,
                     (data, signature),
                 )
                 .await?;
@@ -208,11 +282,44 @@ impl SignatureBridgeContractWatcher
 where
     Self: BridgeWatcher,
 {
+    /// Sends the `cmd` back to the bridge watcher once the
+    /// [`UNCONFIRMED_SIGNATURE_RETRY_DELAY`] elapsed, without holding back
+    /// the other commands of the bridge in the meantime.
+    fn retry_later(
+        &self,
+        store: Arc<<Self as EventWatcher>::Store>,
+        bridge_key: BridgeKey,
+        cmd: BridgeCommand,
+    ) {
+        let commands = self.commands.clone();
+        let task = async move {
+            tokio::time::sleep(UNCONFIRMED_SIGNATURE_RETRY_DELAY).await;
+            let result = match commands {
+                Some(commands) => {
+                    commands.send(store.as_ref(), bridge_key, cmd).await
+                }
+                None => QueueStore::<BridgeCommand>::enqueue_item(
+                    store.as_ref(),
+                    SledQueueKey::from_bridge_key(bridge_key),
+                    cmd,
+                ),
+            };
+            if let Err(e) = result {
+                tracing::error!(
+                    %bridge_key,
+                    "Failed to retry the proposal execution: {e}",
+                );
+            }
+        };
+        tokio::spawn(task.in_current_span());
+    }
+
     #[tracing::instrument(skip_all)]
     async fn execute_proposal_with_signature(
         &self,
         store: Arc<<Self as EventWatcher>::Store>,
         contract: &SignatureBridgeContract<EthersTimeLagClient>,
+        status_confirmations: Option<u64>,
         (proposal_data, signature): (Vec<u8>, Vec<u8>),
     ) -> webb_relayer_utils::Result<()> {
         let proposal_data_hex = hex::encode(&proposal_data);
@@ -227,7 +334,7 @@ where
 
         // 2. Verify if proposal already exists in transaction queue
         let chain_id = contract.get_chain_id().call().await?;
-        let proposal_data_hash = utils::keccak256(&proposal_data);
+        let proposal_data_hash = compute_proposal_hash(&proposal_data);
         let tx_key = SledQueueKey::from_evm_with_custom_key(
             chain_id.as_u32(),
             make_execute_proposal_key(proposal_data_hash),
@@ -245,29 +352,61 @@ where
         }
 
         // 3. Verify proposal signature. Proposal should be signed by active maintainer/dkg-key
-        let (proposal_data_clone, signature_clone) =
-            (proposal_data.clone(), signature.clone());
-        let is_signature_valid = contract
-            .is_signature_from_governor(
-                proposal_data_clone.into(),
-                signature_clone.into(),
-            )
-            .call()
-            .await?;
-
-        let governor = contract.governor().call().await?;
+        // read the governor at a confirmed block, so a change of governor
+        // that gets reverted does not get proposals executed.
+        let client = contract.client();
+        let head = client.inner().get_block_number().await?;
+        let confirmed_block = match status_confirmations {
+            Some(confirmations) => head.saturating_sub(confirmations.into()),
+            // the time lag client is `block-confirmations` behind the head.
+            None => client.get_block_number().await?,
+        };
+        tracing::trace!(%head, %confirmed_block, "Checking the proposal status");
+        let governor =
+            contract.governor().block(confirmed_block).call().await?;
         tracing::debug!(
             governor = ?hex::encode(governor),
             "GOVERNOR",
         );
+        // the time lag client would not read past the confirmed block.
+        let latest = SignatureBridgeContract::new(
+            contract.address(),
+            client.inner().clone(),
+        );
+        let status = check_proposal_signature(
+            &latest,
+            confirmed_block,
+            head,
+            (&proposal_data, &signature),
+        )
+        .await?;
         let signature_hex = hex::encode(&signature);
-        if !is_signature_valid {
-            tracing::warn!(
-                proposal_data = ?proposal_data_hex,
-                signature = ?signature_hex,
-                "Skipping execution of this proposal : Invalid Signature ",
-            );
-            return Ok(());
+        match status {
+            ProposalSignature::Confirmed => {}
+            ProposalSignature::Unconfirmed => {
+                tracing::debug!(
+                    proposal_data = ?proposal_data_hex,
+                    signature = ?signature_hex,
+                    "The proposal is only signed by the governor as of the latest block, retrying once it is confirmed",
+                );
+                let bridge_key = BridgeKey::new(
+                    webb_proposals::TypedChainId::Evm(chain_id.as_u32()),
+                );
+                let cmd = BridgeCommand::ExecuteProposalWithSignature {
+                    data: proposal_data,
+                    signature,
+                };
+                self.retry_later(store, bridge_key, cmd);
+                return Ok(());
+            }
+            ProposalSignature::Invalid => {
+                tracing::warn!(
+                    proposal_data = ?proposal_data_hex,
+                    signature = ?signature_hex,
+                    "Skipping execution of this proposal : Invalid Signature ",
+                );
+                return Ok(());
+            }
         }
 
         // 3. Enqueue proposal for execution.
@@ -281,16 +420,63 @@ where
             signature = ?signature_hex,
             proposal_data_hash = ?hex::encode(proposal_data_hash),
         );
+        // wait for room in the proposals in flight on the chain, so they do
+        // not fight over the nonces of the relayer account.
+        let permit = match &self.proposal_permits {
+            Some(permits) => Some(permits.acquire(chain_id.as_u32()).await),
+            None => None,
+        };
+        let mut proposal_events = store.subscribe_proposal_events();
         // Enqueue transaction call data in evm transaction queue
         let call = contract.execute_proposal_with_signature(
-            proposal_data.into(),
+            proposal_data.clone().into(),
             signature.into(),
         );
+        // link the call back to the proposal, so once the tx queue executes it
+        // the originating deposit (if any) gets marked as propagated.
+        let call_data_hash = call.tx.data().map(utils::keccak256);
+        if let Some(call_data_hash) = call_data_hash {
+            store.link_execution_call(call_data_hash, proposal_data_hash)?;
+        }
         QueueStore::<TypedTransaction>::enqueue_item(&store, tx_key, call.tx)?;
+        store.record_proposal(
+            ProposalEventKind::ExecutionQueued,
+            &proposal_data,
+            proposal_data_hash,
+            None,
+        );
+        if let Some(call_data_hash) = call_data_hash {
+            store.link_proposal(
+                call_data_hash,
+                ProposalEventKind::Executed,
+                &proposal_data,
+                proposal_data_hash,
+            );
+        }
         tracing::debug!(
             proposal_data_hash = ?hex::encode(proposal_data_hash),
             "Enqueued execute-proposal call for execution through evm tx queue",
         );
+        if let (Some(permits), Some(permit)) = (&self.proposal_permits, permit)
+        {
+            let data_hash = types::H256(proposal_data_hash);
+            permits.release_once(permit, async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match proposal_events.recv().await {
+                        Ok(record)
+                            if record.event.data_hash == data_hash
+                                && record.event.kind
+                                    == ProposalEventKind::Executed =>
+                        {
+                            break
+                        }
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
         Ok(())
     }
 
@@ -417,6 +603,45 @@ fn make_transfer_ownership_key(new_owner_address: [u8; 20]) -> [u8; 64] {
     result
 }
 
+/// Whether a proposal is signed by the governor of the bridge, see
+/// [`check_proposal_signature`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProposalSignature {
+    /// Signed by the governor as of the confirmed block.
+    Confirmed,
+    /// Only signed by the governor as of the latest block, such as right
+    /// after a change of governor, which could still get reverted.
+    Unconfirmed,
+    /// Not signed by the governor, even as of the latest block.
+    Invalid,
+}
+
+/// Checks that the proposal is signed by the governor of the `contract` as
+/// of the `confirmed_block`, or else as of the `latest_block`.
+async fn check_proposal_signature<M: Middleware>(
+    contract: &SignatureBridgeContract<M>,
+    confirmed_block: U64,
+    latest_block: U64,
+    (proposal_data, signature): (&[u8], &[u8]),
+) -> Result<ProposalSignature, ContractError<M>> {
+    let is_signature_from_governor = |block: U64| {
+        contract
+            .is_signature_from_governor(
+                proposal_data.to_vec().into(),
+                signature.to_vec().into(),
+            )
+            .block(block)
+    };
+    if is_signature_from_governor(confirmed_block).call().await? {
+        return Ok(ProposalSignature::Confirmed);
+    }
+    if is_signature_from_governor(latest_block).call().await? {
+        Ok(ProposalSignature::Unconfirmed)
+    } else {
+        Ok(ProposalSignature::Invalid)
+    }
+}
+
 /// Get the Ethereum address from the uncompressed EcDSA public key.
 fn eth_address_from_uncompressed_public_key(pub_key: &[u8]) -> Address {
     // hash the public key.
@@ -429,7 +654,11 @@ fn eth_address_from_uncompressed_public_key(pub_key: &[u8]) -> Address {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::signature_bridge_watcher::eth_address_from_uncompressed_public_key;
+    use webb_relayer_config::evm::{
+        CommonContractConfig, SignatureBridgeContractConfig,
+    };
 
     #[test]
     fn should_get_the_correct_eth_address_from_public_key() {
@@ -449,4 +678,84 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[tokio::test]
+    async fn unconfirmed_proposal_signatures_should_be_retried() {
+        let (provider, mock) = Provider::mocked();
+        let contract =
+            SignatureBridgeContract::new(Address::random(), Arc::new(provider));
+        let word = |signed: bool| -> Bytes {
+            abi::encode(&[abi::Token::Bool(signed)]).into()
+        };
+        let check = || {
+            check_proposal_signature(
+                &contract,
+                U64::from(90),
+                U64::from(100),
+                (&[1; 40][..], &[2; 65][..]),
+            )
+        };
+
+        // the responses are served last in, first out.
+        mock.push(word(true)).unwrap();
+        assert_eq!(check().await.unwrap(), ProposalSignature::Confirmed);
+        mock.push(word(true)).unwrap();
+        mock.push(word(false)).unwrap();
+        assert_eq!(check().await.unwrap(), ProposalSignature::Unconfirmed);
+        mock.push(word(false)).unwrap();
+        mock.push(word(false)).unwrap();
+        assert_eq!(check().await.unwrap(), ProposalSignature::Invalid);
+    }
+
+    #[test]
+    fn should_use_the_custom_abi_of_the_contract() {
+        let (provider, _mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let config = SignatureBridgeContractConfig {
+            common: CommonContractConfig {
+                address: Address::random(),
+                deployed_at: 1,
+                deployment_tx_hash: None,
+            },
+            events_watcher: Default::default(),
+            gas_limit_overrides: Default::default(),
+            gas_limit_padding: 0,
+            status_confirmations: None,
+        };
+        // the generated ABI, with one more function of a forked contract.
+        let generated =
+            SignatureBridgeContract::new(config.common.address, client.clone());
+        let mut abi = serde_json::to_value(generated.abi()).unwrap();
+        abi.as_array_mut().unwrap().push(serde_json::json!({
+            "type": "function",
+            "name": "pauseBridge",
+            "inputs": [],
+            "outputs": [],
+            "stateMutability": "nonpayable",
+        }));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("SignatureBridge.json");
+        std::fs::write(&path, abi.to_string()).unwrap();
+
+        let wrapper = SignatureBridgeContractWrapper::new(
+            config.clone(),
+            &Default::default(),
+            client.clone(),
+        )
+        .unwrap();
+        assert!(!wrapper.has_custom_abi());
+        assert!(wrapper.abi().function("pauseBridge").is_err());
+
+        let overrides = [(config.common.address, path)].into_iter().collect();
+        let wrapper =
+            SignatureBridgeContractWrapper::new(config, &overrides, client)
+                .unwrap();
+        assert!(wrapper.has_custom_abi());
+        assert!(wrapper.abi().function("pauseBridge").is_ok());
+        // the original functions are still there.
+        assert!(wrapper
+            .abi()
+            .function("executeProposalWithSignature")
+            .is_ok());
+    }
 }
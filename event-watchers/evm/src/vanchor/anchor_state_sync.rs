@@ -0,0 +1,321 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use webb::evm::ethers::types;
+use webb_proposal_signing_backends::queue::policy::ProposalPolicy;
+use webb_proposal_signing_backends::queue::root::AnchorRootSource;
+use webb_proposal_signing_backends::queue::{
+    ProposalsQueue, QueuedAnchorUpdateProposal,
+};
+use webb_proposals::{ProposalTrait, ResourceId};
+use webb_relayer_store::{AnchorEdge, LeafCacheStore, SledStore};
+use webb_relayer_utils::clock::SharedClock;
+use webb_relayer_utils::metric;
+
+use super::vanchor_deposit_handler::{
+    build_proposal, dispatch_anchor_update, AnchorUpdate,
+};
+use super::NeighborEdgesSource;
+
+/// Returns true if the `edge` a linked anchor has of the source anchor is
+/// behind the latest leaf of the source anchor, at `leaf_index` with `root`.
+pub fn is_edge_stale(
+    edge: Option<&AnchorEdge>,
+    leaf_index: u32,
+    root: types::H256,
+) -> bool {
+    match edge {
+        None => true,
+        Some(edge) => {
+            edge.latest_leaf_index < leaf_index
+                || (edge.latest_leaf_index == leaf_index && edge.root != root)
+        }
+    }
+}
+
+/// Checks the edges the linked anchors have of a source anchor against the
+/// leaves cached for the source anchor, and proposes an anchor update to the
+/// linked anchors whose edge is stale, such as after a missed event.
+///
+/// The proposals go through the same queue as those of the deposits.
+#[derive(typed_builder::TypedBuilder)]
+pub struct CrossChainAnchorStateSync<Q, P, R, E> {
+    #[builder(setter(into))]
+    store: Arc<SledStore>,
+    src_resource_id: ResourceId,
+    linked_anchors: Vec<ResourceId>,
+    proposals_queue: Q,
+    policy: P,
+    root_source: R,
+    neighbor_edges: E,
+    interval: Duration,
+    clock: SharedClock,
+}
+
+impl<Q, P, R, E> CrossChainAnchorStateSync<Q, P, R, E>
+where
+    Q: ProposalsQueue<Proposal = QueuedAnchorUpdateProposal> + Send + Sync,
+    P: ProposalPolicy + Send + Sync + Clone,
+    R: AnchorRootSource,
+    E: NeighborEdgesSource + Send + Sync,
+{
+    /// Checks the edges every interval, until the task is dropped.
+    #[tracing::instrument(
+        skip_all,
+        fields(src_resource_id = ?self.src_resource_id),
+    )]
+    pub async fn run(
+        self,
+        metrics: Arc<Mutex<metric::Metrics>>,
+    ) -> webb_relayer_utils::Result<()> {
+        loop {
+            self.clock.sleep(self.interval).await;
+            if let Err(e) = self.sync_once(&metrics).await {
+                tracing::warn!("Failed to check the edges of the anchor: {e}");
+            }
+        }
+    }
+
+    /// Checks the edges of the linked anchors once, and returns how many
+    /// got proposed an update.
+    pub async fn sync_once(
+        &self,
+        metrics: &Mutex<metric::Metrics>,
+    ) -> webb_relayer_utils::Result<usize> {
+        let current = self.root_source.current_root().await?;
+        let cached = self.store.next_leaf_index(self.src_resource_id)?;
+        // the leaves the relayer has not cached yet are proposed by the
+        // deposit handler, once the events watcher gets to them.
+        if cached != current.next_index {
+            tracing::debug!(
+                cached,
+                next_index = current.next_index,
+                "Leaf cache not in sync with the anchor, skipping the edges",
+            );
+            return Ok(0);
+        }
+        // like the deposits, only the odd leaves get the anchors updated.
+        let leaf_index = match current.next_index.checked_sub(1) {
+            Some(leaf_index) if leaf_index % 2 != 0 => leaf_index,
+            _ => return Ok(0),
+        };
+        let root = types::H256(current.merkle_root);
+        let src_chain_id = self.src_resource_id.typed_chain_id().chain_id();
+        let mut proposed = 0;
+        for &target_resource_id in &self.linked_anchors {
+            if target_resource_id == self.src_resource_id {
+                continue;
+            }
+            let edges = match self
+                .neighbor_edges
+                .latest_neighbor_edges(target_resource_id)
+                .await
+            {
+                Ok(edges) => edges,
+                Err(e) => {
+                    tracing::warn!(
+                        ?target_resource_id,
+                        "Failed to read the edges of the linked anchor: {e}",
+                    );
+                    continue;
+                }
+            };
+            let edge = edges.iter().find(|e| e.src_chain_id == src_chain_id);
+            if !is_edge_stale(edge, leaf_index, root) {
+                continue;
+            }
+            // an update still in the queue lands the edge already.
+            let queued = self
+                .proposals_queue
+                .find(|p| p.header().resource_id() == target_resource_id)?;
+            if queued.is_some() {
+                continue;
+            }
+            tracing::warn!(
+                ?target_resource_id,
+                edge_leaf_index = ?edge.map(|e| e.latest_leaf_index),
+                edge_root = ?edge.map(|e| e.root),
+                leaf_index,
+                %root,
+                "Linked anchor has a stale edge, proposing an update",
+            );
+            metrics.lock().await.cross_chain_stale_edges_detected.inc();
+            let update = AnchorUpdate {
+                root: current.merkle_root,
+                leaf_index,
+                src_resource_id: self.src_resource_id,
+                target_resource_id,
+            };
+            dispatch_anchor_update(
+                &self.store,
+                &self.proposals_queue,
+                self.policy.clone(),
+                &update,
+                build_proposal(&update),
+            )?;
+            proposed += 1;
+        }
+        Ok(proposed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use webb_proposal_signing_backends::queue::mem::InMemoryProposalsQueue;
+    use webb_proposal_signing_backends::queue::policy::AlwaysHigherNoncePolicy;
+    use webb_proposal_signing_backends::queue::root::CurrentRoot;
+    use webb_proposals::{TargetSystem, TypedChainId};
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    struct MockedRoot(CurrentRoot);
+
+    #[async_trait::async_trait]
+    impl AnchorRootSource for MockedRoot {
+        async fn current_root(
+            &self,
+        ) -> webb_relayer_utils::Result<CurrentRoot> {
+            Ok(self.0)
+        }
+    }
+
+    /// The edges of each linked anchor, the others could not be read.
+    struct MockedEdges(HashMap<ResourceId, Vec<AnchorEdge>>);
+
+    #[async_trait::async_trait]
+    impl NeighborEdgesSource for MockedEdges {
+        async fn latest_neighbor_edges(
+            &self,
+            anchor: ResourceId,
+        ) -> webb_relayer_utils::Result<Vec<AnchorEdge>> {
+            self.0
+                .get(&anchor)
+                .cloned()
+                .ok_or(webb_relayer_utils::Error::Generic("unreachable"))
+        }
+    }
+
+    fn anchor(chain_id: u32, address: u8) -> ResourceId {
+        ResourceId::new(
+            TargetSystem::new_contract_address([address; 20]),
+            TypedChainId::Evm(chain_id),
+        )
+    }
+
+    fn edge(leaf_index: u32, root: u8) -> AnchorEdge {
+        AnchorEdge {
+            src_chain_id: TypedChainId::Evm(5).chain_id(),
+            root: types::H256::repeat_byte(root),
+            latest_leaf_index: leaf_index,
+            block_number: 1,
+        }
+    }
+
+    #[test]
+    fn edges_behind_the_latest_leaf_should_be_stale() {
+        let root = types::H256::repeat_byte(7);
+        assert!(is_edge_stale(None, 3, root));
+        assert!(is_edge_stale(Some(&edge(1, 7)), 3, root));
+        assert!(is_edge_stale(Some(&edge(3, 6)), 3, root));
+        assert!(!is_edge_stale(Some(&edge(3, 7)), 3, root));
+        // updated by another relayer in the meantime.
+        assert!(!is_edge_stale(Some(&edge(5, 8)), 3, root));
+    }
+
+    #[tokio::test]
+    async fn stale_edges_should_get_an_anchor_update_proposed() {
+        let src = anchor(5, 1);
+        let (up_to_date, stale, missing, unreachable) =
+            (anchor(4, 2), anchor(3, 3), anchor(2, 4), anchor(1, 5));
+        let store = SledStore::temporary().unwrap();
+        let leaves: Vec<_> = (0..4).map(|i| (i, vec![i as u8; 32])).collect();
+        store
+            .insert_leaves_and_last_deposit_block_number(src, &leaves, 10)
+            .unwrap();
+        let sync = CrossChainAnchorStateSync::builder()
+            .store(store)
+            .src_resource_id(src)
+            .linked_anchors(vec![src, up_to_date, stale, missing, unreachable])
+            .proposals_queue(InMemoryProposalsQueue::new())
+            .policy(AlwaysHigherNoncePolicy)
+            .root_source(MockedRoot(CurrentRoot {
+                merkle_root: [7u8; 32],
+                next_index: 4,
+            }))
+            .neighbor_edges(MockedEdges(HashMap::from([
+                (up_to_date, vec![edge(3, 7)]),
+                (stale, vec![edge(1, 6)]),
+                // an edge of another anchor only.
+                (
+                    missing,
+                    vec![AnchorEdge {
+                        src_chain_id: TypedChainId::Evm(9).chain_id(),
+                        ..edge(3, 7)
+                    }],
+                ),
+            ])))
+            .interval(Duration::from_secs(60))
+            .clock(FrozenClock::default().shared())
+            .build();
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+
+        assert_eq!(sync.sync_once(&metrics).await.unwrap(), 2);
+        let queued_for = |anchor: ResourceId| {
+            sync.proposals_queue
+                .find(|p| p.header().resource_id() == anchor)
+                .unwrap()
+        };
+        assert!(queued_for(up_to_date).is_none());
+        assert!(queued_for(stale).is_some());
+        assert!(queued_for(missing).is_some());
+        assert_eq!(sync.proposals_queue.len().unwrap(), 2);
+        // not proposed again while the updates are in the queue.
+        assert_eq!(sync.sync_once(&metrics).await.unwrap(), 0);
+        assert_eq!(sync.proposals_queue.len().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn edges_should_not_be_checked_until_the_leaf_cache_caught_up() {
+        let src = anchor(5, 1);
+        let stale = anchor(3, 3);
+        let store = SledStore::temporary().unwrap();
+        let leaves: Vec<_> = (0..2).map(|i| (i, vec![i as u8; 32])).collect();
+        store
+            .insert_leaves_and_last_deposit_block_number(src, &leaves, 10)
+            .unwrap();
+        let sync = CrossChainAnchorStateSync::builder()
+            .store(store)
+            .src_resource_id(src)
+            .linked_anchors(vec![stale])
+            .proposals_queue(InMemoryProposalsQueue::new())
+            .policy(AlwaysHigherNoncePolicy)
+            .root_source(MockedRoot(CurrentRoot {
+                merkle_root: [7u8; 32],
+                next_index: 4,
+            }))
+            .neighbor_edges(MockedEdges(HashMap::from([(stale, vec![])])))
+            .interval(Duration::from_secs(60))
+            .clock(FrozenClock::default().shared())
+            .build();
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+
+        assert_eq!(sync.sync_once(&metrics).await.unwrap(), 0);
+        assert!(sync.proposals_queue.is_empty().unwrap());
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::VAnchorContractWrapper;
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::time::Duration;
+use webb::evm::contract::protocol_solidity::v_anchor_contract::NewCommitmentFilter;
+use webb::evm::ethers::abi::RawLog;
+use webb::evm::ethers::contract::EthEvent;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types;
+use webb_event_watcher_traits::{
+    get_logs_in_range, BlockRange, EthersTimeLagClient, WatchableContract,
+};
+use webb_relayer_store::{HistoryStoreKey, LeafCacheStore};
+use webb_relayer_utils::retry;
+
+/// How many times a logs query is retried while the provider lags behind.
+const MAX_LOGS_RETRY_COUNT: usize = 10;
+
+/// The maximum number of `eth_getLogs` probes we are willing to make while
+/// bisecting for the missing leaves, before falling back to a full rescan.
+pub const MAX_BISECT_STEPS: usize = 32;
+
+/// A source of the deposit (`NewCommitment`) logs of a VAnchor contract.
+#[async_trait::async_trait]
+pub trait DepositLogSource {
+    /// Returns the contract's current `nextIndex`, i.e the number of leaves
+    /// inserted in the on-chain merkle tree.
+    async fn next_index(&self) -> webb_relayer_utils::Result<u32>;
+    /// Returns the leaf indices of the deposits emitted in the given
+    /// (inclusive) block range.
+    async fn leaf_indices(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> webb_relayer_utils::Result<Vec<u32>>;
+}
+
+#[async_trait::async_trait]
+impl DepositLogSource for VAnchorContractWrapper<EthersTimeLagClient> {
+    async fn next_index(&self) -> webb_relayer_utils::Result<u32> {
+        let next_index = self.contract.next_index().call().await?;
+        Ok(next_index)
+    }
+
+    async fn leaf_indices(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> webb_relayer_utils::Result<Vec<u32>> {
+        let filter = self.contract.event::<NewCommitmentFilter>().filter;
+        deposit_leaf_indices(
+            &*self.contract.client(),
+            &filter,
+            from_block,
+            to_block,
+            self.max_blocks_per_step().as_u64(),
+        )
+        .await
+    }
+}
+
+/// Returns the leaf indices of the deposits matching `filter` in the given
+/// (inclusive) block range, querying the logs `step` blocks at a time like
+/// the events watcher does, since the range can span the whole history of
+/// the contract.
+async fn deposit_leaf_indices<M>(
+    client: &M,
+    filter: &types::Filter,
+    from_block: u64,
+    to_block: u64,
+    step: u64,
+) -> webb_relayer_utils::Result<Vec<u32>>
+where
+    M: Middleware,
+    M::Error: Into<webb_relayer_utils::Error>,
+{
+    let step = step.max(1);
+    let mut indices = Vec::new();
+    let mut from = from_block;
+    while from <= to_block {
+        let to = from.saturating_add(step - 1).min(to_block);
+        let range = BlockRange {
+            from,
+            to,
+            target: to_block,
+        };
+        let backoff = retry::ConstantWithMaxRetryCount::new(
+            Duration::from_millis(500),
+            MAX_LOGS_RETRY_COUNT,
+        );
+        let logs = get_logs_in_range(client, filter, range, backoff).await?;
+        for log in logs {
+            let raw_log = RawLog {
+                topics: log.topics,
+                data: log.data.to_vec(),
+            };
+            let deposit = NewCommitmentFilter::decode_log(&raw_log)?;
+            indices.push(deposit.leaf_index.as_u32());
+        }
+        if to == u64::MAX {
+            break;
+        }
+        from = to + 1;
+    }
+    Ok(indices)
+}
+
+/// The outcome of [`recover_leaf_cache_checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointRecovery {
+    /// The leaf cache agrees with the contract, nothing was changed.
+    Consistent,
+    /// The checkpoint was walked back so that the watcher re-fetches the
+    /// deposit with `missing_leaf_index`, which was emitted at `block_number`.
+    Rewound {
+        /// The first leaf index missing from the cache.
+        missing_leaf_index: u32,
+        /// The block in which that leaf was inserted.
+        block_number: u64,
+    },
+    /// The missing leaves could not be located, the checkpoint was reset to
+    /// the contract deployment block.
+    FullRescan,
+}
+
+/// Cross-validates the leaf cache against the contract's `nextIndex` and
+/// repairs the last block number checkpoint if they disagree.
+///
+/// The checkpoint can end up ahead of the highest cached leaf, for example if
+/// the relayer was stopped between fetching the events and storing the leaves.
+/// Since the events watcher resumes from the checkpoint, these leaves would
+/// otherwise never be fetched again. In that case, we bisect the block range
+/// `[deployed_at, checkpoint]` using the deposit logs to find the block of the
+/// first missing leaf and move the checkpoint right before it.
+///
+/// If the bisection fails or does not converge within [`MAX_BISECT_STEPS`],
+/// or if the cache holds leaves the contract does not know about, we fall back
+/// to a full rescan from `deployed_at`.
+#[tracing::instrument(skip(store, source))]
+pub async fn recover_leaf_cache_checkpoint<S, K, L>(
+    store: &S,
+    key: K,
+    source: &L,
+    deployed_at: u64,
+) -> webb_relayer_utils::Result<CheckpointRecovery>
+where
+    S: LeafCacheStore,
+    K: Into<HistoryStoreKey> + Debug + Clone + Send,
+    L: DepositLogSource + Sync,
+{
+    let next_index = source.next_index().await?;
+    let cached: BTreeSet<u32> = store
+        .get_leaves(key.clone())?
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect();
+    if cached.range(next_index..).next().is_some() {
+        tracing::warn!(
+            next_index,
+            cached_leaves = cached.len(),
+            "Leaf cache has leaves beyond the contract next index, clearing it and doing a full rescan",
+        );
+        store.clear_leaves_cache(key.clone())?;
+        store.set_last_block_number(key, deployed_at)?;
+        return Ok(CheckpointRecovery::FullRescan);
+    }
+    let missing = match (0..next_index).find(|i| !cached.contains(i)) {
+        Some(missing) => missing,
+        None => return Ok(CheckpointRecovery::Consistent),
+    };
+    let checkpoint = store.get_last_block_number(key.clone(), deployed_at)?;
+    tracing::warn!(
+        next_index,
+        cached_leaves = cached.len(),
+        missing_leaf_index = missing,
+        checkpoint,
+        "Leaf cache is behind the contract, looking for the missing leaves",
+    );
+
+    // Find the first block in `[lo, hi]` that contains a deposit with a leaf
+    // index at or above the missing one. Leaf indices grow with the block
+    // number, so whenever `[lo, mid]` has none of them we can skip it.
+    let (mut lo, mut hi) = (deployed_at, checkpoint);
+    let mut found = None;
+    let mut converged = false;
+    for _ in 0..MAX_BISECT_STEPS {
+        if lo > hi {
+            converged = true;
+            break;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let indices = match source.leaf_indices(lo, mid).await {
+            Ok(indices) => indices,
+            Err(e) => {
+                tracing::warn!(
+                    from_block = lo,
+                    to_block = mid,
+                    error = %e,
+                    "Failed to query the deposit logs while bisecting",
+                );
+                break;
+            }
+        };
+        if indices.iter().any(|i| *i >= missing) {
+            if lo == mid {
+                found = Some(mid);
+                converged = true;
+                break;
+            }
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+
+    match found {
+        Some(block_number) => {
+            let recovered = block_number.saturating_sub(1).max(deployed_at);
+            store.set_last_block_number(key, recovered)?;
+            tracing::info!(
+                missing_leaf_index = missing,
+                block_number,
+                checkpoint,
+                recovered,
+                "Walked the leaf cache checkpoint back",
+            );
+            Ok(CheckpointRecovery::Rewound {
+                missing_leaf_index: missing,
+                block_number,
+            })
+        }
+        // the missing leaves are after the checkpoint, the watcher will
+        // pick them up normally.
+        None if converged => Ok(CheckpointRecovery::Consistent),
+        None => {
+            tracing::warn!(
+                missing_leaf_index = missing,
+                checkpoint,
+                deployed_at,
+                "Could not locate the missing leaves, falling back to a full rescan",
+            );
+            store.set_last_block_number(key, deployed_at)?;
+            Ok(CheckpointRecovery::FullRescan)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use webb::evm::ethers::abi::{self, Token};
+    use webb::evm::ethers::providers;
+    use webb::evm::ethers::types;
+    use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+    use webb_relayer_store::{HistoryStore, SledStore};
+
+    /// A fake chain, mapping block numbers to the deposits' leaf indices.
+    struct MockChain {
+        deposits: BTreeMap<u64, Vec<u32>>,
+        failing: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl DepositLogSource for MockChain {
+        async fn next_index(&self) -> webb_relayer_utils::Result<u32> {
+            Ok(self.deposits.values().map(|v| v.len() as u32).sum())
+        }
+
+        async fn leaf_indices(
+            &self,
+            from_block: u64,
+            to_block: u64,
+        ) -> webb_relayer_utils::Result<Vec<u32>> {
+            if self.failing {
+                return Err(webb_relayer_utils::Error::Generic("rpc error"));
+            }
+            Ok(self
+                .deposits
+                .range(from_block..=to_block)
+                .flat_map(|(_, v)| v.clone())
+                .collect())
+        }
+    }
+
+    fn key() -> ResourceId {
+        ResourceId::new(
+            TargetSystem::new_contract_address([1u8; 20]),
+            TypedChainId::Evm(5001),
+        )
+    }
+
+    /// Deposits at blocks 110, 120, ... with leaf indices 0, 1, ...
+    fn chain(leaves: u32) -> MockChain {
+        let deposits = (0..leaves)
+            .map(|i| (110 + u64::from(i) * 10, vec![i]))
+            .collect();
+        MockChain {
+            deposits,
+            failing: false,
+        }
+    }
+
+    fn store_with_leaves(leaves: u32, checkpoint: u64) -> SledStore {
+        let store = SledStore::temporary().unwrap();
+        let leaves = (0..leaves)
+            .map(|i| (i, types::H256::random().to_fixed_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        store
+            .insert_leaves_and_last_deposit_block_number(
+                key(),
+                &leaves,
+                checkpoint,
+            )
+            .unwrap();
+        store.set_last_block_number(key(), checkpoint).unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn consistent_cache_should_not_touch_the_checkpoint() {
+        let store = store_with_leaves(10, 210);
+        let result =
+            recover_leaf_cache_checkpoint(&store, key(), &chain(10), 100)
+                .await
+                .unwrap();
+        assert_eq!(result, CheckpointRecovery::Consistent);
+        assert_eq!(store.get_last_block_number(key(), 100).unwrap(), 210);
+    }
+
+    #[tokio::test]
+    async fn advanced_checkpoint_should_be_walked_back() {
+        // only 4 leaves stored, but the checkpoint claims we synced all 10.
+        let store = store_with_leaves(4, 210);
+        let result =
+            recover_leaf_cache_checkpoint(&store, key(), &chain(10), 100)
+                .await
+                .unwrap();
+        assert_eq!(
+            result,
+            CheckpointRecovery::Rewound {
+                missing_leaf_index: 4,
+                block_number: 150,
+            }
+        );
+        assert_eq!(store.get_last_block_number(key(), 100).unwrap(), 149);
+    }
+
+    #[tokio::test]
+    async fn missing_leaves_after_checkpoint_should_be_left_to_the_watcher() {
+        // the watcher did not reach the remaining deposits yet.
+        let store = store_with_leaves(4, 145);
+        let result =
+            recover_leaf_cache_checkpoint(&store, key(), &chain(10), 100)
+                .await
+                .unwrap();
+        assert_eq!(result, CheckpointRecovery::Consistent);
+        assert_eq!(store.get_last_block_number(key(), 100).unwrap(), 145);
+    }
+
+    #[tokio::test]
+    async fn failing_log_queries_should_fall_back_to_full_rescan() {
+        let store = store_with_leaves(4, 210);
+        let mut source = chain(10);
+        source.failing = true;
+        let result = recover_leaf_cache_checkpoint(&store, key(), &source, 100)
+            .await
+            .unwrap();
+        assert_eq!(result, CheckpointRecovery::FullRescan);
+        assert_eq!(store.get_last_block_number(key(), 100).unwrap(), 100);
+    }
+
+    #[tokio::test]
+    async fn unknown_leaves_should_clear_the_cache() {
+        let store = store_with_leaves(12, 210);
+        let result =
+            recover_leaf_cache_checkpoint(&store, key(), &chain(10), 100)
+                .await
+                .unwrap();
+        assert_eq!(result, CheckpointRecovery::FullRescan);
+        assert_eq!(store.get_leaves(key()).unwrap().len(), 0);
+        assert_eq!(store.get_last_block_number(key(), 100).unwrap(), 100);
+    }
+
+    fn deposit_log(block: u64, leaf_index: u32) -> types::Log {
+        let data = abi::encode(&[
+            Token::Uint(types::U256::from(block)),
+            Token::Uint(types::U256::zero()),
+            Token::Uint(leaf_index.into()),
+            Token::Bytes(vec![0u8; 32]),
+        ]);
+        types::Log {
+            topics: vec![NewCommitmentFilter::signature()],
+            data: data.into(),
+            block_number: Some(block.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn deposit_logs_should_be_queried_one_step_at_a_time() {
+        let (provider, mock) = providers::Provider::mocked();
+        let filter = types::Filter::new();
+        // the responses are served last in, first out: the logs of the
+        // blocks 100..=109, 110..=119 and 120..=125, then the head of the
+        // provider once the last step reached the end of the range.
+        mock.push(types::U64::from(125)).unwrap();
+        mock.push(vec![deposit_log(121, 2)]).unwrap();
+        mock.push(Vec::<types::Log>::new()).unwrap();
+        mock.push(vec![deposit_log(100, 0), deposit_log(109, 1)])
+            .unwrap();
+        let indices = deposit_leaf_indices(&provider, &filter, 100, 125, 10)
+            .await
+            .unwrap();
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::merkle_tree::SparseMerkleTree;
+use arkworks_native_gadgets::poseidon::Poseidon;
+use arkworks_setups::common::setup_params;
+use arkworks_setups::Curve;
+use arkworks_utils::bytes_vec_to_f;
+use std::collections::BTreeMap;
+use webb::evm::ethers::signers::LocalWallet;
+use webb::evm::ethers::types::{Address, Signature, H256};
+use webb::evm::ethers::utils::keccak256;
+use webb_relayer_utils::Error;
+
+type MerkleTree = SparseMerkleTree<Bn254Fr, Poseidon<Bn254Fr>, 30>;
+
+/// Every leaf snapshot starts with these bytes.
+const MAGIC: &[u8; 4] = b"WRLS";
+/// The version of the leaf snapshot format.
+pub const LEAF_SNAPSHOT_VERSION: u8 = 1;
+/// magic + version + chain id + contract + leaf count + last block + root
+/// + leaves hash.
+const HEADER_LEN: usize = 4 + 1 + 4 + 20 + 4 + 8 + 32 + 32;
+const SIGNATURE_LEN: usize = 65;
+
+/// The header of a [`LeafSnapshot`], which is what the relayer signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafSnapshotHeader {
+    /// The chain id of the VAnchor contract.
+    pub chain_id: u32,
+    /// The address of the VAnchor contract.
+    pub contract: Address,
+    /// The number of leaves in the snapshot.
+    pub leaf_count: u32,
+    /// The last block the leaves were queried at.
+    pub last_block: u64,
+    /// The Merkle root of the leaves, as computed by the relayer.
+    pub merkle_root: [u8; 32],
+    /// The keccak256 hash of all the leaves, in order.
+    pub leaves_hash: [u8; 32],
+}
+
+impl LeafSnapshotHeader {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(LEAF_SNAPSHOT_VERSION);
+        bytes.extend_from_slice(&self.chain_id.to_be_bytes());
+        bytes.extend_from_slice(self.contract.as_bytes());
+        bytes.extend_from_slice(&self.leaf_count.to_be_bytes());
+        bytes.extend_from_slice(&self.last_block.to_be_bytes());
+        bytes.extend_from_slice(&self.merkle_root);
+        bytes.extend_from_slice(&self.leaves_hash);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> webb_relayer_utils::Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(Error::Generic("Not a leaf snapshot"));
+        }
+        if bytes[4] != LEAF_SNAPSHOT_VERSION {
+            return Err(Error::Generic("Unsupported leaf snapshot version"));
+        }
+        let mut at = 5;
+        let mut take = |len: usize| {
+            let chunk = &bytes[at..at + len];
+            at += len;
+            chunk
+        };
+        let chain_id = u32::from_be_bytes(take(4).try_into().unwrap());
+        let contract = Address::from_slice(take(20));
+        let leaf_count = u32::from_be_bytes(take(4).try_into().unwrap());
+        let last_block = u64::from_be_bytes(take(8).try_into().unwrap());
+        let merkle_root = take(32).try_into().unwrap();
+        let leaves_hash = take(32).try_into().unwrap();
+        Ok(Self {
+            chain_id,
+            contract,
+            leaf_count,
+            last_block,
+            merkle_root,
+            leaves_hash,
+        })
+    }
+
+    /// The hash the relayer signs.
+    pub fn signing_hash(&self) -> H256 {
+        H256(keccak256(self.to_bytes()))
+    }
+}
+
+/// A signed export of the cached leaves of a VAnchor contract.
+///
+/// The binary layout is the header, the 65 bytes signature over
+/// [`LeafSnapshotHeader::signing_hash`], then every leaf as 32 bytes.
+#[derive(Debug, Clone)]
+pub struct LeafSnapshot {
+    /// What the snapshot contains.
+    pub header: LeafSnapshotHeader,
+    /// The signature of the relayer over the header.
+    pub signature: Signature,
+    /// The leaves, starting from index zero.
+    pub leaves: Vec<H256>,
+}
+
+impl LeafSnapshot {
+    /// Creates a snapshot of the given leaves, signed with the wallet.
+    ///
+    /// The leaves have to start at index zero and have no gaps, otherwise
+    /// the Merkle root would not match the one on chain.
+    pub fn new(
+        chain_id: u32,
+        contract: Address,
+        leaves: BTreeMap<u32, H256>,
+        last_block: u64,
+        empty_leaf: &[u8],
+        wallet: &LocalWallet,
+    ) -> webb_relayer_utils::Result<Self> {
+        let has_gaps = leaves.keys().enumerate().any(|(i, k)| i as u32 != *k);
+        if has_gaps {
+            return Err(Error::Generic("The leaf cache has gaps"));
+        }
+        let leaves: Vec<H256> = leaves.into_values().collect();
+        let header = LeafSnapshotHeader {
+            chain_id,
+            contract,
+            leaf_count: leaves.len() as u32,
+            last_block,
+            merkle_root: merkle_root(&leaves, empty_leaf)?,
+            leaves_hash: leaves_hash(&leaves),
+        };
+        let signature = wallet.sign_hash(header.signing_hash())?;
+        Ok(Self {
+            header,
+            signature,
+            leaves,
+        })
+    }
+
+    /// Encodes the snapshot into its binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.signature.to_vec());
+        for leaf in &self.leaves {
+            bytes.extend_from_slice(leaf.as_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a snapshot, making sure the leaves match the header.
+    ///
+    /// This does not check who signed it, see [`LeafSnapshot::signer`].
+    pub fn from_bytes(bytes: &[u8]) -> webb_relayer_utils::Result<Self> {
+        let header = LeafSnapshotHeader::from_bytes(bytes)?;
+        let body = &bytes[HEADER_LEN..];
+        let expected_len =
+            SIGNATURE_LEN + header.leaf_count as usize * H256::len_bytes();
+        if body.len() != expected_len {
+            return Err(Error::Generic("Truncated leaf snapshot"));
+        }
+        let signature = Signature::try_from(&body[..SIGNATURE_LEN])
+            .map_err(|_| Error::Generic("Invalid leaf snapshot signature"))?;
+        let leaves: Vec<H256> = body[SIGNATURE_LEN..]
+            .chunks_exact(H256::len_bytes())
+            .map(H256::from_slice)
+            .collect();
+        if leaves_hash(&leaves) != header.leaves_hash {
+            return Err(Error::Generic(
+                "The leaves do not match the leaf snapshot header",
+            ));
+        }
+        Ok(Self {
+            header,
+            signature,
+            leaves,
+        })
+    }
+
+    /// Recovers the address of the relayer that signed the snapshot.
+    pub fn signer(&self) -> webb_relayer_utils::Result<Address> {
+        self.signature
+            .recover(self.header.signing_hash())
+            .map_err(|_| Error::Generic("Invalid leaf snapshot signature"))
+    }
+
+    /// The leaves with their index, as stored in the leaf cache.
+    pub fn indexed_leaves(&self) -> Vec<(u32, Vec<u8>)> {
+        self.leaves
+            .iter()
+            .enumerate()
+            .map(|(i, leaf)| (i as u32, leaf.as_bytes().to_vec()))
+            .collect()
+    }
+}
+
+fn leaves_hash(leaves: &[H256]) -> [u8; 32] {
+    keccak256(leaves.iter().flat_map(|l| l.0).collect::<Vec<u8>>())
+}
+
+/// Computes the Merkle root of the leaves, the same way the VAnchor
+/// contract does.
+///
+/// `empty_leaf` is the zero hash of the contract, see `getZeroHash(0)`.
+pub fn merkle_root(
+    leaves: &[H256],
+    empty_leaf: &[u8],
+) -> webb_relayer_utils::Result<[u8; 32]> {
+    let params = setup_params::<Bn254Fr>(Curve::Bn254, 5, 3);
+    let poseidon = Poseidon::<Bn254Fr>::new(params);
+    let empty_leaf_scalar: Vec<Bn254Fr> =
+        bytes_vec_to_f(&vec![empty_leaf.to_vec()]);
+    let empty_leaf_vec = empty_leaf_scalar
+        .get(0)
+        .map(|d| d.into_repr().to_bytes_be())
+        .ok_or(Error::ConvertLeafScalarError)?;
+    let batch: BTreeMap<u32, Bn254Fr> = leaves
+        .iter()
+        .enumerate()
+        .map(|(i, leaf)| {
+            (i as u32, Bn254Fr::from_be_bytes_mod_order(leaf.as_bytes()))
+        })
+        .collect();
+    let mt = MerkleTree::new(&batch, &poseidon, &empty_leaf_vec)?;
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&mt.root().into_repr().to_bytes_be());
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb::evm::ethers::signers::Signer;
+
+    const EMPTY_LEAF: &str =
+        "2fe54c60d3acabf3343a35b6eba15db4821b340f76e741e2249685ed4899af6c";
+
+    fn leaves() -> BTreeMap<u32, H256> {
+        [
+            "0x017dc570cb5c6807dbaa475c9d4e445ac95a73400692541c367786c009c844cf",
+            "0x04568790fcfc67d855dfb60de6844f6d82f4b8dc6dd0115f9f04ece21ebffb8d",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (i as u32, l.parse().unwrap()))
+        .collect()
+    }
+
+    fn wallet() -> LocalWallet {
+        "0x8917174396171783496173419137618235192359106130478137647163400318"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshot_should_round_trip_and_recover_the_signer() {
+        let empty_leaf = hex::decode(EMPTY_LEAF).unwrap();
+        let contract = Address::repeat_byte(0x42);
+        let wallet = wallet();
+        let snapshot =
+            LeafSnapshot::new(5, contract, leaves(), 100, &empty_leaf, &wallet)
+                .unwrap();
+        assert_eq!(
+            hex::encode(snapshot.header.merkle_root),
+            "304341db4305ca71db912b3ea85acb4ab8f687435aa51a9a65220bfc558eb8d1"
+        );
+
+        let decoded = LeafSnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(decoded.header, snapshot.header);
+        assert_eq!(decoded.leaves, snapshot.leaves);
+        assert_eq!(decoded.signer().unwrap(), wallet.address());
+    }
+
+    #[test]
+    fn tampered_snapshots_should_be_rejected() {
+        let empty_leaf = hex::decode(EMPTY_LEAF).unwrap();
+        let snapshot = LeafSnapshot::new(
+            5,
+            Address::repeat_byte(0x42),
+            leaves(),
+            100,
+            &empty_leaf,
+            &wallet(),
+        )
+        .unwrap();
+        let mut bytes = snapshot.to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(LeafSnapshot::from_bytes(&bytes).is_err());
+
+        let mut with_gap = leaves();
+        let leaf = with_gap.remove(&0).unwrap();
+        with_gap.insert(2, leaf);
+        assert!(LeafSnapshot::new(
+            5,
+            Address::repeat_byte(0x42),
+            with_gap,
+            100,
+            &empty_leaf,
+            &wallet(),
+        )
+        .is_err());
+    }
+}
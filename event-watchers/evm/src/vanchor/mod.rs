@@ -1,11 +1,29 @@
 use super::*;
+pub mod anchor_state_sync;
+pub mod leaf_cache_recovery;
+pub mod leaf_snapshot;
 pub mod vanchor_deposit_handler;
 pub mod vanchor_encrypted_outputs_handler;
+pub mod vanchor_executed_proposals_handler;
 pub mod vanchor_leaves_handler;
+pub mod vanchor_neighbor_roots;
+pub mod vanchor_root_source;
 
+#[doc(hidden)]
+pub use anchor_state_sync::*;
+#[doc(hidden)]
+pub use leaf_cache_recovery::*;
+#[doc(hidden)]
+pub use leaf_snapshot::*;
 #[doc(hidden)]
 pub use vanchor_deposit_handler::*;
 #[doc(hidden)]
 pub use vanchor_encrypted_outputs_handler::*;
 #[doc(hidden)]
+pub use vanchor_executed_proposals_handler::*;
+#[doc(hidden)]
 pub use vanchor_leaves_handler::*;
+#[doc(hidden)]
+pub use vanchor_neighbor_roots::*;
+#[doc(hidden)]
+pub use vanchor_root_source::*;
@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::NeighborRootsSource;
 use crate::VAnchorContractWrapper;
 use ethereum_types::H256;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use webb::evm::contract::protocol_solidity::v_anchor_contract::NewCommitmentFilter;
 use webb::evm::contract::protocol_solidity::VAnchorContractEvents;
 use webb::evm::ethers::prelude::LogMeta;
 use webb::evm::ethers::types;
@@ -25,16 +28,21 @@ use webb_event_watcher_traits::EthersTimeLagClient;
 use webb_proposal_signing_backends::proposal_handler;
 use webb_proposal_signing_backends::queue::policy::ProposalPolicy;
 use webb_proposal_signing_backends::queue::{
-    ProposalsQueue, QueuedAnchorUpdateProposal,
+    ProposalHash, ProposalsQueue, QueuedAnchorUpdateProposal,
 };
-use webb_relayer_config::anchor::LinkedAnchorConfig;
+use webb_proposals::ProposalTrait;
+use webb_relayer_config::anchor::{resolve_linked_anchors, LinkedAnchorConfig};
+use webb_relayer_config::evm::VAnchorContractConfig;
 use webb_relayer_store::SledStore;
-use webb_relayer_store::{EventHashStore, HistoryStore};
+use webb_relayer_store::{
+    DepositCorrelationId, DepositPropagationStore, EventHashStore,
+    HistoryStore, ProposalEventKind, ProposalEventStore,
+};
 use webb_relayer_utils::metric;
 
 /// Represents an VAnchor Contract Watcher which will use a configured signing backend for signing proposals.
 #[derive(typed_builder::TypedBuilder)]
-pub struct VAnchorDepositHandler<Q, P, C> {
+pub struct VAnchorDepositHandler<Q, P, C, R> {
     #[builder(setter(into))]
     chain_id: types::U256,
     #[builder(setter(into))]
@@ -42,14 +50,262 @@ pub struct VAnchorDepositHandler<Q, P, C> {
     proposals_queue: Q,
     policy: P,
     bridge_registry_backend: C,
+    neighbor_roots: R,
+}
+
+/// A new leaf of the source anchor, as found in its events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Deposit {
+    leaf_index: u32,
+    commitment: H256,
+}
+
+impl Deposit {
+    /// Whether the linked anchors have to be updated after this deposit.
+    ///
+    /// `VAnchor` on every `transact` call, emits two events, similar to the
+    /// `Deposit` event but we call it the `Insertion` event, a la two `UTXO`
+    /// and since we only need to update the target `VAnchor` only when needed,
+    /// the first `Insertion` event sounds redundant in this case.
+    fn needs_anchor_update(&self) -> bool {
+        self.leaf_index % 2 != 0
+    }
+}
+
+impl From<&NewCommitmentFilter> for Deposit {
+    fn from(event: &NewCommitmentFilter) -> Self {
+        let commitment: [u8; 32] = event.commitment.into();
+        Self {
+            leaf_index: event.leaf_index.as_u32(),
+            commitment: H256::from(commitment),
+        }
+    }
+}
+
+/// An update of a linked anchor to the new root of the source anchor.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AnchorUpdate {
+    pub(crate) root: [u8; 32],
+    pub(crate) leaf_index: u32,
+    pub(crate) src_resource_id: webb_proposals::ResourceId,
+    pub(crate) target_resource_id: webb_proposals::ResourceId,
+}
+
+/// Encodes the anchor update proposal for the target system of the linked
+/// anchor.
+pub(crate) fn build_proposal(
+    update: &AnchorUpdate,
+) -> QueuedAnchorUpdateProposal {
+    let AnchorUpdate {
+        root,
+        leaf_index,
+        src_resource_id,
+        target_resource_id,
+    } = *update;
+    match target_resource_id.target_system() {
+        webb_proposals::TargetSystem::ContractAddress(_) => {
+            let p = proposal_handler::evm_anchor_update_proposal(
+                root,
+                leaf_index,
+                target_resource_id,
+                src_resource_id,
+            );
+            QueuedAnchorUpdateProposal::new(p)
+        }
+        webb_proposals::TargetSystem::Substrate(_) => {
+            let p = proposal_handler::substrate_anchor_update_proposal(
+                root,
+                leaf_index,
+                target_resource_id,
+                src_resource_id,
+            );
+            QueuedAnchorUpdateProposal::new(p)
+        }
+    }
+}
+
+/// Enqueues the anchor update proposal, to be signed, and tracks it as the
+/// propagation of the deposit to the linked anchor.
+///
+/// The queued proposals of the same anchor that the policy drops in favor
+/// of this one are recorded as cancelled.
+pub(crate) fn dispatch_anchor_update<Q, P>(
+    store: &SledStore,
+    proposals_queue: &Q,
+    policy: P,
+    update: &AnchorUpdate,
+    proposal: QueuedAnchorUpdateProposal,
+) -> webb_relayer_utils::Result<()>
+where
+    Q: ProposalsQueue<Proposal = QueuedAnchorUpdateProposal>,
+    P: ProposalPolicy,
+{
+    let proposal_data_hash = proposal.full_hash();
+    let proposal_data = proposal.to_vec();
+    let resource_id = proposal.header().resource_id();
+    // the proposals of the same anchor, hashed once.
+    let mut queued = Vec::new();
+    proposals_queue.retain(|p| {
+        if p.header().resource_id() == resource_id {
+            queued.push((p.full_hash(), p.to_vec()));
+        }
+        true
+    })?;
+    proposals_queue.enqueue(proposal, policy)?;
+    store.record_proposal(
+        ProposalEventKind::Created,
+        &proposal_data,
+        proposal_data_hash,
+        None,
+    );
+    if !queued.is_empty() {
+        let mut remaining = HashSet::new();
+        proposals_queue.retain(|p| {
+            if p.header().resource_id() == resource_id {
+                remaining.insert(p.full_hash());
+            }
+            true
+        })?;
+        for (hash, data) in queued {
+            if !remaining.contains(&hash) {
+                store.record_proposal(
+                    ProposalEventKind::Cancelled,
+                    &data,
+                    hash,
+                    None,
+                );
+            }
+        }
+    }
+    let correlation_id =
+        DepositCorrelationId::new(update.src_resource_id, update.leaf_index);
+    store.insert_deposit_proposal(
+        correlation_id,
+        update.target_resource_id,
+        proposal_data_hash,
+    )?;
+    Ok(())
+}
+
+impl<Q, P, C, R> VAnchorDepositHandler<Q, P, C, R>
+where
+    Q: ProposalsQueue<Proposal = QueuedAnchorUpdateProposal> + Send + Sync,
+    P: ProposalPolicy + Send + Sync + Clone,
+    C: BridgeRegistryBackend + Send + Sync,
+    R: NeighborRootsSource + Send + Sync,
+{
+    /// Returns the linked anchors of the source anchor, from the config or
+    /// the bridge registry.
+    async fn resolve_destinations(
+        &self,
+        src_resource_id: webb_proposals::ResourceId,
+        linked_anchors: &Option<Vec<LinkedAnchorConfig>>,
+    ) -> webb_relayer_utils::Result<Vec<webb_proposals::ResourceId>> {
+        let linked_anchors = self
+            .bridge_registry_backend
+            .config_or_dkg_bridges(linked_anchors, &src_resource_id)
+            .await?;
+        Ok(resolve_linked_anchors(src_resource_id, linked_anchors))
+    }
+
+    /// Returns true if the linked anchor already has the root.
+    ///
+    /// Failing to read its roots is not fatal, the update gets proposed anyway.
+    async fn is_already_updated(&self, update: &AnchorUpdate) -> bool {
+        let roots = self
+            .neighbor_roots
+            .latest_neighbor_roots(update.target_resource_id)
+            .await;
+        match roots {
+            Ok(roots) => roots.contains(&update.root),
+            Err(e) => {
+                tracing::warn!(
+                    target_resource_id = ?update.target_resource_id,
+                    "Failed to read the roots of the linked anchor: {}",
+                    e,
+                );
+                false
+            }
+        }
+    }
+
+    /// Enqueues the proposal, to be signed, and tracks it as the
+    /// propagation of the deposit to the linked anchor.
+    fn dispatch(
+        &self,
+        update: &AnchorUpdate,
+        proposal: QueuedAnchorUpdateProposal,
+    ) -> webb_relayer_utils::Result<()> {
+        dispatch_anchor_update(
+            &self.store,
+            &self.proposals_queue,
+            self.policy.clone(),
+            update,
+            proposal,
+        )
+    }
+
+    /// Enqueues the anchor update proposal of the linked anchor, unless it is
+    /// already updated and `skip_if_already_updated` is on.
+    async fn propose_anchor_update(
+        &self,
+        update: AnchorUpdate,
+        skip_if_already_updated: bool,
+        metrics: &Mutex<metric::Metrics>,
+    ) -> webb_relayer_utils::Result<()> {
+        if skip_if_already_updated && self.is_already_updated(&update).await {
+            tracing::debug!(
+                target_resource_id = ?update.target_resource_id,
+                root = %hex::encode(update.root),
+                "Linked anchor already has the root, skipping proposal.",
+            );
+            metrics.lock().await.skipped_already_updated.inc();
+            return Ok(());
+        }
+        // Anchor update proposal proposed metric
+        metrics.lock().await.anchor_update_proposals.inc();
+        let proposal = build_proposal(&update);
+        self.dispatch(&update, proposal)
+    }
+
+    /// Proposes the update of every linked anchor of the source anchor to
+    /// `root`, after the deposit.
+    async fn propose_anchor_updates(
+        &self,
+        deposit: Deposit,
+        root: [u8; 32],
+        src_resource_id: webb_proposals::ResourceId,
+        config: &VAnchorContractConfig,
+        metrics: &Mutex<metric::Metrics>,
+    ) -> webb_relayer_utils::Result<()> {
+        let targets = self
+            .resolve_destinations(src_resource_id, &config.linked_anchors)
+            .await?;
+        for target_resource_id in targets {
+            let update = AnchorUpdate {
+                root,
+                leaf_index: deposit.leaf_index,
+                src_resource_id,
+                target_resource_id,
+            };
+            self.propose_anchor_update(
+                update,
+                config.skip_if_already_updated,
+                metrics,
+            )
+            .await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
-impl<Q, P, C> EventHandler for VAnchorDepositHandler<Q, P, C>
+impl<Q, P, C, R> EventHandler for VAnchorDepositHandler<Q, P, C, R>
 where
     Q: ProposalsQueue<Proposal = QueuedAnchorUpdateProposal> + Send + Sync,
     P: ProposalPolicy + Send + Sync + Clone,
     C: BridgeRegistryBackend + Send + Sync,
+    R: NeighborRootsSource + Send + Sync,
 {
     type Contract = VAnchorContractWrapper<EthersTimeLagClient>;
 
@@ -96,37 +352,28 @@ where
         (event, log): (Self::Events, LogMeta),
         metrics: Arc<Mutex<metric::Metrics>>,
     ) -> webb_relayer_utils::Result<()> {
-        use VAnchorContractEvents::*;
         let event_data = match event {
-            NewCommitmentFilter(data) => {
-                let commitment: [u8; 32] = data.commitment.into();
-                let info = (data.leaf_index.as_u32(), H256::from(commitment));
-                tracing::event!(
-                    target: webb_relayer_utils::probe::TARGET,
-                    tracing::Level::DEBUG,
-                    kind = %webb_relayer_utils::probe::Kind::MerkleTreeInsertion,
-                    leaf_index = %info.0,
-                    leaf = %info.1,
-                    chain_id = %self.chain_id,
-                    block_number = %log.block_number
-                );
-                data
-            }
+            VAnchorContractEvents::NewCommitmentFilter(data) => data,
             _ => return Ok(()),
         };
-        // Only construct the `AnchorUpdateProposal` if this condition evaluates to `true`: `leaf_index % 2 != 0`
-        // The reason behind this is that `VAnchor` on every `transact` call, emits two events,
-        // similar to the `Deposit` event but we call it the `Insertion` event, a la two `UTXO`
-        // and since we only need to update the target `VAnchor` only when needed,
-        // the first `Insertion` event sounds redundant in this case.
+        let deposit = Deposit::from(&event_data);
+        tracing::event!(
+            target: webb_relayer_utils::probe::TARGET,
+            tracing::Level::DEBUG,
+            kind = %webb_relayer_utils::probe::Kind::MerkleTreeInsertion,
+            leaf_index = %deposit.leaf_index,
+            leaf = %deposit.commitment,
+            chain_id = %self.chain_id,
+            block_number = %log.block_number
+        );
         tracing::debug!(
             event = ?event_data,
             "VAnchor new leaf event",
         );
 
-        if event_data.leaf_index.as_u32() % 2 == 0 {
+        if !deposit.needs_anchor_update() {
             tracing::debug!(
-                leaf_index = %event_data.leaf_index,
+                leaf_index = %deposit.leaf_index,
                 is_even_index = %event_data.leaf_index.as_u32() % 2 == 0,
                 "VAnchor new leaf index does not satisfy the condition, skipping proposal.",
             );
@@ -135,7 +382,6 @@ where
 
         let root: [u8; 32] =
             wrapper.contract.get_last_root().call().await?.into();
-        let leaf_index = event_data.leaf_index.as_u32();
         let src_chain_id =
             webb_proposals::TypedChainId::Evm(self.chain_id.as_u32());
         let src_target_system =
@@ -144,49 +390,23 @@ where
             );
         let src_resource_id =
             webb_proposals::ResourceId::new(src_target_system, src_chain_id);
+        // track this deposit, so we can later tell where it got propagated to.
+        let correlation_id =
+            DepositCorrelationId::new(src_resource_id, deposit.leaf_index);
+        self.store.insert_deposit(
+            correlation_id,
+            log.transaction_hash,
+            log.block_number.as_u64(),
+        )?;
 
-        let linked_anchors = self
-            .bridge_registry_backend
-            .config_or_dkg_bridges(
-                &wrapper.config.linked_anchors,
-                &src_resource_id,
-            )
-            .await?;
-        for linked_anchor in linked_anchors {
-            let target_resource_id = match linked_anchor {
-                LinkedAnchorConfig::Raw(target) => {
-                    let bytes: [u8; 32] = target.resource_id.into();
-                    webb_proposals::ResourceId::from(bytes)
-                }
-                _ => unreachable!("unsupported"),
-            };
-            // Anchor update proposal proposed metric
-            metrics.lock().await.anchor_update_proposals.inc();
-
-            let proposal = match target_resource_id.target_system() {
-                webb_proposals::TargetSystem::ContractAddress(_) => {
-                    let p = proposal_handler::evm_anchor_update_proposal(
-                        root,
-                        leaf_index,
-                        target_resource_id,
-                        src_resource_id,
-                    );
-                    QueuedAnchorUpdateProposal::new(p)
-                }
-                webb_proposals::TargetSystem::Substrate(_) => {
-                    let p = proposal_handler::substrate_anchor_update_proposal(
-                        root,
-                        leaf_index,
-                        target_resource_id,
-                        src_resource_id,
-                    );
-                    QueuedAnchorUpdateProposal::new(p)
-                }
-            };
-
-            self.proposals_queue
-                .enqueue(proposal, self.policy.clone())?;
-        }
+        self.propose_anchor_updates(
+            deposit,
+            root,
+            src_resource_id,
+            &wrapper.config,
+            &metrics,
+        )
+        .await?;
         // mark this event as processed.
         let events_bytes = serde_json::to_vec(&event_data)?;
         store.store_event(&events_bytes)?;
@@ -194,3 +414,199 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_bridge_registry_backends::mocked::MockedBridgeRegistryBackend;
+    use webb_proposal_signing_backends::queue::mem::InMemoryProposalsQueue;
+    use webb_proposal_signing_backends::queue::policy::AlwaysHigherNoncePolicy;
+    use webb_proposals::{
+        ProposalTrait, ResourceId, SubstrateTargetSystem, TargetSystem,
+        TypedChainId,
+    };
+
+    /// A linked anchor that already knows the given roots.
+    struct MockedNeighborRoots(Vec<[u8; 32]>);
+
+    #[async_trait::async_trait]
+    impl NeighborRootsSource for MockedNeighborRoots {
+        async fn latest_neighbor_roots(
+            &self,
+            _anchor: ResourceId,
+        ) -> webb_relayer_utils::Result<Vec<[u8; 32]>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn anchor_update(root: [u8; 32]) -> AnchorUpdate {
+        AnchorUpdate {
+            root,
+            leaf_index: 1,
+            src_resource_id: ResourceId::new(
+                TargetSystem::new_contract_address([1u8; 20]),
+                TypedChainId::Evm(5),
+            ),
+            target_resource_id: ResourceId::new(
+                TargetSystem::new_contract_address([2u8; 20]),
+                TypedChainId::Evm(4),
+            ),
+        }
+    }
+
+    fn handler(
+        known_roots: Vec<[u8; 32]>,
+    ) -> VAnchorDepositHandler<
+        InMemoryProposalsQueue,
+        AlwaysHigherNoncePolicy,
+        MockedBridgeRegistryBackend,
+        MockedNeighborRoots,
+    > {
+        VAnchorDepositHandler::builder()
+            .chain_id(5u32)
+            .store(SledStore::temporary().unwrap())
+            .bridge_registry_backend(
+                MockedBridgeRegistryBackend::builder().build(),
+            )
+            .proposals_queue(InMemoryProposalsQueue::new())
+            .policy(AlwaysHigherNoncePolicy)
+            .neighbor_roots(MockedNeighborRoots(known_roots))
+            .build()
+    }
+
+    #[test]
+    fn should_only_update_the_linked_anchors_after_the_odd_leaf() {
+        let first = Deposit {
+            leaf_index: 4,
+            commitment: H256::zero(),
+        };
+        let second = Deposit {
+            leaf_index: 5,
+            ..first
+        };
+        assert!(!first.needs_anchor_update());
+        assert!(second.needs_anchor_update());
+    }
+
+    /// The encoded anchor update proposal checked in under
+    /// `fixtures/anchor-update-proposal`, as hex.
+    fn golden_proposal(hex: &str) -> Vec<u8> {
+        hex::decode(hex.trim()).unwrap()
+    }
+
+    #[test]
+    fn should_encode_the_proposal_for_the_target_system() {
+        // the header, with the `updateEdge(uint256,uint32,bytes32)` selector,
+        // then the merkle root and the source resource id.
+        let evm_update = anchor_update([42u8; 32]);
+        let proposal = build_proposal(&evm_update);
+        assert_eq!(
+            proposal.to_vec(),
+            golden_proposal(include_str!(
+                "../../fixtures/anchor-update-proposal/evm.hex"
+            )),
+        );
+
+        let substrate_update = AnchorUpdate {
+            target_resource_id: ResourceId::new(
+                TargetSystem::Substrate(
+                    SubstrateTargetSystem::builder()
+                        .pallet_index(44)
+                        .tree_id(3)
+                        .build(),
+                ),
+                TypedChainId::Substrate(1081),
+            ),
+            ..evm_update
+        };
+        let proposal = build_proposal(&substrate_update);
+        // the header, then the SCALE encoded call of the pallet.
+        assert_eq!(
+            proposal.to_vec(),
+            golden_proposal(include_str!(
+                "../../fixtures/anchor-update-proposal/substrate.hex"
+            )),
+        );
+    }
+
+    #[tokio::test]
+    async fn should_propose_an_update_to_every_linked_anchor() {
+        let handler = handler(vec![]);
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+        let config: VAnchorContractConfig =
+            serde_json::from_value(serde_json::json!({
+                "address": "0x0101010101010101010101010101010101010101",
+                "deployed-at": 1,
+                "events-watcher": { "polling-interval": 1000 },
+                "linked-anchors": [
+                    {
+                        "type": "Evm",
+                        "chain-id": 4,
+                        "address": "0x0202020202020202020202020202020202020202",
+                    },
+                    { "type": "Substrate", "chain-id": 1081, "pallet": 44, "tree-id": 3 },
+                    // the source anchor itself.
+                    {
+                        "type": "Evm",
+                        "chain-id": 5,
+                        "address": "0x0101010101010101010101010101010101010101",
+                    },
+                ],
+            }))
+            .unwrap();
+        let src_resource_id = anchor_update([0u8; 32]).src_resource_id;
+        let deposit = Deposit {
+            leaf_index: 1,
+            commitment: H256::repeat_byte(9),
+        };
+
+        handler
+            .propose_anchor_updates(
+                deposit,
+                [42u8; 32],
+                src_resource_id,
+                &config,
+                &metrics,
+            )
+            .await
+            .unwrap();
+        assert_eq!(handler.proposals_queue.len().unwrap(), 2);
+        let queued_for = |chain_id: TypedChainId| {
+            handler
+                .proposals_queue
+                .find(|p| p.header().resource_id().typed_chain_id() == chain_id)
+                .unwrap()
+        };
+        assert!(queued_for(TypedChainId::Evm(4)).is_some());
+        assert!(queued_for(TypedChainId::Substrate(1081)).is_some());
+        assert!(queued_for(TypedChainId::Evm(5)).is_none());
+    }
+
+    #[tokio::test]
+    async fn should_skip_linked_anchors_that_already_have_the_root() {
+        let root = [42u8; 32];
+        let handler = handler(vec![[1u8; 32], root]);
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+
+        handler
+            .propose_anchor_update(anchor_update(root), true, &metrics)
+            .await
+            .unwrap();
+        assert!(handler.proposals_queue.is_empty().unwrap());
+
+        // a new root is proposed.
+        handler
+            .propose_anchor_update(anchor_update([7u8; 32]), true, &metrics)
+            .await
+            .unwrap();
+        assert_eq!(handler.proposals_queue.len().unwrap(), 1);
+
+        // and so is a known one, once the check is off.
+        handler.proposals_queue.clear().unwrap();
+        handler
+            .propose_anchor_update(anchor_update(root), false, &metrics)
+            .await
+            .unwrap();
+        assert_eq!(handler.proposals_queue.len().unwrap(), 1);
+    }
+}
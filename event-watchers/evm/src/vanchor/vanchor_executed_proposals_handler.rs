@@ -0,0 +1,224 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::VAnchorContractWrapper;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use webb::evm::contract::protocol_solidity::VAnchorContractEvents;
+use webb::evm::ethers::prelude::LogMeta;
+use webb::evm::ethers::types;
+use webb_event_watcher_traits::evm::EventHandler;
+use webb_event_watcher_traits::EthersTimeLagClient;
+use webb_proposal_signing_backends::proposal_handler;
+use webb_proposal_signing_backends::queue::{
+    ProposalHash, QueuedAnchorUpdateProposal,
+};
+use webb_proposals::{ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_config::anchor::{resolve_linked_anchors, LinkedAnchorConfig};
+use webb_relayer_store::{DepositPropagationStore, SledStore};
+use webb_relayer_utils::metric;
+
+/// An Executed Proposals Handler that handles the `EdgeAddition` and
+/// `EdgeUpdate` events of the anchor, and marks the anchor update proposals
+/// they come from as executed in the store.
+///
+/// The anchor handler executes an anchor update proposal by updating the edge
+/// of the source anchor, so this confirms the executions on-chain, including
+/// the ones sent by other relayers, rather than trusting our own transaction
+/// queue only.
+#[derive(Clone, Debug)]
+pub struct VAnchorExecutedProposalsHandler {
+    resource_id: ResourceId,
+    linked_anchors: Vec<ResourceId>,
+}
+
+impl VAnchorExecutedProposalsHandler {
+    pub fn new(
+        chain_id: types::U256,
+        address: types::Address,
+        linked_anchors: impl IntoIterator<Item = LinkedAnchorConfig>,
+    ) -> Self {
+        let resource_id = ResourceId::new(
+            TargetSystem::new_contract_address(address.to_fixed_bytes()),
+            TypedChainId::Evm(chain_id.as_u32()),
+        );
+        Self {
+            resource_id,
+            linked_anchors: resolve_linked_anchors(resource_id, linked_anchors),
+        }
+    }
+
+    /// Marks the proposal that set the edge of the linked anchor on
+    /// `src_chain_id` to `root` at `leaf_index` as executed in `tx_hash`.
+    ///
+    /// Returns `true` if the proposal was one of ours, and was not already
+    /// marked as executed.
+    fn mark_executed(
+        &self,
+        store: &SledStore,
+        src_chain_id: u64,
+        leaf_index: u32,
+        root: [u8; 32],
+        tx_hash: types::H256,
+    ) -> webb_relayer_utils::Result<bool> {
+        let src_resource_id = self
+            .linked_anchors
+            .iter()
+            .find(|anchor| anchor.typed_chain_id().chain_id() == src_chain_id);
+        let Some(src_resource_id) = src_resource_id else {
+            tracing::trace!(
+                src_chain_id,
+                "Edge of an anchor that is not linked in the config",
+            );
+            return Ok(false);
+        };
+        let proposal = proposal_handler::evm_anchor_update_proposal(
+            root,
+            leaf_index,
+            self.resource_id,
+            *src_resource_id,
+        );
+        let proposal_data_hash =
+            QueuedAnchorUpdateProposal::new(proposal).full_hash();
+        let executed =
+            store.mark_proposal_execution(proposal_data_hash, tx_hash)?;
+        if executed {
+            let chain_id = self.resource_id.typed_chain_id();
+            tracing::event!(
+                target: webb_relayer_utils::probe::TARGET,
+                tracing::Level::DEBUG,
+                kind = %webb_relayer_utils::probe::Kind::ProposalExecuted,
+                chain_id = %chain_id.underlying_chain_id(),
+                src_chain_id,
+                leaf_index,
+                proposal_data_hash = %hex::encode(proposal_data_hash),
+                %tx_hash,
+            );
+        }
+        Ok(executed)
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for VAnchorExecutedProposalsHandler {
+    type Contract = VAnchorContractWrapper<EthersTimeLagClient>;
+
+    type Events = VAnchorContractEvents;
+
+    type Store = SledStore;
+
+    async fn can_handle_events(
+        &self,
+        (events, _meta): (Self::Events, LogMeta),
+        _wrapper: &Self::Contract,
+    ) -> webb_relayer_utils::Result<bool> {
+        use VAnchorContractEvents::*;
+        let has_event =
+            matches!(events, EdgeAdditionFilter(_) | EdgeUpdateFilter(_));
+        Ok(has_event)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn handle_event(
+        &self,
+        store: Arc<Self::Store>,
+        _wrapper: &Self::Contract,
+        (event, log): (Self::Events, LogMeta),
+        _metrics: Arc<Mutex<metric::Metrics>>,
+    ) -> webb_relayer_utils::Result<()> {
+        use VAnchorContractEvents::*;
+        let (src_chain_id, leaf_index, root) = match event {
+            EdgeAdditionFilter(v) => {
+                (v.chain_id, v.latest_leaf_index, v.merkle_root)
+            }
+            EdgeUpdateFilter(v) => {
+                (v.chain_id, v.latest_leaf_index, v.merkle_root)
+            }
+            _ => return Ok(()),
+        };
+        self.mark_executed(
+            &store,
+            src_chain_id.as_u64(),
+            leaf_index.as_u32(),
+            root.into(),
+            log.transaction_hash,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_relayer_config::evm::EvmLinkedAnchorConfig;
+    use webb_relayer_store::DepositCorrelationId;
+
+    #[test]
+    fn edges_should_mark_our_proposals_executed() {
+        let store = SledStore::temporary().unwrap();
+        let linked = EvmLinkedAnchorConfig {
+            chain_id: 5,
+            address: types::Address::from([1u8; 20]),
+        };
+        let handler = VAnchorExecutedProposalsHandler::new(
+            4.into(),
+            types::Address::from([2u8; 20]),
+            [LinkedAnchorConfig::Evm(linked)],
+        );
+        let src_resource_id = ResourceId::new(
+            TargetSystem::new_contract_address([1u8; 20]),
+            TypedChainId::Evm(5),
+        );
+        let root = [42u8; 32];
+        let proposal = proposal_handler::evm_anchor_update_proposal(
+            root,
+            3,
+            handler.resource_id,
+            src_resource_id,
+        );
+        let id = DepositCorrelationId::new(src_resource_id, 3);
+        store.insert_deposit(id, types::H256::random(), 1).unwrap();
+        store
+            .insert_deposit_proposal(
+                id,
+                handler.resource_id,
+                QueuedAnchorUpdateProposal::new(proposal).full_hash(),
+            )
+            .unwrap();
+
+        let src_chain_id = TypedChainId::Evm(5).chain_id();
+        let tx_hash = types::H256::random();
+        // an anchor we are not linked to, or another root.
+        let unlinked = TypedChainId::Evm(6).chain_id();
+        assert!(!handler
+            .mark_executed(&store, unlinked, 3, root, tx_hash)
+            .unwrap());
+        assert!(!handler
+            .mark_executed(&store, src_chain_id, 3, [7u8; 32], tx_hash)
+            .unwrap());
+
+        assert!(handler
+            .mark_executed(&store, src_chain_id, 3, root, tx_hash)
+            .unwrap());
+        let record = store.get_deposit_propagation(id).unwrap().unwrap();
+        assert!(record.is_fully_propagated());
+        let destination =
+            &record.destinations[&hex::encode(handler.resource_id.to_bytes())];
+        assert_eq!(destination.execution_tx, Some(tx_hash));
+        // the same edge seen again changes nothing.
+        assert!(!handler
+            .mark_executed(&store, src_chain_id, 3, root, tx_hash)
+            .unwrap());
+    }
+}
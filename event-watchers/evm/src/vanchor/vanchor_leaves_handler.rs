@@ -123,7 +123,7 @@ impl EventHandler for VAnchorLeavesHandler {
         store: Arc<Self::Store>,
         wrapper: &Self::Contract,
         (event, log): (Self::Events, LogMeta),
-        _metrics: Arc<Mutex<metric::Metrics>>,
+        metrics: Arc<Mutex<metric::Metrics>>,
     ) -> webb_relayer_utils::Result<()> {
         use VAnchorContractEvents::*;
         let mut batch: BTreeMap<u32, Bn254Fr> = BTreeMap::new();
@@ -143,52 +143,142 @@ impl EventHandler for VAnchorLeavesHandler {
                 let history_store_key =
                     ResourceId::new(target_system, typed_chain_id);
 
-                // 1. We will validate leaf before inserting it into store
-                let leaf: Bn254Fr =
-                    Bn254Fr::from_be_bytes_mod_order(commitment.as_slice());
-                batch.insert(leaf_index, leaf);
-                mt.insert_batch(&batch, &self.hasher)?;
-                // If leaf index is even number then we don't need to verify commitment
-                if event_data.leaf_index.as_u32() % 2 == 0 {
+                // the leaves before it did not show up yet, so the root can
+                // not be checked, the store buffers it until they do.
+                let next_leaf_index =
+                    store.next_leaf_index(history_store_key)?;
+                let is_ahead = leaf_index > next_leaf_index;
+                if is_ahead {
                     tracing::debug!(
-                        leaf_index = leaf_index,
-                        commitment = hex::encode(commitment.as_slice()),
-                        "Verified commitment",
+                        leaf_index,
+                        next_leaf_index,
+                        "Leaf is ahead of the cache, buffering it",
                     );
                 } else {
-                    // We will verify commitment
-                    let root_bytes = mt.root().into_repr().to_bytes_be();
-                    let root = U256::from_big_endian(root_bytes.as_slice());
-                    let is_known_root = wrapper
-                        .contract
-                        .is_known_root(root)
-                        .block(log.block_number)
-                        .call()
-                        .await?;
-
-                    tracing::debug!(
-                        leaf_index = leaf_index,
-                        root = hex::encode(root_bytes.as_slice()),
-                        is_known_root,
-                        "New commitment need to be verified",
-                    );
+                    // 1. We will validate leaf before inserting it into store,
+                    // along with the pending leaves right after it, which it
+                    // unblocks.
+                    let leaf: Bn254Fr =
+                        Bn254Fr::from_be_bytes_mod_order(commitment.as_slice());
+                    batch.insert(leaf_index, leaf);
+                    let pending =
+                        store.get_pending_leaves(history_store_key)?;
+                    let mut root_block = log.block_number;
+                    let mut next = leaf_index + 1;
+                    while let Some((pending_leaf, block)) = pending.get(&next) {
+                        let pending_leaf = Bn254Fr::from_be_bytes_mod_order(
+                            pending_leaf.as_bytes(),
+                        );
+                        batch.insert(next, pending_leaf);
+                        root_block = root_block.max((*block).into());
+                        next += 1;
+                    }
+                    let unblocks_pending = batch.len() > 1;
+                    mt.insert_batch(&batch, &self.hasher)?;
+                    // If leaf index is even number then we don't need to verify commitment,
+                    // unless pending leaves come with it, their root was never checked.
+                    if event_data.leaf_index.as_u32() % 2 == 0
+                        && !unblocks_pending
+                    {
+                        tracing::debug!(
+                            leaf_index = leaf_index,
+                            commitment = hex::encode(commitment.as_slice()),
+                            "Verified commitment",
+                        );
+                    } else {
+                        // We will verify commitment
+                        let root_bytes = mt.root().into_repr().to_bytes_be();
+                        let root = U256::from_big_endian(root_bytes.as_slice());
+                        let is_known_root = wrapper
+                            .contract
+                            .is_known_root(root)
+                            .block(root_block)
+                            .call()
+                            .await?;
 
-                    if !is_known_root {
-                        tracing::warn!(
-                            expected_root = ?root,
-                            "Invalid merkle root. Maybe invalid leaf or commitment"
+                        tracing::debug!(
+                            leaf_index = leaf_index,
+                            root = hex::encode(root_bytes.as_slice()),
+                            is_known_root,
+                            unblocks_pending,
+                            "New commitment need to be verified",
                         );
-                        // Restore previous state of the tree.
-                        mt.tree = mt_snapshot;
-                        return Err(Error::InvalidMerkleRootError(leaf_index));
+
+                        if !is_known_root {
+                            tracing::warn!(
+                                expected_root = ?root,
+                                "Invalid merkle root. Maybe invalid leaf or commitment"
+                            );
+                            // Restore previous state of the tree.
+                            mt.tree = mt_snapshot;
+                            // the pending leaves could be the invalid ones,
+                            // they are fetched again with the missing ones.
+                            if unblocks_pending {
+                                store
+                                    .remove_pending_leaves(history_store_key)?;
+                            }
+                            return Err(Error::InvalidMerkleRootError(
+                                leaf_index,
+                            ));
+                        }
                     }
                 }
                 // 2. We will insert leaf and last deposit block number into store
-                store.insert_leaves_and_last_deposit_block_number(
+                let result = store.insert_leaves_with_max_pending(
                     history_store_key,
                     &[value.clone()],
                     log.block_number.as_u64(),
-                )?;
+                    wrapper.config.events_watcher.max_pending_leaves,
+                );
+                let insertion = match result {
+                    Ok(insertion) => insertion,
+                    Err(e) => {
+                        // Restore previous state of the tree.
+                        mt.tree = mt_snapshot;
+                        if let Error::LeafCacheCorrupted { cached, .. } = &e {
+                            tracing::event!(
+                                target: webb_relayer_utils::probe::TARGET,
+                                tracing::Level::ERROR,
+                                kind = %webb_relayer_utils::probe::Kind::LeafCacheCorrupted,
+                                chain_id = %self.chain_id,
+                                leaf_index,
+                                cached = %cached,
+                                leaf = %hex::encode(commitment),
+                                block_number = %log.block_number,
+                            );
+                        }
+                        return Err(e);
+                    }
+                };
+                if let Some(from) = insertion.missing_from {
+                    tracing::warn!(
+                        leaf_index,
+                        missing_from = from,
+                        max_pending_leaves =
+                            wrapper.config.events_watcher.max_pending_leaves,
+                        "Too many leaves are pending, the missing ones are fetched again",
+                    );
+                }
+                if !insertion.duplicates.is_empty() {
+                    metrics
+                        .lock()
+                        .await
+                        .duplicate_commitments
+                        .inc_by(insertion.duplicates.len() as f64);
+                }
+                // the pending leaves this one unblocked, that were not
+                // checked with it.
+                let unblocked: BTreeMap<u32, Bn254Fr> = insertion
+                    .inserted
+                    .into_iter()
+                    .filter(|(i, _)| !batch.contains_key(i))
+                    .map(|(i, leaf)| {
+                        (i, Bn254Fr::from_be_bytes_mod_order(leaf.as_bytes()))
+                    })
+                    .collect();
+                if !unblocked.is_empty() {
+                    mt.insert_batch(&unblocked, &self.hasher)?;
+                }
                 let events_bytes = serde_json::to_vec(&event_data)?;
                 store.store_event(&events_bytes)?;
                 tracing::trace!(
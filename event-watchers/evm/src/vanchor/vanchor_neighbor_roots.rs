@@ -0,0 +1,170 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use webb::evm::contract::protocol_solidity::VAnchorContract;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types;
+use webb_event_watcher_traits::EthersClient;
+use webb_proposals::{ResourceId, TargetSystem};
+use webb_relayer_context::RelayerContext;
+use webb_relayer_store::{AnchorEdge, EdgeStore};
+
+/// Reads the latest roots a linked anchor knows of its neighbors, to tell
+/// whether it already got updated with a given root.
+#[async_trait::async_trait]
+pub trait NeighborRootsSource {
+    /// Returns the latest neighbor roots of the given anchor.
+    ///
+    /// Anchors that could not be read return no roots, so they always get
+    /// updated.
+    async fn latest_neighbor_roots(
+        &self,
+        anchor: ResourceId,
+    ) -> webb_relayer_utils::Result<Vec<[u8; 32]>>;
+}
+
+/// Reads the edges a linked anchor has of its neighbors, to tell whether
+/// they are stale.
+#[async_trait::async_trait]
+pub trait NeighborEdgesSource {
+    /// Returns the latest edges of the given anchor, one per neighbor
+    /// anchor.
+    async fn latest_neighbor_edges(
+        &self,
+        anchor: ResourceId,
+    ) -> webb_relayer_utils::Result<Vec<AnchorEdge>>;
+}
+
+/// Reads the neighbor roots of the linked VAnchor contracts, using the
+/// providers of the relayer.
+#[derive(Clone)]
+pub struct VAnchorNeighborRoots {
+    ctx: RelayerContext,
+}
+
+impl VAnchorNeighborRoots {
+    /// Creates a new neighbor roots source.
+    pub fn new(ctx: RelayerContext) -> Self {
+        Self { ctx }
+    }
+
+    /// Returns the provider of the chain of the given anchor, which is
+    /// configured under its chain id, without the chain type.
+    async fn evm_provider(
+        &self,
+        anchor: ResourceId,
+    ) -> webb_relayer_utils::Result<Arc<EthersClient>> {
+        let chain_id = anchor.typed_chain_id().underlying_chain_id();
+        self.ctx.evm_provider(chain_id).await
+    }
+}
+
+#[async_trait::async_trait]
+impl NeighborRootsSource for VAnchorNeighborRoots {
+    async fn latest_neighbor_roots(
+        &self,
+        anchor: ResourceId,
+    ) -> webb_relayer_utils::Result<Vec<[u8; 32]>> {
+        match anchor.target_system() {
+            TargetSystem::ContractAddress(address) => {
+                let client = self.evm_provider(anchor).await?;
+                let contract =
+                    VAnchorContract::new(types::Address::from(address), client);
+                let roots = contract.get_latest_neighbor_roots().call().await?;
+                Ok(roots)
+            }
+            TargetSystem::Substrate(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl NeighborEdgesSource for VAnchorNeighborRoots {
+    /// The edges of the anchors on Substrate nodes are the ones recorded by
+    /// the edge watcher of their tree.
+    async fn latest_neighbor_edges(
+        &self,
+        anchor: ResourceId,
+    ) -> webb_relayer_utils::Result<Vec<AnchorEdge>> {
+        match anchor.target_system() {
+            TargetSystem::ContractAddress(address) => {
+                let client = self.evm_provider(anchor).await?;
+                let block_number = client.get_block_number().await?;
+                let contract =
+                    VAnchorContract::new(types::Address::from(address), client);
+                let edges = contract
+                    .get_latest_neighbor_edges()
+                    .block(block_number)
+                    .call()
+                    .await?;
+                Ok(edges
+                    .into_iter()
+                    .map(|edge| AnchorEdge {
+                        src_chain_id: edge.chain_id.as_u64(),
+                        root: types::H256(edge.root),
+                        latest_leaf_index: edge.latest_leaf_index.as_u32(),
+                        block_number: block_number.as_u64(),
+                    })
+                    .collect())
+            }
+            TargetSystem::Substrate(_) => self.ctx.store().get_edges(anchor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webb_proposals::TypedChainId;
+    use webb_relayer_config::evm::EvmChainConfig;
+    use webb_relayer_config::WebbRelayerConfig;
+    use webb_relayer_store::SledStore;
+
+    fn anchor_on(chain: TypedChainId) -> ResourceId {
+        ResourceId::new(TargetSystem::new_contract_address([1u8; 20]), chain)
+    }
+
+    #[tokio::test]
+    async fn providers_should_be_resolved_by_the_underlying_chain_id() {
+        let chain: EvmChainConfig = serde_json::from_value(serde_json::json!({
+            "name": "hermes",
+            "http-endpoint": "http://localhost:5001",
+            "ws-endpoint": "ws://localhost:5001",
+            "chain-id": 5001,
+        }))
+        .unwrap();
+        let mut config = WebbRelayerConfig::default();
+        config.evm.insert(chain.chain_id.to_string(), chain);
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(config, store).unwrap();
+        let source = VAnchorNeighborRoots::new(ctx.clone());
+
+        let provider = source
+            .evm_provider(anchor_on(TypedChainId::Evm(5001)))
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &provider,
+            &ctx.evm_provider(5001).await.unwrap()
+        ));
+        let result = source
+            .evm_provider(anchor_on(TypedChainId::Evm(5002)))
+            .await;
+        assert!(matches!(
+            result,
+            Err(webb_relayer_utils::Error::ChainNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use webb::evm::contract::protocol_solidity::VAnchorContract;
+use webb::evm::ethers::prelude::Middleware;
+use webb_event_watcher_traits::EthersTimeLagClient;
+use webb_proposal_signing_backends::queue::root::{
+    AnchorRootSource, CurrentRoot,
+};
+use webb_proposal_signing_backends::queue::{
+    ProposalHash, QueuedAnchorUpdateProposal,
+};
+use webb_proposals::ProposalTrait;
+use webb_relayer_store::{
+    DepositPropagationStore, ProposalEventKind, ProposalEventStore, SledStore,
+};
+
+/// Reads the current merkle root of a VAnchor contract, used to keep
+/// the root of its queued anchor update proposals fresh.
+#[derive(Clone)]
+pub struct VAnchorRootSource {
+    client: Arc<EthersTimeLagClient>,
+    contract: VAnchorContract<EthersTimeLagClient>,
+    store: Arc<SledStore>,
+}
+
+impl VAnchorRootSource {
+    /// Creates a new root source for the given VAnchor contract.
+    pub fn new(
+        client: Arc<EthersTimeLagClient>,
+        contract: VAnchorContract<EthersTimeLagClient>,
+        store: Arc<SledStore>,
+    ) -> Self {
+        Self {
+            client,
+            contract,
+            store,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AnchorRootSource for VAnchorRootSource {
+    async fn current_root(&self) -> webb_relayer_utils::Result<CurrentRoot> {
+        // read both at the same block, so they describe the same tree.
+        let block = self.client.get_block_number().await?;
+        let merkle_root =
+            self.contract.get_last_root().block(block).call().await?;
+        let next_index = self.contract.next_index().block(block).call().await?;
+        Ok(CurrentRoot {
+            merkle_root,
+            next_index,
+        })
+    }
+
+    async fn on_proposal_refreshed(
+        &self,
+        old_proposal: &QueuedAnchorUpdateProposal,
+        new_proposal: &QueuedAnchorUpdateProposal,
+    ) -> webb_relayer_utils::Result<()> {
+        let old_proposal_hash = old_proposal.full_hash();
+        let new_proposal_hash = new_proposal.full_hash();
+        // keep tracking the deposits of the old proposal.
+        self.store
+            .relink_deposit_proposal(old_proposal_hash, new_proposal_hash)?;
+        self.store.record_proposal(
+            ProposalEventKind::Expired,
+            &old_proposal.to_vec(),
+            old_proposal_hash,
+            None,
+        );
+        self.store.record_proposal(
+            ProposalEventKind::Created,
+            &new_proposal.to_vec(),
+            new_proposal_hash,
+            None,
+        );
+        Ok(())
+    }
+}
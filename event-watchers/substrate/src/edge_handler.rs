@@ -0,0 +1,276 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use webb::evm::ethers::types;
+use webb::substrate::subxt::{self, OnlineClient, PolkadotConfig};
+use webb::substrate::tangle_runtime::api as RuntimeApi;
+use webb::substrate::tangle_runtime::api::linkable_tree_bn254;
+use webb_event_watcher_traits::substrate::EventHandler;
+
+use webb_proposals::{
+    ResourceId, SubstrateTargetSystem, TargetSystem, TypedChainId,
+};
+use webb_relayer_store::sled::SledStore;
+use webb_relayer_store::{AnchorEdge, EdgeStore};
+use webb_relayer_utils::metric;
+
+/// An update of the edge of a tree to one of its neighbor anchors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeUpdate {
+    /// Whether the edge got added by this update, when the neighbor anchor
+    /// got linked to the tree.
+    pub added: bool,
+    /// The tree the edge belongs to.
+    pub tree_id: u32,
+    /// The typed chain id of the neighbor anchor.
+    pub src_chain_id: u64,
+    /// The merkle root of the neighbor anchor.
+    pub root: [u8; 32],
+    /// The index of the latest leaf of the neighbor anchor behind that root.
+    pub latest_leaf_index: u32,
+}
+
+impl EdgeUpdate {
+    /// Decodes the given event, returns `None` if it does not add or update
+    /// an edge.
+    pub fn decode(
+        event: &subxt::events::EventDetails,
+    ) -> webb_relayer_utils::Result<Option<Self>> {
+        let added = event
+            .as_event::<linkable_tree_bn254::events::EdgeAdded>()?
+            .map(|e| Self {
+                added: true,
+                tree_id: e.tree_id,
+                src_chain_id: e.chain_id,
+                root: e.merkle_root.0,
+                latest_leaf_index: e.latest_leaf_index,
+            });
+        if added.is_some() {
+            return Ok(added);
+        }
+        let update = event
+            .as_event::<linkable_tree_bn254::events::EdgeUpdated>()?
+            .map(|e| Self {
+                added: false,
+                tree_id: e.tree_id,
+                src_chain_id: e.chain_id,
+                root: e.merkle_root.0,
+                latest_leaf_index: e.latest_leaf_index,
+            });
+        Ok(update)
+    }
+
+    /// Converts the update into the edge to store, as of `block_number`.
+    pub fn into_edge(self, block_number: u64) -> AnchorEdge {
+        AnchorEdge {
+            src_chain_id: self.src_chain_id,
+            root: types::H256(self.root),
+            latest_leaf_index: self.latest_leaf_index,
+            block_number,
+        }
+    }
+}
+
+/// Stores the given edge updates, keyed by the tree they belong to.
+///
+/// Returns the number of updates stored.
+pub fn store_edge_updates<S, I>(
+    store: &S,
+    chain_id: u32,
+    pallet_index: u8,
+    updates: I,
+    block_number: u64,
+) -> webb_relayer_utils::Result<usize>
+where
+    S: EdgeStore,
+    I: IntoIterator<Item = EdgeUpdate>,
+{
+    let mut count = 0;
+    for update in updates {
+        let target = SubstrateTargetSystem::builder()
+            .pallet_index(pallet_index)
+            .tree_id(update.tree_id)
+            .build();
+        let key = ResourceId::new(
+            TargetSystem::Substrate(target),
+            TypedChainId::Substrate(chain_id),
+        );
+        tracing::event!(
+            target: webb_relayer_utils::probe::TARGET,
+            tracing::Level::DEBUG,
+            kind = %webb_relayer_utils::probe::Kind::EdgesStore,
+            chain_id = %chain_id,
+            tree_id = %update.tree_id,
+            src_chain_id = %update.src_chain_id,
+            latest_leaf_index = %update.latest_leaf_index,
+            added = %update.added,
+            block_number = %block_number
+        );
+        if update.added {
+            tracing::info!(
+                tree_id = %update.tree_id,
+                src_chain_id = %update.src_chain_id,
+                "New edge added to the tree",
+            );
+        }
+        store.insert_edge(key, update.into_edge(block_number))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// A Substrate Edge Handler that watches for `EdgeAdded` and `EdgeUpdated`
+/// events of the linkable tree pallet and saves the neighbor roots of every
+/// tree to the store.
+#[derive(Clone, Debug, Default)]
+pub struct SubstrateEdgeHandler;
+
+#[async_trait::async_trait]
+impl EventHandler<PolkadotConfig> for SubstrateEdgeHandler {
+    type Client = OnlineClient<PolkadotConfig>;
+    type Store = SledStore;
+    async fn can_handle_events(
+        &self,
+        events: subxt::events::Events<PolkadotConfig>,
+    ) -> webb_relayer_utils::Result<bool> {
+        let has_event = events
+            .has::<linkable_tree_bn254::events::EdgeAdded>()?
+            || events.has::<linkable_tree_bn254::events::EdgeUpdated>()?;
+        Ok(has_event)
+    }
+
+    async fn handle_events(
+        &self,
+        store: Arc<Self::Store>,
+        api: Arc<Self::Client>,
+        (events, block_number): (subxt::events::Events<PolkadotConfig>, u64),
+        metrics: Arc<Mutex<metric::Metrics>>,
+    ) -> webb_relayer_utils::Result<()> {
+        let mut updates = Vec::new();
+        for event in events.iter() {
+            if let Some(update) = EdgeUpdate::decode(&event?)? {
+                updates.push(update);
+            }
+        }
+        // fetch chain_id
+        let chain_id_addr = RuntimeApi::constants()
+            .linkable_tree_bn254()
+            .chain_identifier();
+        let chain_id = api.constants().at(&chain_id_addr)?;
+        // the edges are keyed like the leaves, so they could be served
+        // for the same resource id.
+        let pallet_index = {
+            let metadata = api.metadata();
+            let pallet = metadata.pallet("VAnchorHandlerBn254")?;
+            pallet.index()
+        };
+        let additions = updates.iter().filter(|update| update.added).count();
+        store_edge_updates(
+            store.as_ref(),
+            chain_id as u32,
+            pallet_index,
+            updates,
+            block_number,
+        )?;
+        metrics
+            .lock()
+            .await
+            .edge_additions_processed
+            .inc_by(additions as f64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(src_chain_id: u64, latest_leaf_index: u32) -> EdgeUpdate {
+        EdgeUpdate {
+            added: false,
+            tree_id: 3,
+            src_chain_id,
+            root: [latest_leaf_index as u8; 32],
+            latest_leaf_index,
+        }
+    }
+
+    #[test]
+    fn edge_updates_should_be_stored_per_tree() {
+        let store = SledStore::temporary().unwrap();
+        let updates = vec![
+            update(5, 1),
+            update(2, 4),
+            update(5, 2),
+            EdgeUpdate {
+                tree_id: 4,
+                ..update(5, 9)
+            },
+        ];
+        let stored = store_edge_updates(&store, 1080, 44, updates, 100);
+        assert_eq!(stored.unwrap(), 4);
+
+        let key = |tree_id| {
+            let target = SubstrateTargetSystem::builder()
+                .pallet_index(44)
+                .tree_id(tree_id)
+                .build();
+            ResourceId::new(
+                TargetSystem::Substrate(target),
+                TypedChainId::Substrate(1080),
+            )
+        };
+        assert_eq!(
+            store.get_edges(key(3)).unwrap(),
+            vec![update(2, 4).into_edge(100), update(5, 2).into_edge(100)]
+        );
+        assert_eq!(
+            store.get_edges(key(4)).unwrap(),
+            vec![update(5, 9).into_edge(100)]
+        );
+    }
+
+    #[test]
+    fn added_edges_should_be_updated_later() {
+        let store = SledStore::temporary().unwrap();
+        let added = EdgeUpdate {
+            added: true,
+            ..update(5, 0)
+        };
+        let stored = store_edge_updates(&store, 1080, 44, vec![added], 100);
+        assert_eq!(stored.unwrap(), 1);
+
+        let target = SubstrateTargetSystem::builder()
+            .pallet_index(44)
+            .tree_id(3)
+            .build();
+        let key = ResourceId::new(
+            TargetSystem::Substrate(target),
+            TypedChainId::Substrate(1080),
+        );
+        assert_eq!(
+            store.get_edges(key).unwrap(),
+            vec![update(5, 0).into_edge(100)]
+        );
+
+        store_edge_updates(&store, 1080, 44, vec![update(5, 3)], 120).unwrap();
+        assert_eq!(
+            store.get_edges(key).unwrap(),
+            vec![update(5, 3).into_edge(120)]
+        );
+    }
+}
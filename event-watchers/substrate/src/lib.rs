@@ -12,14 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod edge_handler;
 mod signature_bridge_watcher;
+mod tree_filter;
 mod vanchor_deposit_handler;
 mod vanchor_encrypted_output_handler;
 mod vanchor_leaves_handler;
 
+#[doc(hidden)]
+pub use edge_handler::*;
 #[doc(hidden)]
 pub use signature_bridge_watcher::*;
 #[doc(hidden)]
+pub use tree_filter::*;
+#[doc(hidden)]
 pub use vanchor_deposit_handler::*;
 #[doc(hidden)]
 pub use vanchor_encrypted_output_handler::*;
@@ -28,6 +34,7 @@ pub use vanchor_leaves_handler::*;
 use webb::substrate::subxt::events::StaticEvent;
 use webb::substrate::{
     subxt::PolkadotConfig,
+    tangle_runtime::api::linkable_tree_bn254::events::EdgeUpdated,
     tangle_runtime::api::v_anchor_bn254::events::Transaction,
 };
 use webb_event_watcher_traits::SubstrateEventWatcher;
@@ -44,3 +51,17 @@ impl SubstrateEventWatcher<PolkadotConfig> for SubstrateVAnchorEventWatcher {
 
     type Store = SledStore;
 }
+
+/// Watches the edges added to and updated in the linkable tree pallet, see
+/// [`SubstrateEdgeHandler`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SubstrateEdgeWatcher;
+
+#[async_trait::async_trait]
+impl SubstrateEventWatcher<PolkadotConfig> for SubstrateEdgeWatcher {
+    const TAG: &'static str = "Substrate Edge Watcher";
+
+    const PALLET_NAME: &'static str = EdgeUpdated::PALLET;
+
+    type Store = SledStore;
+}
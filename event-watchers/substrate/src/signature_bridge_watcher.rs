@@ -26,7 +26,9 @@ use webb_event_watcher_traits::substrate::{
 };
 use webb_event_watcher_traits::SubstrateEventWatcher;
 use webb_relayer_store::sled::{SledQueueKey, SledStore};
-use webb_relayer_store::{BridgeCommand, QueueStore};
+use webb_relayer_store::{
+    compute_proposal_hash, BridgeCommand, DepositPropagationStore, QueueStore,
+};
 
 use webb::evm::ethers::utils;
 use webb::substrate::tangle_runtime::api as RuntimeApi;
@@ -209,6 +211,7 @@ where
         );
 
         let typed_chain_id = webb_proposals::TypedChainId::Substrate(chain_id);
+        let proposal_data_hash = compute_proposal_hash(&proposal_data);
 
         let execute_proposal_tx =
             RuntimeApi::tx().signature_bridge().execute_proposal(
@@ -225,6 +228,9 @@ where
             make_execute_proposal_key(data_hash),
         );
         let tx = TypeErasedStaticTxPayload::try_from(execute_proposal_tx)?;
+        // link the call back to the proposal, so once the tx queue executes it
+        // the originating deposit (if any) gets marked as propagated.
+        store.link_execution_call(data_hash, proposal_data_hash)?;
         QueueStore::enqueue_item(&store, tx_key, tx)?;
         tracing::debug!(
             data_hash = ?hex::encode(data_hash),
@@ -0,0 +1,49 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+/// The merkle trees of the VAnchor pallet a handler takes care of, see
+/// `tree-ids` in the pallet config.
+///
+/// By default, every tree is handled.
+#[derive(Clone, Debug, Default)]
+pub struct TreeFilter(Option<HashSet<u32>>);
+
+impl TreeFilter {
+    /// Only handles the given trees, or all of them if `None`.
+    pub fn new(tree_ids: Option<Vec<u32>>) -> Self {
+        Self(tree_ids.map(|ids| ids.into_iter().collect()))
+    }
+
+    /// Returns true if the events of the given tree should be handled.
+    pub fn contains(&self, tree_id: u32) -> bool {
+        self.0.as_ref().map_or(true, |ids| ids.contains(&tree_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_configured_trees_should_be_handled() {
+        let all = TreeFilter::default();
+        assert!(all.contains(3) && all.contains(7));
+
+        let some = TreeFilter::new(Some(vec![3, 5]));
+        assert!(some.contains(3) && some.contains(5));
+        assert!(!some.contains(7));
+    }
+}
@@ -25,15 +25,18 @@ use webb_event_watcher_traits::substrate::EventHandler;
 use webb_proposal_signing_backends::{
     proposal_handler, ProposalSigningBackend,
 };
-use webb_relayer_config::anchor::LinkedAnchorConfig;
+use webb_relayer_config::anchor::{resolve_linked_anchors, LinkedAnchorConfig};
 use webb_relayer_store::EventHashStore;
 use webb_relayer_store::SledStore;
 use webb_relayer_utils::metric;
+
+use crate::TreeFilter;
 /// SubstrateVAnchorDeposit handler handles `Transaction` event and creates `AnchorUpdate` proposals for linked anchors.
 pub struct SubstrateVAnchorDepositHandler<B, C> {
     proposal_signing_backend: B,
     bridge_registry_backend: C,
     linked_anchors: Option<Vec<LinkedAnchorConfig>>,
+    trees: TreeFilter,
 }
 
 impl<B, C> SubstrateVAnchorDepositHandler<B, C>
@@ -45,11 +48,13 @@ where
         proposal_signing_backend: B,
         bridge_registry_backend: C,
         linked_anchors: Option<Vec<LinkedAnchorConfig>>,
+        trees: TreeFilter,
     ) -> Self {
         Self {
             proposal_signing_backend,
             bridge_registry_backend,
             linked_anchors,
+            trees,
         }
     }
 }
@@ -90,6 +95,7 @@ where
         let transaction_events = events
             .find::<v_anchor_bn254::events::Transaction>()
             .flatten()
+            .filter(|event| self.trees.contains(event.tree_id))
             .collect::<Vec<_>>();
         for event in transaction_events {
             // fetch tree
@@ -142,14 +148,9 @@ where
                 .await?;
 
             // update linked anchors
-            for linked_anchor in linked_anchors {
-                let target_resource_id = match linked_anchor {
-                    LinkedAnchorConfig::Raw(target) => {
-                        let bytes: [u8; 32] = target.resource_id.into();
-                        webb_proposals::ResourceId::from(bytes)
-                    }
-                    _ => unreachable!("unsupported"),
-                };
+            let targets =
+                resolve_linked_anchors(src_resource_id, linked_anchors);
+            for target_resource_id in targets {
                 // Proposal proposed metric
                 metrics.lock().await.anchor_update_proposals.inc();
                 match target_resource_id.target_system() {
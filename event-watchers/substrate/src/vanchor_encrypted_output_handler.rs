@@ -26,10 +26,21 @@ use webb_proposals::{
 use webb_relayer_store::sled::SledStore;
 use webb_relayer_store::EncryptedOutputCacheStore;
 use webb_relayer_utils::{metric, Error};
+
+use crate::TreeFilter;
 // An Substrate VAnchor encrypted output Watcher that watches for Deposit events and save the encrypted output to the store.
 /// It serves as a cache for encrypted outputs that could be used by dApp.
 #[derive(Clone, Debug, Default)]
-pub struct SubstrateVAnchorEncryptedOutputHandler;
+pub struct SubstrateVAnchorEncryptedOutputHandler {
+    trees: TreeFilter,
+}
+
+impl SubstrateVAnchorEncryptedOutputHandler {
+    /// Creates an encrypted output handler for the given trees only.
+    pub fn new(trees: TreeFilter) -> Self {
+        Self { trees }
+    }
+}
 
 #[async_trait::async_trait]
 impl EventHandler<PolkadotConfig> for SubstrateVAnchorEncryptedOutputHandler {
@@ -56,6 +67,7 @@ impl EventHandler<PolkadotConfig> for SubstrateVAnchorEncryptedOutputHandler {
         let transaction_events = events
             .find::<v_anchor_bn254::events::Transaction>()
             .flatten()
+            .filter(|event| self.trees.contains(event.tree_id))
             .collect::<Vec<_>>();
         for event in transaction_events {
             // fetch leaf_index from merkle tree at given block_number
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -26,10 +27,96 @@ use webb_proposals::{
 use webb_relayer_store::sled::SledStore;
 use webb_relayer_store::LeafCacheStore;
 use webb_relayer_utils::{metric, Error};
-// An Substrate VAnchor Leaves Watcher that watches for Deposit events and save the leaves to the store.
+
+use crate::TreeFilter;
+
+/// A VAnchor pallet event that inserts leaves in a merkle tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VAnchorLeafEvent {
+    /// A transaction, inserting its output commitments.
+    Transaction {
+        /// The tree the leaves got inserted in.
+        tree_id: u32,
+        /// The inserted leaves, in order.
+        leaves: Vec<[u8; 32]>,
+    },
+    /// A plain deposit, inserting a single leaf.
+    Deposit {
+        /// The tree the leaf got inserted in.
+        tree_id: u32,
+        /// The inserted leaf.
+        leaf: [u8; 32],
+    },
+}
+
+impl VAnchorLeafEvent {
+    /// Decodes the given event, returns `None` if it does not insert leaves.
+    pub fn decode(
+        event: &subxt::events::EventDetails,
+    ) -> webb_relayer_utils::Result<Option<Self>> {
+        if let Some(e) =
+            event.as_event::<v_anchor_bn254::events::Transaction>()?
+        {
+            return Ok(Some(Self::Transaction {
+                tree_id: e.tree_id,
+                leaves: e.leafs.into_iter().map(|leaf| leaf.0).collect(),
+            }));
+        }
+        if let Some(e) = event.as_event::<v_anchor_bn254::events::Deposit>()? {
+            return Ok(Some(Self::Deposit {
+                tree_id: e.tree_id,
+                leaf: e.leaf.0,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// Groups the leaves inserted by the given events per tree, keeping the
+/// order in which they got inserted.
+pub fn leaves_by_tree<I>(events: I) -> BTreeMap<u32, Vec<[u8; 32]>>
+where
+    I: IntoIterator<Item = VAnchorLeafEvent>,
+{
+    let mut trees = BTreeMap::<u32, Vec<[u8; 32]>>::new();
+    for event in events {
+        match event {
+            VAnchorLeafEvent::Transaction { tree_id, leaves } => {
+                trees.entry(tree_id).or_default().extend(leaves)
+            }
+            VAnchorLeafEvent::Deposit { tree_id, leaf } => {
+                trees.entry(tree_id).or_default().push(leaf)
+            }
+        }
+    }
+    trees
+}
+
+/// Assigns their index to the leaves inserted in a tree during a block,
+/// given the `next_leaf_index` of the tree at the end of that block.
+pub fn index_leaves(
+    next_leaf_index: u32,
+    leaves: &[[u8; 32]],
+) -> Vec<(u32, Vec<u8>)> {
+    let first = next_leaf_index.saturating_sub(leaves.len() as u32);
+    (first..)
+        .zip(leaves.iter().map(|leaf| leaf.to_vec()))
+        .collect()
+}
+
+// An Substrate VAnchor Leaves Watcher that watches for Transaction and Deposit events and save the leaves to the store.
 /// It serves as a cache for leaves that could be used by dApp for proof generation.
 #[derive(Clone, Debug, Default)]
-pub struct SubstrateVAnchorLeavesHandler;
+pub struct SubstrateVAnchorLeavesHandler {
+    trees: TreeFilter,
+}
+
+impl SubstrateVAnchorLeavesHandler {
+    /// Creates a leaves handler for the given trees only.
+    pub fn new(trees: TreeFilter) -> Self {
+        Self { trees }
+    }
+}
 
 #[async_trait::async_trait]
 impl EventHandler<PolkadotConfig> for SubstrateVAnchorLeavesHandler {
@@ -39,7 +126,8 @@ impl EventHandler<PolkadotConfig> for SubstrateVAnchorLeavesHandler {
         &self,
         events: subxt::events::Events<PolkadotConfig>,
     ) -> webb_relayer_utils::Result<bool> {
-        let has_event = events.has::<v_anchor_bn254::events::Transaction>()?;
+        let has_event = events.has::<v_anchor_bn254::events::Transaction>()?
+            || events.has::<v_anchor_bn254::events::Deposit>()?;
         Ok(has_event)
     }
 
@@ -48,18 +136,35 @@ impl EventHandler<PolkadotConfig> for SubstrateVAnchorLeavesHandler {
         store: Arc<Self::Store>,
         api: Arc<Self::Client>,
         (events, block_number): (subxt::events::Events<PolkadotConfig>, u64),
-        _metrics: Arc<Mutex<metric::Metrics>>,
+        metrics: Arc<Mutex<metric::Metrics>>,
     ) -> webb_relayer_utils::Result<()> {
         let at_hash = events.block_hash();
-        let transaction_events = events
-            .find::<v_anchor_bn254::events::Transaction>()
-            .flatten()
-            .collect::<Vec<_>>();
-        for event in transaction_events {
+        let mut leaf_events = Vec::new();
+        for event in events.iter() {
+            if let Some(event) = VAnchorLeafEvent::decode(&event?)? {
+                leaf_events.push(event);
+            }
+        }
+        // fetch chain_id
+        let chain_id_addr = RuntimeApi::constants()
+            .linkable_tree_bn254()
+            .chain_identifier();
+        let chain_id = api.constants().at(&chain_id_addr)?;
+        // pallet index
+        let pallet_index = {
+            let metadata = api.metadata();
+            let pallet = metadata.pallet("VAnchorHandlerBn254")?;
+            pallet.index()
+        };
+        let src_chain_id = TypedChainId::Substrate(chain_id as u32);
+        let trees = leaves_by_tree(leaf_events)
+            .into_iter()
+            .filter(|(tree_id, _)| self.trees.contains(*tree_id));
+        for (tree_id, leaves) in trees {
             // fetch leaf_index from merkle tree at given block_number
             let next_leaf_index_addr = RuntimeApi::storage()
                 .merkle_tree_bn254()
-                .next_leaf_index(event.tree_id);
+                .next_leaf_index(tree_id);
             let next_leaf_index = api
                 .storage()
                 .at(Some(at_hash))
@@ -67,52 +172,389 @@ impl EventHandler<PolkadotConfig> for SubstrateVAnchorLeavesHandler {
                 .fetch(&next_leaf_index_addr)
                 .await?
                 .ok_or(Error::ReadSubstrateStorageError)?;
-            // fetch chain_id
-            let chain_id_addr = RuntimeApi::constants()
-                .linkable_tree_bn254()
-                .chain_identifier();
-            let chain_id = api.constants().at(&chain_id_addr)?;
-            let tree_id = event.tree_id.to_string();
-            let leaf_count = event.leafs.len();
-
-            // pallet index
-            let pallet_index = {
-                let metadata = api.metadata();
-                let pallet = metadata.pallet("VAnchorHandlerBn254")?;
-                pallet.index()
-            };
-            let src_chain_id = TypedChainId::Substrate(chain_id as u32);
             let target = SubstrateTargetSystem::builder()
                 .pallet_index(pallet_index)
-                .tree_id(event.tree_id)
+                .tree_id(tree_id)
                 .build();
             let src_target_system = TargetSystem::Substrate(target);
             let history_store_key =
                 ResourceId::new(src_target_system, src_chain_id);
-            let mut leaf_index =
-                next_leaf_index.saturating_sub(leaf_count as u32);
-            let mut leaf_store = Vec::with_capacity(leaf_count);
-            for leaf in event.leafs {
-                let value = (leaf_index, leaf.0.to_vec());
-                store.insert_leaves_and_last_deposit_block_number(
-                    history_store_key,
-                    &[value],
-                    block_number,
-                )?;
-                leaf_index += 1;
-                leaf_store.push(leaf.0);
+            // all the leaves of the block are inserted before the tree
+            // reaches `next_leaf_index`, in the order of their events.
+            let indexed_leaves = index_leaves(next_leaf_index, &leaves);
+            let insertion = store.insert_leaves_and_last_deposit_block_number(
+                history_store_key,
+                &indexed_leaves,
+                block_number,
+            )?;
+            if !insertion.duplicates.is_empty() {
+                metrics
+                    .lock()
+                    .await
+                    .duplicate_commitments
+                    .inc_by(insertion.duplicates.len() as f64);
             }
             tracing::event!(
                 target: webb_relayer_utils::probe::TARGET,
                 tracing::Level::DEBUG,
                 kind = %webb_relayer_utils::probe::Kind::LeavesStore,
                 chain_id = %chain_id,
-                leaf_index = leaf_index,
-                leafs = %format!("{leaf_store:?}"),
+                leaf_index = next_leaf_index,
+                leafs = %format!("{leaves:?}"),
                 tree_id = %tree_id,
                 block_number = %block_number
             );
+
+            // a reconnection may have left holes in the cached leaves since
+            // the last one cached without any gap, fill them from the merkle
+            // tree itself.
+            backfill_recent_leaves(
+                &store,
+                &StorageLeaves::new(&api, Some(at_hash)),
+                history_store_key,
+                tree_id,
+                next_leaf_index,
+                &metrics,
+            )
+            .await?;
         }
         Ok(())
     }
 }
+
+/// Where the leaves of the merkle trees are read from, to fill the holes of
+/// the leaf cache.
+#[async_trait::async_trait]
+pub trait LeavesSource {
+    /// The number of leaves inserted in the tree.
+    async fn next_leaf_index(
+        &self,
+        tree_id: u32,
+    ) -> webb_relayer_utils::Result<u32>;
+    /// The leaf of the tree at the given index.
+    async fn leaf(
+        &self,
+        tree_id: u32,
+        leaf_index: u32,
+    ) -> webb_relayer_utils::Result<[u8; 32]>;
+}
+
+/// Reads the leaves from the storage of the merkle tree pallet, at the given
+/// block or at the latest one.
+pub struct StorageLeaves<'a> {
+    api: &'a OnlineClient<PolkadotConfig>,
+    at: Option<<PolkadotConfig as subxt::Config>::Hash>,
+}
+
+impl<'a> StorageLeaves<'a> {
+    /// Reads the storage at the given block, or at the latest one if `None`.
+    pub fn new(
+        api: &'a OnlineClient<PolkadotConfig>,
+        at: Option<<PolkadotConfig as subxt::Config>::Hash>,
+    ) -> Self {
+        Self { api, at }
+    }
+}
+
+#[async_trait::async_trait]
+impl LeavesSource for StorageLeaves<'_> {
+    async fn next_leaf_index(
+        &self,
+        tree_id: u32,
+    ) -> webb_relayer_utils::Result<u32> {
+        let next_leaf_index_addr = RuntimeApi::storage()
+            .merkle_tree_bn254()
+            .next_leaf_index(tree_id);
+        let next_leaf_index = self
+            .api
+            .storage()
+            .at(self.at)
+            .await?
+            .fetch(&next_leaf_index_addr)
+            .await?
+            .ok_or(Error::ReadSubstrateStorageError)?;
+        Ok(next_leaf_index)
+    }
+
+    async fn leaf(
+        &self,
+        tree_id: u32,
+        leaf_index: u32,
+    ) -> webb_relayer_utils::Result<[u8; 32]> {
+        let leaf_addr = RuntimeApi::storage()
+            .merkle_tree_bn254()
+            .leaves(tree_id, leaf_index);
+        let leaf = self
+            .api
+            .storage()
+            .at(self.at)
+            .await?
+            .fetch(&leaf_addr)
+            .await?
+            .ok_or(Error::ReadSubstrateStorageError)?;
+        Ok(leaf.0)
+    }
+}
+
+/// The leaf cache key of the given tree of the VAnchor pallet.
+pub fn tree_history_store_key(
+    api: &OnlineClient<PolkadotConfig>,
+    tree_id: u32,
+) -> webb_relayer_utils::Result<ResourceId> {
+    let chain_id_addr = RuntimeApi::constants()
+        .linkable_tree_bn254()
+        .chain_identifier();
+    let chain_id = api.constants().at(&chain_id_addr)?;
+    let pallet_index = api.metadata().pallet("VAnchorHandlerBn254")?.index();
+    let target = SubstrateTargetSystem::builder()
+        .pallet_index(pallet_index)
+        .tree_id(tree_id)
+        .build();
+    Ok(ResourceId::new(
+        TargetSystem::Substrate(target),
+        TypedChainId::Substrate(chain_id as u32),
+    ))
+}
+
+/// Makes sure the leaf cache of the tree holds every leaf the tree has on
+/// chain, fetching the missing ones, such as after a long disconnection.
+///
+/// Returns the number of leaves that were missing.
+pub async fn reconcile_leaves<S: LeavesSource + Sync>(
+    store: &SledStore,
+    source: &S,
+    history_store_key: ResourceId,
+    tree_id: u32,
+    metrics: &Mutex<metric::Metrics>,
+) -> webb_relayer_utils::Result<usize> {
+    let next_leaf_index = source.next_leaf_index(tree_id).await?;
+    let missing =
+        store.get_missing_leaf_indices(history_store_key, next_leaf_index)?;
+    backfill_leaves(
+        store,
+        source,
+        history_store_key,
+        tree_id,
+        next_leaf_index,
+        missing,
+        metrics,
+    )
+    .await
+}
+
+/// Fetches the leaves in `0..next_leaf_index` that are missing from the leaf
+/// cache of the tree after the last leaf cached without any gap.
+///
+/// Unlike [`reconcile_leaves`], the cached leaves are not scanned, so this
+/// only takes time for the leaves that are missing.
+async fn backfill_recent_leaves<S: LeavesSource + Sync>(
+    store: &SledStore,
+    source: &S,
+    history_store_key: ResourceId,
+    tree_id: u32,
+    next_leaf_index: u32,
+    metrics: &Mutex<metric::Metrics>,
+) -> webb_relayer_utils::Result<usize> {
+    let cached = store.next_leaf_index(history_store_key)?;
+    if cached >= next_leaf_index {
+        return Ok(0);
+    }
+    // the pending leaves are not missing, only the leaves before them.
+    let pending = store.get_pending_leaves(history_store_key)?;
+    let missing = (cached..next_leaf_index)
+        .filter(|index| !pending.contains_key(index))
+        .collect();
+    backfill_leaves(
+        store,
+        source,
+        history_store_key,
+        tree_id,
+        next_leaf_index,
+        missing,
+        metrics,
+    )
+    .await
+}
+
+/// Fetches the `missing` leaves of the tree into its leaf cache.
+async fn backfill_leaves<S: LeavesSource + Sync>(
+    store: &SledStore,
+    source: &S,
+    history_store_key: ResourceId,
+    tree_id: u32,
+    next_leaf_index: u32,
+    missing: Vec<u32>,
+    metrics: &Mutex<metric::Metrics>,
+) -> webb_relayer_utils::Result<usize> {
+    if missing.is_empty() {
+        return Ok(0);
+    }
+    tracing::warn!(
+        ?missing,
+        %tree_id,
+        next_leaf_index,
+        "Found gaps in the cached leaf indices, backfilling them",
+    );
+    let mut backfilled = Vec::with_capacity(missing.len());
+    for index in missing {
+        let leaf = source.leaf(tree_id, index).await?;
+        backfilled.push((index, leaf.to_vec()));
+    }
+    // keep the last deposit block as is, the leaves are older than it.
+    let last_block = store.get_last_deposit_block_number(history_store_key)?;
+    store.insert_leaves_and_last_deposit_block_number(
+        history_store_key,
+        &backfilled,
+        last_block,
+    )?;
+    let metrics = metrics.lock().await;
+    metrics.leaf_index_gaps_detected.inc();
+    metrics.leaves_reconciled.inc_by(backfilled.len() as f64);
+    Ok(backfilled.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A merkle tree with the given leaves.
+    struct MockedLeaves(Vec<[u8; 32]>);
+
+    #[async_trait::async_trait]
+    impl LeavesSource for MockedLeaves {
+        async fn next_leaf_index(
+            &self,
+            _tree_id: u32,
+        ) -> webb_relayer_utils::Result<u32> {
+            Ok(self.0.len() as u32)
+        }
+
+        async fn leaf(
+            &self,
+            _tree_id: u32,
+            leaf_index: u32,
+        ) -> webb_relayer_utils::Result<[u8; 32]> {
+            Ok(self.0[leaf_index as usize])
+        }
+    }
+
+    #[tokio::test]
+    async fn reconciliation_should_fetch_the_missing_leaves() {
+        let store = SledStore::temporary().unwrap();
+        let tree: Vec<[u8; 32]> = (0..7).map(|i| [i; 32]).collect();
+        let key = ResourceId::new(
+            TargetSystem::Substrate(
+                SubstrateTargetSystem::builder()
+                    .pallet_index(44)
+                    .tree_id(5)
+                    .build(),
+            ),
+            TypedChainId::Substrate(1080),
+        );
+        // 5 of the 7 leaves are cached, 2 and 5 got lost.
+        let cached: Vec<(u32, Vec<u8>)> = [0, 1, 3, 4, 6]
+            .into_iter()
+            .map(|i| (i, tree[i as usize].to_vec()))
+            .collect();
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &cached, 42)
+            .unwrap();
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+
+        let reconciled = reconcile_leaves(
+            &store,
+            &MockedLeaves(tree.clone()),
+            key,
+            5,
+            &metrics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reconciled, 2);
+        let leaves = store.get_leaves(key).unwrap();
+        assert_eq!(leaves.len(), 7);
+        for (index, leaf) in leaves {
+            assert_eq!(leaf.0, tree[index as usize]);
+        }
+        assert_eq!(store.get_last_deposit_block_number(key).unwrap(), 42);
+
+        // nothing left to do.
+        let reconciled =
+            reconcile_leaves(&store, &MockedLeaves(tree), key, 5, &metrics)
+                .await
+                .unwrap();
+        assert_eq!(reconciled, 0);
+    }
+
+    #[tokio::test]
+    async fn new_leaves_should_only_fetch_the_leaves_after_the_cache() {
+        let store = SledStore::temporary().unwrap();
+        let tree: Vec<[u8; 32]> = (0..6).map(|i| [i; 32]).collect();
+        let key = ResourceId::new(
+            TargetSystem::Substrate(
+                SubstrateTargetSystem::builder()
+                    .pallet_index(44)
+                    .tree_id(5)
+                    .build(),
+            ),
+            TypedChainId::Substrate(1080),
+        );
+        // the leaves 2 and 3 got lost, 4 waits for them.
+        let cached: Vec<(u32, Vec<u8>)> = [0, 1, 4]
+            .into_iter()
+            .map(|i| (i, tree[i as usize].to_vec()))
+            .collect();
+        store
+            .insert_leaves_and_last_deposit_block_number(key, &cached, 42)
+            .unwrap();
+        let metrics = Mutex::new(metric::Metrics::new().unwrap());
+
+        let backfilled = backfill_recent_leaves(
+            &store,
+            &MockedLeaves(tree.clone()),
+            key,
+            5,
+            6,
+            &metrics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(backfilled, 3, "the leaves 2, 3 and 5");
+        let leaves = store.get_leaves(key).unwrap();
+        assert_eq!(leaves.len(), 6);
+        for (index, leaf) in leaves {
+            assert_eq!(leaf.0, tree[index as usize]);
+        }
+        assert_eq!(store.next_leaf_index(key).unwrap(), 6);
+    }
+
+    #[test]
+    fn deposits_and_transactions_should_be_indexed_in_order() {
+        let events = vec![
+            VAnchorLeafEvent::Transaction {
+                tree_id: 5,
+                leaves: vec![[1; 32], [2; 32]],
+            },
+            VAnchorLeafEvent::Deposit {
+                tree_id: 7,
+                leaf: [9; 32],
+            },
+            VAnchorLeafEvent::Deposit {
+                tree_id: 5,
+                leaf: [3; 32],
+            },
+        ];
+        let trees = leaves_by_tree(events);
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[&5], vec![[1; 32], [2; 32], [3; 32]]);
+        assert_eq!(trees[&7], vec![[9; 32]]);
+
+        // the tree had 10 leaves before the block.
+        assert_eq!(
+            index_leaves(13, &trees[&5]),
+            vec![(10, vec![1; 32]), (11, vec![2; 32]), (12, vec![3; 32])]
+        );
+        // a single deposit takes the last index.
+        assert_eq!(index_leaves(1, &trees[&7]), vec![(0, vec![9; 32])]);
+    }
+}
@@ -23,7 +23,7 @@ use webb_relayer::service;
 use webb_relayer_config::{
     event_watcher::EventsWatcherConfig,
     evm::{
-        CommonContractConfig, Contract, EvmChainConfig,
+        CommonContractConfig, Contract, EvmChainConfig, GasLimitOverride,
         SignatureBridgeContractConfig, VAnchorContractConfig,
     },
     signing_backend::{
@@ -45,10 +45,15 @@ async fn main() -> anyhow::Result<()> {
     // Construct the configuration manually.
     let config = WebbRelayerConfig {
         port: 9955,
+        host: String::from("0.0.0.0"),
+        admin_listener: None,
         features: FeaturesConfig {
             data_query: true,
             private_tx_relay: true,
             governance_relay: true,
+            withdrawals_query: true,
+            proof_preverification: false,
+            relay_deduplication: false,
         },
         evm: HashMap::from([(
             String::from("polygon"),
@@ -70,6 +75,7 @@ async fn main() -> anyhow::Result<()> {
                         common: CommonContractConfig {
                             address: ethereum_types::Address::random(),
                             deployed_at: 69420,
+                            deployment_tx_hash: None,
                         },
                         events_watcher: EventsWatcherConfig {
                             enable_data_query: true,
@@ -78,6 +84,11 @@ async fn main() -> anyhow::Result<()> {
                             max_blocks_per_step: 1000,
                             print_progress_interval: 60_000,
                             sync_blocks_from: None,
+                            event_watcher_start_delay_secs: None,
+                            max_pending_leaves: 1024,
+                            watchdog_timeout: 300_000,
+                            safety_lag: 1,
+                            event_type_allowlist: None,
                         },
                         proposal_signing_backend: Some(
                             ProposalSigningBackendConfig::Mocked(
@@ -89,11 +100,18 @@ async fn main() -> anyhow::Result<()> {
                         ),
                         linked_anchors: None,
                         smart_anchor_updates: Default::default(),
+                        root_history_depth: 30,
+                        skip_if_already_updated: true,
+                        max_amount_per_hour: None,
+                        max_delay: None,
+                        edge_sync_interval_secs: None,
+                        anchor_update_cooldown_secs: None,
                     }),
                     Contract::SignatureBridge(SignatureBridgeContractConfig {
                         common: CommonContractConfig {
                             address: ethereum_types::Address::random(),
                             deployed_at: 69420,
+                            deployment_tx_hash: None,
                         },
                         events_watcher: EventsWatcherConfig {
                             enable_data_query: true,
@@ -102,12 +120,33 @@ async fn main() -> anyhow::Result<()> {
                             max_blocks_per_step: 1000,
                             print_progress_interval: 60_000,
                             sync_blocks_from: None,
+                            event_watcher_start_delay_secs: None,
+                            max_pending_leaves: 1024,
+                            watchdog_timeout: 300_000,
+                            safety_lag: 1,
+                            event_type_allowlist: None,
                         },
+                        gas_limit_overrides: vec![GasLimitOverride {
+                            method: "execute_proposal".to_string(),
+                            gas_limit: 900_000,
+                        }],
+                        gas_limit_padding: 20,
+                        status_confirmations: None,
                     }),
                 ],
                 block_poller: None,
+                contract_abi_override: Default::default(),
                 block_confirmations: 0,
+                leaf_final_confirmations: 12,
                 tx_queue: Default::default(),
+                max_gas_price_gwei: None,
+                gas_price_check_interval_secs: 60,
+                block_time_ms: Some(2_000),
+                provider_interval_ms: None,
+                multicall: None,
+                fee_collector: None,
+                enabled_contracts: None,
+                provider_labels: Default::default(),
             },
         )]),
         ..Default::default()
@@ -85,7 +85,7 @@ pub trait BlockPoller {
     #[tracing::instrument(
         skip_all,
         fields(
-            chain_id = ?client.get_chainid().await,
+            chain_id = tracing::field::Empty,
             tag = %Self::TAG,
         ),
     )]
@@ -107,6 +107,7 @@ pub trait BlockPoller {
                 .map_err(backoff::Error::transient)
                 .await?
                 .as_u32();
+            tracing::Span::current().record("chain_id", chain_id);
             tracing::info!("chain id: {}", chain_id);
             // now we start polling for new events.
             loop {
@@ -65,6 +65,10 @@ pub async fn ignite(
 #[paw::main]
 #[tokio::main]
 async fn main(args: Opts) -> anyhow::Result<()> {
+    if args.version {
+        println!("webb-block-poller {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
     setup_logger(args.verbose, "webb_block_poller")?;
     match dotenv::dotenv() {
         Ok(_) => {
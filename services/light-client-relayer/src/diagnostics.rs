@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use ethereum_types::H256;
+use tokio::signal::unix;
+use webb_relayer_store::{DepositPropagationStore, SledStore};
+
+/// Logs a last-gasp crash report, with the depth of the queues and the
+/// anchor update proposals that were not executed yet.
+///
+/// # Arguments
+///
+/// * `signal` - The name of the signal that is about to stop the relayer
+/// * `store` - The store of the relayer
+pub fn log_crash_report(signal: &str, store: &SledStore) {
+    let metrics = store.export_metrics();
+    let pending_proposals = match store.list_pending_proposals() {
+        Ok(hashes) => hashes.into_iter().map(H256::from).collect(),
+        Err(e) => {
+            tracing::error!("Failed to list the pending proposals: {e}");
+            Vec::new()
+        }
+    };
+    tracing::error!(
+        signal,
+        queue_depths = ?metrics.queue_depth_by_chain,
+        ?pending_proposals,
+        "Received a fatal signal, shutting down",
+    );
+}
+
+/// Writes the store metrics to `out` every time the process receives
+/// `SIGUSR1`, without stopping the relayer.
+///
+/// # Arguments
+///
+/// * `store` - The store of the relayer
+/// * `out` - Where to write the metrics, usually stderr
+pub async fn dump_store_metrics_on_sigusr1<W: Write>(
+    store: SledStore,
+    mut out: W,
+) -> std::io::Result<()> {
+    let mut signal = unix::signal(unix::SignalKind::user_defined1())?;
+    while signal.recv().await.is_some() {
+        writeln!(out, "Store metrics: {:#?}", store.export_metrics())?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A writer shared with the test, standing in for stderr.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sigusr1_should_dump_the_store_metrics() {
+        let store = SledStore::temporary().unwrap();
+        let out = SharedBuffer::default();
+        let task =
+            tokio::spawn(dump_store_metrics_on_sigusr1(store, out.clone()));
+        // let the task register its signal handler.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let status = std::process::Command::new("kill")
+            .args(["-USR1", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut dump = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            dump = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+            if !dump.is_empty() {
+                break;
+            }
+        }
+        assert!(dump.starts_with("Store metrics: StoreMetrics {"));
+        assert!(dump.contains("queue_depth_by_chain"));
+        // the relayer keeps running.
+        assert!(!task.is_finished());
+        task.abort();
+    }
+}
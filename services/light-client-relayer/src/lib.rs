@@ -7,6 +7,9 @@ use webb_relayer_utils::Result;
 
 mod light_client;
 
+/// Last-gasp diagnostics, logged when the relayer receives a signal.
+pub mod diagnostics;
+
 /// A struct for listening to blocks / block headers that implements
 /// the [`LightClientPoller`] trait.
 #[derive(Copy, Clone, Debug, Default)]
@@ -4,11 +4,16 @@
 #![warn(missing_docs)]
 
 use tokio::signal::unix;
+use webb_light_client_relayer::diagnostics::{
+    dump_store_metrics_on_sigusr1, log_crash_report,
+};
 use webb_light_client_relayer::start_light_client_service;
 
 use webb_relayer_config::{
     block_poller::BlockPollerConfig,
-    cli::{create_store, load_config, setup_logger, Opts},
+    cli::{
+        create_store, load_config, runtime_worker_threads, setup_logger, Opts,
+    },
 };
 use webb_relayer_context::RelayerContext;
 
@@ -54,12 +59,33 @@ pub async fn ignite(ctx: &RelayerContext) -> anyhow::Result<()> {
 
 /// The main entry point for the relayer.
 ///
+/// Builds the async runtime, sized by `--worker-threads` if given,
+/// and runs the relayer on it.
+///
 /// # Arguments
 ///
 /// * `args` - The command line arguments.
 #[paw::main]
-#[tokio::main(flavor = "multi_thread")]
-async fn main(args: Opts) -> anyhow::Result<()> {
+fn main(args: Opts) -> anyhow::Result<()> {
+    if args.version {
+        println!("webb-light-client-relayer {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(worker_threads) = runtime_worker_threads(&args)? {
+        builder.worker_threads(worker_threads);
+    }
+    let runtime = builder.build()?;
+    runtime.block_on(run(args))
+}
+
+/// Runs the relayer until a shutdown signal is received.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments.
+async fn run(args: Opts) -> anyhow::Result<()> {
     setup_logger(args.verbose, "webb_light_client_relayer")?;
     match dotenv::dotenv() {
         Ok(_) => {
@@ -98,6 +124,15 @@ async fn main(args: Opts) -> anyhow::Result<()> {
     let mut ctrlc_signal = unix::signal(unix::SignalKind::interrupt())?;
     let mut termination_signal = unix::signal(unix::SignalKind::terminate())?;
     let mut quit_signal = unix::signal(unix::SignalKind::quit())?;
+    let mut sys_signal =
+        unix::signal(unix::SignalKind::from_raw(libc::SIGSYS))?;
+    let mut bus_signal =
+        unix::signal(unix::SignalKind::from_raw(libc::SIGBUS))?;
+    // dump the store metrics on SIGUSR1, without stopping the relayer.
+    tokio::spawn(dump_store_metrics_on_sigusr1(
+        ctx.store().clone(),
+        std::io::stderr(),
+    ));
     let shutdown = || {
         tracing::event!(
             target: webb_relayer_utils::probe::TARGET,
@@ -124,6 +159,14 @@ async fn main(args: Opts) -> anyhow::Result<()> {
             tracing::warn!("Quitting ...");
             shutdown();
         },
+        _ = sys_signal.recv() => {
+            log_crash_report("SIGSYS", ctx.store());
+            shutdown();
+        },
+        _ = bus_signal.recv() => {
+            log_crash_report("SIGBUS", ctx.store());
+            shutdown();
+        },
         _ = server_handle => {
             tracing::warn!("Relayer axum server stopped");
             shutdown();
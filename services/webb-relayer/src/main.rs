@@ -16,24 +16,74 @@
 #![deny(unsafe_code)]
 #![warn(missing_docs)]
 
+use anyhow::Context;
+use axum::Router;
+use std::net::TcpListener;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal::unix;
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
 use tokio::time;
-use webb_relayer::service::build_web_services;
+use webb_relayer::service::config_watcher::{
+    ConfigWatcher, DEFAULT_SETTLE_DELAY,
+};
+use webb_relayer::service::flush::{flush_store_periodically, FlushStatus};
+use webb_relayer::service::reload::{self, RunningChains};
+use webb_relayer::service::{
+    bind_listener, bind_listeners, build_routes, serve_admin_services,
+    serve_web_services,
+};
 
-use webb_relayer_config::cli::{create_store, load_config, setup_logger, Opts};
+use webb_relayer_config::cli::{
+    apply_audit_log, apply_log_filter_by_chain, create_store, load_config,
+    resolve_config_dir, runtime_worker_threads, setup_logger, AdminCommand,
+    LoggerHandle, Opts,
+};
+use webb_relayer_config::WebbRelayerConfig;
+use webb_relayer_context::reconnects::ReconnectRequest;
+use webb_relayer_context::substrate_accounts::SubstrateAccounts;
 use webb_relayer_context::RelayerContext;
+use webb_relayer_handlers::routes::info::BuildInfo;
+use webb_relayer_store::{
+    LeafCacheDump, ProposalEventStore, RelayFingerprintStore,
+};
 
 /// The main entry point for the relayer.
 ///
+/// Builds the async runtime, sized by `--worker-threads` if given,
+/// and runs the relayer on it.
+///
 /// # Arguments
 ///
 /// * `args` - The command line arguments.
 #[paw::main]
-#[tokio::main]
-async fn main(args: Opts) -> anyhow::Result<()> {
-    setup_logger(args.verbose, "webb_relayer")?;
+fn main(args: Opts) -> anyhow::Result<()> {
+    if args.version {
+        println!("{}", BuildInfo::current());
+        return Ok(());
+    }
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(worker_threads) = runtime_worker_threads(&args)? {
+        builder.worker_threads(worker_threads);
+    }
+    let runtime = builder.build()?;
+    runtime.block_on(run(args))
+}
+
+/// Runs the relayer until a shutdown signal is received.
+///
+/// The server socket is bound before the store is opened and before the
+/// chains are synced, so that the health checks see the relayer as
+/// `starting` instead of unreachable.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments.
+async fn run(mut args: Opts) -> anyhow::Result<()> {
+    let logger = setup_logger(args.verbose, "webb_relayer")?;
     match dotenv::dotenv() {
         Ok(_) => {
             tracing::trace!("Loaded .env file");
@@ -45,23 +95,218 @@ async fn main(args: Opts) -> anyhow::Result<()> {
 
     // The configuration is validated and configured from the given directory
     let config = load_config(args.config_dir.clone())?;
+    apply_log_filter_by_chain(&logger, &config.log_filter_by_chain)?;
+    apply_audit_log(&logger, config.audit_log.as_ref())?;
+
+    if let Some(command) = args.command.take() {
+        let store = create_store(&args).await?;
+        let ctx = RelayerContext::new(config, store.clone())?;
+        return run_admin_command(&ctx, store, command).await;
+    }
+
+    // the server serves the health checks right away, the rest of the routes
+    // are handed to it once the relayer context is ready.
+    let listeners = bind_listeners(&config.listen_hosts(), config.port)?;
+    // the admin endpoints and the gRPC services are served once the relayer
+    // is ready, but their listeners are bound right away to fail early if
+    // they are not available.
+    let admin_listener = config
+        .admin_listener
+        .as_ref()
+        .map(|admin| bind_listener(&admin.host, admin.port))
+        .transpose()?;
+    let grpc_listener = config
+        .grpc
+        .as_ref()
+        .filter(|grpc| grpc.enabled)
+        .map(|grpc| bind_listener(&grpc.host, grpc.port))
+        .transpose()?;
+    let (routes_tx, routes_rx) = watch::channel(None);
+    let (stop_server, server_stopped) = oneshot::channel::<()>();
+    let flush_status = FlushStatus::default();
+    let substrate_accounts = SubstrateAccounts::default();
+    let mut server_handle = tokio::spawn(serve_web_services(
+        listeners,
+        routes_rx,
+        flush_status.clone(),
+        substrate_accounts.clone(),
+        async move {
+            let _ = server_stopped.await;
+        },
+    ));
+
+    // watch for signals, even while starting.
+    let mut signals = ShutdownSignals::new()?;
+    let started = tokio::select! {
+        started = start(
+            config,
+            &args,
+            &routes_tx,
+            admin_listener,
+            grpc_listener,
+            flush_status.clone(),
+            substrate_accounts,
+        ) => {
+            Some(started?)
+        }
+        _ = signals.recv() => None,
+    };
+    let (mut ctx, sled_metric_task_handle, mut chains) = match started {
+        Some(started) => started,
+        None => {
+            tracing::warn!("Shutting down before the relayer got started...");
+            stop_web_services(stop_server, &mut server_handle).await;
+            tracing::info!("Clean Exit ..");
+            return Ok(());
+        }
+    };
+
+    tracing::event!(
+        target: webb_relayer_utils::probe::TARGET,
+        tracing::Level::DEBUG,
+        kind = %webb_relayer_utils::probe::Kind::Lifecycle,
+        started = true
+    );
+    let mut reloads = ReloadSignal::new(ctx.config.reload_on_sighup)?;
+    let mut config_changes =
+        ConfigChanges::new(ctx.config.hot_reload, args.config_dir.as_ref())?;
+    // the background tasks started by `ignite` are run, and their failures
+    // handled, from here on.
+    let mut supervisor = ctx
+        .background_tasks()
+        .supervisor()
+        .expect("the background tasks are only supervised here");
+    let mut reconnects = ctx
+        .reconnects()
+        .receiver()
+        .expect("the reconnects are only handled here");
+    let mut task_failure = None;
+    loop {
+        tokio::select! {
+            _ = signals.recv() => break,
+            error = supervisor.supervise(&ctx) => {
+                task_failure = Some(error);
+                break;
+            }
+            _ = reloads.recv() => {
+                ctx = reload_config(
+                    &args,
+                    &logger,
+                    ctx,
+                    &mut chains,
+                    &routes_tx,
+                )
+                .await;
+            }
+            _ = config_changes.recv() => {
+                ctx = reload_config(
+                    &args,
+                    &logger,
+                    ctx,
+                    &mut chains,
+                    &routes_tx,
+                )
+                .await;
+            }
+            Some(request) = reconnects.recv() => {
+                ctx = reconnect_chain(ctx, &mut chains, request, &routes_tx)
+                    .await;
+            }
+        }
+    }
+    tracing::event!(
+        target: webb_relayer_utils::probe::TARGET,
+        tracing::Level::DEBUG,
+        kind = %webb_relayer_utils::probe::Kind::Lifecycle,
+        shutdown = true
+    );
+    tracing::warn!("Shutting down...");
+    // send shutdown signal to all of the application.
+    ctx.shutdown();
+    // stop the server, letting the in-flight requests finish.
+    stop_web_services(stop_server, &mut server_handle).await;
+    // abort get sled storage data task
+    sled_metric_task_handle.abort();
+    // make sure nothing written by the background services is lost.
+    let flushed = ctx.store().flush();
+    match &flushed {
+        Ok(bytes) => tracing::info!("Flushed {bytes} bytes of the store"),
+        Err(e) => tracing::error!("Failed to flush the store: {e}"),
+    }
+    flush_status.record(&flushed);
+    time::sleep(Duration::from_millis(300)).await;
+    if let Some(error) = task_failure {
+        return Err(error);
+    }
+    tracing::info!("Clean Exit ..");
+    Ok(())
+}
 
+/// Opens the store and starts all the background services of the relayer,
+/// then hands the routes that need the relayer context to the server.
+///
+/// Returns the relayer context, the handle of the store metric task, and the
+/// chains started.
+///
+/// # Arguments
+///
+/// * `config` - The relayer configuration.
+/// * `args` - The command line arguments.
+/// * `routes` - Where to send the routes once the context is ready.
+/// * `admin_listener` - The listener of the admin endpoints, if configured.
+/// * `grpc_listener` - The listener of the gRPC server, if enabled.
+/// * `flush_status` - Where to record the outcome of the periodic flushes.
+/// * `substrate_accounts` - Where to record the relayer accounts on the
+///   Substrate nodes.
+async fn start(
+    config: WebbRelayerConfig,
+    args: &Opts,
+    routes: &watch::Sender<Option<Router>>,
+    admin_listener: Option<TcpListener>,
+    grpc_listener: Option<TcpListener>,
+    flush_status: FlushStatus,
+    substrate_accounts: SubstrateAccounts,
+) -> anyhow::Result<(RelayerContext, JoinHandle<()>, RunningChains)> {
     // persistent storage for the relayer
-    let store = create_store(&args).await?;
+    let store = create_store(args).await?;
     let cloned_store = store.clone();
 
     // The RelayerContext takes a configuration, and populates objects that are needed
     // throughout the lifetime of the relayer. Items such as wallets and providers, as well
     // as a convenient place to access the configuration.
-    let ctx = RelayerContext::new(config, store.clone())?;
+    let ctx = RelayerContext::new(config, store.clone())?
+        .with_substrate_accounts(substrate_accounts);
     let metrics_clone = ctx.metrics.clone();
+    let clock = ctx.clock().clone();
 
-    // metric for data stored which is determined every 1 hour
+    // metric for data stored which is determined every 1 hour, along with
+    // the cleanup of the expired relay fingerprints and proposal calls.
     let sled_metric_task_handle = tokio::task::spawn(async move {
         let mut sled_data_metric_interval =
             time::interval(Duration::from_secs(3600));
         loop {
             sled_data_metric_interval.tick().await;
+            match cloned_store.prune_relay_fingerprints(clock.unix_timestamp())
+            {
+                Ok(0) => {}
+                Ok(pruned) => {
+                    tracing::debug!(pruned, "Pruned the relay fingerprints")
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to prune the relay fingerprints: {e}"
+                    )
+                }
+            }
+            match cloned_store.prune_proposal_calls(clock.unix_timestamp()) {
+                Ok(0) => {}
+                Ok(pruned) => {
+                    tracing::debug!(pruned, "Pruned the proposal calls")
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to prune the proposal calls: {e}")
+                }
+            }
             // set data stored
             metrics_clone
                 .lock()
@@ -71,54 +316,352 @@ async fn main(args: Opts) -> anyhow::Result<()> {
         }
     });
 
-    // the build_web_relayer command sets up routing (endpoint queries / requests mapped to handled code)
-    // so clients can interact with the relayer
-    let server_handle = tokio::spawn(build_web_services(ctx.clone()));
+    if let Some(interval_ms) = ctx.config.flush_interval_ms {
+        tokio::spawn(flush_store_periodically(
+            ctx.store().clone(),
+            Duration::from_millis(interval_ms),
+            flush_status,
+            ctx.shutdown_signal(),
+        ));
+    }
+
     // start all background services.
     // this does not block, will fire the services on background tasks.
-    webb_relayer::service::ignite(ctx.clone(), Arc::new(store)).await?;
+    let chains =
+        webb_relayer::service::ignite(ctx.clone(), Arc::new(store)).await?;
+    // the server may already be gone if it failed, nothing to serve then.
+    let _ = routes.send(Some(build_routes(ctx.clone())));
+    if let Some(listener) = admin_listener {
+        let admin_server = serve_admin_services(listener, ctx.clone());
+        tokio::spawn(async move {
+            if let Err(e) = admin_server.await {
+                tracing::error!("Admin server stopped: {e}");
+            }
+        });
+    }
+    if let (Some(listener), Some(grpc)) = (grpc_listener, &ctx.config.grpc) {
+        let grpc_server =
+            webb_relayer_grpc::serve(listener, ctx.clone(), grpc.token.clone());
+        tokio::spawn(async move {
+            if let Err(e) = grpc_server.await {
+                tracing::error!("gRPC server stopped: {e}");
+            }
+        });
+    }
+    Ok((ctx, sled_metric_task_handle, chains))
+}
 
-    tracing::event!(
-        target: webb_relayer_utils::probe::TARGET,
-        tracing::Level::DEBUG,
-        kind = %webb_relayer_utils::probe::Kind::Lifecycle,
-        started = true
-    );
-    // watch for signals
-    let mut ctrlc_signal = unix::signal(unix::SignalKind::interrupt())?;
-    let mut termination_signal = unix::signal(unix::SignalKind::terminate())?;
-    let mut quit_signal = unix::signal(unix::SignalKind::quit())?;
-    let shutdown = || {
-        tracing::event!(
-            target: webb_relayer_utils::probe::TARGET,
-            tracing::Level::DEBUG,
-            kind = %webb_relayer_utils::probe::Kind::Lifecycle,
-            shutdown = true
-        );
-        tracing::warn!("Shutting down...");
-        // shut down storage fetching
-        // send shutdown signal to all of the application.
-        ctx.shutdown();
-        // also abort the server task
-        server_handle.abort();
-        // abort get sled storage data task
-        sled_metric_task_handle.abort();
-        std::thread::sleep(std::time::Duration::from_millis(300));
-        tracing::info!("Clean Exit ..");
+/// Reloads the config files, and applies them to the running relayer, see
+/// [`reload::reload`].
+///
+/// Returns the relayer context to use from now on, which is the running
+/// one if the reload failed.
+async fn reload_config(
+    args: &Opts,
+    logger: &LoggerHandle,
+    ctx: RelayerContext,
+    chains: &mut RunningChains,
+    routes: &watch::Sender<Option<Router>>,
+) -> RelayerContext {
+    let config = match load_config(args.config_dir.clone()) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Failed to reload the config: {e:#}");
+            return ctx;
+        }
     };
-    tokio::select! {
-        _ = ctrlc_signal.recv() => {
-            tracing::warn!("Interrupted (Ctrl+C) ...");
-            shutdown();
-        },
-        _ = termination_signal.recv() => {
-            tracing::warn!("Got Terminate signal ...");
-            shutdown();
-        },
-        _ = quit_signal.recv() => {
-            tracing::warn!("Quitting ...");
-            shutdown();
-        },
+    let store = Arc::new(ctx.store().clone());
+    let ctx = match reload::reload(&ctx, chains, store, config).await {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            tracing::error!("{e}");
+            return ctx;
+        }
+    };
+    if let Err(e) =
+        apply_log_filter_by_chain(logger, &ctx.config.log_filter_by_chain)
+    {
+        tracing::error!("Failed to reload the log filter by chain: {e:#}");
+    }
+    if let Err(e) = apply_audit_log(logger, ctx.config.audit_log.as_ref()) {
+        tracing::error!("Failed to reload the audit log: {e:#}");
+    }
+    // the new requests see the reloaded chains, while the open websocket
+    // connections keep going.
+    let _ = routes.send(Some(build_routes(ctx.clone())));
+    ctx
+}
+
+/// Reconnects the services of the chain of the `request` to its
+/// endpoints, see [`reload::reconnect`], and answers the request.
+///
+/// Returns the relayer context to use from now on, which is the running
+/// one if the chain could not be reconnected.
+async fn reconnect_chain(
+    ctx: RelayerContext,
+    chains: &mut RunningChains,
+    request: ReconnectRequest,
+    routes: &watch::Sender<Option<Router>>,
+) -> RelayerContext {
+    let store = Arc::new(ctx.store().clone());
+    let chain = request.chain;
+    match reload::reconnect(&ctx, chains, store, chain).await {
+        Ok(reconnected) => {
+            // the new requests use the new providers too.
+            let _ = routes.send(Some(build_routes(reconnected.clone())));
+            request.respond(Ok(()));
+            reconnected
+        }
+        Err(e) => {
+            tracing::error!("Failed to reconnect {chain:?}: {e}");
+            request.respond(Err(e.to_string()));
+            ctx
+        }
+    }
+}
+
+/// Stops the web server gracefully, giving up on the connections that are
+/// still open after a few seconds, like websockets.
+async fn stop_web_services(
+    stop_server: oneshot::Sender<()>,
+    server_handle: &mut JoinHandle<webb_relayer_utils::Result<()>>,
+) {
+    let _ = stop_server.send(());
+    if time::timeout(Duration::from_secs(5), &mut *server_handle)
+        .await
+        .is_err()
+    {
+        tracing::warn!("Server did not stop in time, aborting it");
+        server_handle.abort();
+    }
+}
+
+/// The signals that stop the relayer.
+struct ShutdownSignals {
+    ctrlc: unix::Signal,
+    termination: unix::Signal,
+    quit: unix::Signal,
+}
+
+impl ShutdownSignals {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            ctrlc: unix::signal(unix::SignalKind::interrupt())?,
+            termination: unix::signal(unix::SignalKind::terminate())?,
+            quit: unix::signal(unix::SignalKind::quit())?,
+        })
+    }
+
+    /// Waits for any of the signals.
+    async fn recv(&mut self) {
+        tokio::select! {
+            _ = self.ctrlc.recv() => {
+                tracing::warn!("Interrupted (Ctrl+C) ...");
+            },
+            _ = self.termination.recv() => {
+                tracing::warn!("Got Terminate signal ...");
+            },
+            _ = self.quit.recv() => {
+                tracing::warn!("Quitting ...");
+            },
+        }
+    }
+}
+
+/// The `SIGHUP` signal, reloading the config when `reload-on-sighup` is on.
+///
+/// Otherwise no handler is installed, and `SIGHUP` stops the relayer.
+struct ReloadSignal(Option<unix::Signal>);
+
+impl ReloadSignal {
+    fn new(enabled: bool) -> std::io::Result<Self> {
+        let hangup = enabled
+            .then(|| unix::signal(unix::SignalKind::hangup()))
+            .transpose()?;
+        Ok(Self(hangup))
+    }
+
+    /// Waits for the signal, forever if it is not handled.
+    async fn recv(&mut self) {
+        match &mut self.0 {
+            Some(hangup) => {
+                hangup.recv().await;
+                tracing::warn!("Got Hangup signal, reloading the config ...");
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// The changes of the config files, reloading the config when
+/// `hot-reload` is on.
+///
+/// Otherwise the config files are not watched.
+struct ConfigChanges(Option<ConfigWatcher>);
+
+impl ConfigChanges {
+    fn new(
+        enabled: bool,
+        config_dir: Option<&PathBuf>,
+    ) -> anyhow::Result<Self> {
+        if !enabled {
+            return Ok(Self(None));
+        }
+        let config_dir = resolve_config_dir(config_dir)?;
+        let watcher = ConfigWatcher::new(&config_dir, DEFAULT_SETTLE_DELAY)
+            .context("failed to watch the config files")?;
+        tracing::info!("Watching {} for config changes", config_dir.display());
+        Ok(Self(Some(watcher)))
+    }
+
+    /// Waits for the config files to change, forever if they are not
+    /// watched.
+    async fn recv(&mut self) {
+        match &mut self.0 {
+            Some(watcher) => {
+                let files = watcher.changed().await;
+                tracing::warn!(
+                    ?files,
+                    "Config files changed, reloading the config ...",
+                );
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Runs an admin command to completion, printing its outcome.
+///
+/// # Arguments
+///
+/// * `ctx` - The relayer context.
+/// * `store` - The store of the relayer.
+/// * `command` - The admin command to run.
+async fn run_admin_command(
+    ctx: &RelayerContext,
+    store: webb_relayer_store::SledStore,
+    command: AdminCommand,
+) -> anyhow::Result<()> {
+    match command {
+        AdminCommand::ReplayEvent {
+            chain,
+            tx_hash,
+            log_index,
+        } => {
+            let report = webb_relayer::service::evm::replay_event(
+                ctx,
+                Arc::new(store),
+                &chain,
+                tx_hash,
+                log_index,
+            )
+            .await?;
+            println!(
+                "Replayed log #{log_index} of {tx_hash:?} emitted by {:?}",
+                report.contract_address
+            );
+            for (name, outcome) in &report.handlers {
+                match outcome {
+                    Ok(true) => println!("  {name}: handled"),
+                    Ok(false) => println!("  {name}: not interested"),
+                    Err(e) => println!("  {name}: failed: {e}"),
+                }
+            }
+            if report.queued_proposals > 0 {
+                println!(
+                    "  {} anchor update proposal(s) queued, but not sent",
+                    report.queued_proposals
+                );
+            }
+        }
+        AdminCommand::ImportSnapshot {
+            file,
+            verify_signer,
+            verify_root_onchain,
+        } => {
+            let snapshot = std::fs::read(&file).with_context(|| {
+                format!("failed to read the leaf snapshot {}", file.display())
+            })?;
+            let header = webb_relayer::service::evm::import_leaf_snapshot(
+                ctx,
+                &store,
+                &snapshot,
+                verify_signer,
+                verify_root_onchain,
+            )
+            .await?;
+            println!(
+                "Imported {} leaves of {:?} on chain {}, up to block {}",
+                header.leaf_count,
+                header.contract,
+                header.chain_id,
+                header.last_block
+            );
+        }
+        AdminCommand::ForceAnchorUpdate {
+            src_chain,
+            src_anchor,
+            dest_chain,
+        } => {
+            let update = webb_relayer::service::evm::force_anchor_update(
+                ctx,
+                Arc::new(store),
+                &src_chain,
+                src_anchor,
+                &dest_chain,
+            )
+            .await?;
+            let hex = |bytes: [u8; 32]| ethereum_types::H256::from(bytes);
+            println!(
+                "Anchor update of {:?} from {:?} at root {:?} (nonce {})",
+                hex(update.target_resource_id.to_bytes()),
+                hex(update.src_resource_id.to_bytes()),
+                hex(update.merkle_root),
+                update.nonce
+            );
+            if update.signed {
+                println!("  handed to the signing backend");
+            } else {
+                println!("  not supported by the signing backend");
+            }
+        }
+        AdminCommand::ExportCache { out } => {
+            let dump = store.export_leaf_cache()?;
+            std::fs::write(&out, dump.to_bytes()).with_context(|| {
+                format!("failed to write the leaf cache to {}", out.display())
+            })?;
+            println!(
+                "Exported {} leaves of {} contracts to {}",
+                dump.leaf_count(),
+                dump.contracts.len(),
+                out.display()
+            );
+        }
+        AdminCommand::ImportCache { input } => {
+            let bytes = std::fs::read(&input).with_context(|| {
+                format!("failed to read the leaf cache {}", input.display())
+            })?;
+            let dump = LeafCacheDump::from_bytes(&bytes)?;
+            store.import_leaf_cache(&dump)?;
+            store.flush()?;
+            println!(
+                "Imported {} leaves of {} contracts",
+                dump.leaf_count(),
+                dump.contracts.len()
+            );
+        }
+        AdminCommand::ExportProposals { out } => {
+            let proposals = store.export_proposals()?;
+            let json = serde_json::to_vec_pretty(&proposals)?;
+            std::fs::write(&out, json).with_context(|| {
+                format!("failed to write the proposals to {}", out.display())
+            })?;
+            println!(
+                "Exported {} proposals to {}",
+                proposals.len(),
+                out.display()
+            );
+        }
     }
     Ok(())
 }
@@ -0,0 +1,301 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use sp_core::crypto::Ss58Codec;
+use webb::substrate::subxt::utils::AccountId32;
+use webb::substrate::tangle_runtime::api;
+use webb_proposals::TypedChainId;
+use webb_relayer_config::substrate::SubstrateConfig;
+use webb_relayer_context::substrate_accounts::{
+    from_native_unit, to_native_unit, SubstrateAccountStatus,
+};
+use webb_relayer_context::RelayerContext;
+
+use super::substrate::TangleClient;
+
+/// The relayer account, as stored by a Substrate node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountInfo {
+    /// The free balance, in the smallest unit of the chain.
+    pub free: u128,
+    /// The nonce of the next transaction of the account.
+    pub nonce: u32,
+}
+
+/// Reads the relayer account from a Substrate node.
+#[async_trait::async_trait]
+pub trait AccountStorage: Send + Sync {
+    /// The decimals of the native unit of the chain.
+    async fn decimals(&self) -> crate::Result<u8>;
+    /// The account stored under `account_id`, the default one if the
+    /// account does not exist.
+    async fn account(
+        &self,
+        account_id: &AccountId32,
+    ) -> crate::Result<AccountInfo>;
+}
+
+#[async_trait::async_trait]
+impl AccountStorage for TangleClient {
+    async fn decimals(&self) -> crate::Result<u8> {
+        self.rpc()
+            .system_properties()
+            .await?
+            .get("tokenDecimals")
+            .and_then(serde_json::Value::as_u64)
+            .and_then(|decimals| u8::try_from(decimals).ok())
+            .ok_or(crate::Error::ReadSubstrateStorageError)
+    }
+
+    async fn account(
+        &self,
+        account_id: &AccountId32,
+    ) -> crate::Result<AccountInfo> {
+        let address = api::storage().system().account(account_id);
+        // an account without existential deposit is not stored at all.
+        let info = self
+            .storage()
+            .at(None)
+            .await?
+            .fetch(&address)
+            .await?
+            .map(|info| AccountInfo {
+                free: info.data.free,
+                nonce: info.nonce,
+            })
+            .unwrap_or(AccountInfo { free: 0, nonce: 0 });
+        Ok(info)
+    }
+}
+
+/// Reads the relayer account from the node, and records it in the context
+/// and the metrics.
+///
+/// Returns the recorded status of the account.
+pub async fn check_account<S: AccountStorage>(
+    ctx: &RelayerContext,
+    node_config: &SubstrateConfig,
+    storage: &S,
+    account_id: &AccountId32,
+) -> crate::Result<SubstrateAccountStatus> {
+    let decimals = storage.decimals().await?;
+    let info = storage.account(account_id).await?;
+    let low_balance = node_config
+        .min_balance
+        .map(|min| info.free < from_native_unit(min, decimals))
+        .unwrap_or(false);
+    let status = SubstrateAccountStatus {
+        address: sp_core::crypto::AccountId32::from(account_id.0)
+            .to_ss58check(),
+        free_balance: info.free,
+        decimals,
+        nonce: info.nonce,
+        low_balance,
+        checked_at: ctx.clock().unix_timestamp(),
+    };
+    if low_balance {
+        tracing::warn!(
+            address = %status.address,
+            balance = status.balance(),
+            min_balance = ?node_config.min_balance,
+            "The relayer account is low on funds",
+        );
+    }
+    let chain = TypedChainId::Substrate(node_config.chain_id);
+    let mut metrics = ctx.metrics.lock().await;
+    metrics
+        .account_balance_entry(chain)
+        .set(to_native_unit(info.free, decimals));
+    metrics
+        .account_nonce_entry(chain)
+        .set(f64::from(info.nonce));
+    drop(metrics);
+    ctx.substrate_accounts()
+        .record(node_config.chain_id, status.clone());
+    Ok(status)
+}
+
+/// Checks the relayer account on the node every
+/// `account-check-interval-secs`, until the relayer shuts down.
+///
+/// Nothing is checked if the node has no `suri` configured.
+pub fn start_account_monitor(
+    ctx: RelayerContext,
+    node_config: &SubstrateConfig,
+) -> crate::Result<()> {
+    let Some(suri) = node_config.suri.clone() else {
+        return Ok(());
+    };
+    let pair: sp_core::sr25519::Pair = suri.into();
+    let account_id = AccountId32::from(sp_core::Pair::public(&pair).0);
+    let node_config = node_config.clone();
+    let interval =
+        Duration::from_secs(node_config.account_check_interval_secs.max(1));
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let ctx = my_ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let node_config = node_config.clone();
+        let account_id = account_id.clone();
+        async move {
+            let check = async {
+                loop {
+                    let checked = async {
+                        let client: TangleClient = ctx
+                            .substrate_provider(node_config.chain_id)
+                            .await?;
+                        check_account(&ctx, &node_config, &client, &account_id)
+                            .await
+                    };
+                    if let Err(e) = checked.await {
+                        tracing::warn!(
+                            "Failed to check the relayer account: {e}"
+                        );
+                    }
+                    ctx.clock().sleep(interval).await;
+                }
+            };
+            tokio::select! {
+                _ = check => {},
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping the account monitor of node({})",
+                        node_config.chain_id
+                    );
+                },
+            }
+            crate::Result::Ok(())
+        }
+    };
+    let chain_id = node_config.chain_id;
+    ctx.background_tasks()
+        .spawn(format!("Account monitor node({chain_id})"), task);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use webb_relayer_utils::clock::test_utils::FrozenClock;
+
+    /// A node storing a single account.
+    struct MockedStorage {
+        decimals: u8,
+        account: Mutex<AccountInfo>,
+    }
+
+    #[async_trait::async_trait]
+    impl AccountStorage for MockedStorage {
+        async fn decimals(&self) -> crate::Result<u8> {
+            Ok(self.decimals)
+        }
+
+        async fn account(
+            &self,
+            _account_id: &AccountId32,
+        ) -> crate::Result<AccountInfo> {
+            Ok(*self.account.lock().unwrap())
+        }
+    }
+
+    fn node_config(min_balance: Option<f64>) -> SubstrateConfig {
+        let mut config: SubstrateConfig =
+            serde_json::from_value(serde_json::json!({
+                "name": "tangle",
+                "enabled": true,
+                "http-endpoint": "http://localhost:9933",
+                "ws-endpoint": "ws://localhost:9944",
+                "chain-id": 1081,
+            }))
+            .unwrap();
+        config.min_balance = min_balance;
+        config
+    }
+
+    fn context() -> RelayerContext {
+        let store = webb_relayer_store::SledStore::temporary().unwrap();
+        RelayerContext::new(Default::default(), store)
+            .unwrap()
+            .with_clock(FrozenClock::default().shared())
+    }
+
+    #[tokio::test]
+    async fn should_report_a_low_balance_in_the_native_unit() {
+        let ctx = context();
+        let config = node_config(Some(1.5));
+        let storage = MockedStorage {
+            decimals: 12,
+            account: Mutex::new(AccountInfo {
+                free: 2 * 10u128.pow(12),
+                nonce: 7,
+            }),
+        };
+        let account_id = AccountId32::from([1u8; 32]);
+
+        let status = check_account(&ctx, &config, &storage, &account_id)
+            .await
+            .unwrap();
+        assert!(!status.low_balance);
+        assert_eq!(status.nonce, 7);
+        assert_eq!(status.balance(), 2.0);
+
+        // spent down to 1 unit, under the 1.5 of the config.
+        *storage.account.lock().unwrap() = AccountInfo {
+            free: 10u128.pow(12),
+            nonce: 8,
+        };
+        check_account(&ctx, &config, &storage, &account_id)
+            .await
+            .unwrap();
+        let recorded = ctx.substrate_accounts().get(1081).unwrap();
+        assert!(recorded.low_balance);
+        assert_eq!(recorded.nonce, 8);
+        let health = ctx.substrate_accounts().to_json();
+        assert_eq!(health["1081"]["freeBalance"], "1000000000000");
+        assert_eq!(health["1081"]["lowBalance"], true);
+        // the balance gauge is in the native unit too.
+        let balance = ctx
+            .metrics
+            .lock()
+            .await
+            .account_balance_entry(TypedChainId::Substrate(1081))
+            .get();
+        assert_eq!(balance, 1.0);
+    }
+
+    #[tokio::test]
+    async fn should_resync_the_nonce_with_the_node() {
+        use webb_relayer_context::substrate_accounts::PENDING_NONCE_TTL_SECS;
+
+        let ctx = context();
+        let accounts = ctx.substrate_accounts();
+        assert_eq!(accounts.sync_nonce(1081, 3, 0), 3);
+        accounts.record_submitted(1081, 3, 0);
+        // the node did not count the transaction yet.
+        assert_eq!(accounts.sync_nonce(1081, 3, 1), 4);
+        accounts.record_submitted(1081, 4, 1);
+        accounts.record_submitted(1081, 3, 1);
+        assert_eq!(accounts.sync_nonce(1081, 4, 2), 5);
+        assert_eq!(accounts.sync_nonce(1081, 5, 2), 5);
+        // a transaction got sent outside of the relayer meanwhile.
+        assert_eq!(accounts.sync_nonce(1081, 7, 3), 7);
+        accounts.record_submitted(1081, 7, 3);
+        assert_eq!(accounts.sync_nonce(1081, 7, 4), 8);
+        // the transaction got dropped, the node is right again.
+        assert_eq!(accounts.sync_nonce(1081, 7, 3 + PENDING_NONCE_TTL_SECS), 7);
+    }
+}
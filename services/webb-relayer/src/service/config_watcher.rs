@@ -0,0 +1,229 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long the config files have to stay untouched after a change before
+/// they get reloaded, editors write them in several steps.
+pub const DEFAULT_SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Watches the config directory for changes of the config files, to reload
+/// them with `hot-reload`.
+pub struct ConfigWatcher {
+    // stops watching once dropped.
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+    settle_delay: Duration,
+    // the files changed so far, and when they settle down, kept here so
+    // they are not lost if waiting for them gets cancelled.
+    pending: Vec<PathBuf>,
+    settles_at: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the config files under `config_dir`, and its
+    /// subdirectories.
+    pub fn new(
+        config_dir: &Path,
+        settle_delay: Duration,
+    ) -> notify::Result<Self> {
+        let (tx, changes) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(
+            move |event: notify::Result<Event>| match event {
+                Ok(event) if is_config_change(&event.kind) => {
+                    for path in
+                        event.paths.into_iter().filter(|p| is_config_file(p))
+                    {
+                        let _ = tx.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to watch the config files: {e}")
+                }
+            },
+        )?;
+        watcher.watch(config_dir, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+            settle_delay,
+            pending: Vec::new(),
+            settles_at: None,
+        })
+    }
+
+    /// Waits for the config files to change, then for them to settle down,
+    /// and returns the files changed in the meantime.
+    ///
+    /// This method is cancel safe, the changes received before it got
+    /// cancelled are returned by the next call.
+    pub async fn changed(&mut self) -> Vec<PathBuf> {
+        loop {
+            let change = match self.settles_at {
+                Some(at) => {
+                    match tokio::time::timeout_at(at, self.changes.recv()).await
+                    {
+                        Ok(change) => change,
+                        Err(_) => break,
+                    }
+                }
+                None => self.changes.recv().await,
+            };
+            match change {
+                Some(path) => {
+                    self.pending.push(path);
+                    self.settles_at = Some(Instant::now() + self.settle_delay);
+                }
+                None if self.pending.is_empty() => {
+                    return std::future::pending().await
+                }
+                None => break,
+            }
+        }
+        self.settles_at = None;
+        let mut changed = std::mem::take(&mut self.pending);
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// Whether the event could change the config, the config files being read
+/// again from scratch.
+fn is_config_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    )
+}
+
+/// Whether the file is one of the config files, as loaded by
+/// [`webb_relayer_config::utils::search_config_files`].
+fn is_config_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("toml" | "json")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::reload::{ChainKey, ChainsDiff};
+    use webb_relayer_config::utils::load;
+
+    const MAIN: &str = "port = 9955\n";
+
+    fn chain_file(name: &str, chain_id: u32) -> String {
+        format!(
+            "[evm.{name}]\n\
+             name = \"{name}\"\n\
+             http-endpoint = \"http://localhost:8545\"\n\
+             ws-endpoint = \"ws://localhost:8545\"\n\
+             chain-id = {chain_id}\n\
+             enabled = true\n"
+        )
+    }
+
+    async fn changed(watcher: &mut ConfigWatcher) -> Vec<PathBuf> {
+        tokio::time::timeout(Duration::from_secs(10), watcher.changed())
+            .await
+            .expect("the config files changed")
+    }
+
+    #[tokio::test]
+    async fn added_chains_should_be_found_once_their_file_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.toml"), MAIN).unwrap();
+        std::fs::write(
+            dir.path().join("hermes.toml"),
+            chain_file("hermes", 5001),
+        )
+        .unwrap();
+        let running = load(dir.path()).unwrap();
+        let mut watcher =
+            ConfigWatcher::new(dir.path(), Duration::from_millis(100)).unwrap();
+
+        std::fs::write(
+            dir.path().join("athena.toml"),
+            chain_file("athena", 5002),
+        )
+        .unwrap();
+        // not a config file, ignored.
+        std::fs::write(dir.path().join("athena.toml.swp"), "").unwrap();
+
+        let changed = changed(&mut watcher).await;
+        assert!(changed.iter().any(|p| p.ends_with("athena.toml")));
+        let reloaded = load(dir.path()).unwrap();
+        let diff = ChainsDiff::between(&running, &reloaded);
+        assert_eq!(diff.added, vec![ChainKey::Evm("athena".into())]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn changes_should_not_be_lost_when_waiting_for_them_is_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.toml"), MAIN).unwrap();
+        let mut watcher =
+            ConfigWatcher::new(dir.path(), Duration::from_secs(1)).unwrap();
+
+        std::fs::write(
+            dir.path().join("hermes.toml"),
+            chain_file("hermes", 5001),
+        )
+        .unwrap();
+        // cancelled while the files settle down, like another branch of the
+        // run loop firing first.
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(300), watcher.changed())
+                .await;
+        assert!(cancelled.is_err());
+
+        let changed = changed(&mut watcher).await;
+        assert!(changed.iter().any(|p| p.ends_with("hermes.toml")));
+    }
+
+    #[tokio::test]
+    async fn removed_chains_should_be_found_once_their_file_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.toml"), MAIN).unwrap();
+        std::fs::create_dir(dir.path().join("chains")).unwrap();
+        let removed = dir.path().join("chains").join("athena.toml");
+        std::fs::write(&removed, chain_file("athena", 5002)).unwrap();
+        std::fs::write(
+            dir.path().join("chains").join("hermes.toml"),
+            chain_file("hermes", 5001),
+        )
+        .unwrap();
+        let running = load(dir.path()).unwrap();
+        let mut watcher =
+            ConfigWatcher::new(dir.path(), Duration::from_millis(100)).unwrap();
+
+        std::fs::remove_file(&removed).unwrap();
+
+        let changed = changed(&mut watcher).await;
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].ends_with("chains/athena.toml"));
+        let reloaded = load(dir.path()).unwrap();
+        let diff = ChainsDiff::between(&running, &reloaded);
+        assert_eq!(diff.removed, vec![ChainKey::Evm("athena".into())]);
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+    }
+}
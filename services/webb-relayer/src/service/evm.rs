@@ -1,12 +1,19 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::routing::get;
-use axum::Router;
+use axum::{Extension, Router};
+use tokio::sync::Mutex;
+use webb::evm::contract::protocol_solidity::VAnchorContract;
 use webb::evm::ethers::prelude::TimeLag;
+use webb::evm::ethers::providers::{JsonRpcClient, Middleware, Provider};
+use webb::evm::ethers::types;
 use webb_bridge_registry_backends::dkg::DkgBridgeRegistryBackend;
 use webb_bridge_registry_backends::mocked::MockedBridgeRegistryBackend;
+use webb_bridge_registry_backends::BridgeRegistryBackend;
 use webb_event_watcher_traits::{
-    BridgeWatcher, EthersClient, EthersTimeLagClient, EventWatcher,
+    fetch_log, BridgeWatcher, EthersClient, EthersTimeLagClient,
+    EventHandlerFor, EventWatcher, StartDelay, WatchableContract,
 };
 
 use webb_ew_evm::signature_bridge_watcher::{
@@ -14,21 +21,33 @@ use webb_ew_evm::signature_bridge_watcher::{
     SignatureBridgeGovernanceOwnershipTransferredHandler,
 };
 use webb_ew_evm::vanchor::{
-    VAnchorDepositHandler, VAnchorEncryptedOutputHandler, VAnchorLeavesHandler,
+    merkle_root, recover_leaf_cache_checkpoint, CrossChainAnchorStateSync,
+    LeafSnapshot, LeafSnapshotHeader, VAnchorDepositHandler,
+    VAnchorEncryptedOutputHandler, VAnchorExecutedProposalsHandler,
+    VAnchorLeavesHandler, VAnchorNeighborRoots, VAnchorRootSource,
 };
 use webb_ew_evm::{VAnchorContractWatcher, VAnchorContractWrapper};
-use webb_proposal_signing_backends::queue::{self, policy};
-use webb_proposals::TypedChainId;
+use webb_proposal_signing_backends::proposal_handler;
+use webb_proposal_signing_backends::queue::{self, policy, ProposalsQueue};
+use webb_proposal_signing_backends::ProposalSigningBackend;
+use webb_proposals::{ProposalTrait, ResourceId, TargetSystem, TypedChainId};
+use webb_relayer_config::anchor::resolve_linked_anchors;
+use webb_relayer_config::event_watcher::EventsWatcherConfig;
 use webb_relayer_config::evm::{
-    Contract, SignatureBridgeContractConfig, SmartAnchorUpdatesConfig,
-    VAnchorContractConfig,
+    Contract, EvmChainConfig, SignatureBridgeContractConfig,
+    SmartAnchorUpdatesConfig, VAnchorContractConfig,
 };
 use webb_relayer_context::RelayerContext;
 use webb_relayer_handlers::handle_evm_fee_info;
-use webb_relayer_handlers::routes::{encrypted_outputs, leaves, metric};
+use webb_relayer_handlers::routes::{
+    deposits, edges, encrypted_outputs, leaves, metric, snapshot, withdrawals,
+};
+use webb_relayer_store::LeafCacheStore;
 use webb_relayer_tx_queue::evm::TxQueue;
+use webb_relayer_utils::metric::Metrics;
 
 use super::make_proposal_signing_backend;
+use super::reload::{ChainKey, RunningChains};
 use super::ProposalSigningBackendSelector;
 
 /// Type alias for providers
@@ -51,12 +70,29 @@ pub fn build_web_services() -> Router<Arc<RelayerContext>> {
             "/metrics/evm/:chain_id/:contract",
             get(metric::handle_evm_metric_info),
         )
-        // for backward compatibility
-        .route("/metrics", get(metric::handle_metric_info))
+        .route(
+            "/chains/:chain_id/metrics",
+            get(metric::handle_chain_metrics),
+        )
+        .route("/metrics/chains", get(metric::handle_all_chain_metrics))
         .route(
             "/fee_info/evm/:chain_id/:vanchor/:gas_amount",
             get(handle_evm_fee_info),
         )
+        .route(
+            "/deposits/:chain_id/:anchor/:leaf_index/status",
+            get(deposits::handle_deposit_status_evm),
+        )
+        .route(
+            "/withdrawals/:chain_id/:contract",
+            get(withdrawals::handle_relayed_withdrawals_evm),
+        )
+        .route(
+            "/snapshot/:chain_id/:contract",
+            get(snapshot::handle_leaf_snapshot_evm),
+        )
+        .route("/edges/:chain_id/:contract", get(edges::handle_edges_evm))
+        .layer(Extension(snapshot::LeafSnapshotCache::default()))
 }
 
 /// Fires up all background services for all EVM chains configured in the config file.
@@ -70,52 +106,181 @@ pub fn build_web_services() -> Router<Arc<RelayerContext>> {
 pub async fn ignite(
     ctx: &RelayerContext,
     store: Arc<super::Store>,
+    chains: &mut RunningChains,
 ) -> crate::Result<()> {
+    if ctx.config.fail_fast_startup {
+        verify_contracts_deployed(ctx).await?;
+    }
+    for chain_config in ctx.config.evm.values() {
+        if !chain_config.enabled {
+            continue;
+        }
+        let key = ChainKey::Evm(chain_config.name.clone());
+        chains.start(key, ctx, store.clone()).await?;
+    }
+    // the delayed withdrawals that were held when the relayer stopped.
+    webb_relayer_tx_relay::evm::scheduled::resume_scheduled_relays(ctx)?;
+    Ok(())
+}
+
+/// Checks that every enabled contract of the enabled chains has code on
+/// chain, so that a wrong address is caught before watching it for nothing.
+///
+/// Returns an error listing all the contracts that are not deployed.
+async fn verify_contracts_deployed(ctx: &RelayerContext) -> crate::Result<()> {
+    let mut undeployed = Vec::new();
     for chain_config in ctx.config.evm.values() {
         if !chain_config.enabled {
             continue;
         }
-        let chain_name = &chain_config.name;
-        let chain_id = chain_config.chain_id;
-        let client = ctx.evm_provider(chain_id).await?;
-        // Time lag offset tip.
-        let block_confirmations = chain_config.block_confirmations;
-        let timelag_client =
-            Arc::new(TimeLag::new(client.clone(), block_confirmations));
-        tracing::debug!(
-            "Starting Background Services for ({}) chain.",
-            chain_name
+        let client = ctx.evm_provider(chain_config.chain_id).await?;
+        let addresses = chain_config
+            .contracts
+            .iter()
+            .map(|contract| match contract {
+                Contract::VAnchor(config) => config.common.address,
+                Contract::SignatureBridge(config) => config.common.address,
+            })
+            .filter(|address| chain_config.is_contract_enabled(*address));
+        let missing = find_undeployed_contracts(&*client, addresses).await?;
+        undeployed.extend(
+            missing
+                .into_iter()
+                .map(|address| (chain_config.name.clone(), address)),
         );
+    }
+    ctx.metrics
+        .lock()
+        .await
+        .undeployed_contracts
+        .set(undeployed.len() as f64);
+    if undeployed.is_empty() {
+        return Ok(());
+    }
+    Err(undeployed_contracts_error(&undeployed))
+}
 
-        for contract in &chain_config.contracts {
-            match contract {
-                Contract::VAnchor(config) => {
-                    start_vanchor_events_watcher(
-                        ctx,
-                        config,
-                        chain_id,
-                        timelag_client.clone(),
-                        store.clone(),
-                    )
-                    .await?;
-                }
-                Contract::SignatureBridge(config) => {
-                    start_signature_bridge_events_watcher(
-                        ctx,
-                        config,
-                        timelag_client.clone(),
-                        store.clone(),
-                    )
-                    .await?;
-                }
+/// Returns the addresses, among the given ones, without any code on chain.
+async fn find_undeployed_contracts<P: JsonRpcClient>(
+    client: &Provider<P>,
+    addresses: impl IntoIterator<Item = types::Address>,
+) -> crate::Result<Vec<types::Address>> {
+    let mut undeployed = Vec::new();
+    for address in addresses {
+        let code = client.get_code(address, None).await?;
+        if code.as_ref().is_empty() {
+            undeployed.push(address);
+        }
+    }
+    Ok(undeployed)
+}
+
+/// Lists the undeployed contracts, one `<chain>: <address>` per line.
+fn undeployed_contracts_error(
+    undeployed: &[(String, types::Address)],
+) -> crate::Error {
+    let list = undeployed
+        .iter()
+        .map(|(chain, address)| format!("  - {chain}: {address:?}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    crate::Error::UndeployedContracts(list)
+}
+
+/// How many times the leaf cache checkpoint of a VAnchor is checked, when
+/// its watcher starts, before it gives up.
+const MAX_CHECKPOINT_RECOVERY_ATTEMPTS: u32 = 5;
+
+/// Starts all background services for the given EVM chain.
+pub(crate) async fn ignite_chain(
+    ctx: &RelayerContext,
+    chain_config: &EvmChainConfig,
+    store: Arc<super::Store>,
+) -> crate::Result<()> {
+    let chain_name = &chain_config.name;
+    let chain_id = chain_config.chain_id;
+    let client = ctx.evm_provider(chain_id).await?;
+    // Time lag offset tip.
+    let block_confirmations = chain_config.block_confirmations;
+    let timelag_client =
+        Arc::new(TimeLag::new(client.clone(), block_confirmations));
+    tracing::debug!("Starting Background Services for ({}) chain.", chain_name);
+
+    // the watchers are numbered in the order they are started, to stagger
+    // their startups.
+    let mut watcher_index = 0;
+    for (contract, events_watcher) in watched_contracts(chain_config) {
+        let start_delay = StartDelay::new(
+            events_watcher.event_watcher_start_delay_secs,
+            watcher_index,
+        );
+        if events_watcher.enabled {
+            watcher_index += 1;
+        }
+        match contract {
+            Contract::VAnchor(config) => {
+                start_vanchor_events_watcher(
+                    ctx,
+                    chain_config,
+                    config,
+                    timelag_client.clone(),
+                    store.clone(),
+                    start_delay,
+                )
+                .await?;
+            }
+            Contract::SignatureBridge(config) => {
+                start_signature_bridge_events_watcher(
+                    ctx,
+                    chain_config,
+                    config,
+                    timelag_client.clone(),
+                    store.clone(),
+                    start_delay,
+                )
+                .await?;
             }
         }
-        // start the transaction queue after starting other tasks.
-        start_tx_queue(ctx.clone(), chain_config.chain_id, store.clone())?;
     }
+    // start the transaction queue after starting other tasks.
+    start_tx_queue(ctx.clone(), chain_config.chain_id, store.clone())?;
     Ok(())
 }
 
+/// The contracts of the chain to start the services of, with their events
+/// watcher config: the enabled ones, that are not static.
+fn watched_contracts(
+    chain_config: &EvmChainConfig,
+) -> Vec<(&Contract, &EventsWatcherConfig)> {
+    let mut watched = Vec::new();
+    for contract in &chain_config.contracts {
+        let (address, events_watcher) = match contract {
+            Contract::VAnchor(config) => {
+                (config.common.address, &config.events_watcher)
+            }
+            Contract::SignatureBridge(config) => {
+                (config.common.address, &config.events_watcher)
+            }
+        };
+        if !chain_config.is_contract_enabled(address) {
+            tracing::info!(
+                %address,
+                "Contract is not enabled, skipping its services",
+            );
+            continue;
+        }
+        if events_watcher.is_static() {
+            tracing::info!(
+                %address,
+                "Contract is static, serving its data without watching it",
+            );
+            continue;
+        }
+        watched.push((contract, events_watcher));
+    }
+    watched
+}
+
 /// Starts the event watcher for EVM VAnchor events.
 ///
 /// Returns Ok(()) if successful, or an error if not.
@@ -123,16 +288,20 @@ pub async fn ignite(
 /// # Arguments
 ///
 /// * `ctx` - RelayContext reference that holds the configuration
+/// * `chain_config` - The configuration of the chain of the contract
 /// * `config` - VAnchor contract configuration
 /// * `client` - EVM Chain api client
 /// * `store` -[Sled](https://sled.rs)-based database store
+/// * `start_delay` - How long the watcher waits before it starts
 async fn start_vanchor_events_watcher(
     ctx: &RelayerContext,
+    chain_config: &EvmChainConfig,
     config: &VAnchorContractConfig,
-    chain_id: u32,
     client: Arc<TimeLagClient>,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
+    let chain_id = chain_config.chain_id;
     if !config.events_watcher.enabled {
         tracing::warn!(
             "VAnchor events watcher is disabled for ({}).",
@@ -143,259 +312,855 @@ async fn start_vanchor_events_watcher(
     let wrapper = VAnchorContractWrapper::new(
         config.clone(),
         ctx.config.clone(), // the original config to access all networks.
+        &chain_config.contract_abi_override,
         client.clone(),
-    );
-    let mut shutdown_signal = ctx.shutdown_signal();
+    )?;
+    if wrapper.has_custom_abi() {
+        ctx.metrics.lock().await.custom_abi_loaded.inc();
+    }
     let contract_address = config.common.address;
     let my_ctx = ctx.clone();
     let my_config = config.clone();
-    let task = async move {
-        tracing::debug!(
-            "VAnchor events watcher for ({}) Started.",
-            contract_address,
-        );
-        let contract_watcher = VAnchorContractWatcher::default();
-        let proposal_signing_backend = make_proposal_signing_backend(
-            &my_ctx,
-            store.clone(),
-            TypedChainId::Evm(chain_id),
-            my_config.linked_anchors,
-            my_config.proposal_signing_backend,
-        )
-        .await?;
-        tracing::debug!(
-            %chain_id,
-            %contract_address,
-            "Fetching the Zero Hash from the contract",
-        );
-        let zero_hash = wrapper.contract.get_zero_hash(0).call().await?;
-        tracing::debug!(
-            %chain_id,
-            %contract_address,
-            %zero_hash,
-            "Found the Zero Hash",
-        );
-        let mut zero_hash_bytes = [0u8; 32];
-        zero_hash.to_big_endian(&mut zero_hash_bytes);
-
-        let proposals_queue = queue::mem::InMemoryProposalsQueue::new();
-        let time_delay_policy = {
-            let defaults = SmartAnchorUpdatesConfig::default();
-            let v = &my_config.smart_anchor_updates;
-            let initial_delay = v
-                .initial_time_delay
-                .or(defaults.initial_time_delay)
-                .expect("initial time delay is set by default");
-            let min_delay = v
-                .min_time_delay
-                .or(defaults.min_time_delay)
-                .expect("min time delay is set by default");
-            let max_delay = v
-                .max_time_delay
-                .or(defaults.max_time_delay)
-                .expect("max time delay is set by default");
-            let window_size = v
-                .time_delay_window_size
-                .or(defaults.time_delay_window_size)
-                .expect("time delay window size is set by default");
-
-            policy::TimeDelayPolicy::builder()
-                .initial_delay(initial_delay)
-                .min_delay(min_delay)
-                .max_delay(max_delay)
-                .window_size(window_size)
-                .build()
-        };
-
-        if my_config.smart_anchor_updates.enabled {
-            tracing::info!(
+    let task = move || {
+        let my_ctx = my_ctx.clone();
+        let my_config = my_config.clone();
+        let wrapper = wrapper.clone();
+        let client = client.clone();
+        let store = store.clone();
+        let mut shutdown_signal = my_ctx.shutdown_signal();
+        async move {
+            start_delay.wait(&my_ctx.metrics).await;
+            tracing::debug!(
+                "VAnchor events watcher for ({}) Started.",
+                contract_address,
+            );
+            let contract_watcher = VAnchorContractWatcher::default();
+            let proposal_signing_backend = make_proposal_signing_backend(
+                &my_ctx,
+                store.clone(),
+                TypedChainId::Evm(chain_id),
+                my_config.linked_anchors.clone(),
+                my_config.proposal_signing_backend,
+            )
+            .await?;
+            tracing::debug!(
                 %chain_id,
                 %contract_address,
-                "Smart Anchor Updates enabled",
+                "Fetching the Zero Hash from the contract",
             );
-        } else {
-            tracing::info!(
-                chain_id,
+            let zero_hash = wrapper.contract.get_zero_hash(0).call().await?;
+            tracing::debug!(
+                %chain_id,
                 %contract_address,
-                "Smart Anchor Updates disabled",
+                %zero_hash,
+                "Found the Zero Hash",
             );
-        }
+            let mut zero_hash_bytes = [0u8; 32];
+            zero_hash.to_big_endian(&mut zero_hash_bytes);
 
-        let enqueue_policy = my_config.smart_anchor_updates.enabled.then_some(
-            (policy::AlwaysHigherNoncePolicy, time_delay_policy.clone()),
-        );
-        let dequeue_policy = my_config
-            .smart_anchor_updates
-            .enabled
-            .then_some(time_delay_policy);
-
-        let metrics = my_ctx.metrics.clone();
-        match proposal_signing_backend {
-            ProposalSigningBackendSelector::Dkg(backend) => {
-                let bridge_registry =
-                    DkgBridgeRegistryBackend::new(backend.client.clone());
-                let deposit_handler = VAnchorDepositHandler::builder()
-                    .chain_id(chain_id)
-                    .store(store.clone())
-                    .bridge_registry_backend(bridge_registry)
-                    .proposals_queue(proposals_queue.clone())
-                    .policy(enqueue_policy)
-                    .build();
-                let leaves_handler = VAnchorLeavesHandler::new(
-                    chain_id.into(),
-                    contract_address,
-                    store.clone(),
-                    zero_hash_bytes.to_vec(),
-                )?;
-                let encrypted_output_handler =
-                    VAnchorEncryptedOutputHandler::new(chain_id.into());
-                let vanchor_watcher_task = contract_watcher.run(
-                    client,
-                    store,
-                    wrapper,
-                    vec![
-                        Box::new(deposit_handler),
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    &my_ctx,
+            // make sure the leaf cache checkpoint did not get ahead of the
+            // leaves we actually have, before loading them in the handlers.
+            let history_store_key = ResourceId::new(
+                TargetSystem::new_contract_address(
+                    contract_address.to_fixed_bytes(),
+                ),
+                TypedChainId::Evm(chain_id),
+            );
+            // a failed check must not keep the watcher from starting, it
+            // then resumes from the checkpoint as it is.
+            let mut attempts = 0;
+            while let Err(e) = recover_leaf_cache_checkpoint(
+                store.as_ref(),
+                history_store_key,
+                &wrapper,
+                wrapper.deployed_at().as_u64(),
+            )
+            .await
+            {
+                attempts += 1;
+                if attempts >= MAX_CHECKPOINT_RECOVERY_ATTEMPTS {
+                    tracing::warn!(
+                        %chain_id,
+                        %contract_address,
+                        "Failed to check the leaf cache checkpoint, \
+                         resuming from it: {e}",
+                    );
+                    break;
+                }
+                tracing::debug!(
+                    %chain_id,
+                    %contract_address,
+                    attempts,
+                    "Failed to check the leaf cache checkpoint, retrying: {e}",
                 );
+                tokio::time::sleep(Duration::from_secs(1 << attempts)).await;
+            }
 
-                let proposals_queue_task = queue::run(
-                    proposals_queue,
-                    dequeue_policy,
-                    backend,
-                    metrics,
+            let proposals_queue = queue::mem::InMemoryProposalsQueue::new();
+            let time_delay_policy =
+                time_delay_policy(&my_config.smart_anchor_updates, &my_ctx);
+
+            if my_config.smart_anchor_updates.enabled {
+                tracing::info!(
+                    %chain_id,
+                    %contract_address,
+                    "Smart Anchor Updates enabled",
+                );
+            } else {
+                tracing::info!(
+                    chain_id,
+                    %contract_address,
+                    "Smart Anchor Updates disabled",
                 );
+            }
+
+            // holds back the updates of each linked anchor for a while
+            // after one got proposed to it.
+            let cooldown_policy =
+                my_config.anchor_update_cooldown_secs.map(|secs| {
+                    policy::EdgeCooldownPolicy::builder()
+                        .cooldown(Duration::from_secs(secs))
+                        .clock(my_ctx.clock().clone())
+                        .build()
+                });
+            let enqueue_policy = (
+                my_config.smart_anchor_updates.enabled.then_some((
+                    policy::AlwaysHigherNoncePolicy,
+                    time_delay_policy.clone(),
+                )),
+                // the updates held back coalesce to the latest root.
+                cooldown_policy
+                    .is_some()
+                    .then_some(policy::AlwaysHigherNoncePolicy),
+            );
+            let dequeue_policy = (
+                my_config
+                    .smart_anchor_updates
+                    .enabled
+                    .then_some(time_delay_policy),
+                cooldown_policy,
+            );
 
-                tokio::select! {
-                    _ = proposals_queue_task => {
-                        tracing::warn!(
-                            "Proposals queue task stopped for ({})",
-                            contract_address,
-                        );
-                    },
-                    _ = vanchor_watcher_task => {
-                        tracing::warn!(
-                            "VAnchor watcher task stopped for ({})",
-                            contract_address,
-                        );
-                    },
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping VAnchor watcher for ({})",
-                            contract_address,
-                        );
-                    },
+            // checks the edges the linked anchors have of this anchor, and
+            // proposes the updates they missed.
+            let anchor_state_sync =
+                my_config.edge_sync_interval_secs.map(|secs| {
+                    CrossChainAnchorStateSync::builder()
+                        .store(store.clone())
+                        .src_resource_id(history_store_key)
+                        .linked_anchors(resolve_linked_anchors(
+                            history_store_key,
+                            my_config
+                                .linked_anchors
+                                .clone()
+                                .unwrap_or_default(),
+                        ))
+                        .proposals_queue(proposals_queue.clone())
+                        .policy(enqueue_policy.clone())
+                        .root_source(VAnchorRootSource::new(
+                            client.clone(),
+                            wrapper.contract.clone(),
+                            store.clone(),
+                        ))
+                        .neighbor_edges(VAnchorNeighborRoots::new(
+                            my_ctx.clone(),
+                        ))
+                        .interval(Duration::from_secs(secs.max(1)))
+                        .clock(my_ctx.clock().clone())
+                        .build()
+                });
+            let sync_metrics = my_ctx.metrics.clone();
+            let anchor_state_sync_task = async move {
+                match anchor_state_sync {
+                    Some(sync) => sync.run(sync_metrics).await,
+                    None => std::future::pending().await,
                 }
-            }
-            ProposalSigningBackendSelector::Mocked(backend) => {
-                let bridge_registry =
-                    MockedBridgeRegistryBackend::builder().build();
-                let deposit_handler = VAnchorDepositHandler::builder()
-                    .chain_id(chain_id)
-                    .store(store.clone())
-                    .bridge_registry_backend(bridge_registry)
-                    .proposals_queue(proposals_queue.clone())
-                    .policy(enqueue_policy)
-                    .build();
-                let leaves_handler = VAnchorLeavesHandler::new(
+            };
+
+            let executed_proposals_handler =
+                VAnchorExecutedProposalsHandler::new(
                     chain_id.into(),
                     contract_address,
-                    store.clone(),
-                    zero_hash_bytes.to_vec(),
-                )?;
-                let encrypted_output_handler =
-                    VAnchorEncryptedOutputHandler::new(chain_id.into());
-                let vanchor_watcher_task = contract_watcher.run(
-                    client,
-                    store,
-                    wrapper,
-                    vec![
-                        Box::new(deposit_handler),
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    &my_ctx,
+                    my_config.linked_anchors.unwrap_or_default(),
                 );
+            let metrics = my_ctx.metrics.clone();
+            let root_source = VAnchorRootSource::new(
+                client.clone(),
+                wrapper.contract.clone(),
+                store.clone(),
+            );
+            match proposal_signing_backend {
+                ProposalSigningBackendSelector::Dkg(backend) => {
+                    let bridge_registry =
+                        DkgBridgeRegistryBackend::new(backend.client.clone());
+                    let deposit_handler = VAnchorDepositHandler::builder()
+                        .chain_id(chain_id)
+                        .store(store.clone())
+                        .bridge_registry_backend(bridge_registry)
+                        .proposals_queue(proposals_queue.clone())
+                        .policy(enqueue_policy)
+                        .neighbor_roots(VAnchorNeighborRoots::new(
+                            my_ctx.clone(),
+                        ))
+                        .build();
+                    let leaves_handler = VAnchorLeavesHandler::new(
+                        chain_id.into(),
+                        contract_address,
+                        store.clone(),
+                        zero_hash_bytes.to_vec(),
+                    )?;
+                    let encrypted_output_handler =
+                        VAnchorEncryptedOutputHandler::new(chain_id.into());
+                    let vanchor_watcher_task = contract_watcher.run(
+                        client,
+                        store,
+                        wrapper,
+                        vec![
+                            Box::new(deposit_handler),
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                            Box::new(executed_proposals_handler),
+                        ],
+                        &my_ctx,
+                    );
 
-                let proposals_queue_task = queue::run(
-                    proposals_queue,
-                    dequeue_policy,
-                    backend,
-                    metrics,
-                );
+                    let proposals_queue_task = queue::run(
+                        proposals_queue,
+                        dequeue_policy,
+                        backend,
+                        root_source,
+                        my_config.root_history_depth,
+                        metrics,
+                        my_ctx.clock().clone(),
+                    );
 
-                tokio::select! {
-                    _ = proposals_queue_task => {
-                        tracing::warn!(
-                            "Proposals queue task stopped for ({})",
-                            contract_address,
-                        );
-                    },
-                    _ = vanchor_watcher_task => {
-                        tracing::warn!(
-                            "VAnchor watcher task stopped for ({})",
-                            contract_address,
-                        );
-                    },
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping VAnchor watcher for ({})",
-                            contract_address,
-                        );
-                    },
+                    tokio::select! {
+                        _ = proposals_queue_task => {
+                            tracing::warn!(
+                                "Proposals queue task stopped for ({})",
+                                contract_address,
+                            );
+                        },
+                        result = vanchor_watcher_task => {
+                            tracing::warn!(
+                                "VAnchor watcher task stopped for ({})",
+                                contract_address,
+                            );
+                            result?;
+                        },
+                        result = anchor_state_sync_task => {
+                            tracing::warn!(
+                                "Anchor state sync task stopped for ({})",
+                                contract_address,
+                            );
+                            result?;
+                        },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping VAnchor watcher for ({})",
+                                contract_address,
+                            );
+                        },
+                    }
                 }
-            }
-            ProposalSigningBackendSelector::None => {
-                let leaves_handler = VAnchorLeavesHandler::new(
-                    chain_id.into(),
-                    contract_address,
-                    store.clone(),
-                    zero_hash_bytes.to_vec(),
-                )?;
-                let encrypted_output_handler =
-                    VAnchorEncryptedOutputHandler::new(chain_id.into());
-                let vanchor_watcher_task = contract_watcher.run(
-                    client,
-                    store,
-                    wrapper,
-                    vec![
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    &my_ctx,
-                );
-                tokio::select! {
-                    _ = vanchor_watcher_task => {
-                        tracing::warn!(
-                            "VAnchor watcher task stopped for ({})",
-                            contract_address,
-                        );
-                    },
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping VAnchor watcher for ({})",
-                            contract_address,
-                        );
-                    },
+                ProposalSigningBackendSelector::Mocked(backend) => {
+                    let bridge_registry =
+                        MockedBridgeRegistryBackend::builder().build();
+                    let deposit_handler = VAnchorDepositHandler::builder()
+                        .chain_id(chain_id)
+                        .store(store.clone())
+                        .bridge_registry_backend(bridge_registry)
+                        .proposals_queue(proposals_queue.clone())
+                        .policy(enqueue_policy)
+                        .neighbor_roots(VAnchorNeighborRoots::new(
+                            my_ctx.clone(),
+                        ))
+                        .build();
+                    let leaves_handler = VAnchorLeavesHandler::new(
+                        chain_id.into(),
+                        contract_address,
+                        store.clone(),
+                        zero_hash_bytes.to_vec(),
+                    )?;
+                    let encrypted_output_handler =
+                        VAnchorEncryptedOutputHandler::new(chain_id.into());
+                    let vanchor_watcher_task = contract_watcher.run(
+                        client,
+                        store,
+                        wrapper,
+                        vec![
+                            Box::new(deposit_handler),
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                            Box::new(executed_proposals_handler),
+                        ],
+                        &my_ctx,
+                    );
+
+                    let proposals_queue_task = queue::run(
+                        proposals_queue,
+                        dequeue_policy,
+                        backend,
+                        root_source,
+                        my_config.root_history_depth,
+                        metrics,
+                        my_ctx.clock().clone(),
+                    );
+
+                    tokio::select! {
+                        _ = proposals_queue_task => {
+                            tracing::warn!(
+                                "Proposals queue task stopped for ({})",
+                                contract_address,
+                            );
+                        },
+                        result = vanchor_watcher_task => {
+                            tracing::warn!(
+                                "VAnchor watcher task stopped for ({})",
+                                contract_address,
+                            );
+                            result?;
+                        },
+                        result = anchor_state_sync_task => {
+                            tracing::warn!(
+                                "Anchor state sync task stopped for ({})",
+                                contract_address,
+                            );
+                            result?;
+                        },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping VAnchor watcher for ({})",
+                                contract_address,
+                            );
+                        },
+                    }
                 }
-            }
-        };
+                ProposalSigningBackendSelector::None => {
+                    let leaves_handler = VAnchorLeavesHandler::new(
+                        chain_id.into(),
+                        contract_address,
+                        store.clone(),
+                        zero_hash_bytes.to_vec(),
+                    )?;
+                    let encrypted_output_handler =
+                        VAnchorEncryptedOutputHandler::new(chain_id.into());
+                    let vanchor_watcher_task = contract_watcher.run(
+                        client,
+                        store,
+                        wrapper,
+                        vec![
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                            Box::new(executed_proposals_handler),
+                        ],
+                        &my_ctx,
+                    );
+                    tokio::select! {
+                        result = vanchor_watcher_task => {
+                            tracing::warn!(
+                                "VAnchor watcher task stopped for ({})",
+                                contract_address,
+                            );
+                            result?;
+                        },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping VAnchor watcher for ({})",
+                                contract_address,
+                            );
+                        },
+                    }
+                }
+            };
 
-        crate::Result::Ok(())
+            crate::Result::Ok(())
+        }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks().spawn(
+        format!("VAnchor watcher ({chain_id}, {contract_address:?})"),
+        task,
+    );
     Ok(())
 }
 
+/// Builds the time delay policy of the anchor update proposals, falling back
+/// to the defaults for the values missing from the config.
+fn time_delay_policy(
+    config: &SmartAnchorUpdatesConfig,
+    ctx: &RelayerContext,
+) -> policy::TimeDelayPolicy {
+    let defaults = SmartAnchorUpdatesConfig::default();
+    let initial_delay = config
+        .initial_time_delay
+        .or(defaults.initial_time_delay)
+        .expect("initial time delay is set by default");
+    let min_delay = config
+        .min_time_delay
+        .or(defaults.min_time_delay)
+        .expect("min time delay is set by default");
+    let max_delay = config
+        .max_time_delay
+        .or(defaults.max_time_delay)
+        .expect("max time delay is set by default");
+    let window_size = config
+        .time_delay_window_size
+        .or(defaults.time_delay_window_size)
+        .expect("time delay window size is set by default");
+
+    policy::TimeDelayPolicy::builder()
+        .initial_delay(initial_delay)
+        .min_delay(min_delay)
+        .max_delay(max_delay)
+        .window_size(window_size)
+        .clock(ctx.clock().clone())
+        .build()
+}
+
+/// The outcome of replaying a single event, see [`replay_event`].
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// The contract that emitted the event.
+    pub contract_address: types::Address,
+    /// The outcome of every event handler of the contract, by name.
+    /// `Ok(false)` means the handler is not interested in the event.
+    pub handlers: Vec<(&'static str, crate::Result<bool>)>,
+    /// The number of anchor update proposals queued while handling the
+    /// event. They are only counted, never sent.
+    pub queued_proposals: usize,
+}
+
+/// Re-handles a single event emitted by one of the contracts configured on
+/// the given EVM chain, as its events watcher would.
+///
+/// Every handler gets the event once, without any retries, and writes to the
+/// store as usual. The block checkpoint of the watcher is left as is.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration
+/// * `store` -[Sled](https://sled.rs)-based database store
+/// * `chain` - The name or the chain id of the chain
+/// * `tx_hash` - The hash of the transaction that emitted the event
+/// * `log_index` - The index of the log in its block
+pub async fn replay_event(
+    ctx: &RelayerContext,
+    store: Arc<super::Store>,
+    chain: &str,
+    tx_hash: types::H256,
+    log_index: u64,
+) -> crate::Result<ReplayReport> {
+    let chain_config = ctx
+        .config
+        .evm
+        .iter()
+        .find(|(chain_id, c)| {
+            chain_id.as_str() == chain || c.name.eq_ignore_ascii_case(chain)
+        })
+        .map(|(_, c)| c)
+        .ok_or_else(|| crate::Error::ChainNotFound {
+            chain_id: chain.to_string(),
+        })?;
+    let chain_id = chain_config.chain_id;
+    let client = ctx.evm_provider(chain_id).await?;
+    let log = fetch_log(client.as_ref(), tx_hash, log_index.into()).await?;
+    let contract_address = log.address;
+    let timelag_client = Arc::new(TimeLag::new(
+        client.clone(),
+        chain_config.block_confirmations,
+    ));
+    let metrics = ctx.metrics.clone();
+    let contract = chain_config
+        .contracts
+        .iter()
+        .find(|c| match c {
+            Contract::VAnchor(c) => c.common.address == contract_address,
+            Contract::SignatureBridge(c) => {
+                c.common.address == contract_address
+            }
+        })
+        .ok_or(crate::Error::Generic(
+            "the log was not emitted by any of the configured contracts",
+        ))?;
+    let report = match contract {
+        Contract::VAnchor(config) => {
+            let wrapper = VAnchorContractWrapper::new(
+                config.clone(),
+                ctx.config.clone(),
+                &chain_config.contract_abi_override,
+                timelag_client,
+            )?;
+            let zero_hash = wrapper.contract.get_zero_hash(0).call().await?;
+            let mut zero_hash_bytes = [0u8; 32];
+            zero_hash.to_big_endian(&mut zero_hash_bytes);
+            let proposals_queue = queue::mem::InMemoryProposalsQueue::new();
+            let enqueue_policy =
+                config.smart_anchor_updates.enabled.then_some((
+                    policy::AlwaysHigherNoncePolicy,
+                    time_delay_policy(&config.smart_anchor_updates, ctx),
+                ));
+            let proposal_signing_backend = make_proposal_signing_backend(
+                ctx,
+                store.clone(),
+                TypedChainId::Evm(chain_id),
+                config.linked_anchors.clone(),
+                config.proposal_signing_backend.clone(),
+            )
+            .await?;
+            let mut names = Vec::new();
+            let mut handlers: Vec<EventHandlerFor<VAnchorContractWatcher>> =
+                Vec::new();
+            match proposal_signing_backend {
+                ProposalSigningBackendSelector::Dkg(backend) => {
+                    let bridge_registry =
+                        DkgBridgeRegistryBackend::new(backend.client.clone());
+                    names.push("deposit");
+                    handlers.push(Box::new(
+                        VAnchorDepositHandler::builder()
+                            .chain_id(chain_id)
+                            .store(store.clone())
+                            .bridge_registry_backend(bridge_registry)
+                            .proposals_queue(proposals_queue.clone())
+                            .policy(enqueue_policy)
+                            .neighbor_roots(VAnchorNeighborRoots::new(
+                                ctx.clone(),
+                            ))
+                            .build(),
+                    ));
+                }
+                ProposalSigningBackendSelector::Mocked(_) => {
+                    let bridge_registry =
+                        MockedBridgeRegistryBackend::builder().build();
+                    names.push("deposit");
+                    handlers.push(Box::new(
+                        VAnchorDepositHandler::builder()
+                            .chain_id(chain_id)
+                            .store(store.clone())
+                            .bridge_registry_backend(bridge_registry)
+                            .proposals_queue(proposals_queue.clone())
+                            .policy(enqueue_policy)
+                            .neighbor_roots(VAnchorNeighborRoots::new(
+                                ctx.clone(),
+                            ))
+                            .build(),
+                    ));
+                }
+                ProposalSigningBackendSelector::None => {}
+            }
+            names.push("leaves");
+            handlers.push(Box::new(VAnchorLeavesHandler::new(
+                chain_id.into(),
+                config.common.address,
+                store.clone(),
+                zero_hash_bytes.to_vec(),
+            )?));
+            names.push("encrypted_output");
+            handlers.push(Box::new(VAnchorEncryptedOutputHandler::new(
+                chain_id.into(),
+            )));
+            names.push("executed_proposals");
+            handlers.push(Box::new(VAnchorExecutedProposalsHandler::new(
+                chain_id.into(),
+                config.common.address,
+                config.linked_anchors.clone().unwrap_or_default(),
+            )));
+            let outcomes = VAnchorContractWatcher::default()
+                .replay_log(store, &wrapper, &handlers, log, metrics)
+                .await?;
+            ReplayReport {
+                contract_address,
+                handlers: names.into_iter().zip(outcomes).collect(),
+                queued_proposals: proposals_queue.len()?,
+            }
+        }
+        Contract::SignatureBridge(config) => {
+            let wrapper = SignatureBridgeContractWrapper::new(
+                config.clone(),
+                &chain_config.contract_abi_override,
+                timelag_client,
+            )?;
+            let handlers: Vec<EventHandlerFor<SignatureBridgeContractWatcher>> =
+                vec![Box::new(
+                SignatureBridgeGovernanceOwnershipTransferredHandler::default(),
+            )];
+            let outcomes = SignatureBridgeContractWatcher::default()
+                .replay_log(store, &wrapper, &handlers, log, metrics)
+                .await?;
+            ReplayReport {
+                contract_address,
+                handlers: vec!["governance_ownership_transferred"]
+                    .into_iter()
+                    .zip(outcomes)
+                    .collect(),
+                queued_proposals: 0,
+            }
+        }
+    };
+    Ok(report)
+}
+
+/// Imports a signed leaf snapshot, as served by
+/// `/api/v1/snapshot/:chain_id/:contract`, into the leaf cache.
+///
+/// The leaves are always checked against the snapshot header. Optionally,
+/// the snapshot has to be signed by `verify_signer`, and the Merkle root of
+/// its leaves has to be known by the contract on chain.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration
+/// * `store` -[Sled](https://sled.rs)-based database store
+/// * `snapshot` - The encoded leaf snapshot
+/// * `verify_signer` - The relayer the snapshot has to be signed by
+/// * `verify_root_onchain` - Whether to check the root against the contract
+pub async fn import_leaf_snapshot(
+    ctx: &RelayerContext,
+    store: &super::Store,
+    snapshot: &[u8],
+    verify_signer: Option<types::Address>,
+    verify_root_onchain: bool,
+) -> crate::Result<LeafSnapshotHeader> {
+    let snapshot = LeafSnapshot::from_bytes(snapshot)?;
+    let header = snapshot.header;
+    if let Some(expected) = verify_signer {
+        if snapshot.signer()? != expected {
+            return Err(crate::Error::Generic(
+                "the leaf snapshot is not signed by the expected relayer",
+            ));
+        }
+    }
+    let chain_config = ctx
+        .config
+        .evm
+        .get(&header.chain_id.to_string())
+        .ok_or_else(|| crate::Error::ChainNotFound {
+            chain_id: header.chain_id.to_string(),
+        })?;
+    let is_configured = chain_config.contracts.iter().any(|c| {
+        matches!(c, Contract::VAnchor(c) if c.common.address == header.contract)
+    });
+    if !is_configured {
+        return Err(crate::Error::Generic(
+            "the leaf snapshot is for a VAnchor contract that is not configured",
+        ));
+    }
+    if verify_root_onchain {
+        let client = ctx.evm_provider(header.chain_id).await?;
+        let contract = VAnchorContract::new(header.contract, Arc::new(client));
+        let zero_hash = contract.get_zero_hash(0).call().await?;
+        let mut zero_hash_bytes = [0u8; 32];
+        zero_hash.to_big_endian(&mut zero_hash_bytes);
+        let root = merkle_root(&snapshot.leaves, &zero_hash_bytes)?;
+        if root != header.merkle_root {
+            return Err(crate::Error::Generic(
+                "the leaves do not match the Merkle root of the leaf snapshot",
+            ));
+        }
+        let is_known_root = contract
+            .is_known_root(types::U256::from_big_endian(&root))
+            .call()
+            .await?;
+        if !is_known_root {
+            return Err(crate::Error::Generic(
+                "the Merkle root of the leaf snapshot is not known on chain",
+            ));
+        }
+    }
+    let history_store_key = ResourceId::new(
+        TargetSystem::new_contract_address(header.contract.to_fixed_bytes()),
+        TypedChainId::Evm(header.chain_id),
+    );
+    store.insert_leaves_and_last_deposit_block_number(
+        history_store_key,
+        &snapshot.indexed_leaves(),
+        header.last_block,
+    )?;
+    Ok(header)
+}
+
+/// The anchor update proposal forced by [`force_anchor_update`].
+#[derive(Debug)]
+pub struct ForcedAnchorUpdate {
+    /// The anchor the update is from.
+    pub src_resource_id: ResourceId,
+    /// The linked anchor the update is for.
+    pub target_resource_id: ResourceId,
+    /// The current root of the source anchor.
+    pub merkle_root: [u8; 32],
+    /// The nonce of the proposal, the index of the last leaf of the source
+    /// anchor.
+    pub nonce: u32,
+    /// Whether the signing backend took the proposal, it only takes those
+    /// it can sign.
+    pub signed: bool,
+}
+
+/// Signs and submits the anchor update proposal of the linked anchor on
+/// `dest_chain` at the current root of the source anchor, as the deposit
+/// handler would after a deposit.
+///
+/// The proposal skips the proposals queue and its policies, so it gets
+/// signed right away, even if the linked anchor already has the root.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration
+/// * `store` -[Sled](https://sled.rs)-based database store
+/// * `src_chain` - The name or the chain id of the EVM chain of the source
+///   anchor
+/// * `src_anchor` - The address of the source anchor
+/// * `dest_chain` - The name or the chain id of the chain of the linked
+///   anchor, EVM or Substrate
+pub async fn force_anchor_update(
+    ctx: &RelayerContext,
+    store: Arc<super::Store>,
+    src_chain: &str,
+    src_anchor: types::Address,
+    dest_chain: &str,
+) -> crate::Result<ForcedAnchorUpdate> {
+    let is_chain = |id: &String, name: &str, chain: &str| {
+        id.as_str() == chain || name.eq_ignore_ascii_case(chain)
+    };
+    let chain_config = ctx
+        .config
+        .evm
+        .iter()
+        .find(|(id, c)| is_chain(id, &c.name, src_chain))
+        .map(|(_, c)| c)
+        .ok_or_else(|| crate::Error::ChainNotFound {
+            chain_id: src_chain.to_string(),
+        })?;
+    let dest_chain_id = ctx
+        .config
+        .evm
+        .iter()
+        .find(|(id, c)| is_chain(id, &c.name, dest_chain))
+        .map(|(_, c)| TypedChainId::Evm(c.chain_id))
+        .or_else(|| {
+            ctx.config
+                .substrate
+                .iter()
+                .find(|(id, c)| is_chain(id, &c.name, dest_chain))
+                .map(|(_, c)| TypedChainId::Substrate(c.chain_id))
+        })
+        .ok_or_else(|| crate::Error::ChainNotFound {
+            chain_id: dest_chain.to_string(),
+        })?;
+    let chain_id = chain_config.chain_id;
+    let config = chain_config
+        .contracts
+        .iter()
+        .find_map(|c| match c {
+            Contract::VAnchor(c) if c.common.address == src_anchor => Some(c),
+            _ => None,
+        })
+        .ok_or(crate::Error::Generic(
+            "the source anchor is not a configured VAnchor contract",
+        ))?;
+    let client = ctx.evm_provider(chain_id).await?;
+    let contract = VAnchorContract::new(src_anchor, Arc::new(client));
+    let merkle_root: [u8; 32] = contract.get_last_root().call().await?.into();
+    // the nonce of the proposals is the index of the leaf that got the
+    // anchor to its root.
+    let nonce = contract.next_index().call().await?.saturating_sub(1);
+    let src_resource_id = ResourceId::new(
+        TargetSystem::new_contract_address(src_anchor.to_fixed_bytes()),
+        TypedChainId::Evm(chain_id),
+    );
+    let backend = make_proposal_signing_backend(
+        ctx,
+        store,
+        TypedChainId::Evm(chain_id),
+        config.linked_anchors.clone(),
+        config.proposal_signing_backend.clone(),
+    )
+    .await?;
+    let linked_anchors = match &backend {
+        ProposalSigningBackendSelector::Dkg(backend) => {
+            DkgBridgeRegistryBackend::new(backend.client.clone())
+                .config_or_dkg_bridges(&config.linked_anchors, &src_resource_id)
+                .await?
+        }
+        _ => config.linked_anchors.clone().unwrap_or_default(),
+    };
+    let target_resource_id = resolve_linked_anchors(
+        src_resource_id,
+        linked_anchors,
+    )
+    .into_iter()
+    .find(|target| target.typed_chain_id() == dest_chain_id)
+    .ok_or(crate::Error::Generic(
+        "the source anchor has no linked anchor on the destination chain",
+    ))?;
+    let metrics = ctx.metrics.clone();
+    let signed = match target_resource_id.target_system() {
+        TargetSystem::ContractAddress(_) => {
+            let proposal = proposal_handler::evm_anchor_update_proposal(
+                merkle_root,
+                nonce,
+                target_resource_id,
+                src_resource_id,
+            );
+            sign_proposal(&backend, &proposal, metrics).await?
+        }
+        TargetSystem::Substrate(_) => {
+            let proposal = proposal_handler::substrate_anchor_update_proposal(
+                merkle_root,
+                nonce,
+                target_resource_id,
+                src_resource_id,
+            );
+            sign_proposal(&backend, &proposal, metrics).await?
+        }
+    };
+    Ok(ForcedAnchorUpdate {
+        src_resource_id,
+        target_resource_id,
+        merkle_root,
+        nonce,
+        signed,
+    })
+}
+
+/// Hands the proposal to the signing backend, if it can sign it.
+async fn sign_proposal(
+    backend: &ProposalSigningBackendSelector,
+    proposal: &(impl ProposalTrait + Sync + Send + 'static),
+    metrics: Arc<Mutex<Metrics>>,
+) -> crate::Result<bool> {
+    async fn sign<PB: ProposalSigningBackend>(
+        backend: &PB,
+        proposal: &(impl ProposalTrait + Sync + Send + 'static),
+        metrics: Arc<Mutex<Metrics>>,
+    ) -> crate::Result<bool> {
+        if !backend.can_handle_proposal(proposal).await? {
+            return Ok(false);
+        }
+        backend.handle_proposal(proposal, metrics).await?;
+        Ok(true)
+    }
+    match backend {
+        ProposalSigningBackendSelector::Dkg(backend) => {
+            sign(backend, proposal, metrics).await
+        }
+        ProposalSigningBackendSelector::Mocked(backend) => {
+            sign(backend, proposal, metrics).await
+        }
+        ProposalSigningBackendSelector::None => Err(crate::Error::Generic(
+            "the source anchor has no proposal signing backend",
+        )),
+    }
+}
+
 /// Starts the event watcher for Signature Bridge contract.
 pub async fn start_signature_bridge_events_watcher(
     ctx: &RelayerContext,
+    chain_config: &EvmChainConfig,
     config: &SignatureBridgeContractConfig,
     client: Arc<TimeLagClient>,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
     if !config.events_watcher.enabled {
         tracing::warn!(
@@ -404,59 +1169,83 @@ pub async fn start_signature_bridge_events_watcher(
         );
         return Ok(());
     }
-    let mut shutdown_signal = ctx.shutdown_signal();
     let contract_address = config.common.address;
 
-    let wrapper =
-        SignatureBridgeContractWrapper::new(config.clone(), client.clone());
+    let wrapper = SignatureBridgeContractWrapper::new(
+        config.clone(),
+        &chain_config.contract_abi_override,
+        client.clone(),
+    )?;
+    if wrapper.has_custom_abi() {
+        ctx.metrics.lock().await.custom_abi_loaded.inc();
+    }
     let metrics = ctx.metrics.clone();
     let my_ctx = ctx.clone();
-    let task = async move {
-        tracing::debug!(
-            "Signature Bridge watcher for ({}) Started.",
-            contract_address
-        );
-        let bridge_contract_watcher = SignatureBridgeContractWatcher::default();
-        let governance_transfer_handler =
-            SignatureBridgeGovernanceOwnershipTransferredHandler::default();
-        let events_watcher_task = EventWatcher::run(
-            &bridge_contract_watcher,
-            client.clone(),
-            store.clone(),
-            wrapper.clone(),
-            vec![Box::new(governance_transfer_handler)],
-            &my_ctx,
-        );
-        let cmd_handler_task = BridgeWatcher::run(
-            &bridge_contract_watcher,
-            client,
-            store,
-            wrapper,
-            metrics.clone(),
-        );
-        tokio::select! {
-            _ = events_watcher_task => {
-                tracing::warn!(
-                    "signature bridge events watcher task stopped for ({})",
-                    contract_address
-                );
-            },
-            _ = cmd_handler_task => {
-                tracing::warn!(
-                    "signature bridge cmd handler task stopped for ({})",
-                    contract_address
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping Signature Bridge watcher for ({})",
-                    contract_address,
-                );
-            },
+    let task = move || {
+        let metrics = metrics.clone();
+        let my_ctx = my_ctx.clone();
+        let client = client.clone();
+        let store = store.clone();
+        let wrapper = wrapper.clone();
+        let mut shutdown_signal = my_ctx.shutdown_signal();
+        async move {
+            start_delay.wait(&metrics).await;
+            tracing::debug!(
+                "Signature Bridge watcher for ({}) Started.",
+                contract_address
+            );
+            let bridge_contract_watcher = SignatureBridgeContractWatcher::new(
+                my_ctx.proposal_permits().clone(),
+                my_ctx.bridge_commands().clone(),
+            );
+            let governance_transfer_handler =
+                SignatureBridgeGovernanceOwnershipTransferredHandler::default();
+            let events_watcher_task = EventWatcher::run(
+                &bridge_contract_watcher,
+                client.clone(),
+                store.clone(),
+                wrapper.clone(),
+                vec![Box::new(governance_transfer_handler)],
+                &my_ctx,
+            );
+            let cmd_handler_task = BridgeWatcher::run(
+                &bridge_contract_watcher,
+                client,
+                store,
+                wrapper,
+                metrics.clone(),
+                my_ctx.bridge_commands().clone(),
+            );
+            tokio::select! {
+                result = events_watcher_task => {
+                    tracing::warn!(
+                        "signature bridge events watcher task stopped for ({})",
+                        contract_address
+                    );
+                    result?;
+                },
+                result = cmd_handler_task => {
+                    tracing::warn!(
+                        "signature bridge cmd handler task stopped for ({})",
+                        contract_address
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping Signature Bridge watcher for ({})",
+                        contract_address,
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks().spawn(
+        format!("Signature Bridge watcher ({contract_address:?})"),
+        task,
+    );
     Ok(())
 }
 
@@ -480,27 +1269,102 @@ pub fn start_tx_queue(
         return Ok(());
     }
 
-    let mut shutdown_signal = ctx.shutdown_signal();
-    let tx_queue = TxQueue::new(ctx, chain_id.into(), store);
-
     tracing::debug!("Transaction Queue for ({}) Started.", chain_id);
-    let task = async move {
-        tokio::select! {
-            _ = tx_queue.run() => {
-                tracing::warn!(
-                    "Transaction Queue task stopped for ({})",
-                    chain_id,
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping Transaction Queue for ({})",
-                    chain_id,
-                );
-            },
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let mut shutdown_signal = my_ctx.shutdown_signal();
+        let tx_queue =
+            TxQueue::new(my_ctx.clone(), chain_id.into(), store.clone());
+        async move {
+            tokio::select! {
+                result = tx_queue.run() => {
+                    tracing::warn!(
+                        "Transaction Queue task stopped for ({})",
+                        chain_id,
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping Transaction Queue for ({})",
+                        chain_id,
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the tx_queue.
-    tokio::task::spawn(task);
+    ctx.background_tasks()
+        .spawn(format!("Transaction Queue ({chain_id})"), task);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_list_the_contracts_without_code() {
+        let (provider, mock) = Provider::mocked();
+        // the responses are symmetric, so that the order they are served
+        // in does not matter.
+        mock.push(types::Bytes::from(vec![0x60, 0x80])).unwrap();
+        mock.push(types::Bytes::default()).unwrap();
+        mock.push(types::Bytes::from(vec![0x60, 0x80])).unwrap();
+        let addresses = [
+            types::Address::repeat_byte(1),
+            types::Address::repeat_byte(2),
+            types::Address::repeat_byte(3),
+        ];
+        let undeployed = find_undeployed_contracts(&provider, addresses)
+            .await
+            .unwrap();
+        assert_eq!(undeployed, vec![addresses[1]]);
+
+        let error =
+            undeployed_contracts_error(&[("goerli".to_string(), addresses[1])]);
+        assert_eq!(
+            error.to_string(),
+            "Contracts not deployed (fail-fast-startup is on):\n  - goerli: \
+             0x0202020202020202020202020202020202020202"
+        );
+    }
+
+    #[test]
+    fn static_contracts_should_not_be_watched() {
+        let contract = |address: u8, sync: &str| {
+            serde_json::json!({
+                "contract": "VAnchor",
+                "address": types::Address::repeat_byte(address),
+                "deployed-at": 1,
+                "events-watcher": { "polling-interval": 1000, "sync": sync },
+            })
+        };
+        let mut chain: EvmChainConfig =
+            serde_json::from_value(serde_json::json!({
+                "name": "hermes",
+                "http-endpoint": "http://localhost:5001",
+                "ws-endpoint": "ws://localhost:5001",
+                "chain-id": 5001,
+                "contracts": [
+                    contract(1, "watch"),
+                    contract(2, "static"),
+                    contract(3, "watch"),
+                ],
+            }))
+            .unwrap();
+        chain.enabled_contracts = Some(vec![
+            types::Address::repeat_byte(1),
+            types::Address::repeat_byte(2),
+        ]);
+        let watched: Vec<_> = watched_contracts(&chain)
+            .into_iter()
+            .map(|(contract, _)| match contract {
+                Contract::VAnchor(c) => c.common.address,
+                Contract::SignatureBridge(c) => c.common.address,
+            })
+            .collect();
+        assert_eq!(watched, vec![types::Address::repeat_byte(1)]);
+    }
+}
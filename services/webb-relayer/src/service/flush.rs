@@ -0,0 +1,124 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use webb_relayer_context::Shutdown;
+use webb_relayer_store::SledStore;
+
+/// The outcome of the last flush of the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastFlush {
+    /// Whether the flush succeeded.
+    pub ok: bool,
+    /// When the flush happened, in seconds since the unix epoch.
+    pub at: u64,
+    /// Why the flush failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Keeps track of the last flush of the store, for the health endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct FlushStatus(Arc<RwLock<Option<LastFlush>>>);
+
+impl FlushStatus {
+    /// Records the outcome of a flush.
+    pub fn record(&self, result: &crate::Result<usize>) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let last = LastFlush {
+            ok: result.is_ok(),
+            at,
+            error: result.as_ref().err().map(ToString::to_string),
+        };
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(last);
+    }
+
+    /// The outcome of the last flush, `None` if the store was never flushed.
+    pub fn last(&self) -> Option<LastFlush> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// The last flush as reported by the health endpoint.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self.last() {
+            Some(last) => serde_json::json!({
+                "ok": last.ok,
+                "at": last.at,
+                "error": last.error,
+            }),
+            None => serde_json::Value::Null,
+        }
+    }
+}
+
+/// Flushes the store to disk every `interval`, until the relayer shuts
+/// down.
+///
+/// # Arguments
+///
+/// * `store` - The store to flush
+/// * `interval` - How often to flush the store
+/// * `status` - Where to record the outcome of every flush
+/// * `shutdown` - Stops the task once the relayer shuts down
+pub async fn flush_store_periodically(
+    store: SledStore,
+    interval: Duration,
+    status: FlushStatus,
+    mut shutdown: Shutdown,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // the first tick completes right away, nothing to flush yet.
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let store = store.clone();
+                let result = tokio::task::spawn_blocking(move || store.flush())
+                    .await
+                    .unwrap_or(Err(crate::Error::Generic(
+                        "The flush task panicked",
+                    )));
+                if let Err(e) = &result {
+                    tracing::error!("Failed to flush the store: {e}");
+                }
+                status.record(&result);
+            }
+            _ = shutdown.recv() => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_should_report_the_last_flush() {
+        let status = FlushStatus::default();
+        assert!(status.to_json().is_null());
+
+        status.record(&Ok(42));
+        assert!(status.last().unwrap().ok);
+
+        status.record(&Err(crate::Error::Generic("disk full")));
+        let last = status.last().unwrap();
+        assert!(!last.ok);
+        assert_eq!(status.to_json()["error"], last.error.unwrap());
+    }
+}
@@ -22,11 +22,23 @@
 //! Services handle keeping up to date with the configured chains.
 
 use std::collections::HashSet;
-use std::net::SocketAddr;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
 use std::sync::Arc;
 
-use axum::routing::get;
-use axum::Router;
+use axum::body::Body;
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::{FromRef, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{BoxError, Extension, Json, Router};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::sync::watch;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::{ServiceBuilder, ServiceExt};
 use tower_http::cors::Any;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
@@ -37,49 +49,443 @@ use webb_proposal_signing_backends::{
 use webb_relayer_config::anchor::LinkedAnchorConfig;
 
 use webb_relayer_config::signing_backend::ProposalSigningBackendConfig;
+use webb_relayer_context::substrate_accounts::SubstrateAccounts;
 use webb_relayer_context::RelayerContext;
-use webb_relayer_handlers::routes::info::handle_relayer_info;
+use webb_relayer_handlers::router::CommandRouter;
+use webb_relayer_handlers::routes::commitments::handle_commitment_lookup;
+use webb_relayer_handlers::routes::earnings::handle_earnings;
+use webb_relayer_handlers::routes::info::{
+    handle_build_info, handle_relayer_info,
+};
+use webb_relayer_handlers::routes::leaves::handle_reconcile_leaves_substrate;
+use webb_relayer_handlers::routes::metric::{
+    handle_metric_info, handle_store_metrics,
+};
+use webb_relayer_handlers::routes::provider_usage::handle_provider_usage;
+use webb_relayer_handlers::routes::reconnect::handle_reconnect_chain;
 use webb_relayer_handlers::{handle_socket_info, websocket_handler};
 use webb_relayer_store::SledStore;
+use webb_relayer_utils::auth::token_matches;
 
+/// Monitoring of the relayer accounts on the Substrate nodes
+pub mod accounts;
+/// Watching of the config files, to reload them on changes
+pub mod config_watcher;
 /// EVM Specific Services
 pub mod evm;
+/// Periodic flushing of the store
+pub mod flush;
+/// Config reloading, chain by chain
+pub mod reload;
 /// Substrate Specific Services
 pub mod substrate;
 
 /// Type alias for [Sled](https://sled.rs)-based database store
 pub type Store = SledStore;
 
-/// Sets up the web socket server for the relayer, routing (endpoint queries / requests mapped to
-/// handled code) and instantiates the database store. Allows clients to interact with the relayer.
+/// The routes of the relayer that need its context.
+///
+/// Holds `None` while the relayer is still starting.
+pub type ReadyRoutes = watch::Receiver<Option<Router>>;
+
+/// The state of the server that is up before the relayer context is ready.
+#[derive(Clone)]
+struct ServerState {
+    routes: ReadyRoutes,
+    flush: flush::FlushStatus,
+    accounts: SubstrateAccounts,
+}
+
+impl FromRef<ServerState> for ReadyRoutes {
+    fn from_ref(state: &ServerState) -> Self {
+        state.routes.clone()
+    }
+}
+
+/// Sets up the routing (endpoint queries / requests mapped to handled code) of the relayer.
 ///
 /// # Arguments
 ///
 /// * `ctx` - RelayContext reference that holds the configuration and database
-pub async fn build_web_services(ctx: RelayerContext) -> crate::Result<()> {
-    let socket_addr = SocketAddr::new([0, 0, 0, 0].into(), ctx.config.port);
-    let api = Router::new()
+///
+/// The admin endpoints are only part of these routes if there is no admin
+/// listener configured, see [`build_admin_routes`]. The ones acting on the
+/// relayer, or reporting its earnings, are then only served to the callers
+/// with the `admin-token`, and not at all without one.
+pub fn build_routes(ctx: RelayerContext) -> Router {
+    let ctx = Arc::new(ctx);
+    let mut api = Router::new()
         .route("/ip", get(handle_socket_info))
         .route("/info", get(handle_relayer_info))
+        .route("/commitment/:commitment", get(handle_commitment_lookup))
         .merge(evm::build_web_services())
         .merge(substrate::build_web_services());
+    if ctx.config.admin_listener.is_none() {
+        api = api.merge(metrics_api());
+        let has_admin_token = ctx
+            .config
+            .admin_token
+            .as_ref()
+            .map_or(false, |t| !t.is_empty());
+        if has_admin_token {
+            api = api.merge(privileged_api().route_layer(
+                middleware::from_fn_with_state(
+                    ctx.clone(),
+                    require_admin_token,
+                ),
+            ));
+        }
+    }
 
-    let app = Router::new()
-        .nest("/api/v1", api)
-        .route("/ws", get(websocket_handler))
+    let commands = CommandRouter::from_config(&ctx.config);
+    let mut routes = Router::new().nest("/api/v1", api).route(
+        "/ws",
+        get(websocket_handler).layer(Extension(Arc::new(commands))),
+    );
+    // zero, such as in a default config, means no limit.
+    let max_concurrent_requests = ctx.config.max_concurrent_requests;
+    if max_concurrent_requests > 0 {
+        routes = limit_concurrency(routes, max_concurrent_requests);
+    }
+    routes
         .layer(CorsLayer::new().allow_origin(Any))
-        .layer(TraceLayer::new_for_http())
+        .with_state(ctx)
+}
+
+/// Refuses the requests that do not carry the `admin-token` of the relayer,
+/// as a bearer token.
+async fn require_admin_token<B>(
+    State(ctx): State<Arc<RelayerContext>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (&ctx.config.admin_token, token) {
+        (Some(expected), Some(token)) if token_matches(expected, token) => {
+            next.run(request).await
+        }
+        _ => (StatusCode::UNAUTHORIZED, "Invalid or missing admin token")
+            .into_response(),
+    }
+}
+
+/// Caps the number of requests the `routes` handle at the same time, the
+/// requests over the `limit` are shed with `503 Service Unavailable`
+/// instead of piling up, such as a flood of leaves requests.
+fn limit_concurrency<S>(routes: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    routes.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_overload))
+            .load_shed()
+            // a single limit shared by all the routes.
+            .layer(GlobalConcurrencyLimitLayer::new(limit)),
+    )
+}
+
+/// Answers the requests shed by [`limit_concurrency`].
+async fn handle_overload(err: BoxError) -> (StatusCode, &'static str) {
+    if err.is::<tower::load_shed::error::Overloaded>() {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many requests in flight, try again later",
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unhandled internal error",
+        )
+    }
+}
+
+/// Sets up the routing of the admin endpoints of the relayer, served on the
+/// admin listener.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration and database
+pub fn build_admin_routes(ctx: RelayerContext) -> Router {
+    Router::new()
+        .nest("/api/v1", admin_api())
         .with_state(Arc::new(ctx))
+}
+
+/// The endpoints that expose the internals of the relayer.
+fn admin_api() -> Router<Arc<RelayerContext>> {
+    metrics_api().merge(privileged_api())
+}
+
+/// The endpoints reporting the metrics of the relayer.
+fn metrics_api() -> Router<Arc<RelayerContext>> {
+    Router::new()
+        .route("/store/metrics", get(handle_store_metrics))
+        .route("/metrics", get(handle_metric_info))
+}
+
+/// The endpoints acting on the relayer, or reporting its earnings and the
+/// usage of its providers.
+fn privileged_api() -> Router<Arc<RelayerContext>> {
+    Router::new()
+        .route("/earnings", get(handle_earnings))
+        .route("/provider-usage", get(handle_provider_usage))
+        .route(
+            "/admin/reconcile-leaves/:chain_id/:tree_id",
+            post(handle_reconcile_leaves_substrate),
+        )
+        .route(
+            "/admin/reconnect/:kind/:chain_id",
+            post(handle_reconnect_chain),
+        )
+}
+
+/// Resolves the `host`, an IP address or a hostname, and binds a listener
+/// on it.
+///
+/// Fails right away if the host could not be resolved, or if none of its
+/// addresses could be bound.
+///
+/// # Arguments
+///
+/// * `host` - The host to listen on
+/// * `port` - The port to listen on
+pub fn bind_listener(host: &str, port: u16) -> crate::Result<TcpListener> {
+    bind_host(host, port, false)
+}
+
+/// Binds a listener on each of the `hosts`, sharing the same `port`.
+///
+/// When there are several hosts, the IPv6 listeners only accept IPv6
+/// connections, so that `::` and `0.0.0.0` can both be bound on a
+/// dual-stack host.
+///
+/// # Arguments
+///
+/// * `hosts` - The hosts to listen on
+/// * `port` - The port to listen on
+pub fn bind_listeners(
+    hosts: &[String],
+    port: u16,
+) -> crate::Result<Vec<TcpListener>> {
+    let only_v6 = hosts.len() > 1;
+    hosts
+        .iter()
+        .map(|host| bind_host(host, port, only_v6))
+        .collect()
+}
+
+fn bind_host(
+    host: &str,
+    port: u16,
+    only_v6: bool,
+) -> crate::Result<TcpListener> {
+    let listen_error = |reason: String| crate::Error::Listen {
+        address: format!("{host}:{port}"),
+        reason,
+    };
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| listen_error(e.to_string()))?;
+    let mut reason = String::from("the host did not resolve to any address");
+    for addr in addrs {
+        match bind_addr(addr, only_v6) {
+            Ok(listener) => {
+                listener.set_nonblocking(true)?;
+                return Ok(listener);
+            }
+            Err(e) => {
+                tracing::debug!(%addr, "Could not bind: {e}");
+                reason = format!("{addr}: {e}");
+            }
+        }
+    }
+    Err(listen_error(reason))
+}
+
+fn bind_addr(addr: SocketAddr, only_v6: bool) -> std::io::Result<TcpListener> {
+    if !(only_v6 && addr.is_ipv6()) {
+        return TcpListener::bind(addr);
+    }
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(true)?;
+    // like the listeners bound by the standard library.
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+/// Sets up the web socket server for the relayer, routing (endpoint queries / requests mapped to
+/// handled code) and instantiates the database store. Allows clients to interact with the relayer.
+///
+/// The routes are served by [`serve_web_services`], ready right away, and
+/// every listener stops gracefully once the relayer shuts down.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration and database
+pub async fn build_web_services(ctx: RelayerContext) -> crate::Result<()> {
+    let listeners =
+        bind_listeners(&ctx.config.listen_hosts(), ctx.config.port)?;
+    let admin_listener = ctx
+        .config
+        .admin_listener
+        .as_ref()
+        .map(|admin| bind_listener(&admin.host, admin.port))
+        .transpose()?;
+    let (_, routes) = watch::channel(Some(build_routes(ctx.clone())));
+    let mut shutdown_signal = ctx.shutdown_signal();
+    let server = serve_web_services(
+        listeners,
+        routes,
+        flush::FlushStatus::default(),
+        SubstrateAccounts::default(),
+        async move { shutdown_signal.recv().await },
+    );
+    let admin_server = async {
+        match admin_listener {
+            Some(listener) => serve_admin_services(listener, ctx.clone()).await,
+            None => Ok(()),
+        }
+    };
+    tokio::try_join!(server, admin_server)?;
+    Ok(())
+}
+
+/// Serves the admin endpoints of the relayer on the admin `listener`, until
+/// the relayer shuts down.
+///
+/// # Arguments
+///
+/// * `listener` - The bound (non-blocking) socket of the admin listener
+/// * `ctx` - RelayContext reference that holds the configuration and database
+pub async fn serve_admin_services(
+    listener: TcpListener,
+    ctx: RelayerContext,
+) -> crate::Result<()> {
+    let mut shutdown_signal = ctx.shutdown_signal();
+    let app = build_admin_routes(ctx)
+        .layer(TraceLayer::new_for_http())
         .into_make_service_with_connect_info::<SocketAddr>();
 
-    tracing::info!("Starting the server on {}", socket_addr);
-    axum::Server::bind(&socket_addr).serve(app).await?;
+    tracing::info!("Starting the admin server on {}", listener.local_addr()?);
+    axum::Server::from_tcp(listener)?
+        .serve(app)
+        .with_graceful_shutdown(async move { shutdown_signal.recv().await })
+        .await?;
     Ok(())
 }
 
+/// Serves the web services of the relayer on the already bound `listeners`,
+/// before its context is ready.
+///
+/// `/api/v1/health` and `/api/v1/version` are always served. Until the
+/// `routes` are sent, every other request is answered with
+/// `503 Service Unavailable`, then they are all handed to the `routes`.
+///
+/// # Arguments
+///
+/// * `listeners` - The bound (non-blocking) sockets to serve on
+/// * `routes` - The routes that need the relayer context, once ready
+/// * `flush` - The outcome of the last flush of the store, for the health
+///   checks
+/// * `accounts` - The relayer accounts on the Substrate nodes, for the
+///   health checks
+/// * `shutdown` - Stops the servers of all the listeners gracefully once it
+///   resolves
+pub async fn serve_web_services<F>(
+    listeners: Vec<TcpListener>,
+    routes: ReadyRoutes,
+    flush: flush::FlushStatus,
+    accounts: SubstrateAccounts,
+    shutdown: F,
+) -> crate::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let app = Router::new()
+        .route("/api/v1/health", get(handle_health))
+        .route("/api/v1/version", get(handle_build_info))
+        .fallback(route_when_ready)
+        .layer(TraceLayer::new_for_http())
+        .with_state(ServerState {
+            routes,
+            flush,
+            accounts,
+        })
+        .into_make_service_with_connect_info::<SocketAddr>();
+
+    // every listener is stopped by the same signal.
+    let (stop, stopped) = watch::channel(());
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = stop.send(());
+    });
+    let servers = listeners.into_iter().map(|listener| {
+        let app = app.clone();
+        let mut stopped = stopped.clone();
+        async move {
+            tracing::info!("Starting the server on {}", listener.local_addr()?);
+            axum::Server::from_tcp(listener)?
+                .serve(app)
+                .with_graceful_shutdown(async move {
+                    let _ = stopped.changed().await;
+                })
+                .await?;
+            crate::Result::Ok(())
+        }
+    });
+    futures::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+/// Reports whether the relayer is still `starting` or `ready`, whether
+/// the last flush of the store succeeded, and the balance and nonce of the
+/// relayer account on every Substrate node.
+async fn handle_health(
+    State(state): State<ServerState>,
+) -> Json<serde_json::Value> {
+    let status = if state.routes.borrow().is_some() {
+        "ready"
+    } else {
+        "starting"
+    };
+    Json(serde_json::json!({
+        "status": status,
+        "lastFlush": state.flush.to_json(),
+        "substrateAccounts": state.accounts.to_json(),
+    }))
+}
+
+/// Hands the request to the routes of the relayer, once they are ready.
+async fn route_when_ready(
+    State(routes): State<ReadyRoutes>,
+    request: Request<Body>,
+) -> Response {
+    let app = routes.borrow().clone();
+    match app {
+        Some(app) => match app.oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        },
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The relayer is still starting",
+        )
+            .into_response(),
+    }
+}
+
 /// Starts all background services for all chains configured in the config file.
 ///
-/// Returns a future that resolves when all services are started successfully.
+/// Returns a future that resolves when all services are started successfully,
+/// to the chains started, which a config reload may stop or restart.
 ///
 /// # Arguments
 ///
@@ -88,14 +494,15 @@ pub async fn build_web_services(ctx: RelayerContext) -> crate::Result<()> {
 pub async fn ignite(
     ctx: RelayerContext,
     store: Arc<Store>,
-) -> crate::Result<()> {
+) -> crate::Result<reload::RunningChains> {
     tracing::trace!(
         "Relayer configuration: {}",
         serde_json::to_string_pretty(&ctx.config)?
     );
-    evm::ignite(&ctx, store.clone()).await?;
-    substrate::ignite(ctx.clone(), store.clone()).await?;
-    Ok(())
+    let mut chains = reload::RunningChains::default();
+    evm::ignite(&ctx, store.clone(), &mut chains).await?;
+    substrate::ignite(ctx.clone(), store.clone(), &mut chains).await?;
+    Ok(chains)
 }
 
 /// Proposal signing backend config
@@ -172,6 +579,7 @@ pub async fn make_proposal_signing_backend(
             });
             let backend = MockedProposalSigningBackend::builder()
                 .store(store.clone())
+                .commands(ctx.bridge_commands().clone())
                 .private_key(mocked.private_key)
                 .signature_bridges(signature_bridges)
                 .build();
@@ -183,3 +591,288 @@ pub async fn make_proposal_signing_backend(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    /// Sends a GET request, returns the status code and the body.
+    async fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+        get_with_headers(addr, path, &[]).await
+    }
+
+    /// Sends a GET request with extra headers, returns the status code and
+    /// the body.
+    async fn get_with_headers(
+        addr: SocketAddr,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> (u16, String) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}\r\n"))
+            .collect();
+        let request = format!(
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\n\
+             {headers}Connection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    #[test]
+    fn listener_should_fail_fast_on_unavailable_addresses() {
+        let listener = bind_listener("127.0.0.1", 0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // the port is taken.
+        let err = bind_listener("127.0.0.1", port).unwrap_err();
+        assert!(err.to_string().contains(&format!("127.0.0.1:{port}")));
+        // not a local address.
+        assert!(bind_listener("192.0.2.1", 0).is_err());
+        // hostnames are resolved.
+        assert!(bind_listener("localhost", 0).is_ok());
+    }
+
+    #[tokio::test]
+    async fn health_should_be_served_while_the_store_opens() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (routes_tx, routes_rx) = watch::channel(None);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_web_services(
+            vec![listener],
+            routes_rx,
+            flush::FlushStatus::default(),
+            SubstrateAccounts::default(),
+            async move {
+                let _ = stopped.await;
+            },
+        ));
+        // a store that takes its time to open.
+        let slow_store = tokio::task::spawn_blocking(|| {
+            std::thread::sleep(Duration::from_millis(500));
+            SledStore::temporary()
+        });
+
+        let (status, body) = get(addr, "/api/v1/health").await;
+        assert_eq!(status, 200);
+        assert!(body.contains("starting"));
+        assert!(!slow_store.is_finished());
+        assert_eq!(get(addr, "/api/v1/version").await.0, 200);
+        assert_eq!(get(addr, "/api/v1/info").await.0, 503);
+
+        let store = slow_store.await.unwrap().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store).unwrap();
+        routes_tx.send(Some(build_routes(ctx))).unwrap();
+
+        let (status, body) = get(addr, "/api/v1/health").await;
+        assert_eq!(status, 200);
+        assert!(body.contains("ready"));
+        assert_eq!(get(addr, "/api/v1/info").await.0, 200);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn web_services_should_stop_once_the_relayer_shuts_down() {
+        let mut config = webb_relayer_config::WebbRelayerConfig::default();
+        config.host = "127.0.0.1".to_string();
+        config.port = 0;
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(config, store).unwrap();
+        let server = tokio::spawn(build_web_services(ctx.clone()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!server.is_finished());
+
+        ctx.shutdown();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the servers stopped gracefully")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn listeners_should_be_served_and_stopped_together() {
+        let hosts = vec!["127.0.0.1".to_string(), "localhost".to_string()];
+        let listeners = bind_listeners(&hosts, 0).unwrap();
+        let addrs = listeners
+            .iter()
+            .map(|listener| listener.local_addr().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(addrs.len(), 2);
+        let (_, routes_rx) = watch::channel(None);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_web_services(
+            listeners,
+            routes_rx,
+            flush::FlushStatus::default(),
+            SubstrateAccounts::default(),
+            async move {
+                let _ = stopped.await;
+            },
+        ));
+        for addr in &addrs {
+            assert_eq!(get(*addr, "/api/v1/health").await.0, 200);
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        for addr in addrs {
+            assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+        }
+    }
+
+    /// Serves the routes of the relayer on a local port, until `stop`.
+    fn serve_routes(
+        ctx: RelayerContext,
+    ) -> (
+        SocketAddr,
+        oneshot::Sender<()>,
+        JoinHandle<crate::Result<()>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_, routes_rx) = watch::channel(Some(build_routes(ctx)));
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_web_services(
+            vec![listener],
+            routes_rx,
+            flush::FlushStatus::default(),
+            SubstrateAccounts::default(),
+            async move {
+                let _ = stopped.await;
+            },
+        ));
+        (addr, stop, server)
+    }
+
+    #[tokio::test]
+    async fn ip_should_ignore_spoofed_headers_of_untrusted_peers() {
+        let spoofed = [
+            ("X-Forwarded-For", "203.0.113.9"),
+            ("Forwarded", "for=\"[2001:db8::1]:4711\""),
+        ];
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(Default::default(), store).unwrap();
+        let (addr, stop, server) = serve_routes(ctx);
+        let (status, body) =
+            get_with_headers(addr, "/api/v1/ip", &spoofed).await;
+        assert_eq!(status, 200);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["ip"], "127.0.0.1");
+        assert_eq!(body["family"], "ipv4");
+        assert_eq!(body["source"], "socket");
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        // the same headers, from a trusted proxy.
+        let config = webb_relayer_config::WebbRelayerConfig {
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+            ..Default::default()
+        };
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(config, store).unwrap();
+        let (addr, stop, server) = serve_routes(ctx);
+        let (_, body) = get_with_headers(addr, "/api/v1/ip", &spoofed).await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["ip"], "203.0.113.9");
+        assert_eq!(body["source"], "forwarded");
+        let forwarded = &spoofed[1..];
+        let (_, body) = get_with_headers(addr, "/api/v1/ip", forwarded).await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["ip"], "2001:db8::1");
+        assert_eq!(body["family"], "ipv6");
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn privileged_endpoints_should_require_the_admin_token() {
+        let request = |token: Option<&str>| {
+            let mut request = Request::builder().uri("/api/v1/earnings");
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, token);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let routes = |admin_token: Option<&str>| {
+            let config = webb_relayer_config::WebbRelayerConfig {
+                admin_token: admin_token.map(ToOwned::to_owned),
+                ..Default::default()
+            };
+            let store = SledStore::temporary().unwrap();
+            build_routes(RelayerContext::new(config, store).unwrap())
+        };
+
+        for admin_token in [None, Some("")] {
+            let routes = routes(admin_token);
+            let response = routes.clone().oneshot(request(None)).await;
+            assert_eq!(response.unwrap().status(), StatusCode::NOT_FOUND);
+            let metrics = Request::builder()
+                .uri("/api/v1/metrics")
+                .body(Body::empty())
+                .unwrap();
+            let response = routes.oneshot(metrics).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let routes = routes(Some("secret"));
+        for token in [None, Some("secret"), Some("Bearer other")] {
+            let response = routes.clone().oneshot(request(token)).await;
+            assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = routes
+            .oneshot(request(Some("Bearer secret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_limit_should_be_shed() {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let released = release.clone();
+        let slow = move || {
+            let released = released.clone();
+            async move {
+                released.notified().await;
+                "done"
+            }
+        };
+        let routes = limit_concurrency(Router::new().route("/", get(slow)), 1);
+        let request = || Request::builder().uri("/").body(Body::empty());
+
+        let in_flight =
+            tokio::spawn(routes.clone().oneshot(request().unwrap()));
+        // let the first request take the only slot.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let shed = routes.clone().oneshot(request().unwrap()).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        release.notify_one();
+        let done = in_flight.await.unwrap().unwrap();
+        assert_eq!(done.status(), StatusCode::OK);
+        let after = routes.oneshot(request().unwrap()).await.unwrap();
+        assert_eq!(after.status(), StatusCode::OK);
+    }
+}
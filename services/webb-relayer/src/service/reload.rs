@@ -0,0 +1,628 @@
+// Copyright 2022 Webb Technologies Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use sp_core::Pair;
+use tracing::Instrument;
+use webb_proposals::TypedChainId;
+use webb_relayer_config::log_filter::chain_span;
+use webb_relayer_config::WebbRelayerConfig;
+use webb_relayer_context::RelayerContext;
+
+use super::Store;
+
+/// A chain of the config, by its kind and name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChainKey {
+    /// An EVM chain, under `[evm.<name>]`.
+    Evm(String),
+    /// A Substrate node, under `[substrate.<name>]`.
+    Substrate(String),
+}
+
+impl fmt::Display for ChainKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Evm(name) => write!(f, "evm.{name}"),
+            Self::Substrate(name) => write!(f, "substrate.{name}"),
+        }
+    }
+}
+
+/// The enabled chains whose services have to be started or stopped to go
+/// from a config to another.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChainsDiff {
+    /// The chains added, or enabled.
+    pub added: Vec<ChainKey>,
+    /// The chains removed, or disabled.
+    pub removed: Vec<ChainKey>,
+    /// The chains whose config changed, to restart.
+    pub changed: Vec<ChainKey>,
+}
+
+impl ChainsDiff {
+    /// Compares the enabled chains of the two configs.
+    pub fn between(old: &WebbRelayerConfig, new: &WebbRelayerConfig) -> Self {
+        let old = enabled_chains(old);
+        let new = enabled_chains(new);
+        let mut diff = Self::default();
+        for (key, fingerprint) in &new {
+            match old.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(old) if old != fingerprint => {
+                    diff.changed.push(key.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|key| !new.contains_key(*key))
+            .cloned()
+            .collect();
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    /// Returns true if no chain has to be started or stopped.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Returns the enabled chains of the config, with a fingerprint of their
+/// config that changes whenever any of it does.
+fn enabled_chains(config: &WebbRelayerConfig) -> HashMap<ChainKey, String> {
+    // the secrets are left out of the `Debug` output, the public part of
+    // the keys stands for them.
+    let evm = config.evm.values().filter(|c| c.enabled).map(|c| {
+        let key = c
+            .private_key
+            .as_ref()
+            .map(|k| webb::evm::ethers::utils::keccak256(k.as_bytes()));
+        (ChainKey::Evm(c.name.clone()), format!("{c:?} {key:?}"))
+    });
+    let substrate = config.substrate.values().filter(|c| c.enabled).map(|c| {
+        let key = c.suri.as_ref().map(|suri| suri.public());
+        (
+            ChainKey::Substrate(c.name.clone()),
+            format!("{c:?} {key:?}"),
+        )
+    });
+    evm.chain(substrate).collect()
+}
+
+/// Returns the settings that differ between the two configs, but are only
+/// read at startup, so changing them needs a restart.
+pub fn startup_only_changes(
+    old: &WebbRelayerConfig,
+    new: &WebbRelayerConfig,
+) -> Vec<&'static str> {
+    let etherscan_chains = |c: &WebbRelayerConfig| {
+        c.evm_etherscan
+            .iter()
+            .map(|(chain, api)| {
+                (format!("{chain:?}"), format!("{:?}", api.api_url))
+            })
+            .collect::<BTreeSet<_>>()
+    };
+    let debug = |v: &dyn fmt::Debug| format!("{v:?}");
+    [
+        ("port", old.port != new.port),
+        ("host", old.host != new.host),
+        ("hosts", old.hosts != new.hosts),
+        (
+            "admin-listener",
+            debug(&old.admin_listener) != debug(&new.admin_listener),
+        ),
+        ("grpc", debug(&old.grpc) != debug(&new.grpc)),
+        ("features", debug(&old.features) != debug(&new.features)),
+        ("assets", debug(&old.assets) != debug(&new.assets)),
+        ("metrics", debug(&old.metrics) != debug(&new.metrics)),
+        (
+            "evm-etherscan",
+            etherscan_chains(old) != etherscan_chains(new),
+        ),
+        (
+            "leaf-snapshot-interval",
+            old.leaf_snapshot_interval != new.leaf_snapshot_interval,
+        ),
+        (
+            "flush-interval-ms",
+            old.flush_interval_ms != new.flush_interval_ms,
+        ),
+        (
+            "trusted-proxies",
+            old.trusted_proxies != new.trusted_proxies,
+        ),
+        (
+            "max-concurrent-requests",
+            old.max_concurrent_requests != new.max_concurrent_requests,
+        ),
+        (
+            "ws-ping-interval-secs",
+            old.ws_ping_interval_secs != new.ws_ping_interval_secs,
+        ),
+        (
+            "ws-send-queue-size",
+            old.ws_send_queue_size != new.ws_send_queue_size,
+        ),
+        (
+            "ws-slow-client-policy",
+            old.ws_slow_client_policy != new.ws_slow_client_policy,
+        ),
+        (
+            "reload-on-sighup",
+            old.reload_on_sighup != new.reload_on_sighup,
+        ),
+        ("hot-reload", old.hot_reload != new.hot_reload),
+        (
+            "bridge-command-buffer",
+            old.bridge_command_buffer != new.bridge_command_buffer,
+        ),
+        (
+            "max-concurrent-proposals-per-chain",
+            old.max_concurrent_proposals_per_chain
+                != new.max_concurrent_proposals_per_chain,
+        ),
+        (
+            "proposal-permit-timeout-secs",
+            old.proposal_permit_timeout_secs
+                != new.proposal_permit_timeout_secs,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect()
+}
+
+/// The chains whose services are running, each under its own shutdown
+/// signal.
+#[derive(Default)]
+pub struct RunningChains {
+    chains: HashMap<ChainKey, RelayerContext>,
+}
+
+impl RunningChains {
+    /// Starts the background services of the chain, which must be in the
+    /// config of `ctx`.
+    pub(crate) async fn start(
+        &mut self,
+        key: ChainKey,
+        ctx: &RelayerContext,
+        store: Arc<Store>,
+    ) -> crate::Result<()> {
+        let chain_ctx = ctx.scoped();
+        let result = match &key {
+            ChainKey::Evm(name) => {
+                // the chains are keyed by chain id once the config is loaded.
+                let chain_config = chain_ctx
+                    .config
+                    .evm
+                    .values()
+                    .find(|c| &c.name == name)
+                    .ok_or_else(|| crate::Error::ChainNotFound {
+                        chain_id: name.clone(),
+                    })?;
+                // every background service of the chain runs inside this
+                // span, so its logs could be filtered by chain.
+                let span =
+                    chain_span(&chain_config.name, chain_config.chain_id);
+                super::evm::ignite_chain(&chain_ctx, chain_config, store)
+                    .instrument(span)
+                    .await
+            }
+            ChainKey::Substrate(name) => {
+                let node_config = chain_ctx
+                    .config
+                    .substrate
+                    .values()
+                    .find(|c| &c.name == name)
+                    .ok_or_else(|| crate::Error::NodeNotFound {
+                        chain_id: name.clone(),
+                    })?;
+                let span = chain_span(&node_config.name, node_config.chain_id);
+                super::substrate::ignite_tangle_runtime(
+                    chain_ctx.clone(),
+                    store,
+                    node_config,
+                )
+                .instrument(span)
+                .await
+            }
+        };
+        // the services started before the failure, if any, are stopped
+        // with the chain.
+        self.chains.insert(key, chain_ctx);
+        result
+    }
+
+    /// Stops the background services of the chain, if running.
+    pub(crate) fn stop(&mut self, key: &ChainKey) {
+        if let Some(chain_ctx) = self.chains.remove(key) {
+            chain_ctx.shutdown();
+        }
+    }
+
+    /// Returns the chains that are running.
+    pub fn keys(&self) -> impl Iterator<Item = &ChainKey> {
+        self.chains.keys()
+    }
+}
+
+/// Returns the enabled chain of the config with the given chain id.
+fn chain_key(
+    config: &WebbRelayerConfig,
+    chain: TypedChainId,
+) -> Option<ChainKey> {
+    match chain {
+        TypedChainId::Evm(chain_id) => config
+            .evm
+            .values()
+            .find(|c| c.enabled && c.chain_id == chain_id)
+            .map(|c| ChainKey::Evm(c.name.clone())),
+        TypedChainId::Substrate(chain_id) => config
+            .substrate
+            .values()
+            .find(|c| c.enabled && c.chain_id == chain_id)
+            .map(|c| ChainKey::Substrate(c.name.clone())),
+        _ => None,
+    }
+}
+
+/// Reconnects the services of the `chain` to its endpoints, such as after
+/// its RPC provider got rotated.
+///
+/// The providers are built again from the config, and the services of the
+/// chain are restarted with them, the other chains are left running.
+/// Returns the context using the new providers.
+///
+/// If the services fail to start with the new providers, they are started
+/// again with the ones of `ctx`, and the failure is returned.
+pub async fn reconnect(
+    ctx: &RelayerContext,
+    chains: &mut RunningChains,
+    store: Arc<Store>,
+    chain: TypedChainId,
+) -> crate::Result<RelayerContext> {
+    let key = chain_key(&ctx.config, chain).ok_or_else(|| {
+        crate::Error::ChainNotFound {
+            chain_id: format!("{chain:?}"),
+        }
+    })?;
+    let reconnected = ctx.reconfigure(ctx.config.clone()).await;
+    tracing::info!(chain = %key, "Reconnecting the chain services");
+    chains.stop(&key);
+    let started = chains.start(key.clone(), &reconnected, store.clone()).await;
+    if let Err(e) = started {
+        tracing::error!(
+            chain = %key,
+            error = %e,
+            "Failed to start the chain services, restoring them",
+        );
+        chains.stop(&key);
+        let restored = chains.start(key.clone(), ctx, store).await;
+        if let Err(e) = restored {
+            tracing::error!(
+                chain = %key,
+                error = %e,
+                "Failed to restore the chain services",
+            );
+        }
+        return Err(e);
+    }
+    Ok(reconnected)
+}
+
+/// Applies the reloaded `config` to the running relayer.
+///
+/// The services of the chains added or changed by the new config are
+/// started, those of the chains removed or changed are stopped, and the
+/// others are left running. Returns the context using the new config.
+///
+/// The config is not applied at all if it changes settings only read at
+/// startup, see [`startup_only_changes`].
+pub async fn reload(
+    ctx: &RelayerContext,
+    chains: &mut RunningChains,
+    store: Arc<Store>,
+    config: WebbRelayerConfig,
+) -> crate::Result<RelayerContext> {
+    let startup_only = startup_only_changes(&ctx.config, &config);
+    if !startup_only.is_empty() {
+        return Err(crate::Error::RestartRequired(startup_only.join(", ")));
+    }
+    let diff = ChainsDiff::between(&ctx.config, &config);
+    let ctx = ctx.reconfigure(config).await;
+    for key in diff.removed.iter().chain(&diff.changed) {
+        tracing::info!(chain = %key, "Stopping the chain services");
+        chains.stop(key);
+    }
+    for key in diff.added.iter().chain(&diff.changed) {
+        tracing::info!(chain = %key, "Starting the chain services");
+        let started = chains.start(key.clone(), &ctx, store.clone()).await;
+        if let Err(e) = started {
+            tracing::error!(
+                chain = %key,
+                error = %e,
+                "Failed to start the chain services",
+            );
+        }
+    }
+    tracing::info!(
+        added = diff.added.len(),
+        removed = diff.removed.len(),
+        restarted = diff.changed.len(),
+        "Config reloaded",
+    );
+    Ok(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use webb_relayer_config::evm::EvmChainConfig;
+    use webb_relayer_store::SledStore;
+
+    fn evm_chain(name: &str, chain_id: u32) -> EvmChainConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "enabled": true,
+            "http-endpoint": "http://localhost:8545",
+            "ws-endpoint": "ws://localhost:8545",
+            "chain-id": chain_id,
+        }))
+        .unwrap()
+    }
+
+    fn config(chains: &[EvmChainConfig]) -> WebbRelayerConfig {
+        WebbRelayerConfig {
+            evm: chains.iter().map(|c| (c.name.clone(), c.clone())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_diff_the_enabled_chains() {
+        let kept = evm_chain("hermes", 5001);
+        let changed = evm_chain("athena", 5002);
+        let removed = evm_chain("demeter", 5003);
+        let old = config(&[kept.clone(), changed.clone(), removed.clone()]);
+
+        let mut rotated = changed;
+        rotated.private_key = Some(ethereum_types::Secret::random().into());
+        let mut disabled = removed;
+        disabled.enabled = false;
+        let added = evm_chain("hera", 5004);
+        let new = config(&[kept, rotated, disabled, added]);
+
+        let diff = ChainsDiff::between(&old, &new);
+        assert_eq!(diff.added, vec![ChainKey::Evm("hera".into())]);
+        assert_eq!(diff.removed, vec![ChainKey::Evm("demeter".into())]);
+        assert_eq!(diff.changed, vec![ChainKey::Evm("athena".into())]);
+        assert!(ChainsDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn should_restart_the_chains_whose_endpoints_changed() {
+        let old = config(&[evm_chain("hermes", 5001)]);
+        let mut rotated = evm_chain("hermes", 5001);
+        rotated.http_endpoint =
+            serde_json::from_value(serde_json::json!("http://rpc.example.org"))
+                .unwrap();
+        let new = config(&[rotated]);
+
+        let diff = ChainsDiff::between(&old, &new);
+        assert_eq!(diff.changed, vec![ChainKey::Evm("hermes".into())]);
+        assert_eq!(
+            chain_key(&new, TypedChainId::Evm(5001)),
+            Some(ChainKey::Evm("hermes".into()))
+        );
+        assert_eq!(chain_key(&new, TypedChainId::Substrate(5001)), None);
+    }
+
+    /// A JSON-RPC endpoint of the chain `5001`, counting the requests it
+    /// gets.
+    async fn mock_endpoint() -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let result = match request["method"].as_str() {
+                    Some("eth_chainId") => serde_json::json!("0x1389"),
+                    Some("eth_blockNumber") => serde_json::json!("0x64"),
+                    _ => serde_json::Value::Null,
+                };
+                Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": result,
+                }))
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+        (url, requests)
+    }
+
+    /// A chain watching a VAnchor through the `endpoint`.
+    fn watched_chain(endpoint: &str) -> EvmChainConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "hermes",
+            "enabled": true,
+            "http-endpoint": endpoint,
+            "ws-endpoint": endpoint.replace("http", "ws"),
+            "chain-id": 5001,
+            "contracts": [{
+                "contract": "VAnchor",
+                "address": "0x0000000000000000000000000000000000000001",
+                "deployed-at": 1,
+                "events-watcher": { "polling-interval": 100 },
+            }],
+        }))
+        .unwrap()
+    }
+
+    async fn requested(requests: &AtomicUsize) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while requests.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("the endpoint got requests");
+    }
+
+    #[tokio::test]
+    async fn watchers_should_reconnect_to_the_swapped_endpoint() {
+        let (old_endpoint, old_requests) = mock_endpoint().await;
+        let (new_endpoint, new_requests) = mock_endpoint().await;
+        let mut old = config(&[watched_chain(&old_endpoint)]);
+        old.features.governance_relay = false;
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(old.clone(), store.clone()).unwrap();
+        let store = Arc::new(store);
+        let mut chains = RunningChains::default();
+        let key = ChainKey::Evm("hermes".into());
+        chains
+            .start(key.clone(), &ctx, store.clone())
+            .await
+            .unwrap();
+        requested(&old_requests).await;
+        assert_eq!(new_requests.load(Ordering::SeqCst), 0);
+
+        let new = WebbRelayerConfig {
+            evm: config(&[watched_chain(&new_endpoint)]).evm,
+            ..old
+        };
+        let ctx = reload(&ctx, &mut chains, store, new).await.unwrap();
+        assert_eq!(chains.keys().collect::<Vec<_>>(), [&key]);
+        requested(&new_requests).await;
+        ctx.shutdown();
+    }
+
+    #[tokio::test]
+    async fn chains_failing_to_reconnect_should_be_reported() {
+        let (endpoint, _requests) = mock_endpoint().await;
+        let mut chain = watched_chain(&endpoint);
+        // the custom ABI of the VAnchor cannot be loaded.
+        chain.contract_abi_override.insert(
+            "0x0000000000000000000000000000000000000001"
+                .parse()
+                .unwrap(),
+            "missing-abi.json".into(),
+        );
+        let mut config = config(&[chain]);
+        config.features.governance_relay = false;
+        let store = SledStore::temporary().unwrap();
+        let ctx = RelayerContext::new(config, store.clone()).unwrap();
+        let mut chains = RunningChains::default();
+
+        let result = reconnect(
+            &ctx,
+            &mut chains,
+            Arc::new(store),
+            TypedChainId::Evm(5001),
+        )
+        .await;
+        assert!(result.is_err());
+        ctx.shutdown();
+    }
+
+    #[test]
+    fn should_require_a_restart_for_startup_only_settings() {
+        let old = config(&[evm_chain("hermes", 5001)]);
+        let mut new = config(&[]);
+        new.log_filter_by_chain
+            .insert("hermes".into(), "warn".into());
+        assert!(startup_only_changes(&old, &new).is_empty());
+
+        new.port = old.port + 1;
+        new.hosts = vec!["::".into()];
+        new.features.governance_relay = !old.features.governance_relay;
+        new.metrics.max_label_sets = 1;
+        new.bridge_command_buffer = old.bridge_command_buffer * 2;
+        new.proposal_permit_timeout_secs = 1;
+        assert_eq!(
+            startup_only_changes(&old, &new),
+            [
+                "port",
+                "hosts",
+                "features",
+                "metrics",
+                "bridge-command-buffer",
+                "proposal-permit-timeout-secs",
+            ]
+        );
+    }
+
+    #[test]
+    fn reloaded_config_files_should_only_restart_what_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let load = |main: &str, athena_endpoint: &str| {
+            let chains = format!(
+                "[evm.hermes]\n\
+                 name = \"hermes\"\n\
+                 http-endpoint = \"http://localhost:5001\"\n\
+                 ws-endpoint = \"ws://localhost:5001\"\n\
+                 chain-id = 5001\n\
+                 enabled = true\n\
+                 [evm.athena]\n\
+                 name = \"athena\"\n\
+                 http-endpoint = \"{athena_endpoint}\"\n\
+                 ws-endpoint = \"ws://localhost:5002\"\n\
+                 chain-id = 5002\n\
+                 enabled = true\n"
+            );
+            std::fs::write(dir.path().join("main.toml"), main).unwrap();
+            std::fs::write(dir.path().join("chains.toml"), chains).unwrap();
+            webb_relayer_config::utils::load(dir.path()).unwrap()
+        };
+        let old = load("port = 9955\n", "http://localhost:5002");
+
+        // the message size limit applies to the new websocket connections.
+        let new = load(
+            "port = 9955\nws-max-message-size-bytes = 4096\n",
+            "http://rpc.example.org",
+        );
+        assert_eq!(new.ws_max_message_size_bytes, 4096);
+        assert!(startup_only_changes(&old, &new).is_empty());
+        let diff = ChainsDiff::between(&old, &new);
+        assert_eq!(diff.changed, vec![ChainKey::Evm("athena".into())]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+
+        let new = load("port = 9956\n", "http://localhost:5002");
+        assert_eq!(startup_only_changes(&old, &new), ["port"]);
+        assert!(ChainsDiff::between(&old, &new).is_empty());
+    }
+}
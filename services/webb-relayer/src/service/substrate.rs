@@ -8,7 +8,7 @@ use webb::substrate::subxt::{self, PolkadotConfig};
 use webb_bridge_registry_backends::dkg::DkgBridgeRegistryBackend;
 use webb_bridge_registry_backends::mocked::MockedBridgeRegistryBackend;
 use webb_event_watcher_traits::{
-    SubstrateBridgeWatcher, SubstrateEventWatcher,
+    StartDelay, SubstrateBridgeWatcher, SubstrateEventWatcher,
 };
 use webb_ew_dkg::{
     DKGMetadataWatcher, DKGProposalHandlerWatcher, DKGPublicKeyChangedHandler,
@@ -16,18 +16,21 @@ use webb_ew_dkg::{
 };
 use webb_ew_substrate::{
     MaintainerSetEventHandler, SubstrateBridgeEventWatcher,
-    SubstrateVAnchorDepositHandler, SubstrateVAnchorEncryptedOutputHandler,
-    SubstrateVAnchorEventWatcher, SubstrateVAnchorLeavesHandler,
+    SubstrateEdgeHandler, SubstrateEdgeWatcher, SubstrateVAnchorDepositHandler,
+    SubstrateVAnchorEncryptedOutputHandler, SubstrateVAnchorEventWatcher,
+    SubstrateVAnchorLeavesHandler, TreeFilter,
 };
+use webb_relayer_config::event_watcher::EventsWatcherConfig;
 use webb_relayer_config::substrate::{
     DKGPalletConfig, DKGProposalHandlerPalletConfig, Pallet,
     SignatureBridgePalletConfig, SubstrateConfig, VAnchorBn254PalletConfig,
 };
 use webb_relayer_context::RelayerContext;
 use webb_relayer_handlers::handle_substrate_fee_info;
-use webb_relayer_handlers::routes::{leaves, metric};
+use webb_relayer_handlers::routes::{edges, leaves, metric};
 use webb_relayer_tx_queue::substrate::SubstrateTxQueue;
 
+use super::reload::{ChainKey, RunningChains};
 use super::ProposalSigningBackendSelector;
 
 /// Type alias for the Tangle DefaultConfig
@@ -40,6 +43,10 @@ pub fn build_web_services() -> Router<Arc<RelayerContext>> {
             "/leaves/substrate/:chain_id/:tree_id/:pallet_id",
             get(leaves::handle_leaves_cache_substrate),
         )
+        .route(
+            "/edges/substrate/:chain_id/:tree_id/:pallet_id",
+            get(edges::handle_edges_substrate),
+        )
         .route(
             "/metrics/substrate/:chain_id/:tree_id/:pallet_id",
             get(metric::handle_substrate_metric_info),
@@ -61,22 +68,37 @@ pub fn build_web_services() -> Router<Arc<RelayerContext>> {
 pub async fn ignite(
     ctx: RelayerContext,
     store: Arc<super::Store>,
+    chains: &mut RunningChains,
 ) -> crate::Result<()> {
-    for (_, node_config) in ctx.clone().config.substrate {
+    for node_config in ctx.config.substrate.values() {
         if !node_config.enabled {
             continue;
         }
-        ignite_tangle_runtime(ctx.clone(), store.clone(), &node_config).await?;
+        let key = ChainKey::Substrate(node_config.name.clone());
+        chains.start(key, &ctx, store.clone()).await?;
     }
     Ok(())
 }
 
-async fn ignite_tangle_runtime(
+pub(crate) async fn ignite_tangle_runtime(
     ctx: RelayerContext,
     store: Arc<super::Store>,
     node_config: &SubstrateConfig,
 ) -> crate::Result<()> {
     let chain_id = node_config.chain_id;
+    // the watchers are numbered in the order they are started, to stagger
+    // their startups.
+    let mut watcher_index = 0;
+    let mut next_start_delay = |events_watcher: &EventsWatcherConfig| {
+        let start_delay = StartDelay::new(
+            events_watcher.event_watcher_start_delay_secs,
+            watcher_index,
+        );
+        if events_watcher.enabled {
+            watcher_index += 1;
+        }
+        start_delay
+    };
     for pallet in &node_config.pallets {
         match pallet {
             Pallet::DKGProposalHandler(config) => {
@@ -85,6 +107,7 @@ async fn ignite_tangle_runtime(
                     config,
                     chain_id,
                     store.clone(),
+                    next_start_delay(&config.events_watcher),
                 )?;
             }
             Pallet::Dkg(config) => {
@@ -93,6 +116,7 @@ async fn ignite_tangle_runtime(
                     config,
                     chain_id,
                     store.clone(),
+                    next_start_delay(&config.events_watcher),
                 )?;
             }
             Pallet::DKGProposals(_) => {
@@ -104,6 +128,7 @@ async fn ignite_tangle_runtime(
                     config,
                     chain_id,
                     store.clone(),
+                    next_start_delay(&config.events_watcher),
                 )
                 .await?;
             }
@@ -113,10 +138,19 @@ async fn ignite_tangle_runtime(
                     config,
                     chain_id,
                     store.clone(),
+                    next_start_delay(&config.events_watcher),
+                )?;
+                start_substrate_edge_event_watcher(
+                    ctx.clone(),
+                    config,
+                    chain_id,
+                    store.clone(),
+                    next_start_delay(&config.events_watcher),
                 )?;
             }
         }
     }
+    super::accounts::start_account_monitor(ctx.clone(), node_config)?;
     // start the transaction queue for dkg-substrate extrinsics after starting other tasks.
     start_tx_queue::<PolkadotConfig>(ctx.clone(), chain_id, store.clone())?;
     Ok(())
@@ -133,11 +167,13 @@ async fn ignite_tangle_runtime(
 /// * `client` - DKG client
 /// * `chain_id` - An u32 representing the chain id of the chain
 /// * `store` -[Sled](https://sled.rs)-based database store
+/// * `start_delay` - How long the watcher waits before it starts
 pub fn start_dkg_proposal_handler(
     ctx: RelayerContext,
     config: &DKGProposalHandlerPalletConfig,
     chain_id: u32,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
     // check first if we should start the events watcher for this contract.
     if !config.events_watcher.enabled {
@@ -151,37 +187,49 @@ pub fn start_dkg_proposal_handler(
         "DKG Proposal Handler events watcher for ({}) Started.",
         chain_id,
     );
-    let mut shutdown_signal = ctx.shutdown_signal();
     let metrics = ctx.metrics.clone();
     let my_config = config.clone();
-    let task = async move {
-        let proposal_handler_watcher = DKGProposalHandlerWatcher::default();
-        let proposal_signed_handler = ProposalSignedHandler::default();
-        let proposal_handler_watcher_task = proposal_handler_watcher.run(
-            chain_id,
-            ctx.clone(),
-            store,
-            my_config.events_watcher,
-            vec![Box::new(proposal_signed_handler)],
-            metrics,
-        );
-        tokio::select! {
-            _ = proposal_handler_watcher_task => {
-                tracing::warn!(
-                    "DKG Proposal Handler events watcher stopped for ({})",
-                    chain_id,
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping DKG Proposal Handler events watcher for ({})",
-                    chain_id,
-                );
-            },
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let ctx = my_ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let metrics = metrics.clone();
+        let my_config = my_config.clone();
+        let store = store.clone();
+        async move {
+            start_delay.wait(&metrics).await;
+            let proposal_handler_watcher = DKGProposalHandlerWatcher::default();
+            let proposal_signed_handler =
+                ProposalSignedHandler::new(ctx.bridge_commands().clone());
+            let proposal_handler_watcher_task = proposal_handler_watcher.run(
+                chain_id,
+                ctx.clone(),
+                store,
+                my_config.events_watcher,
+                vec![Box::new(proposal_signed_handler)],
+                metrics,
+            );
+            tokio::select! {
+                result = proposal_handler_watcher_task => {
+                    tracing::warn!(
+                        "DKG Proposal Handler events watcher stopped for ({})",
+                        chain_id,
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping DKG Proposal Handler events watcher for ({})",
+                        chain_id,
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks()
+        .spawn(format!("DKG Proposal Handler watcher ({chain_id})"), task);
     Ok(())
 }
 
@@ -196,11 +244,13 @@ pub fn start_dkg_proposal_handler(
 /// * `client` - DKG client
 /// * `chain_id` - An u32 representing the chain id of the chain
 /// * `store` -[Sled](https://sled.rs)-based database store
+/// * `start_delay` - How long the watcher waits before it starts
 pub fn start_dkg_pallet_watcher(
     ctx: RelayerContext,
     config: &DKGPalletConfig,
     chain_id: u32,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
     // check first if we should start the events watcher for this pallet.
     if !config.events_watcher.enabled {
@@ -211,40 +261,54 @@ pub fn start_dkg_pallet_watcher(
         return Ok(());
     }
     tracing::debug!("DKG Pallet events watcher for ({}) Started.", chain_id,);
-    let mut shutdown_signal = ctx.shutdown_signal();
     let webb_config = ctx.config.clone();
     let metrics = ctx.metrics.clone();
     let my_config = config.clone();
-    let task = async move {
-        let dkg_event_watcher = DKGMetadataWatcher::default();
-        let public_key_changed_handler =
-            DKGPublicKeyChangedHandler::new(webb_config);
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let ctx = my_ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let webb_config = webb_config.clone();
+        let metrics = metrics.clone();
+        let my_config = my_config.clone();
+        let store = store.clone();
+        async move {
+            start_delay.wait(&metrics).await;
+            let dkg_event_watcher = DKGMetadataWatcher::default();
+            let public_key_changed_handler = DKGPublicKeyChangedHandler::new(
+                webb_config,
+                ctx.bridge_commands().clone(),
+            );
 
-        let dkg_event_watcher_task = dkg_event_watcher.run(
-            chain_id,
-            ctx.clone(),
-            store,
-            my_config.events_watcher,
-            vec![Box::new(public_key_changed_handler)],
-            metrics,
-        );
-        tokio::select! {
-            _ = dkg_event_watcher_task => {
-                tracing::warn!(
-                    "DKG Pallet events watcher stopped for ({})",
-                    chain_id,
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping DKG Pallet events watcher for ({})",
-                    chain_id,
-                );
-            },
+            let dkg_event_watcher_task = dkg_event_watcher.run(
+                chain_id,
+                ctx.clone(),
+                store,
+                my_config.events_watcher,
+                vec![Box::new(public_key_changed_handler)],
+                metrics,
+            );
+            tokio::select! {
+                result = dkg_event_watcher_task => {
+                    tracing::warn!(
+                        "DKG Pallet events watcher stopped for ({})",
+                        chain_id,
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping DKG Pallet events watcher for ({})",
+                        chain_id,
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks()
+        .spawn(format!("DKG Pallet watcher ({chain_id})"), task);
     Ok(())
 }
 
@@ -259,11 +323,13 @@ pub fn start_dkg_pallet_watcher(
 /// * `client` - WebbProtocol client
 /// * `chain_id` - An u32 representing the chain id of the chain
 /// * `store` -[Sled](https://sled.rs)-based database store
+/// * `start_delay` - How long the watcher waits before it starts
 pub fn start_substrate_vanchor_event_watcher(
     ctx: RelayerContext,
     config: &VAnchorBn254PalletConfig,
     chain_id: u32,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
     if !config.events_watcher.enabled {
         tracing::warn!(
@@ -278,141 +344,233 @@ pub fn start_substrate_vanchor_event_watcher(
     );
     let my_ctx = ctx.clone();
     let my_config = config.clone();
-    let mut shutdown_signal = ctx.shutdown_signal();
     let metrics = ctx.metrics.clone();
-    let task = async move {
-        let proposal_signing_backend = super::make_proposal_signing_backend(
-            &my_ctx,
-            store.clone(),
-            webb_proposals::TypedChainId::Substrate(chain_id),
-            my_config.linked_anchors.clone(),
-            my_config.proposal_signing_backend,
-        )
-        .await?;
-        match proposal_signing_backend {
-            ProposalSigningBackendSelector::Dkg(backend) => {
-                let bridge_registry =
-                    DkgBridgeRegistryBackend::new(backend.client.clone());
+    let task = move || {
+        let my_ctx = my_ctx.clone();
+        let mut shutdown_signal = my_ctx.shutdown_signal();
+        let my_config = my_config.clone();
+        let metrics = metrics.clone();
+        let store = store.clone();
+        async move {
+            start_delay.wait(&metrics).await;
+            let trees = TreeFilter::new(my_config.tree_ids.clone());
+            let proposal_signing_backend =
+                super::make_proposal_signing_backend(
+                    &my_ctx,
+                    store.clone(),
+                    webb_proposals::TypedChainId::Substrate(chain_id),
+                    my_config.linked_anchors.clone(),
+                    my_config.proposal_signing_backend,
+                )
+                .await?;
+            match proposal_signing_backend {
+                ProposalSigningBackendSelector::Dkg(backend) => {
+                    let bridge_registry =
+                        DkgBridgeRegistryBackend::new(backend.client.clone());
 
-                let deposit_handler = SubstrateVAnchorDepositHandler::new(
-                    backend,
-                    bridge_registry,
-                    my_config.linked_anchors,
-                );
-                let leaves_handler = SubstrateVAnchorLeavesHandler::default();
-                let encrypted_output_handler =
-                    SubstrateVAnchorEncryptedOutputHandler::default();
+                    let deposit_handler = SubstrateVAnchorDepositHandler::new(
+                        backend,
+                        bridge_registry,
+                        my_config.linked_anchors,
+                        trees.clone(),
+                    );
+                    let leaves_handler =
+                        SubstrateVAnchorLeavesHandler::new(trees.clone());
+                    let encrypted_output_handler =
+                        SubstrateVAnchorEncryptedOutputHandler::new(trees);
 
-                let watcher = SubstrateVAnchorEventWatcher::default();
-                let substrate_vanchor_watcher_task = watcher.run(
-                    chain_id,
-                    ctx.clone(),
-                    store.clone(),
-                    my_config.events_watcher,
-                    vec![
-                        Box::new(deposit_handler),
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    metrics.clone(),
-                );
+                    let watcher = SubstrateVAnchorEventWatcher::default();
+                    let substrate_vanchor_watcher_task = watcher.run(
+                        chain_id,
+                        my_ctx.clone(),
+                        store.clone(),
+                        my_config.events_watcher,
+                        vec![
+                            Box::new(deposit_handler),
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                        ],
+                        metrics.clone(),
+                    );
 
-                tokio::select! {
-                    _ = substrate_vanchor_watcher_task => {
-                        tracing::warn!(
-                            "Substrate VAnchor watcher (DKG Backend) task stopped for ({})",
-                            chain_id,
-                        );
-                    },
+                    tokio::select! {
+                        result = substrate_vanchor_watcher_task => {
+                            tracing::warn!(
+                                "Substrate VAnchor watcher (DKG Backend) task stopped for ({})",
+                                chain_id,
+                            );
+                            result?;
+                        },
 
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping Substrate VAnchor watcher (DKG Backend) for ({})",
-                            chain_id,
-                        );
-                    },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping Substrate VAnchor watcher (DKG Backend) for ({})",
+                                chain_id,
+                            );
+                        },
+                    }
                 }
-            }
-            ProposalSigningBackendSelector::Mocked(backend) => {
-                let bridge_registry =
-                    MockedBridgeRegistryBackend::builder().build();
+                ProposalSigningBackendSelector::Mocked(backend) => {
+                    let bridge_registry =
+                        MockedBridgeRegistryBackend::builder().build();
 
-                let deposit_handler = SubstrateVAnchorDepositHandler::new(
-                    backend,
-                    bridge_registry,
-                    my_config.linked_anchors,
-                );
-                let leaves_handler = SubstrateVAnchorLeavesHandler::default();
-                let encrypted_output_handler =
-                    SubstrateVAnchorEncryptedOutputHandler::default();
+                    let deposit_handler = SubstrateVAnchorDepositHandler::new(
+                        backend,
+                        bridge_registry,
+                        my_config.linked_anchors,
+                        trees.clone(),
+                    );
+                    let leaves_handler =
+                        SubstrateVAnchorLeavesHandler::new(trees.clone());
+                    let encrypted_output_handler =
+                        SubstrateVAnchorEncryptedOutputHandler::new(trees);
 
-                let watcher = SubstrateVAnchorEventWatcher::default();
-                let substrate_vanchor_watcher_task = watcher.run(
-                    chain_id,
-                    ctx.clone(),
-                    store.clone(),
-                    my_config.events_watcher,
-                    vec![
-                        Box::new(deposit_handler),
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    metrics.clone(),
-                );
-                tokio::select! {
-                    _ = substrate_vanchor_watcher_task => {
-                        tracing::warn!(
-                            "Substrate VAnchor watcher (Mocked Backend) task stopped for ({})",
-                            chain_id,
-                        );
-                    },
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping Substrate VAnchor watcher (Mocked Backend) for ({})",
-                            chain_id,
-                        );
-                    },
+                    let watcher = SubstrateVAnchorEventWatcher::default();
+                    let substrate_vanchor_watcher_task = watcher.run(
+                        chain_id,
+                        my_ctx.clone(),
+                        store.clone(),
+                        my_config.events_watcher,
+                        vec![
+                            Box::new(deposit_handler),
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                        ],
+                        metrics.clone(),
+                    );
+                    tokio::select! {
+                        result = substrate_vanchor_watcher_task => {
+                            tracing::warn!(
+                                "Substrate VAnchor watcher (Mocked Backend) task stopped for ({})",
+                                chain_id,
+                            );
+                            result?;
+                        },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping Substrate VAnchor watcher (Mocked Backend) for ({})",
+                                chain_id,
+                            );
+                        },
+                    }
                 }
-            }
-            ProposalSigningBackendSelector::None => {
-                let leaves_handler = SubstrateVAnchorLeavesHandler::default();
-                let encrypted_output_handler =
-                    SubstrateVAnchorEncryptedOutputHandler::default();
+                ProposalSigningBackendSelector::None => {
+                    let leaves_handler =
+                        SubstrateVAnchorLeavesHandler::new(trees.clone());
+                    let encrypted_output_handler =
+                        SubstrateVAnchorEncryptedOutputHandler::new(trees);
 
-                let watcher = SubstrateVAnchorEventWatcher::default();
-                let substrate_vanchor_watcher_task = watcher.run(
-                    chain_id,
-                    ctx.clone(),
-                    store.clone(),
-                    my_config.events_watcher,
-                    vec![
-                        Box::new(leaves_handler),
-                        Box::new(encrypted_output_handler),
-                    ],
-                    metrics.clone(),
-                );
-                tokio::select! {
-                    _ = substrate_vanchor_watcher_task => {
-                        tracing::warn!(
-                            "Substrate VAnchor watcher task stopped for ({})",
-                            chain_id,
-                        );
-                    },
-                    _ = shutdown_signal.recv() => {
-                        tracing::trace!(
-                            "Stopping Substrate VAnchor watcher task for ({})",
-                            chain_id,
-                        );
-                    },
+                    let watcher = SubstrateVAnchorEventWatcher::default();
+                    let substrate_vanchor_watcher_task = watcher.run(
+                        chain_id,
+                        my_ctx.clone(),
+                        store.clone(),
+                        my_config.events_watcher,
+                        vec![
+                            Box::new(leaves_handler),
+                            Box::new(encrypted_output_handler),
+                        ],
+                        metrics.clone(),
+                    );
+                    tokio::select! {
+                        result = substrate_vanchor_watcher_task => {
+                            tracing::warn!(
+                                "Substrate VAnchor watcher task stopped for ({})",
+                                chain_id,
+                            );
+                            result?;
+                        },
+                        _ = shutdown_signal.recv() => {
+                            tracing::trace!(
+                                "Stopping Substrate VAnchor watcher task for ({})",
+                                chain_id,
+                            );
+                        },
+                    }
                 }
-            }
-        };
+            };
+
+            tracing::debug!("Task resturned");
+            crate::Result::Ok(())
+        }
+    };
+    // kick off the watcher.
+    ctx.background_tasks()
+        .spawn(format!("Substrate VAnchor watcher ({chain_id})"), task);
+    Ok(())
+}
 
-        tracing::debug!("Task resturned");
-        crate::Result::Ok(())
+/// Starts the event watcher for the edges of the Substrate vanchor trees,
+/// kept up to date by the linkable tree pallet.
+///
+/// Returns Ok(()) if successful, or an error if not.
+///
+/// # Arguments
+///
+/// * `ctx` - RelayContext reference that holds the configuration
+/// * `config` - VAnchorBn254 configuration
+/// * `chain_id` - An u32 representing the chain id of the chain
+/// * `store` -[Sled](https://sled.rs)-based database store
+/// * `start_delay` - How long the watcher waits before it starts
+pub fn start_substrate_edge_event_watcher(
+    ctx: RelayerContext,
+    config: &VAnchorBn254PalletConfig,
+    chain_id: u32,
+    store: Arc<super::Store>,
+    start_delay: StartDelay,
+) -> crate::Result<()> {
+    if !config.events_watcher.enabled {
+        tracing::warn!(
+            "Substrate Edge events watcher is disabled for ({}).",
+            chain_id,
+        );
+        return Ok(());
+    }
+    tracing::debug!(
+        "Substrate Edge events watcher for ({}) Started.",
+        chain_id
+    );
+    let my_config = config.clone();
+    let metrics = ctx.metrics.clone();
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let ctx = my_ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let my_config = my_config.clone();
+        let metrics = metrics.clone();
+        let store = store.clone();
+        async move {
+            start_delay.wait(&metrics).await;
+            let watcher = SubstrateEdgeWatcher::default();
+            let edge_watcher_task = watcher.run(
+                chain_id,
+                ctx.clone(),
+                store,
+                my_config.events_watcher,
+                vec![Box::new(SubstrateEdgeHandler::default())],
+                metrics,
+            );
+            tokio::select! {
+                result = edge_watcher_task => {
+                    tracing::warn!(
+                        "Substrate Edge watcher task stopped for ({})",
+                        chain_id,
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping Substrate Edge watcher task for ({})",
+                        chain_id,
+                    );
+                },
+            }
+            crate::Result::Ok(())
+        }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks()
+        .spawn(format!("Substrate Edge watcher ({chain_id})"), task);
     Ok(())
 }
 
@@ -422,6 +580,7 @@ pub async fn start_substrate_signature_bridge_events_watcher(
     config: &SignatureBridgePalletConfig,
     chain_id: u32,
     store: Arc<super::Store>,
+    start_delay: StartDelay,
 ) -> crate::Result<()> {
     if !config.events_watcher.enabled {
         tracing::warn!(
@@ -430,55 +589,70 @@ pub async fn start_substrate_signature_bridge_events_watcher(
         );
         return Ok(());
     }
-    let mut shutdown_signal = ctx.shutdown_signal();
     let my_config = config.clone();
     let pair = ctx.substrate_wallet(chain_id).await?;
-    let task = async move {
-        tracing::debug!(
-            "Substrate Signature Bridge watcher for ({}) Started.",
-            chain_id
-        );
-        let substrate_bridge_watcher = SubstrateBridgeEventWatcher::default();
-        let bridge_event_handler = MaintainerSetEventHandler::default();
-        let events_watcher_task = SubstrateEventWatcher::run(
-            &substrate_bridge_watcher,
-            chain_id,
-            ctx.clone(),
-            store.clone(),
-            my_config.events_watcher,
-            vec![Box::new(bridge_event_handler)],
-            ctx.metrics.clone(),
-        );
-        let cmd_handler_task = SubstrateBridgeWatcher::run(
-            &substrate_bridge_watcher,
-            chain_id,
-            ctx.clone(),
-            pair.clone(),
-            store.clone(),
-        );
-        tokio::select! {
-            _ = events_watcher_task => {
-                tracing::warn!(
-                    "Substrate signature bridge events watcher task stopped for ({})",
-                    chain_id
-                );
-            },
-            _ = cmd_handler_task => {
-                tracing::warn!(
-                    "Substrate signature bridge cmd handler task stopped for ({})",
-                    chain_id
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping Substrate Signature Bridge watcher for ({})",
-                    chain_id,
-                );
-            },
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let ctx = my_ctx.clone();
+        let mut shutdown_signal = ctx.shutdown_signal();
+        let my_config = my_config.clone();
+        let store = store.clone();
+        let pair = pair.clone();
+        async move {
+            start_delay.wait(&ctx.metrics).await;
+            tracing::debug!(
+                "Substrate Signature Bridge watcher for ({}) Started.",
+                chain_id
+            );
+            let substrate_bridge_watcher =
+                SubstrateBridgeEventWatcher::default();
+            let bridge_event_handler = MaintainerSetEventHandler::default();
+            let events_watcher_task = SubstrateEventWatcher::run(
+                &substrate_bridge_watcher,
+                chain_id,
+                ctx.clone(),
+                store.clone(),
+                my_config.events_watcher,
+                vec![Box::new(bridge_event_handler)],
+                ctx.metrics.clone(),
+            );
+            let cmd_handler_task = SubstrateBridgeWatcher::run(
+                &substrate_bridge_watcher,
+                chain_id,
+                ctx.clone(),
+                pair.clone(),
+                store.clone(),
+            );
+            tokio::select! {
+                result = events_watcher_task => {
+                    tracing::warn!(
+                        "Substrate signature bridge events watcher task stopped for ({})",
+                        chain_id
+                    );
+                    result?;
+                },
+                result = cmd_handler_task => {
+                    tracing::warn!(
+                        "Substrate signature bridge cmd handler task stopped for ({})",
+                        chain_id
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping Substrate Signature Bridge watcher for ({})",
+                        chain_id,
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the watcher.
-    tokio::task::spawn(task);
+    ctx.background_tasks().spawn(
+        format!("Substrate Signature Bridge watcher ({chain_id})"),
+        task,
+    );
     Ok(())
 }
 
@@ -507,28 +681,33 @@ where
     <X as subxt::Config>::AccountId:
         From<sp_runtime::AccountId32> + Send + Sync,
 {
-    let mut shutdown_signal = ctx.shutdown_signal();
-
-    let tx_queue = SubstrateTxQueue::new(ctx, chain_id, store);
-
     tracing::debug!("Transaction Queue for node({}) Started.", chain_id);
-    let task = async move {
-        tokio::select! {
-            _ = tx_queue.run::<X>() => {
-                tracing::warn!(
-                    "Transaction Queue task stopped for node({})",
-                    chain_id
-                );
-            },
-            _ = shutdown_signal.recv() => {
-                tracing::trace!(
-                    "Stopping Transaction Queue for node({})",
-                    chain_id
-                );
-            },
+    let my_ctx = ctx.clone();
+    let task = move || {
+        let mut shutdown_signal = my_ctx.shutdown_signal();
+        let tx_queue =
+            SubstrateTxQueue::new(my_ctx.clone(), chain_id, store.clone());
+        async move {
+            tokio::select! {
+                result = tx_queue.run::<X>() => {
+                    tracing::warn!(
+                        "Transaction Queue task stopped for node({})",
+                        chain_id
+                    );
+                    result?;
+                },
+                _ = shutdown_signal.recv() => {
+                    tracing::trace!(
+                        "Stopping Transaction Queue for node({})",
+                        chain_id
+                    );
+                },
+            }
+            crate::Result::Ok(())
         }
     };
     // kick off the substrate tx_queue.
-    tokio::task::spawn(task);
+    ctx.background_tasks()
+        .spawn(format!("Transaction Queue node({chain_id})"), task);
     Ok(())
 }
@@ -427,6 +427,7 @@ async function txHashOrReject(ws: WebSocket, cmd: any): Promise<`0x${string}`> {
         const isError =
           msg.withdraw === 'invalidMerkleRoots' ||
           msg.withdraw === 'droppedFromMemPool' ||
+          (msg.withdraw as { invalidProof: any }).invalidProof ||
           (msg.withdraw as { errored: any }).errored;
         const success = msg.withdraw as {
           finalized: { txHash: `0x${string}` };
@@ -486,6 +487,7 @@ async function substrateTxHashOrReject(
         const isError =
           msg.withdraw === 'invalidMerkleRoots' ||
           msg.withdraw === 'droppedFromMemPool' ||
+          (msg.withdraw as { invalidProof: any }).invalidProof ||
           (msg.withdraw as { errored: any }).errored;
         const success = msg.withdraw as {
           finalized: { txHash: `0x${string}` };
@@ -602,11 +604,13 @@ export interface WebbRelayerInfo {
 export interface LeavesCacheResponse {
   leaves: [`0x${string}`];
   lastQueriedBlock: string;
+  static: boolean;
 }
 
 export interface EncryptedOutputsCacheResponse {
   encryptedOutputs: [string];
   lastQueriedBloc: string;
+  static: boolean;
 }
 
 export interface RelayerMetricResponse {
@@ -766,7 +770,9 @@ type WithdrawMessage = {
     | { finalized: { txHash: string } }
     | 'valid'
     | 'invalidMerkleRoots'
+    | { invalidProof: { reason: string } }
     | 'droppedFromMemPool'
+    | { rateLimitAmount: { retryAfterSecs: number | null } }
     | { errored: { code: number; reason: string } };
 };
 